@@ -0,0 +1,22 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/apikeys.proto");
+        let descriptors = protox::compile(["proto/apikeys.proto"], ["proto"])
+            .expect("proto/apikeys.proto should compile");
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)
+            .expect("gRPC code generation failed");
+    }
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+        cbindgen::generate(&crate_dir)
+            .expect("C header generation failed")
+            .write_to_file("include/newrelic_apikeys.h");
+    }
+}
@@ -0,0 +1,58 @@
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#ifndef NEWRELIC_APIKEYS_H
+#define NEWRELIC_APIKEYS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The share of the user keys, in percent, that keys picked only because their owners are
+// missing from the organization may make up before a sweep needs an explicit confirmation.
+#define MAX_UNLISTED_PERCENT 20
+
+// Opaque client handle.
+typedef struct NrClient NrClient;
+
+// The last error on this thread, or NULL. Valid until the next failing call on this thread.
+const char *nr_last_error(void);
+
+// Create a client; `endpoint` may be NULL for the US NerdGraph endpoint.
+//
+// # Safety
+// `api_key` and `endpoint` must be NULL or valid NUL-terminated strings.
+struct NrClient *nr_client_new(const char *api_key, const char *endpoint);
+
+// # Safety
+// `client` must be NULL or a pointer returned by `nr_client_new`, freed at most once.
+void nr_client_free(struct NrClient *client);
+
+// List keys in the given accounts as a JSON array. `key_types` is a comma-separated list
+// such as `"USER,INGEST"`, or NULL for both.
+//
+// # Safety
+// `client` must come from `nr_client_new`; `account_ids` must point to `account_count`
+// integers; `key_types` must be NULL or a valid NUL-terminated string.
+char *nr_list_keys(const struct NrClient *client,
+                   const int64_t *account_ids,
+                   size_t account_count,
+                   const char *key_types);
+
+// Create a key from a JSON spec (`type`, `account_id`, `name`, optional `notes`,
+// `ingest_type`, `user_id`), returning the created key, secret included, as JSON.
+//
+// # Safety
+// `client` must come from `nr_client_new`; `spec_json` must be a valid NUL-terminated string.
+char *nr_create_key(const struct NrClient *client, const char *spec_json);
+
+// Delete one key (`key_type` is `INGEST` or `USER`). Returns 0 on success, -1 on failure.
+//
+// # Safety
+// `client` must come from `nr_client_new`; `key_id` and `key_type` must be valid
+// NUL-terminated strings.
+int32_t nr_delete_key(const struct NrClient *client, const char *key_id, const char *key_type);
+
+// # Safety
+// `value` must be NULL or a string returned by this library, freed at most once.
+void nr_string_free(char *value);
+
+#endif  /* NEWRELIC_APIKEYS_H */
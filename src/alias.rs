@@ -0,0 +1,165 @@
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+
+use clap::CommandFactory;
+
+/// Replace a user-defined alias in the subcommand position with its expansion, the way git
+/// aliases work. Built-in subcommands always take precedence over aliases.
+pub fn expand<C: CommandFactory>(
+    args: Vec<OsString>,
+    aliases: &BTreeMap<String, String>,
+) -> anyhow::Result<Vec<OsString>> {
+    if aliases.is_empty() {
+        return Ok(args);
+    }
+
+    let command = C::command();
+    let builtins: HashSet<&str> = command
+        .get_subcommands()
+        .flat_map(|sub| std::iter::once(sub.get_name()).chain(sub.get_all_aliases()))
+        .chain(["help"])
+        .collect();
+    let mut value_options: HashSet<String> = HashSet::new();
+    for arg in command.get_arguments() {
+        if !arg.get_action().takes_values() {
+            continue;
+        }
+        if let Some(short) = arg.get_short() {
+            value_options.insert(format!("-{}", short));
+        }
+        if let Some(long) = arg.get_long() {
+            value_options.insert(format!("--{}", long));
+        }
+    }
+
+    let mut args = args;
+    let mut seen = Vec::new();
+    loop {
+        let Some(position) = subcommand_position(&args, &value_options) else {
+            return Ok(args);
+        };
+        let Some(name) = args[position].to_str().map(str::to_string) else {
+            return Ok(args);
+        };
+        if builtins.contains(name.as_str()) {
+            return Ok(args);
+        }
+        let Some(expansion) = aliases.get(&name) else {
+            return Ok(args);
+        };
+        if seen.contains(&name) {
+            seen.push(name);
+            return Err(anyhow::anyhow!(
+                "Alias loop detected: {}",
+                seen.join(" -> ")
+            ));
+        }
+
+        let words = shell_words::split(expansion)
+            .map_err(|e| anyhow::anyhow!("Invalid alias '{}': {}", name, e))?;
+        if words.is_empty() {
+            return Err(anyhow::anyhow!("Alias '{}' is empty", name));
+        }
+        args.splice(position..=position, words.into_iter().map(OsString::from));
+        seen.push(name);
+    }
+}
+
+/// Index of the first positional argument after the binary name, skipping global options and
+/// their values.
+fn subcommand_position(args: &[OsString], value_options: &HashSet<String>) -> Option<usize> {
+    let mut index = 1;
+    while index < args.len() {
+        let arg = args[index].to_str()?;
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            return Some(index);
+        }
+        // `--endpoint=...` or `-fjson` carry their value inline
+        let takes_next = value_options.contains(arg);
+        index += if takes_next { 2 } else { 1 };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[arg(short, long)]
+        profile: Option<String>,
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(subcommand)]
+        command: TestCommands,
+    }
+
+    #[derive(clap::Subcommand)]
+    enum TestCommands {
+        Delete {
+            #[arg(long)]
+            key_id: String,
+        },
+        Query,
+    }
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    fn aliases(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_expands_alias_after_global_options() {
+        let aliases = aliases(&[("rm", "delete --key-id 'abc 123'")]);
+        let expanded = expand::<TestCli>(args("cli -v --profile prod rm"), &aliases).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                "cli",
+                "-v",
+                "--profile",
+                "prod",
+                "delete",
+                "--key-id",
+                "abc 123"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builtin_commands_win_over_aliases() {
+        let aliases = aliases(&[("query", "delete --key-id x")]);
+        let expanded = expand::<TestCli>(args("cli query"), &aliases).unwrap();
+        assert_eq!(expanded, vec!["cli", "query"]);
+    }
+
+    #[test]
+    fn test_nested_aliases_and_loops() {
+        let nested = aliases(&[("q", "qq"), ("qq", "query")]);
+        assert_eq!(
+            expand::<TestCli>(args("cli q"), &nested).unwrap(),
+            vec!["cli", "query"]
+        );
+
+        let looping = aliases(&[("a", "b"), ("b", "a")]);
+        assert!(expand::<TestCli>(args("cli a"), &looping).is_err());
+    }
+
+    #[test]
+    fn test_option_value_is_not_mistaken_for_alias() {
+        let aliases = aliases(&[("prod", "delete --key-id x")]);
+        let expanded = expand::<TestCli>(args("cli -p prod query"), &aliases).unwrap();
+        assert_eq!(expanded, vec!["cli", "-p", "prod", "query"]);
+    }
+}
@@ -0,0 +1,195 @@
+//! Ingest anomalies for `daemon --anomaly-webhook`: every check compares the ingest attributed to
+//! each key (see [`consumption`](crate::consumption)) with a moving baseline of the key's earlier
+//! checks, and reports keys that start sending, stop sending or send far more than usual. A key
+//! that starts sending out of nowhere may have leaked; one that stops after a rotation usually
+//! means a pipeline still uses the old secret.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::consumption::Usage;
+use crate::inventory::ApiKey;
+
+/// Weight of the newest check in the baseline.
+const SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// A key without ingest so far is sending data
+    Started,
+    /// A key that was sending data is silent
+    Stopped,
+    /// A key sends `factor` times its baseline or more
+    Spike,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Anomaly {
+    pub kind: Kind,
+    pub account_id: i64,
+    pub key_id: String,
+    pub name: Option<String>,
+    pub ingest_type: String,
+    /// GB per hour in the latest check
+    pub gigabytes_per_hour: f64,
+    /// GB per hour the key usually sends
+    pub baseline: f64,
+}
+
+impl Anomaly {
+    pub fn describe(&self) -> String {
+        let key = match &self.name {
+            Some(name) => format!("{} ({})", self.key_id, name),
+            None => self.key_id.clone(),
+        };
+        match self.kind {
+            Kind::Started => format!(
+                "{} key {} in account {} started sending data: {:.3} GB/h",
+                self.ingest_type, key, self.account_id, self.gigabytes_per_hour
+            ),
+            Kind::Stopped => format!(
+                "{} key {} in account {} stopped sending data (usually {:.3} GB/h)",
+                self.ingest_type, key, self.account_id, self.baseline
+            ),
+            Kind::Spike => format!(
+                "{} key {} in account {} sends {:.3} GB/h, usually {:.3} GB/h",
+                self.ingest_type, key, self.account_id, self.gigabytes_per_hour, self.baseline
+            ),
+        }
+    }
+}
+
+struct Baseline {
+    /// GB per hour
+    rate: f64,
+}
+
+pub struct Detector {
+    /// How many times its baseline a key must send to count as a spike
+    factor: f64,
+    /// GB per hour below which a key counts as silent
+    min_rate: f64,
+    baselines: HashMap<String, Baseline>,
+}
+
+impl Detector {
+    pub fn new(factor: f64, min_rate: f64) -> Self {
+        Detector {
+            factor,
+            min_rate,
+            baselines: HashMap::new(),
+        }
+    }
+
+    /// Compare a check over `hours` with the baselines and fold it into them. `keys` are the
+    /// ingest keys that exist now: those without a row sent nothing, and deleted keys are
+    /// forgotten. Keys seen for the first time only set their baseline.
+    pub fn observe(&mut self, usage: &[Usage], hours: f64, keys: &[ApiKey]) -> Vec<Anomaly> {
+        let rates: HashMap<&str, f64> = usage
+            .iter()
+            .filter_map(|row| Some((row.key_id.as_deref()?, row.gigabytes / hours)))
+            .collect();
+        let mut anomalies = Vec::new();
+        let mut baselines = HashMap::new();
+        for key in keys.iter().filter(|key| !key.is_user_key()) {
+            let rate = rates.get(key.id.as_str()).copied().unwrap_or(0.0);
+            let Some(mut baseline) = self.baselines.remove(&key.id) else {
+                baselines.insert(key.id.clone(), Baseline { rate });
+                continue;
+            };
+            let kind = if baseline.rate < self.min_rate && rate >= self.min_rate {
+                Some(Kind::Started)
+            } else if baseline.rate >= self.min_rate && rate < self.min_rate {
+                Some(Kind::Stopped)
+            } else if baseline.rate >= self.min_rate && rate >= baseline.rate * self.factor {
+                Some(Kind::Spike)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                anomalies.push(Anomaly {
+                    kind,
+                    account_id: key.account_id.unwrap_or_default(),
+                    key_id: key.id.clone(),
+                    name: key.name.clone(),
+                    ingest_type: key
+                        .ingest_type
+                        .clone()
+                        .unwrap_or_else(|| "LICENSE".to_string()),
+                    gigabytes_per_hour: rate,
+                    baseline: baseline.rate,
+                });
+            }
+            // A key that starts or stops has a new normal; a spike should not become one.
+            baseline.rate = match kind {
+                Some(Kind::Started | Kind::Stopped) => rate,
+                Some(Kind::Spike) => baseline.rate,
+                None => baseline.rate + SMOOTHING * (rate - baseline.rate),
+            };
+            baselines.insert(key.id.clone(), baseline);
+        }
+        self.baselines = baselines;
+        anomalies.sort_by(|a, b| a.key_id.cmp(&b.key_id));
+        anomalies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "type": "INGEST", "ingestType": "LICENSE",
+            "createdAt": 1, "accountId": 1
+        }))
+    }
+
+    fn usage(key_id: &str, gigabytes: f64) -> Usage {
+        Usage {
+            account_id: 1,
+            key_id: Some(key_id.to_string()),
+            name: None,
+            ingest_type: "LICENSE".to_string(),
+            gigabytes,
+            share: 1.0,
+            attribution: "exact".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_detects_start_stop_and_spike() {
+        let mut detector = Detector::new(5.0, 0.01);
+        let keys = vec![key("A"), key("B"), key("C")];
+        let first = detector.observe(&[usage("A", 1.0), usage("B", 1.0)], 1.0, &keys);
+        assert!(first.is_empty());
+
+        let second = detector.observe(&[usage("A", 10.0), usage("C", 2.0)], 1.0, &keys);
+        let kinds: Vec<(&str, Kind)> = second.iter().map(|a| (a.key_id.as_str(), a.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("A", Kind::Spike),
+                ("B", Kind::Stopped),
+                ("C", Kind::Started)
+            ]
+        );
+        // The spike did not raise A's baseline.
+        assert!(detector
+            .observe(&[usage("A", 1.0), usage("C", 2.0)], 1.0, &keys)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_new_and_deleted_keys() {
+        let mut detector = Detector::new(5.0, 0.01);
+        detector.observe(&[usage("A", 1.0)], 1.0, &[key("A")]);
+        // A was deleted and D is new: neither is an anomaly.
+        assert!(detector
+            .observe(&[usage("D", 3.0)], 1.0, &[key("D")])
+            .is_empty());
+        assert!(!detector.baselines.contains_key("A"));
+    }
+}
@@ -0,0 +1,663 @@
+//! Key hygiene checks: `audit` reports every finding, `policy check` enforces the configured
+//! [`Policies`] and fails on violations. Both can write the results as a JUnit report for CI or
+//! as SARIF for code scanning dashboards.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::Policies;
+use crate::filter::{self, KeyFilter};
+use crate::inventory::ApiKey;
+use crate::output::{Format, Renderer};
+use crate::report::escape;
+use crate::{webhook, NewRelicClient};
+
+/// The age limit `audit` applies when `policies.max_key_age_days` is not set.
+const DEFAULT_MAX_KEY_AGE_DAYS: u32 = 90;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+    /// Keys older than this many days are due for rotation
+    KeyAge(u32),
+    /// Keys must carry notes naming their owner or purpose
+    RequireNotes,
+    /// Accounts have at most `max` keys, or `max` keys of one type
+    KeyCount {
+        key_type: Option<&'static str>,
+        max: usize,
+    },
+}
+
+impl Rule {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::KeyAge(_) => "key-age",
+            Rule::RequireNotes => "require-notes",
+            Rule::KeyCount { key_type: None, .. } => "key-count",
+            Rule::KeyCount {
+                key_type: Some("INGEST"),
+                ..
+            } => "ingest-key-count",
+            Rule::KeyCount { .. } => "user-key-count",
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Rule::KeyAge(days) => format!("Keys are rotated at least every {} days", days),
+            Rule::RequireNotes => "Keys have notes naming their owner or purpose".to_string(),
+            Rule::KeyCount { key_type, max } => format!(
+                "Accounts have at most {} {}keys",
+                max,
+                key_type.map(|t| format!("{} ", t)).unwrap_or_default()
+            ),
+        }
+    }
+
+    /// SARIF level of a violation.
+    fn level(&self) -> &'static str {
+        match self {
+            Rule::KeyAge(_) | Rule::KeyCount { .. } => "warning",
+            Rule::RequireNotes => "note",
+        }
+    }
+
+    /// Why `key` violates the rule, if it does. Key counts depend on the other keys of the
+    /// account and are checked by [`Rule::count`] instead.
+    fn check(&self, key: &ApiKey, now: DateTime<Utc>) -> Option<String> {
+        match self {
+            Rule::KeyAge(max) => key
+                .age_days(now)
+                .filter(|age| *age > i64::from(*max))
+                .map(|age| format!("created {} days ago, more than {} days", age, max)),
+            Rule::RequireNotes => key
+                .notes
+                .as_deref()
+                .is_none_or(|notes| notes.trim().is_empty())
+                .then(|| "has no notes naming its owner or purpose".to_string()),
+            Rule::KeyCount { .. } => None,
+        }
+    }
+
+    /// The accounts in `keys` over a key-count limit. The newest keys beyond the limit are the
+    /// ones flagged, since those are the likely recreations.
+    fn count<'k>(&self, keys: &'k [ApiKey]) -> Vec<(Breach, Vec<&'k ApiKey>)> {
+        let Rule::KeyCount { key_type, max } = *self else {
+            return Vec::new();
+        };
+        let mut accounts: BTreeMap<i64, Vec<&ApiKey>> = BTreeMap::new();
+        for key in keys {
+            if key_type.is_none_or(|t| key.key_type.as_deref() == Some(t)) {
+                if let Some(account_id) = key.account_id {
+                    accounts.entry(account_id).or_default().push(key);
+                }
+            }
+        }
+        accounts
+            .into_iter()
+            .filter(|(_, keys)| keys.len() > max)
+            .map(|(account_id, mut keys)| {
+                let breach = Breach {
+                    rule: self.id(),
+                    account_id,
+                    keys: keys.len(),
+                    max,
+                };
+                keys.sort_by_key(|key| std::cmp::Reverse(key.created_at));
+                keys.truncate(breach.keys - max);
+                (breach, keys)
+            })
+            .collect()
+    }
+}
+
+/// An account over a key-count limit.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Breach {
+    pub rule: &'static str,
+    pub account_id: i64,
+    pub keys: usize,
+    pub max: usize,
+}
+
+impl Breach {
+    pub fn describe(&self) -> String {
+        format!(
+            "account {} has {} keys, more than {} ({})",
+            self.account_id, self.keys, self.max, self.rule
+        )
+    }
+}
+
+/// Post `breaches` to `url`.
+pub async fn notify(url: &str, breaches: &[Breach]) -> anyhow::Result<()> {
+    let lines: Vec<String> = breaches.iter().map(Breach::describe).collect();
+    let text = format!(
+        "{} account(s) over a key-count limit:\n{}",
+        breaches.len(),
+        lines.join("\n")
+    );
+    webhook::notify(url, &text, &serde_json::json!({ "breaches": breaches })).await
+}
+
+/// The key-count rules of the configured policies.
+pub fn count_rules(policies: &Policies) -> Vec<Rule> {
+    [
+        (None, policies.max_keys_per_account),
+        (Some("INGEST"), policies.max_ingest_keys_per_account),
+        (Some("USER"), policies.max_user_keys_per_account),
+    ]
+    .into_iter()
+    .filter_map(|(key_type, max)| {
+        Some(Rule::KeyCount {
+            key_type,
+            max: max?,
+        })
+    })
+    .collect()
+}
+
+/// The configured policies.
+pub fn policy_rules(policies: &Policies) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    if let Some(days) = policies.max_key_age_days {
+        rules.push(Rule::KeyAge(days));
+    }
+    if policies.require_notes == Some(true) {
+        rules.push(Rule::RequireNotes);
+    }
+    rules.extend(count_rules(policies));
+    rules
+}
+
+/// Every rule, with the configured thresholds where there are any. An explicit
+/// `require_notes = false` turns the notes rule off.
+pub fn audit_rules(policies: &Policies) -> Vec<Rule> {
+    let mut rules = vec![Rule::KeyAge(
+        policies
+            .max_key_age_days
+            .unwrap_or(DEFAULT_MAX_KEY_AGE_DAYS),
+    )];
+    if policies.require_notes != Some(false) {
+        rules.push(Rule::RequireNotes);
+    }
+    rules.extend(count_rules(policies));
+    rules
+}
+
+/// One key violating one rule.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub key_id: String,
+    pub key_name: Option<String>,
+    pub key_type: Option<String>,
+    pub account_id: Option<i64>,
+    pub message: String,
+}
+
+/// The outcome of checking `keys` against `rules`.
+pub struct Evaluation {
+    pub rules: Vec<Rule>,
+    pub keys: Vec<ApiKey>,
+    pub findings: Vec<Finding>,
+    /// Accounts over a key-count limit
+    pub breaches: Vec<Breach>,
+}
+
+impl Evaluation {
+    pub fn new(rules: Vec<Rule>, mut keys: Vec<ApiKey>, now: DateTime<Utc>) -> Self {
+        keys.sort_by(|a, b| (a.account_id, &a.id).cmp(&(b.account_id, &b.id)));
+        let mut findings = Vec::new();
+        let mut breaches = Vec::new();
+        for rule in &rules {
+            let counted = rule.count(&keys);
+            for key in &keys {
+                let message = match counted
+                    .iter()
+                    .find(|(_, excess)| excess.iter().any(|k| k.id == key.id))
+                {
+                    Some((breach, _)) => Some(format!(
+                        "was created after the first {} of {} {}keys in its account",
+                        breach.max,
+                        breach.keys,
+                        match rule {
+                            Rule::KeyCount {
+                                key_type: Some(key_type),
+                                ..
+                            } => format!("{} ", key_type),
+                            _ => String::new(),
+                        },
+                    )),
+                    None => rule.check(key, now),
+                };
+                if let Some(message) = message {
+                    findings.push(Finding {
+                        rule: rule.id(),
+                        key_id: key.id.clone(),
+                        key_name: key.name.clone(),
+                        key_type: key.key_type.clone(),
+                        account_id: key.account_id,
+                        message,
+                    });
+                }
+            }
+            breaches.extend(counted.into_iter().map(|(breach, _)| breach));
+        }
+        Self {
+            rules,
+            keys,
+            findings,
+            breaches,
+        }
+    }
+
+    fn finding(&self, rule: &Rule, key: &ApiKey) -> Option<&Finding> {
+        self.findings
+            .iter()
+            .find(|f| f.rule == rule.id() && f.key_id == key.id)
+    }
+}
+
+/// A JUnit report with one test suite per rule and one test case per key, so CI systems show
+/// each violation as a failed test.
+pub fn junit(evaluation: &Evaluation, name: &str) -> String {
+    let tests = evaluation.rules.len() * evaluation.keys.len();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\">",
+        escape(name),
+        tests,
+        evaluation.findings.len()
+    );
+    for rule in &evaluation.rules {
+        let failures = evaluation
+            .findings
+            .iter()
+            .filter(|f| f.rule == rule.id())
+            .count();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">",
+            rule.id(),
+            evaluation.keys.len(),
+            failures
+        );
+        let _ = writeln!(
+            xml,
+            "    <properties><property name=\"description\" value=\"{}\"/></properties>",
+            escape(&rule.description())
+        );
+        for key in &evaluation.keys {
+            let classname = match key.account_id {
+                Some(account_id) => format!("account.{}", account_id),
+                None => "account.unknown".to_string(),
+            };
+            let case = match &key.name {
+                Some(key_name) => format!("{} ({})", key.id, key_name),
+                None => key.id.clone(),
+            };
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                escape(&classname),
+                escape(&case)
+            );
+            match evaluation.finding(rule, key) {
+                Some(finding) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\" type=\"{}\">Key {} ({}) in account {}: {}</failure>\n    </testcase>",
+                        escape(&finding.message),
+                        rule.id(),
+                        escape(&key.id),
+                        escape(key.key_type.as_deref().unwrap_or("unknown type")),
+                        key.account_id.map(|id| id.to_string()).unwrap_or_default(),
+                        escape(&finding.message)
+                    );
+                }
+                None => xml.push_str("/>\n"),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    Junit,
+    Sarif,
+}
+
+impl ReportFormat {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "junit" => Ok(ReportFormat::Junit),
+            "sarif" => Ok(ReportFormat::Sarif),
+            _ => anyhow::bail!(
+                "Unsupported report format '{}' (expected junit or sarif)",
+                value
+            ),
+        }
+    }
+
+    /// SARIF for `*.sarif` and `*.sarif.json`, JUnit for anything else.
+    pub fn from_path(path: &Path) -> Self {
+        let name = path.to_string_lossy().to_lowercase();
+        if name.ends_with(".sarif") || name.ends_with(".sarif.json") {
+            ReportFormat::Sarif
+        } else {
+            ReportFormat::Junit
+        }
+    }
+}
+
+/// A SARIF 2.1.0 log. Keys have no source file, so every result points at a
+/// `newrelic/accounts/<account>/api-keys/<id>` location and carries a fingerprint made of the
+/// rule and key ID, which lets code scanning track a finding across runs.
+pub fn sarif(evaluation: &Evaluation) -> serde_json::Value {
+    let rules: Vec<serde_json::Value> = evaluation
+        .rules
+        .iter()
+        .map(|rule| {
+            serde_json::json!({
+                "id": rule.id(),
+                "shortDescription": {"text": rule.description()},
+                "defaultConfiguration": {"level": rule.level()},
+                "properties": {"tags": ["security", "api-keys"]},
+            })
+        })
+        .collect();
+    let results: Vec<serde_json::Value> = evaluation
+        .findings
+        .iter()
+        .map(|finding| {
+            let index = evaluation
+                .rules
+                .iter()
+                .position(|rule| rule.id() == finding.rule)
+                .unwrap_or_default();
+            let account = finding
+                .account_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            let label = match &finding.key_name {
+                Some(name) => format!("{} ({})", finding.key_id, name),
+                None => finding.key_id.clone(),
+            };
+            serde_json::json!({
+                "ruleId": finding.rule,
+                "ruleIndex": index,
+                "level": evaluation.rules[index].level(),
+                "message": {
+                    "text": format!("Key {} in account {} {}", label, account, finding.message),
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {
+                            "uri": format!("newrelic/accounts/{}/api-keys/{}", account, finding.key_id),
+                        },
+                    },
+                    "logicalLocations": [{
+                        "name": finding.key_id,
+                        "fullyQualifiedName": format!("{}/{}", account, finding.key_id),
+                        "kind": "resource",
+                    }],
+                }],
+                "partialFingerprints": {
+                    "primaryLocationLineHash": format!("{}:{}", finding.rule, finding.key_id),
+                },
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "newrelic-apikeys-cli",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+const HEADERS: [&str; 6] = ["RULE", "ACCOUNT", "TYPE", "ID", "NAME", "FINDING"];
+
+impl Renderer for Evaluation {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(&self.findings)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.findings
+            .iter()
+            .map(|f| {
+                vec![
+                    f.rule.to_string(),
+                    f.account_id.map(|id| id.to_string()).unwrap_or_default(),
+                    f.key_type.clone().unwrap_or_default(),
+                    f.key_id.clone(),
+                    f.key_name.clone().unwrap_or_default(),
+                    f.message.clone(),
+                ]
+            })
+            .collect()
+    }
+
+    fn empty_message(&self) -> Option<String> {
+        Some(format!(
+            "No findings: {} key(s) pass {} rule(s)",
+            self.keys.len(),
+            self.rules.len()
+        ))
+    }
+}
+
+/// Check every key in `account_ids` that matches `filter` against `rules`, print the findings and write a report to
+/// `report` if given. `name` labels JUnit reports, e.g. `policy check`.
+pub async fn run(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    filter: &KeyFilter,
+    rules: Vec<Rule>,
+    format: Format,
+    report: Option<(&Path, ReportFormat)>,
+    name: &str,
+) -> anyhow::Result<Evaluation> {
+    let keys = filter::fetch(client, account_ids, &["INGEST", "USER"], filter).await?;
+    let evaluation = Evaluation::new(rules, keys, Utc::now());
+
+    print!("{}", evaluation.render(&format)?);
+
+    if let Some((path, report_format)) = report {
+        let (contents, label) = match report_format {
+            ReportFormat::Junit => (junit(&evaluation, name), "JUnit"),
+            ReportFormat::Sarif => (
+                serde_json::to_string_pretty(&sarif(&evaluation))? + "\n",
+                "SARIF",
+            ),
+        };
+        std::fs::write(path, contents)
+            .map_err(|e| anyhow::anyhow!("Could not write {}: {}", path.display(), e))?;
+        eprintln!("{} report written to {}", label, path.display());
+    }
+    Ok(evaluation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, notes: Option<&str>, age_days: i64) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "name": "ci <prod>", "notes": notes, "type": "INGEST",
+            "createdAt": 1_700_000_000 - age_days * 86_400, "accountId": 1
+        }))
+    }
+
+    fn sample() -> Evaluation {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let policies = Policies {
+            max_key_age_days: Some(30),
+            require_notes: Some(true),
+            ..Policies::default()
+        };
+        Evaluation::new(
+            policy_rules(&policies),
+            vec![
+                key("B", Some("team-a"), 45),
+                key("A", Some("  "), 10),
+                key("C", Some("team-b"), 5),
+            ],
+            now,
+        )
+    }
+
+    #[test]
+    fn test_rules_from_policies() {
+        assert!(policy_rules(&Policies::default()).is_empty());
+        assert_eq!(
+            audit_rules(&Policies::default()),
+            vec![Rule::KeyAge(90), Rule::RequireNotes]
+        );
+        let no_notes = Policies {
+            max_key_age_days: Some(30),
+            require_notes: Some(false),
+            max_user_keys_per_account: Some(10),
+            ..Policies::default()
+        };
+        assert_eq!(
+            audit_rules(&no_notes),
+            vec![
+                Rule::KeyAge(30),
+                Rule::KeyCount {
+                    key_type: Some("USER"),
+                    max: 10
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_evaluation_findings() {
+        let evaluation = sample();
+        let findings: Vec<(&str, &str, &str)> = evaluation
+            .findings
+            .iter()
+            .map(|f| (f.rule, f.key_id.as_str(), f.message.as_str()))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                ("key-age", "B", "created 45 days ago, more than 30 days"),
+                (
+                    "require-notes",
+                    "A",
+                    "has no notes naming its owner or purpose"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_count_flags_the_newest_keys() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let rules = vec![
+            Rule::KeyCount {
+                key_type: None,
+                max: 2,
+            },
+            Rule::KeyCount {
+                key_type: Some("USER"),
+                max: 0,
+            },
+        ];
+        let keys = vec![
+            key("A", Some("team-a"), 30),
+            key("B", Some("team-a"), 1),
+            key("C", Some("team-a"), 10),
+        ];
+        let evaluation = Evaluation::new(rules, keys, now);
+        assert_eq!(
+            evaluation.breaches,
+            vec![Breach {
+                rule: "key-count",
+                account_id: 1,
+                keys: 3,
+                max: 2
+            }]
+        );
+        let flagged: Vec<&str> = evaluation
+            .findings
+            .iter()
+            .map(|f| f.key_id.as_str())
+            .collect();
+        assert_eq!(flagged, vec!["B"]);
+        assert_eq!(
+            evaluation.findings[0].message,
+            "was created after the first 2 of 3 keys in its account"
+        );
+        assert_eq!(
+            evaluation.breaches[0].describe(),
+            "account 1 has 3 keys, more than 2 (key-count)"
+        );
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let xml = junit(&sample(), "policy check");
+        assert!(xml.contains("<testsuites name=\"policy check\" tests=\"6\" failures=\"2\">"));
+        assert!(xml.contains("<testsuite name=\"key-age\" tests=\"3\" failures=\"1\">"));
+        assert!(xml.contains(
+            "<testcase classname=\"account.1\" name=\"B (ci &lt;prod&gt;)\">\n      \
+             <failure message=\"created 45 days ago, more than 30 days\" type=\"key-age\">"
+        ));
+        assert!(xml.contains("<testcase classname=\"account.1\" name=\"C (ci &lt;prod&gt;)\"/>"));
+        assert_eq!(xml.matches("<testcase").count(), 6);
+    }
+
+    #[test]
+    fn test_sarif_log() {
+        let log = sarif(&sample());
+        let run = &log["runs"][0];
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"][1]["id"], "require-notes");
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1]["ruleId"], "require-notes");
+        assert_eq!(results[1]["ruleIndex"], 1);
+        assert_eq!(results[1]["level"], "note");
+        assert_eq!(
+            results[0]["message"]["text"],
+            "Key B (ci <prod>) in account 1 created 45 days ago, more than 30 days"
+        );
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "newrelic/accounts/1/api-keys/B"
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("out/keys.SARIF")),
+            ReportFormat::Sarif
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("junit.xml")),
+            ReportFormat::Junit
+        );
+    }
+}
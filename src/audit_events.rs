@@ -0,0 +1,178 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::output::{Format, Renderer};
+use crate::{nrql, NewRelicClient};
+
+/// NrAuditEvent has no dedicated attribute for API keys; key changes are recorded with an
+/// `api_access` action or a key target type.
+const KEY_EVENTS: &str = "(actionIdentifier LIKE 'api_access%' OR targetType LIKE '%key%')";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub account_id: i64,
+    pub actor: Option<String>,
+    pub actor_type: Option<String>,
+    pub action: Option<String>,
+    pub target_id: Option<String>,
+    pub target_type: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Turn `7d`, `12h`, `30m` or `2w` into an NRQL `SINCE` clause; anything else (`1 week ago`,
+/// `'2024-01-01 00:00:00'`) is passed through as NRQL.
+pub(crate) fn since_clause(since: &str) -> String {
+    let since = since.trim();
+    let (number, unit) = since.split_at(since.len().saturating_sub(1));
+    let unit = match unit {
+        "m" => Some("minutes"),
+        "h" => Some("hours"),
+        "d" => Some("days"),
+        "w" => Some("weeks"),
+        _ => None,
+    };
+    match (number.parse::<u32>(), unit) {
+        (Ok(number), Some(unit)) => format!("{} {} ago", number, unit),
+        _ => since.to_string(),
+    }
+}
+
+fn events_query(since: &str, actor: Option<&str>) -> String {
+    let mut conditions = vec![KEY_EVENTS.to_string()];
+    if let Some(actor) = actor {
+        let attribute = if actor.contains('@') {
+            "actorEmail"
+        } else {
+            "actorId"
+        };
+        conditions.push(format!("{} = {}", attribute, nrql::quote(actor)));
+    }
+    format!(
+        "SELECT timestamp, actorEmail, actorId, actorType, actionIdentifier, targetId, \
+         targetType, description FROM NrAuditEvent WHERE {} SINCE {} LIMIT MAX",
+        conditions.join(" AND "),
+        since_clause(since)
+    )
+}
+
+fn parse_event(account_id: i64, row: &serde_json::Value) -> AuditEvent {
+    let text = |field: &str| match &row[field] {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Null => None,
+        other => Some(other.to_string()),
+    };
+    AuditEvent {
+        timestamp: nrql::timestamp(&row["timestamp"]),
+        account_id,
+        actor: text("actorEmail").or_else(|| text("actorId")),
+        actor_type: text("actorType"),
+        action: text("actionIdentifier"),
+        target_id: text("targetId"),
+        target_type: text("targetType"),
+        description: text("description"),
+    }
+}
+
+/// API key change events across the given accounts, oldest first.
+pub async fn fetch(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    since: &str,
+    actor: Option<&str>,
+) -> anyhow::Result<Vec<AuditEvent>> {
+    let nrql = events_query(since, actor);
+    let mut events = Vec::new();
+    for &account_id in account_ids {
+        let rows = nrql::query(client, account_id, &nrql).await?;
+        events.extend(rows.iter().map(|row| parse_event(account_id, row)));
+    }
+    events.sort_by_key(|e| e.timestamp);
+    Ok(events)
+}
+
+const HEADERS: [&str; 6] = [
+    "TIME",
+    "ACCOUNT",
+    "ACTOR",
+    "ACTION",
+    "TARGET",
+    "DESCRIPTION",
+];
+
+impl Renderer for [AuditEvent] {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|e| {
+                vec![
+                    e.timestamp
+                        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_default(),
+                    e.account_id.to_string(),
+                    e.actor.clone().unwrap_or_default(),
+                    e.action.clone().unwrap_or_default(),
+                    e.target_id.clone().unwrap_or_default(),
+                    e.description.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+
+    fn empty_message(&self) -> Option<String> {
+        Some("No API key changes found".to_string())
+    }
+}
+
+pub fn print(events: &[AuditEvent], format: Format) -> anyhow::Result<()> {
+    print!("{}", events.render(&format)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_since_clause() {
+        assert_eq!(since_clause("7d"), "7 days ago");
+        assert_eq!(since_clause("12h"), "12 hours ago");
+        assert_eq!(since_clause("1 week ago"), "1 week ago");
+        assert_eq!(
+            since_clause("'2024-01-01 00:00:00'"),
+            "'2024-01-01 00:00:00'"
+        );
+    }
+
+    #[test]
+    fn test_events_query_filters_actor() {
+        let nrql = events_query("7d", Some("jane@example.com"));
+        assert!(nrql.contains("AND actorEmail = 'jane@example.com'"));
+        assert!(nrql.ends_with("SINCE 7 days ago LIMIT MAX"));
+        assert!(events_query("7d", Some("1234")).contains("actorId = '1234'"));
+    }
+
+    #[test]
+    fn test_parse_event_prefers_actor_email() {
+        let event = parse_event(
+            1,
+            &serde_json::json!({
+                "timestamp": 1700000000000_i64,
+                "actorEmail": null,
+                "actorId": 42,
+                "actionIdentifier": "api_access.delete_key",
+                "targetId": "ABC"
+            }),
+        );
+        assert_eq!(event.actor.as_deref(), Some("42"));
+        assert_eq!(event.action.as_deref(), Some("api_access.delete_key"));
+        assert!(event.timestamp.is_some());
+    }
+}
@@ -0,0 +1,371 @@
+//! `bench`: throughput of the list, create and delete pipelines against an in-memory NerdGraph,
+//! so that a slower client, pagination or batching layer shows up as a number rather than in
+//! production. Nothing leaves the machine; the mock answers each request after a fixed latency.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::client::RetryPolicy;
+use crate::inventory::{self, NewKey};
+use crate::output::{Format, Renderer};
+use crate::transport::{HttpRequest, HttpResponse, Transport, TransportFuture};
+use crate::NewRelicClient;
+
+const ACCOUNT_ID: i64 = 1;
+
+/// A NerdGraph that knows `keySearch`, `apiAccessCreateKeys` and `apiAccessDeleteKeys`, keeps
+/// its keys in memory and counts the requests it answers.
+pub struct MockNerdGraph {
+    keys: Mutex<Vec<serde_json::Value>>,
+    page_size: usize,
+    latency: Duration,
+    next_id: AtomicUsize,
+    requests: AtomicUsize,
+}
+
+impl MockNerdGraph {
+    pub fn new(page_size: usize, latency: Duration) -> Self {
+        MockNerdGraph {
+            keys: Mutex::new(Vec::new()),
+            page_size: page_size.max(1),
+            latency,
+            next_id: AtomicUsize::new(1),
+            requests: AtomicUsize::new(0),
+        }
+    }
+
+    fn key(&self, name: &str) -> serde_json::Value {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        serde_json::json!({
+            "id": format!("BENCH-{:08}", id),
+            "name": name,
+            "notes": null,
+            "type": "INGEST",
+            "key": format!("NRII-BENCH{:08}", id),
+            "createdAt": 1_700_000_000,
+            "accountId": ACCOUNT_ID,
+            "ingestType": "LICENSE",
+        })
+    }
+
+    /// Add `count` keys directly, without requests.
+    pub fn seed(&self, count: usize) {
+        let keys: Vec<_> = (0..count)
+            .map(|i| self.key(&format!("seed {}", i)))
+            .collect();
+        self.keys.lock().unwrap().extend(keys);
+    }
+
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    fn answer(&self, body: &serde_json::Value) -> serde_json::Value {
+        let query = body["query"].as_str().unwrap_or_default();
+        let variables = &body["variables"];
+        let mut keys = self.keys.lock().unwrap();
+        if query.contains("apiAccessCreateKeys") {
+            let created: Vec<_> = variables["keys"]["ingest"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|spec| self.key(spec["name"].as_str().unwrap_or_default()))
+                .collect();
+            keys.extend(created.iter().cloned());
+            serde_json::json!({"apiAccessCreateKeys": {"createdKeys": created, "errors": []}})
+        } else if query.contains("apiAccessDeleteKeys") {
+            let ids: Vec<&str> = variables["keys"]["ingestKeyIds"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|id| id.as_str())
+                .collect();
+            keys.retain(|key| !ids.contains(&key["id"].as_str().unwrap_or_default()));
+            let deleted: Vec<_> = ids.iter().map(|id| serde_json::json!({"id": id})).collect();
+            serde_json::json!({"apiAccessDeleteKeys": {"deletedKeys": deleted, "errors": []}})
+        } else if query.contains("keySearch") {
+            let start: usize = variables["cursor"]
+                .as_str()
+                .and_then(|cursor| cursor.parse().ok())
+                .unwrap_or(0);
+            let end = (start + self.page_size).min(keys.len());
+            let page = keys.get(start..end).unwrap_or_default();
+            let next_cursor = (end < keys.len()).then(|| end.to_string());
+            serde_json::json!({"actor": {"apiAccess": {"keySearch": {
+                "keys": page,
+                "nextCursor": next_cursor,
+            }}}})
+        } else {
+            serde_json::Value::Null
+        }
+    }
+}
+
+impl Transport for MockNerdGraph {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            if !self.latency.is_zero() {
+                tokio::time::sleep(self.latency).await;
+            }
+            let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+            let data = self.answer(&body);
+            if data.is_null() {
+                anyhow::bail!("The benchmark NerdGraph does not answer this request");
+            }
+            Ok(HttpResponse {
+                status: 200,
+                headers: Vec::new(),
+                body: serde_json::to_vec(&serde_json::json!({ "data": data }))?,
+            })
+        })
+    }
+}
+
+/// Options for [`run`].
+pub struct Options {
+    /// Keys in the account when the list pipeline runs
+    pub keys: usize,
+    pub page_size: usize,
+    pub concurrency: Vec<usize>,
+    /// Keys per delete mutation
+    pub batch_size: Vec<usize>,
+    pub latency: Duration,
+}
+
+/// One measured run of a pipeline.
+#[derive(Debug, Serialize)]
+pub struct Measurement {
+    pub pipeline: &'static str,
+    pub concurrency: usize,
+    pub batch_size: usize,
+    pub keys: usize,
+    pub requests: usize,
+    pub elapsed_ms: u128,
+    pub keys_per_second: f64,
+}
+
+impl Measurement {
+    fn new(
+        pipeline: &'static str,
+        concurrency: usize,
+        batch_size: usize,
+        keys: usize,
+        requests: usize,
+        elapsed: Duration,
+    ) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        Measurement {
+            pipeline,
+            concurrency,
+            batch_size,
+            keys,
+            requests,
+            elapsed_ms: elapsed.as_millis(),
+            keys_per_second: if seconds > 0.0 {
+                (keys as f64 / seconds * 10.0).round() / 10.0
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// The client owns its transport; this one hands requests to a mock the benchmark also reads.
+struct Shared(Arc<MockNerdGraph>);
+
+impl Transport for Shared {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        self.0.send(request)
+    }
+}
+
+fn mock_client(mock: &Arc<MockNerdGraph>) -> anyhow::Result<NewRelicClient> {
+    NewRelicClient::builder()
+        .api_key("NRAK-BENCH")
+        .retry_policy(RetryPolicy::none())
+        .transport(Shared(Arc::clone(mock)))
+        .build()
+}
+
+/// Run `jobs` with at most `concurrency` of them in flight.
+async fn bounded<F>(concurrency: usize, jobs: Vec<F>) -> anyhow::Result<()>
+where
+    F: std::future::Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for job in jobs {
+        let permits = Arc::clone(&permits);
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await?;
+            job.await
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        joined??;
+    }
+    Ok(())
+}
+
+/// Measure listing `options.keys` keys, then creating and deleting as many at each
+/// concurrency level (and for deletes, each batch size).
+pub async fn measure(options: &Options) -> anyhow::Result<Vec<Measurement>> {
+    let mut measurements = Vec::new();
+
+    let mock = Arc::new(MockNerdGraph::new(options.page_size, options.latency));
+    mock.seed(options.keys);
+    let client = mock_client(&mock)?;
+    let started = Instant::now();
+    let listed = inventory::fetch(&client, &[ACCOUNT_ID], &["INGEST"]).await?;
+    measurements.push(Measurement::new(
+        "list",
+        1,
+        options.page_size,
+        listed.len(),
+        mock.requests(),
+        started.elapsed(),
+    ));
+
+    for &concurrency in &options.concurrency {
+        let mock = Arc::new(MockNerdGraph::new(options.page_size, options.latency));
+        let client = mock_client(&mock)?;
+        let jobs: Vec<_> = (0..options.keys)
+            .map(|i| {
+                let client = client.clone();
+                async move {
+                    let spec = NewKey {
+                        key_type: "INGEST".to_string(),
+                        account_id: ACCOUNT_ID,
+                        name: format!("bench {}", i),
+                        notes: None,
+                        ingest_type: Some("LICENSE".to_string()),
+                        user_id: None,
+                    };
+                    inventory::create(&client, &spec).await.map(|_| ())
+                }
+            })
+            .collect();
+        let started = Instant::now();
+        bounded(concurrency, jobs).await?;
+        measurements.push(Measurement::new(
+            "create",
+            concurrency,
+            1,
+            options.keys,
+            mock.requests(),
+            started.elapsed(),
+        ));
+
+        for &batch_size in &options.batch_size {
+            let mock = Arc::new(MockNerdGraph::new(options.page_size, options.latency));
+            mock.seed(options.keys);
+            let client = mock_client(&mock)?;
+            let ids: Vec<String> = mock
+                .keys
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|key| key["id"].as_str().map(str::to_string))
+                .collect();
+            let jobs: Vec<_> = ids
+                .chunks(batch_size.max(1))
+                .map(|batch| {
+                    let client = client.clone();
+                    let batch = batch.to_vec();
+                    async move {
+                        let outcome = inventory::delete_keys(&client, &batch, &[]).await?;
+                        match outcome.errors.first() {
+                            Some(error) => Err(anyhow::anyhow!("{}", error)),
+                            None => Ok(()),
+                        }
+                    }
+                })
+                .collect();
+            let started = Instant::now();
+            bounded(concurrency, jobs).await?;
+            measurements.push(Measurement::new(
+                "delete",
+                concurrency,
+                batch_size,
+                ids.len(),
+                mock.requests(),
+                started.elapsed(),
+            ));
+        }
+    }
+    Ok(measurements)
+}
+
+const HEADERS: [&str; 7] = [
+    "PIPELINE",
+    "CONCURRENCY",
+    "BATCH",
+    "KEYS",
+    "REQUESTS",
+    "ELAPSED_MS",
+    "KEYS/S",
+];
+
+impl Renderer for [Measurement] {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|m| {
+                vec![
+                    m.pipeline.to_string(),
+                    m.concurrency.to_string(),
+                    m.batch_size.to_string(),
+                    m.keys.to_string(),
+                    m.requests.to_string(),
+                    m.elapsed_ms.to_string(),
+                    m.keys_per_second.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Print [`measure`]'s results.
+pub async fn run(options: &Options, format: &Format) -> anyhow::Result<()> {
+    print!("{}", measure(options).await?.render(format)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_measure_counts_requests_per_pipeline() {
+        let options = Options {
+            keys: 25,
+            page_size: 10,
+            concurrency: vec![4],
+            batch_size: vec![10],
+            latency: Duration::ZERO,
+        };
+        let measurements = measure(&options).await.unwrap();
+        let by_pipeline: HashMap<&str, &Measurement> =
+            measurements.iter().map(|m| (m.pipeline, m)).collect();
+        assert_eq!(by_pipeline["list"].keys, 25);
+        assert_eq!(by_pipeline["list"].requests, 3);
+        assert_eq!(by_pipeline["create"].requests, 25);
+        assert_eq!(by_pipeline["delete"].keys, 25);
+        assert_eq!(by_pipeline["delete"].requests, 3);
+    }
+}
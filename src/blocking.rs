@@ -0,0 +1,126 @@
+//! A synchronous wrapper around [`NewRelicClient`], in the spirit of `reqwest::blocking`.
+//!
+//! Each client owns a single-threaded Tokio runtime and drives the async calls to completion
+//! on it. Like `reqwest::blocking`, it must not be used from within an async runtime; call the
+//! async client directly there.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::inventory::{self, ApiKey, DeleteOutcome, NewKey};
+use crate::rotation::{self, Rotation};
+use crate::usage::{self, UsageSignal};
+use crate::{NewRelicClient, Variables};
+
+/// Blocking counterpart of [`NewRelicClient`]; cheap to clone, clones share the runtime.
+#[derive(Clone)]
+pub struct NewRelicClientBlocking {
+    inner: NewRelicClient,
+    runtime: Arc<Runtime>,
+}
+
+impl NewRelicClientBlocking {
+    /// Wrap a client configured with [`NewRelicClient::builder`].
+    pub fn from_async(inner: NewRelicClient) -> anyhow::Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The wrapped async client.
+    pub fn inner(&self) -> &NewRelicClient {
+        &self.inner
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    pub fn execute_query(
+        &self,
+        query: &str,
+        variables: Option<Variables>,
+    ) -> anyhow::Result<serde_json::Value> {
+        self.block_on(self.inner.execute_query(query, variables))
+    }
+
+    /// See [`inventory::fetch`].
+    pub fn list_keys(
+        &self,
+        account_ids: &[i64],
+        key_types: &[&str],
+    ) -> anyhow::Result<Vec<ApiKey>> {
+        self.block_on(inventory::fetch(&self.inner, account_ids, key_types))
+    }
+
+    /// See [`inventory::get`].
+    pub fn get_key(&self, key_id: &str, key_type: &str) -> anyhow::Result<ApiKey> {
+        self.block_on(inventory::get(&self.inner, key_id, key_type))
+    }
+
+    /// See [`inventory::create`].
+    pub fn create_key(&self, spec: &NewKey) -> anyhow::Result<ApiKey> {
+        self.block_on(inventory::create(&self.inner, spec))
+    }
+
+    /// See [`inventory::delete_keys`].
+    pub fn delete_keys(
+        &self,
+        ingest_key_ids: &[String],
+        user_key_ids: &[String],
+    ) -> anyhow::Result<DeleteOutcome> {
+        self.block_on(inventory::delete_keys(
+            &self.inner,
+            ingest_key_ids,
+            user_key_ids,
+        ))
+    }
+
+    /// See [`rotation::rotate`].
+    pub fn rotate_key(
+        &self,
+        key_id: &str,
+        key_type: &str,
+        keep_old: bool,
+    ) -> anyhow::Result<Rotation> {
+        self.block_on(rotation::rotate(&self.inner, key_id, key_type, keep_old))
+    }
+
+    /// See [`usage::signals`].
+    pub fn usage_signals(
+        &self,
+        account_id: i64,
+        key_id: &str,
+        since_days: u32,
+    ) -> anyhow::Result<Vec<UsageSignal>> {
+        self.block_on(usage::signals(&self.inner, account_id, key_id, since_days))
+    }
+}
+
+#[cfg(all(test, feature = "reqwest"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_query_surfaces_transport_errors() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/graphql", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .endpoint(endpoint)
+            .retry_policy(crate::RetryPolicy::none())
+            .build()
+            .and_then(NewRelicClientBlocking::from_async)
+            .unwrap();
+        assert_eq!(client.inner().api_key(), "NRAK-TEST");
+        assert!(client
+            .execute_query("{ actor { user { id } } }", None)
+            .is_err());
+    }
+}
@@ -0,0 +1,471 @@
+//! `bootstrap`: the standard key layout of a new account, described in a blueprint file and
+//! created in one run. Every key carries the label `blueprint:<name>` and the blueprint's labels
+//! in its notes; running the same blueprint again creates only the keys that are missing and adds
+//! labels the existing keys lack, so it is safe to repeat after a failure or a blueprint change.
+//!
+//! ```yaml
+//! name: standard
+//! labels:                     # added to the notes of every key as name:value
+//!   team: platform
+//!   cost-center: "4711"
+//! secret_sink: "file:keys-{account_id}.json"   # optional; --secret-sink wins
+//! webhook: https://hooks.example.com/bootstrap # optional; notified with the summary
+//! keys:
+//!   - name: "{account_id} {ingest_type}"
+//!     ingest_type: LICENSE    # type defaults to INGEST
+//!   - name: "{account_id} {ingest_type}"
+//!     ingest_type: BROWSER
+//!   - name: "deploy bot ({account_id})"
+//!     type: USER
+//!     user_id: 1001           # default: the user of the API key
+//!     notes: "used by CI"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::environment::ReportEntry;
+use crate::inventory::{self, ApiKey, KeyChange, NewKey};
+use crate::onboard::validate_label_value;
+use crate::prompt::Confirmation;
+use crate::sink::Secrets;
+use crate::{fetch_identity, webhook, NewRelicClient};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Blueprint {
+    pub name: String,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Where the secrets of created keys go; `{account_id}` is replaced
+    #[serde(default)]
+    pub secret_sink: Option<String>,
+    #[serde(default)]
+    pub webhook: Option<String>,
+    pub keys: Vec<BlueprintKey>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlueprintKey {
+    /// `{account_id}`, `{blueprint}` and `{ingest_type}` are replaced
+    pub name: String,
+    #[serde(rename = "type", default = "default_key_type")]
+    pub key_type: String,
+    /// `LICENSE` or `BROWSER`, for ingest keys (default: `LICENSE`)
+    #[serde(default)]
+    pub ingest_type: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Owner of a user key (default: the user of the API key)
+    #[serde(default)]
+    pub user_id: Option<i64>,
+}
+
+fn default_key_type() -> String {
+    "INGEST".to_string()
+}
+
+impl Blueprint {
+    /// Parse a YAML blueprint, or a JSON one in builds without the `yaml` feature.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        #[cfg(feature = "yaml")]
+        let blueprint: Self =
+            serde_yaml::from_str(text).map_err(|e| anyhow::anyhow!("Invalid blueprint: {}", e))?;
+        #[cfg(not(feature = "yaml"))]
+        let blueprint: Self = serde_json::from_str(text).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid blueprint: {} (YAML blueprints need the `yaml` feature)",
+                e
+            )
+        })?;
+        validate_label_value("blueprint name", &blueprint.name)?;
+        for (name, value) in &blueprint.labels {
+            validate_label_value("label name", name)?;
+            validate_label_value("label value", value)?;
+        }
+        if blueprint.keys.is_empty() {
+            anyhow::bail!("Blueprint '{}' declares no keys", blueprint.name);
+        }
+        for key in &blueprint.keys {
+            match key.key_type.to_uppercase().as_str() {
+                "INGEST" => {
+                    let ingest_type = key.ingest_type.as_deref().unwrap_or("LICENSE");
+                    if !matches!(ingest_type.to_uppercase().as_str(), "LICENSE" | "BROWSER") {
+                        anyhow::bail!(
+                            "Unsupported ingest type '{}' for '{}' in the blueprint (expected \
+                             LICENSE or BROWSER)",
+                            ingest_type,
+                            key.name
+                        );
+                    }
+                }
+                "USER" => {}
+                _ => anyhow::bail!(
+                    "Unsupported key type '{}' for '{}' in the blueprint (expected INGEST or USER)",
+                    key.key_type,
+                    key.name
+                ),
+            }
+        }
+        Ok(blueprint)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// The labels every key of the blueprint carries, `blueprint:<name>` first.
+    pub fn labels(&self) -> Vec<String> {
+        std::iter::once(format!("blueprint:{}", self.name))
+            .chain(
+                self.labels
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}", name, value)),
+            )
+            .collect()
+    }
+
+    /// Whether some key of the blueprint is a user key without an owner.
+    pub fn needs_caller(&self) -> bool {
+        self.keys
+            .iter()
+            .any(|key| key.key_type.eq_ignore_ascii_case("USER") && key.user_id.is_none())
+    }
+}
+
+fn render(template: &str, blueprint: &str, account_id: i64, ingest_type: &str) -> String {
+    template
+        .replace("{blueprint}", blueprint)
+        .replace("{account_id}", &account_id.to_string())
+        .replace("{ingest_type}", &ingest_type.to_lowercase())
+}
+
+/// What a run does to bring an account in line with a blueprint.
+pub struct Plan<'k> {
+    pub create: Vec<NewKey>,
+    /// Keys of the blueprint that lack some of its labels
+    pub relabel: Vec<(&'k ApiKey, KeyChange)>,
+    /// Keys of the blueprint that are already as it describes them
+    pub present: Vec<&'k ApiKey>,
+}
+
+impl Plan<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.relabel.is_empty()
+    }
+}
+
+/// Compare `existing` with the keys `blueprint` wants in `account_id`: a key counts as created
+/// when it has the template's type and name and carries the `blueprint:<name>` label. User keys
+/// without a `user_id` belong to `caller`.
+pub fn plan<'k>(
+    blueprint: &Blueprint,
+    account_id: i64,
+    caller: Option<i64>,
+    existing: &'k [ApiKey],
+) -> anyhow::Result<Plan<'k>> {
+    let labels = blueprint.labels();
+    let mut plan = Plan {
+        create: Vec::new(),
+        relabel: Vec::new(),
+        present: Vec::new(),
+    };
+    for template in &blueprint.keys {
+        let key_type = template.key_type.to_uppercase();
+        let ingest_type = match key_type.as_str() {
+            "INGEST" => Some(
+                template
+                    .ingest_type
+                    .as_deref()
+                    .unwrap_or("LICENSE")
+                    .to_uppercase(),
+            ),
+            _ => None,
+        };
+        let name = render(
+            &template.name,
+            &blueprint.name,
+            account_id,
+            ingest_type.as_deref().unwrap_or(""),
+        );
+        let found = existing.iter().find(|key| {
+            key.account_id == Some(account_id)
+                && key.key_type.as_deref() == Some(key_type.as_str())
+                && key.name.as_deref() == Some(name.as_str())
+                && words(key).any(|word| word == labels[0])
+        });
+        if let Some(key) = found {
+            let missing: Vec<&str> = labels
+                .iter()
+                .map(String::as_str)
+                .filter(|label| !words(key).any(|word| word == *label))
+                .collect();
+            if missing.is_empty() {
+                plan.present.push(key);
+            } else {
+                let notes = match key.notes.as_deref().map(str::trim) {
+                    Some(notes) if !notes.is_empty() => {
+                        format!("{} {}", notes, missing.join(" "))
+                    }
+                    _ => missing.join(" "),
+                };
+                let change = KeyChange {
+                    key_id: key.id.clone(),
+                    key_type: key_type.clone(),
+                    name: None,
+                    notes: Some(notes),
+                };
+                plan.relabel.push((key, change));
+            }
+            continue;
+        }
+        let notes = match &template.notes {
+            Some(notes) => format!(
+                "{} {}",
+                render(
+                    notes,
+                    &blueprint.name,
+                    account_id,
+                    ingest_type.as_deref().unwrap_or("")
+                ),
+                labels.join(" ")
+            ),
+            None => labels.join(" "),
+        };
+        let user_id = match key_type.as_str() {
+            "USER" => Some(template.user_id.or(caller).ok_or_else(|| {
+                anyhow::anyhow!("'{}' is a USER key but has no user_id", template.name)
+            })?),
+            _ => None,
+        };
+        plan.create.push(NewKey {
+            key_type,
+            account_id,
+            name,
+            notes: Some(notes),
+            ingest_type,
+            user_id,
+        });
+    }
+    Ok(plan)
+}
+
+fn words(key: &ApiKey) -> impl Iterator<Item = &str> {
+    key.notes.as_deref().unwrap_or("").split_whitespace()
+}
+
+/// Options for [`bootstrap`].
+pub struct Options<'a> {
+    pub dry_run: bool,
+    pub confirmation: Confirmation,
+    /// Overrides the blueprint's `webhook`
+    pub webhook: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    blueprint: &'a str,
+    account_id: i64,
+    generated_at: DateTime<Utc>,
+    created: Vec<ReportEntry>,
+    relabeled: Vec<ReportEntry>,
+    /// Keys of the blueprint the account already had
+    existing: Vec<ReportEntry>,
+    errors: Vec<String>,
+}
+
+/// Create the keys of `blueprint` that `account_id` lacks, label the ones it has, hand new
+/// secrets to `secrets` and notify the webhook. Keys created before a failure are still
+/// published, so that no secret is lost.
+pub async fn bootstrap(
+    client: &NewRelicClient,
+    blueprint: &Blueprint,
+    account_id: i64,
+    options: Options<'_>,
+    secrets: &Secrets,
+) -> anyhow::Result<()> {
+    let existing = inventory::fetch(client, &[account_id], &["INGEST", "USER"]).await?;
+    let caller = if blueprint.needs_caller() {
+        fetch_identity(client).await?.actor.user.map(|user| user.id)
+    } else {
+        None
+    };
+    let plan = plan(blueprint, account_id, caller, &existing)?;
+    if plan.is_empty() {
+        eprintln!(
+            "Account {} already matches blueprint {} ({} key(s))",
+            account_id,
+            blueprint.name,
+            plan.present.len()
+        );
+        return Ok(());
+    }
+    eprintln!(
+        "Blueprint {} in account {}: {} key(s) to create, {} to relabel, {} already there",
+        blueprint.name,
+        account_id,
+        plan.create.len(),
+        plan.relabel.len(),
+        plan.present.len()
+    );
+    for key in &plan.create {
+        eprintln!(
+            "  create {} {}",
+            key.ingest_type.as_deref().unwrap_or(&key.key_type),
+            key.name
+        );
+    }
+    for (key, _) in &plan.relabel {
+        eprintln!(
+            "  relabel {}  {}",
+            key.id,
+            key.name.as_deref().unwrap_or("-")
+        );
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+    if !plan.create.is_empty() {
+        secrets.check()?;
+    }
+    let question = format!(
+        "Create {} key(s) and relabel {} in account {}?",
+        plan.create.len(),
+        plan.relabel.len(),
+        account_id
+    );
+    if !options.confirmation.confirm(&question)? {
+        println!("Nothing changed");
+        return Ok(());
+    }
+
+    let mut created = Vec::new();
+    let mut errors = Vec::new();
+    for spec in &plan.create {
+        match inventory::create(client, spec).await {
+            Ok(key) => created.push(key),
+            Err(e) => {
+                errors.push(format!("{:#}", e));
+                break;
+            }
+        }
+    }
+    let created: Vec<&ApiKey> = created.iter().collect();
+    if !created.is_empty() {
+        secrets
+            .publish(&format!("Bootstrapped account {}", account_id), &created)
+            .await?;
+        secrets.print(&created, &created)?;
+    }
+    let mut relabeled = Vec::new();
+    if errors.is_empty() && !plan.relabel.is_empty() {
+        let changes: Vec<KeyChange> = plan
+            .relabel
+            .iter()
+            .map(|(_, change)| change.clone())
+            .collect();
+        match inventory::update_keys(client, &changes).await {
+            Ok(outcome) => {
+                relabeled = outcome.updated.iter().map(ReportEntry::from).collect();
+                errors.extend(outcome.errors);
+            }
+            Err(e) => errors.push(format!("{:#}", e)),
+        }
+    }
+
+    let report = Report {
+        blueprint: &blueprint.name,
+        account_id,
+        generated_at: Utc::now(),
+        created: created.iter().map(|key| ReportEntry::from(*key)).collect(),
+        relabeled,
+        existing: plan.present.into_iter().map(ReportEntry::from).collect(),
+        errors,
+    };
+    let summary = format!(
+        "Bootstrapped account {} from blueprint {}: {} key(s) created, {} relabeled, {} error(s)",
+        account_id,
+        blueprint.name,
+        report.created.len(),
+        report.relabeled.len(),
+        report.errors.len()
+    );
+    eprintln!("{}", summary);
+    if let Some(url) = options.webhook.or(blueprint.webhook.as_deref()) {
+        webhook::notify(url, &summary, &report).await?;
+    }
+    if !report.errors.is_empty() {
+        anyhow::bail!(
+            "Bootstrapping account {} is incomplete; run it again to finish: {}",
+            account_id,
+            report.errors.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUEPRINT: &str = r#"{
+        "name": "standard",
+        "labels": {"team": "platform"},
+        "keys": [
+            {"name": "{account_id} {ingest_type}", "ingest_type": "license"},
+            {"name": "{account_id} {ingest_type}", "ingest_type": "BROWSER"},
+            {"name": "deploy bot", "type": "USER", "notes": "used by CI"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_plan_creates_missing_keys_and_relabels_existing_ones() {
+        let blueprint = Blueprint::parse(BLUEPRINT).unwrap();
+        assert!(blueprint.needs_caller());
+        let existing = vec![
+            ApiKey::fixture(serde_json::json!({
+                "id": "K1", "name": "7 license", "notes": "blueprint:standard team:platform",
+                "type": "INGEST", "createdAt": 1, "accountId": 7, "ingestType": "LICENSE"
+            })),
+            ApiKey::fixture(serde_json::json!({
+                "id": "K2", "name": "7 browser", "notes": "blueprint:standard",
+                "type": "INGEST", "createdAt": 1, "accountId": 7, "ingestType": "BROWSER"
+            })),
+        ];
+        let plan = plan(&blueprint, 7, Some(5), &existing).unwrap();
+        assert_eq!(plan.present.len(), 1);
+        assert_eq!(plan.relabel.len(), 1);
+        assert_eq!(
+            plan.relabel[0].1.notes.as_deref(),
+            Some("blueprint:standard team:platform")
+        );
+        assert_eq!(plan.create.len(), 1);
+        assert_eq!(plan.create[0].key_type, "USER");
+        assert_eq!(plan.create[0].user_id, Some(5));
+        assert_eq!(
+            plan.create[0].notes.as_deref(),
+            Some("used by CI blueprint:standard team:platform")
+        );
+
+        assert!(super::plan(&blueprint, 7, None, &existing).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_blueprints() {
+        assert!(Blueprint::parse(r#"{"name": "standard", "keys": []}"#).is_err());
+        assert!(Blueprint::parse(
+            r#"{"name": "standard", "keys": [{"name": "x", "type": "LICENSE"}]}"#
+        )
+        .is_err());
+        assert!(Blueprint::parse(
+            r#"{"name": "standard", "labels": {"team": "two words"}, "keys": [{"name": "x"}]}"#
+        )
+        .is_err());
+    }
+}
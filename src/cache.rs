@@ -0,0 +1,194 @@
+//! NerdGraph response cache behind `--offline`.
+//!
+//! Every successful read query is stored, with key secrets stripped, under the cache directory.
+//! With `--offline` the cache answers instead of the network, so key IDs and metadata stay
+//! available during an outage; mutations are refused rather than queued.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::middleware::{Middleware, Next};
+use crate::transport::{HttpRequest, HttpResponse, TransportFuture};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    fetched_at: DateTime<Utc>,
+    status: u16,
+    body: serde_json::Value,
+}
+
+/// Clones share the record of what was served from the cache.
+#[derive(Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    offline: bool,
+    oldest_hit: Arc<Mutex<Option<DateTime<Utc>>>>,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, offline: bool) -> Self {
+        Self {
+            dir,
+            offline,
+            oldest_hit: Arc::default(),
+        }
+    }
+
+    /// When the oldest response served from the cache was fetched, if any was.
+    pub fn oldest_hit(&self) -> Option<DateTime<Utc>> {
+        *self.oldest_hit.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The same query with the same credentials maps to the same file, whatever order its
+    /// variables were serialized in.
+    fn path(&self, request: &HttpRequest) -> PathBuf {
+        let body = serde_json::from_slice::<serde_json::Value>(&request.body)
+            .map(|body| body.to_string())
+            .unwrap_or_default();
+        let mut hasher = Sha256::new();
+        for part in [
+            request.url.as_str(),
+            request.header("API-Key").unwrap_or_default(),
+            body.as_str(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let digest: String = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        self.dir.join(format!("{}.json", digest))
+    }
+
+    fn load(&self, request: &HttpRequest) -> anyhow::Result<HttpResponse> {
+        let contents = fs::read_to_string(self.path(request)).map_err(|_| {
+            anyhow::anyhow!(
+                "No cached response for this request; run the command once without --offline"
+            )
+        })?;
+        let entry: Entry = serde_json::from_str(&contents)?;
+        let mut oldest = self.oldest_hit.lock().unwrap_or_else(|e| e.into_inner());
+        if oldest.is_none_or(|oldest| entry.fetched_at < oldest) {
+            *oldest = Some(entry.fetched_at);
+        }
+        Ok(HttpResponse {
+            status: entry.status,
+            headers: Vec::new(),
+            body: serde_json::to_vec(&entry.body)?,
+        })
+    }
+
+    fn store(&self, request: &HttpRequest, response: &HttpResponse) -> anyhow::Result<()> {
+        let mut body: serde_json::Value = serde_json::from_slice(&response.body)?;
+        if body.get("errors").is_some() {
+            return Ok(());
+        }
+        strip_secrets(&mut body);
+        let entry = Entry {
+            fetched_at: Utc::now(),
+            status: response.status,
+            body,
+        };
+        config::write_private(&self.path(request), &serde_json::to_string(&entry)?)
+    }
+}
+
+/// Drop the `key` secret of every API key in a response.
+fn strip_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if map.get("key").is_some_and(|key| key.is_string()) {
+                map.remove("key");
+            }
+            map.values_mut().for_each(strip_secrets);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_secrets),
+        _ => {}
+    }
+}
+
+impl Middleware for ResponseCache {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            if request.is_mutation() {
+                if self.offline {
+                    return Err(anyhow::anyhow!(
+                        "Changes to keys are not possible with --offline"
+                    ));
+                }
+                return next.run(request).await;
+            }
+            if self.offline {
+                return self.load(&request);
+            }
+            let response = next.run(request.clone()).await?;
+            if (200..300).contains(&response.status) {
+                // A cache that cannot be written must not fail the command.
+                let _ = self.store(&request, &response);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NewRelicClient;
+
+    fn client(cache: &ResponseCache) -> NewRelicClient {
+        NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(cache.clone())
+            .transport(|request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                assert!(!request.is_mutation());
+                Ok(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: br#"{"data":{"key":{"id":"k1","key":"NRAK-SECRET"}}}"#.to_vec(),
+                })
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_offline_serves_cached_responses_without_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let online = ResponseCache::new(dir.path().to_path_buf(), false);
+        let data = client(&online)
+            .execute_query("{ key }", None)
+            .await
+            .unwrap();
+        assert_eq!(data["key"]["key"], "NRAK-SECRET");
+        assert!(online.oldest_hit().is_none());
+
+        let offline = ResponseCache::new(dir.path().to_path_buf(), true);
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(offline.clone())
+            .transport(|_request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                panic!("the network should not be used offline")
+            })
+            .build()
+            .unwrap();
+        let data = client.execute_query("{ key }", None).await.unwrap();
+        assert_eq!(data["key"]["id"], "k1");
+        assert!(data["key"].get("key").is_none());
+        assert!(offline.oldest_hit().is_some());
+
+        assert!(client.execute_query("{ other }", None).await.is_err());
+        assert!(client
+            .execute_query("mutation { delete }", None)
+            .await
+            .is_err());
+    }
+}
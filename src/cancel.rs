@@ -0,0 +1,157 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Why bulk work was asked to stop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reason {
+    Interrupted,
+    Terminated,
+    DeadlineExceeded,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Interrupted => write!(f, "interrupted"),
+            Reason::Terminated => write!(f, "terminated"),
+            Reason::DeadlineExceeded => write!(f, "deadline exceeded"),
+        }
+    }
+}
+
+/// Shared stop flag checked by bulk operations between batches, so a deadline or Ctrl-C never
+/// abandons a mutation halfway.
+#[derive(Clone, Default)]
+pub struct Cancellation(Arc<Mutex<Option<Reason>>>);
+
+impl Cancellation {
+    /// The first reason wins.
+    pub fn cancel(&self, reason: Reason) {
+        let mut slot = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        slot.get_or_insert(reason);
+    }
+
+    pub fn reason(&self) -> Option<Reason> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Watch for Ctrl-C, SIGTERM and the optional deadline. The first signal only requests a
+    /// graceful stop; a second one exits immediately.
+    pub fn install(deadline: Option<Duration>) -> Self {
+        let cancellation = Self::default();
+        let handle = cancellation.clone();
+        tokio::spawn(async move {
+            let deadline = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::pin!(deadline);
+            let mut deadline_done = false;
+            let mut signalled = false;
+            loop {
+                let reason = tokio::select! {
+                    Some(reason) = signal() => reason,
+                    _ = &mut deadline, if !deadline_done => {
+                        deadline_done = true;
+                        handle.cancel(Reason::DeadlineExceeded);
+                        eprintln!("Deadline reached: finishing the current batch");
+                        continue;
+                    }
+                    else => return,
+                };
+                if signalled {
+                    eprintln!("Aborting");
+                    std::process::exit(130);
+                }
+                signalled = true;
+                handle.cancel(reason);
+                eprintln!(
+                    "{}: finishing the current batch (press Ctrl-C again to abort)",
+                    match reason {
+                        Reason::Terminated => "Terminated",
+                        _ => "Interrupted",
+                    }
+                );
+            }
+        });
+        cancellation
+    }
+}
+
+/// The next Ctrl-C or SIGTERM, or `None` if signals cannot be watched.
+async fn signal() -> Option<Reason> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).ok()?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.ok().map(|_| Reason::Interrupted),
+            _ = terminate.recv() => Some(Reason::Terminated),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .ok()
+            .map(|_| Reason::Interrupted)
+    }
+}
+
+/// Parse `90s`, `2m`, `1h` or `1h30m`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid duration '{}' (expected e.g. 90s, 2m or 1h30m)",
+            value
+        )
+    };
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let amount: u64 = digits.parse().map_err(|_| invalid())?;
+        digits.clear();
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            _ => return Err(invalid()),
+        };
+        total = total.saturating_add(amount.saturating_mul(unit));
+    }
+    if !digits.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("2d").is_err());
+        assert!(parse_duration("0s").is_err());
+    }
+
+    #[test]
+    fn test_first_reason_wins() {
+        let cancellation = Cancellation::default();
+        assert_eq!(cancellation.reason(), None);
+        cancellation.cancel(Reason::DeadlineExceeded);
+        cancellation.cancel(Reason::Interrupted);
+        assert_eq!(cancellation.reason(), Some(Reason::DeadlineExceeded));
+    }
+}
@@ -0,0 +1,325 @@
+//! `--ci`: hand the keys that `create` and `rotate` produce to a CI system in the form it
+//! consumes, and keep their secrets out of the job log.
+//!
+//! - `github` prints `::add-mask::` for each secret and writes IDs and metadata, never secrets,
+//!   to `$GITHUB_OUTPUT` and `$GITHUB_STEP_SUMMARY`.
+//! - `gitlab` writes a dotenv file for `artifacts:reports:dotenv`.
+//! - `jenkins` writes a properties file for `readProperties`.
+//!
+//! GitLab and Jenkins have no command to mask a value at runtime, so with those adapters the
+//! secrets only go to the file and are replaced by [`MASKED`] on stdout.
+
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::config;
+use crate::inventory::ApiKey;
+
+/// What GitLab and Jenkins runs print instead of a secret.
+pub const MASKED: &str = "[masked]";
+
+pub enum Ci {
+    GitHub {
+        output: PathBuf,
+        summary: Option<PathBuf>,
+    },
+    GitLab {
+        file: PathBuf,
+    },
+    Jenkins {
+        file: PathBuf,
+    },
+}
+
+impl Ci {
+    /// The adapter for `value`; `file` overrides where GitLab and Jenkins variables are written.
+    pub fn parse(value: &str, file: Option<PathBuf>) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "github" => {
+                if file.is_some() {
+                    anyhow::bail!(
+                        "--ci-file does not apply to --ci github, which writes to $GITHUB_OUTPUT"
+                    );
+                }
+                Self::github_from_env()
+            }
+            "gitlab" => Ok(Ci::GitLab {
+                file: file.unwrap_or_else(|| PathBuf::from("newrelic-apikeys.env")),
+            }),
+            "jenkins" => Ok(Ci::Jenkins {
+                file: file.unwrap_or_else(|| PathBuf::from("newrelic-apikeys.properties")),
+            }),
+            _ => anyhow::bail!(
+                "Unsupported CI system '{}' (expected github, gitlab or jenkins)",
+                value
+            ),
+        }
+    }
+
+    /// The files GitHub Actions names in `$GITHUB_OUTPUT` and `$GITHUB_STEP_SUMMARY`.
+    fn github_from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+        let output = var("GITHUB_OUTPUT").ok_or_else(|| {
+            anyhow::anyhow!(
+                "--ci github needs $GITHUB_OUTPUT, which GitHub Actions sets for every step"
+            )
+        })?;
+        Ok(Ci::GitHub {
+            output: PathBuf::from(output),
+            summary: var("GITHUB_STEP_SUMMARY").map(PathBuf::from),
+        })
+    }
+
+    /// Print `value` as JSON without exposing the secrets of `keys` in the job log.
+    pub fn print<T: Serialize>(&self, value: &T, keys: &[&ApiKey]) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(value)?;
+        match self {
+            Ci::GitHub { .. } => {
+                for secret in secrets(keys) {
+                    println!("::add-mask::{}", secret);
+                }
+                println!("{}", json);
+            }
+            Ci::GitLab { .. } | Ci::Jenkins { .. } => println!("{}", mask(&json, keys)?),
+        }
+        Ok(())
+    }
+
+    /// Hand `keys` to the CI system. GitHub gets the step outputs `key-id` (the first key),
+    /// `key-ids` and `keys` plus a summary table; GitLab and Jenkins get a variables file that
+    /// includes the secrets.
+    pub fn publish(&self, heading: &str, keys: &[&ApiKey]) -> anyhow::Result<()> {
+        match self {
+            Ci::GitHub { output, summary } => {
+                append(output, &github_outputs(keys)?)?;
+                if let Some(summary) = summary {
+                    append(summary, &summary_table(heading, keys))?;
+                }
+            }
+            Ci::GitLab { file } => write_variables(file, &variables(keys), dotenv_line)?,
+            Ci::Jenkins { file } => write_variables(file, &variables(keys), properties_line)?,
+        }
+        Ok(())
+    }
+}
+
+fn secrets<'a>(keys: &'a [&ApiKey]) -> impl Iterator<Item = &'a str> {
+    keys.iter().filter_map(|key| key.secret())
+}
+
+/// `json` with every secret of `keys` replaced by [`MASKED`].
+pub fn mask(json: &str, keys: &[&ApiKey]) -> anyhow::Result<String> {
+    let mut masked = json.to_string();
+    let replacement = serde_json::to_string(MASKED)?;
+    for secret in secrets(keys) {
+        masked = masked.replace(&serde_json::to_string(secret)?, &replacement);
+    }
+    Ok(masked)
+}
+
+fn redacted(key: &ApiKey) -> ApiKey {
+    ApiKey {
+        key: None,
+        ..key.clone()
+    }
+}
+
+fn github_outputs(keys: &[&ApiKey]) -> anyhow::Result<String> {
+    let ids: Vec<&str> = keys.iter().map(|key| key.id.as_str()).collect();
+    let metadata: Vec<ApiKey> = keys.iter().map(|key| redacted(key)).collect();
+    let mut outputs = String::new();
+    if let Some(first) = ids.first() {
+        let _ = writeln!(outputs, "key-id={}", first);
+    }
+    let _ = writeln!(outputs, "key-ids={}", serde_json::to_string(&ids)?);
+    let _ = writeln!(outputs, "keys={}", serde_json::to_string(&metadata)?);
+    Ok(outputs)
+}
+
+fn summary_table(heading: &str, keys: &[&ApiKey]) -> String {
+    let cell = |value: Option<String>| value.unwrap_or_default().replace('|', "\\|");
+    let mut summary = format!(
+        "### {}\n\n| ID | Type | Account | Name |\n| --- | --- | --- | --- |\n",
+        heading
+    );
+    for key in keys {
+        let _ = writeln!(
+            summary,
+            "| `{}` | {} | {} | {} |",
+            key.id,
+            cell(key.key_type.clone()),
+            cell(key.account_id.map(|id| id.to_string())),
+            cell(key.name.clone())
+        );
+    }
+    summary.push('\n');
+    summary
+}
+
+/// Steps before us may have written the file from PowerShell, with CRLF line endings and
+/// without a final newline; what we add follows the file's line endings and starts on a line
+/// of its own.
+fn append(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let existing = std::fs::read(path).unwrap_or_default();
+    let contents = line_endings(&existing, contents);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Could not open {}: {}", path.display(), e))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+fn line_endings(existing: &[u8], contents: &str) -> String {
+    let crlf = existing.windows(2).any(|pair| pair == b"\r\n");
+    let mut out = String::new();
+    if !existing.is_empty() && !existing.ends_with(b"\n") {
+        out.push('\n');
+    }
+    out.push_str(contents);
+    if crlf {
+        out = out.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+    out
+}
+
+/// `NEW_RELIC_KEY_ID`, `NEW_RELIC_KEY` etc. for the first key; later keys get a `_2`, `_3`, ...
+/// suffix. `NEW_RELIC_KEY_IDS` lists every ID, comma-separated.
+fn variables(keys: &[&ApiKey]) -> Vec<(String, String)> {
+    let mut variables = vec![(
+        "NEW_RELIC_KEY_IDS".to_string(),
+        keys.iter()
+            .map(|key| key.id.as_str())
+            .collect::<Vec<_>>()
+            .join(","),
+    )];
+    for (index, key) in keys.iter().enumerate() {
+        let suffix = match index {
+            0 => String::new(),
+            n => format!("_{}", n + 1),
+        };
+        let mut push = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                variables.push((format!("NEW_RELIC_{}{}", name, suffix), value));
+            }
+        };
+        push("KEY_ID", Some(key.id.clone()));
+        push("KEY_TYPE", key.key_type.clone());
+        push("ACCOUNT_ID", key.account_id.map(|id| id.to_string()));
+        push("KEY_NAME", key.name.clone());
+        push("KEY", key.secret().map(str::to_string));
+    }
+    variables
+}
+
+/// GitLab dotenv values are single-line and unquoted.
+fn dotenv_line(name: &str, value: &str) -> String {
+    format!("{}={}\n", name, value.replace(['\r', '\n'], " "))
+}
+
+/// Java properties escape backslashes, line breaks and leading spaces.
+fn properties_line(name: &str, value: &str) -> String {
+    let mut escaped = String::new();
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            ' ' if i == 0 => escaped.push_str("\\ "),
+            c => escaped.push(c),
+        }
+    }
+    format!("{}={}\n", name, escaped)
+}
+
+/// The file holds secrets, so it is written readable only by the current user.
+fn write_variables(
+    path: &Path,
+    variables: &[(String, String)],
+    line: fn(&str, &str) -> String,
+) -> anyhow::Result<()> {
+    let contents: String = variables
+        .iter()
+        .map(|(name, value)| line(name, value))
+        .collect();
+    config::write_private(path, &contents)?;
+    eprintln!(
+        "Wrote {} variable(s) to {}",
+        variables.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, name: &str, secret: &str) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "name": name, "type": "INGEST",
+            "key": secret, "createdAt": 1, "accountId": 123
+        }))
+    }
+
+    #[test]
+    fn test_github_outputs_and_summary_omit_secrets() {
+        let key = key("K1", "ci | prod", "SECRET-NRAL");
+        let outputs = github_outputs(&[&key]).unwrap();
+        assert_eq!(
+            outputs,
+            "key-id=K1\nkey-ids=[\"K1\"]\nkeys=[{\"id\":\"K1\",\"name\":\"ci | prod\",\
+             \"notes\":null,\"type\":\"INGEST\",\"createdAt\":1,\"accountId\":123}]\n"
+        );
+        let summary = summary_table("Created API keys", &[&key]);
+        assert!(summary.contains("| `K1` | INGEST | 123 | ci \\| prod |"));
+        assert!(!summary.contains("SECRET"));
+    }
+
+    #[test]
+    fn test_append_follows_existing_line_endings() {
+        assert_eq!(line_endings(b"", "a\nb\n"), "a\nb\n");
+        assert_eq!(line_endings(b"x=1", "a\n"), "\na\n");
+        assert_eq!(line_endings(b"x=1\r\ny=2", "a\nb\n"), "\r\na\r\nb\r\n");
+    }
+
+    #[test]
+    fn test_variables_files_and_masked_stdout() {
+        let first = key("K1", " ci\\prod", "S1-NRAL");
+        let second = key("K2", "ci", "S2-NRAL");
+        let variables = variables(&[&first, &second]);
+        let names: Vec<&str> = variables.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "NEW_RELIC_KEY_IDS",
+                "NEW_RELIC_KEY_ID",
+                "NEW_RELIC_KEY_TYPE",
+                "NEW_RELIC_ACCOUNT_ID",
+                "NEW_RELIC_KEY_NAME",
+                "NEW_RELIC_KEY",
+                "NEW_RELIC_KEY_ID_2",
+                "NEW_RELIC_KEY_TYPE_2",
+                "NEW_RELIC_ACCOUNT_ID_2",
+                "NEW_RELIC_KEY_NAME_2",
+                "NEW_RELIC_KEY_2",
+            ]
+        );
+        assert_eq!(variables[0].1, "K1,K2");
+        assert_eq!(
+            properties_line("NEW_RELIC_KEY_NAME", " ci\\prod"),
+            "NEW_RELIC_KEY_NAME=\\ ci\\\\prod\n"
+        );
+        assert_eq!(dotenv_line("NOTES", "a\nb"), "NOTES=a b\n");
+
+        let json = serde_json::to_string_pretty(&first).unwrap();
+        let masked = mask(&json, &[&first]).unwrap();
+        assert!(masked.contains("\"key\": \"[masked]\""));
+        assert!(!masked.contains("S1-NRAL"));
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::Cancellation;
+use crate::filter::{self, KeyFilter};
+use crate::inventory::{self, ApiKey};
+use crate::paths::Paths;
+use crate::prompt::Confirmation;
+use crate::{config, consumption, usage, NewRelicClient};
+
+/// Options for [`stale`].
+pub struct StaleOptions<'a> {
+    pub days: u32,
+    pub delete: bool,
+    pub confirmation: Confirmation,
+    /// Continue from the progress saved by a cancelled run
+    pub resume: bool,
+    pub report_path: Option<&'a Path>,
+    /// Only keys that match
+    pub filter: &'a KeyFilter,
+}
+
+#[derive(Serialize)]
+struct Report {
+    generated_at: DateTime<Utc>,
+    days: u32,
+    entries: Vec<ReportEntry>,
+}
+
+#[derive(Serialize)]
+struct ReportEntry {
+    id: String,
+    name: Option<String>,
+    key_type: Option<String>,
+    account_id: Option<i64>,
+    created_at: Option<DateTime<Utc>>,
+    reason: String,
+    /// Whether the key is known to be unused, which `--delete` requires
+    known_unused: bool,
+    deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Usage checks done so far, saved when a run is cancelled so `--resume` can skip them.
+#[derive(Debug, Serialize, Deserialize)]
+struct Progress {
+    started_at: DateTime<Utc>,
+    days: u32,
+    account_ids: BTreeSet<i64>,
+    checked: BTreeSet<String>,
+    stale: BTreeSet<String>,
+}
+
+impl Progress {
+    fn new(days: u32, account_ids: &[i64]) -> Self {
+        Self {
+            started_at: Utc::now(),
+            days,
+            account_ids: account_ids.iter().copied().collect(),
+            checked: BTreeSet::new(),
+            stale: BTreeSet::new(),
+        }
+    }
+
+    fn load(path: &Path, days: u32, account_ids: &[i64]) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("No saved progress to resume at {}: {}", path.display(), e)
+        })?;
+        let progress: Self = serde_json::from_str(&contents)?;
+        progress.ensure_matches(days, account_ids)?;
+        Ok(progress)
+    }
+
+    fn ensure_matches(&self, days: u32, account_ids: &[i64]) -> anyhow::Result<()> {
+        let account_ids: BTreeSet<i64> = account_ids.iter().copied().collect();
+        if self.days != days || self.account_ids != account_ids {
+            return Err(anyhow::anyhow!(
+                "The saved progress is for --days {} and accounts {:?}; rerun with the same options or without --resume",
+                self.days,
+                self.account_ids
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn discard_progress(path: &Path) -> anyhow::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Keys created within the window cannot have been unused for the whole window.
+fn old_enough(key: &ApiKey, now: DateTime<Utc>, days: u32) -> bool {
+    key.age_days(now).is_none_or(|age| age >= i64::from(days))
+}
+
+/// Whether `key`, which had no changes or ingest errors in the last `days` days, is known to be
+/// unused, and why. Those signals are not use, so only an ingest key in an account that
+/// ingested nothing of its type in that time counts; New Relic does not meter user key use at
+/// all. `ingest` holds the GB per account and ingest type.
+fn evidence(key: &ApiKey, ingest: &BTreeMap<(i64, &str), f64>, days: u32) -> (bool, String) {
+    if key.is_user_key() {
+        return (
+            false,
+            "New Relic does not meter the use of user keys".to_string(),
+        );
+    }
+    let ingest_type = key.ingest_type.as_deref().unwrap_or("LICENSE");
+    let Some(account_id) = key.account_id else {
+        return (false, "the key's account is unknown".to_string());
+    };
+    match ingest.get(&(account_id, ingest_type)).copied() {
+        Some(gigabytes) if gigabytes > 0.0 => (
+            false,
+            format!(
+                "account {} ingested {:.2} GB of {} data in the last {} days",
+                account_id,
+                gigabytes,
+                ingest_type.to_lowercase(),
+                days
+            ),
+        ),
+        _ => (
+            true,
+            format!(
+                "account {} ingested no {} data in the last {} days",
+                account_id,
+                ingest_type.to_lowercase(),
+                days
+            ),
+        ),
+    }
+}
+
+/// List keys without changes or ingest errors in the last `days` days, optionally deleting the
+/// ones [`evidence`] shows are unused.
+///
+/// Cancellation is checked between usage checks and before deleting; a cancelled run saves its
+/// progress so the next `--resume` run only checks the remaining keys.
+pub async fn stale(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    options: StaleOptions<'_>,
+    paths: &Paths,
+    cancellation: &Cancellation,
+) -> anyhow::Result<()> {
+    let StaleOptions {
+        days,
+        delete,
+        confirmation,
+        resume,
+        report_path,
+        filter,
+    } = options;
+    let now = Utc::now();
+    let progress_path = paths.resume_file("cleanup-stale");
+    let mut progress = if resume {
+        Progress::load(&progress_path, days, account_ids)?
+    } else {
+        Progress::new(days, account_ids)
+    };
+    let cancelled = |progress: &Progress, total: usize| -> anyhow::Result<()> {
+        let Some(reason) = cancellation.reason() else {
+            return Ok(());
+        };
+        config::write_private(&progress_path, &serde_json::to_string_pretty(progress)?)?;
+        Err(anyhow::anyhow!(
+            "Cancelled ({}) after checking {} of {} key(s); nothing was deleted. Continue with `cleanup stale --resume`",
+            reason,
+            progress.checked.len(),
+            total
+        ))
+    };
+
+    let keys = filter::fetch(client, account_ids, &["INGEST", "USER"], filter).await?;
+    let candidates: Vec<ApiKey> = keys
+        .into_iter()
+        .filter(|k| old_enough(k, now, days))
+        .collect();
+    let total = candidates.len();
+
+    let mut stale = Vec::new();
+    for key in candidates {
+        if progress.checked.contains(&key.id) {
+            if progress.stale.contains(&key.id) {
+                stale.push(key);
+            }
+            continue;
+        }
+        cancelled(&progress, total)?;
+
+        let account_id = key.account_id.or(account_ids.first().copied());
+        let Some(account_id) = account_id else {
+            continue;
+        };
+        let signals = usage::signals(client, account_id, &key.id, days).await?;
+        progress.checked.insert(key.id.clone());
+        if usage::last_activity(&signals).is_none() {
+            progress.stale.insert(key.id.clone());
+            stale.push(key);
+        }
+    }
+
+    if stale.is_empty() {
+        discard_progress(&progress_path)?;
+        println!(
+            "No stale keys found (no key without changes or ingest errors in the last {} days)",
+            days
+        );
+        return Ok(());
+    }
+
+    let ingest_accounts: BTreeSet<i64> = stale
+        .iter()
+        .filter(|key| !key.is_user_key())
+        .filter_map(|key| key.account_id)
+        .collect();
+    let mut ingest = BTreeMap::new();
+    for account_id in ingest_accounts {
+        let since = format!("{}d", days);
+        for (ingest_type, gigabytes) in
+            consumption::ingest_by_type(client, account_id, &since).await?
+        {
+            ingest.insert((account_id, ingest_type), gigabytes);
+        }
+    }
+
+    println!(
+        "{} key(s) without changes or ingest errors in the last {} days:",
+        stale.len(),
+        days
+    );
+    let mut entries = Vec::new();
+    let mut unused = Vec::new();
+    for key in &stale {
+        let (known_unused, reason) = evidence(key, &ingest, days);
+        println!(
+            "  {}  {:<6}  account {:<10}  {}  (created {})",
+            key.id,
+            key.key_type.as_deref().unwrap_or("N/A"),
+            key.account_id.map(|id| id.to_string()).unwrap_or_default(),
+            key.name.as_deref().unwrap_or("N/A"),
+            key.created()
+                .map(|c| c.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "unknown".to_string())
+        );
+        println!(
+            "      {}: {}",
+            if known_unused {
+                "unused"
+            } else {
+                "may be in use"
+            },
+            reason
+        );
+        if known_unused {
+            unused.push(key);
+        }
+        entries.push(ReportEntry {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            key_type: key.key_type.clone(),
+            account_id: key.account_id,
+            created_at: key.created(),
+            reason,
+            known_unused,
+            deleted: false,
+            error: None,
+        });
+    }
+
+    if delete && unused.is_empty() {
+        println!(
+            "Nothing deleted: no key is known to be unused. Review the keys above and delete \
+             the ones nobody holds with `delete`"
+        );
+    } else if delete {
+        let question = format!(
+            "Delete {} key(s) known to be unused{}?",
+            unused.len(),
+            match stale.len() - unused.len() {
+                0 => String::new(),
+                kept => format!(" (keeping {} that may be in use)", kept),
+            }
+        );
+        if confirmation.confirm(&question)? {
+            cancelled(&progress, total)?;
+
+            let (user, ingest): (Vec<&ApiKey>, Vec<&ApiKey>) =
+                unused.iter().copied().partition(|k| k.is_user_key());
+            let ids = |keys: &[&ApiKey]| keys.iter().map(|k| k.id.clone()).collect::<Vec<_>>();
+            let outcome = inventory::delete_keys(client, &ids(&ingest), &ids(&user)).await?;
+
+            for entry in entries.iter_mut().filter(|entry| entry.known_unused) {
+                entry.deleted = outcome.deleted.contains(&entry.id);
+                if !entry.deleted {
+                    entry.error = Some(if outcome.errors.is_empty() {
+                        "not reported as deleted".to_string()
+                    } else {
+                        outcome.errors.join(", ")
+                    });
+                }
+            }
+            println!(
+                "Deleted {} of {} key(s)",
+                outcome.deleted.len(),
+                unused.len()
+            );
+            for error in &outcome.errors {
+                println!("  error: {}", error);
+            }
+        } else {
+            println!("Nothing deleted");
+        }
+    }
+
+    discard_progress(&progress_path)?;
+
+    if delete || report_path.is_some() {
+        let default_path;
+        let path = match report_path {
+            Some(path) => path,
+            None => {
+                default_path = paths
+                    .reports_dir()
+                    .join(format!("cleanup-{}.json", now.format("%Y%m%dT%H%M%SZ")));
+                &default_path
+            }
+        };
+        let report = Report {
+            generated_at: now,
+            days,
+            entries,
+        };
+        config::write_private(path, &serde_json::to_string_pretty(&report)?)?;
+        println!("Report written to {}", path.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_created_at(created_at: Option<i64>) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": "k", "type": "INGEST",
+            "createdAt": created_at, "accountId": 1
+        }))
+    }
+
+    #[test]
+    fn test_old_enough() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let day = 86_400;
+
+        assert!(old_enough(
+            &key_created_at(Some(1_700_000_000 - 40 * day)),
+            now,
+            30
+        ));
+        assert!(!old_enough(
+            &key_created_at(Some(1_700_000_000 - 5 * day)),
+            now,
+            30
+        ));
+        assert!(old_enough(&key_created_at(None), now, 30));
+    }
+
+    #[test]
+    fn test_progress_must_match_options() {
+        let progress = Progress::new(90, &[2, 1]);
+        assert!(progress.ensure_matches(90, &[1, 2]).is_ok());
+        assert!(progress.ensure_matches(30, &[1, 2]).is_err());
+        assert!(progress.ensure_matches(90, &[1]).is_err());
+    }
+
+    #[test]
+    fn test_only_ingest_keys_without_account_ingest_are_known_unused() {
+        let key = |key_type: &str, ingest_type: &str| -> ApiKey {
+            ApiKey::fixture(serde_json::json!({
+                "id": "k", "type": key_type,
+                "ingestType": ingest_type, "accountId": 1
+            }))
+        };
+        let ingest = BTreeMap::from([((1, "LICENSE"), 12.5), ((1, "BROWSER"), 0.0)]);
+
+        let (unused, reason) = evidence(&key("INGEST", "LICENSE"), &ingest, 90);
+        assert!(!unused);
+        assert_eq!(
+            reason,
+            "account 1 ingested 12.50 GB of license data in the last 90 days"
+        );
+        assert!(evidence(&key("INGEST", "BROWSER"), &ingest, 90).0);
+        assert!(evidence(&key("INGEST", "LICENSE"), &BTreeMap::new(), 90).0);
+        assert!(!evidence(&key("USER", "LICENSE"), &BTreeMap::new(), 90).0);
+    }
+}
@@ -0,0 +1,747 @@
+//! The `newrelic-apikeys-cli` command line interface.
+
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::{
+    alias, cache, cancel, ci, commands, config, context, credentials, guardrails, hints, history,
+    middleware, output_file, paths, prompt, protect, schema::SchemaDrift, session, sink, time,
+    warnings, window, GraphQLErrors, NewRelicClient, RequestError, SecretString,
+};
+use commands::cleanup::CleanupCommands;
+use commands::decommission::DecommissionCommands;
+use commands::onboard::OnboardCommands;
+use commands::provision::ProvisionCommands;
+use commands::scheduler::SchedulerCommands;
+use commands::sweep::SweepCommands;
+use context::ExecutionContext;
+use prompt::Confirmation;
+use warnings::Code;
+
+#[derive(Parser)]
+#[command(name = "newrelic-apikeys-cli")]
+#[command(about = "A CLI tool for interacting with New Relic's Nerdgraph API")]
+#[command(version = "0.0.1")]
+struct Cli {
+    /// New Relic API key
+    #[arg(short, long, env = "NEW_RELIC_API_KEY", hide_env_values = true)]
+    api_key: Option<SecretString>,
+
+    /// New Relic API endpoint (default: https://api.newrelic.com/graphql)
+    #[arg(short, long, env = "NEW_RELIC_ENDPOINT")]
+    endpoint: Option<String>,
+
+    /// Output format: json, ndjson, psobject, table, csv, yaml or template (default: json)
+    #[arg(short, long)]
+    format: Option<String>,
+
+    /// Config profile to use (default: default_profile from the config file)
+    #[arg(short, long, env = "NEW_RELIC_PROFILE")]
+    profile: Option<String>,
+
+    /// Handlebars template for --format template (implied): inline, @file, or a name from
+    /// [templates] in the config
+    #[arg(long)]
+    template: Option<String>,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Warning format on stderr: text or json (one object per line)
+    #[arg(long, default_value = "text")]
+    warnings: String,
+
+    /// Never page long listings through $PAGER
+    #[arg(long)]
+    no_pager: bool,
+
+    /// Fail instead of warning when a NerdGraph response lacks fields the CLI depends on
+    #[arg(long)]
+    strict: bool,
+
+    /// Answer read commands from the responses cached by earlier runs instead of the network
+    #[arg(long)]
+    offline: bool,
+
+    /// Change keys even in accounts whose maintenance window is closed
+    #[arg(long)]
+    override_window: bool,
+
+    /// Delete or rotate keys even if they are protected
+    #[arg(long)]
+    allow_protected: bool,
+
+    /// Refuse every command that changes keys, as `read_only` in the config does
+    #[arg(long, env = "NEW_RELIC_READ_ONLY")]
+    read_only: bool,
+
+    /// Hand created and rotated keys to a CI system and keep secrets out of the job log:
+    /// github, gitlab (dotenv file) or jenkins (properties file)
+    #[arg(long)]
+    ci: Option<String>,
+
+    /// Where --ci gitlab or jenkins writes the key variables (default: newrelic-apikeys.env or
+    /// newrelic-apikeys.properties)
+    #[arg(long, requires = "ci")]
+    ci_file: Option<PathBuf>,
+
+    /// Shorthand for --ci github
+    #[arg(long, conflicts_with = "ci")]
+    github_output: bool,
+
+    /// Store the secrets of created and rotated keys here instead of printing them: keyring,
+    /// file:<path> or vault:<mount>/<path>
+    #[arg(long, value_name = "SINK")]
+    secret_sink: Option<String>,
+
+    /// Write stdout to this file instead, created atomically and readable only by you
+    #[arg(long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// Replace the --output-file if it exists
+    #[arg(long, requires = "output_file")]
+    force: bool,
+
+    /// Stop bulk work gracefully after this long, e.g. 90s, 2m or 1h30m
+    #[arg(long, value_parser = cancel::parse_duration)]
+    deadline: Option<std::time::Duration>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Query API keys
+    Query(commands::query::Args),
+    /// Run a raw NerdGraph query or mutation and print its data as JSON
+    Graphql(commands::graphql::Args),
+    /// List the keys in one or more accounts
+    List(commands::list::Args),
+    /// Find keys by approximate name or ID, best matches first
+    Find(commands::find::Args),
+    /// Create a new API key
+    Create(commands::create::Args),
+    /// Update an API key, or every key that matches --selector, --where or --filter-name
+    Update(commands::update::Args),
+    /// Rename every key whose name matches a regular expression, e.g. after a team rename
+    Rename(commands::rename::Args),
+    /// Delete an API key, or every key that matches --selector, --where or --filter-name
+    Delete(commands::delete::Args),
+    /// Create a key like an existing one (same type, name and notes, new secret) in another
+    /// account, or one like every key that matches --selector, --where or --filter-name
+    Clone(commands::clone::Args),
+    /// Replace a key with a new one of the same type, account, name and notes
+    Rotate(commands::rotate::Args),
+    /// Move a user key to another user: create it again under --to-user with the new owner in
+    /// its notes, then delete the old key
+    Transfer(commands::transfer::Args),
+    /// Protect keys from being deleted or rotated, or list the protected keys
+    #[command(subcommand)]
+    Protect(commands::protect::ProtectCommands),
+    /// Run, list or cancel the deletions queued by `rotate --delete-after`
+    #[command(subcommand)]
+    Scheduler(commands::scheduler::SchedulerCommands),
+    /// Show the authenticated user, their roles and whether they can read API keys
+    Whoami,
+    /// Show when a key last changed and last appeared in an ingest error (not when it was last
+    /// used: New Relic does not meter use per key)
+    Usage(commands::usage::Args),
+    /// Check connectivity, TLS, proxy settings and credentials
+    Doctor,
+    /// Interactively create a profile: region, API key (stored in the keyring) and default account
+    Init,
+    /// Authentication helpers
+    #[command(subcommand)]
+    Auth(commands::auth::AuthCommands),
+    /// Inspect and manage CLI configuration
+    #[command(subcommand)]
+    Config(commands::config::ConfigCommands),
+    /// Show or repeat previous invocations
+    #[command(subcommand)]
+    History(commands::history::HistoryCommands),
+    /// Show who created, changed or deleted API keys, from NrAuditEvent
+    AuditEvents(commands::audit_events::Args),
+    /// Check every key against the hygiene rules and list the findings
+    Audit(commands::audit::Args),
+    /// Check the local command history for tampering
+    #[command(subcommand)]
+    AuditLog(commands::audit_log::AuditLogCommands),
+    /// Generate reports about the keys in one or more accounts
+    #[command(subcommand)]
+    Report(commands::report::ReportCommands),
+    /// Enforce the `policies` from the config file
+    #[command(subcommand)]
+    Policy(commands::policy::PolicyCommands),
+    /// Print the fingerprint of a key secret, or of every key in a file, without the secret
+    Fingerprint(commands::fingerprint::Args),
+    /// Find New Relic keys hardcoded in files and report which are live keys
+    Scan(commands::scan::Args),
+    /// Manage the git pre-commit hook that blocks commits containing live keys
+    #[command(subcommand)]
+    Hooks(commands::hooks::HooksCommands),
+    /// Compare keys with other sources of truth
+    #[command(subcommand)]
+    Check(commands::check::CheckCommands),
+    /// Compare the keys declared in a manifest with the live keys, e.g. in pull request pipelines
+    #[command(subcommand)]
+    Drift(commands::drift::DriftCommands),
+    /// Save inventory snapshots and compare the current keys with them
+    #[command(subcommand)]
+    Snapshot(commands::snapshot::SnapshotCommands),
+    /// Print infrastructure-as-code definitions for existing keys, e.g. Terraform resources
+    Export(commands::export::Args),
+    /// Recreate the keys of an `export --format json` file that no longer exist
+    Import(commands::import::Args),
+    /// Create the standard keys of a new environment
+    #[command(subcommand)]
+    Provision(commands::provision::ProvisionCommands),
+    /// Create the key layout of a blueprint in a new account; running it again only adds what
+    /// is missing
+    Bootstrap(commands::bootstrap::Args),
+    /// Set up a managed customer's account with the standard keys
+    #[command(subcommand)]
+    Onboard(commands::onboard::OnboardCommands),
+    /// Delete the keys of an environment that is being torn down
+    #[command(subcommand)]
+    Decommission(commands::decommission::DecommissionCommands),
+    /// Keep collecting the key inventory and expose it as Prometheus metrics
+    Daemon(commands::daemon::Args),
+    /// Serve list/create/rotate/delete as an authenticated HTTP JSON API
+    Serve(commands::serve::Args),
+    /// Serve the key operations over gRPC (see proto/apikeys.proto)
+    #[cfg(feature = "grpc")]
+    Grpc(commands::grpc::Args),
+    /// Run a Model Context Protocol server on stdio so AI assistants can inventory keys
+    Mcp(commands::mcp::Args),
+    /// Check every built-in query and mutation against the bundled NerdGraph schema snapshot
+    #[command(hide = true)]
+    ValidateQueries,
+    /// Measure list, create and delete throughput against an in-memory NerdGraph
+    #[command(hide = true)]
+    Bench(commands::bench::Args),
+    /// Generate release metadata: completions, man pages, Homebrew formula and Scoop manifest
+    #[cfg(feature = "package")]
+    #[command(hide = true)]
+    #[command(subcommand)]
+    Package(commands::package::PackageCommands),
+    /// Find and remove keys that are no longer used
+    #[command(subcommand)]
+    Cleanup(commands::cleanup::CleanupCommands),
+    /// Revoke keys as part of offboarding
+    #[command(subcommand)]
+    Sweep(commands::sweep::SweepCommands),
+}
+
+/// Entry point of the `newrelic-apikeys-cli` binary.
+pub async fn main() -> anyhow::Result<()> {
+    let paths = paths::Paths::discover()?;
+    match paths.migrate_legacy() {
+        Ok(Some(legacy_dir)) => eprintln!(
+            "Migrated files from {} to the platform config/data directories",
+            legacy_dir.display()
+        ),
+        Ok(None) => {}
+        Err(e) => warnings::warn(
+            Code::Config,
+            format!("unable to migrate legacy files: {}", e),
+        ),
+    }
+
+    let loaded_config = config::Config::load(&paths.config_file());
+    let args = match &loaded_config {
+        Ok(config) => alias::expand::<Cli>(std::env::args_os().collect(), &config.alias)?,
+        Err(_) => std::env::args_os().collect(),
+    };
+    let matches = Cli::command().get_matches_from(&args);
+    let command = command_path(&matches);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    warnings::set_format(&cli.warnings)?;
+
+    let record = !matches!(cli.command, Commands::History { .. });
+    let signing_key = match loaded_config
+        .as_ref()
+        .ok()
+        .and_then(|c| c.audit_log.signing_key.as_deref())
+    {
+        Some(key) => match history::load_signing_key(&config::resolve_path(key, &paths.config_dir))
+        {
+            Ok(key) => Some(key),
+            Err(e) => {
+                warnings::warn(
+                    Code::History,
+                    format!("history entries will not be signed: {}", e),
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    let verbose = cli.verbose;
+    let stats = middleware::Stats::default();
+    let cache = cache::ResponseCache::new(paths.responses_dir(), cli.offline);
+    let output_file = cli
+        .output_file
+        .as_deref()
+        .map(|path| output_file::OutputFile::create(path, cli.force))
+        .transpose()?;
+    let result = run(cli, &command, &paths, loaded_config, &stats, &cache).await;
+    let result = match output_file {
+        Some(file) => file.finish(result),
+        None => result,
+    };
+    if let Some(fetched_at) = cache.oldest_hit() {
+        warnings::warn(
+            Code::StaleData,
+            format!(
+                "offline: showing cached data from {} ({}); it may be out of date",
+                fetched_at.format("%Y-%m-%d %H:%M UTC"),
+                time::ago(fetched_at, chrono::Utc::now())
+            ),
+        );
+    }
+    if verbose && stats.requests() > 0 {
+        eprintln!(
+            "NerdGraph calls: {} ({} failed)",
+            stats.requests(),
+            stats.failures()
+        );
+    }
+    if record {
+        if let Err(e) = history::record(
+            &paths.history_file(),
+            &args[1..],
+            &command,
+            &global_value_options(),
+            &result,
+            signing_key.as_ref(),
+        ) {
+            warnings::warn(Code::History, format!("unable to record history: {}", e));
+        }
+    }
+    if let Err(e) = &result {
+        let annotated = graphql_errors(e)
+            .map(|errors| errors.annotate())
+            .filter(|annotated| !annotated.is_empty());
+        let hint = hints::hint(e);
+        if annotated.is_some() || hint.is_some() {
+            // Same rendering as returning the error from `main`, with details underneath.
+            eprintln!("Error: {:?}", e);
+            if let Some(annotated) = annotated {
+                eprint!("\n{}", annotated);
+            }
+            if let Some(hint) = hint {
+                eprintln!("\nhint: {}", hint);
+            }
+            std::process::exit(1);
+        }
+    }
+    result
+}
+
+/// `--template` text: a `[templates]` entry by that name, the contents of `@file`, or the value
+/// itself.
+fn resolve_template(value: &str, config: &config::Config) -> anyhow::Result<String> {
+    if let Some(template) = config.templates.get(value) {
+        return Ok(template.clone());
+    }
+    match value.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Unable to read template {}: {}", path, e)),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// The GraphQL errors behind a failed request, if that is what failed.
+fn graphql_errors(error: &anyhow::Error) -> Option<&GraphQLErrors> {
+    error
+        .downcast_ref::<RequestError>()
+        .and_then(|e| e.error.downcast_ref::<GraphQLErrors>())
+        .or_else(|| error.downcast_ref::<GraphQLErrors>())
+}
+
+/// Whether `command` creates, changes or deletes keys. Dry runs do not; commands that only
+/// sometimes send mutations, such as `graphql` and the servers, are stopped by
+/// [`middleware::ReadOnly`] instead.
+fn changes_keys(command: &Commands) -> bool {
+    match command {
+        Commands::Create(_) | Commands::Delete(_) | Commands::Rotate(_) => true,
+        Commands::Update(_)
+        | Commands::Rename(_)
+        | Commands::Clone(_)
+        | Commands::Transfer(_)
+        | Commands::Bootstrap(_)
+        | Commands::Import(_)
+        | Commands::Provision(ProvisionCommands::Env { .. })
+        | Commands::Onboard(OnboardCommands::Customer { .. })
+        | Commands::Decommission(DecommissionCommands::Env { .. })
+        | Commands::Scheduler(SchedulerCommands::Run { .. })
+        | Commands::Sweep(SweepCommands::DepartedUsers { .. }) => !dry_run(command),
+        Commands::Cleanup(CleanupCommands::Stale { delete, .. }) => *delete,
+        _ => false,
+    }
+}
+
+/// Whether `command` was asked only to report what it would change (`--dry-run`).
+fn dry_run(command: &Commands) -> bool {
+    match command {
+        Commands::Update(commands::update::Args { dry_run, .. })
+        | Commands::Rename(commands::rename::Args { dry_run, .. })
+        | Commands::Clone(commands::clone::Args { dry_run, .. })
+        | Commands::Transfer(commands::transfer::Args { dry_run, .. })
+        | Commands::Bootstrap(commands::bootstrap::Args { dry_run, .. })
+        | Commands::Import(commands::import::Args { dry_run, .. })
+        | Commands::Provision(ProvisionCommands::Env { dry_run, .. })
+        | Commands::Onboard(OnboardCommands::Customer { dry_run, .. })
+        | Commands::Decommission(DecommissionCommands::Env { dry_run, .. })
+        | Commands::Scheduler(SchedulerCommands::Run { dry_run })
+        | Commands::Sweep(SweepCommands::DepartedUsers { dry_run, .. }) => *dry_run,
+        _ => false,
+    }
+}
+
+/// Whether `command` asks before destructive steps or was given `--yes`.
+fn confirmation(command: &Commands) -> Confirmation {
+    let yes = match command {
+        Commands::Update(commands::update::Args { yes, .. })
+        | Commands::Rename(commands::rename::Args { yes, .. })
+        | Commands::Clone(commands::clone::Args { yes, .. })
+        | Commands::Transfer(commands::transfer::Args { yes, .. })
+        | Commands::Bootstrap(commands::bootstrap::Args { yes, .. })
+        | Commands::Delete(commands::delete::Args { yes, .. })
+        | Commands::Rotate(commands::rotate::Args { yes, .. })
+        | Commands::Scan(commands::scan::Args { yes, .. })
+        | Commands::Import(commands::import::Args { yes, .. })
+        | Commands::Provision(ProvisionCommands::Env { yes, .. })
+        | Commands::Onboard(OnboardCommands::Customer { yes, .. })
+        | Commands::Decommission(DecommissionCommands::Env { yes, .. })
+        | Commands::Cleanup(CleanupCommands::Stale { yes, .. })
+        | Commands::Sweep(SweepCommands::DepartedUsers { yes, .. }) => *yes,
+        _ => false,
+    };
+    Confirmation::assume(yes)
+}
+
+/// The global options that take a value, long and short, e.g. `--profile` and `-p`.
+fn global_value_options() -> Vec<String> {
+    Cli::command()
+        .get_arguments()
+        .filter(|arg| arg.get_action().takes_values())
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{}", long));
+            let short = arg.get_short().map(|short| format!("-{}", short));
+            long.into_iter().chain(short)
+        })
+        .collect()
+}
+
+/// The subcommands of an invocation, e.g. `scheduler run`.
+fn command_path(matches: &clap::ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut current = matches;
+    while let Some((name, sub)) = current.subcommand() {
+        names.push(name);
+        current = sub;
+    }
+    names.join(" ")
+}
+
+async fn run(
+    cli: Cli,
+    command: &str,
+    paths: &paths::Paths,
+    loaded_config: anyhow::Result<config::Config>,
+    stats: &middleware::Stats,
+    cache: &cache::ResponseCache,
+) -> anyhow::Result<()> {
+    let config = match loaded_config {
+        Ok(config) => config,
+        Err(e)
+            if matches!(
+                cli.command,
+                Commands::Config { .. } | Commands::Doctor | Commands::Init
+            ) =>
+        {
+            warnings::warn(Code::Config, &e);
+            config::Config::default()
+        }
+        Err(e) => return Err(e),
+    };
+    let guard = match window::Guard::new(&config) {
+        _ if cli.override_window => None,
+        Ok(guard) => guard,
+        Err(e)
+            if matches!(
+                cli.command,
+                Commands::Config { .. } | Commands::Doctor | Commands::Init
+            ) =>
+        {
+            warnings::warn(Code::Config, &e);
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    let limits = guardrails::Limits::new(&config.guardrails)?;
+    let protection = if cli.allow_protected {
+        None
+    } else {
+        protect::Protection::new(&config, &paths.protected_file())?
+    };
+    // Commands that mutate keys finish their current step on Ctrl-C instead of aborting.
+    let cancellation = if matches!(
+        cli.command,
+        Commands::Create { .. }
+            | Commands::Update { .. }
+            | Commands::Rename { .. }
+            | Commands::Clone { .. }
+            | Commands::Transfer { .. }
+            | Commands::Bootstrap { .. }
+            | Commands::Delete { .. }
+            | Commands::Rotate { .. }
+            | Commands::Cleanup { .. }
+            | Commands::Sweep { .. }
+    ) {
+        cancel::Cancellation::install(cli.deadline)
+    } else {
+        cancel::Cancellation::default()
+    };
+    let profile = config.profile(cli.profile.as_deref())?;
+    if let (Some(profile), Some(name)) = (
+        profile,
+        cli.profile.as_deref().or(config.default_profile.as_deref()),
+    ) {
+        profile.check_command(name, command)?;
+    }
+    let read_only = cli.read_only || config.read_only || profile.is_some_and(|p| p.read_only);
+    if read_only && changes_keys(&cli.command) {
+        anyhow::bail!(
+            "This command changes keys and read-only mode is on (--read-only or read_only in \
+             the config)"
+        );
+    }
+    let endpoint = cli.endpoint.unwrap_or_else(|| config.endpoint(profile));
+    let format = cli.format.unwrap_or_else(|| config.format(profile));
+    let profile_name = cli.profile.as_deref().or(config.default_profile.as_deref());
+    let explicit_api_key = cli.api_key.is_some();
+    let mut api_key = cli
+        .api_key
+        .or_else(|| profile.and_then(|p| p.api_key.clone()));
+    if let (None, Some(name)) = (&api_key, profile_name) {
+        match credentials::load_api_key(name) {
+            Ok(stored) => api_key = stored,
+            Err(e) if cli.verbose => eprintln!("Keyring lookup failed: {}", e),
+            Err(_) => {}
+        }
+    }
+    // A profile that signs in with session tokens uses them unless a key is given explicitly.
+    let mut session_error = None;
+    let session_token = match (profile.and_then(|p| p.session.as_ref()), profile_name) {
+        (Some(session), Some(name)) if !explicit_api_key => {
+            match session::token(session, &paths.session_file(name)).await {
+                Ok(token) => Some(token),
+                Err(e) => {
+                    session_error = Some(e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let template = cli
+        .template
+        .as_deref()
+        .map(|template| resolve_template(template, &config))
+        .transpose()?;
+    let ci = match (cli.ci.as_deref(), cli.github_output) {
+        (Some(system), _) => Some(ci::Ci::parse(system, cli.ci_file.clone())?),
+        (None, true) => Some(ci::Ci::parse("github", None)?),
+        (None, false) => None,
+    };
+    let secrets = sink::Secrets {
+        ci,
+        sink: cli
+            .secret_sink
+            .as_deref()
+            .map(sink::Sink::parse)
+            .transpose()?,
+        strict: sink::Secrets::strict_from(config.strict_secrets),
+    };
+    if cli.verbose {
+        eprintln!("Using endpoint: {}", endpoint);
+        eprintln!("Output format: {}", format);
+    }
+
+    // Checks on mutations, outermost so that nothing is sent or cached for a refused one.
+    let vetted = |mut builder: crate::NewRelicClientBuilder| {
+        if read_only {
+            builder = builder.middleware(middleware::ReadOnly);
+        }
+        if let Some(guard) = &guard {
+            builder = builder.middleware(guard.clone());
+        }
+        if let Some(protection) = &protection {
+            builder = builder.middleware(protection.clone());
+        }
+        if let Some(limits) = &limits {
+            builder = builder.middleware(limits.clone());
+        }
+        builder
+    };
+    let builder = match (session_token, api_key) {
+        (Some(token), _) => Some(NewRelicClient::builder().session_token(token)),
+        (None, Some(api_key)) => Some(NewRelicClient::builder().api_key(api_key)),
+        (None, None) => None,
+    };
+    let client = builder
+        .map(|builder| {
+            vetted(builder)
+                .endpoint(endpoint.clone())
+                .verbose(cli.verbose)
+                .middleware(cache.clone())
+                .middleware(stats.clone())
+                .strict_schema(cli.strict)
+                .on_schema_drift(|drift: &SchemaDrift| warnings::warn(Code::SchemaDrift, drift))
+                .build()
+        })
+        .transpose()?;
+    let first_run = !paths.config_file().exists();
+    let client = client.ok_or_else(|| {
+        if let Some(e) = &session_error {
+            format!("Unable to get a session token: {:#}", e)
+        } else if first_run {
+            "Missing API key: run `newrelic-apikeys-cli init` to set up a profile, or pass \
+             --api-key / set NEW_RELIC_API_KEY"
+                .to_string()
+        } else {
+            "Missing API key: pass --api-key, set NEW_RELIC_API_KEY or add api_key to a profile"
+                .to_string()
+        }
+    });
+    let mut ctx = ExecutionContext {
+        client,
+        config: &config,
+        profile,
+        paths,
+        format,
+        template,
+        dry_run: dry_run(&cli.command),
+        confirmation: confirmation(&cli.command),
+        secrets,
+    };
+
+    let profile_client = |api_key: SecretString, endpoint: String| {
+        vetted(NewRelicClient::builder().api_key(api_key))
+            .endpoint(endpoint)
+            .verbose(cli.verbose)
+            .middleware(cache.clone())
+            .middleware(stats.clone())
+            .strict_schema(cli.strict)
+            .on_schema_drift(|drift: &SchemaDrift| warnings::warn(Code::SchemaDrift, drift))
+            .build()
+    };
+    let globals = commands::Globals {
+        pager: !cli.no_pager,
+        profile: cli.profile.as_deref(),
+        secret_sink: cli.secret_sink.as_deref(),
+        endpoint: &endpoint,
+        protection: protection.as_ref(),
+        cancellation: &cancellation,
+        profile_client: &profile_client,
+    };
+
+    match cli.command {
+        Commands::Query(args) => commands::query::run(args, &ctx).await,
+        Commands::Graphql(args) => commands::graphql::run(args, &ctx).await,
+        Commands::List(args) => commands::list::run(args, &ctx, &globals).await,
+        Commands::Find(args) => commands::find::run(args, &ctx, &globals).await,
+        Commands::Create(args) => commands::create::run(args, &ctx).await,
+        Commands::Update(args) => commands::update::run(args, &ctx).await,
+        Commands::Rename(args) => commands::rename::run(args, &ctx).await,
+        Commands::Delete(args) => commands::delete::run(args, &ctx).await,
+        Commands::Clone(args) => commands::clone::run(args, &ctx).await,
+        Commands::Rotate(args) => commands::rotate::run(args, &ctx, &globals).await,
+        Commands::Transfer(args) => commands::transfer::run(args, &ctx, &globals).await,
+        Commands::Protect(command) => commands::protect::run(command, &ctx).await,
+        Commands::Scheduler(command) => commands::scheduler::run(command, &ctx).await,
+        Commands::Whoami => commands::whoami::run(&ctx).await,
+        Commands::Usage(args) => commands::usage::run(args, &ctx).await,
+        Commands::Doctor => commands::doctor::run(&ctx, &globals).await,
+        Commands::Init => commands::init::run(&ctx).await,
+        Commands::Auth(command) => commands::auth::run(command, &ctx).await,
+        Commands::Config(command) => commands::config::run(command, &ctx),
+        Commands::History(command) => commands::history::run(command, &ctx),
+        Commands::AuditEvents(args) => commands::audit_events::run(args, &ctx).await,
+        Commands::Audit(args) => commands::audit::run(args, &ctx).await,
+        Commands::AuditLog(command) => commands::audit_log::run(command, &ctx).await,
+        Commands::Report(command) => commands::report::run(command, &ctx, &globals).await,
+        Commands::Policy(command) => commands::policy::run(command, &ctx).await,
+        Commands::Fingerprint(args) => commands::fingerprint::run(args, &ctx),
+        Commands::Scan(args) => commands::scan::run(args, &ctx).await,
+        Commands::Hooks(command) => commands::hooks::run(command, &globals),
+        Commands::Check(command) => commands::check::run(command, &ctx).await,
+        Commands::Drift(command) => commands::drift::run(command, &ctx).await,
+        Commands::Snapshot(command) => commands::snapshot::run(command, &ctx).await,
+        Commands::Export(args) => commands::export::run(args, &ctx, &globals).await,
+        Commands::Import(args) => commands::import::run(args, &ctx).await,
+        Commands::Provision(command) => commands::provision::run(command, &ctx).await,
+        Commands::Bootstrap(args) => commands::bootstrap::run(args, &mut ctx, &globals).await,
+        Commands::Onboard(command) => commands::onboard::run(command, &mut ctx, &globals).await,
+        Commands::Decommission(command) => commands::decommission::run(command, &ctx).await,
+        Commands::Daemon(args) => commands::daemon::run(args, &ctx).await,
+        Commands::Serve(args) => commands::serve::run(args, &ctx).await,
+        #[cfg(feature = "grpc")]
+        Commands::Grpc(args) => commands::grpc::run(args, &ctx).await,
+        Commands::Mcp(args) => commands::mcp::run(args, &ctx).await,
+        Commands::ValidateQueries => commands::validate_queries::run(),
+        Commands::Bench(args) => commands::bench::run(args, &ctx).await,
+        #[cfg(feature = "package")]
+        Commands::Package(command) => commands::package::run(command, Cli::command()),
+        Commands::Cleanup(command) => commands::cleanup::run(command, &ctx, &globals).await,
+        Commands::Sweep(command) => commands::sweep::run(command, &ctx).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_key_id_filtering() {
+        // Test data with multiple keys
+        let test_data = serde_json::json!({
+            "actor": {
+                "apiAccess": {
+                    "keys": [
+                        {
+                            "id": "key-123",
+                            "name": "First Key",
+                            "type": "USER",
+                            "notes": "First key notes"
+                        },
+                        {
+                            "id": "key-456",
+                            "name": "Second Key",
+                            "type": "INGEST",
+                            "notes": "Second key notes"
+                        }
+                    ]
+                }
+            }
+        });
+
+        let keys = test_data["actor"]["apiAccess"]["keys"].as_array().unwrap();
+        let mut filtered_keys = keys.clone();
+
+        // Filter by key ID
+        let key_id_filter = "key-123";
+        filtered_keys.retain(|key| {
+            key.get("id")
+                .and_then(|id| id.as_str())
+                .is_some_and(|id| id == key_id_filter)
+        });
+
+        assert_eq!(filtered_keys.len(), 1);
+        assert_eq!(filtered_keys[0]["id"], "key-123");
+        assert_eq!(filtered_keys[0]["name"], "First Key");
+    }
+}
@@ -0,0 +1,116 @@
+//! `clone`: create keys like existing ones in another account, e.g. when an environment is
+//! copied into a new sub-account. A clone has the original's type, name, notes and ingest type,
+//! and its own secret.
+
+use serde::Serialize;
+
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::NewRelicClient;
+
+/// The key to create for `key` in `account_id`. User keys belong to `user_id` if given, and to
+/// the original's owner otherwise.
+pub fn plan(key: &ApiKey, account_id: i64, user_id: Option<i64>) -> anyhow::Result<NewKey> {
+    let mut spec = NewKey::replacing(key)?;
+    if spec.account_id == account_id {
+        anyhow::bail!("Key {} is already in account {}", key.id, account_id);
+    }
+    spec.account_id = account_id;
+    if key.is_user_key() {
+        spec.user_id = user_id.or(spec.user_id);
+    }
+    Ok(spec)
+}
+
+#[derive(Serialize)]
+pub struct ClonedKey {
+    pub source_key_id: String,
+    /// The new key, including its secret
+    pub key: ApiKey,
+}
+
+#[derive(Serialize)]
+pub struct FailedClone {
+    pub key_id: String,
+    pub error: String,
+}
+
+/// Outcome of cloning several keys; every key ends up in exactly one list.
+#[derive(Serialize, Default)]
+pub struct BulkClone {
+    pub cloned: Vec<ClonedKey>,
+    pub failed: Vec<FailedClone>,
+}
+
+/// Create the planned keys one by one, collecting failures rather than stopping at the first,
+/// so the secrets of the keys that were created are never lost, and hand the new keys to
+/// `store`. If `store` fails, the new keys are deleted again and reported as failed.
+pub async fn clone_all(
+    client: &NewRelicClient,
+    planned: &[(String, NewKey)],
+    store: impl AsyncFn(&[&ApiKey]) -> anyhow::Result<()>,
+) -> BulkClone {
+    let mut bulk = BulkClone::default();
+    for (source_key_id, spec) in planned {
+        match inventory::create(client, spec).await {
+            Ok(key) => bulk.cloned.push(ClonedKey {
+                source_key_id: source_key_id.clone(),
+                key,
+            }),
+            Err(e) => bulk.failed.push(FailedClone {
+                key_id: source_key_id.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    let keys: Vec<&ApiKey> = bulk.cloned.iter().map(|cloned| &cloned.key).collect();
+    let Err(e) = store(&keys).await else {
+        return bulk;
+    };
+    let (user, ingest): (Vec<&ApiKey>, Vec<&ApiKey>) = keys.iter().partition(|k| k.is_user_key());
+    let ids = |keys: &[&ApiKey]| keys.iter().map(|k| k.id.clone()).collect::<Vec<_>>();
+    let deleted = inventory::delete_keys(client, &ids(&ingest), &ids(&user))
+        .await
+        .map(|outcome| outcome.deleted)
+        .unwrap_or_default();
+    for cloned in std::mem::take(&mut bulk.cloned) {
+        let outcome = if deleted.contains(&cloned.key.id) {
+            "deleted it again".to_string()
+        } else {
+            format!("could not delete {}, delete it by hand", cloned.key.id)
+        };
+        bulk.failed.push(FailedClone {
+            key_id: cloned.source_key_id,
+            error: format!(
+                "Could not store the secret of {}: {:#}; {}",
+                cloned.key.id, e, outcome
+            ),
+        });
+    }
+    bulk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_type: &str) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": "K1", "name": "deploy", "notes": "team:payments", "type": key_type, "accountId": 123, "ingestType": "BROWSER", "userId": 5
+        }))
+    }
+
+    #[test]
+    fn test_plan_keeps_metadata_in_the_new_account() {
+        let spec = plan(&key("INGEST"), 456, Some(9)).unwrap();
+        assert_eq!(spec.account_id, 456);
+        assert_eq!(spec.name, "deploy");
+        assert_eq!(spec.notes.as_deref(), Some("team:payments"));
+        assert_eq!(spec.ingest_type.as_deref(), Some("BROWSER"));
+
+        let spec = plan(&key("USER"), 456, Some(9)).unwrap();
+        assert_eq!(spec.user_id, Some(9));
+        assert_eq!(plan(&key("USER"), 456, None).unwrap().user_id, Some(5));
+
+        assert!(plan(&key("INGEST"), 123, None).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! `audit`: check every key against the hygiene rules and list the findings.
+
+use std::path::{Path, PathBuf};
+
+use super::FilterArgs;
+use crate::audit;
+use crate::context::ExecutionContext;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    #[command(flatten)]
+    pub check: PolicyArgs,
+}
+
+/// Accounts and report file shared by `audit` and `policy check`.
+#[derive(clap::Args)]
+pub(crate) struct PolicyArgs {
+    /// Account to check (repeatable; default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Check every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Also write the results as a report to this file, e.g. junit.xml or audit.sarif
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
+    /// Report format: junit or sarif (default: sarif for *.sarif files, junit otherwise)
+    #[arg(long, requires = "report")]
+    pub report_format: Option<String>,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+impl PolicyArgs {
+    pub fn report(&self) -> anyhow::Result<Option<(&Path, audit::ReportFormat)>> {
+        let Some(path) = self.report.as_deref() else {
+            return Ok(None);
+        };
+        let format = match self.report_format.as_deref() {
+            Some(format) => audit::ReportFormat::parse(format)?,
+            None => audit::ReportFormat::from_path(path),
+        };
+        Ok(Some((path, format)))
+    }
+}
+
+/// Post the accounts over a key-count limit to `policies.webhook`, if one is set.
+pub(crate) async fn notify_breaches(
+    ctx: &ExecutionContext<'_>,
+    evaluation: &audit::Evaluation,
+) -> anyhow::Result<()> {
+    match &ctx.config.policies.webhook {
+        Some(url) if !evaluation.breaches.is_empty() => {
+            audit::notify(url, &evaluation.breaches).await
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args { check } = args;
+    let account_ids = ctx.account_ids(check.account_id.clone(), check.account_group.as_deref())?;
+    let evaluation = audit::run(
+        ctx.client()?,
+        &account_ids,
+        &check.filter.parse(ctx.config)?,
+        audit::audit_rules(&ctx.config.policies),
+        ctx.format()?,
+        check.report()?,
+        "audit",
+    )
+    .await?;
+    notify_breaches(ctx, &evaluation).await
+}
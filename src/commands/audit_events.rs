@@ -0,0 +1,68 @@
+//! `audit-events`: show who created, changed or deleted API keys, from NrAuditEvent.
+
+use crate::context::ExecutionContext;
+use crate::{audit_events, siem};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// How far back to look: 30m, 12h, 7d, 2w or an NRQL SINCE expression
+    #[arg(short, long, default_value = "7d")]
+    pub since: String,
+
+    /// Only events by this user (email address or user ID)
+    #[arg(long)]
+    pub actor: Option<String>,
+
+    /// Account to query (repeatable; default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Query every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Export events for a SIEM: cef or ocsf (one event per line)
+    #[arg(long)]
+    pub export: Option<String>,
+
+    /// Ship exported events instead of printing them: syslog://host[:port],
+    /// syslog+tcp://host[:port] or an http(s) URL
+    #[arg(long, requires = "export")]
+    pub ship: Option<String>,
+
+    /// Extra HTTP header for --ship, e.g. "Authorization: Splunk <token>" (repeatable)
+    #[arg(long = "ship-header", requires = "ship")]
+    pub ship_headers: Vec<String>,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        since,
+        actor,
+        account_id,
+        account_group,
+        export,
+        ship,
+        ship_headers,
+    } = args;
+    let export = export
+        .as_deref()
+        .map(siem::ExportFormat::parse)
+        .transpose()?;
+    let format = ctx.format()?;
+    let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+    let events = audit_events::fetch(ctx.client()?, &account_ids, &since, actor.as_deref()).await?;
+    match (export, ship) {
+        (Some(export), Some(url)) => {
+            let shipped = siem::ship(&events, export, &url, &ship_headers).await?;
+            println!("Shipped {} event(s) to {}", shipped, url);
+        }
+        (Some(export), None) => {
+            for event in &events {
+                println!("{}", export.render(event));
+            }
+        }
+        (None, _) => audit_events::print(&events, format)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! `audit-log`: check the local command history for tampering.
+
+use std::path::PathBuf;
+
+use crate::context::ExecutionContext;
+use crate::{config, history};
+
+#[derive(clap::Subcommand)]
+pub(crate) enum AuditLogCommands {
+    /// Verify the hash chain and, given a public key, the signature of every entry
+    Verify {
+        /// Minisign public key (default: audit_log.public_key from the config)
+        #[arg(long)]
+        public_key: Option<PathBuf>,
+    },
+}
+
+pub(crate) async fn run(
+    command: AuditLogCommands,
+    ctx: &ExecutionContext<'_>,
+) -> anyhow::Result<()> {
+    match command {
+        AuditLogCommands::Verify { public_key } => {
+            let public_key = public_key.or_else(|| {
+                ctx.config
+                    .audit_log
+                    .public_key
+                    .as_deref()
+                    .map(|key| config::resolve_path(key, &ctx.paths.config_dir))
+            });
+            history::verify(&ctx.paths.history_file(), public_key.as_deref())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! `auth`: authentication helpers.
+
+use super::whoami::print_identity;
+use crate::context::ExecutionContext;
+use crate::{fetch_identity, key_type_from_prefix, NewRelicClient};
+
+#[derive(clap::Subcommand)]
+pub(crate) enum AuthCommands {
+    /// Verify the API key and show which user, organization and accounts it belongs to
+    Verify,
+}
+
+async fn verify_credentials(client: &NewRelicClient) -> anyhow::Result<()> {
+    let identity = fetch_identity(client).await?;
+
+    println!("API key is valid");
+    println!("Key type: {}", key_type_from_prefix(client.api_key()));
+    print_identity(&identity);
+    println!("Accessible accounts ({}):", identity.actor.accounts.len());
+    for account in &identity.actor.accounts {
+        println!("  {} - {}", account.id, account.name);
+    }
+    Ok(())
+}
+
+pub(crate) async fn run(command: AuthCommands, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    match command {
+        AuthCommands::Verify => {
+            verify_credentials(ctx.client()?).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! `bench`: measure list, create and delete throughput against an in-memory NerdGraph.
+
+use crate::bench;
+use crate::context::ExecutionContext;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Keys to list, create and delete in each run
+    #[arg(long, default_value_t = 1000)]
+    pub keys: usize,
+
+    /// Keys per keySearch page
+    #[arg(long, default_value_t = 100)]
+    pub page_size: usize,
+
+    /// Requests in flight at once; each level is measured (comma-separated)
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 4, 16])]
+    pub concurrency: Vec<usize>,
+
+    /// Keys per delete mutation; each size is measured (comma-separated)
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 10, 100])]
+    pub batch_size: Vec<usize>,
+
+    /// Simulated NerdGraph round trip in milliseconds
+    #[arg(long, default_value_t = 5)]
+    pub latency_ms: u64,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        keys,
+        page_size,
+        concurrency,
+        batch_size,
+        latency_ms,
+    } = args;
+    let options = bench::Options {
+        keys,
+        page_size,
+        concurrency,
+        batch_size,
+        latency: std::time::Duration::from_millis(latency_ms),
+    };
+    bench::run(&options, &ctx.format()?).await
+}
@@ -0,0 +1,65 @@
+//! `bootstrap`: create the key layout of a blueprint in a new account; running it again only adds
+//! what is missing.
+
+use std::path::PathBuf;
+
+use super::Globals;
+use crate::context::ExecutionContext;
+use crate::{blueprint, sink};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Blueprint file: keys, labels, secret sink and webhook (YAML, or JSON in builds
+    /// without the `yaml` feature)
+    #[arg(long, value_name = "FILE")]
+    pub blueprint: PathBuf,
+
+    /// The account to set up (default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Option<i64>,
+
+    /// Notify this webhook instead of the blueprint's
+    #[arg(long)]
+    pub webhook: Option<String>,
+
+    /// Only list what would be created and relabeled
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+pub(crate) async fn run(
+    args: Args,
+    ctx: &mut ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    let Args {
+        blueprint,
+        account_id,
+        webhook,
+        ..
+    } = args;
+    let blueprint = blueprint::Blueprint::load(&blueprint)?;
+    let account_id = ctx.account_id(account_id)?;
+    if globals.secret_sink.is_none() {
+        if let Some(sink) = &blueprint.secret_sink {
+            let sink = sink.replace("{account_id}", &account_id.to_string());
+            ctx.secrets.sink = Some(sink::Sink::parse(&sink)?);
+        }
+    }
+    blueprint::bootstrap(
+        ctx.client()?,
+        &blueprint,
+        account_id,
+        blueprint::Options {
+            dry_run: ctx.dry_run,
+            confirmation: ctx.confirmation,
+            webhook: webhook.as_deref(),
+        },
+        &ctx.secrets,
+    )
+    .await
+}
@@ -0,0 +1,42 @@
+//! `check`: compare keys with other sources of truth.
+
+use std::path::PathBuf;
+
+use crate::context::ExecutionContext;
+use crate::tfstate;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum CheckCommands {
+    /// Report keys deleted or edited outside Terraform/OpenTofu and keys it does not manage
+    TerraformState {
+        /// The state file, e.g. from `terraform state pull > terraform.tfstate`
+        #[arg(long)]
+        state_file: PathBuf,
+
+        /// Account to compare (repeatable; default: the accounts the state refers to)
+        #[arg(short, long)]
+        account_id: Vec<i64>,
+
+        /// Compare every account in this group from `account_groups` in the config
+        #[arg(short = 'g', long, conflicts_with = "account_id")]
+        account_group: Option<String>,
+    },
+}
+
+pub(crate) async fn run(command: CheckCommands, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    match command {
+        CheckCommands::TerraformState {
+            state_file,
+            account_id,
+            account_group,
+        } => {
+            let account_ids = if account_id.is_empty() && account_group.is_none() {
+                None
+            } else {
+                Some(ctx.account_ids(account_id, account_group.as_deref())?)
+            };
+            tfstate::check(ctx.client()?, &state_file, account_ids, ctx.format()?).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! `cleanup`: find and remove keys that are no longer used.
+
+use std::path::PathBuf;
+
+use super::{FilterArgs, Globals};
+use crate::cleanup;
+use crate::context::ExecutionContext;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum CleanupCommands {
+    /// List keys without changes or ingest errors in the last N days
+    Stale {
+        /// Days without changes or ingest errors before a key counts as stale
+        #[arg(long, default_value_t = 90)]
+        days: u32,
+
+        /// Account to scan (repeatable; default: account_id of the selected profile)
+        #[arg(short, long)]
+        account_id: Vec<i64>,
+
+        /// Scan every account in this group from `account_groups` in the config
+        #[arg(short = 'g', long, conflicts_with = "account_id")]
+        account_group: Option<String>,
+
+        /// Delete the stale keys known to be unused (ingest keys of accounts that ingested
+        /// nothing of their type) after confirmation
+        #[arg(long)]
+        delete: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long, requires = "delete")]
+        yes: bool,
+
+        /// Where to write the JSON report (default: the reports directory)
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        /// Continue a run that was cancelled by Ctrl-C or --deadline
+        #[arg(long)]
+        resume: bool,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+}
+
+pub(crate) async fn run(
+    command: CleanupCommands,
+    ctx: &ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    match command {
+        CleanupCommands::Stale {
+            days,
+            account_id,
+            account_group,
+            delete,
+            report,
+            resume,
+            filter,
+            ..
+        } => {
+            let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+            let filter = filter.parse(ctx.config)?;
+            let options = cleanup::StaleOptions {
+                days,
+                delete,
+                confirmation: ctx.confirmation,
+                resume,
+                report_path: report.as_deref(),
+                filter: &filter,
+            };
+            cleanup::stale(
+                ctx.client()?,
+                &account_ids,
+                options,
+                ctx.paths,
+                globals.cancellation,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
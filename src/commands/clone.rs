@@ -0,0 +1,127 @@
+//! `clone`: create a key like an existing one (same type, name and notes, new secret) in another
+//! account, or one like every key that matches --selector, --where or --filter-name.
+
+use super::{supported_key_type, FilterArgs};
+use crate::context::ExecutionContext;
+use crate::{cloning, filter, inventory};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Key ID (repeatable to clone several keys)
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["selector", "where", "filter_name"],
+        conflicts_with_all = ["selector", "where", "filter_name"]
+    )]
+    pub key_id: Vec<String>,
+
+    /// Key type (INGEST or USER)
+    #[arg(short = 't', long, default_value = "INGEST")]
+    pub key_type: String,
+
+    /// Account to create the new keys in
+    #[arg(long, value_name = "ACCOUNT_ID")]
+    pub to_account: i64,
+
+    /// Owner of new user keys (default: the owner of the original key)
+    #[arg(long)]
+    pub user_id: Option<i64>,
+
+    /// Account whose matching keys to clone (repeatable; default: account_id of the
+    /// selected profile)
+    #[arg(short, long, requires = "FilterArgs")]
+    pub account_id: Vec<i64>,
+
+    /// Clone the matching keys of every account in this group from `account_groups`
+    #[arg(
+        short = 'g',
+        long,
+        conflicts_with = "account_id",
+        requires = "FilterArgs"
+    )]
+    pub account_group: Option<String>,
+
+    /// Print the keys that would be created without creating them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        key_id,
+        key_type,
+        to_account,
+        user_id,
+        account_id,
+        account_group,
+        filter,
+        ..
+    } = args;
+    ctx.secrets.check()?;
+    let key_type = supported_key_type(&key_type)?;
+    let selected = key_id.is_empty();
+    let keys = if selected {
+        let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+        let filter = filter.parse(ctx.config)?;
+        filter::fetch(ctx.client()?, &account_ids, &[&key_type], &filter).await?
+    } else {
+        let mut keys = Vec::new();
+        for key_id in &key_id {
+            keys.push(inventory::get(ctx.client()?, key_id, &key_type).await?);
+        }
+        keys
+    };
+    if keys.is_empty() {
+        println!("No keys match");
+        return Ok(());
+    }
+    let mut planned = Vec::new();
+    for key in &keys {
+        let spec = cloning::plan(key, to_account, user_id)?;
+        eprintln!(
+            "  {}  {}  (account {} -> {})",
+            key.id,
+            spec.name,
+            key.account_id.unwrap_or_default(),
+            to_account
+        );
+        planned.push((key.id.clone(), spec));
+    }
+    if ctx.dry_run {
+        println!("Dry run: {} key(s) would be created", planned.len());
+        return Ok(());
+    }
+    let question = format!(
+        "Clone {} {} key(s) into account {}?",
+        planned.len(),
+        key_type,
+        to_account
+    );
+    if selected && !ctx.confirm(&question)? {
+        println!("Nothing changed");
+        return Ok(());
+    }
+    let bulk = cloning::clone_all(
+        ctx.client()?,
+        &planned,
+        async |keys: &[&inventory::ApiKey]| ctx.secrets.publish("Cloned API keys", keys).await,
+    )
+    .await;
+    let new_keys: Vec<&inventory::ApiKey> = bulk.cloned.iter().map(|cloned| &cloned.key).collect();
+    ctx.secrets.print(&bulk, &new_keys)?;
+    if !bulk.failed.is_empty() {
+        for failed in &bulk.failed {
+            eprintln!("  {}: {}", failed.key_id, failed.error);
+        }
+        anyhow::bail!("Cloned {} of {} key(s)", bulk.cloned.len(), planned.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! `config`: inspect and manage CLI configuration.
+
+use crate::config;
+use crate::context::ExecutionContext;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum ConfigCommands {
+    /// Print where the config file, cache and data live
+    Path,
+    /// Set a value, e.g. `config set profiles.prod.region eu`
+    Set {
+        /// Dotted config key
+        key: String,
+        /// Value, parsed as TOML when possible (numbers, booleans, arrays) and as a string otherwise
+        value: String,
+    },
+    /// Print a single value, with API keys and other secrets masked
+    Get {
+        /// Dotted config key
+        key: String,
+    },
+    /// Print every configured value, with API keys and other secrets masked
+    List,
+    /// Remove a value
+    Unset {
+        /// Dotted config key
+        key: String,
+    },
+    /// Open the config file in $VISUAL/$EDITOR, validating it before saving
+    Edit,
+}
+
+pub(crate) fn run(command: ConfigCommands, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let path = ctx.paths.config_file();
+    match command {
+        ConfigCommands::Path => ctx.paths.print(),
+        ConfigCommands::Set { key, value } => {
+            let mut file = config::ConfigFile::load(&path)?;
+            file.set(&key, &value)?;
+            file.save(&path)?;
+        }
+        ConfigCommands::Get { key } => {
+            let file = config::ConfigFile::load(&path)?;
+            let value = file
+                .get(&key)
+                .ok_or_else(|| anyhow::anyhow!("Config key '{}' is not set", key))?;
+            println!("{}", config::display_value(&config::masked(&key, value)));
+        }
+        ConfigCommands::List => {
+            let file = config::ConfigFile::load(&path)?;
+            for (key, value) in file.entries() {
+                let value = config::masked(&key, value);
+                println!("{} = {}", key, config::display_value(&value));
+            }
+        }
+        ConfigCommands::Unset { key } => {
+            let mut file = config::ConfigFile::load(&path)?;
+            file.unset(&key)?;
+            file.save(&path)?;
+        }
+        ConfigCommands::Edit => config::edit(&path)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+//! `create`: create a new API key.
+
+use crate::context::ExecutionContext;
+use crate::{fetch_identity, inventory, service_account};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Account ID (default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Option<i64>,
+
+    /// Key type
+    #[arg(short, long)]
+    pub key_type: String,
+
+    /// Key name
+    #[arg(short, long)]
+    pub name: String,
+
+    /// Key notes/description
+    #[arg(long)]
+    pub notes: Option<String>,
+
+    /// Ingest key kind, LICENSE or BROWSER
+    #[arg(long, default_value = "LICENSE")]
+    pub ingest_type: String,
+
+    /// Owner of a USER key (default: the user the API key belongs to)
+    #[arg(long)]
+    pub user_id: Option<i64>,
+
+    /// Own the USER key by this service account, creating the user if it does not exist
+    #[arg(long, value_name = "NAME", conflicts_with = "user_id")]
+    pub service_account: Option<String>,
+
+    /// Email of the service account (default: `email` from [service_accounts] in the config)
+    #[arg(long, requires = "service_account")]
+    pub service_account_email: Option<String>,
+
+    /// Authentication domain ID or name to create the service account in (default: the only
+    /// one)
+    #[arg(long, requires = "service_account")]
+    pub authentication_domain: Option<String>,
+
+    /// User tier of a new service account: basic, core or full (default: basic)
+    #[arg(long, requires = "service_account")]
+    pub user_tier: Option<String>,
+
+    /// Add the service account to this group (ID or name), creating the group if needed
+    #[arg(long, requires = "service_account")]
+    pub group: Option<String>,
+
+    /// Grant the --group this role (ID or name) on the key's account, e.g. "All Product
+    /// Admin"
+    #[arg(long, value_name = "ROLE", requires = "group")]
+    pub grant_role: Option<String>,
+}
+
+async fn create_api_key(
+    ctx: &ExecutionContext<'_>,
+    mut spec: inventory::NewKey,
+) -> anyhow::Result<()> {
+    let (client, secrets) = (ctx.client()?, &ctx.secrets);
+    secrets.check()?;
+    if spec.key_type.eq_ignore_ascii_case("USER") && spec.user_id.is_none() {
+        let identity = fetch_identity(client).await?;
+        spec.user_id = identity.actor.user.map(|user| user.id);
+    }
+    let key = inventory::create(client, &spec).await?;
+    secrets.publish("Created API key", &[&key]).await?;
+    secrets.print(&key, &[&key])
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        account_id,
+        key_type,
+        name,
+        notes,
+        ingest_type,
+        user_id,
+        service_account,
+        service_account_email,
+        authentication_domain,
+        user_tier,
+        group,
+        grant_role,
+    } = args;
+    let account_id = ctx.account_id(account_id)?;
+    let user_id = match service_account {
+        Some(account_name) => {
+            if !key_type.eq_ignore_ascii_case("USER") {
+                anyhow::bail!("--service-account owns USER keys; pass --key-type USER");
+            }
+            ctx.secrets.check()?;
+            let defaults = &ctx.config.service_accounts;
+            let email = match service_account_email {
+                Some(email) => email,
+                None => defaults
+                    .email
+                    .as_deref()
+                    .map(|template| service_account::email_for(template, &account_name))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "--service-account needs --service-account-email or `email` \
+                                     in [service_accounts] in the config"
+                        )
+                    })?,
+            };
+            let request = service_account::Request {
+                name: &account_name,
+                email: &email,
+                authentication_domain: authentication_domain
+                    .as_deref()
+                    .or(defaults.authentication_domain.as_deref()),
+                tier: user_tier
+                    .as_deref()
+                    .or(defaults.user_tier.as_deref())
+                    .unwrap_or("basic"),
+            };
+            let role = match &grant_role {
+                Some(role) => Some(service_account::find_role(ctx.client()?, role).await?),
+                None => None,
+            };
+            let owner = service_account::provision(ctx.client()?, &request).await?;
+            eprintln!(
+                "{} service account {} <{}> (user {})",
+                if owner.created { "Created" } else { "Using" },
+                owner.name,
+                owner.email,
+                owner.id
+            );
+            if let Some(group) = group {
+                let granted = service_account::grant(
+                    ctx.client()?,
+                    &owner,
+                    &group,
+                    role.as_ref(),
+                    account_id,
+                )
+                .await?;
+                eprintln!(
+                    "Added {} to {}group {} ({})",
+                    owner.email,
+                    if granted.created_group { "new " } else { "" },
+                    granted.group,
+                    granted.group_id
+                );
+                if let Some(role) = &granted.role {
+                    eprintln!(
+                        "Granted {} on account {} to group {}",
+                        role, granted.account_id, granted.group
+                    );
+                }
+            }
+            Some(owner.id)
+        }
+        None => user_id,
+    };
+    let spec = inventory::NewKey {
+        key_type: key_type.to_uppercase(),
+        account_id,
+        name,
+        notes,
+        ingest_type: Some(ingest_type.to_uppercase()),
+        user_id,
+    };
+    create_api_key(ctx, spec).await
+}
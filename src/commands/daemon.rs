@@ -0,0 +1,69 @@
+//! `daemon`: keep collecting the key inventory and expose it as Prometheus metrics.
+
+use crate::context::ExecutionContext;
+use crate::{anomaly, daemon};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Address for the /metrics endpoint
+    #[arg(long, default_value = "127.0.0.1:9464")]
+    pub listen: std::net::SocketAddr,
+
+    /// Seconds between inventory collections
+    #[arg(long, default_value_t = 300)]
+    pub interval: u64,
+
+    /// Account to watch (repeatable; default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Watch every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Compare each key's ingest with its baseline and POST keys that start sending, stop
+    /// sending or spike to this URL
+    #[arg(long)]
+    pub anomaly_webhook: Option<String>,
+
+    /// Seconds between ingest anomaly checks, and the window each one looks at
+    #[arg(long, default_value_t = 3600, requires = "anomaly_webhook")]
+    pub anomaly_interval: u64,
+
+    /// How many times its baseline a key must send to count as a spike
+    #[arg(long, default_value_t = 5.0, requires = "anomaly_webhook")]
+    pub anomaly_factor: f64,
+
+    /// GB per hour below which a key counts as not sending data
+    #[arg(long, default_value_t = 0.001, requires = "anomaly_webhook")]
+    pub anomaly_min_gb: f64,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        listen,
+        interval,
+        account_id,
+        account_group,
+        anomaly_webhook,
+        anomaly_interval,
+        anomaly_factor,
+        anomaly_min_gb,
+    } = args;
+    let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+    let anomalies = anomaly_webhook.map(|webhook| daemon::Anomalies {
+        webhook,
+        every: std::time::Duration::from_secs(anomaly_interval.max(60)),
+        detector: anomaly::Detector::new(anomaly_factor, anomaly_min_gb),
+    });
+    daemon::run(
+        ctx.client()?,
+        &account_ids,
+        &ctx.config.policies,
+        listen,
+        std::time::Duration::from_secs(interval.max(1)),
+        ctx.paths.history_file(),
+        anomalies,
+    )
+    .await
+}
@@ -0,0 +1,76 @@
+//! `decommission`: delete the keys of an environment that is being torn down.
+
+use std::path::PathBuf;
+
+use super::provision::resolve_environment;
+use crate::context::ExecutionContext;
+use crate::environment;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum DecommissionCommands {
+    /// Delete every key labeled env:<name> in the environment's accounts, write a report and
+    /// notify the environment's webhook
+    Env {
+        /// The environment, e.g. staging
+        name: String,
+
+        /// Delete every key of the environment's accounts, labeled or not
+        #[arg(long)]
+        all_keys: bool,
+
+        /// Only list the keys that would be deleted
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Where to write the report (default: the reports directory)
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        /// POST the report to this URL (default: `webhook` of the environment)
+        #[arg(long)]
+        webhook: Option<String>,
+    },
+}
+
+pub(crate) async fn run(
+    command: DecommissionCommands,
+    ctx: &ExecutionContext<'_>,
+) -> anyhow::Result<()> {
+    match command {
+        DecommissionCommands::Env {
+            name,
+            all_keys,
+            report,
+            webhook,
+            ..
+        } => {
+            let (environment, account_ids) = resolve_environment(&name, ctx)?;
+            let report_path = report.unwrap_or_else(|| {
+                ctx.paths.reports_dir().join(format!(
+                    "decommission-{}-{}.json",
+                    name,
+                    chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+                ))
+            });
+            environment::decommission(
+                ctx.client()?,
+                &name,
+                environment,
+                &account_ids,
+                environment::DecommissionOptions {
+                    all_keys,
+                    dry_run: ctx.dry_run,
+                    confirmation: ctx.confirmation,
+                    report_path: &report_path,
+                    webhook: webhook.as_deref(),
+                },
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
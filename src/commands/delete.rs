@@ -0,0 +1,163 @@
+//! `delete`: delete one key by `--key-id`, or every key a filter selects after listing them and
+//! asking first.
+
+use super::{confirm_selected, Command, FilterArgs, Request};
+use crate::context::ExecutionContext;
+use crate::{inventory, output};
+
+pub(crate) struct Delete {
+    pub key_id: String,
+    pub key_type: String,
+}
+
+impl Command for Delete {
+    fn validate(&self) -> anyhow::Result<()> {
+        match self.key_type.to_uppercase().as_str() {
+            "INGEST" | "USER" => Ok(()),
+            other => Err(anyhow::anyhow!(
+                "Unsupported key type '{}' (expected INGEST or USER)",
+                other
+            )),
+        }
+    }
+
+    fn build_request(&self) -> anyhow::Result<Request> {
+        let key_ids = [self.key_id.clone()];
+        let variables = if self.key_type.eq_ignore_ascii_case("USER") {
+            inventory::delete_variables(&[], &key_ids)
+        } else {
+            inventory::delete_variables(&key_ids, &[])
+        };
+        Ok(Request {
+            query: inventory::DELETE_QUERY.into(),
+            variables: Some(variables),
+        })
+    }
+
+    fn render(&self, data: serde_json::Value) -> anyhow::Result<()> {
+        let outcome = inventory::parse_delete_outcome(&data["apiAccessDeleteKeys"]);
+        println!("{}", output::json(&outcome)?);
+        if outcome.deleted.is_empty() {
+            anyhow::bail!(
+                "Unable to delete key {}: {}",
+                self.key_id,
+                if outcome.errors.is_empty() {
+                    "no key deleted".to_string()
+                } else {
+                    outcome.errors.join(", ")
+                }
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Key ID
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["selector", "where", "filter_name"],
+        conflicts_with_all = ["selector", "where", "filter_name"]
+    )]
+    pub key_id: Option<String>,
+
+    /// Key type (INGEST or USER)
+    #[arg(short = 't', long, default_value = "INGEST")]
+    pub key_type: String,
+
+    /// Account whose matching keys to delete (repeatable; default: account_id of the
+    /// selected profile)
+    #[arg(short, long, requires = "FilterArgs")]
+    pub account_id: Vec<i64>,
+
+    /// Delete the matching keys of every account in this group from `account_groups`
+    #[arg(
+        short = 'g',
+        long,
+        conflicts_with = "account_id",
+        requires = "FilterArgs"
+    )]
+    pub account_group: Option<String>,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        key_id,
+        key_type,
+        account_id,
+        account_group,
+        filter,
+        ..
+    } = args;
+    if let Some(key_id) = key_id {
+        return super::execute(&Delete { key_id, key_type }, ctx).await;
+    }
+    let client = ctx.client()?;
+    let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+    let filter = filter.parse(ctx.config)?;
+    let keys = confirm_selected(ctx, &account_ids, &key_type, &filter, "Delete").await?;
+    if !keys.is_empty() {
+        let ids: Vec<String> = keys.into_iter().map(|key| key.id).collect();
+        let outcome = if key_type.eq_ignore_ascii_case("USER") {
+            inventory::delete_keys(client, &[], &ids).await?
+        } else {
+            inventory::delete_keys(client, &ids, &[]).await?
+        };
+        println!("{}", output::json(&outcome)?);
+        if !outcome.errors.is_empty() {
+            anyhow::bail!(
+                "Deleted {} of {} key(s): {}",
+                outcome.deleted.len(),
+                ids.len(),
+                outcome.errors.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_routes_key_type() {
+        let delete = Delete {
+            key_id: "KEY-1".to_string(),
+            key_type: "user".to_string(),
+        };
+        delete.validate().unwrap();
+        assert_eq!(
+            serde_json::to_value(delete.build_request().unwrap().variables).unwrap(),
+            serde_json::json!({"keys": {"ingestKeyIds": [], "userKeyIds": ["KEY-1"]}})
+        );
+
+        let delete = Delete {
+            key_type: "license".to_string(),
+            ..delete
+        };
+        assert!(delete.validate().is_err());
+    }
+
+    #[test]
+    fn test_delete_fails_when_nothing_was_deleted() {
+        let delete = Delete {
+            key_id: "KEY-1".to_string(),
+            key_type: "INGEST".to_string(),
+        };
+        let data = serde_json::json!({
+            "apiAccessDeleteKeys": {"deletedKeys": [], "errors": [{"message": "Not found"}]}
+        });
+        let error = delete.render(data).unwrap_err();
+        assert_eq!(error.to_string(), "Unable to delete key KEY-1: Not found");
+    }
+}
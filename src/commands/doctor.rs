@@ -0,0 +1,14 @@
+//! `doctor`: check connectivity, TLS, proxy settings and credentials.
+
+use super::Globals;
+use crate::context::ExecutionContext;
+use crate::doctor;
+
+pub(crate) async fn run(ctx: &ExecutionContext<'_>, globals: &Globals<'_>) -> anyhow::Result<()> {
+    doctor::run(
+        ctx.client.as_ref().ok(),
+        globals.endpoint,
+        &ctx.paths.config_file(),
+    )
+    .await
+}
@@ -0,0 +1,77 @@
+//! `drift`: compare the keys declared in a manifest with the live keys, e.g. in pull request
+//! pipelines.
+
+use std::path::PathBuf;
+
+use crate::context::ExecutionContext;
+use crate::drift;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum DriftCommands {
+    /// Print the drift as Markdown and fail when any key drifted from the manifest
+    Check {
+        /// The manifest of keys, e.g. keys.yaml
+        #[arg(long)]
+        manifest: PathBuf,
+
+        /// Account to compare (repeatable; default: the accounts of the manifest)
+        #[arg(short, long)]
+        account_id: Vec<i64>,
+
+        /// Compare every account in this group from `account_groups` in the config
+        #[arg(short = 'g', long, conflicts_with = "account_id")]
+        account_group: Option<String>,
+
+        /// Report format: markdown or json
+        #[arg(short, long, default_value = "markdown")]
+        format: String,
+
+        /// Post the Markdown as a comment on the pull request when anything drifted
+        /// (needs $GITHUB_TOKEN and $GITHUB_REPOSITORY)
+        #[arg(long)]
+        post_comment: bool,
+
+        /// Pull request to comment on (default: the one of the GitHub Actions event)
+        #[arg(long, requires = "post_comment")]
+        pr: Option<u64>,
+    },
+}
+
+pub(crate) async fn run(command: DriftCommands, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    match command {
+        DriftCommands::Check {
+            manifest,
+            account_id,
+            account_group,
+            format,
+            post_comment,
+            pr,
+        } => {
+            let json = match format.to_lowercase().as_str() {
+                "markdown" | "md" => false,
+                "json" => true,
+                other => anyhow::bail!(
+                    "Unsupported drift format '{}' (expected markdown or json)",
+                    other
+                ),
+            };
+            let account_ids = if account_id.is_empty() && account_group.is_none() {
+                None
+            } else {
+                Some(ctx.account_ids(account_id, account_group.as_deref())?)
+            };
+            drift::check(
+                ctx.client()?,
+                &manifest,
+                account_ids,
+                &drift::Options {
+                    json,
+                    post_comment,
+                    pr,
+                },
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
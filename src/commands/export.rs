@@ -0,0 +1,110 @@
+//! `export`: print infrastructure-as-code definitions for existing keys, e.g. Terraform resources.
+
+use std::path::PathBuf;
+
+use super::{FilterArgs, Globals};
+use crate::context::ExecutionContext;
+use crate::{crypt, export, filter};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Export format: terraform, pulumi (a Pulumi YAML program) or crossplane (managed
+    /// resource manifests)
+    #[arg(short, long)]
+    pub format: String,
+
+    /// apiVersion of the Crossplane manifests, if your provider package differs from the
+    /// default
+    #[arg(long)]
+    pub api_version: Option<String>,
+
+    /// Account to export (repeatable; default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Export every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Only keys of this type (INGEST or USER; default: both)
+    #[arg(short, long)]
+    pub key_type: Option<String>,
+
+    /// Encrypt the export to this age recipient, e.g. age1... (repeatable)
+    #[arg(long, value_name = "RECIPIENT")]
+    pub encrypt_to: Vec<String>,
+
+    /// Encrypt the export with a passphrase, prompted for or read from
+    /// $NEW_RELIC_EXPORT_PASSPHRASE
+    #[arg(long, conflicts_with = "encrypt_to")]
+    pub passphrase: bool,
+
+    /// Write the definitions to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+pub(crate) async fn run(
+    args: Args,
+    ctx: &ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    let Args {
+        format: export_format,
+        api_version,
+        account_id,
+        account_group,
+        key_type,
+        encrypt_to,
+        passphrase,
+        output,
+        filter,
+    } = args;
+    let mut export_format = export::ExportFormat::parse(&export_format)?;
+    if let Some(version) = api_version {
+        match &mut export_format {
+            export::ExportFormat::Crossplane { api_version } => *api_version = version,
+            _ => anyhow::bail!("--api-version only applies to --format crossplane"),
+        }
+    }
+    let key_type = key_type.map(|t| t.to_uppercase());
+    let key_types = match key_type.as_deref() {
+        Some(key_type) => vec![key_type],
+        None => vec!["INGEST", "USER"],
+    };
+    let encryption = if passphrase {
+        Some(crypt::Encryption::passphrase()?)
+    } else if !encrypt_to.is_empty() {
+        Some(crypt::Encryption::recipients(&encrypt_to)?)
+    } else {
+        None
+    };
+    if ctx.secrets.strict
+        && export_format == export::ExportFormat::Json
+        && encryption.is_none()
+        && output.is_none()
+    {
+        anyhow::bail!(
+            "strict_secrets is on: pass --encrypt-to, --passphrase or --output so the \
+                     export is not printed"
+        );
+    }
+    let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+    let keys = filter::fetch(
+        ctx.client()?,
+        &account_ids,
+        &key_types,
+        &filter.parse(ctx.config)?,
+    )
+    .await?;
+    export::run(
+        keys,
+        &export_format,
+        encryption.as_ref(),
+        output.as_deref(),
+        globals.pager,
+    )
+}
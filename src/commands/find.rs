@@ -0,0 +1,73 @@
+//! `find`: find keys by approximate name or ID, best matches first.
+
+use super::list::ViewArgs;
+use super::Globals;
+use crate::context::ExecutionContext;
+use crate::{list, pager};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Characters to look for in order, e.g. `payprd` for "payments-prod-license"
+    pub pattern: String,
+
+    /// Account to search (repeatable; default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Search every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Search every profile's accounts, including each profile's extra `endpoints`
+    #[arg(long, conflicts_with_all = ["account_id", "account_group"])]
+    pub all_profiles: bool,
+
+    /// Search the accounts of these profiles, e.g. prod-us,prod-eu
+    #[arg(
+        long,
+        value_delimiter = ',',
+        conflicts_with_all = ["account_id", "account_group", "all_profiles"]
+    )]
+    pub profiles: Vec<String>,
+
+    /// Number of matches to show
+    #[arg(short, long, default_value_t = 10)]
+    pub limit: usize,
+
+    #[command(flatten)]
+    pub view: ViewArgs,
+}
+
+pub(crate) async fn run(
+    args: Args,
+    ctx: &ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    let Args {
+        pattern,
+        account_id,
+        account_group,
+        all_profiles,
+        profiles,
+        limit,
+        view,
+    } = args;
+    let view = view.parse(ctx.config)?;
+    let targets = globals.targets(
+        ctx,
+        all_profiles,
+        &profiles,
+        account_id,
+        account_group.as_deref(),
+    )?;
+    let matches = list::find(
+        targets,
+        &["INGEST", "USER"],
+        &pattern,
+        limit,
+        ctx.format()?,
+        &view,
+    )
+    .await?;
+    pager::print(&matches, globals.pager)
+}
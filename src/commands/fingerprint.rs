@@ -0,0 +1,15 @@
+//! `fingerprint`: print the fingerprint of a key secret, or of every key in a file, without the
+//! secret.
+
+use crate::context::ExecutionContext;
+use crate::fingerprint;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// The key secret, a file to find keys in, or - to read the secret from stdin
+    pub secret_or_file: String,
+}
+
+pub(crate) fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    fingerprint::run(&args.secret_or_file, ctx.format()?)
+}
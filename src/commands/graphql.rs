@@ -0,0 +1,75 @@
+//! `graphql`: an arbitrary document, answered with its `data` as JSON.
+
+use std::path::PathBuf;
+
+use super::{Command, Request};
+use crate::context::ExecutionContext;
+use crate::{output, sink, Variables};
+
+pub(crate) struct Graphql<'a> {
+    pub query: String,
+    pub variables: Option<Variables>,
+    pub secrets: &'a sink::Secrets,
+}
+
+impl Command for Graphql<'_> {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.query.trim().is_empty() {
+            anyhow::bail!("graphql needs a query document");
+        }
+        Ok(())
+    }
+
+    fn build_request(&self) -> anyhow::Result<Request> {
+        Ok(Request {
+            query: self.query.clone().into(),
+            variables: self.variables.clone(),
+        })
+    }
+
+    fn render(&self, data: serde_json::Value) -> anyhow::Result<()> {
+        println!("{}", self.secrets.redact(&output::json(&data)?));
+        Ok(())
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// The query document, or - to read it from stdin
+    #[arg(required_unless_present = "file")]
+    pub query: Option<String>,
+
+    /// Read the query document from a file
+    #[arg(long, conflicts_with = "query")]
+    pub file: Option<PathBuf>,
+
+    /// Variables as a JSON object, e.g. '{"accountId": 123}'
+    #[arg(long)]
+    pub variables: Option<String>,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        query,
+        file,
+        variables,
+    } = args;
+    let query = match (query.as_deref(), file) {
+        (_, Some(file)) => std::fs::read_to_string(&file)
+            .map_err(|e| anyhow::anyhow!("Unable to read {}: {}", file.display(), e))?,
+        (Some("-"), None) => std::io::read_to_string(std::io::stdin())?,
+        (query, None) => query.unwrap_or_default().to_string(),
+    };
+    let variables = variables
+        .map(|variables| {
+            serde_json::from_str::<Variables>(&variables)
+                .map_err(|e| anyhow::anyhow!("--variables must be a JSON object: {}", e))
+        })
+        .transpose()?;
+    let graphql = Graphql {
+        query,
+        variables,
+        secrets: &ctx.secrets,
+    };
+    super::execute(&graphql, ctx).await
+}
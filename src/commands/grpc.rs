@@ -0,0 +1,34 @@
+//! `grpc`: serve the key operations over gRPC (see proto/apikeys.proto).
+
+use crate::context::ExecutionContext;
+use crate::grpc;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    pub listen: std::net::SocketAddr,
+
+    /// Bearer token clients must send as `authorization` metadata (at least 16 characters)
+    #[arg(long, env = "NEW_RELIC_APIKEYS_SERVE_TOKEN", hide_env_values = true)]
+    pub token: String,
+
+    /// Accounts listed when a request names none (repeatable; default: the profile's account)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Default to every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        listen,
+        token,
+        account_id,
+        account_group,
+    } = args;
+    let default_account_ids = ctx.default_account_ids(account_id, account_group.as_deref())?;
+    grpc::run(ctx.client()?.clone(), token, default_account_ids, listen).await
+}
@@ -0,0 +1,26 @@
+//! `history`: show or repeat previous invocations.
+
+use crate::context::ExecutionContext;
+use crate::history;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum HistoryCommands {
+    /// List recent invocations, oldest first
+    List {
+        /// Number of entries to show
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Run a previous invocation again
+    Rerun {
+        /// Entry number as shown by `history list`
+        number: usize,
+    },
+}
+
+pub(crate) fn run(command: HistoryCommands, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    match command {
+        HistoryCommands::List { limit } => history::list(&ctx.paths.history_file(), limit),
+        HistoryCommands::Rerun { number } => history::rerun(&ctx.paths.history_file(), number),
+    }
+}
@@ -0,0 +1,46 @@
+//! `hooks`: manage the git pre-commit hook that blocks commits containing live keys.
+
+use std::path::PathBuf;
+
+use super::Globals;
+use crate::hooks;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum HooksCommands {
+    /// Install a pre-commit hook that runs `scan --staged` and blocks commits containing live keys
+    Install {
+        /// Repository to install the hook in
+        #[arg(long, default_value = ".")]
+        repo: PathBuf,
+
+        /// Ignore findings in files matching this glob (repeatable; passed on to `scan --allow`)
+        #[arg(long, value_name = "GLOB")]
+        allow: Vec<String>,
+
+        /// Replace an existing pre-commit hook that was not installed by this command
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove the pre-commit hook installed by `hooks install`
+    Uninstall {
+        /// Repository to remove the hook from
+        #[arg(long, default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+pub(crate) fn run(command: HooksCommands, globals: &Globals<'_>) -> anyhow::Result<()> {
+    match command {
+        HooksCommands::Install { repo, allow, force } => {
+            let mut args = Vec::new();
+            if let Some(name) = globals.profile {
+                args.extend(["--profile".to_string(), name.to_string()]);
+            }
+            for pattern in allow {
+                args.extend(["--allow".to_string(), pattern]);
+            }
+            hooks::install(&repo, &args, force)
+        }
+        HooksCommands::Uninstall { repo } => hooks::uninstall(&repo),
+    }
+}
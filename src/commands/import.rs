@@ -0,0 +1,55 @@
+//! `import`: recreate the keys of an `export --format json` file that no longer exist.
+
+use std::path::PathBuf;
+
+use crate::context::ExecutionContext;
+use crate::export;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// The export, plain or age-encrypted, or - to read it from stdin
+    pub file: PathBuf,
+
+    /// age identity file to decrypt the export with (repeatable)
+    #[arg(short, long)]
+    pub identity: Vec<PathBuf>,
+
+    /// Recreate the keys in this account instead of the ones they were exported from
+    #[arg(short, long)]
+    pub account_id: Option<i64>,
+
+    /// Only list the keys that would be recreated
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        file,
+        identity,
+        account_id,
+        ..
+    } = args;
+    let contents = if file.as_os_str() == "-" {
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut contents)?;
+        contents
+    } else {
+        std::fs::read(&file)
+            .map_err(|e| anyhow::anyhow!("Could not read {}: {}", file.display(), e))?
+    };
+    let keys = export::read_export(&contents, &identity)?;
+    export::import(
+        ctx.client()?,
+        keys,
+        account_id,
+        ctx.dry_run,
+        ctx.confirmation,
+        &ctx.secrets,
+    )
+    .await
+}
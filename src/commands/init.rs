@@ -0,0 +1,9 @@
+//! `init`: create a profile interactively, with its region, API key (stored in the keyring) and
+//! default account.
+
+use crate::context::ExecutionContext;
+use crate::init;
+
+pub(crate) async fn run(ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    init::run(ctx.paths).await
+}
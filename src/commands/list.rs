@@ -0,0 +1,121 @@
+//! `list`: list the keys in one or more accounts.
+
+use super::{FilterArgs, Globals};
+use crate::context::ExecutionContext;
+use crate::{config, list, pager, time};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Account to list (repeatable; default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// List every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Only keys of this type (INGEST or USER; default: both)
+    #[arg(short, long)]
+    pub key_type: Option<String>,
+
+    /// List every profile's accounts, including each profile's extra `endpoints`,
+    /// concurrently
+    #[arg(long, conflicts_with_all = ["account_id", "account_group"])]
+    pub all_profiles: bool,
+
+    /// List the accounts of these profiles, e.g. prod-us,prod-eu, concurrently, with a
+    /// profile column
+    #[arg(
+        long,
+        value_delimiter = ',',
+        conflicts_with_all = ["account_id", "account_group", "all_profiles"]
+    )]
+    pub profiles: Vec<String>,
+
+    #[command(flatten)]
+    pub view: ViewArgs,
+}
+
+/// Ordering and column selection shared by the listing commands.
+#[derive(clap::Args)]
+pub(crate) struct ViewArgs {
+    /// Sort by created, name or type
+    #[arg(long)]
+    sort: Option<String>,
+
+    /// Reverse the order
+    #[arg(long)]
+    reverse: bool,
+
+    /// Comma-separated columns to show, e.g. id,name,notes (profile, region, account, type, id,
+    /// name, created, notes, fingerprint)
+    #[arg(long)]
+    columns: Option<String>,
+
+    /// Only keys created before this time: RFC 3339, YYYY-MM-DD or an age like 30d, 6mo or 1y
+    #[arg(long)]
+    created_before: Option<String>,
+
+    /// Only keys created at or after this time (same formats as --created-before)
+    #[arg(long)]
+    created_after: Option<String>,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+impl ViewArgs {
+    pub fn parse(self, config: &config::Config) -> anyhow::Result<list::View> {
+        let now = chrono::Utc::now();
+        Ok(list::View {
+            sort: self.sort.as_deref().map(list::SortKey::parse).transpose()?,
+            reverse: self.reverse,
+            columns: self
+                .columns
+                .as_deref()
+                .map(list::Column::parse_list)
+                .transpose()?,
+            created_before: self
+                .created_before
+                .as_deref()
+                .map(|value| time::parse_time(value, now))
+                .transpose()?,
+            created_after: self
+                .created_after
+                .as_deref()
+                .map(|value| time::parse_time(value, now))
+                .transpose()?,
+            filter: self.filter.parse(config)?,
+        })
+    }
+}
+
+pub(crate) async fn run(
+    args: Args,
+    ctx: &ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    let Args {
+        account_id,
+        account_group,
+        key_type,
+        all_profiles,
+        profiles,
+        view,
+    } = args;
+    let view = view.parse(ctx.config)?;
+    let key_type = key_type.map(|t| t.to_uppercase());
+    let key_types = match key_type.as_deref() {
+        Some(key_type) => vec![key_type],
+        None => vec!["INGEST", "USER"],
+    };
+    let targets = globals.targets(
+        ctx,
+        all_profiles,
+        &profiles,
+        account_id,
+        account_group.as_deref(),
+    )?;
+    let listing = list::run(targets, &key_types, ctx.format()?, &view).await?;
+    pager::print(&listing, globals.pager)
+}
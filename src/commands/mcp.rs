@@ -0,0 +1,31 @@
+//! `mcp`: run a Model Context Protocol server on stdio so AI assistants can inventory keys.
+
+use crate::context::ExecutionContext;
+use crate::mcp;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Also expose create_key, rotate_key and delete_key
+    #[arg(long)]
+    pub allow_mutations: bool,
+
+    /// Accounts listed when a tool call names none (repeatable; default: the profile's account)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Default to every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        allow_mutations,
+        account_id,
+        account_group,
+    } = args;
+    let default_account_ids = ctx.default_account_ids(account_id, account_group.as_deref())?;
+    mcp::Server::new(ctx.client()?.clone(), default_account_ids, allow_mutations)
+        .run()
+        .await
+}
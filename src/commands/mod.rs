@@ -0,0 +1,219 @@
+//! One module per subcommand, each with its clap arguments and a `run` that `cli::run` dispatches
+//! to with the [`ExecutionContext`]. Subcommands that send a single NerdGraph request implement
+//! [`Command`] instead of writing their own `run` body: it checks the arguments, builds the
+//! document and variables to send, and renders the `data` NerdGraph answers with, and [`execute`]
+//! runs those steps in order.
+
+pub(crate) mod audit;
+pub(crate) mod audit_events;
+pub(crate) mod audit_log;
+pub(crate) mod auth;
+pub(crate) mod bench;
+pub(crate) mod bootstrap;
+pub(crate) mod check;
+pub(crate) mod cleanup;
+pub(crate) mod clone;
+pub(crate) mod config;
+pub(crate) mod create;
+pub(crate) mod daemon;
+pub(crate) mod decommission;
+pub(crate) mod delete;
+pub(crate) mod doctor;
+pub(crate) mod drift;
+pub(crate) mod export;
+pub(crate) mod find;
+pub(crate) mod fingerprint;
+pub(crate) mod graphql;
+#[cfg(feature = "grpc")]
+pub(crate) mod grpc;
+pub(crate) mod history;
+pub(crate) mod hooks;
+pub(crate) mod import;
+pub(crate) mod init;
+pub(crate) mod list;
+pub(crate) mod mcp;
+pub(crate) mod onboard;
+#[cfg(feature = "package")]
+pub(crate) mod package;
+pub(crate) mod policy;
+pub(crate) mod protect;
+pub(crate) mod provision;
+pub(crate) mod query;
+pub(crate) mod rename;
+pub(crate) mod report;
+pub(crate) mod rotate;
+pub(crate) mod scan;
+pub(crate) mod scheduler;
+pub(crate) mod serve;
+pub(crate) mod snapshot;
+pub(crate) mod sweep;
+pub(crate) mod transfer;
+pub(crate) mod update;
+pub(crate) mod usage;
+pub(crate) mod validate_queries;
+pub(crate) mod whoami;
+
+use std::borrow::Cow;
+
+use crate::context::ExecutionContext;
+use crate::{cancel, expression, filter, inventory, selector};
+use crate::{NewRelicClient, SecretString, Variables};
+
+/// The document and variables a [`Command`] sends.
+pub(crate) struct Request {
+    pub query: Cow<'static, str>,
+    pub variables: Option<Variables>,
+}
+
+pub(crate) trait Command {
+    /// Reject arguments NerdGraph would, before anything is sent.
+    fn validate(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn build_request(&self) -> anyhow::Result<Request>;
+
+    /// Print the `data` of a successful response, or fail if it reports per-item errors.
+    fn render(&self, data: serde_json::Value) -> anyhow::Result<()>;
+}
+
+/// Validate `command`, send its request with the context's client and render the answer.
+pub(crate) async fn execute(
+    command: &impl Command,
+    ctx: &ExecutionContext<'_>,
+) -> anyhow::Result<()> {
+    command.validate()?;
+    let request = command.build_request()?;
+    let data = ctx
+        .client()?
+        .execute_query(&request.query, request.variables)
+        .await?;
+    command.render(data)
+}
+
+/// Global options and state that only some subcommands use, set up once in `cli::run`.
+pub(crate) struct Globals<'a> {
+    /// Page long output (no `--no-pager`)
+    pub pager: bool,
+    /// `--profile`
+    pub profile: Option<&'a str>,
+    /// `--secret-sink`
+    pub secret_sink: Option<&'a str>,
+    pub endpoint: &'a str,
+    /// The protected keys, unless `--allow-protected` is given
+    pub protection: Option<&'a crate::protect::Protection>,
+    pub cancellation: &'a cancel::Cancellation,
+    /// A client for another profile's API key and endpoint, with the same checks and middleware
+    /// as the selected profile's
+    pub profile_client: &'a dyn Fn(SecretString, String) -> anyhow::Result<NewRelicClient>,
+}
+
+impl Globals<'_> {
+    /// Where `list` and `find` look: the given profiles' accounts, or the selected client's.
+    pub fn targets(
+        &self,
+        ctx: &ExecutionContext<'_>,
+        all_profiles: bool,
+        profiles: &[String],
+        account_ids: Vec<i64>,
+        account_group: Option<&str>,
+    ) -> anyhow::Result<Vec<crate::list::Target>> {
+        if all_profiles || !profiles.is_empty() {
+            return crate::list::profile_targets(ctx.config, profiles, self.profile_client);
+        }
+        Ok(vec![crate::list::Target {
+            profile: None,
+            region: crate::list::region_label(self.endpoint),
+            client: ctx.client()?.clone(),
+            account_ids: ctx.account_ids(account_ids, account_group)?,
+        }])
+    }
+}
+
+/// Which keys a listing or bulk command acts on.
+#[derive(clap::Args)]
+pub(crate) struct FilterArgs {
+    /// Only keys whose labels (name:value words in the notes) match, e.g.
+    /// 'env=staging,team in (payments,web),!legacy'
+    #[arg(long)]
+    selector: Option<String>,
+
+    /// Only keys for which this expression holds, e.g.
+    /// "type == 'INGEST' && age_days > 90 && name =~ 'legacy-'"
+    #[arg(long = "where", id = "where", value_name = "EXPRESSION")]
+    where_: Option<String>,
+
+    /// Only keys for which this `[filters]` expression from the config holds (repeatable)
+    #[arg(long, value_name = "NAME")]
+    filter_name: Vec<String>,
+}
+
+impl FilterArgs {
+    pub fn parse(&self, config: &crate::config::Config) -> anyhow::Result<filter::KeyFilter> {
+        let mut expressions = Vec::new();
+        for name in &self.filter_name {
+            let text = config
+                .filters
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("Filter '{}' is not defined in [filters]", name))?;
+            expressions.push(
+                expression::Expression::parse(text)
+                    .map_err(|e| anyhow::anyhow!("filters.{}: {}", name, e))?,
+            );
+        }
+        if let Some(text) = &self.where_ {
+            expressions.push(expression::Expression::parse(text)?);
+        }
+        Ok(filter::KeyFilter {
+            selector: self
+                .selector
+                .as_deref()
+                .map(selector::Selector::parse)
+                .transpose()?,
+            expression: expressions.into_iter().reduce(expression::Expression::and),
+        })
+    }
+}
+
+/// The keys that match `filter`, listed on stderr, once the user confirms they should be
+/// `verb`ed (e.g. "Delete"); empty if nothing matches or the user declines.
+pub(crate) async fn confirm_selected(
+    ctx: &ExecutionContext<'_>,
+    account_ids: &[i64],
+    key_type: &str,
+    filter: &filter::KeyFilter,
+    verb: &str,
+) -> anyhow::Result<Vec<inventory::ApiKey>> {
+    let key_type = supported_key_type(key_type)?;
+    let keys = filter::fetch(ctx.client()?, account_ids, &[&key_type], filter).await?;
+    if keys.is_empty() {
+        println!("No keys match");
+        return Ok(keys);
+    }
+    for key in &keys {
+        eprintln!(
+            "  {}  {}  (account {})",
+            key.id,
+            key.name.as_deref().unwrap_or("-"),
+            key.account_id.unwrap_or_default()
+        );
+    }
+    let question = format!("{} {} {} key(s)?", verb, keys.len(), key_type);
+    if !ctx.confirm(&question)? {
+        println!("Nothing changed");
+        return Ok(Vec::new());
+    }
+    Ok(keys)
+}
+
+/// `key_type` in upper case, if it is a type keys can be selected and changed by.
+pub(crate) fn supported_key_type(key_type: &str) -> anyhow::Result<String> {
+    let key_type = key_type.to_uppercase();
+    if !matches!(key_type.as_str(), "INGEST" | "USER") {
+        anyhow::bail!(
+            "Unsupported key type '{}' (expected INGEST or USER)",
+            key_type
+        );
+    }
+    Ok(key_type)
+}
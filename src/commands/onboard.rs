@@ -0,0 +1,90 @@
+//! `onboard`: set up a managed customer's account with the standard keys.
+
+use std::path::PathBuf;
+
+use super::Globals;
+use crate::context::ExecutionContext;
+use crate::{onboard, sink};
+
+#[derive(clap::Subcommand)]
+pub(crate) enum OnboardCommands {
+    /// Create the key set from [onboarding] in a customer's account, labeled with the customer
+    /// and owner, store the secrets in the secret sink and write a report
+    Customer {
+        /// The customer's account
+        account_id: i64,
+
+        /// Customer name, used in key names and the customer:<name> label
+        #[arg(long)]
+        customer: String,
+
+        /// Owner label of the keys (default: owner from [onboarding])
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Only list the keys that would be created
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Where to write the report (default: the reports directory)
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+}
+
+pub(crate) async fn run(
+    command: OnboardCommands,
+    ctx: &mut ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    match command {
+        OnboardCommands::Customer {
+            account_id,
+            customer,
+            owner,
+            report,
+            ..
+        } => {
+            onboard::validate_label_value("customer", &customer)?;
+            let owner = owner
+                .or_else(|| ctx.config.onboarding.owner.clone())
+                .ok_or_else(|| anyhow::anyhow!("Pass --owner or set owner in [onboarding]"))?;
+            onboard::validate_label_value("owner", &owner)?;
+            let sink = globals.secret_sink.map(str::to_string).or_else(|| {
+                ctx.config
+                    .onboarding
+                    .secret_sink
+                    .as_ref()
+                    .map(|sink| sink.replace("{customer}", &customer))
+            });
+            ctx.secrets.sink = sink.as_deref().map(sink::Sink::parse).transpose()?;
+            let report_path = report.unwrap_or_else(|| {
+                ctx.paths.reports_dir().join(format!(
+                    "onboard-{}-{}.json",
+                    customer,
+                    chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+                ))
+            });
+            onboard::customer(
+                ctx.client()?,
+                &customer,
+                &owner,
+                &ctx.config.onboarding,
+                account_id,
+                onboard::Options {
+                    dry_run: ctx.dry_run,
+                    confirmation: ctx.confirmation,
+                    report_path: &report_path,
+                    secret_sink: sink.as_deref(),
+                },
+                &ctx.secrets,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
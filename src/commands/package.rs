@@ -0,0 +1,49 @@
+//! `package`: generate release metadata, i.e. completions, man pages, a Homebrew formula and a
+//! Scoop manifest.
+
+use std::path::PathBuf;
+
+use crate::package;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum PackageCommands {
+    /// Write completions and man pages, and for the given archives a Homebrew formula and a
+    /// Scoop manifest, to the dist directory
+    Metadata {
+        /// Directory to write to
+        #[arg(long, default_value = "dist")]
+        out_dir: PathBuf,
+
+        /// Version the formula and manifest install (default: the crate version)
+        #[arg(long, default_value = env!("CARGO_PKG_VERSION"))]
+        release_version: String,
+
+        /// Download URL of each archive; {version} and {archive} (its file name) are filled in
+        #[arg(long, default_value = package::DEFAULT_URL)]
+        url: String,
+
+        /// A release archive as TARGET=PATH, e.g. aarch64-apple-darwin=dist/cli.tar.gz (repeatable)
+        #[arg(long, value_parser = package::Archive::parse)]
+        archive: Vec<package::Archive>,
+    },
+}
+
+/// Write the release metadata for `cli`, the top-level command.
+pub(crate) fn run(command: PackageCommands, cli: clap::Command) -> anyhow::Result<()> {
+    match command {
+        PackageCommands::Metadata {
+            out_dir,
+            release_version,
+            url,
+            archive,
+        } => package::metadata(
+            cli,
+            package::Options {
+                out_dir: &out_dir,
+                version: &release_version,
+                url: &url,
+                archives: &archive,
+            },
+        ),
+    }
+}
@@ -0,0 +1,49 @@
+//! `policy`: enforce the `policies` from the config file.
+
+use super::audit::{notify_breaches, PolicyArgs};
+use crate::audit;
+use crate::context::ExecutionContext;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum PolicyCommands {
+    /// Fail when a key violates one of the configured policies
+    Check {
+        #[command(flatten)]
+        check: PolicyArgs,
+    },
+}
+
+pub(crate) async fn run(command: PolicyCommands, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    match command {
+        PolicyCommands::Check { check } => {
+            let rules = audit::policy_rules(&ctx.config.policies);
+            if rules.is_empty() {
+                anyhow::bail!(
+                    "No policies configured; set policies.max_key_age_days, \
+                         policies.require_notes or policies.max_keys_per_account with `config set`"
+                );
+            }
+            let account_ids =
+                ctx.account_ids(check.account_id.clone(), check.account_group.as_deref())?;
+            let evaluation = audit::run(
+                ctx.client()?,
+                &account_ids,
+                &check.filter.parse(ctx.config)?,
+                rules,
+                ctx.format()?,
+                check.report()?,
+                "policy check",
+            )
+            .await?;
+            notify_breaches(ctx, &evaluation).await?;
+            if !evaluation.findings.is_empty() {
+                anyhow::bail!(
+                    "{} policy violation(s) in {} key(s)",
+                    evaluation.findings.len(),
+                    evaluation.keys.len()
+                );
+            }
+        }
+    }
+    Ok(())
+}
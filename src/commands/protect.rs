@@ -0,0 +1,58 @@
+//! `protect`: protect keys from being deleted or rotated, or list the protected keys.
+
+use crate::context::ExecutionContext;
+use crate::protect;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum ProtectCommands {
+    /// Protect keys, by ID or name
+    Add {
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Withdraw the protection of a key added with `protect add`
+    Remove {
+        /// Key ID or name
+        key: String,
+    },
+    /// Show the protected keys from the config and from `protect add`
+    List,
+}
+
+pub(crate) async fn run(
+    command: ProtectCommands,
+    ctx: &ExecutionContext<'_>,
+) -> anyhow::Result<()> {
+    let protected_file = ctx.paths.protected_file();
+    match command {
+        ProtectCommands::Add { keys } => {
+            let added = protect::add(&protected_file, &keys)?;
+            for key in &keys {
+                if added.contains(key) {
+                    println!("Protected {}", key);
+                } else {
+                    println!("{} is protected already", key);
+                }
+            }
+        }
+        ProtectCommands::Remove { key } => {
+            if protect::remove(&protected_file, &key)? {
+                println!("{} is no longer protected", key);
+            } else if ctx.config.protected_keys.contains(&key) {
+                anyhow::bail!(
+                    "{} is protected by protected_keys in the config; remove it there",
+                    key
+                );
+            } else {
+                anyhow::bail!("{} is not protected", key);
+            }
+        }
+        ProtectCommands::List => {
+            protect::print(
+                &protect::entries(ctx.config, &protected_file)?,
+                ctx.format()?,
+            )?;
+        }
+    }
+    Ok(())
+}
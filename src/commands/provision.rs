@@ -0,0 +1,60 @@
+//! `provision`: create the standard keys of a new environment.
+
+use crate::context::ExecutionContext;
+use crate::{config, environment};
+
+#[derive(clap::Subcommand)]
+pub(crate) enum ProvisionCommands {
+    /// Create the keys of an environment from [environments.<name>] in each of its accounts,
+    /// skipping those that already exist
+    Env {
+        /// The environment, e.g. prod or staging
+        name: String,
+
+        /// Only list the keys that would be created
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// The `[environments]` entry called `name` and its accounts.
+pub(crate) fn resolve_environment<'c>(
+    name: &str,
+    ctx: &ExecutionContext<'c>,
+) -> anyhow::Result<(&'c config::Environment, Vec<i64>)> {
+    let environment = ctx.config.environments.get(name).ok_or_else(|| {
+        anyhow::anyhow!("Environment '{}' is not defined in [environments]", name)
+    })?;
+    let account_ids = match environment.account_group.as_deref() {
+        Some(group) => ctx.account_ids(Vec::new(), Some(group))?,
+        None if !environment.account_ids.is_empty() => environment.account_ids.clone(),
+        None => anyhow::bail!("Environment '{}' needs account_group or account_ids", name),
+    };
+    Ok((environment, account_ids))
+}
+
+pub(crate) async fn run(
+    command: ProvisionCommands,
+    ctx: &ExecutionContext<'_>,
+) -> anyhow::Result<()> {
+    match command {
+        ProvisionCommands::Env { name, .. } => {
+            let (environment, account_ids) = resolve_environment(&name, ctx)?;
+            environment::provision(
+                ctx.client()?,
+                &name,
+                environment,
+                &account_ids,
+                ctx.dry_run,
+                ctx.confirmation,
+                &ctx.secrets,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
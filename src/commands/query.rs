@@ -0,0 +1,121 @@
+//! `query`: the details of one key.
+
+use super::{Command, Request};
+use crate::context::ExecutionContext;
+use crate::{ci, inventory};
+
+pub(crate) const KEY_QUERY: &str = r#"
+    query($id: ID!, $keyType: ApiAccessKeyType!) {
+        actor {
+            apiAccess {
+                key(
+                    id: $id
+                    keyType: $keyType
+                ) {
+                    key
+                    name
+                    notes
+                    type
+                }
+            }
+        }
+    }"#;
+
+pub(crate) struct Query {
+    pub key_id: Option<String>,
+    pub key_type: Option<String>,
+    pub strict_secrets: bool,
+}
+
+impl Command for Query {
+    fn validate(&self) -> anyhow::Result<()> {
+        // KEY_QUERY needs both; without them NerdGraph only answers with a validation error.
+        if self.key_id.is_none() || self.key_type.is_none() {
+            anyhow::bail!("query needs both --key-id and --key-type");
+        }
+        Ok(())
+    }
+
+    fn build_request(&self) -> anyhow::Result<Request> {
+        let (Some(key_id), Some(key_type)) = (&self.key_id, &self.key_type) else {
+            anyhow::bail!("query needs both --key-id and --key-type");
+        };
+        Ok(Request {
+            query: KEY_QUERY.into(),
+            variables: Some(inventory::get_variables(key_id, key_type)),
+        })
+    }
+
+    fn render(&self, data: serde_json::Value) -> anyhow::Result<()> {
+        let Some(key) = data
+            .get("actor")
+            .and_then(|a| a.get("apiAccess"))
+            .and_then(|a| a.get("key"))
+        else {
+            println!("No API keys found or unable to retrieve keys");
+            return Ok(());
+        };
+        let na = serde_json::Value::String("N/A".to_string());
+        let secret = match key.get("key") {
+            Some(secret) if self.strict_secrets && !secret.is_null() => {
+                serde_json::Value::String(ci::MASKED.to_string())
+            }
+            Some(secret) => secret.clone(),
+            None => na.clone(),
+        };
+        println!();
+        println!("API Key Details:");
+        println!("Key: {}", secret);
+        println!("Name: {}", key.get("name").unwrap_or(&na));
+        println!("Type: {}", key.get("type").unwrap_or(&na));
+        println!("Notes: {}", key.get("notes").unwrap_or(&na));
+        Ok(())
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Key type filter
+    #[arg(short, long)]
+    pub key_type: Option<String>,
+
+    /// Key ID to search for
+    #[arg(short = 'i', long)]
+    pub key_id: Option<String>,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let query = Query {
+        key_id: args.key_id,
+        key_type: args.key_type,
+        strict_secrets: ctx.secrets.strict,
+    };
+    super::execute(&query, ctx).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_needs_id_and_type() {
+        let query = Query {
+            key_id: Some("KEY-1".to_string()),
+            key_type: None,
+            strict_secrets: false,
+        };
+        assert!(query.validate().is_err());
+
+        let query = Query {
+            key_type: Some("user".to_string()),
+            ..query
+        };
+        query.validate().unwrap();
+        let request = query.build_request().unwrap();
+        assert_eq!(request.query, KEY_QUERY);
+        assert_eq!(
+            serde_json::to_value(request.variables).unwrap(),
+            serde_json::json!({"id": "KEY-1", "keyType": "USER"})
+        );
+    }
+}
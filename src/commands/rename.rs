@@ -0,0 +1,61 @@
+//! `rename`: rename every key whose name matches a regular expression, e.g. after a team rename.
+
+use super::update::{apply_changes, renames};
+use super::{supported_key_type, FilterArgs};
+use crate::context::ExecutionContext;
+use crate::filter;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Regular expression to find in key names
+    #[arg(long = "match", value_name = "REGEX")]
+    pub pattern: String,
+
+    /// Replacement for each match; $1 or ${name} insert capture groups
+    #[arg(long, value_name = "TEXT")]
+    pub replace: String,
+
+    /// Key type (INGEST or USER)
+    #[arg(short = 't', long, default_value = "INGEST")]
+    pub key_type: String,
+
+    /// Account whose keys to rename (repeatable; default: account_id of the selected
+    /// profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Rename the keys of every account in this group from `account_groups`
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Print the old and new names without renaming anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        pattern,
+        replace,
+        key_type,
+        account_id,
+        account_group,
+        filter,
+        ..
+    } = args;
+    let pattern = regex::Regex::new(&pattern)
+        .map_err(|e| anyhow::anyhow!("Invalid --match pattern '{}': {}", pattern, e))?;
+    let key_type = supported_key_type(&key_type)?;
+    let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+    let filter = filter.parse(ctx.config)?;
+    let keys = filter::fetch(ctx.client()?, &account_ids, &[&key_type], &filter).await?;
+    let changes = renames(&keys, &key_type, &pattern, &replace);
+    apply_changes(ctx, &key_type, keys.len(), changes).await
+}
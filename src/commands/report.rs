@@ -0,0 +1,136 @@
+//! `report`: generate reports about the keys in one or more accounts.
+
+use std::path::PathBuf;
+
+use super::Globals;
+use crate::context::ExecutionContext;
+use crate::{consumption, report};
+
+#[derive(clap::Subcommand)]
+pub(crate) enum ReportCommands {
+    /// Every key with per-account summaries and an age histogram, for access reviews
+    Inventory {
+        /// Account to include (repeatable; default: account_id of the selected profile)
+        #[arg(short, long)]
+        account_id: Vec<i64>,
+
+        /// Include every account in this group from `account_groups` in the config
+        #[arg(short = 'g', long, conflicts_with = "account_id")]
+        account_group: Option<String>,
+
+        /// Report format: html, json, table or csv (default: the global output format)
+        #[arg(short, long)]
+        format: Option<String>,
+
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Key counts by type and age, accounts without keys and accounts over a key-count
+    /// threshold, for management reporting
+    Summary {
+        /// Account to include (repeatable; default: account_id of the selected profile)
+        #[arg(short, long)]
+        account_id: Vec<i64>,
+
+        /// Include every account in this group from `account_groups` in the config
+        #[arg(short = 'g', long, conflicts_with = "account_id")]
+        account_group: Option<String>,
+
+        /// Flag accounts with more keys than this
+        #[arg(long, value_name = "COUNT")]
+        max_keys_per_account: Option<usize>,
+
+        /// Report format: html, json, table or csv (default: the global output format)
+        #[arg(short, long)]
+        format: Option<String>,
+
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Estimate how much of each account's ingest (NrConsumption) each license and browser key
+    /// is responsible for
+    UsageByKey {
+        /// Account to include (repeatable; default: account_id of the selected profile)
+        #[arg(short, long)]
+        account_id: Vec<i64>,
+
+        /// Include every account in this group from `account_groups` in the config
+        #[arg(short = 'g', long, conflicts_with = "account_id")]
+        account_group: Option<String>,
+
+        /// How far back to look: 30m, 12h, 7d, 2w or an NRQL SINCE expression
+        #[arg(long, default_value = "30d")]
+        since: String,
+    },
+}
+
+/// The `--format` of a report subcommand, falling back to the global format.
+fn report_format_of(
+    ctx: &ExecutionContext<'_>,
+    report_format: Option<&str>,
+) -> anyhow::Result<report::ReportFormat> {
+    match report_format {
+        Some(report_format) => report::ReportFormat::parse(report_format),
+        None if ctx.template.is_some() => Ok(report::ReportFormat::Output(ctx.format()?)),
+        None => report::ReportFormat::parse(&ctx.format),
+    }
+}
+
+pub(crate) async fn run(
+    command: ReportCommands,
+    ctx: &ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    match command {
+        ReportCommands::Inventory {
+            account_id,
+            account_group,
+            format: report_format,
+            output,
+        } => {
+            let report_format = report_format_of(ctx, report_format.as_deref())?;
+            let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+            report::inventory(
+                ctx.client()?,
+                &account_ids,
+                ctx.config.policies.max_key_age_days,
+                report_format,
+                output.as_deref(),
+                globals.pager,
+            )
+            .await?;
+        }
+        ReportCommands::Summary {
+            account_id,
+            account_group,
+            max_keys_per_account,
+            format: report_format,
+            output,
+        } => {
+            let report_format = report_format_of(ctx, report_format.as_deref())?;
+            let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+            report::summary(
+                ctx.client()?,
+                &account_ids,
+                max_keys_per_account,
+                report_format,
+                output.as_deref(),
+                globals.pager,
+            )
+            .await?;
+        }
+        ReportCommands::UsageByKey {
+            account_id,
+            account_group,
+            since,
+        } => {
+            let format = ctx.format()?;
+            let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+            let usage = consumption::usage_by_key(ctx.client()?, &account_ids, &since).await?;
+            consumption::print(&usage, format)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+//! `rotate`: replace a key with a new one of the same type, account, name and notes.
+
+use super::{confirm_selected, FilterArgs, Globals};
+use crate::context::ExecutionContext;
+use crate::{inventory, paths, rotation, scheduler, time};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Key ID (repeatable to rotate several keys)
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["selector", "where", "filter_name"],
+        conflicts_with_all = ["selector", "where", "filter_name"]
+    )]
+    pub key_id: Vec<String>,
+
+    /// Key type (INGEST or USER)
+    #[arg(short = 't', long, default_value = "INGEST")]
+    pub key_type: String,
+
+    /// Keep the old key active instead of deleting it
+    #[arg(long)]
+    pub keep_old: bool,
+
+    /// Create every replacement before deleting any old key, and delete the replacements
+    /// again if one fails or the run is interrupted
+    #[arg(long)]
+    pub atomic: bool,
+
+    /// Use each replacement once before the old key is deleted: a NerdGraph query for user
+    /// keys, a test event for license keys. A replacement that fails is deleted again
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Keep the old key for this long (e.g. 7d) and let `scheduler run` delete it then
+    #[arg(long, value_name = "AGE", conflicts_with = "keep_old")]
+    pub delete_after: Option<String>,
+
+    /// Account whose matching keys to rotate (repeatable; default: account_id of the
+    /// selected profile)
+    #[arg(short, long, requires = "FilterArgs")]
+    pub account_id: Vec<i64>,
+
+    /// Rotate the matching keys of every account in this group from `account_groups`
+    #[arg(
+        short = 'g',
+        long,
+        conflicts_with = "account_id",
+        requires = "FilterArgs"
+    )]
+    pub account_group: Option<String>,
+
+    /// Skip the confirmation prompt for the keys that match the filters
+    #[arg(short, long)]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+/// Queue the deletion of every rotated key's old key `delay` from now.
+fn schedule_deletions(
+    paths: &paths::Paths,
+    rotations: &[&rotation::Rotation],
+    delay: chrono::Duration,
+) -> anyhow::Result<()> {
+    let now = chrono::Utc::now();
+    for rotation in rotations {
+        scheduler::schedule(
+            &paths.schedule_file(),
+            scheduler::Deletion {
+                key_id: rotation.old_key_id.clone(),
+                key_type: rotation.new_key.key_type.clone().unwrap_or_default(),
+                account_id: rotation.new_key.account_id,
+                name: rotation.new_key.name.clone(),
+                replaced_by: Some(rotation.new_key.id.clone()),
+                scheduled_at: now,
+                delete_after: now + delay,
+                last_error: None,
+            },
+        )?;
+        eprintln!(
+            "Scheduled the deletion of {} for {}",
+            rotation.old_key_id,
+            (now + delay).format("%Y-%m-%d %H:%M UTC")
+        );
+    }
+    Ok(())
+}
+
+pub(crate) async fn run(
+    args: Args,
+    ctx: &ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    let Args {
+        key_id,
+        key_type,
+        keep_old,
+        atomic,
+        verify,
+        delete_after,
+        account_id,
+        account_group,
+        filter,
+        ..
+    } = args;
+    ctx.secrets.check()?;
+    if key_id.len() == 1 && !atomic {
+        if let Some(protection) = globals.protection {
+            protection
+                .check_rotation(ctx.client()?, &key_id, &key_type.to_uppercase())
+                .await?;
+        }
+        let delete_after = delete_after.as_deref().map(time::parse_age).transpose()?;
+        let rotation = rotation::rotate_verified(
+            ctx.client()?,
+            &key_id[0],
+            &key_type.to_uppercase(),
+            keep_old || delete_after.is_some(),
+            verify,
+            async |keys: &[&inventory::ApiKey]| ctx.secrets.publish("Rotated API key", keys).await,
+        )
+        .await?;
+        if let Some(delay) = delete_after {
+            schedule_deletions(ctx.paths, &[&rotation], delay)?;
+        }
+        ctx.secrets.print(&rotation, &[&rotation.new_key])?;
+        if let Some(error) = &rotation.delete_error {
+            return Err(anyhow::anyhow!(
+                "Created {} but could not delete {}: {}",
+                rotation.new_key.id,
+                rotation.old_key_id,
+                error
+            ));
+        }
+        return Ok(());
+    }
+    let key_id = if key_id.is_empty() {
+        let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+        let filter = filter.parse(ctx.config)?;
+        let keys = confirm_selected(ctx, &account_ids, &key_type, &filter, "Rotate").await?;
+        if keys.is_empty() {
+            return Ok(());
+        }
+        keys.into_iter().map(|key| key.id).collect()
+    } else {
+        key_id
+    };
+    if let Some(protection) = globals.protection {
+        protection
+            .check_rotation(ctx.client()?, &key_id, &key_type.to_uppercase())
+            .await?;
+    }
+    let delete_after = delete_after.as_deref().map(time::parse_age).transpose()?;
+    let options = rotation::BulkOptions {
+        keep_old: keep_old || delete_after.is_some(),
+        atomic,
+        verify,
+    };
+    let bulk = rotation::rotate_all(
+        ctx.client()?,
+        &key_id,
+        &key_type.to_uppercase(),
+        options,
+        || globals.cancellation.reason().is_some(),
+        async |keys: &[&inventory::ApiKey]| ctx.secrets.publish("Rotated API keys", keys).await,
+    )
+    .await;
+    if let Some(delay) = delete_after {
+        let rotated: Vec<&rotation::Rotation> = bulk.rotated.iter().collect();
+        schedule_deletions(ctx.paths, &rotated, delay)?;
+    }
+    let new_keys: Vec<&inventory::ApiKey> = bulk
+        .rotated
+        .iter()
+        .map(|rotation| &rotation.new_key)
+        .collect();
+    ctx.secrets.print(&bulk, &new_keys)?;
+    eprintln!("Rotation summary: {}", bulk.summary());
+    for failed in &bulk.failed {
+        eprintln!("  {}: {}", failed.key_id, failed.error);
+    }
+    for error in &bulk.rollback_errors {
+        eprintln!("  rollback error: {}", error);
+    }
+    if let Some(reason) = globals.cancellation.reason() {
+        return Err(anyhow::anyhow!("Rotation {}: {}", reason, bulk.summary()));
+    }
+    if !bulk.is_complete() {
+        return Err(anyhow::anyhow!("Rotation incomplete: {}", bulk.summary()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//! `scan`: find New Relic keys hardcoded in files and report which are live keys.
+
+use std::path::PathBuf;
+
+use crate::context::ExecutionContext;
+use crate::scan;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Directory or file to scan
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+
+    /// Account whose keys to match against (repeatable; default: account_id of the selected
+    /// profile)
+    #[arg(short, long)]
+    pub account_id: Vec<i64>,
+
+    /// Match against every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    pub account_group: Option<String>,
+
+    /// Scan the files staged for the next commit of the repository at PATH
+    #[arg(long)]
+    pub staged: bool,
+
+    /// Ignore findings in files matching this glob, relative to PATH (repeatable; adds to
+    /// `scan.allow_paths` in the config)
+    #[arg(long, value_name = "GLOB")]
+    pub allow: Vec<String>,
+
+    /// Rotate the live keys that were found, after confirmation
+    #[arg(long)]
+    pub rotate: bool,
+
+    /// Keep the old keys active when rotating
+    #[arg(long, requires = "rotate")]
+    pub keep_old: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long, requires = "rotate")]
+    pub yes: bool,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        path,
+        account_id,
+        account_group,
+        staged,
+        allow,
+        rotate,
+        keep_old,
+        ..
+    } = args;
+    let mut allow_paths = ctx.config.scan.allow_paths.clone();
+    allow_paths.extend(allow);
+    scan::run(
+        || {
+            let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+            Ok((ctx.client()?, account_ids))
+        },
+        &path,
+        ctx.format()?,
+        &ctx.secrets,
+        scan::ScanOptions {
+            staged,
+            allow_paths,
+            allow_key_ids: ctx.config.scan.allow_key_ids.clone(),
+            rotate,
+            keep_old,
+            confirmation: ctx.confirmation,
+        },
+    )
+    .await
+}
@@ -0,0 +1,70 @@
+//! `scheduler`: run, list or cancel the deletions queued by `rotate --delete-after`.
+
+use crate::context::ExecutionContext;
+use crate::scheduler;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum SchedulerCommands {
+    /// Delete the keys whose grace period is over; run it from cron or a systemd timer
+    Run {
+        /// Only list the keys that are due
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the queued deletions
+    List,
+    /// Keep a key by withdrawing its queued deletion
+    Cancel {
+        /// Key ID
+        key_id: String,
+    },
+}
+
+pub(crate) async fn run(
+    command: SchedulerCommands,
+    ctx: &ExecutionContext<'_>,
+) -> anyhow::Result<()> {
+    let schedule_file = ctx.paths.schedule_file();
+    match command {
+        SchedulerCommands::Run { dry_run } => {
+            let run =
+                scheduler::run(ctx.client()?, &schedule_file, chrono::Utc::now(), dry_run).await?;
+            let verb = if dry_run { "Due" } else { "Deleted" };
+            for key_id in &run.deleted {
+                println!("{} {}", verb, key_id);
+            }
+            for failed in &run.failed {
+                println!(
+                    "Could not delete {}: {}",
+                    failed.key_id,
+                    failed.last_error.as_deref().unwrap_or_default()
+                );
+            }
+            println!(
+                "{} {}, {} failed, {} not due yet",
+                run.deleted.len(),
+                verb.to_lowercase(),
+                run.failed.len(),
+                run.waiting
+            );
+            if !run.failed.is_empty() {
+                anyhow::bail!(
+                    "{} scheduled deletion(s) failed and stay queued",
+                    run.failed.len()
+                );
+            }
+        }
+        SchedulerCommands::List => {
+            scheduler::print(&scheduler::load(&schedule_file)?, ctx.format()?)?;
+        }
+        SchedulerCommands::Cancel { key_id } => match scheduler::cancel(&schedule_file, &key_id)? {
+            Some(cancelled) => println!(
+                "Cancelled the deletion of {} scheduled for {}",
+                cancelled.key_id,
+                cancelled.delete_after.format("%Y-%m-%d %H:%M UTC")
+            ),
+            None => anyhow::bail!("No deletion of {} is scheduled", key_id),
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! `snapshot`: save inventory snapshots and compare the current keys with them.
+
+use crate::context::ExecutionContext;
+use crate::snapshot;
+
+#[derive(clap::Subcommand)]
+pub(crate) enum SnapshotCommands {
+    /// Save the current keys, without their secrets, replacing the previous snapshot
+    Save {
+        #[command(flatten)]
+        snapshot: SnapshotArgs,
+    },
+    /// Show the keys added, removed or renamed since the snapshot was saved
+    Diff {
+        #[command(flatten)]
+        snapshot: SnapshotArgs,
+
+        /// Replace the snapshot with the current keys afterwards
+        #[arg(long)]
+        update: bool,
+    },
+}
+
+/// Accounts and store shared by the `snapshot` subcommands.
+#[derive(clap::Args)]
+pub(crate) struct SnapshotArgs {
+    /// Account to include (repeatable; default: account_id of the selected profile)
+    #[arg(short, long)]
+    account_id: Vec<i64>,
+
+    /// Include every account in this group from `account_groups` in the config
+    #[arg(short = 'g', long, conflicts_with = "account_id")]
+    account_group: Option<String>,
+
+    /// Where snapshots are kept: a directory, s3://<bucket>/<prefix> or gs://<bucket>/<prefix>
+    /// (default: snapshot_store from the config, or the data directory)
+    #[arg(long, env = "NEW_RELIC_SNAPSHOT_STORE")]
+    store: Option<String>,
+
+    /// Name of the snapshot in the store
+    #[arg(long, default_value = snapshot::DEFAULT_NAME)]
+    name: String,
+}
+
+pub(crate) async fn run(
+    command: SnapshotCommands,
+    ctx: &ExecutionContext<'_>,
+) -> anyhow::Result<()> {
+    let (snapshot, update) = match command {
+        SnapshotCommands::Save { snapshot } => (snapshot, None),
+        SnapshotCommands::Diff { snapshot, update } => (snapshot, Some(update)),
+    };
+    let account_ids = ctx.account_ids(snapshot.account_id, snapshot.account_group.as_deref())?;
+    let store = snapshot::open(
+        snapshot
+            .store
+            .as_deref()
+            .or(ctx.config.snapshot_store.as_deref()),
+    )?;
+    match update {
+        None => snapshot::save(ctx.client()?, &*store, &snapshot.name, &account_ids).await?,
+        Some(update) => {
+            snapshot::compare(
+                ctx.client()?,
+                &*store,
+                &snapshot.name,
+                &account_ids,
+                update,
+                ctx.format()?,
+            )
+            .await?
+        }
+    }
+    Ok(())
+}
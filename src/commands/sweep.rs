@@ -0,0 +1,150 @@
+//! `sweep`: revoke keys as part of offboarding.
+
+use std::path::PathBuf;
+
+use crate::context::ExecutionContext;
+use crate::prompt::Confirmation;
+use crate::{config, inventory, sweep};
+
+#[derive(clap::Subcommand)]
+pub(crate) enum SweepCommands {
+    /// Delete the user keys of users who left, and write a report of what was deleted
+    DepartedUsers {
+        /// CSV file with the IDs of departed users in its first column
+        #[arg(
+            long,
+            value_name = "PATH",
+            required_unless_present_any = ["user_id", "not_in_organization"]
+        )]
+        user_ids_file: Option<PathBuf>,
+
+        /// ID of a departed user (repeatable)
+        #[arg(long)]
+        user_id: Vec<i64>,
+
+        /// Also treat owners that are no longer users of the organization as departed. When
+        /// these make up more than 20% of the user keys, --yes is refused unless
+        /// --user-ids-file is given
+        #[arg(long)]
+        not_in_organization: bool,
+
+        /// Account to sweep (repeatable; default: account_id of the selected profile)
+        #[arg(short, long)]
+        account_id: Vec<i64>,
+
+        /// Sweep every account in this group from `account_groups` in the config
+        #[arg(short = 'g', long, conflicts_with = "account_id")]
+        account_group: Option<String>,
+
+        /// Print the keys that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Where to write the JSON report (default: the reports directory)
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+}
+
+pub(crate) async fn run(command: SweepCommands, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    match command {
+        SweepCommands::DepartedUsers {
+            user_ids_file,
+            user_id,
+            not_in_organization,
+            account_id,
+            account_group,
+            report,
+            ..
+        } => {
+            let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+            let mut departed = sweep::Departed {
+                listed: user_id.into_iter().collect(),
+                organization: None,
+            };
+            if let Some(path) = &user_ids_file {
+                departed.listed.extend(sweep::read_user_ids(path)?);
+            }
+            if not_in_organization {
+                departed.organization = Some(sweep::organization_user_ids(ctx.client()?).await?);
+            }
+            let keys = inventory::fetch(ctx.client()?, &account_ids, &["USER"]).await?;
+            let mut swept = sweep::select(&keys, &departed);
+            if swept.keys.is_empty() {
+                println!("No keys of departed users found");
+                return Ok(());
+            }
+            println!(
+                "{} key(s) of {} departed user(s):",
+                swept.keys.len(),
+                swept.departed_users.len()
+            );
+            for key in &swept.keys {
+                println!(
+                    "  {}  account {:<10}  user {:<10}  {}  ({})",
+                    key.id,
+                    key.account_id.map(|id| id.to_string()).unwrap_or_default(),
+                    key.user_id,
+                    key.name.as_deref().unwrap_or("N/A"),
+                    key.reason
+                );
+            }
+            let write_report = |swept: &sweep::Report| -> anyhow::Result<()> {
+                let path = report.clone().unwrap_or_else(|| {
+                    ctx.paths.reports_dir().join(format!(
+                        "sweep-departed-users-{}.json",
+                        swept.generated_at.format("%Y%m%dT%H%M%SZ")
+                    ))
+                });
+                config::write_private(&path, &serde_json::to_string_pretty(swept)?)?;
+                println!("Report written to {}", path.display());
+                Ok(())
+            };
+            // Owners missing from a partial user list look departed too.
+            let unlisted = swept.mostly_unlisted() && user_ids_file.is_none();
+            if unlisted {
+                eprintln!(
+                    "More than {}% of the {} user key(s) would go only because their owners \
+                         are missing from the organization's user list",
+                    sweep::MAX_UNLISTED_PERCENT,
+                    swept.user_keys
+                );
+            }
+            if ctx.dry_run {
+                println!("Dry run: {} key(s) would be deleted", swept.keys.len());
+                if report.is_some() {
+                    write_report(&swept)?;
+                }
+                return Ok(());
+            }
+            if unlisted && ctx.confirmation == Confirmation::Assume {
+                anyhow::bail!(
+                    "Refusing to sweep this many keys with --yes: list the departed users \
+                         with --user-ids-file, or run without --yes to confirm"
+                );
+            }
+            if !ctx.confirm(&format!("Delete {} key(s)?", swept.keys.len()))? {
+                println!("Nothing deleted");
+                return Ok(());
+            }
+            sweep::revoke(ctx.client()?, &mut swept).await?;
+            println!("Deleted {} of {} key(s)", swept.deleted(), swept.keys.len());
+            write_report(&swept)?;
+            if swept.deleted() < swept.keys.len() {
+                for key in swept.keys.iter().filter(|key| !key.deleted) {
+                    eprintln!("  {}: {}", key.id, key.error.as_deref().unwrap_or_default());
+                }
+                anyhow::bail!(
+                    "Deleted {} of {} key(s) of departed users",
+                    swept.deleted(),
+                    swept.keys.len()
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! `transfer`: move a user key to another user: create it again under --to-user with the new owner
+//! in its notes, then delete the old key.
+
+use super::Globals;
+use crate::context::ExecutionContext;
+use crate::{inventory, transfer};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// User key ID
+    #[arg(short, long)]
+    pub key_id: String,
+
+    /// User who owns the new key
+    #[arg(long, value_name = "USER_ID")]
+    pub to_user: i64,
+
+    /// Owner label written to the notes as `owner:<OWNER>` (default: the new user ID)
+    #[arg(long)]
+    pub owner: Option<String>,
+
+    /// Keep the old key active instead of deleting it
+    #[arg(long)]
+    pub keep_old: bool,
+
+    /// Print the key that would be created without creating it
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+pub(crate) async fn run(
+    args: Args,
+    ctx: &ExecutionContext<'_>,
+    globals: &Globals<'_>,
+) -> anyhow::Result<()> {
+    let Args {
+        key_id,
+        to_user,
+        owner,
+        keep_old,
+        ..
+    } = args;
+    ctx.secrets.check()?;
+    if let (Some(protection), false) = (globals.protection, keep_old) {
+        protection
+            .check_rotation(ctx.client()?, std::slice::from_ref(&key_id), "USER")
+            .await?;
+    }
+    let key = inventory::get(ctx.client()?, &key_id, "USER").await?;
+    let spec = transfer::plan(&key, to_user, owner.as_deref())?;
+    eprintln!(
+        "  {}  {}  (user {} -> {})",
+        key.id,
+        spec.name,
+        key.user_id
+            .map_or_else(|| "unknown".to_string(), |id| id.to_string()),
+        to_user
+    );
+    eprintln!("  notes: {}", spec.notes.as_deref().unwrap_or_default());
+    if ctx.dry_run {
+        println!(
+            "Dry run: 1 key would be created{}",
+            if keep_old { "" } else { " and 1 deleted" }
+        );
+        return Ok(());
+    }
+    let question = if keep_old {
+        format!("Create a copy of {} for user {}?", key.id, to_user)
+    } else {
+        format!("Transfer {} to user {} and delete it?", key.id, to_user)
+    };
+    if !ctx.confirm(&question)? {
+        println!("Nothing changed");
+        return Ok(());
+    }
+    let transfer = transfer::transfer(
+        ctx.client()?,
+        &key,
+        &spec,
+        keep_old,
+        async |keys: &[&inventory::ApiKey]| ctx.secrets.publish("Transferred API key", keys).await,
+    )
+    .await?;
+    ctx.secrets.print(&transfer, &[&transfer.new_key])?;
+    if let Some(error) = &transfer.delete_error {
+        return Err(anyhow::anyhow!(
+            "Created {} but could not delete {}: {}",
+            transfer.new_key.id,
+            transfer.old_key_id,
+            error
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! `update`: rename a key or change its notes by `--key-id`. Updating every key a filter selects,
+//! and `rename --match`, preview the changes and ask first with [`apply_changes`].
+
+use regex::Regex;
+
+use super::{supported_key_type, Command, FilterArgs, Request};
+use crate::context::ExecutionContext;
+use crate::inventory::{ApiKey, KeyChange};
+use crate::{filter, inventory, sink};
+
+pub(crate) struct Update<'a> {
+    pub key_id: String,
+    pub key_type: String,
+    pub name: Option<String>,
+    pub notes: Option<String>,
+    pub secrets: &'a sink::Secrets,
+}
+
+impl Command for Update<'_> {
+    fn build_request(&self) -> anyhow::Result<Request> {
+        Ok(Request {
+            query: inventory::update_query().into(),
+            variables: Some(inventory::update_variables(
+                &self.key_id,
+                &self.key_type,
+                self.name.as_deref(),
+                self.notes.as_deref(),
+            )?),
+        })
+    }
+
+    fn render(&self, data: serde_json::Value) -> anyhow::Result<()> {
+        let key = inventory::parse_update(&self.key_id, &data)?;
+        self.secrets.print(&key, &[&key])
+    }
+}
+
+/// The changes `--rename-template` and `--set-notes` make to `keys`, leaving out keys they
+/// would leave as they are.
+pub(crate) fn changes<'k>(
+    keys: &'k [ApiKey],
+    key_type: &str,
+    rename_template: Option<&str>,
+    notes: Option<&str>,
+) -> Vec<(&'k ApiKey, KeyChange)> {
+    keys.iter()
+        .filter_map(|key| {
+            let name = rename_template
+                .map(|template| rename(template, key, key_type))
+                .filter(|name| key.name.as_deref() != Some(name));
+            let notes = notes
+                .filter(|notes| key.notes.as_deref() != Some(*notes))
+                .map(str::to_string);
+            if name.is_none() && notes.is_none() {
+                return None;
+            }
+            let change = KeyChange {
+                key_id: key.id.clone(),
+                key_type: key_type.to_string(),
+                name,
+                notes,
+            };
+            Some((key, change))
+        })
+        .collect()
+}
+
+/// `rename --match --replace`: the keys whose names `pattern` matches, renamed with every
+/// match replaced (`$1` and `${name}` insert capture groups).
+pub(crate) fn renames<'k>(
+    keys: &'k [ApiKey],
+    key_type: &str,
+    pattern: &Regex,
+    replacement: &str,
+) -> Vec<(&'k ApiKey, KeyChange)> {
+    keys.iter()
+        .filter_map(|key| {
+            let name = key.name.as_deref()?;
+            let renamed = pattern.replace_all(name, replacement);
+            if renamed == name {
+                return None;
+            }
+            let change = KeyChange {
+                key_id: key.id.clone(),
+                key_type: key_type.to_string(),
+                name: Some(renamed.into_owned()),
+                notes: None,
+            };
+            Some((key, change))
+        })
+        .collect()
+}
+
+fn rename(template: &str, key: &ApiKey, key_type: &str) -> String {
+    template
+        .replace("{name}", key.name.as_deref().unwrap_or(""))
+        .replace("{id}", &key.id)
+        .replace(
+            "{account_id}",
+            &key.account_id.map(|id| id.to_string()).unwrap_or_default(),
+        )
+        .replace("{type}", key_type)
+}
+
+/// One line of the preview printed before a batch update.
+pub(crate) fn preview(key: &ApiKey, change: &KeyChange) -> String {
+    let mut line = format!(
+        "  {}  (account {})",
+        key.id,
+        key.account_id.unwrap_or_default()
+    );
+    let old_name = key.name.as_deref().unwrap_or("-");
+    match &change.name {
+        Some(name) => line.push_str(&format!("  name: {} -> {}", old_name, name)),
+        None => line.push_str(&format!("  {}", old_name)),
+    }
+    if let Some(notes) = &change.notes {
+        line.push_str(&format!("  notes: {:?}", notes));
+    }
+    line
+}
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Key ID
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["selector", "where", "filter_name"],
+        conflicts_with_all = ["selector", "where", "filter_name"]
+    )]
+    pub key_id: Option<String>,
+
+    /// Key type (INGEST or USER)
+    #[arg(short = 't', long, default_value = "INGEST")]
+    pub key_type: String,
+
+    /// New name
+    #[arg(short, long, conflicts_with = "rename_template")]
+    pub name: Option<String>,
+
+    /// New notes/description
+    #[arg(long, visible_alias = "set-notes")]
+    pub notes: Option<String>,
+
+    /// Rename every matching key after this template; {name}, {id}, {account_id} and
+    /// {type} are replaced with the key's current values, e.g. 'legacy-{name}'
+    #[arg(long, value_name = "TEMPLATE", requires = "FilterArgs")]
+    pub rename_template: Option<String>,
+
+    /// Account whose matching keys to update (repeatable; default: account_id of the
+    /// selected profile)
+    #[arg(short, long, requires = "FilterArgs")]
+    pub account_id: Vec<i64>,
+
+    /// Update the matching keys of every account in this group from `account_groups`
+    #[arg(
+        short = 'g',
+        long,
+        conflicts_with = "account_id",
+        requires = "FilterArgs"
+    )]
+    pub account_group: Option<String>,
+
+    /// Print the changes without making them
+    #[arg(long, requires = "FilterArgs")]
+    pub dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        key_id,
+        key_type,
+        name,
+        notes,
+        rename_template,
+        account_id,
+        account_group,
+        filter,
+        ..
+    } = args;
+    if let Some(key_id) = key_id {
+        let update = Update {
+            key_id,
+            key_type,
+            name,
+            notes,
+            secrets: &ctx.secrets,
+        };
+        return super::execute(&update, ctx).await;
+    }
+    if notes.is_none() && rename_template.is_none() {
+        anyhow::bail!("Nothing to change: pass --set-notes and/or --rename-template");
+    }
+    let key_type = supported_key_type(&key_type)?;
+    let account_ids = ctx.account_ids(account_id, account_group.as_deref())?;
+    let filter = filter.parse(ctx.config)?;
+    let keys = filter::fetch(ctx.client()?, &account_ids, &[&key_type], &filter).await?;
+    let changes = changes(
+        &keys,
+        &key_type,
+        rename_template.as_deref(),
+        notes.as_deref(),
+    );
+    apply_changes(ctx, &key_type, keys.len(), changes).await
+}
+
+/// Preview `changes` to the `matched` selected keys, ask, and make them in one mutation.
+pub(crate) async fn apply_changes(
+    ctx: &ExecutionContext<'_>,
+    key_type: &str,
+    matched: usize,
+    changes: Vec<(&inventory::ApiKey, inventory::KeyChange)>,
+) -> anyhow::Result<()> {
+    if changes.is_empty() {
+        println!(
+            "{}",
+            if matched == 0 {
+                "No keys match"
+            } else {
+                "Nothing to change"
+            }
+        );
+        return Ok(());
+    }
+    for (key, change) in &changes {
+        eprintln!("{}", preview(key, change));
+    }
+    if ctx.dry_run {
+        println!("Dry run: {} key(s) would be updated", changes.len());
+        return Ok(());
+    }
+    let question = format!("Update {} {} key(s)?", changes.len(), key_type);
+    if !ctx.confirm(&question)? {
+        println!("Nothing changed");
+        return Ok(());
+    }
+    let changes: Vec<inventory::KeyChange> =
+        changes.into_iter().map(|(_, change)| change).collect();
+    let outcome = inventory::update_keys(ctx.client()?, &changes).await?;
+    let updated: Vec<&inventory::ApiKey> = outcome.updated.iter().collect();
+    ctx.secrets.print(&outcome, &updated)?;
+    if !outcome.errors.is_empty() {
+        anyhow::bail!(
+            "Updated {} of {} key(s): {}",
+            outcome.updated.len(),
+            changes.len(),
+            outcome.errors.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, name: &str, notes: &str) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "name": name, "notes": notes, "type": "USER", "accountId": 1
+        }))
+    }
+
+    #[test]
+    fn test_changes_skip_keys_already_up_to_date() {
+        let keys = [
+            key("K1", "ci", "team:payments"),
+            key("K2", "legacy-ci", "owned by platform team"),
+        ];
+        let planned = changes(
+            &keys,
+            "USER",
+            Some("legacy-{name}-{account_id}"),
+            Some("owned by platform team"),
+        );
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].1.name.as_deref(), Some("legacy-ci-1"));
+        assert_eq!(
+            planned[0].1.notes.as_deref(),
+            Some("owned by platform team")
+        );
+        assert_eq!(planned[1].1.notes, None);
+        assert_eq!(
+            preview(planned[0].0, &planned[0].1),
+            "  K1  (account 1)  name: ci -> legacy-ci-1  notes: \"owned by platform team\""
+        );
+
+        let planned = changes(&keys, "USER", None, Some("owned by platform team"));
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].0.id, "K1");
+    }
+
+    #[test]
+    fn test_renames_use_capture_groups() {
+        let keys = [
+            key("K1", "old-team-ingest", ""),
+            key("K2", "old-team-browser", ""),
+            key("K3", "platform", ""),
+        ];
+        let pattern = Regex::new("^old-team-(\\w+)$").unwrap();
+        let planned = renames(&keys, "INGEST", &pattern, "new-team-$1");
+        let names: Vec<_> = planned
+            .iter()
+            .map(|(_, change)| change.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(names, ["new-team-ingest", "new-team-browser"]);
+        assert!(planned.iter().all(|(_, change)| change.notes.is_none()));
+    }
+}
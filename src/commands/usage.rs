@@ -0,0 +1,30 @@
+//! `usage`: show when a key last changed and last appeared in an ingest error (not when it was last
+//! used: New Relic does not meter use per key).
+
+use crate::context::ExecutionContext;
+use crate::usage;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Key ID
+    #[arg(short, long)]
+    pub key_id: String,
+
+    /// Account to query (default: account_id of the selected profile)
+    #[arg(short, long)]
+    pub account_id: Option<i64>,
+
+    /// How many days back to look
+    #[arg(long, default_value_t = 30)]
+    pub since_days: u32,
+}
+
+pub(crate) async fn run(args: Args, ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let Args {
+        key_id,
+        account_id,
+        since_days,
+    } = args;
+    let account_id = ctx.account_id(account_id)?;
+    usage::report(ctx.client()?, account_id, &key_id, since_days).await
+}
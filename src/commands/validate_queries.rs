@@ -0,0 +1,38 @@
+//! `validate-queries`: check every built-in query and mutation against the bundled NerdGraph schema
+//! snapshot.
+
+use crate::contract;
+
+pub(crate) fn run() -> anyhow::Result<()> {
+    let schema = contract::Schema::parse(contract::SCHEMA)?;
+    let mut checks: Vec<(String, Vec<String>)> = contract::builtin_queries()
+        .into_iter()
+        .map(|(name, document)| (name.to_string(), contract::validate(&schema, &document)))
+        .collect();
+    for (name, document, variables) in contract::builtin_variables()? {
+        checks.push((
+            format!("{} (variables)", name),
+            contract::validate_variables(&schema, &document, &variables),
+        ));
+    }
+    let mut failed = 0;
+    for (name, problems) in &checks {
+        if problems.is_empty() {
+            println!("ok   {}", name);
+        } else {
+            failed += 1;
+            println!("FAIL {}", name);
+            for problem in problems {
+                println!("       {}", problem);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} built-in queries do not match the schema snapshot",
+            failed,
+            checks.len()
+        ));
+    }
+    Ok(())
+}
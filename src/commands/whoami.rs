@@ -0,0 +1,309 @@
+//! `whoami`: show the authenticated user, their roles and whether they can read API keys.
+
+use crate::context::ExecutionContext;
+use crate::{fetch_identity, key_type_from_prefix, Identity, NewRelicClient, Variables};
+
+pub(crate) fn print_identity(identity: &Identity) {
+    match &identity.actor.user {
+        Some(user) => println!(
+            "User: {} <{}> (id: {})",
+            user.name.as_deref().unwrap_or("N/A"),
+            user.email,
+            user.id
+        ),
+        None => println!("User: N/A"),
+    }
+    match &identity.actor.organization {
+        Some(org) => println!(
+            "Organization: {} (id: {})",
+            org.name.as_deref().unwrap_or("N/A"),
+            org.id
+        ),
+        None => println!("Organization: N/A"),
+    }
+}
+
+struct DomainMembership {
+    domain_id: String,
+    domain_name: String,
+    user_type: Option<String>,
+    groups: Vec<(String, String)>,
+}
+
+/// Find the authentication domains (and groups within them) the given user belongs to.
+fn parse_domain_memberships(data: &serde_json::Value) -> Vec<DomainMembership> {
+    let domains = data["actor"]["organization"]["userManagement"]["authenticationDomains"]
+        ["authenticationDomains"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    let mut memberships = Vec::new();
+    for domain in domains {
+        let users = domain["users"]["users"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for user in users {
+            let groups = user["groups"]["groups"]
+                .as_array()
+                .map(|groups| {
+                    groups
+                        .iter()
+                        .filter_map(|g| {
+                            Some((
+                                g.get("id")?.as_str()?.to_string(),
+                                g.get("displayName")?.as_str()?.to_string(),
+                            ))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            memberships.push(DomainMembership {
+                domain_id: domain["id"].as_str().unwrap_or_default().to_string(),
+                domain_name: domain["name"].as_str().unwrap_or("N/A").to_string(),
+                user_type: user["type"]["displayName"].as_str().map(str::to_string),
+                groups,
+            });
+        }
+    }
+    memberships
+}
+
+async fn fetch_domain_memberships(
+    client: &NewRelicClient,
+    email: &str,
+) -> anyhow::Result<Vec<DomainMembership>> {
+    let query = r#"
+    query($email: String!) {
+        actor {
+            organization {
+                userManagement {
+                    authenticationDomains {
+                        authenticationDomains {
+                            id
+                            name
+                            users(filter: {email: {eq: $email}}) {
+                                users {
+                                    id
+                                    type {
+                                        displayName
+                                    }
+                                    groups {
+                                        groups {
+                                            id
+                                            displayName
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }"#;
+
+    let variables = Variables::new().string("email", email);
+
+    let result = client.execute_query(query, Some(variables)).await?;
+    Ok(parse_domain_memberships(&result))
+}
+
+/// Collect "role name (account)" descriptions for the given groups of an authentication domain.
+async fn fetch_group_roles(
+    client: &NewRelicClient,
+    membership: &DomainMembership,
+) -> anyhow::Result<Vec<String>> {
+    let query = r#"
+    query($domainId: [ID!], $groupIds: [ID!]) {
+        actor {
+            organization {
+                authorizationManagement {
+                    authenticationDomains(id: $domainId) {
+                        authenticationDomains {
+                            groups(id: $groupIds) {
+                                groups {
+                                    displayName
+                                    roles {
+                                        roles {
+                                            name
+                                            accountId
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }"#;
+
+    let variables = Variables::new()
+        .list("domainId", [membership.domain_id.as_str()])
+        .list(
+            "groupIds",
+            membership.groups.iter().map(|(id, _)| id.as_str()),
+        );
+
+    let result = client.execute_query(query, Some(variables)).await?;
+    let mut roles = Vec::new();
+    let domains = result["actor"]["organization"]["authorizationManagement"]
+        ["authenticationDomains"]["authenticationDomains"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    for domain in domains {
+        for group in domain["groups"]["groups"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+        {
+            for role in group["roles"]["roles"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+            {
+                let name = role["name"].as_str().unwrap_or("N/A");
+                match role["accountId"].as_i64() {
+                    Some(account_id) => roles.push(format!("{} (account {})", name, account_id)),
+                    None => roles.push(format!("{} (organization)", name)),
+                }
+            }
+        }
+    }
+    roles.sort();
+    roles.dedup();
+    Ok(roles)
+}
+
+pub(crate) const PROBE_QUERY: &str = r#"
+    query($accountIds: [Int!]) {
+        actor {
+            apiAccess {
+                keySearch(query: {types: [INGEST], scope: {accountIds: $accountIds}}) {
+                    count
+                }
+            }
+        }
+    }"#;
+
+/// Probe whether the key can read API keys by searching for ingest keys. A successful search
+/// does not show that the create/update/delete mutations are allowed.
+async fn probe_api_access(client: &NewRelicClient, account_id: i64) -> anyhow::Result<()> {
+    let variables = Variables::new().list("accountIds", [account_id]);
+
+    client.execute_query(PROBE_QUERY, Some(variables)).await?;
+    Ok(())
+}
+
+pub(crate) async fn run(ctx: &ExecutionContext<'_>) -> anyhow::Result<()> {
+    let client = ctx.client()?;
+    let identity = fetch_identity(client).await?;
+
+    println!("Key type: {}", key_type_from_prefix(client.api_key()));
+    print_identity(&identity);
+
+    if let Some(user) = &identity.actor.user {
+        match fetch_domain_memberships(client, &user.email).await {
+            Ok(memberships) if memberships.is_empty() => {
+                println!("Authentication domain: N/A");
+            }
+            Ok(memberships) => {
+                for membership in &memberships {
+                    println!(
+                        "Authentication domain: {} (id: {})",
+                        membership.domain_name, membership.domain_id
+                    );
+                    println!(
+                        "  User type: {}",
+                        membership.user_type.as_deref().unwrap_or("N/A")
+                    );
+                    let group_names: Vec<&str> = membership
+                        .groups
+                        .iter()
+                        .map(|(_, name)| name.as_str())
+                        .collect();
+                    println!("  Groups: {}", group_names.join(", "));
+                    if membership.groups.is_empty() {
+                        continue;
+                    }
+                    match fetch_group_roles(client, membership).await {
+                        Ok(roles) => println!("  Roles: {}", roles.join(", ")),
+                        Err(e) => println!("  Roles: unavailable ({})", e),
+                    }
+                }
+            }
+            Err(e) => println!("Authentication domain: unavailable ({})", e),
+        }
+    }
+
+    match identity.actor.accounts.first() {
+        Some(account) => match probe_api_access(client, account.id).await {
+            Ok(()) => println!(
+                "API key access: read access (checked against account {}; changing keys was not \
+                 checked)",
+                account.id
+            ),
+            Err(e) => println!(
+                "API key access: no read access for account {} ({})",
+                account.id, e
+            ),
+        },
+        None => println!("API key access: no accessible accounts to check"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_domain_memberships() {
+        let data = serde_json::json!({
+            "actor": {
+                "organization": {
+                    "userManagement": {
+                        "authenticationDomains": {
+                            "authenticationDomains": [
+                                {
+                                    "id": "domain-1",
+                                    "name": "Default",
+                                    "users": {
+                                        "users": [
+                                            {
+                                                "id": "1001",
+                                                "type": {"displayName": "Full platform"},
+                                                "groups": {
+                                                    "groups": [
+                                                        {"id": "g-1", "displayName": "Admin"}
+                                                    ]
+                                                }
+                                            }
+                                        ]
+                                    }
+                                },
+                                {
+                                    "id": "domain-2",
+                                    "name": "SSO",
+                                    "users": {"users": []}
+                                }
+                            ]
+                        }
+                    }
+                }
+            }
+        });
+
+        let memberships = parse_domain_memberships(&data);
+        assert_eq!(memberships.len(), 1);
+        assert_eq!(memberships[0].domain_name, "Default");
+        assert_eq!(memberships[0].user_type.as_deref(), Some("Full platform"));
+        assert_eq!(
+            memberships[0].groups,
+            vec![("g-1".to_string(), "Admin".to_string())]
+        );
+    }
+}
@@ -0,0 +1,1018 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::Format;
+use crate::SecretString;
+pub use crate::{Region, DEFAULT_ENDPOINT};
+
+/// Guard against `!include` cycles.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Contents of `config.toml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profile used when `--profile` is not given
+    pub default_profile: Option<String>,
+    /// Region used when neither the profile nor `--endpoint` selects one
+    pub region: Option<Region>,
+    /// Output format used when neither the profile nor `--format` selects one
+    pub format: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Named lists of account IDs
+    #[serde(default)]
+    pub account_groups: BTreeMap<String, Vec<i64>>,
+    #[serde(default)]
+    pub policies: Policies,
+    /// Shortcuts expanded in place of the subcommand, e.g. `prod-rotate = "rotate --account-group prod"`
+    #[serde(default)]
+    pub alias: BTreeMap<String, String>,
+    #[serde(default)]
+    pub audit_log: AuditLog,
+    /// Named `--template`s, e.g. `tf-import = "import { ... id = \"{{id}}\" }"`
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+    /// Named `--where` expressions for `--filter-name`, e.g. `stale = "age_days > 180"`
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+    #[serde(default)]
+    pub scan: Scan,
+    /// Never print key secrets; commands that create keys need `--secret-sink`
+    #[serde(default)]
+    pub strict_secrets: bool,
+    /// Refuse every command that changes keys, whatever the profile
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub service_accounts: ServiceAccounts,
+    /// Named environments for `provision env` and `decommission env`
+    #[serde(default)]
+    pub environments: BTreeMap<String, Environment>,
+    /// When keys in the accounts of a group may be changed, per group from `account_groups`
+    #[serde(default)]
+    pub maintenance_windows: BTreeMap<String, Vec<MaintenanceWindow>>,
+    #[serde(default)]
+    pub guardrails: Guardrails,
+    /// IDs or names of keys that must not be deleted or rotated
+    #[serde(default)]
+    pub protected_keys: Vec<String>,
+    #[serde(default)]
+    pub onboarding: Onboarding,
+    /// Where `snapshot` keeps inventory snapshots when `--store` is not given, e.g.
+    /// `s3://bucket/prefix`
+    pub snapshot_store: Option<String>,
+    /// Why the references of a profile could not be resolved, per profile
+    #[serde(skip)]
+    pub unresolved: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub api_key: Option<SecretString>,
+    pub region: Option<Region>,
+    pub endpoint: Option<String>,
+    pub format: Option<String>,
+    pub account_id: Option<i64>,
+    /// Further endpoints with their own credentials, queried by `list --all-profiles`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<ProfileEndpoint>,
+    /// Sign in with short-lived session tokens instead of `api_key`
+    pub session: Option<Session>,
+    /// Refuse every command that changes keys, e.g. for a shared reporting credential
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// Subcommands the profile may run, e.g. `["list", "rotate", "scheduler list"]`; every
+    /// subcommand when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_commands: Vec<String>,
+    /// Subcommands the profile must not run, even when `allowed_commands` names them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_commands: Vec<String>,
+}
+
+impl Profile {
+    /// Fail unless the profile may run `command`, a subcommand path such as `scheduler run`.
+    /// An entry covers the subcommands under it: `scheduler` covers `scheduler run`.
+    pub fn check_command(&self, name: &str, command: &str) -> anyhow::Result<()> {
+        let covers = |entry: &String| {
+            let entry: Vec<&str> = entry.split_whitespace().collect();
+            let command: Vec<&str> = command.split_whitespace().collect();
+            !entry.is_empty() && command.starts_with(&entry)
+        };
+        if self.denied_commands.iter().any(covers) {
+            anyhow::bail!(
+                "Profile '{}' may not run `{}` (denied_commands)",
+                name,
+                command
+            );
+        }
+        if !self.allowed_commands.is_empty() && !self.allowed_commands.iter().any(covers) {
+            anyhow::bail!(
+                "Profile '{}' may not run `{}`; allowed_commands: {}",
+                name,
+                command,
+                self.allowed_commands.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Where a profile gets session tokens: a command that prints one, or an OAuth token endpoint
+/// that exchanges a refresh token for one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Session {
+    /// Prints a token, or JSON with `access_token` and optionally `expires_in`
+    pub token_command: Option<String>,
+    /// OAuth token endpoint for the `refresh_token` grant
+    pub refresh_url: Option<String>,
+    pub client_id: Option<String>,
+    /// Initial refresh token; rotated ones returned by the endpoint are cached instead
+    pub refresh_token: Option<SecretString>,
+    /// Lifetime assumed for tokens that do not state one (default: 300)
+    pub ttl_seconds: Option<u64>,
+}
+
+/// Another NerdGraph endpoint of a profile, e.g. the EU side of an org with US and EU accounts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileEndpoint {
+    pub region: Option<Region>,
+    pub endpoint: Option<String>,
+    /// Defaults to the profile's API key
+    pub api_key: Option<SecretString>,
+    #[serde(default)]
+    pub account_ids: Vec<i64>,
+}
+
+impl ProfileEndpoint {
+    pub fn url(&self) -> String {
+        self.endpoint
+            .clone()
+            .or_else(|| self.region.map(|r| r.endpoint().to_string()))
+            .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+    }
+}
+
+/// Key hygiene rules shared by the commands that audit keys.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policies {
+    /// Keys older than this many days are considered due for rotation
+    pub max_key_age_days: Option<u32>,
+    /// Every key must carry notes describing its owner/purpose
+    pub require_notes: Option<bool>,
+    /// Accounts with more keys than this are flagged; a growing count is often a sign of keys
+    /// being leaked and recreated
+    pub max_keys_per_account: Option<usize>,
+    /// Like `max_keys_per_account`, counting only ingest keys
+    pub max_ingest_keys_per_account: Option<usize>,
+    /// Like `max_keys_per_account`, counting only user keys
+    pub max_user_keys_per_account: Option<usize>,
+    /// Webhook notified when `audit`, `policy check` or the daemon finds an account over one of
+    /// the key-count limits
+    pub webhook: Option<String>,
+}
+
+/// Signing of the local command history; paths are relative to the config directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditLog {
+    /// Unencrypted minisign secret key used to sign every new entry
+    pub signing_key: Option<String>,
+    /// Minisign public key used by `audit-log verify` when `--public-key` is not given
+    pub public_key: Option<String>,
+}
+
+/// Findings `scan` and the pre-commit hook accept.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scan {
+    /// Globs of paths, relative to the scanned directory, whose keys are ignored, e.g.
+    /// `tests/fixtures/**`
+    #[serde(default)]
+    pub allow_paths: Vec<String>,
+    /// Keys that may appear in files, e.g. browser keys, which are public by design
+    #[serde(default)]
+    pub allow_key_ids: Vec<String>,
+}
+
+/// Defaults for `create --service-account`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceAccounts {
+    /// Email of new service accounts, with `{name}` for the account name, e.g.
+    /// `newrelic+{name}@example.com`
+    pub email: Option<String>,
+    /// ID or name of the authentication domain to create them in
+    pub authentication_domain: Option<String>,
+    /// basic, core or full (default: basic)
+    pub user_tier: Option<String>,
+}
+
+/// Limits every run of the CLI observes, whatever the command, to contain automation mistakes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Guardrails {
+    /// Refuse to delete more than this many keys in one account per run
+    pub max_deletions_per_account: Option<usize>,
+    /// Refuse to delete the last license or browser key of an account
+    #[serde(default)]
+    pub keep_last_ingest_key: bool,
+    /// Mutations of one account that may be in flight at the same time
+    pub max_concurrent_mutations_per_account: Option<usize>,
+}
+
+/// A stretch of time in which mutations are allowed: it opens whenever the cron expression
+/// `schedule` matches (in UTC) and stays open for `duration`, e.g. `4h`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceWindow {
+    pub schedule: String,
+    pub duration: String,
+}
+
+/// The accounts of an environment and the ingest keys each of them gets.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Environment {
+    /// Group from `account_groups` holding the environment's accounts
+    pub account_group: Option<String>,
+    #[serde(default)]
+    pub account_ids: Vec<i64>,
+    /// Keys created in every account (default: a license and a browser key)
+    #[serde(default)]
+    pub keys: Vec<KeyTemplate>,
+    /// Notified with the report of `decommission env`
+    pub webhook: Option<String>,
+}
+
+/// One key of an environment. `name` and `notes` may use `{env}`, `{account_id}` and
+/// `{ingest_type}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyTemplate {
+    /// LICENSE or BROWSER
+    pub ingest_type: String,
+    pub name: String,
+    pub notes: Option<String>,
+}
+
+/// The standard key set of `onboard customer`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Onboarding {
+    /// Keys created for every customer (default: a license and a browser key); `name` and
+    /// `notes` may use `{customer}`, `{account_id}` and `{ingest_type}`
+    #[serde(default)]
+    pub keys: Vec<KeyTemplate>,
+    /// Where the secrets go when `--secret-sink` is not given; `{customer}` is replaced, e.g.
+    /// `vault:secret/customers/{customer}`
+    pub secret_sink: Option<String>,
+    /// Owner label of the keys when `--owner` is not given
+    pub owner: Option<String>,
+}
+
+impl Config {
+    /// Load the config file, treating a missing file as an empty config.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::parse_in(&contents, &parent_dir(path))
+                .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow::anyhow!(
+                "Unable to read config file {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Parse config contents, expanding `${VAR}`, `!include` and `!file` references relative to
+    /// `base_dir`. A profile whose references cannot be resolved only fails when it is used; see
+    /// [`Config::profile`].
+    pub fn parse_in(contents: &str, base_dir: &Path) -> anyhow::Result<Self> {
+        Self::parse_with(contents, base_dir, false)
+    }
+
+    /// Check the structure of config contents without the environment it will run in: unset
+    /// `${VAR}`s are left as they are.
+    pub fn validate_in(contents: &str, base_dir: &Path) -> anyhow::Result<()> {
+        Self::parse_with(contents, base_dir, true)?;
+        Ok(())
+    }
+
+    fn parse_with(contents: &str, base_dir: &Path, lenient: bool) -> anyhow::Result<Self> {
+        let mut table: toml::Table = contents.parse()?;
+        let lookup = |name: &str| std::env::var(name).ok();
+        let expand = |value| expand(value, base_dir, &lookup, lenient, 0);
+        let profiles = table.remove("profiles");
+        let mut expanded = expand(toml::Value::Table(table))?;
+
+        let mut unresolved = BTreeMap::new();
+        if let Some(profiles) = profiles {
+            // `profiles = "!include profiles.toml"`
+            let profiles = match profiles {
+                toml::Value::String(_) => expand(profiles)?,
+                other => other,
+            };
+            let profiles = match profiles {
+                toml::Value::Table(profiles) => toml::Value::Table(
+                    profiles
+                        .into_iter()
+                        .map(|(name, profile)| match expand(profile) {
+                            Ok(profile) => (name, profile),
+                            Err(e) => {
+                                unresolved.insert(name.clone(), e.to_string());
+                                (name, toml::Value::Table(toml::Table::new()))
+                            }
+                        })
+                        .collect(),
+                ),
+                other => other,
+            };
+            if let toml::Value::Table(table) = &mut expanded {
+                table.insert("profiles".to_string(), profiles);
+            }
+        }
+
+        let mut config: Self = expanded.try_into()?;
+        config.check_formats()?;
+        config.unresolved = unresolved;
+        Ok(config)
+    }
+
+    /// Fail on a `format` that `--format` would not accept.
+    fn check_formats(&self) -> anyhow::Result<()> {
+        let profiles = self
+            .profiles
+            .iter()
+            .map(|(name, profile)| (format!("profiles.{}.format", name), &profile.format));
+        for (key, format) in std::iter::once(("format".to_string(), &self.format)).chain(profiles) {
+            match format.as_deref() {
+                // Needs a --template, which a run may pass along.
+                Some(format) if format.eq_ignore_ascii_case("template") => {}
+                Some(format) => {
+                    Format::parse(format).map_err(|e| anyhow::anyhow!("{}: {}", key, e))?;
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Select the profile named on the command line, falling back to `default_profile`.
+    pub fn profile(&self, name: Option<&str>) -> anyhow::Result<Option<&Profile>> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => {
+                self.check_resolved(name)?;
+                self.profiles
+                    .get(name)
+                    .map(Some)
+                    .ok_or_else(|| anyhow::anyhow!("Profile '{}' not found in config", name))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Fail if the references of profile `name` could not be resolved.
+    pub fn check_resolved(&self, name: &str) -> anyhow::Result<()> {
+        match self.unresolved.get(name) {
+            Some(error) => anyhow::bail!("Profile '{}': {}", name, error),
+            None => Ok(()),
+        }
+    }
+
+    pub fn endpoint(&self, profile: Option<&Profile>) -> String {
+        profile
+            .and_then(|p| p.endpoint.clone())
+            .or_else(|| {
+                profile
+                    .and_then(|p| p.region)
+                    .or(self.region)
+                    .map(|r| r.endpoint().to_string())
+            })
+            .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+    }
+
+    pub fn format(&self, profile: Option<&Profile>) -> String {
+        profile
+            .and_then(|p| p.format.clone())
+            .or_else(|| self.format.clone())
+            .unwrap_or_else(|| "json".to_string())
+    }
+}
+
+/// Raw, dotted-key view of the config file used by the `config` subcommands, so values can be
+/// edited without losing anything the typed [`Config`] does not model yet.
+pub struct ConfigFile {
+    table: toml::Table,
+    base_dir: PathBuf,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let table = match fs::read_to_string(path) {
+            Ok(contents) => contents
+                .parse::<toml::Table>()
+                .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            table,
+            base_dir: parent_dir(path),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&toml::Value> {
+        let mut parts = key.split('.');
+        let mut value = self.table.get(parts.next()?)?;
+        for part in parts {
+            value = value.as_table()?.get(part)?;
+        }
+        Some(value)
+    }
+
+    /// Set a dotted key, parsing `raw` as a TOML value (number, bool, array...) and falling back
+    /// to a plain string.
+    pub fn set(&mut self, key: &str, raw: &str) -> anyhow::Result<()> {
+        let value = parse_value(raw);
+        let parts: Vec<&str> = key.split('.').collect();
+        let (last, parents) = parts
+            .split_last()
+            .filter(|(last, _)| !last.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Invalid config key '{}'", key))?;
+
+        let mut table = &mut self.table;
+        for part in parents {
+            table = table
+                .entry(part.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| anyhow::anyhow!("'{}' is not a table in '{}'", part, key))?;
+        }
+        table.insert(last.to_string(), value);
+        self.validate()
+    }
+
+    pub fn unset(&mut self, key: &str) -> anyhow::Result<()> {
+        let parts: Vec<&str> = key.split('.').collect();
+        let (last, parents) = parts
+            .split_last()
+            .ok_or_else(|| anyhow::anyhow!("Invalid config key '{}'", key))?;
+
+        let mut table = &mut self.table;
+        for part in parents {
+            table = table
+                .get_mut(*part)
+                .and_then(|v| v.as_table_mut())
+                .ok_or_else(|| anyhow::anyhow!("Config key '{}' is not set", key))?;
+        }
+        table
+            .remove(*last)
+            .ok_or_else(|| anyhow::anyhow!("Config key '{}' is not set", key))?;
+        self.validate()
+    }
+
+    /// Every leaf value as `(dotted.key, value)`, sorted by key.
+    pub fn entries(&self) -> Vec<(String, &toml::Value)> {
+        let mut entries = Vec::new();
+        flatten("", &self.table, &mut entries);
+        entries
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        Config::validate_in(&toml::to_string(&self.table)?, &self.base_dir)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        write_private(path, &toml::to_string_pretty(&self.table)?)
+    }
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    path.parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Resolve references inside string values:
+///
+/// - `${VAR}` / `${VAR:-default}` are replaced with environment variables
+/// - `"!include other.toml"` is replaced with the parsed contents of that file
+/// - `"!file path"` is replaced with the (trimmed) contents of that file, e.g. a secret
+///
+/// When `lenient`, a string with an unset variable is left as it is.
+fn expand(
+    value: toml::Value,
+    base_dir: &Path,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    lenient: bool,
+    depth: usize,
+) -> anyhow::Result<toml::Value> {
+    match value {
+        toml::Value::String(s) => {
+            let s = match interpolate(&s, lookup) {
+                Ok(interpolated) => interpolated,
+                Err(_) if lenient => return Ok(toml::Value::String(s)),
+                Err(e) => return Err(e),
+            };
+            if let Some(reference) = s.strip_prefix("!include ") {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(anyhow::anyhow!(
+                        "!include nested more than {} levels deep",
+                        MAX_INCLUDE_DEPTH
+                    ));
+                }
+                let path = resolve_path(reference.trim(), base_dir);
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Unable to include {}: {}", path.display(), e))?;
+                let table: toml::Table = contents.parse().map_err(|e| {
+                    anyhow::anyhow!("Invalid included file {}: {}", path.display(), e)
+                })?;
+                expand(
+                    toml::Value::Table(table),
+                    &parent_dir(&path),
+                    lookup,
+                    lenient,
+                    depth + 1,
+                )
+            } else if let Some(reference) = s.strip_prefix("!file ") {
+                let path = resolve_path(reference.trim(), base_dir);
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Unable to read {}: {}", path.display(), e))?;
+                Ok(toml::Value::String(contents.trim().to_string()))
+            } else {
+                Ok(toml::Value::String(s))
+            }
+        }
+        toml::Value::Array(items) => Ok(toml::Value::Array(
+            items
+                .into_iter()
+                .map(|item| expand(item, base_dir, lookup, lenient, depth))
+                .collect::<anyhow::Result<_>>()?,
+        )),
+        toml::Value::Table(table) => Ok(toml::Value::Table(
+            table
+                .into_iter()
+                .map(|(key, item)| Ok((key, expand(item, base_dir, lookup, lenient, depth)?)))
+                .collect::<anyhow::Result<_>>()?,
+        )),
+        other => Ok(other),
+    }
+}
+
+/// Replace `${VAR}` and `${VAR:-default}` occurrences; `$$` produces a literal `$`.
+fn interpolate(input: &str, lookup: &dyn Fn(&str) -> Option<String>) -> anyhow::Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(stripped) = after.strip_prefix('$') {
+            output.push('$');
+            rest = stripped;
+        } else if let Some(body) = after.strip_prefix('{') {
+            let end = body
+                .find('}')
+                .ok_or_else(|| anyhow::anyhow!("Unterminated ${{...}} in '{}'", input))?;
+            let expression = &body[..end];
+            let (name, default) = match expression.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (expression, None),
+            };
+            match lookup(name).or_else(|| default.map(str::to_string)) {
+                Some(value) => output.push_str(&value),
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Environment variable '{}' is not set",
+                        name
+                    ))
+                }
+            }
+            rest = &body[end + 1..];
+        } else {
+            output.push('$');
+            rest = after;
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+pub fn resolve_path(reference: &str, base_dir: &Path) -> PathBuf {
+    if let Some(home_relative) = reference.strip_prefix("~/") {
+        if let Some(base) = directories::BaseDirs::new() {
+            return base.home_dir().join(home_relative);
+        }
+    }
+    base_dir.join(reference)
+}
+
+fn parse_value(raw: &str) -> toml::Value {
+    format!("value = {}", raw)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+fn flatten<'a>(prefix: &str, table: &'a toml::Table, entries: &mut Vec<(String, &'a toml::Value)>) {
+    for (key, value) in table {
+        let full_key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(inner) => flatten(&full_key, inner, entries),
+            _ => entries.push((full_key, value)),
+        }
+    }
+}
+
+/// Fields whose values are secrets, wherever they appear, e.g. `profiles.<name>.api_key`.
+const SECRET_FIELDS: [&str; 2] = ["api_key", "refresh_token"];
+
+/// The value of `key` for printing, with secrets in it replaced. `!file` and `${VAR}` references
+/// are kept, since they only say where a secret comes from.
+pub fn masked(key: &str, value: &toml::Value) -> toml::Value {
+    let field = key.rsplit('.').next().unwrap_or(key);
+    match value {
+        toml::Value::String(s)
+            if SECRET_FIELDS.contains(&field) && !s.starts_with("!file ") && !s.contains("${") =>
+        {
+            toml::Value::String("[REDACTED]".to_string())
+        }
+        toml::Value::Array(items) => {
+            toml::Value::Array(items.iter().map(|item| masked(field, item)).collect())
+        }
+        toml::Value::Table(table) => toml::Value::Table(
+            table
+                .iter()
+                .map(|(name, item)| (name.clone(), masked(name, item)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Render a value the way a user would type it back into `config set`.
+pub fn display_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Write a file readable only by the current user, since config may contain API keys. The
+/// contents go to a private temporary file next to it first, which is then renamed over it, so
+/// the file is never readable by others nor left half written.
+pub fn write_private(path: &Path, contents: &str) -> anyhow::Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{} is not a file name", path.display()))?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+    let temp = dir.join(format!(
+        ".{}.{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let written = create_private(&temp).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        Ok(fs::rename(&temp, path)?)
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// A new file that only the current user can read, with no window in which it is readable by
+/// others on Unix.
+pub fn create_private(path: &Path) -> anyhow::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    restrict_to_owner(path)?;
+    Ok(file)
+}
+
+/// Make `path` readable and writable by the current user only: mode 0600 on Unix, and on
+/// Windows an ACL without inherited entries that grants the user full control.
+pub fn restrict_to_owner(path: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(windows)]
+    {
+        let user = std::env::var("USERNAME").map_err(|_| {
+            anyhow::anyhow!("Cannot restrict {}: %USERNAME% is unset", path.display())
+        })?;
+        let status = std::process::Command::new("icacls")
+            .arg(path)
+            .args(["/inheritance:r", "/grant:r"])
+            .arg(format!("{}:F", user))
+            .stdout(std::process::Stdio::null())
+            .status()
+            .map_err(|e| anyhow::anyhow!("Could not run icacls: {}", e))?;
+        if !status.success() {
+            anyhow::bail!("icacls could not restrict {} to {}", path.display(), user);
+        }
+    }
+    Ok(())
+}
+
+/// Open the config file in `$VISUAL`/`$EDITOR` and only keep the result if it validates.
+pub fn edit(path: &Path) -> anyhow::Result<()> {
+    let original = fs::read_to_string(path).unwrap_or_default();
+    let draft = path.with_extension("toml.edit");
+    write_private(&draft, &original)?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        });
+    let status = std::process::Command::new(&editor).arg(&draft).status()?;
+    if !status.success() {
+        fs::remove_file(&draft)?;
+        return Err(anyhow::anyhow!(
+            "Editor '{}' exited with {}",
+            editor,
+            status
+        ));
+    }
+
+    let edited = fs::read_to_string(&draft)?;
+    if let Err(e) = Config::validate_in(&edited, &parent_dir(path)) {
+        return Err(anyhow::anyhow!(
+            "Config not saved, validation failed: {}\nYour changes are kept in {}",
+            e,
+            draft.display()
+        ));
+    }
+    fs::rename(&draft, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_resolution() {
+        let config = Config::parse_in(
+            r#"
+            default_profile = "prod"
+            format = "json"
+
+            [profiles.prod]
+            region = "eu"
+
+            [profiles.staging]
+            endpoint = "https://staging.example.com/graphql"
+            format = "table"
+            "#,
+            Path::new("."),
+        )
+        .unwrap();
+
+        let prod = config.profile(None).unwrap();
+        assert_eq!(config.endpoint(prod), "https://api.eu.newrelic.com/graphql");
+        assert_eq!(config.format(prod), "json");
+
+        let staging = config.profile(Some("staging")).unwrap();
+        assert_eq!(
+            config.endpoint(staging),
+            "https://staging.example.com/graphql"
+        );
+        assert_eq!(config.format(staging), "table");
+
+        assert!(config.profile(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_profile_command_permissions() {
+        let config = Config::parse_in(
+            r#"
+            [profiles.oncall]
+            allowed_commands = ["list", "rotate", "scheduler"]
+            denied_commands = ["scheduler run"]
+
+            [profiles.admin]
+            "#,
+            Path::new("."),
+        )
+        .unwrap();
+        let oncall = &config.profiles["oncall"];
+        assert!(oncall.check_command("oncall", "list").is_ok());
+        assert!(oncall.check_command("oncall", "scheduler list").is_ok());
+        assert!(oncall.check_command("oncall", "scheduler run").is_err());
+        assert!(oncall.check_command("oncall", "delete").is_err());
+        assert!(oncall.check_command("oncall", "rotate-all").is_err());
+        assert!(config.profiles["admin"]
+            .check_command("admin", "delete")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_set_get_unset() {
+        let mut file = ConfigFile {
+            table: toml::Table::new(),
+            base_dir: PathBuf::from("."),
+        };
+
+        file.set("profiles.prod.region", "eu").unwrap();
+        file.set("account_groups.prod", "[1, 2]").unwrap();
+        file.set("policies.max_key_age_days", "90").unwrap();
+
+        assert_eq!(
+            file.get("profiles.prod.region"),
+            Some(&toml::Value::String("eu".to_string()))
+        );
+        assert_eq!(
+            file.get("policies.max_key_age_days"),
+            Some(&toml::Value::Integer(90))
+        );
+        assert_eq!(file.entries().len(), 3);
+
+        file.unset("profiles.prod.region").unwrap();
+        assert!(file.get("profiles.prod.region").is_none());
+        assert!(file.unset("profiles.prod.region").is_err());
+    }
+
+    #[test]
+    fn test_set_rejects_invalid_values() {
+        let mut file = ConfigFile {
+            table: toml::Table::new(),
+            base_dir: PathBuf::from("."),
+        };
+
+        assert!(file.set("profiles.prod.region", "mars").is_err());
+        assert!(file.set("unknown_setting", "1").is_err());
+        assert!(file.set("policies.max_key_age_days", "soon").is_err());
+
+        let mut file = ConfigFile {
+            table: toml::Table::new(),
+            base_dir: PathBuf::from("."),
+        };
+        assert!(file.set("format", "bogus").is_err());
+        file.unset("format").unwrap();
+        assert!(file.set("profiles.prod.format", "table").is_ok());
+        assert!(file.set("profiles.prod.format", "bogus").is_err());
+    }
+
+    #[test]
+    fn test_masked_secrets() {
+        let value = toml::Value::String("NRAK-SECRET".to_string());
+        assert_eq!(
+            display_value(&masked("profiles.prod.api_key", &value)),
+            "[REDACTED]"
+        );
+        let reference = toml::Value::String("!file key.txt".to_string());
+        assert_eq!(
+            display_value(&masked("profiles.prod.api_key", &reference)),
+            "!file key.txt"
+        );
+        let endpoints: toml::Value = toml::from_str::<toml::Table>(
+            r#"endpoints = [{ endpoint = "https://example.com", api_key = "NRAK-OTHER" }]"#,
+        )
+        .unwrap()
+        .remove("endpoints")
+        .unwrap();
+        let shown = display_value(&masked("profiles.prod.endpoints", &endpoints));
+        assert!(!shown.contains("NRAK-OTHER"));
+        assert!(shown.contains("https://example.com"));
+    }
+
+    #[test]
+    fn test_interpolate_environment_variables() {
+        let lookup = |name: &str| (name == "NR_KEY").then(|| "NRAK-123".to_string());
+
+        assert_eq!(interpolate("${NR_KEY}", &lookup).unwrap(), "NRAK-123");
+        assert_eq!(
+            interpolate("key=${NR_KEY}, region=${NR_REGION:-us}", &lookup).unwrap(),
+            "key=NRAK-123, region=us"
+        );
+        assert_eq!(interpolate("cost: $$5", &lookup).unwrap(), "cost: $5");
+        assert!(interpolate("${MISSING}", &lookup).is_err());
+        assert!(interpolate("${NR_KEY", &lookup).is_err());
+    }
+
+    #[test]
+    fn test_include_and_file_references() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("groups.toml"), "prod = [1, 2]\n").unwrap();
+        fs::write(dir.path().join("key.txt"), "NRAK-SECRET\n").unwrap();
+
+        let config = Config::parse_in(
+            r#"
+            account_groups = "!include groups.toml"
+
+            [profiles.prod]
+            api_key = "!file key.txt"
+            "#,
+            dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(config.account_groups["prod"], vec![1, 2]);
+        assert_eq!(
+            config.profiles["prod"]
+                .api_key
+                .as_ref()
+                .map(SecretString::expose),
+            Some("NRAK-SECRET")
+        );
+    }
+
+    #[test]
+    fn test_unset_variable_only_fails_its_profile() {
+        let contents = r#"
+            [profiles.prod]
+            api_key = "${NR_TEST_UNSET_PROD_KEY}"
+
+            [profiles.dev]
+            region = "eu"
+            "#;
+        let config = Config::parse_in(contents, Path::new(".")).unwrap();
+        assert!(config.profile(Some("dev")).unwrap().is_some());
+        let error = config.profile(Some("prod")).err().unwrap();
+        assert!(error.to_string().contains("NR_TEST_UNSET_PROD_KEY"));
+
+        let mut file = ConfigFile {
+            table: contents.parse().unwrap(),
+            base_dir: PathBuf::from("."),
+        };
+        file.set("profiles.dev.region", "us").unwrap();
+        assert!(file.set("profiles.dev.region", "mars").is_err());
+    }
+
+    #[test]
+    fn test_include_cycle_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.toml"), "nested = \"!include a.toml\"\n").unwrap();
+
+        let result = Config::parse_in(r#"policies = "!include a.toml""#, dir.path());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_write_private_replaces_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "old").unwrap();
+
+        write_private(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_create_private_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out");
+        create_private(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(create_private(&path).is_err());
+    }
+}
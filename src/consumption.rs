@@ -0,0 +1,244 @@
+//! `report usage-by-key`: ingest from `NrConsumption` attributed to the ingest keys of each
+//! account. New Relic meters ingest per account and usage metric, not per key, so an account's
+//! browser ingest is split between its browser keys and everything else between its license
+//! keys. The attribution is exact for accounts with a single key of the type and an even share
+//! otherwise, which the report says for every row.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::inventory::{self, ApiKey};
+use crate::output::{Format, Renderer};
+use crate::{audit_events, nrql, NewRelicClient};
+
+/// The usage metric of data sent with browser keys.
+const BROWSER_METRIC: &str = "BrowserEventsBytes";
+
+/// Usage metrics of data that no API key sends, such as mobile app tokens.
+const KEYLESS_METRICS: [&str; 1] = ["MobileEventsBytes"];
+
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub account_id: i64,
+    /// None for ingest of an account without a key of the type
+    pub key_id: Option<String>,
+    pub name: Option<String>,
+    pub ingest_type: String,
+    /// The key's estimated share of the account's ingest, in GB
+    pub gigabytes: f64,
+    /// Fraction of the account's ingest of this type assigned to the key
+    pub share: f64,
+    pub attribution: String,
+}
+
+fn consumption_query(account_id: i64, since: &str) -> String {
+    format!(
+        "SELECT sum(GigabytesIngested) FROM NrConsumption WHERE productLine = 'DataPlatform' \
+         AND consumingAccountId = {} FACET usageMetric SINCE {} LIMIT MAX",
+        account_id,
+        audit_events::since_clause(since)
+    )
+}
+
+/// GB ingested per usage metric, from the rows of [`consumption_query`].
+fn by_metric(rows: &[serde_json::Value]) -> BTreeMap<String, f64> {
+    let mut metrics = BTreeMap::new();
+    for row in rows {
+        let metric = row["usageMetric"]
+            .as_str()
+            .or_else(|| row["facet"].as_str())
+            .unwrap_or("unknown");
+        let gigabytes = row["sum.GigabytesIngested"].as_f64().unwrap_or(0.0);
+        *metrics.entry(metric.to_string()).or_insert(0.0) += gigabytes;
+    }
+    metrics
+}
+
+/// GB ingested per ingest key type (`LICENSE` or `BROWSER`), from GB per usage metric.
+fn by_ingest_type(metrics: &BTreeMap<String, f64>) -> BTreeMap<&'static str, f64> {
+    let mut totals = BTreeMap::new();
+    for (metric, gigabytes) in metrics {
+        let ingest_type = match metric.as_str() {
+            BROWSER_METRIC => "BROWSER",
+            metric if KEYLESS_METRICS.contains(&metric) => continue,
+            _ => "LICENSE",
+        };
+        *totals.entry(ingest_type).or_insert(0.0) += gigabytes;
+    }
+    totals
+}
+
+/// GB an account ingested per ingest key type since `since`. A type without ingest is missing
+/// or zero, which shows that none of the account's keys of that type was used.
+pub(crate) async fn ingest_by_type(
+    client: &NewRelicClient,
+    account_id: i64,
+    since: &str,
+) -> anyhow::Result<BTreeMap<&'static str, f64>> {
+    let rows = nrql::query(client, account_id, &consumption_query(account_id, since)).await?;
+    Ok(by_ingest_type(&by_metric(&rows)))
+}
+
+/// Split the ingest of one account between its keys of the matching type.
+fn attribute(account_id: i64, metrics: &BTreeMap<String, f64>, keys: &[&ApiKey]) -> Vec<Usage> {
+    let mut usage = Vec::new();
+    for (ingest_type, total) in by_ingest_type(metrics) {
+        let owners: Vec<&&ApiKey> = keys
+            .iter()
+            .filter(|key| key.ingest_type.as_deref().unwrap_or("LICENSE") == ingest_type)
+            .collect();
+        if owners.is_empty() {
+            usage.push(Usage {
+                account_id,
+                key_id: None,
+                name: None,
+                ingest_type: ingest_type.to_string(),
+                gigabytes: total,
+                share: 1.0,
+                attribution: format!("unattributed: no {} key", ingest_type.to_lowercase()),
+            });
+            continue;
+        }
+        let share = 1.0 / owners.len() as f64;
+        let attribution = if owners.len() == 1 {
+            "exact".to_string()
+        } else {
+            format!("even share of {} keys", owners.len())
+        };
+        usage.extend(owners.into_iter().map(|key| Usage {
+            account_id,
+            key_id: Some(key.id.clone()),
+            name: key.name.clone(),
+            ingest_type: ingest_type.to_string(),
+            gigabytes: total * share,
+            share,
+            attribution: attribution.clone(),
+        }));
+    }
+    usage
+}
+
+/// Estimated ingest per license and browser key across the given accounts, largest first.
+pub async fn usage_by_key(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    since: &str,
+) -> anyhow::Result<Vec<Usage>> {
+    let keys = inventory::fetch(client, account_ids, &["INGEST"]).await?;
+    attribute_to(client, &keys, account_ids, since).await
+}
+
+/// Like [`usage_by_key`], for keys that were already fetched.
+pub async fn attribute_to(
+    client: &NewRelicClient,
+    keys: &[ApiKey],
+    account_ids: &[i64],
+    since: &str,
+) -> anyhow::Result<Vec<Usage>> {
+    let mut usage = Vec::new();
+    for &account_id in account_ids {
+        let rows = nrql::query(client, account_id, &consumption_query(account_id, since)).await?;
+        let account_keys: Vec<&ApiKey> = keys
+            .iter()
+            .filter(|key| key.account_id == Some(account_id))
+            .collect();
+        usage.extend(attribute(account_id, &by_metric(&rows), &account_keys));
+    }
+    usage.sort_by(|a, b| b.gigabytes.total_cmp(&a.gigabytes));
+    Ok(usage)
+}
+
+const HEADERS: [&str; 7] = [
+    "ACCOUNT",
+    "TYPE",
+    "KEY ID",
+    "NAME",
+    "GB",
+    "SHARE",
+    "ATTRIBUTION",
+];
+
+impl Renderer for [Usage] {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|u| {
+                vec![
+                    u.account_id.to_string(),
+                    u.ingest_type.clone(),
+                    u.key_id.clone().unwrap_or_default(),
+                    u.name.clone().unwrap_or_default(),
+                    format!("{:.3}", u.gigabytes),
+                    format!("{:.0}%", u.share * 100.0),
+                    u.attribution.clone(),
+                ]
+            })
+            .collect()
+    }
+
+    fn empty_message(&self) -> Option<String> {
+        Some("No ingest found".to_string())
+    }
+}
+
+pub fn print(usage: &[Usage], format: Format) -> anyhow::Result<()> {
+    print!("{}", usage.render(&format)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, ingest_type: &str) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "name": id, "type": "INGEST", "ingestType": ingest_type,
+            "createdAt": 1, "accountId": 1
+        }))
+    }
+
+    #[test]
+    fn test_attribute_ingest_to_keys() {
+        let rows = vec![
+            serde_json::json!({"facet": "MetricsBytes", "usageMetric": "MetricsBytes", "sum.GigabytesIngested": 6.0}),
+            serde_json::json!({"facet": "TracingBytes", "usageMetric": "TracingBytes", "sum.GigabytesIngested": 2.0}),
+            serde_json::json!({"facet": "BrowserEventsBytes", "usageMetric": "BrowserEventsBytes", "sum.GigabytesIngested": 1.0}),
+            serde_json::json!({"facet": "MobileEventsBytes", "usageMetric": "MobileEventsBytes", "sum.GigabytesIngested": 5.0}),
+        ];
+        let (a, b) = (key("A", "LICENSE"), key("B", "LICENSE"));
+        let usage = attribute(1, &by_metric(&rows), &[&a, &b]);
+
+        let browser = usage.iter().find(|u| u.ingest_type == "BROWSER").unwrap();
+        assert!(browser.key_id.is_none());
+        assert_eq!(browser.attribution, "unattributed: no browser key");
+        let licensed: Vec<&Usage> = usage.iter().filter(|u| u.key_id.is_some()).collect();
+        assert_eq!(licensed.len(), 2);
+        assert_eq!(licensed[0].gigabytes, 4.0);
+        assert_eq!(licensed[0].attribution, "even share of 2 keys");
+
+        let browser_key = key("C", "BROWSER");
+        let usage = attribute(1, &by_metric(&rows), &[&browser_key]);
+        let exact = usage.iter().find(|u| u.key_id.is_some()).unwrap();
+        assert_eq!(
+            (exact.gigabytes, exact.attribution.as_str()),
+            (1.0, "exact")
+        );
+    }
+
+    #[test]
+    fn test_consumption_query() {
+        assert_eq!(
+            consumption_query(42, "7d"),
+            "SELECT sum(GigabytesIngested) FROM NrConsumption WHERE productLine = 'DataPlatform' \
+             AND consumingAccountId = 42 FACET usageMetric SINCE 7 days ago LIMIT MAX"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! What a command runs with besides its own arguments: the client, the loaded config and the
+//! selected profile, and how it may act (dry run, confirmations, where new secrets go). Built
+//! once in `cli::run`, so that an option every command honours is added here rather than to
+//! each command's parameter list.
+
+use crate::config::{Config, Profile};
+use crate::output::Format;
+use crate::paths::Paths;
+use crate::prompt::Confirmation;
+use crate::sink::Secrets;
+use crate::NewRelicClient;
+
+pub(crate) struct ExecutionContext<'a> {
+    /// The client for the selected profile, or why there is none
+    pub client: Result<NewRelicClient, String>,
+    pub config: &'a Config,
+    pub profile: Option<&'a Profile>,
+    pub paths: &'a Paths,
+    /// `--format`, or the profile's or config's default
+    pub format: String,
+    /// The `--template` source, resolved from the config
+    pub template: Option<String>,
+    /// Report what would change without changing it
+    pub dry_run: bool,
+    pub confirmation: Confirmation,
+    pub secrets: Secrets,
+}
+
+impl ExecutionContext<'_> {
+    pub fn client(&self) -> anyhow::Result<&NewRelicClient> {
+        self.client
+            .as_ref()
+            .map_err(|message| anyhow::anyhow!("{}", message))
+    }
+
+    /// The output format the command should print in.
+    pub fn format(&self) -> anyhow::Result<Format> {
+        self.parse_format(&self.format)
+    }
+
+    /// `value` as a format, unless `--template` overrides it.
+    pub fn parse_format(&self, value: &str) -> anyhow::Result<Format> {
+        match &self.template {
+            #[cfg(feature = "templates")]
+            Some(template) => Ok(Format::Template(crate::output::Template::new(template)?)),
+            #[cfg(not(feature = "templates"))]
+            Some(_) => Format::parse("template"),
+            None => Format::parse(value),
+        }
+    }
+
+    /// Use the given account ID, falling back to the selected profile's default account.
+    pub fn account_id(&self, account_id: Option<i64>) -> anyhow::Result<i64> {
+        account_id
+            .or_else(|| self.profile.and_then(|p| p.account_id))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Missing account ID: pass --account-id or set account_id in the profile"
+                )
+            })
+    }
+
+    /// Accounts to operate on: explicit IDs, a named account group, or the profile's default
+    /// account.
+    pub fn account_ids(
+        &self,
+        account_ids: Vec<i64>,
+        account_group: Option<&str>,
+    ) -> anyhow::Result<Vec<i64>> {
+        if let Some(group) = account_group {
+            return self
+                .config
+                .account_groups
+                .get(group)
+                .filter(|ids| !ids.is_empty())
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Account group '{}' is not defined", group));
+        }
+        if !account_ids.is_empty() {
+            return Ok(account_ids);
+        }
+        Ok(vec![self.account_id(None)?])
+    }
+
+    /// Like [`account_ids`](Self::account_ids), but no accounts at all is fine: servers then
+    /// require every request to name its accounts.
+    pub fn default_account_ids(
+        &self,
+        account_ids: Vec<i64>,
+        account_group: Option<&str>,
+    ) -> anyhow::Result<Vec<i64>> {
+        if account_ids.is_empty() && account_group.is_none() {
+            return Ok(self
+                .profile
+                .and_then(|p| p.account_id)
+                .into_iter()
+                .collect());
+        }
+        self.account_ids(account_ids, account_group)
+    }
+
+    /// Ask `question` unless `--yes` assumed the answer.
+    pub fn confirm(&self, question: &str) -> std::io::Result<bool> {
+        self.confirmation.confirm(question)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(
+        config: &'a Config,
+        profile: Option<&'a Profile>,
+        paths: &'a Paths,
+    ) -> ExecutionContext<'a> {
+        ExecutionContext {
+            client: Err("Missing API key".to_string()),
+            config,
+            profile,
+            paths,
+            format: "json".to_string(),
+            template: None,
+            dry_run: false,
+            confirmation: Confirmation::Assume,
+            secrets: Secrets::default(),
+        }
+    }
+
+    #[test]
+    fn test_account_ids_fall_back_to_the_profile() {
+        let mut config = Config::default();
+        config.account_groups.insert("prod".to_string(), vec![1, 2]);
+        let profile = Profile {
+            account_id: Some(7),
+            ..Profile::default()
+        };
+        let paths = Paths {
+            config_dir: "config".into(),
+            cache_dir: "cache".into(),
+            data_dir: "data".into(),
+        };
+
+        let ctx = context(&config, Some(&profile), &paths);
+        assert_eq!(ctx.account_ids(vec![3], None).unwrap(), [3]);
+        assert_eq!(ctx.account_ids(vec![3], Some("prod")).unwrap(), [1, 2]);
+        assert_eq!(ctx.account_ids(Vec::new(), None).unwrap(), [7]);
+        assert!(ctx.account_ids(Vec::new(), Some("staging")).is_err());
+        assert_eq!(
+            ctx.client().err().map(|e| e.to_string()).as_deref(),
+            Some("Missing API key")
+        );
+        assert!(ctx.confirm("Delete everything?").unwrap());
+
+        let ctx = context(&config, None, &paths);
+        assert!(ctx.account_ids(Vec::new(), None).is_err());
+        assert!(ctx
+            .default_account_ids(Vec::new(), None)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,861 @@
+//! Contract checks of the built-in queries against `schema/api-access.graphql`, a snapshot of
+//! the NerdGraph types they use.
+//!
+//! `validate-queries` and the test suite run every query and mutation the CLI sends through
+//! [`validate`], which checks fields, arguments, input objects, enum values and variable types,
+//! so a query that no longer fits the schema fails before a release instead of in front of a
+//! user. [`validate_variables`] does the same for the variables sent with a query.
+
+use std::collections::{HashMap, HashSet};
+
+use graphql_parser::query::{
+    self as query, Definition, FragmentDefinition, OperationDefinition, Selection, SelectionSet,
+    TypeCondition, Value,
+};
+use graphql_parser::schema::{self as schema, Type, TypeDefinition};
+use graphql_parser::Pos;
+
+use crate::{commands, identity, inventory, nrql, rotation, service_account, sweep, Variables};
+
+/// The bundled snapshot.
+pub const SCHEMA: &str = include_str!("../schema/api-access.graphql");
+
+/// Every query and mutation the CLI sends, named after the code that sends it.
+pub fn builtin_queries() -> Vec<(&'static str, String)> {
+    vec![
+        ("inventory::fetch", inventory::search_query()),
+        ("inventory::get", inventory::get_query()),
+        ("inventory::create", inventory::create_query()),
+        ("inventory::update", inventory::update_query()),
+        (
+            "inventory::delete_keys",
+            inventory::DELETE_QUERY.to_string(),
+        ),
+        ("fetch_identity", identity::IDENTITY_QUERY.to_string()),
+        ("nrql::query", nrql::QUERY.to_string()),
+        ("rotation::verify", rotation::VERIFY_QUERY.to_string()),
+        ("query", commands::query::KEY_QUERY.to_string()),
+        ("whoami", commands::whoami::PROBE_QUERY.to_string()),
+        (
+            "service_account::provision",
+            service_account::DOMAINS_QUERY.to_string(),
+        ),
+        (
+            "service_account::create_user",
+            service_account::CREATE_USER_QUERY.to_string(),
+        ),
+        (
+            "service_account::groups",
+            service_account::GROUPS_QUERY.to_string(),
+        ),
+        (
+            "service_account::create_group",
+            service_account::CREATE_GROUP_QUERY.to_string(),
+        ),
+        (
+            "service_account::add_to_group",
+            service_account::ADD_TO_GROUP_QUERY.to_string(),
+        ),
+        (
+            "service_account::roles",
+            service_account::ROLES_QUERY.to_string(),
+        ),
+        (
+            "service_account::grant",
+            service_account::GRANT_QUERY.to_string(),
+        ),
+        (
+            "sweep::organization_user_ids",
+            sweep::USERS_QUERY.to_string(),
+        ),
+        ("sweep::caller", sweep::CALLER_QUERY.to_string()),
+    ]
+}
+
+/// Variables as the CLI builds them for the built-in key queries, from typical arguments.
+pub fn builtin_variables() -> anyhow::Result<Vec<(&'static str, String, Variables)>> {
+    let spec = |key_type: &str| inventory::NewKey {
+        key_type: key_type.to_string(),
+        account_id: 1234567,
+        name: "deploy".to_string(),
+        notes: Some("team:payments".to_string()),
+        ingest_type: Some("license".to_string()),
+        user_id: Some(1001),
+    };
+    let create = |key_type: &str| -> anyhow::Result<Variables> {
+        Ok(Variables::new().value("keys", spec(key_type).input()?))
+    };
+    let ids = ["K1".to_string()];
+    Ok(vec![
+        (
+            "inventory::fetch",
+            inventory::search_query(),
+            inventory::search_variables(&[1234567, 7654321], &["ingest", "USER"], Some("c")),
+        ),
+        (
+            "inventory::get",
+            inventory::get_query(),
+            inventory::get_variables("K1", "user"),
+        ),
+        (
+            "inventory::create",
+            inventory::create_query(),
+            create("INGEST")?,
+        ),
+        (
+            "inventory::create",
+            inventory::create_query(),
+            create("user")?,
+        ),
+        (
+            "inventory::update",
+            inventory::update_query(),
+            inventory::update_variables("K1", "ingest", Some("deploy"), None)?,
+        ),
+        (
+            "inventory::update_keys",
+            inventory::update_query(),
+            inventory::update_keys_variables(&[
+                inventory::KeyChange {
+                    key_id: "K1".to_string(),
+                    key_type: "INGEST".to_string(),
+                    name: Some("deploy".to_string()),
+                    notes: None,
+                },
+                inventory::KeyChange {
+                    key_id: "K2".to_string(),
+                    key_type: "USER".to_string(),
+                    name: None,
+                    notes: Some("owned by platform team".to_string()),
+                },
+            ])?,
+        ),
+        (
+            "inventory::delete_keys",
+            inventory::DELETE_QUERY.to_string(),
+            inventory::delete_variables(&ids, &ids),
+        ),
+        (
+            "query",
+            commands::query::KEY_QUERY.to_string(),
+            inventory::get_variables("K1", "INGEST"),
+        ),
+    ])
+}
+
+/// The types of a schema document, by name.
+pub struct Schema {
+    types: HashMap<String, TypeDefinition<'static, String>>,
+    query: String,
+    mutation: String,
+}
+
+impl Schema {
+    pub fn parse(sdl: &'static str) -> anyhow::Result<Self> {
+        let document = schema::parse_schema::<String>(sdl)
+            .map_err(|e| anyhow::anyhow!("Invalid schema snapshot: {}", e))?;
+        let mut schema = Self {
+            types: HashMap::new(),
+            query: "Query".to_string(),
+            mutation: "Mutation".to_string(),
+        };
+        for definition in document.definitions {
+            match definition {
+                schema::Definition::SchemaDefinition(roots) => {
+                    schema.query = roots.query.unwrap_or(schema.query);
+                    schema.mutation = roots.mutation.unwrap_or(schema.mutation);
+                }
+                schema::Definition::TypeDefinition(definition) => {
+                    schema.types.insert(type_name(&definition), definition);
+                }
+                _ => {}
+            }
+        }
+        Ok(schema)
+    }
+
+    /// The output fields of an object or interface type.
+    fn fields(&self, name: &str) -> Option<&[schema::Field<'static, String>]> {
+        match self.types.get(name)? {
+            TypeDefinition::Object(object) => Some(&object.fields),
+            TypeDefinition::Interface(interface) => Some(&interface.fields),
+            _ => None,
+        }
+    }
+
+    fn is_leaf(&self, name: &str) -> bool {
+        BUILTIN_SCALARS.contains(&name)
+            || matches!(
+                self.types.get(name),
+                Some(TypeDefinition::Scalar(_) | TypeDefinition::Enum(_))
+            )
+    }
+}
+
+const BUILTIN_SCALARS: [&str; 5] = ["Int", "Float", "String", "Boolean", "ID"];
+
+fn type_name(definition: &TypeDefinition<'static, String>) -> String {
+    match definition {
+        TypeDefinition::Scalar(t) => t.name.clone(),
+        TypeDefinition::Object(t) => t.name.clone(),
+        TypeDefinition::Interface(t) => t.name.clone(),
+        TypeDefinition::Union(t) => t.name.clone(),
+        TypeDefinition::Enum(t) => t.name.clone(),
+        TypeDefinition::InputObject(t) => t.name.clone(),
+    }
+}
+
+fn named<'t>(ty: &'t Type<'static, String>) -> &'t str {
+    match ty {
+        Type::NamedType(name) => name,
+        Type::ListType(inner) | Type::NonNullType(inner) => named(inner),
+    }
+}
+
+/// Problems with `document` against `schema`, each prefixed with its `line:column`.
+pub fn validate(schema: &Schema, document: &str) -> Vec<String> {
+    let document = match query::parse_query::<String>(document) {
+        Ok(document) => document.into_static(),
+        Err(e) => return vec![format!("invalid query: {}", e)],
+    };
+    let fragments: HashMap<String, FragmentDefinition<'static, String>> = document
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            Definition::Fragment(fragment) => Some((fragment.name.clone(), fragment.clone())),
+            _ => None,
+        })
+        .collect();
+    let mut problems = Vec::new();
+    for definition in &document.definitions {
+        let Definition::Operation(operation) = definition else {
+            continue;
+        };
+        let (root, variables, selection_set) = match operation {
+            OperationDefinition::SelectionSet(set) => (&schema.query, &[][..], set),
+            OperationDefinition::Query(q) => {
+                (&schema.query, &q.variable_definitions[..], &q.selection_set)
+            }
+            OperationDefinition::Mutation(m) => (
+                &schema.mutation,
+                &m.variable_definitions[..],
+                &m.selection_set,
+            ),
+            OperationDefinition::Subscription(s) => {
+                problems.push(format!("{}: subscriptions are not supported", s.position));
+                continue;
+            }
+        };
+        let mut validator = Validator {
+            schema,
+            fragments: &fragments,
+            variables: variables
+                .iter()
+                .map(|v| {
+                    (
+                        v.name.clone(),
+                        (v.var_type.clone(), v.default_value.is_some()),
+                    )
+                })
+                .collect(),
+            used: HashSet::new(),
+            problems: Vec::new(),
+        };
+        for variable in variables {
+            let name = named(&variable.var_type);
+            let input = BUILTIN_SCALARS.contains(&name)
+                || matches!(
+                    schema.types.get(name),
+                    Some(
+                        TypeDefinition::Scalar(_)
+                            | TypeDefinition::Enum(_)
+                            | TypeDefinition::InputObject(_)
+                    )
+                );
+            if !input {
+                validator.problem(
+                    variable.position,
+                    format!("${} has unknown input type {}", variable.name, name),
+                );
+            }
+        }
+        validator.select(root, selection_set);
+        for variable in variables {
+            if !validator.used.contains(&variable.name) {
+                validator.problem(
+                    variable.position,
+                    format!("${} is never used", variable.name),
+                );
+            }
+        }
+        problems.extend(validator.problems);
+    }
+    problems
+}
+
+struct Validator<'s> {
+    schema: &'s Schema,
+    fragments: &'s HashMap<String, FragmentDefinition<'static, String>>,
+    /// Declared variables: type and whether they have a default
+    variables: HashMap<String, (Type<'static, String>, bool)>,
+    used: HashSet<String>,
+    problems: Vec<String>,
+}
+
+impl Validator<'_> {
+    fn problem(&mut self, position: Pos, message: String) {
+        self.problems.push(format!("{}: {}", position, message));
+    }
+
+    fn select(&mut self, parent: &str, selection_set: &SelectionSet<'static, String>) {
+        for selection in &selection_set.items {
+            match selection {
+                Selection::Field(field) => self.field(parent, field),
+                Selection::InlineFragment(fragment) => {
+                    let on = match &fragment.type_condition {
+                        Some(TypeCondition::On(on)) => on.as_str(),
+                        None => parent,
+                    };
+                    if self.schema.fields(on).is_none() {
+                        self.problem(fragment.position, format!("unknown type {}", on));
+                        continue;
+                    }
+                    self.select(on, &fragment.selection_set);
+                }
+                Selection::FragmentSpread(spread) => {
+                    let Some(fragment) = self.fragments.get(&spread.fragment_name) else {
+                        self.problem(
+                            spread.position,
+                            format!("unknown fragment {}", spread.fragment_name),
+                        );
+                        continue;
+                    };
+                    let TypeCondition::On(on) = &fragment.type_condition;
+                    self.select(on, &fragment.selection_set);
+                }
+            }
+        }
+    }
+
+    fn field(&mut self, parent: &str, field: &query::Field<'static, String>) {
+        if field.name == "__typename" {
+            return;
+        }
+        let schema = self.schema;
+        let Some(definition) = schema
+            .fields(parent)
+            .and_then(|fields| fields.iter().find(|f| f.name == field.name))
+        else {
+            self.problem(
+                field.position,
+                format!("{} has no field '{}'", parent, field.name),
+            );
+            return;
+        };
+        for (name, value) in &field.arguments {
+            match definition.arguments.iter().find(|a| &a.name == name) {
+                Some(argument) => {
+                    let place = format!("argument '{}' of {}.{}", name, parent, field.name);
+                    self.value(field.position, value, &argument.value_type, &place);
+                }
+                None => self.problem(
+                    field.position,
+                    format!("{}.{} has no argument '{}'", parent, field.name, name),
+                ),
+            }
+        }
+        for argument in &definition.arguments {
+            let required = matches!(argument.value_type, Type::NonNullType(_))
+                && argument.default_value.is_none();
+            if required
+                && !field
+                    .arguments
+                    .iter()
+                    .any(|(name, _)| name == &argument.name)
+            {
+                self.problem(
+                    field.position,
+                    format!(
+                        "{}.{} requires argument '{}'",
+                        parent, field.name, argument.name
+                    ),
+                );
+            }
+        }
+        let inner = named(&definition.field_type);
+        let leaf = schema.is_leaf(inner);
+        if leaf && !field.selection_set.items.is_empty() {
+            self.problem(
+                field.position,
+                format!("{}.{} is a {} and has no fields", parent, field.name, inner),
+            );
+        } else if !leaf && field.selection_set.items.is_empty() {
+            self.problem(
+                field.position,
+                format!(
+                    "{}.{} needs a selection of {} fields",
+                    parent, field.name, inner
+                ),
+            );
+        } else if !leaf {
+            self.select(inner, &field.selection_set);
+        }
+    }
+
+    /// Check an argument or input field value against the type it is passed as.
+    fn value(
+        &mut self,
+        position: Pos,
+        value: &Value<'static, String>,
+        expected: &Type<'static, String>,
+        place: &str,
+    ) {
+        if let Value::Variable(name) = value {
+            self.used.insert(name.clone());
+            match self.variables.get(name).cloned() {
+                Some((declared, has_default)) => {
+                    if !variable_allowed(&declared, has_default, expected) {
+                        self.problem(
+                            position,
+                            format!(
+                                "${} is {} but {} is {}",
+                                name,
+                                type_string(&declared),
+                                place,
+                                type_string(expected)
+                            ),
+                        );
+                    }
+                }
+                None => self.problem(position, format!("${} is not declared", name)),
+            }
+            return;
+        }
+        match expected {
+            Type::NonNullType(inner) => {
+                if matches!(value, Value::Null) {
+                    self.problem(position, format!("{} cannot be null", place));
+                } else {
+                    self.value(position, value, inner, place);
+                }
+            }
+            // A single value is accepted where a list is expected.
+            Type::ListType(item) => match value {
+                Value::List(items) => {
+                    for value in items {
+                        self.value(position, value, item, place);
+                    }
+                }
+                Value::Null => {}
+                value => self.value(position, value, item, place),
+            },
+            Type::NamedType(name) => self.named_value(position, value, name, place),
+        }
+    }
+
+    fn named_value(
+        &mut self,
+        position: Pos,
+        value: &Value<'static, String>,
+        name: &str,
+        place: &str,
+    ) {
+        if matches!(value, Value::Null) {
+            return;
+        }
+        let schema = self.schema;
+        let fits = match (name, value) {
+            ("Int", Value::Int(_)) => true,
+            ("Float", Value::Int(_) | Value::Float(_)) => true,
+            ("String", Value::String(_)) => true,
+            ("ID", Value::String(_) | Value::Int(_)) => true,
+            ("Boolean", Value::Boolean(_)) => true,
+            ("Int" | "Float" | "String" | "ID" | "Boolean", _) => false,
+            _ => match (schema.types.get(name), value) {
+                (Some(TypeDefinition::Scalar(_)), value) => !matches!(value, Value::Enum(_)),
+                (Some(TypeDefinition::Enum(definition)), Value::Enum(value)) => {
+                    if !definition.values.iter().any(|v| &v.name == value) {
+                        self.problem(
+                            position,
+                            format!("{} is not a {} value (in {})", value, name, place),
+                        );
+                    }
+                    true
+                }
+                (Some(TypeDefinition::InputObject(input)), Value::Object(fields)) => {
+                    for (field, value) in fields {
+                        match input.fields.iter().find(|f| &f.name == field) {
+                            Some(definition) => {
+                                let place = format!("{}.{}", name, field);
+                                self.value(position, value, &definition.value_type, &place);
+                            }
+                            None => self.problem(
+                                position,
+                                format!("{} has no field '{}' (in {})", name, field, place),
+                            ),
+                        }
+                    }
+                    for definition in &input.fields {
+                        let required = matches!(definition.value_type, Type::NonNullType(_))
+                            && definition.default_value.is_none();
+                        if required && !fields.contains_key(&definition.name) {
+                            self.problem(
+                                position,
+                                format!(
+                                    "{} requires field '{}' (in {})",
+                                    name, definition.name, place
+                                ),
+                            );
+                        }
+                    }
+                    true
+                }
+                (None, _) => {
+                    self.problem(position, format!("unknown type {} (in {})", name, place));
+                    true
+                }
+                _ => false,
+            },
+        };
+        if !fits {
+            self.problem(
+                position,
+                format!("{} expects {}, not {}", place, name, value),
+            );
+        }
+    }
+}
+
+/// Problems with the JSON `variables` sent with the operation in `document`: missing required
+/// variables, undeclared ones, and values that do not fit the declared type, such as an
+/// account ID sent as a string where NerdGraph expects an `Int`.
+pub fn validate_variables(schema: &Schema, document: &str, variables: &Variables) -> Vec<String> {
+    let document = match query::parse_query::<String>(document) {
+        Ok(document) => document.into_static(),
+        Err(e) => return vec![format!("invalid query: {}", e)],
+    };
+    let declared = document
+        .definitions
+        .iter()
+        .find_map(|definition| match definition {
+            Definition::Operation(OperationDefinition::Query(q)) => Some(&q.variable_definitions),
+            Definition::Operation(OperationDefinition::Mutation(m)) => {
+                Some(&m.variable_definitions)
+            }
+            _ => None,
+        });
+    let declared = declared.map(Vec::as_slice).unwrap_or_default();
+    let mut problems = Vec::new();
+    for definition in declared {
+        let place = format!("${}", definition.name);
+        match variables.get(&definition.name) {
+            Some(value) => json_value(schema, value, &definition.var_type, &place, &mut problems),
+            None if matches!(definition.var_type, Type::NonNullType(_))
+                && definition.default_value.is_none() =>
+            {
+                problems.push(format!("{} is required", place))
+            }
+            None => {}
+        }
+    }
+    // In name order, as Variables iterates.
+    for (name, _) in variables.iter() {
+        if !declared.iter().any(|definition| definition.name == name) {
+            problems.push(format!("${} is not declared", name));
+        }
+    }
+    problems
+}
+
+/// Check a JSON variable value against the type it is declared as.
+fn json_value(
+    schema: &Schema,
+    value: &serde_json::Value,
+    expected: &Type<'static, String>,
+    place: &str,
+    problems: &mut Vec<String>,
+) {
+    match expected {
+        Type::NonNullType(inner) => {
+            if value.is_null() {
+                problems.push(format!("{} cannot be null", place));
+            } else {
+                json_value(schema, value, inner, place, problems);
+            }
+        }
+        // As in documents, a single value is accepted where a list is expected.
+        Type::ListType(item) => match value {
+            serde_json::Value::Array(items) => {
+                for (index, value) in items.iter().enumerate() {
+                    let place = format!("{}[{}]", place, index);
+                    json_value(schema, value, item, &place, problems);
+                }
+            }
+            serde_json::Value::Null => {}
+            value => json_value(schema, value, item, place, problems),
+        },
+        Type::NamedType(name) => json_named_value(schema, value, name, place, problems),
+    }
+}
+
+fn json_named_value(
+    schema: &Schema,
+    value: &serde_json::Value,
+    name: &str,
+    place: &str,
+    problems: &mut Vec<String>,
+) {
+    if value.is_null() {
+        return;
+    }
+    // GraphQL `Int` is 32 bits.
+    let int = || value.as_i64().is_some_and(|n| i32::try_from(n).is_ok());
+    let fits = match name {
+        "Int" => int(),
+        "Float" => value.is_number(),
+        "String" => value.is_string(),
+        "ID" => value.is_string() || int(),
+        "Boolean" => value.is_boolean(),
+        _ => match (schema.types.get(name), value) {
+            (Some(TypeDefinition::Scalar(_)), _) => true,
+            (Some(TypeDefinition::Enum(definition)), serde_json::Value::String(value)) => {
+                if !definition.values.iter().any(|v| &v.name == value) {
+                    problems.push(format!("{} is not a {} value (in {})", value, name, place));
+                }
+                true
+            }
+            (Some(TypeDefinition::InputObject(input)), serde_json::Value::Object(fields)) => {
+                for (field, value) in fields {
+                    let place = format!("{}.{}", place, field);
+                    match input.fields.iter().find(|f| &f.name == field) {
+                        Some(definition) => {
+                            json_value(schema, value, &definition.value_type, &place, problems)
+                        }
+                        None => problems
+                            .push(format!("{} has no field '{}' (in {})", name, field, place)),
+                    }
+                }
+                for definition in &input.fields {
+                    let required = matches!(definition.value_type, Type::NonNullType(_))
+                        && definition.default_value.is_none();
+                    if required && !fields.contains_key(&definition.name) {
+                        problems.push(format!(
+                            "{} requires field '{}' (in {})",
+                            name, definition.name, place
+                        ));
+                    }
+                }
+                true
+            }
+            (None, _) => {
+                problems.push(format!("unknown type {} (in {})", name, place));
+                true
+            }
+            _ => false,
+        },
+    };
+    if !fits {
+        problems.push(format!("{} expects {}, not {}", place, name, value));
+    }
+}
+
+/// Whether a variable declared as `declared` may be passed where `expected` is required.
+fn variable_allowed(
+    declared: &Type<'static, String>,
+    has_default: bool,
+    expected: &Type<'static, String>,
+) -> bool {
+    match (declared, expected) {
+        (Type::NonNullType(declared), Type::NonNullType(expected)) => {
+            variable_allowed(declared, false, expected)
+        }
+        (declared, Type::NonNullType(expected)) => {
+            has_default && variable_allowed(declared, false, expected)
+        }
+        (Type::NonNullType(declared), expected) => variable_allowed(declared, false, expected),
+        (Type::ListType(declared), Type::ListType(expected)) => {
+            variable_allowed(declared, false, expected)
+        }
+        (Type::NamedType(declared), Type::NamedType(expected)) => declared == expected,
+        _ => false,
+    }
+}
+
+fn type_string(ty: &Type<'static, String>) -> String {
+    match ty {
+        Type::NamedType(name) => name.clone(),
+        Type::ListType(inner) => format!("[{}]", type_string(inner)),
+        Type::NonNullType(inner) => format!("{}!", type_string(inner)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_builtin_queries_match_the_schema_snapshot() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        for (name, document) in builtin_queries() {
+            let problems = validate(&schema, &document);
+            assert!(problems.is_empty(), "{}: {:?}", name, problems);
+        }
+    }
+
+    #[test]
+    fn test_builtin_variables_match_the_schema_snapshot() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        for (name, document, variables) in builtin_variables().unwrap() {
+            let problems = validate_variables(&schema, &document, &variables);
+            assert!(problems.is_empty(), "{}: {:?}", name, problems);
+        }
+    }
+
+    #[test]
+    fn test_validate_variables_reports_mismatches() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        let variables = Variables::new()
+            .value(
+                "query",
+                serde_json::json!({"types": ["INGEST", "ingest"], "scope": {"accountIds": ["123"]}}),
+            )
+            .int("page", 2);
+        assert_eq!(
+            validate_variables(&schema, &inventory::search_query(), &variables),
+            vec![
+                "$query.scope.accountIds[0] expects Int, not \"123\"",
+                "ingest is not a ApiAccessKeyType value (in $query.types[1])",
+                "$page is not declared",
+            ]
+        );
+        assert_eq!(
+            validate_variables(&schema, &inventory::get_query(), &Variables::new()),
+            vec!["$id is required", "$keyType is required"]
+        );
+    }
+
+    fn key_type() -> impl Strategy<Value = String> {
+        prop_oneof![Just("INGEST"), Just("USER")].prop_flat_map(|key_type| {
+            // Any capitalization, as typed on the command line.
+            proptest::collection::vec(any::<bool>(), key_type.len()).prop_map(move |upper| {
+                key_type
+                    .chars()
+                    .zip(upper)
+                    .map(|(c, upper)| if upper { c } else { c.to_ascii_lowercase() })
+                    .collect()
+            })
+        })
+    }
+
+    fn key_ids() -> impl Strategy<Value = Vec<String>> {
+        proptest::collection::vec("[A-Z0-9]{1,40}", 0..5)
+    }
+
+    proptest! {
+        #[test]
+        fn test_search_variables_type_check(
+            account_ids in proptest::collection::vec(1..=i64::from(i32::MAX), 0..5),
+            key_types in proptest::collection::vec(key_type(), 1..3),
+            cursor in proptest::option::of(".*"),
+        ) {
+            let schema = Schema::parse(SCHEMA).unwrap();
+            let key_types: Vec<&str> = key_types.iter().map(String::as_str).collect();
+            let variables =
+                inventory::search_variables(&account_ids, &key_types, cursor.as_deref());
+            let problems = validate_variables(&schema, &inventory::search_query(), &variables);
+            prop_assert!(problems.is_empty(), "{:?}", problems);
+        }
+
+        #[test]
+        fn test_create_variables_type_check(
+            key_type in key_type(),
+            account_id in 1..=i64::from(i32::MAX),
+            name in ".*",
+            notes in proptest::option::of(".*"),
+            ingest_type in proptest::option::of(prop_oneof![
+                Just("LICENSE"), Just("license"), Just("Browser")
+            ]),
+            user_id in 1..=i64::from(i32::MAX),
+        ) {
+            let schema = Schema::parse(SCHEMA).unwrap();
+            let spec = inventory::NewKey {
+                key_type,
+                account_id,
+                name,
+                notes,
+                ingest_type: ingest_type.map(str::to_string),
+                user_id: Some(user_id),
+            };
+            let variables = Variables::new().value("keys", spec.input().unwrap());
+            let problems = validate_variables(&schema, &inventory::create_query(), &variables);
+            prop_assert!(problems.is_empty(), "{:?}", problems);
+        }
+
+        #[test]
+        fn test_get_update_and_delete_variables_type_check(
+            key_type in key_type(),
+            key_id in "[A-Z0-9]{1,40}",
+            name in proptest::option::of(".*"),
+            notes in proptest::option::of(".*"),
+            ingest_key_ids in key_ids(),
+            user_key_ids in key_ids(),
+        ) {
+            let schema = Schema::parse(SCHEMA).unwrap();
+            let checks = [
+                (inventory::get_query(), inventory::get_variables(&key_id, &key_type)),
+                (commands::query::KEY_QUERY.to_string(), inventory::get_variables(&key_id, &key_type)),
+                (
+                    inventory::update_query(),
+                    inventory::update_variables(
+                        &key_id,
+                        &key_type,
+                        name.as_deref(),
+                        notes.as_deref(),
+                    )
+                    .unwrap(),
+                ),
+                (
+                    inventory::DELETE_QUERY.to_string(),
+                    inventory::delete_variables(&ingest_key_ids, &user_key_ids),
+                ),
+            ];
+            for (document, variables) in checks {
+                let problems = validate_variables(&schema, &document, &variables);
+                prop_assert!(problems.is_empty(), "{:?}", problems);
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_mismatches() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        // The shape `delete` used to send: a String where a list of IDs goes.
+        let problems = validate(
+            &schema,
+            "mutation($keyId: String!) {
+                apiAccessDeleteKeys(keys: {ingestKeyIds: $keyId}) {
+                    deletedKeys { id }
+                }
+            }",
+        );
+        assert_eq!(
+            problems,
+            vec!["2:17: $keyId is String! but ApiAccessDeleteInput.ingestKeyIds is [ID!]"]
+        );
+
+        let problems = validate(
+            &schema,
+            "{ actor { apiAccess { key(id: \"1\", keyType: INGST) { nmae } } } }",
+        );
+        assert_eq!(
+            problems,
+            vec![
+                "1:23: INGST is not a ApiAccessKeyType value (in argument 'keyType' of \
+                 ApiAccessActorStitchedFields.key)",
+                "1:54: ApiAccessKey has no field 'nmae'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(feature = "keyring")]
+use keyring::Entry;
+
+use crate::SecretString;
+
+#[cfg(feature = "keyring")]
+const SERVICE: &str = "newrelic-apikeys-cli";
+
+/// Store a profile's API key in the platform keyring (Keychain, Credential Manager, keyutils).
+#[cfg(feature = "keyring")]
+pub fn store_api_key(profile: &str, api_key: &str) -> anyhow::Result<()> {
+    Entry::new(SERVICE, profile)?.set_password(api_key)?;
+    Ok(())
+}
+
+/// Store another secret, such as a new key from `--secret-sink keyring`, under `name`.
+#[cfg(feature = "keyring")]
+pub fn store_secret(name: &str, secret: &str) -> anyhow::Result<()> {
+    Entry::new(SERVICE, name)?.set_password(secret)?;
+    Ok(())
+}
+
+/// Look up a profile's API key in the platform keyring.
+#[cfg(feature = "keyring")]
+pub fn load_api_key(profile: &str) -> anyhow::Result<Option<SecretString>> {
+    match Entry::new(SERVICE, profile)?.get_password() {
+        Ok(api_key) => Ok(Some(SecretString::from(api_key))),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Check that a keyring backend is reachable, without touching any stored credential.
+#[cfg(feature = "keyring")]
+pub fn check_available() -> anyhow::Result<()> {
+    match Entry::new(SERVICE, "__availability_check__")?.get_password() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn unavailable() -> anyhow::Error {
+    anyhow::anyhow!("Keyring support is not compiled in (build with the `keyring` feature)")
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store_api_key(_profile: &str, _api_key: &str) -> anyhow::Result<()> {
+    Err(unavailable())
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store_secret(_name: &str, _secret: &str) -> anyhow::Result<()> {
+    Err(unavailable())
+}
+
+/// Without keyring support, profiles must carry `api_key` in the config file or the environment.
+#[cfg(not(feature = "keyring"))]
+pub fn load_api_key(_profile: &str) -> anyhow::Result<Option<SecretString>> {
+    Ok(None)
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn check_available() -> anyhow::Result<()> {
+    Err(unavailable())
+}
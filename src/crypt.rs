@@ -0,0 +1,156 @@
+//! age encryption for exports that contain key secrets, so they never touch the disk in
+//! plaintext. Files are ASCII-armored, and `age -d` decrypts them as well as `import` does.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use age::armor::{ArmoredReader, ArmoredWriter, Format};
+use age::secrecy::SecretString;
+
+/// Read instead of prompting, for scripts and CI jobs.
+pub const PASSPHRASE_ENV: &str = "NEW_RELIC_EXPORT_PASSPHRASE";
+
+const ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const BINARY_HEADER: &str = "age-encryption.org/v1";
+
+pub enum Encryption {
+    /// age X25519 recipients (`age1...`)
+    Recipients(Vec<age::x25519::Recipient>),
+    Passphrase(SecretString),
+}
+
+impl Encryption {
+    pub fn recipients(values: &[String]) -> anyhow::Result<Self> {
+        let recipients = values
+            .iter()
+            .map(|value| {
+                value.trim().parse().map_err(|e| {
+                    anyhow::anyhow!(
+                        "Invalid age recipient '{}' ({}); expected age1...",
+                        value,
+                        e
+                    )
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Encryption::Recipients(recipients))
+    }
+
+    /// The passphrase from [`PASSPHRASE_ENV`], or prompted for twice.
+    pub fn passphrase() -> anyhow::Result<Self> {
+        if let Some(passphrase) = passphrase_from_env() {
+            return Ok(Encryption::Passphrase(passphrase));
+        }
+        let passphrase = prompt("Passphrase for the export: ")?;
+        if passphrase.is_empty() {
+            anyhow::bail!("The passphrase must not be empty");
+        }
+        if prompt("Confirm the passphrase: ")? != passphrase {
+            anyhow::bail!("The passphrases do not match");
+        }
+        Ok(Encryption::Passphrase(SecretString::from(passphrase)))
+    }
+
+    /// `plaintext` as an armored age file.
+    pub fn encrypt(&self, plaintext: &[u8]) -> anyhow::Result<String> {
+        let encryptor = match self {
+            Encryption::Recipients(recipients) => age::Encryptor::with_recipients(
+                recipients.iter().map(|r| r as &dyn age::Recipient),
+            )?,
+            Encryption::Passphrase(passphrase) => {
+                age::Encryptor::with_user_passphrase(passphrase.clone())
+            }
+        };
+        let mut armored = Vec::new();
+        let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(
+            &mut armored,
+            Format::AsciiArmor,
+        )?)?;
+        writer.write_all(plaintext)?;
+        writer.finish()?.finish()?;
+        Ok(String::from_utf8(armored)?)
+    }
+}
+
+fn prompt(question: &str) -> anyhow::Result<String> {
+    rpassword::prompt_password(question).map_err(|e| {
+        anyhow::anyhow!(
+            "Could not prompt for the passphrase ({}); set ${}",
+            e,
+            PASSPHRASE_ENV
+        )
+    })
+}
+
+fn passphrase_from_env() -> Option<SecretString> {
+    std::env::var(PASSPHRASE_ENV)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(SecretString::from)
+}
+
+/// Whether `contents` is an age file, armored or binary.
+pub fn is_encrypted(contents: &[u8]) -> bool {
+    let start = contents
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(contents.len());
+    let contents = &contents[start..];
+    contents.starts_with(ARMOR_HEADER.as_bytes()) || contents.starts_with(BINARY_HEADER.as_bytes())
+}
+
+/// Decrypt an age file with the identity files given, or with a passphrase from
+/// [`PASSPHRASE_ENV`] or a prompt when it was encrypted to one.
+pub fn decrypt(contents: &[u8], identities: &[PathBuf]) -> anyhow::Result<Vec<u8>> {
+    let decryptor = age::Decryptor::new_buffered(ArmoredReader::new(contents))?;
+    let mut reader = if decryptor.is_scrypt() {
+        let passphrase = match passphrase_from_env() {
+            Some(passphrase) => passphrase,
+            None => SecretString::from(prompt("Passphrase: ")?),
+        };
+        let identity = age::scrypt::Identity::new(passphrase);
+        decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))?
+    } else {
+        if identities.is_empty() {
+            anyhow::bail!("The file is encrypted to age recipients; pass --identity <key file>");
+        }
+        let mut keys = Vec::new();
+        for path in identities {
+            let file = age::IdentityFile::from_file(path.to_string_lossy().into_owned())
+                .map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+            keys.extend(file.into_identities()?);
+        }
+        decryptor.decrypt(keys.iter().map(|key| key.as_ref()))?
+    };
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use age::secrecy::ExposeSecret;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip_with_identity_file() {
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+        let encryption = Encryption::recipients(&[recipient]).unwrap();
+        let encrypted = encryption.encrypt(b"[{\"key\": \"NRAK-SECRET\"}]").unwrap();
+        assert!(encrypted.starts_with(ARMOR_HEADER));
+        assert!(!encrypted.contains("NRAK-SECRET"));
+        assert!(is_encrypted(format!("\n{}", encrypted).as_bytes()));
+        assert!(!is_encrypted(b"[]"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("key.txt");
+        std::fs::write(&key_file, identity.to_string().expose_secret()).unwrap();
+        let decrypted = decrypt(encrypted.as_bytes(), &[key_file]).unwrap();
+        assert_eq!(decrypted, b"[{\"key\": \"NRAK-SECRET\"}]");
+        assert!(decrypt(encrypted.as_bytes(), &[]).is_err());
+
+        assert!(Encryption::recipients(&["age1nope".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use tokio::sync::RwLock;
+
+use crate::anomaly::{Anomaly, Detector};
+use crate::audit::{self, Evaluation, Rule};
+use crate::config::Policies;
+use crate::inventory::ApiKey;
+use crate::metrics::{self, Snapshot};
+use crate::warnings::{self, Code};
+use crate::{consumption, history, inventory, webhook, NewRelicClient};
+
+/// Ingest anomaly detection, reported to a webhook.
+pub struct Anomalies {
+    pub webhook: String,
+    /// Time between checks, which is also the window each check looks at
+    pub every: Duration,
+    pub detector: Detector,
+}
+
+impl Anomalies {
+    async fn check(
+        &mut self,
+        client: &NewRelicClient,
+        keys: &[ApiKey],
+        account_ids: &[i64],
+    ) -> anyhow::Result<()> {
+        let minutes = (self.every.as_secs() / 60).max(1);
+        let usage =
+            consumption::attribute_to(client, keys, account_ids, &format!("{}m", minutes)).await?;
+        let anomalies = self.detector.observe(&usage, minutes as f64 / 60.0, keys);
+        if anomalies.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<String> = anomalies.iter().map(Anomaly::describe).collect();
+        for line in &lines {
+            eprintln!("Ingest anomaly: {}", line);
+        }
+        let text = format!(
+            "{} ingest anomal{}:\n{}",
+            anomalies.len(),
+            if anomalies.len() == 1 { "y" } else { "ies" },
+            lines.join("\n")
+        );
+        webhook::notify(
+            &self.webhook,
+            &text,
+            &serde_json::json!({ "anomalies": anomalies }),
+        )
+        .await
+    }
+}
+
+/// The key-count limits of the policies, checked on every collection. An account is reported
+/// when it goes over a limit, not again on every collection while it stays there.
+struct Thresholds {
+    rules: Vec<Rule>,
+    webhook: Option<String>,
+    reported: BTreeSet<(&'static str, i64)>,
+}
+
+impl Thresholds {
+    async fn check(&mut self, keys: &[ApiKey]) -> anyhow::Result<()> {
+        let evaluation = Evaluation::new(self.rules.clone(), keys.to_vec(), Utc::now());
+        let current: BTreeSet<(&'static str, i64)> = evaluation
+            .breaches
+            .iter()
+            .map(|breach| (breach.rule, breach.account_id))
+            .collect();
+        let new: Vec<audit::Breach> = evaluation
+            .breaches
+            .into_iter()
+            .filter(|breach| !self.reported.contains(&(breach.rule, breach.account_id)))
+            .collect();
+        self.reported = current;
+        if new.is_empty() {
+            return Ok(());
+        }
+        for breach in &new {
+            eprintln!("Key-count limit exceeded: {}", breach.describe());
+        }
+        match &self.webhook {
+            Some(url) => audit::notify(url, &new).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    snapshot: Arc<RwLock<Snapshot>>,
+    history_file: PathBuf,
+}
+
+async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let operations = history::operation_counts(&state.history_file).unwrap_or_else(|e| {
+        warnings::warn(
+            Code::History,
+            format!("unable to read history for metrics: {}", e),
+        );
+        Default::default()
+    });
+    let body = metrics::render(&*state.snapshot.read().await, &operations);
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+}
+
+/// Collect the key inventory every `interval` and expose it as Prometheus metrics on `listen`.
+/// Accounts over a key-count limit of `policies` are reported on stderr and to its webhook.
+pub async fn run(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    policies: &Policies,
+    listen: SocketAddr,
+    interval: Duration,
+    history_file: PathBuf,
+    mut anomalies: Option<Anomalies>,
+) -> anyhow::Result<()> {
+    let mut thresholds = Thresholds {
+        rules: audit::count_rules(policies),
+        webhook: policies.webhook.clone(),
+        reported: BTreeSet::new(),
+    };
+    let state = AppState {
+        snapshot: Arc::new(RwLock::new(Snapshot::default())),
+        history_file,
+    };
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(state.clone());
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .map_err(|e| anyhow::anyhow!("Unable to listen on {}: {}", listen, e))?;
+    println!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut ticker = tokio::time::interval(interval);
+    let mut last_check: Option<Instant> = None;
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            result = &mut shutdown => {
+                result?;
+                break;
+            }
+        }
+        let keys = tokio::select! {
+            keys = inventory::fetch(client, account_ids, &["INGEST", "USER"]) => keys,
+            result = &mut shutdown => {
+                result?;
+                break;
+            }
+        };
+        match keys {
+            Ok(keys) => {
+                state
+                    .snapshot
+                    .write()
+                    .await
+                    .update(&keys, Utc::now(), policies.max_key_age_days);
+                if !thresholds.rules.is_empty() {
+                    if let Err(e) = thresholds.check(&keys).await {
+                        warnings::warn(
+                            Code::PartialResults,
+                            format!("key-count notification failed: {}", e),
+                        );
+                    }
+                }
+                if let Some(anomalies) = &mut anomalies {
+                    if last_check.is_none_or(|at| at.elapsed() >= anomalies.every) {
+                        last_check = Some(Instant::now());
+                        if let Err(e) = anomalies.check(client, &keys, account_ids).await {
+                            warnings::warn(
+                                Code::PartialResults,
+                                format!("ingest anomaly check failed: {}", e),
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                warnings::warn(
+                    Code::PartialResults,
+                    format!("inventory collection failed: {}", e),
+                );
+                state.snapshot.write().await.record_error();
+            }
+        }
+    }
+
+    server.abort();
+    Ok(())
+}
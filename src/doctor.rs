@@ -0,0 +1,332 @@
+use std::error::Error as _;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::{credentials, fetch_identity, key_type_from_prefix, NewRelicClient};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const NO_PROXY_DETAIL: &str = "no proxy configured";
+
+const PROXY_VARIABLES: [&str; 6] = [
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+#[derive(Debug, PartialEq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn print(&self) {
+        let label = match self.status {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", label, self.name, self.detail);
+        if let Some(hint) = &self.hint {
+            println!("       hint: {}", hint);
+        }
+    }
+}
+
+/// Run every diagnostic check, print the checklist and fail if any check failed.
+pub async fn run(
+    client: Option<&NewRelicClient>,
+    endpoint: &str,
+    config_file: &Path,
+) -> anyhow::Result<()> {
+    let proxy = check_proxy(|name| std::env::var(name).ok());
+    let proxied = proxy.detail != NO_PROXY_DETAIL;
+    let mut checks = vec![check_config(config_file), check_keyring(), proxy];
+
+    match reqwest::Url::parse(endpoint) {
+        Ok(url) => {
+            checks.push(check_connectivity(&url, proxied).await);
+            checks.push(check_tls(&url).await);
+        }
+        Err(e) => checks.push(Check::fail(
+            "Endpoint",
+            format!("'{}' is not a valid URL ({})", endpoint, e),
+            "pass a full URL such as https://api.newrelic.com/graphql to --endpoint",
+        )),
+    }
+
+    checks.push(check_credentials(client).await);
+
+    for check in &checks {
+        check.print();
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} check(s) failed", failed));
+    }
+    Ok(())
+}
+
+fn check_config(path: &Path) -> Check {
+    if !path.exists() {
+        return Check::pass(
+            "Config",
+            format!("no config file at {} (using defaults)", path.display()),
+        );
+    }
+    match Config::load(path) {
+        Ok(config) => Check::pass(
+            "Config",
+            format!(
+                "{} is valid ({} profile(s))",
+                path.display(),
+                config.profiles.len()
+            ),
+        ),
+        Err(e) => Check::fail(
+            "Config",
+            e.to_string(),
+            "fix the file with `config edit` or remove the offending key with `config unset`",
+        ),
+    }
+}
+
+fn check_keyring() -> Check {
+    match credentials::check_available() {
+        Ok(()) => Check::pass("Keyring", "system keyring is available"),
+        Err(e) => Check::warn(
+            "Keyring",
+            format!("system keyring is unavailable ({})", e),
+            "API keys cannot be stored by `init`; use NEW_RELIC_API_KEY or a profile api_key instead",
+        ),
+    }
+}
+
+fn check_proxy(lookup: impl Fn(&str) -> Option<String>) -> Check {
+    let configured: Vec<(&str, String)> = PROXY_VARIABLES
+        .iter()
+        .filter_map(|name| lookup(name).map(|value| (*name, value)))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+
+    if configured.is_empty() {
+        return Check::pass("Proxy", NO_PROXY_DETAIL);
+    }
+
+    for (name, value) in &configured {
+        if reqwest::Url::parse(value).is_err() {
+            return Check::fail(
+                "Proxy",
+                format!("{} is set to an invalid URL '{}'", name, value),
+                format!(
+                    "use a full URL, e.g. {}=http://proxy.example.com:8080",
+                    name
+                ),
+            );
+        }
+    }
+
+    let names: Vec<&str> = configured.iter().map(|(name, _)| *name).collect();
+    let mut detail = format!("using {}", names.join(", "));
+    if let Some(no_proxy) = lookup("NO_PROXY").or_else(|| lookup("no_proxy")) {
+        detail.push_str(&format!(" (NO_PROXY={})", no_proxy));
+    }
+    Check::pass("Proxy", detail)
+}
+
+async fn check_connectivity(url: &reqwest::Url, proxied: bool) -> Check {
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Check::fail(
+            "Connectivity",
+            format!("cannot determine host and port from {}", url),
+            "check the --endpoint value",
+        );
+    };
+
+    let address = format!("{}:{}", host, port);
+    let result =
+        tokio::time::timeout(CHECK_TIMEOUT, tokio::net::TcpStream::connect(&address)).await;
+    match result {
+        Ok(Ok(_)) => Check::pass("Connectivity", format!("connected to {}", address)),
+        _ if proxied => Check::warn(
+            "Connectivity",
+            format!("no direct connection to {}", address),
+            "expected when traffic must go through the configured proxy; see the TLS check",
+        ),
+        Ok(Err(e)) => Check::fail(
+            "Connectivity",
+            format!("cannot connect to {} ({})", address, e),
+            "check DNS, firewall rules, or whether a proxy is required on this network",
+        ),
+        Err(_) => Check::fail(
+            "Connectivity",
+            format!("timed out connecting to {}", address),
+            "check firewall rules, or whether a proxy is required on this network",
+        ),
+    }
+}
+
+async fn check_tls(url: &reqwest::Url) -> Check {
+    let client = match reqwest::Client::builder().timeout(CHECK_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::fail(
+                "TLS",
+                format!("cannot build HTTP client ({})", e),
+                "check the system TLS installation",
+            )
+        }
+    };
+
+    match client.get(url.clone()).send().await {
+        Ok(response) => Check::pass(
+            "TLS",
+            format!(
+                "handshake with {} succeeded (HTTP {})",
+                url,
+                response.status()
+            ),
+        ),
+        Err(e) => {
+            let chain = error_chain(&e);
+            if chain.contains("certificate") || chain.contains("tls") || chain.contains("ssl") {
+                Check::fail(
+                    "TLS",
+                    format!("TLS handshake failed ({})", chain),
+                    "install your organization's root CA into the system trust store, \
+                     or check for a TLS-intercepting proxy",
+                )
+            } else {
+                Check::fail(
+                    "TLS",
+                    format!("request failed ({})", chain),
+                    "fix the connectivity check above first",
+                )
+            }
+        }
+    }
+}
+
+async fn check_credentials(client: Option<&NewRelicClient>) -> Check {
+    let Some(client) = client else {
+        return Check::fail(
+            "Credentials",
+            "no API key provided",
+            "export NEW_RELIC_API_KEY=\"NRAK-...\" or pass --api-key",
+        );
+    };
+
+    let key_type = key_type_from_prefix(client.api_key());
+    match fetch_identity(client).await {
+        Ok(identity) => {
+            let email = identity
+                .actor
+                .user
+                .map(|user| user.email)
+                .unwrap_or_else(|| "N/A".to_string());
+            Check::pass(
+                "Credentials",
+                format!("valid {} key for {}", key_type, email),
+            )
+        }
+        Err(e) if key_type != "USER" => Check::fail(
+            "Credentials",
+            e.to_string(),
+            format!(
+                "the key looks like a {} key; NerdGraph requires a User key (NRAK-...)",
+                key_type
+            ),
+        ),
+        Err(e) => Check::fail(
+            "Credentials",
+            e.to_string(),
+            "check that the key has not been deleted and belongs to the right region",
+        ),
+    }
+}
+
+/// Flatten an error and its sources into one lowercase line for classification and display.
+fn error_chain(error: &reqwest::Error) -> String {
+    let mut chain = error.to_string();
+    let mut source = error.source();
+    while let Some(err) = source {
+        let message = err.to_string();
+        if !chain.contains(&message) {
+            chain.push_str(": ");
+            chain.push_str(&message);
+        }
+        source = err.source();
+    }
+    chain.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_proxy_without_proxy() {
+        let check = check_proxy(|_| None);
+        assert_eq!(check.status, Status::Pass);
+        assert_eq!(check.detail, NO_PROXY_DETAIL);
+    }
+
+    #[test]
+    fn test_check_proxy_rejects_invalid_url() {
+        let check = check_proxy(|name| (name == "HTTPS_PROXY").then(|| "not a url".to_string()));
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.hint.is_some());
+    }
+
+    #[test]
+    fn test_check_proxy_reports_no_proxy() {
+        let check = check_proxy(|name| match name {
+            "HTTPS_PROXY" => Some("http://proxy.example.com:8080".to_string()),
+            "NO_PROXY" => Some("localhost".to_string()),
+            _ => None,
+        });
+        assert_eq!(check.status, Status::Pass);
+        assert_eq!(check.detail, "using HTTPS_PROXY (NO_PROXY=localhost)");
+    }
+}
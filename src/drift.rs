@@ -0,0 +1,411 @@
+//! `drift check`: compares the keys declared in a manifest checked into a repository with the
+//! keys NerdGraph has, prints the differences as Markdown and, in pull request pipelines, posts
+//! them as a comment through the GitHub API.
+//!
+//! ```yaml
+//! accounts: [123456]          # optional; default: the accounts the keys below are in
+//! keys:
+//!   - name: prod license
+//!     type: INGEST
+//!     account_id: 123456
+//!     ingest_type: LICENSE
+//!     notes: "owner:payments"
+//!   - id: "ABC123"            # match by ID instead of account, type and name
+//!     name: deploy bot
+//!     type: USER
+//!     account_id: 123456
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::{self, ApiKey};
+use crate::{output, NewRelicClient};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Accounts whose keys must all be declared
+    #[serde(default)]
+    pub accounts: Vec<i64>,
+    #[serde(default)]
+    pub keys: Vec<ManifestKey>,
+}
+
+/// A key the manifest declares; unset optional fields are not compared.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestKey {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub key_type: String,
+    pub account_id: i64,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub ingest_type: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<i64>,
+}
+
+impl Manifest {
+    /// Parse a YAML manifest, or a JSON one in builds without the `yaml` feature.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        #[cfg(feature = "yaml")]
+        let manifest: Self =
+            serde_yaml::from_str(text).map_err(|e| anyhow::anyhow!("Invalid manifest: {}", e))?;
+        #[cfg(not(feature = "yaml"))]
+        let manifest: Self = serde_json::from_str(text).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid manifest: {} (YAML manifests need the `yaml` feature)",
+                e
+            )
+        })?;
+        for key in &manifest.keys {
+            if !matches!(key.key_type.to_uppercase().as_str(), "INGEST" | "USER") {
+                anyhow::bail!(
+                    "Unsupported key type '{}' for '{}' in the manifest (expected INGEST or USER)",
+                    key.key_type,
+                    key.name
+                );
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// The explicit `accounts`, or the accounts of the declared keys.
+    pub fn account_ids(&self) -> Vec<i64> {
+        if !self.accounts.is_empty() {
+            return self.accounts.clone();
+        }
+        let accounts: BTreeSet<i64> = self.keys.iter().map(|key| key.account_id).collect();
+        accounts.into_iter().collect()
+    }
+}
+
+/// One difference between the manifest and NerdGraph.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Drift {
+    /// `missing` (declared, not in NerdGraph), `changed` or `undeclared` (not in the manifest)
+    pub status: &'static str,
+    pub id: Option<String>,
+    pub key_type: String,
+    pub account_id: Option<i64>,
+    pub name: Option<String>,
+    pub detail: String,
+}
+
+fn declares(declared: &ManifestKey, key: &ApiKey) -> bool {
+    match &declared.id {
+        Some(id) => *id == key.id,
+        None => {
+            key.account_id == Some(declared.account_id)
+                && key.key_type.as_deref().unwrap_or_default() == declared.key_type.to_uppercase()
+                && key.name.as_deref() == Some(declared.name.as_str())
+        }
+    }
+}
+
+fn compare(manifest: &Manifest, live: &[ApiKey]) -> Vec<Drift> {
+    let mut drift = Vec::new();
+    let mut declared_ids = BTreeSet::new();
+    for declared in &manifest.keys {
+        let Some(key) = live.iter().find(|key| declares(declared, key)) else {
+            drift.push(Drift {
+                status: "missing",
+                id: declared.id.clone(),
+                key_type: declared.key_type.to_uppercase(),
+                account_id: Some(declared.account_id),
+                name: Some(declared.name.clone()),
+                detail: "declared in the manifest but not in NerdGraph".to_string(),
+            });
+            continue;
+        };
+        declared_ids.insert(key.id.as_str());
+
+        let text = |value: Option<&str>| value.unwrap_or_default().to_string();
+        let mut changes = Vec::new();
+        let mut compare = |field: &str, wanted: Option<String>, actual: Option<String>| {
+            if let Some(wanted) = wanted.filter(|wanted| Some(wanted) != actual.as_ref()) {
+                changes.push(format!(
+                    "{} '{}' -> '{}'",
+                    field,
+                    text(actual.as_deref()),
+                    wanted
+                ));
+            }
+        };
+        compare("name", Some(declared.name.clone()), key.name.clone());
+        compare("notes", declared.notes.clone(), key.notes.clone());
+        compare(
+            "ingest_type",
+            declared.ingest_type.as_ref().map(|t| t.to_uppercase()),
+            key.ingest_type.clone(),
+        );
+        compare(
+            "user_id",
+            declared.user_id.map(|id| id.to_string()),
+            key.user_id.map(|id| id.to_string()),
+        );
+        if !changes.is_empty() {
+            drift.push(Drift {
+                status: "changed",
+                id: Some(key.id.clone()),
+                key_type: key.key_type.clone().unwrap_or_default(),
+                account_id: key.account_id,
+                name: key.name.clone(),
+                detail: changes.join(", "),
+            });
+        }
+    }
+
+    for key in live {
+        if !declared_ids.contains(key.id.as_str()) {
+            drift.push(Drift {
+                status: "undeclared",
+                id: Some(key.id.clone()),
+                key_type: key.key_type.clone().unwrap_or_default(),
+                account_id: key.account_id,
+                name: key.name.clone(),
+                detail: "in NerdGraph but not in the manifest".to_string(),
+            });
+        }
+    }
+    drift
+}
+
+/// The drift as a Markdown section, for the job log and pull request comments.
+pub fn markdown(manifest_path: &Path, declared: usize, drift: &[Drift]) -> String {
+    let mut text = String::from("### API key drift\n\n");
+    if drift.is_empty() {
+        let _ = writeln!(
+            text,
+            "No drift: the {} key(s) declared in `{}` match NerdGraph and every key is declared.",
+            declared,
+            manifest_path.display()
+        );
+        return text;
+    }
+    let _ = writeln!(
+        text,
+        "{} key(s) drifted from `{}`:\n",
+        drift.len(),
+        manifest_path.display()
+    );
+    text.push_str("| Status | ID | Type | Account | Name | Detail |\n");
+    text.push_str("| --- | --- | --- | --- | --- | --- |\n");
+    let cell = |value: &str| value.replace('|', "\\|");
+    for d in drift {
+        let _ = writeln!(
+            text,
+            "| {} | {} | {} | {} | {} | {} |",
+            d.status,
+            d.id.as_deref()
+                .map(|id| format!("`{}`", id))
+                .unwrap_or_default(),
+            d.key_type,
+            d.account_id.map(|id| id.to_string()).unwrap_or_default(),
+            cell(d.name.as_deref().unwrap_or_default()),
+            cell(&d.detail)
+        );
+    }
+    text
+}
+
+/// The pull request a GitHub Actions run belongs to, from its event payload.
+fn pull_request_number() -> anyhow::Result<u64> {
+    let path = std::env::var("GITHUB_EVENT_PATH").map_err(|_| {
+        anyhow::anyhow!("--post-comment needs --pr outside a GitHub Actions pull request run")
+    })?;
+    let event: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    event["pull_request"]["number"]
+        .as_u64()
+        .or_else(|| event["number"].as_u64())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "--post-comment needs --pr: this run was not triggered by a pull request"
+            )
+        })
+}
+
+/// Post `body` as a comment on pull request `pr` of `$GITHUB_REPOSITORY`.
+async fn post_comment(pr: Option<u64>, body: &str) -> anyhow::Result<()> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let token =
+        var("GITHUB_TOKEN").ok_or_else(|| anyhow::anyhow!("--post-comment needs $GITHUB_TOKEN"))?;
+    let repository = var("GITHUB_REPOSITORY").ok_or_else(|| {
+        anyhow::anyhow!("--post-comment needs $GITHUB_REPOSITORY, e.g. octo-org/infra")
+    })?;
+    let api = var("GITHUB_API_URL").unwrap_or_else(|| "https://api.github.com".to_string());
+    let pr = match pr {
+        Some(pr) => pr,
+        None => pull_request_number()?,
+    };
+    let url = format!(
+        "{}/repos/{}/issues/{}/comments",
+        api.trim_end_matches('/'),
+        repository,
+        pr
+    );
+    let response = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(token)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", crate::DEFAULT_USER_AGENT)
+        .json(&serde_json::json!({ "body": body }))
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Could not comment on pull request #{}: {}", pr, e))?;
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "GitHub refused the comment on pull request #{}: HTTP {} {}",
+            pr,
+            response.status(),
+            response.text().await.unwrap_or_default().trim()
+        );
+    }
+    eprintln!("Posted the drift to pull request #{}", pr);
+    Ok(())
+}
+
+/// How `drift check` reports.
+pub struct Options {
+    /// `--format json` instead of Markdown
+    pub json: bool,
+    /// Comment on the pull request when anything drifted
+    pub post_comment: bool,
+    /// `--pr`, when not running for a GitHub Actions pull request event
+    pub pr: Option<u64>,
+}
+
+/// Compare `manifest_path` with the keys of its accounts, or of `account_ids` when given.
+/// Fails when anything drifted.
+pub async fn check(
+    client: &NewRelicClient,
+    manifest_path: &Path,
+    account_ids: Option<Vec<i64>>,
+    options: &Options,
+) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(manifest_path)
+        .map_err(|e| anyhow::anyhow!("Could not read {}: {}", manifest_path.display(), e))?;
+    let mut manifest = Manifest::parse(&text)?;
+    let account_ids = match account_ids {
+        Some(account_ids) => {
+            manifest
+                .keys
+                .retain(|key| account_ids.contains(&key.account_id));
+            account_ids
+        }
+        None => manifest.account_ids(),
+    };
+    if account_ids.is_empty() {
+        anyhow::bail!(
+            "{} declares no keys or accounts; pass --account-id",
+            manifest_path.display()
+        );
+    }
+
+    let live = inventory::fetch(client, &account_ids, &["INGEST", "USER"]).await?;
+    let drift = compare(&manifest, &live);
+    let report = markdown(manifest_path, manifest.keys.len(), &drift);
+    if options.json {
+        println!("{}", output::json(&drift)?);
+    } else {
+        print!("{}", report);
+    }
+    if options.post_comment && !drift.is_empty() {
+        post_comment(options.pr, &report).await?;
+    }
+
+    if !drift.is_empty() {
+        anyhow::bail!(
+            "{} key(s) drifted from {}",
+            drift.len(),
+            manifest_path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(id: &str, name: &str, notes: Option<&str>) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "name": name, "notes": notes, "type": "INGEST",
+            "createdAt": 1, "accountId": 1, "ingestType": "LICENSE"
+        }))
+    }
+
+    const MANIFEST: &str = r#"{
+        "keys": [
+            {"name": "prod license", "type": "ingest", "account_id": 1, "notes": "owner:web"},
+            {"id": "K2", "name": "browser", "type": "INGEST", "account_id": 1},
+            {"name": "gone", "type": "USER", "account_id": 1, "user_id": 7}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        assert_eq!(manifest.keys.len(), 3);
+        assert_eq!(manifest.account_ids(), vec![1]);
+        assert!(
+            Manifest::parse(r#"{"keys": [{"name": "x", "type": "ADMIN", "account_id": 1}]}"#)
+                .is_err()
+        );
+        assert!(Manifest::parse(r#"{"key": []}"#).is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_parse_yaml_manifest() {
+        let manifest = Manifest::parse(
+            "accounts: [1, 2]\nkeys:\n  - name: prod license\n    type: INGEST\n    account_id: 1\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.account_ids(), vec![1, 2]);
+        assert_eq!(manifest.keys[0].name, "prod license");
+    }
+
+    #[test]
+    fn test_compare_reports_missing_changed_and_undeclared() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        let live = vec![
+            live("K1", "prod license", Some("owner:web")),
+            live("K2", "browser (old)", None),
+            live("K3", "hand-made", None),
+        ];
+        let drift = compare(&manifest, &live);
+        let summary: Vec<(&str, Option<&str>, &str)> = drift
+            .iter()
+            .map(|d| (d.status, d.id.as_deref(), d.detail.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("changed", Some("K2"), "name 'browser (old)' -> 'browser'"),
+                (
+                    "missing",
+                    None,
+                    "declared in the manifest but not in NerdGraph"
+                ),
+                (
+                    "undeclared",
+                    Some("K3"),
+                    "in NerdGraph but not in the manifest"
+                ),
+            ]
+        );
+
+        let markdown = markdown(Path::new("keys.yaml"), 3, &drift);
+        assert!(markdown.contains("3 key(s) drifted from `keys.yaml`"));
+        assert!(markdown.contains("| changed | `K2` | INGEST | 1 | browser (old) |"));
+    }
+}
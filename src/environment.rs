@@ -0,0 +1,406 @@
+//! `provision env`: the standard ingest keys of a named environment, a license and a browser key
+//! per account unless `[environments.<name>]` lists others, created in one operation. Every key
+//! carries the label `env:<name>` in its notes, which is how the keys of an environment are
+//! found again.
+//!
+//! `decommission env` is the reverse: it deletes the labeled keys (or every key of the
+//! environment's accounts), checks that none survived, writes a report and notifies a webhook.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::{self, Environment, KeyTemplate};
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::prompt::Confirmation;
+use crate::sink::Secrets;
+use crate::{webhook, NewRelicClient};
+
+/// The notes label of the keys of `env`.
+pub fn label(env: &str) -> String {
+    format!("env:{}", env)
+}
+
+/// Whether `key` carries the label of `env` as a word of its notes.
+pub fn is_labeled(key: &ApiKey, env: &str) -> bool {
+    let label = label(env);
+    key.notes
+        .as_deref()
+        .is_some_and(|notes| notes.split_whitespace().any(|word| word == label))
+}
+
+fn default_templates() -> Vec<KeyTemplate> {
+    ["LICENSE", "BROWSER"]
+        .into_iter()
+        .map(|ingest_type| KeyTemplate {
+            ingest_type: ingest_type.to_string(),
+            name: "{env} {ingest_type} ({account_id})".to_string(),
+            notes: None,
+        })
+        .collect()
+}
+
+fn render(template: &str, env: &str, account_id: i64, ingest_type: &str) -> String {
+    template
+        .replace("{env}", env)
+        .replace("{account_id}", &account_id.to_string())
+        .replace("{ingest_type}", &ingest_type.to_lowercase())
+}
+
+/// The keys `env` still lacks: one per account and template, unless the account already has a
+/// labeled ingest key of that name.
+pub fn plan(
+    env: &str,
+    environment: &Environment,
+    account_ids: &[i64],
+    existing: &[ApiKey],
+) -> anyhow::Result<Vec<NewKey>> {
+    let templates = if environment.keys.is_empty() {
+        default_templates()
+    } else {
+        environment.keys.clone()
+    };
+    let mut planned = Vec::new();
+    for &account_id in account_ids {
+        for template in &templates {
+            let ingest_type = template.ingest_type.to_uppercase();
+            if !matches!(ingest_type.as_str(), "LICENSE" | "BROWSER") {
+                anyhow::bail!(
+                    "Unsupported ingest type '{}' in environment '{}' (expected LICENSE or BROWSER)",
+                    template.ingest_type,
+                    env
+                );
+            }
+            let name = render(&template.name, env, account_id, &ingest_type);
+            let provisioned = existing.iter().any(|key| {
+                key.account_id == Some(account_id)
+                    && key.name.as_deref() == Some(name.as_str())
+                    && is_labeled(key, env)
+            });
+            if provisioned {
+                continue;
+            }
+            let notes = match &template.notes {
+                Some(notes) => format!(
+                    "{} {}",
+                    render(notes, env, account_id, &ingest_type),
+                    label(env)
+                ),
+                None => label(env),
+            };
+            planned.push(NewKey {
+                key_type: "INGEST".to_string(),
+                account_id,
+                name,
+                notes: Some(notes),
+                ingest_type: Some(ingest_type),
+                user_id: None,
+            });
+        }
+    }
+    Ok(planned)
+}
+
+/// Create the keys `env` lacks in `account_ids` and print them. Keys created before a failure
+/// are still printed, so that no secret is lost.
+pub async fn provision(
+    client: &NewRelicClient,
+    env: &str,
+    environment: &Environment,
+    account_ids: &[i64],
+    dry_run: bool,
+    confirmation: Confirmation,
+    secrets: &Secrets,
+) -> anyhow::Result<()> {
+    let existing = inventory::fetch(client, account_ids, &["INGEST"]).await?;
+    let planned = plan(env, environment, account_ids, &existing)?;
+    if planned.is_empty() {
+        eprintln!(
+            "Environment {} is already provisioned in {} account(s)",
+            env,
+            account_ids.len()
+        );
+        return Ok(());
+    }
+    eprintln!(
+        "{} key(s) to create for environment {}:",
+        planned.len(),
+        env
+    );
+    for key in &planned {
+        eprintln!(
+            "  {} {} in account {}",
+            key.ingest_type.as_deref().unwrap_or_default(),
+            key.name,
+            key.account_id
+        );
+    }
+    if dry_run {
+        return Ok(());
+    }
+    secrets.check()?;
+    if !confirmation.confirm(&format!("Create {} key(s)?", planned.len()))? {
+        println!("Nothing created");
+        return Ok(());
+    }
+    let mut created = Vec::new();
+    let mut failure = None;
+    for spec in &planned {
+        match inventory::create(client, spec).await {
+            Ok(key) => created.push(key),
+            Err(e) => {
+                failure = Some(e);
+                break;
+            }
+        }
+    }
+    let created: Vec<&ApiKey> = created.iter().collect();
+    if !created.is_empty() {
+        secrets
+            .publish(&format!("Provisioned {}", env), &created)
+            .await?;
+        secrets.print(&created, &created)?;
+    }
+    match failure {
+        Some(e) => Err(e.context(format!(
+            "Provisioning {} stopped after {} of {} key(s)",
+            env,
+            created.len(),
+            planned.len()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Options for [`decommission`].
+pub struct DecommissionOptions<'a> {
+    /// Every key of the environment's accounts, not only the labeled ones
+    pub all_keys: bool,
+    pub dry_run: bool,
+    pub confirmation: Confirmation,
+    pub report_path: &'a Path,
+    /// Overrides the environment's `webhook`
+    pub webhook: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    environment: &'a str,
+    generated_at: DateTime<Utc>,
+    account_ids: &'a [i64],
+    deleted: Vec<ReportEntry>,
+    /// Keys still there afterwards, which may keep ingesting
+    remaining: Vec<ReportEntry>,
+    errors: Vec<String>,
+}
+
+/// A key in the report, without its secret.
+#[derive(Serialize)]
+pub(crate) struct ReportEntry {
+    id: String,
+    name: Option<String>,
+    key_type: Option<String>,
+    account_id: Option<i64>,
+}
+
+impl From<&ApiKey> for ReportEntry {
+    fn from(key: &ApiKey) -> Self {
+        ReportEntry {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            key_type: key.key_type.clone(),
+            account_id: key.account_id,
+        }
+    }
+}
+
+/// The keys of `env` among `keys`: the labeled ones, or all of them with `all_keys`.
+pub fn select<'k>(keys: &'k [ApiKey], env: &str, all_keys: bool) -> Vec<&'k ApiKey> {
+    keys.iter()
+        .filter(|key| all_keys || is_labeled(key, env))
+        .collect()
+}
+
+/// Delete the keys of `env`, then report what was deleted and what is left.
+pub async fn decommission(
+    client: &NewRelicClient,
+    env: &str,
+    environment: &Environment,
+    account_ids: &[i64],
+    options: DecommissionOptions<'_>,
+) -> anyhow::Result<()> {
+    let keys = inventory::fetch(client, account_ids, &["INGEST", "USER"]).await?;
+    let doomed = select(&keys, env, options.all_keys);
+    if doomed.is_empty() {
+        println!("No keys of environment {} found", env);
+        return Ok(());
+    }
+    println!("{} key(s) of environment {}:", doomed.len(), env);
+    for key in &doomed {
+        println!(
+            "  {}  {:<6}  account {:<10}  {}",
+            key.id,
+            key.key_type.as_deref().unwrap_or("N/A"),
+            key.account_id.map(|id| id.to_string()).unwrap_or_default(),
+            key.name.as_deref().unwrap_or("N/A")
+        );
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+    if !options.confirmation.confirm(&format!(
+        "Delete {} key(s) and decommission {}?",
+        doomed.len(),
+        env
+    ))? {
+        println!("Nothing deleted");
+        return Ok(());
+    }
+
+    let (user, ingest): (Vec<&ApiKey>, Vec<&ApiKey>) =
+        doomed.iter().partition(|key| key.is_user_key());
+    let ids = |keys: &[&ApiKey]| keys.iter().map(|key| key.id.clone()).collect::<Vec<_>>();
+    let outcome = inventory::delete_keys(client, &ids(&ingest), &ids(&user)).await?;
+
+    // Only a fresh listing shows whether anything of the environment survived.
+    let after = inventory::fetch(client, account_ids, &["INGEST", "USER"]).await?;
+    let remaining = select(&after, env, options.all_keys);
+    let report = Report {
+        environment: env,
+        generated_at: Utc::now(),
+        account_ids,
+        deleted: doomed
+            .iter()
+            .filter(|key| outcome.deleted.contains(&key.id))
+            .map(|key| ReportEntry::from(*key))
+            .collect(),
+        remaining: remaining.into_iter().map(ReportEntry::from).collect(),
+        errors: outcome.errors,
+    };
+    println!(
+        "Deleted {} of {} key(s)",
+        report.deleted.len(),
+        doomed.len()
+    );
+    for error in &report.errors {
+        println!("  error: {}", error);
+    }
+    config::write_private(options.report_path, &serde_json::to_string_pretty(&report)?)?;
+    println!("Report written to {}", options.report_path.display());
+
+    if let Some(url) = options.webhook.or(environment.webhook.as_deref()) {
+        let text = format!(
+            "Decommissioned environment {}: {} key(s) deleted, {} remaining",
+            env,
+            report.deleted.len(),
+            report.remaining.len()
+        );
+        webhook::notify(url, &text, &report).await?;
+    }
+    if !report.remaining.is_empty() {
+        anyhow::bail!(
+            "{} key(s) of environment {} still exist and may keep ingesting: {}",
+            report.remaining.len(),
+            env,
+            report
+                .remaining
+                .iter()
+                .map(|key| key.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_skips_provisioned_keys() {
+        let environment = Environment::default();
+        let existing = vec![
+            ApiKey::fixture(serde_json::json!({
+                "id": "K1", "name": "prod license (1)", "notes": "env:prod", "type": "INGEST",
+                "createdAt": 1, "accountId": 1, "ingestType": "LICENSE"
+            })),
+            // Same name without the label: someone else's key, not part of the environment.
+            ApiKey::fixture(serde_json::json!({
+                "id": "K2", "name": "prod browser (1)", "notes": "env:production",
+                "type": "INGEST", "createdAt": 1, "accountId": 1, "ingestType": "BROWSER"
+            })),
+        ];
+        let planned = plan("prod", &environment, &[1, 2], &existing).unwrap();
+        let names: Vec<(i64, &str)> = planned
+            .iter()
+            .map(|key| (key.account_id, key.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (1, "prod browser (1)"),
+                (2, "prod license (2)"),
+                (2, "prod browser (2)")
+            ]
+        );
+        assert_eq!(planned[0].notes.as_deref(), Some("env:prod"));
+        assert_eq!(planned[0].ingest_type.as_deref(), Some("BROWSER"));
+    }
+
+    #[test]
+    fn test_select_for_decommission() {
+        let keys = vec![
+            ApiKey::fixture(serde_json::json!({
+                "id": "K1", "name": "a", "notes": "owner: x env:prod", "type": "INGEST",
+                "createdAt": 1, "accountId": 1
+            })),
+            ApiKey::fixture(serde_json::json!({
+                "id": "K2", "name": "b", "notes": "env:prod-eu", "type": "USER",
+                "createdAt": 1, "accountId": 1
+            })),
+        ];
+        let labeled: Vec<&str> = select(&keys, "prod", false)
+            .iter()
+            .map(|key| key.id.as_str())
+            .collect();
+        assert_eq!(labeled, vec!["K1"]);
+        assert_eq!(select(&keys, "prod", true).len(), 2);
+    }
+
+    #[test]
+    fn test_plan_renders_templates() {
+        let environment = Environment {
+            keys: vec![KeyTemplate {
+                ingest_type: "license".to_string(),
+                name: "{env}-{account_id}-{ingest_type}".to_string(),
+                notes: Some("owner: platform ({env})".to_string()),
+            }],
+            ..Environment::default()
+        };
+        let planned = plan("staging", &environment, &[7], &[]).unwrap();
+        assert_eq!(planned[0].name, "staging-7-license");
+        assert_eq!(
+            planned[0].notes.as_deref(),
+            Some("owner: platform (staging) env:staging")
+        );
+        assert!(is_labeled(
+            &ApiKey::fixture(serde_json::json!({
+                "id": "K", "name": "x", "notes": "owner: platform (staging) env:staging",
+                "type": "INGEST", "createdAt": 1, "accountId": 7
+            })),
+            "staging"
+        ));
+
+        let invalid = Environment {
+            keys: vec![KeyTemplate {
+                ingest_type: "USER".to_string(),
+                name: "x".to_string(),
+                notes: None,
+            }],
+            ..Environment::default()
+        };
+        assert!(plan("staging", &invalid, &[7], &[]).is_err());
+    }
+}
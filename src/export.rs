@@ -0,0 +1,540 @@
+//! Infrastructure-as-code definitions for existing keys, to bring hand-created keys under
+//! management without recreating them, and JSON inventories that `import` can recreate keys from.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::crypt::{self, Encryption};
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::prompt::Confirmation;
+use crate::sink::Secrets;
+use crate::warnings::{self, Code};
+use crate::{config, output, pager, NewRelicClient};
+
+/// The default `apiVersion` of Crossplane manifests: the `ApiAccessKey` kind of the New Relic
+/// provider generated from the Terraform provider.
+pub const CROSSPLANE_API_VERSION: &str = "newrelic.crossplane.io/v1alpha1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// `newrelic_api_access_key` resources followed by the matching `terraform import` commands
+    Terraform,
+    /// A Pulumi YAML program whose resources import the existing keys
+    Pulumi,
+    /// Crossplane `ApiAccessKey` manifests that adopt the existing keys by external name
+    Crossplane { api_version: String },
+    /// The keys as JSON, including the secrets NerdGraph returns, for `import`
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "terraform" | "tf" => Ok(ExportFormat::Terraform),
+            "pulumi" => Ok(ExportFormat::Pulumi),
+            "crossplane" => Ok(ExportFormat::Crossplane {
+                api_version: CROSSPLANE_API_VERSION.to_string(),
+            }),
+            "json" => Ok(ExportFormat::Json),
+            _ => anyhow::bail!(
+                "Unsupported export format '{}' (expected terraform, pulumi, crossplane or json)",
+                value
+            ),
+        }
+    }
+
+    pub fn render(&self, keys: &[ApiKey]) -> String {
+        match self {
+            ExportFormat::Terraform => terraform(keys),
+            ExportFormat::Pulumi => pulumi(keys),
+            ExportFormat::Crossplane { api_version } => crossplane(keys, api_version),
+            ExportFormat::Json => output::json(keys).unwrap_or_default() + "\n",
+        }
+    }
+}
+
+/// Unique resource names made from key names: lowercase letters and digits joined by
+/// `separator`, starting with a letter and at most `max_len` characters long.
+struct Names {
+    separator: char,
+    max_len: usize,
+    taken: HashSet<String>,
+}
+
+impl Names {
+    fn new(separator: char, max_len: usize) -> Self {
+        Self {
+            separator,
+            max_len,
+            taken: HashSet::new(),
+        }
+    }
+
+    fn name(&mut self, key: &ApiKey) -> String {
+        let separator = self.separator;
+        let mut name = String::new();
+        for c in key.name.as_deref().unwrap_or_default().chars() {
+            if c.is_ascii_alphanumeric() {
+                name.push(c.to_ascii_lowercase());
+            } else if !name.is_empty() && !name.ends_with(separator) {
+                name.push(separator);
+            }
+        }
+        let name = name.trim_end_matches(separator);
+        let mut base = match name.chars().next() {
+            None => format!("key{}{}", separator, key.id.to_lowercase()),
+            Some(first) if !first.is_ascii_alphabetic() => format!("key{}{}", separator, name),
+            Some(_) => name.to_string(),
+        };
+        // Leave room for a numeric suffix.
+        base.truncate(self.max_len.saturating_sub(4));
+        let base = base.trim_end_matches(separator).to_string();
+        let mut name = base.clone();
+        let mut n = 2;
+        while self.taken.contains(&name) {
+            name = format!("{}{}{}", base, separator, n);
+            n += 1;
+        }
+        self.taken.insert(name.clone());
+        name
+    }
+}
+
+enum Value {
+    Number(i64),
+    Text(String),
+}
+
+/// The provider's arguments for a key, in Terraform (snake_case) spelling.
+fn arguments(key: &ApiKey) -> Vec<(&'static str, Value)> {
+    let mut arguments = Vec::new();
+    if let Some(account_id) = key.account_id {
+        arguments.push(("account_id", Value::Number(account_id)));
+    }
+    arguments.push(("key_type", Value::Text(key_type(key).to_string())));
+    if let Some(ingest_type) = &key.ingest_type {
+        arguments.push(("ingest_type", Value::Text(ingest_type.clone())));
+    }
+    if let Some(user_id) = key.user_id {
+        arguments.push(("user_id", Value::Number(user_id)));
+    }
+    if let Some(name) = &key.name {
+        arguments.push(("name", Value::Text(name.clone())));
+    }
+    if let Some(notes) = key.notes.as_deref().filter(|notes| !notes.is_empty()) {
+        arguments.push(("notes", Value::Text(notes.to_string())));
+    }
+    arguments
+}
+
+fn key_type(key: &ApiKey) -> &str {
+    key.key_type.as_deref().unwrap_or("INGEST")
+}
+
+/// The ID every provider imports a key by.
+fn import_id(key: &ApiKey) -> String {
+    format!("{}:{}", key.id, key_type(key))
+}
+
+fn camel_case(snake: &str) -> String {
+    let mut camel = String::new();
+    let mut upper = false;
+    for c in snake.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            camel.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+/// A quoted HCL string; `${` and `%{` are escaped so names are never read as interpolations.
+fn hcl_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '$' | '%' if chars.peek() == Some(&'{') => {
+                quoted.push(c);
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A double-quoted YAML scalar; JSON strings are valid YAML.
+fn yaml_string(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+fn terraform(keys: &[ApiKey]) -> String {
+    let mut names = Names::new('_', usize::MAX);
+    let mut resources = String::new();
+    let mut imports = Vec::new();
+    for key in keys {
+        let name = names.name(key);
+        let arguments = arguments(key);
+        let width = arguments.iter().map(|(a, _)| a.len()).max().unwrap_or(0);
+        let _ = writeln!(
+            resources,
+            "resource \"newrelic_api_access_key\" \"{}\" {{",
+            name
+        );
+        for (argument, value) in &arguments {
+            let value = match value {
+                Value::Number(n) => n.to_string(),
+                Value::Text(text) => hcl_string(text),
+            };
+            let _ = writeln!(resources, "  {:width$} = {}", argument, value);
+        }
+        resources.push_str("}\n\n");
+        imports.push(format!(
+            "terraform import newrelic_api_access_key.{} '{}'",
+            name,
+            import_id(key)
+        ));
+    }
+
+    resources.push_str("# Import the existing keys into the Terraform state:\n");
+    for import in imports {
+        let _ = writeln!(resources, "# {}", import);
+    }
+    resources
+}
+
+/// Resource arguments as YAML mapping entries in camelCase, indented by `indent`.
+fn yaml_arguments(out: &mut String, key: &ApiKey, indent: &str) {
+    for (argument, value) in arguments(key) {
+        let value = match value {
+            Value::Number(n) => n.to_string(),
+            Value::Text(text) => yaml_string(&text),
+        };
+        let _ = writeln!(out, "{}{}: {}", indent, camel_case(argument), value);
+    }
+}
+
+fn pulumi(keys: &[ApiKey]) -> String {
+    let mut names = Names::new('-', usize::MAX);
+    let mut program = String::from("name: newrelic-api-keys\nruntime: yaml\nresources:\n");
+    if keys.is_empty() {
+        program.truncate(program.len() - 1);
+        program.push_str(" {}\n");
+    }
+    for key in keys {
+        let _ = writeln!(program, "  {}:", names.name(key));
+        program.push_str("    type: newrelic:ApiAccessKey\n    properties:\n");
+        yaml_arguments(&mut program, key, "      ");
+        let _ = writeln!(
+            program,
+            "    options:\n      import: {}",
+            yaml_string(&import_id(key))
+        );
+    }
+    program
+}
+
+fn crossplane(keys: &[ApiKey], api_version: &str) -> String {
+    // Kubernetes object names are DNS labels: at most 63 characters.
+    let mut names = Names::new('-', 63);
+    let mut manifests = Vec::new();
+    for key in keys {
+        let mut manifest = String::new();
+        let _ = writeln!(
+            manifest,
+            "apiVersion: {}\nkind: ApiAccessKey\nmetadata:\n  name: {}\n  annotations:\n    \
+             crossplane.io/external-name: {}\nspec:\n  forProvider:",
+            api_version,
+            names.name(key),
+            yaml_string(&import_id(key))
+        );
+        yaml_arguments(&mut manifest, key, "    ");
+        manifests.push(manifest);
+    }
+    manifests.join("---\n")
+}
+
+/// Export `keys`, ordered by account and ID. With `encryption`, only the armored age file is
+/// ever written or printed.
+pub fn run(
+    mut keys: Vec<ApiKey>,
+    format: &ExportFormat,
+    encryption: Option<&Encryption>,
+    output_path: Option<&Path>,
+    pager: bool,
+) -> anyhow::Result<()> {
+    keys.sort_by(|a, b| (a.account_id, &a.id).cmp(&(b.account_id, &b.id)));
+    let rendered = match encryption {
+        Some(encryption) => encryption.encrypt(format.render(&keys).as_bytes())?,
+        None => format.render(&keys),
+    };
+    match output_path {
+        Some(path) => {
+            config::write_private(path, &rendered)?;
+            println!("{} key(s) exported to {}", keys.len(), path.display());
+        }
+        None if encryption.is_some() => print!("{}", rendered),
+        None => pager::print(&rendered, pager)?,
+    }
+    let secrets = keys.iter().filter(|key| key.key.is_some()).count();
+    if format == &ExportFormat::Json && encryption.is_none() && secrets > 0 {
+        warnings::warn(
+            Code::PlaintextSecrets,
+            format!(
+                "the export contains {} key secret(s) in plaintext; pass --encrypt-to or \
+                 --passphrase to encrypt it",
+                secrets
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// The keys of a `--format json` export, decrypting it first if it is an age file.
+pub fn read_export(contents: &[u8], identities: &[PathBuf]) -> anyhow::Result<Vec<ApiKey>> {
+    let plaintext = if crypt::is_encrypted(contents) {
+        crypt::decrypt(contents, identities)?
+    } else {
+        contents.to_vec()
+    };
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| anyhow::anyhow!("Not a JSON export from `export --format json`: {}", e))
+}
+
+/// Exported keys that no longer exist among `existing`, by ID or by account, type and name.
+fn missing<'a>(exported: &'a [ApiKey], existing: &[ApiKey]) -> Vec<&'a ApiKey> {
+    let ids: HashSet<&str> = existing.iter().map(|key| key.id.as_str()).collect();
+    let named: HashSet<(Option<i64>, &str, Option<&str>)> = existing
+        .iter()
+        .map(|key| (key.account_id, key_type(key), key.name.as_deref()))
+        .collect();
+    exported
+        .iter()
+        .filter(|key| {
+            !ids.contains(key.id.as_str())
+                && !named.contains(&(key.account_id, key_type(key), key.name.as_deref()))
+        })
+        .collect()
+}
+
+/// Recreate the exported keys that no longer exist, in their own accounts or in `account_id`,
+/// and print the new keys. NerdGraph generates new secrets; the exported ones are not reused.
+pub async fn import(
+    client: &NewRelicClient,
+    exported: Vec<ApiKey>,
+    account_id: Option<i64>,
+    dry_run: bool,
+    confirmation: Confirmation,
+    secrets: &Secrets,
+) -> anyhow::Result<()> {
+    let mut exported = exported;
+    if let Some(account_id) = account_id {
+        for key in &mut exported {
+            key.account_id = Some(account_id);
+        }
+    }
+    let mut account_ids: Vec<i64> = exported.iter().filter_map(|key| key.account_id).collect();
+    account_ids.sort_unstable();
+    account_ids.dedup();
+    let existing = inventory::fetch(client, &account_ids, &["INGEST", "USER"]).await?;
+    let missing = missing(&exported, &existing);
+    eprintln!(
+        "{} of {} exported key(s) no longer exist",
+        missing.len(),
+        exported.len()
+    );
+    for key in &missing {
+        eprintln!(
+            "  {} {} {} in account {}",
+            key.id,
+            key_type(key),
+            key.name.as_deref().unwrap_or_default(),
+            key.account_id.unwrap_or_default()
+        );
+    }
+    if missing.is_empty() || dry_run {
+        return Ok(());
+    }
+    secrets.check()?;
+    if !confirmation.confirm(&format!("Recreate {} key(s)?", missing.len()))? {
+        println!("Nothing recreated");
+        return Ok(());
+    }
+    let mut created = Vec::new();
+    for key in missing {
+        created.push(inventory::create(client, &NewKey::replacing(key)?).await?);
+    }
+    let created: Vec<&ApiKey> = created.iter().collect();
+    secrets.publish("Imported API keys", &created).await?;
+    secrets.print(&created, &created)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terraform_resources_and_imports() {
+        let keys = vec![
+            ApiKey::fixture(serde_json::json!({
+                "id": "K1", "name": "payments-prod license", "notes": "owner: ${team}",
+                "type": "INGEST", "createdAt": 1, "accountId": 123, "ingestType": "LICENSE"
+            })),
+            ApiKey::fixture(serde_json::json!({
+                "id": "K2", "name": "Payments prod-license", "notes": null,
+                "type": "USER", "createdAt": 1, "accountId": 123, "userId": 7
+            })),
+            ApiKey::fixture(serde_json::json!({
+                "id": "K3", "name": "1st \"key\"", "notes": "", "type": "INGEST",
+                "createdAt": 1, "accountId": 123
+            })),
+        ];
+        let rendered = ExportFormat::Terraform.render(&keys);
+        assert_eq!(
+            rendered,
+            r#"resource "newrelic_api_access_key" "payments_prod_license" {
+  account_id  = 123
+  key_type    = "INGEST"
+  ingest_type = "LICENSE"
+  name        = "payments-prod license"
+  notes       = "owner: $${team}"
+}
+
+resource "newrelic_api_access_key" "payments_prod_license_2" {
+  account_id = 123
+  key_type   = "USER"
+  user_id    = 7
+  name       = "Payments prod-license"
+}
+
+resource "newrelic_api_access_key" "key_1st_key" {
+  account_id = 123
+  key_type   = "INGEST"
+  name       = "1st \"key\""
+}
+
+# Import the existing keys into the Terraform state:
+# terraform import newrelic_api_access_key.payments_prod_license 'K1:INGEST'
+# terraform import newrelic_api_access_key.payments_prod_license_2 'K2:USER'
+# terraform import newrelic_api_access_key.key_1st_key 'K3:INGEST'
+"#
+        );
+    }
+
+    #[test]
+    fn test_pulumi_and_crossplane() {
+        let keys = vec![
+            ApiKey::fixture(serde_json::json!({
+                "id": "K1", "name": "ci: \"prod\"", "notes": null, "type": "INGEST",
+                "createdAt": 1, "accountId": 123, "ingestType": "BROWSER"
+            })),
+            ApiKey::fixture(serde_json::json!({
+                "id": "K2", "name": "x".repeat(80), "notes": "rotated", "type": "USER",
+                "createdAt": 1, "accountId": 123, "userId": 7
+            })),
+        ];
+        assert_eq!(
+            ExportFormat::Pulumi.render(&keys[..1]),
+            r#"name: newrelic-api-keys
+runtime: yaml
+resources:
+  ci-prod:
+    type: newrelic:ApiAccessKey
+    properties:
+      accountId: 123
+      keyType: "INGEST"
+      ingestType: "BROWSER"
+      name: "ci: \"prod\""
+    options:
+      import: "K1:INGEST"
+"#
+        );
+
+        let manifests = ExportFormat::parse("crossplane").unwrap().render(&keys);
+        let documents: Vec<&str> = manifests.split("---\n").collect();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(
+            documents[1],
+            format!(
+                r#"apiVersion: newrelic.crossplane.io/v1alpha1
+kind: ApiAccessKey
+metadata:
+  name: {}
+  annotations:
+    crossplane.io/external-name: "K2:USER"
+spec:
+  forProvider:
+    accountId: 123
+    keyType: "USER"
+    userId: 7
+    name: "{}"
+    notes: "rotated"
+"#,
+                "x".repeat(59),
+                "x".repeat(80)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_export_format() {
+        assert_eq!(
+            ExportFormat::parse("Terraform").unwrap(),
+            ExportFormat::Terraform
+        );
+        assert_eq!(
+            ExportFormat::parse("ansible").unwrap_err().to_string(),
+            "Unsupported export format 'ansible' (expected terraform, pulumi, crossplane or json)"
+        );
+    }
+
+    #[test]
+    fn test_json_export_round_trip_and_missing_keys() {
+        let exported = vec![
+            ApiKey::fixture(serde_json::json!({
+                "id": "K1", "name": "ci", "notes": null, "type": "INGEST",
+                "key": "S1-NRAL", "createdAt": 1, "accountId": 123, "ingestType": "LICENSE"
+            })),
+            ApiKey::fixture(serde_json::json!({
+                "id": "K2", "name": "deploy", "notes": null, "type": "USER",
+                "createdAt": 1, "accountId": 123, "userId": 7
+            })),
+            ApiKey::fixture(serde_json::json!({
+                "id": "K3", "name": "gone", "notes": null, "type": "INGEST",
+                "createdAt": 1, "accountId": 123, "ingestType": "BROWSER"
+            })),
+        ];
+        let json = ExportFormat::Json.render(&exported);
+        let read = read_export(json.as_bytes(), &[]).unwrap();
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[0].secret(), Some("S1-NRAL"));
+        assert!(read_export(b"resource {}", &[]).is_err());
+
+        // K1 still exists; K2 was recreated under a new ID with the same name.
+        let existing = vec![
+            exported[0].clone(),
+            ApiKey {
+                id: "K9".to_string(),
+                ..exported[1].clone()
+            },
+        ];
+        let missing: Vec<&str> = missing(&read, &existing)
+            .iter()
+            .map(|key| key.id.as_str())
+            .collect();
+        assert_eq!(missing, ["K3"]);
+    }
+}
@@ -0,0 +1,404 @@
+//! Filter expressions for `--where`, evaluated client-side over each key:
+//! `type == 'INGEST' && age_days > 90 && name =~ 'legacy-'`.
+//!
+//! Fields are `id`, `name`, `notes`, `type`, `ingest_type` and `label.<name>` (strings, see
+//! [`selector::labels`]) and `account_id`, `user_id`, `age_days` and `created_at` (numbers, the
+//! last in epoch seconds). Comparisons are `==`, `!=`, `<`, `<=`, `>`, `>=`, plus `=~` and `!~`
+//! for regular expressions; combine them with `&&`, `||`, `!` and parentheses. A bare field is
+//! true when the key has a value for it. Missing values only equal `null`.
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::inventory::ApiKey;
+use crate::selector;
+
+const STRING_FIELDS: [&str; 5] = ["id", "name", "notes", "type", "ingest_type"];
+const NUMBER_FIELDS: [&str; 4] = ["account_id", "user_id", "age_days", "created_at"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 13] = [
+    "&&", "||", "==", "!=", "<=", ">=", "=~", "!~", "<", ">", "!", "(", ")",
+];
+
+fn tokenize(text: &str) -> anyhow::Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => anyhow::bail!("unterminated string at column {}", start + 1),
+                    Some('\\') if chars.get(i + 1).is_some() => {
+                        value.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        value.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((start, Token::Str(value)));
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            i += 1;
+            while chars
+                .get(i)
+                .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+            {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let number = number.parse().map_err(|_| {
+                anyhow::anyhow!("invalid number '{}' at column {}", number, start + 1)
+            })?;
+            tokens.push((start, Token::Num(number)));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while chars
+                .get(i)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/'))
+            {
+                i += 1;
+            }
+            tokens.push((start, Token::Ident(chars[start..i].iter().collect())));
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                anyhow::bail!("unexpected '{}' at column {}", c, start + 1);
+            };
+            i += operator.len();
+            tokens.push((
+                start,
+                match *operator {
+                    "(" => Token::Open,
+                    ")" => Token::Close,
+                    operator => Token::Op(operator),
+                },
+            ));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Field {
+    Str(String),
+    Num(String),
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Str(String),
+    Num(f64),
+    Null,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Compare(Field, &'static str, Literal),
+    Matches(Field, Regex, bool),
+    Present(Field),
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    length: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.length, |(column, _)| *column)
+            + 1
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> anyhow::Result<Node> {
+        let mut node = self.and()?;
+        while self.peek() == Some(&Token::Op("||")) {
+            self.next();
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Node> {
+        let mut node = self.unary()?;
+        while self.peek() == Some(&Token::Op("&&")) {
+            self.next();
+            node = Node::And(Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Node> {
+        let column = self.column();
+        match self.next() {
+            Some(Token::Op("!")) => Ok(Node::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let node = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(node),
+                    _ => anyhow::bail!("missing ')' for the '(' at column {}", column),
+                }
+            }
+            Some(Token::Ident(name)) => self.comparison(field(&name, column)?),
+            _ => anyhow::bail!("expected a field at column {}", column),
+        }
+    }
+
+    fn comparison(&mut self, field: Field) -> anyhow::Result<Node> {
+        let operator = match self.peek() {
+            Some(Token::Op(op)) if !matches!(*op, "&&" | "||" | "!") => *op,
+            _ => return Ok(Node::Present(field)),
+        };
+        self.next();
+        let column = self.column();
+        let literal = match self.next() {
+            Some(Token::Str(value)) => Literal::Str(value),
+            Some(Token::Num(value)) => Literal::Num(value),
+            Some(Token::Ident(word)) if word == "null" => Literal::Null,
+            _ => anyhow::bail!("expected a value after '{}' at column {}", operator, column),
+        };
+        let is_number = matches!(field, Field::Num(_));
+        match (operator, &literal) {
+            ("=~" | "!~", Literal::Str(pattern)) if !is_number => {
+                let regex = Regex::new(pattern).map_err(|e| {
+                    anyhow::anyhow!("invalid regular expression '{}': {}", pattern, e)
+                })?;
+                Ok(Node::Matches(field, regex, operator == "!~"))
+            }
+            ("=~" | "!~", _) => {
+                anyhow::bail!("'{}' needs a string field and a quoted pattern", operator)
+            }
+            ("==" | "!=", Literal::Null) => Ok(Node::Compare(field, operator, literal)),
+            (_, Literal::Null) => anyhow::bail!("null can only be compared with == or !="),
+            (_, Literal::Num(_)) if !is_number => anyhow::bail!(
+                "{} is a string; compare it with a quoted value",
+                field.name()
+            ),
+            (_, Literal::Str(_)) if is_number => {
+                anyhow::bail!("{} is a number; compare it with a number", field.name())
+            }
+            _ => Ok(Node::Compare(field, operator, literal)),
+        }
+    }
+}
+
+fn field(name: &str, column: usize) -> anyhow::Result<Field> {
+    if let Some(label) = name.strip_prefix("label.") {
+        return Ok(Field::Label(label.to_string()));
+    }
+    if STRING_FIELDS.contains(&name) {
+        return Ok(Field::Str(name.to_string()));
+    }
+    if NUMBER_FIELDS.contains(&name) {
+        return Ok(Field::Num(name.to_string()));
+    }
+    anyhow::bail!(
+        "unknown field '{}' at column {} (expected {}, {} or label.<name>)",
+        name,
+        column,
+        STRING_FIELDS.join(", "),
+        NUMBER_FIELDS.join(", ")
+    )
+}
+
+impl Field {
+    fn name(&self) -> String {
+        match self {
+            Field::Str(name) | Field::Num(name) => name.clone(),
+            Field::Label(label) => format!("label.{}", label),
+        }
+    }
+
+    fn value(&self, key: &ApiKey, now: DateTime<Utc>) -> Literal {
+        let text =
+            |value: Option<&str>| value.map_or(Literal::Null, |v| Literal::Str(v.to_string()));
+        let number = |value: Option<i64>| value.map_or(Literal::Null, |v| Literal::Num(v as f64));
+        match self {
+            Field::Label(label) => text(
+                selector::labels(key.notes.as_deref())
+                    .get(label.as_str())
+                    .copied(),
+            ),
+            Field::Str(name) | Field::Num(name) => match name.as_str() {
+                "id" => Literal::Str(key.id.clone()),
+                "name" => text(key.name.as_deref()),
+                "notes" => text(key.notes.as_deref()),
+                "type" => text(key.key_type.as_deref()),
+                "ingest_type" => text(key.ingest_type.as_deref()),
+                "account_id" => number(key.account_id),
+                "user_id" => number(key.user_id),
+                "age_days" => number(key.age_days(now)),
+                "created_at" => number(key.created_at),
+                _ => Literal::Null,
+            },
+        }
+    }
+}
+
+impl Node {
+    fn eval(&self, key: &ApiKey, now: DateTime<Utc>) -> bool {
+        match self {
+            Node::And(left, right) => left.eval(key, now) && right.eval(key, now),
+            Node::Or(left, right) => left.eval(key, now) || right.eval(key, now),
+            Node::Not(node) => !node.eval(key, now),
+            Node::Present(field) => field.value(key, now) != Literal::Null,
+            Node::Matches(field, regex, negated) => match field.value(key, now) {
+                Literal::Str(value) => regex.is_match(&value) != *negated,
+                _ => *negated,
+            },
+            Node::Compare(field, operator, literal) => {
+                let value = field.value(key, now);
+                let ordering = match (&value, literal) {
+                    (Literal::Null, Literal::Null) => Some(Ordering::Equal),
+                    (Literal::Str(a), Literal::Str(b)) => Some(a.cmp(b)),
+                    (Literal::Num(a), Literal::Num(b)) => a.partial_cmp(b),
+                    _ => None,
+                };
+                match (*operator, ordering) {
+                    ("==", ordering) => ordering == Some(Ordering::Equal),
+                    ("!=", ordering) => ordering != Some(Ordering::Equal),
+                    (_, None) => false,
+                    ("<", Some(ordering)) => ordering.is_lt(),
+                    ("<=", Some(ordering)) => ordering.is_le(),
+                    (">", Some(ordering)) => ordering.is_gt(),
+                    (">=", Some(ordering)) => ordering.is_ge(),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// A parsed `--where` expression.
+#[derive(Debug, Clone)]
+pub struct Expression(Node);
+
+impl Expression {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let invalid =
+            |e: anyhow::Error| anyhow::anyhow!("Invalid --where expression '{}': {}", text, e);
+        let mut parser = Parser {
+            tokens: tokenize(text).map_err(invalid)?,
+            position: 0,
+            length: text.chars().count(),
+        };
+        let node = parser.or().map_err(invalid)?;
+        if parser.peek().is_some() {
+            return Err(invalid(anyhow::anyhow!(
+                "unexpected input at column {}",
+                parser.column()
+            )));
+        }
+        Ok(Expression(node))
+    }
+
+    /// The expression that holds when both `self` and `other` do.
+    pub fn and(self, other: Expression) -> Self {
+        Expression(Node::And(Box::new(self.0), Box::new(other.0)))
+    }
+
+    pub fn matches(&self, key: &ApiKey, now: DateTime<Utc>) -> bool {
+        self.0.eval(key, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, key_type: &str, created_at: i64) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": name, "name": name, "notes": "env:prod", "type": key_type,
+            "createdAt": created_at, "accountId": 1
+        }))
+    }
+
+    #[test]
+    fn test_expression_matches() {
+        let now = DateTime::from_timestamp(200 * 86_400, 0).unwrap();
+        let old = key("legacy-ingest", "INGEST", 0);
+        let new = key("payments", "USER", 150 * 86_400);
+        let matches = |text: &str, key: &ApiKey| Expression::parse(text).unwrap().matches(key, now);
+
+        let stale = "type == 'INGEST' && age_days > 90 && name =~ 'legacy-'";
+        assert!(matches(stale, &old));
+        assert!(!matches(stale, &new));
+        assert!(matches(
+            "type == \"USER\" || (age_days >= 200 && !ingest_type)",
+            &old
+        ));
+        assert!(matches("label.env == 'prod' && label.team == null", &new));
+        assert!(matches("name !~ '^legacy' && account_id == 1", &new));
+        assert!(!matches("!(age_days < 100)", &new));
+
+        let both = Expression::parse("age_days > 90")
+            .unwrap()
+            .and(Expression::parse("type == 'USER'").unwrap());
+        assert!(!both.matches(&old, now));
+        assert!(!both.matches(&new, now));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for text in [
+            "",
+            "age_days >",
+            "owner == 'x'",
+            "name > 3",
+            "age_days == 'old'",
+            "(type == 'USER'",
+            "name =~ '('",
+            "type == 'USER' extra",
+            "name = 'x'",
+        ] {
+            assert!(Expression::parse(text).is_err(), "{}", text);
+        }
+    }
+}
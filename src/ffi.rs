@@ -0,0 +1,231 @@
+//! A minimal C ABI over the blocking client, for Python/Go tooling that links the library
+//! instead of reimplementing NerdGraph calls. The header is `include/newrelic_apikeys.h`.
+//!
+//! Results are JSON strings owned by the caller and released with `nr_string_free`. On
+//! failure a function returns NULL (or -1) and `nr_last_error` describes why.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use crate::inventory::NewKey;
+use crate::{NewRelicClient, NewRelicClientBlocking};
+
+/// Opaque client handle.
+pub struct NrClient(NewRelicClientBlocking);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: anyhow::Error) {
+    let message =
+        CString::new(format!("{:#}", error).replace('\0', " ")).expect("NUL bytes were replaced");
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message));
+}
+
+/// Run `f`, converting errors to NULL plus `nr_last_error`.
+fn json_result(f: impl FnOnce() -> anyhow::Result<serde_json::Value>) -> *mut c_char {
+    match f().and_then(|value| Ok(CString::new(value.to_string())?)) {
+        Ok(json) => json.into_raw(),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe fn string_arg<'a>(value: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    if value.is_null() {
+        return Err(anyhow::anyhow!("{} must not be NULL", name));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| anyhow::anyhow!("{} is not valid UTF-8", name))
+}
+
+unsafe fn client_arg<'a>(client: *const NrClient) -> anyhow::Result<&'a NewRelicClientBlocking> {
+    client
+        .as_ref()
+        .map(|client| &client.0)
+        .ok_or_else(|| anyhow::anyhow!("client must not be NULL"))
+}
+
+/// The last error on this thread, or NULL. Valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn nr_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Create a client; `endpoint` may be NULL for the US NerdGraph endpoint.
+///
+/// # Safety
+/// `api_key` and `endpoint` must be NULL or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn nr_client_new(
+    api_key: *const c_char,
+    endpoint: *const c_char,
+) -> *mut NrClient {
+    let result = (|| {
+        let api_key = string_arg(api_key, "api_key")?;
+        let mut builder = NewRelicClient::builder().api_key(api_key);
+        if !endpoint.is_null() {
+            builder = builder.endpoint(string_arg(endpoint, "endpoint")?);
+        }
+        NewRelicClientBlocking::from_async(builder.build()?)
+    })();
+    match result {
+        Ok(client) => Box::into_raw(Box::new(NrClient(client))),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `client` must be NULL or a pointer returned by `nr_client_new`, freed at most once.
+#[no_mangle]
+pub unsafe extern "C" fn nr_client_free(client: *mut NrClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// List keys in the given accounts as a JSON array. `key_types` is a comma-separated list
+/// such as `"USER,INGEST"`, or NULL for both.
+///
+/// # Safety
+/// `client` must come from `nr_client_new`; `account_ids` must point to `account_count`
+/// integers; `key_types` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nr_list_keys(
+    client: *const NrClient,
+    account_ids: *const i64,
+    account_count: usize,
+    key_types: *const c_char,
+) -> *mut c_char {
+    json_result(|| {
+        let client = client_arg(client)?;
+        if account_ids.is_null() && account_count > 0 {
+            return Err(anyhow::anyhow!("account_ids must not be NULL"));
+        }
+        let account_ids = if account_count == 0 {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts(account_ids, account_count)
+        };
+        let key_types = if key_types.is_null() {
+            "USER,INGEST"
+        } else {
+            string_arg(key_types, "key_types")?
+        };
+        let key_types: Vec<&str> = key_types.split(',').map(str::trim).collect();
+        Ok(serde_json::to_value(
+            client.list_keys(account_ids, &key_types)?,
+        )?)
+    })
+}
+
+/// Create a key from a JSON spec (`type`, `account_id`, `name`, optional `notes`,
+/// `ingest_type`, `user_id`), returning the created key, secret included, as JSON.
+///
+/// # Safety
+/// `client` must come from `nr_client_new`; `spec_json` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nr_create_key(
+    client: *const NrClient,
+    spec_json: *const c_char,
+) -> *mut c_char {
+    json_result(|| {
+        let client = client_arg(client)?;
+        let spec: NewKey = serde_json::from_str(string_arg(spec_json, "spec_json")?)?;
+        Ok(serde_json::to_value(client.create_key(&spec)?)?)
+    })
+}
+
+/// Delete one key (`key_type` is `INGEST` or `USER`). Returns 0 on success, -1 on failure.
+///
+/// # Safety
+/// `client` must come from `nr_client_new`; `key_id` and `key_type` must be valid
+/// NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn nr_delete_key(
+    client: *const NrClient,
+    key_id: *const c_char,
+    key_type: *const c_char,
+) -> i32 {
+    let result = (|| {
+        let client = client_arg(client)?;
+        let key_id = vec![string_arg(key_id, "key_id")?.to_string()];
+        let outcome = match string_arg(key_type, "key_type")?.to_uppercase().as_str() {
+            "INGEST" => client.delete_keys(&key_id, &[])?,
+            "USER" => client.delete_keys(&[], &key_id)?,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported key type '{}' (expected INGEST or USER)",
+                    other
+                ))
+            }
+        };
+        match outcome.errors.first() {
+            Some(error) => Err(anyhow::anyhow!("{}", error)),
+            None => Ok(()),
+        }
+    })();
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(e);
+            -1
+        }
+    }
+}
+
+/// # Safety
+/// `value` must be NULL or a string returned by this library, freed at most once.
+#[no_mangle]
+pub unsafe extern "C" fn nr_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(nr_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_null_arguments_set_last_error() {
+        unsafe {
+            assert!(nr_client_new(ptr::null(), ptr::null()).is_null());
+            assert_eq!(last_error(), "api_key must not be NULL");
+
+            assert!(nr_list_keys(ptr::null(), ptr::null(), 0, ptr::null()).is_null());
+            assert_eq!(last_error(), "client must not be NULL");
+        }
+    }
+
+    #[test]
+    fn test_delete_rejects_unknown_key_type() {
+        let api_key = CString::new("NRAK-TEST").unwrap();
+        let key_id = CString::new("ABC").unwrap();
+        let key_type = CString::new("BROWSER").unwrap();
+        unsafe {
+            let client = nr_client_new(api_key.as_ptr(), ptr::null());
+            assert!(!client.is_null());
+            assert_eq!(
+                nr_delete_key(client, key_id.as_ptr(), key_type.as_ptr()),
+                -1
+            );
+            assert!(last_error().contains("Unsupported key type 'BROWSER'"));
+            nr_client_free(client);
+        }
+    }
+}
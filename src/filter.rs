@@ -0,0 +1,42 @@
+//! Client-side key filters shared by the listing, export and bulk commands.
+
+use chrono::Utc;
+
+use crate::expression::Expression;
+use crate::inventory::{self, ApiKey};
+use crate::selector::Selector;
+use crate::NewRelicClient;
+
+/// Which keys a command acts on; the default filter matches every key.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFilter {
+    /// `--selector`
+    pub selector: Option<Selector>,
+    /// `--where`
+    pub expression: Option<Expression>,
+}
+
+impl KeyFilter {
+    pub fn matches(&self, key: &ApiKey) -> bool {
+        let now = Utc::now();
+        self.selector
+            .as_ref()
+            .is_none_or(|selector| selector.matches(key.notes.as_deref()))
+            && self
+                .expression
+                .as_ref()
+                .is_none_or(|expression| expression.matches(key, now))
+    }
+}
+
+/// The keys of the given types in the given accounts that match `filter`.
+pub async fn fetch(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    key_types: &[&str],
+    filter: &KeyFilter,
+) -> anyhow::Result<Vec<ApiKey>> {
+    let mut keys = inventory::fetch(client, account_ids, key_types).await?;
+    keys.retain(|key| filter.matches(key));
+    Ok(keys)
+}
@@ -0,0 +1,118 @@
+//! Key fingerprints: a short SHA-256 digest of a key secret that can be compared, logged and
+//! pasted into tickets without revealing the secret. `list` shows the fingerprint of every key
+//! whose secret NerdGraph returns, and `fingerprint` computes it for a secret or for the keys
+//! found in a file.
+
+use std::path::Path;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::output::{Format, Renderer};
+use crate::{key_type_from_prefix, scan};
+
+/// Hex characters kept from the digest; 64 bits tell keys apart without being worth brute
+/// forcing back into a secret.
+const LENGTH: usize = 16;
+
+/// The fingerprint of `secret`, ignoring surrounding whitespace.
+pub fn fingerprint(secret: &str) -> String {
+    let digest = Sha256::digest(secret.trim().as_bytes());
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256:{}", &hex[..LENGTH])
+}
+
+#[derive(Serialize)]
+struct Fingerprint {
+    /// `path:line:column` for secrets found in a file
+    source: String,
+    kind: &'static str,
+    fingerprint: String,
+}
+
+const HEADERS: [&str; 3] = ["SOURCE", "KIND", "FINGERPRINT"];
+
+/// Fingerprints for `value`: every key found in the file when `value` names one, the secret
+/// read from stdin for `-`, and otherwise `value` itself.
+fn fingerprints(value: &str) -> anyhow::Result<Vec<Fingerprint>> {
+    let path = Path::new(value);
+    if value != "-" && path.is_file() {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+        let matches = scan::scan_text(path, &text);
+        if matches.is_empty() {
+            anyhow::bail!("No New Relic keys found in {}", path.display());
+        }
+        return Ok(matches
+            .into_iter()
+            .map(|m| Fingerprint {
+                source: format!("{}:{}:{}", m.path.display(), m.line, m.column),
+                kind: key_type_from_prefix(&m.secret),
+                fingerprint: fingerprint(&m.secret),
+            })
+            .collect());
+    }
+    let (source, secret) = if value == "-" {
+        ("stdin", std::io::read_to_string(std::io::stdin())?)
+    } else {
+        ("argument", value.to_string())
+    };
+    if secret.trim().is_empty() {
+        anyhow::bail!("No key secret given");
+    }
+    Ok(vec![Fingerprint {
+        source: source.to_string(),
+        kind: key_type_from_prefix(secret.trim()),
+        fingerprint: fingerprint(&secret),
+    }])
+}
+
+impl Renderer for [Fingerprint] {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|f| vec![f.source.clone(), f.kind.to_string(), f.fingerprint.clone()])
+            .collect()
+    }
+}
+
+pub fn run(value: &str, format: Format) -> anyhow::Result<()> {
+    print!("{}", fingerprints(value)?.render(&format)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        // SHA-256 of "abc" is ba7816bf8f01cfea...
+        assert_eq!(fingerprint("abc"), "sha256:ba7816bf8f01cfea");
+        assert_eq!(fingerprint(" abc\n"), fingerprint("abc"));
+        assert_ne!(fingerprint("NRAK-A"), fingerprint("NRAK-B"));
+    }
+
+    #[test]
+    fn test_fingerprints_of_argument_and_file() {
+        let found = fingerprints("NRII-secret").unwrap();
+        assert_eq!(found[0].source, "argument");
+        assert_eq!(found[0].kind, "INGEST (insert)");
+
+        let path = std::env::temp_dir().join(format!("nr-fingerprint-{}.env", std::process::id()));
+        let secret = "NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0";
+        std::fs::write(&path, format!("# keys\nNEW_RELIC_API_KEY={}\n", secret)).unwrap();
+        let found = fingerprints(&path.to_string_lossy()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].source, format!("{}:2:19", path.display()));
+        assert_eq!(found[0].fingerprint, fingerprint(secret));
+    }
+}
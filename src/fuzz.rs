@@ -0,0 +1,138 @@
+//! Entry points for the `cargo fuzz` targets in `fuzz/`. Whatever NerdGraph answers, parsing
+//! it and rendering the result may fail with an error but must never panic, since the CLI
+//! usually runs unattended in scripts and pipelines.
+
+use std::sync::OnceLock;
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::list::{self, Column, SortKey, View};
+use crate::output::Format;
+use crate::transport::HttpResponse;
+use crate::{identity, NewRelicClient, RetryPolicy};
+
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Unable to start the fuzzing runtime")
+    })
+}
+
+/// A client whose every request is answered with `body` and status 200.
+fn client(body: &[u8]) -> NewRelicClient {
+    let body = body.to_vec();
+    NewRelicClient::builder()
+        .api_key("NRAK-FUZZ")
+        .retry_policy(RetryPolicy::none())
+        .transport(move |_| {
+            Ok(HttpResponse {
+                status: 200,
+                headers: Vec::new(),
+                body: body.clone(),
+            })
+        })
+        .build()
+        .expect("The fuzzing client needs no configuration")
+}
+
+fn formats() -> Vec<Format> {
+    let mut formats = vec![
+        Format::Json,
+        Format::Ndjson,
+        Format::PsObject,
+        Format::Table,
+        Format::Csv,
+    ];
+    #[cfg(feature = "yaml")]
+    formats.push(Format::Yaml);
+    #[cfg(feature = "templates")]
+    formats.extend(
+        crate::output::Template::new("{{id}} {{name}} {{created_at}}").map(Format::Template),
+    );
+    formats
+}
+
+fn views() -> [View; 2] {
+    [
+        View::default(),
+        View {
+            sort: Some(SortKey::Created),
+            reverse: true,
+            columns: Some(vec![Column::Created, Column::Name, Column::Fingerprint]),
+            created_after: chrono::DateTime::from_timestamp(0, 0),
+            ..View::default()
+        },
+    ]
+}
+
+/// Render `keys` in every format and view `list` supports.
+fn render_keys(keys: &[ApiKey]) {
+    for format in formats() {
+        for view in views() {
+            let _ = list::render_keys(keys.to_vec(), format.clone(), &view);
+        }
+    }
+}
+
+/// `data` as the body of every NerdGraph response the key commands parse, with the keys that
+/// parse rendered in every output format.
+pub fn response(data: &[u8]) {
+    let client = client(data);
+    runtime().block_on(async {
+        if let Ok(keys) = inventory::fetch(&client, &[1], &["INGEST", "USER"]).await {
+            render_keys(&keys);
+        }
+        if let Ok(key) = inventory::get(&client, "KEY-1", "INGEST").await {
+            render_keys(&[key]);
+        }
+        let spec = NewKey {
+            key_type: "INGEST".to_string(),
+            account_id: 1,
+            name: "fuzz".to_string(),
+            notes: None,
+            ingest_type: Some("LICENSE".to_string()),
+            user_id: None,
+        };
+        if let Ok(key) = inventory::create(&client, &spec).await {
+            render_keys(&[key]);
+        }
+        if let Ok(key) = inventory::update(&client, "KEY-1", "USER", Some("fuzz"), None).await {
+            render_keys(&[key]);
+        }
+        let _ = inventory::delete_keys(&client, &["KEY-1".to_string()], &[]).await;
+        let _ = identity::fetch_identity(&client).await;
+    });
+}
+
+/// `data` as a JSON array of keys, rendered in every output format; reaches the renderers
+/// with far fewer mutations than [`response`].
+pub fn render(data: &[u8]) {
+    if let Ok(keys) = serde_json::from_slice::<Vec<ApiKey>>(data) {
+        render_keys(&keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unexpected_payloads_do_not_panic() {
+        for data in [
+            &b""[..],
+            b"null",
+            b"{\"data\":null}",
+            b"{\"data\":{\"actor\":{\"apiAccess\":{\"keySearch\":{\"keys\":[{\"id\":1}]}}}}}",
+            b"{\"data\":{\"actor\":{\"apiAccess\":{\"keySearch\":{\"keys\":[{\"id\":\"K\",\"createdAt\":9223372036854775807}],\"nextCursor\":\"c\"}}}}}",
+            b"{\"data\":{\"actor\":{\"apiAccess\":{\"key\":{\"id\":\"K\",\"createdAt\":-9223372036854775808}}}}}",
+            b"{\"errors\":[{\"message\":\"\\u0000\",\"locations\":[{\"line\":-1}]}]}",
+        ] {
+            response(data);
+        }
+        render(b"[{\"id\":\"K\",\"name\":\"\\u001b[31m\",\"createdAt\":253402300800,\"key\":\"NRAK-\"}]");
+    }
+}
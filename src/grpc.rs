@@ -0,0 +1,213 @@
+use std::net::SocketAddr;
+
+use tonic::{Request, Response, Status};
+
+use crate::inventory::{self, NewKey};
+use crate::{rotation, serve, NewRelicClient};
+
+mod proto {
+    tonic::include_proto!("newrelic.apikeys.v1");
+}
+
+use proto::api_keys_server::{ApiKeys, ApiKeysServer};
+use proto::KeyType;
+
+struct Service {
+    client: NewRelicClient,
+    default_account_ids: Vec<i64>,
+}
+
+fn key_type_name(value: i32) -> Result<&'static str, Status> {
+    match KeyType::try_from(value) {
+        Ok(KeyType::Ingest) => Ok("INGEST"),
+        Ok(KeyType::User) => Ok("USER"),
+        _ => Err(Status::invalid_argument("type must be INGEST or USER")),
+    }
+}
+
+fn upstream(e: anyhow::Error) -> Status {
+    Status::unavailable(e.to_string())
+}
+
+impl From<inventory::ApiKey> for proto::ApiKey {
+    fn from(key: inventory::ApiKey) -> Self {
+        let key_type = match key.key_type.as_deref() {
+            Some("INGEST") => KeyType::Ingest,
+            Some("USER") => KeyType::User,
+            _ => KeyType::Unspecified,
+        };
+        proto::ApiKey {
+            id: key.id,
+            name: key.name,
+            notes: key.notes,
+            r#type: key_type as i32,
+            key: key.key.map(|key| key.expose().to_string()),
+            created_at: key.created_at,
+            account_id: key.account_id,
+            ingest_type: key.ingest_type,
+            user_id: key.user_id,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ApiKeys for Service {
+    async fn list_keys(
+        &self,
+        request: Request<proto::ListKeysRequest>,
+    ) -> Result<Response<proto::ListKeysResponse>, Status> {
+        let request = request.into_inner();
+        let account_ids = if request.account_ids.is_empty() {
+            self.default_account_ids.clone()
+        } else {
+            request.account_ids
+        };
+        if account_ids.is_empty() {
+            return Err(Status::invalid_argument("account_ids is required"));
+        }
+        let types = match request.r#type {
+            0 => vec!["INGEST", "USER"],
+            other => vec![key_type_name(other)?],
+        };
+
+        let keys = inventory::fetch(&self.client, &account_ids, &types)
+            .await
+            .map_err(upstream)?;
+        Ok(Response::new(proto::ListKeysResponse {
+            keys: keys
+                .into_iter()
+                .map(|mut key| {
+                    key.key = None;
+                    key.into()
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_key(
+        &self,
+        request: Request<proto::GetKeyRequest>,
+    ) -> Result<Response<proto::ApiKey>, Status> {
+        let request = request.into_inner();
+        let mut key = inventory::get(&self.client, &request.id, key_type_name(request.r#type)?)
+            .await
+            .map_err(upstream)?;
+        key.key = None;
+        Ok(Response::new(key.into()))
+    }
+
+    async fn create_key(
+        &self,
+        request: Request<proto::CreateKeyRequest>,
+    ) -> Result<Response<proto::ApiKey>, Status> {
+        let request = request.into_inner();
+        if request.name.trim().is_empty() {
+            return Err(Status::invalid_argument("name is required"));
+        }
+        let spec = NewKey {
+            key_type: key_type_name(request.r#type)?.to_string(),
+            account_id: request.account_id,
+            name: request.name,
+            notes: request.notes,
+            ingest_type: request.ingest_type,
+            user_id: request.user_id,
+        };
+        let key = inventory::create(&self.client, &spec)
+            .await
+            .map_err(upstream)?;
+        Ok(Response::new(key.into()))
+    }
+
+    async fn rotate_key(
+        &self,
+        request: Request<proto::RotateKeyRequest>,
+    ) -> Result<Response<proto::RotateKeyResponse>, Status> {
+        let request = request.into_inner();
+        let rotation = rotation::rotate(
+            &self.client,
+            &request.id,
+            key_type_name(request.r#type)?,
+            request.keep_old,
+        )
+        .await
+        .map_err(upstream)?;
+        Ok(Response::new(proto::RotateKeyResponse {
+            old_key_id: rotation.old_key_id,
+            new_key: Some(rotation.new_key.into()),
+            old_key_deleted: rotation.old_key_deleted,
+            delete_error: rotation.delete_error,
+        }))
+    }
+
+    async fn delete_key(
+        &self,
+        request: Request<proto::DeleteKeyRequest>,
+    ) -> Result<Response<proto::DeleteKeyResponse>, Status> {
+        let request = request.into_inner();
+        let ids = vec![request.id];
+        let outcome = match key_type_name(request.r#type)? {
+            "USER" => inventory::delete_keys(&self.client, &[], &ids).await,
+            _ => inventory::delete_keys(&self.client, &ids, &[]).await,
+        }
+        .map_err(upstream)?;
+        if outcome.deleted.is_empty() {
+            return Err(Status::failed_precondition(outcome.errors.join(", ")));
+        }
+        Ok(Response::new(proto::DeleteKeyResponse {
+            deleted: outcome.deleted,
+        }))
+    }
+}
+
+/// Serve the key operations over gRPC, authenticated with the same bearer token as `serve`.
+pub async fn run(
+    client: NewRelicClient,
+    token: String,
+    default_account_ids: Vec<i64>,
+    listen: SocketAddr,
+) -> anyhow::Result<()> {
+    if token.len() < 16 {
+        return Err(anyhow::anyhow!(
+            "The serve token must be at least 16 characters long"
+        ));
+    }
+    let expected = format!("Bearer {}", token);
+    let authenticate = move |request: Request<()>| {
+        let authorized = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| serve::constant_time_eq(value.as_bytes(), expected.as_bytes()));
+        if authorized {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("Missing or invalid bearer token"))
+        }
+    };
+
+    let service = Service {
+        client,
+        default_account_ids,
+    };
+    println!("Serving the gRPC key API on {}", listen);
+    tonic::transport::Server::builder()
+        .add_service(ApiKeysServer::with_interceptor(service, authenticate))
+        .serve_with_shutdown(listen, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_type_name() {
+        assert_eq!(key_type_name(KeyType::Ingest as i32).unwrap(), "INGEST");
+        assert_eq!(key_type_name(KeyType::User as i32).unwrap(), "USER");
+        assert!(key_type_name(KeyType::Unspecified as i32).is_err());
+        assert!(key_type_name(42).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! `[guardrails]`: limits every run enforces on the mutations it sends, so a script with a bad
+//! filter cannot wipe out an account. Deletes name keys by ID only, so [`Limits`] looks them up
+//! first to learn their accounts and types, and refuses a mutation whose keys it cannot look up.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard, Semaphore};
+
+use crate::config::Guardrails;
+use crate::middleware::{Middleware, Next};
+use crate::targets;
+use crate::transport::{HttpRequest, TransportFuture};
+
+/// Enforces `[guardrails]`. Clones share their counts, so every client of a run observes the same
+/// limits.
+#[derive(Clone)]
+pub struct Limits(Arc<State>);
+
+struct State {
+    max_deletions: Option<usize>,
+    keep_last_ingest_key: bool,
+    max_concurrent: Option<usize>,
+    /// Keys deleted so far per account
+    deletions: Mutex<HashMap<i64, usize>>,
+    in_flight: Mutex<HashMap<i64, Arc<Semaphore>>>,
+    /// Held per account from the `keep_last_ingest_key` check until the delete is answered, so
+    /// two deletes cannot both see the other's key remaining
+    deleting: Mutex<HashMap<i64, Arc<AsyncMutex<()>>>>,
+}
+
+/// A key a mutation names by ID.
+struct Target {
+    id: String,
+    account_id: i64,
+    ingest_type: Option<String>,
+    deleted: bool,
+}
+
+impl Limits {
+    /// The limits of the config, or `None` when it sets none.
+    pub fn new(guardrails: &Guardrails) -> anyhow::Result<Option<Self>> {
+        if guardrails.max_concurrent_mutations_per_account == Some(0) {
+            anyhow::bail!("guardrails.max_concurrent_mutations_per_account must be at least 1");
+        }
+        if guardrails.max_deletions_per_account.is_none()
+            && !guardrails.keep_last_ingest_key
+            && guardrails.max_concurrent_mutations_per_account.is_none()
+        {
+            return Ok(None);
+        }
+        Ok(Some(Limits(Arc::new(State {
+            max_deletions: guardrails.max_deletions_per_account,
+            keep_last_ingest_key: guardrails.keep_last_ingest_key,
+            max_concurrent: guardrails.max_concurrent_mutations_per_account,
+            deletions: Mutex::default(),
+            in_flight: Mutex::default(),
+            deleting: Mutex::default(),
+        }))))
+    }
+
+    /// The keys of a mutation that matter to the limits, looked up.
+    async fn targets(
+        &self,
+        request: &HttpRequest,
+        next: Next<'_>,
+        variables: &serde_json::Value,
+    ) -> anyhow::Result<Vec<Target>> {
+        let deleted = targets::deleted_key_ids(variables);
+        let checks_deletions =
+            !deleted.is_empty() && (self.0.max_deletions.is_some() || self.0.keep_last_ingest_key);
+        let ids = if self.0.max_concurrent.is_some() {
+            targets::key_ids(variables)
+        } else if checks_deletions {
+            deleted.clone()
+        } else {
+            Vec::new()
+        };
+        let mut found = Vec::new();
+        for (id, key_type) in ids {
+            let key = targets::lookup(request, next, &id, key_type).await?;
+            // Unknown keys are left for NerdGraph to report.
+            let Some(account_id) = key["accountId"].as_i64() else {
+                continue;
+            };
+            found.push(Target {
+                deleted: deleted.iter().any(|(deleted, _)| *deleted == id),
+                id,
+                account_id,
+                ingest_type: (key_type == "INGEST")
+                    .then(|| key["ingestType"].as_str().unwrap_or("LICENSE").to_string()),
+            });
+        }
+        Ok(found)
+    }
+
+    /// Refuse a delete that would break a limit, and count it if it does not.
+    async fn check_deletions(
+        &self,
+        request: &HttpRequest,
+        next: Next<'_>,
+        keys: &[Target],
+    ) -> anyhow::Result<()> {
+        let mut per_account: BTreeMap<i64, Vec<&Target>> = BTreeMap::new();
+        for key in keys.iter().filter(|key| key.deleted) {
+            per_account.entry(key.account_id).or_default().push(key);
+        }
+        if self.0.keep_last_ingest_key {
+            for (&account_id, deleted) in &per_account {
+                let ingest_types: Vec<&str> = deleted
+                    .iter()
+                    .filter_map(|key| key.ingest_type.as_deref())
+                    .collect();
+                if ingest_types.is_empty() {
+                    continue;
+                }
+                let existing = targets::search(request, next, account_id, "INGEST").await?;
+                for ingest_type in ingest_types {
+                    let remaining = existing.iter().any(|key| {
+                        key["ingestType"].as_str().unwrap_or("LICENSE") == ingest_type
+                            && key["id"]
+                                .as_str()
+                                .is_some_and(|id| !deleted.iter().any(|d| d.id == id))
+                    });
+                    if !remaining {
+                        anyhow::bail!(
+                            "Refusing to delete the last {} key of account {} \
+                             (guardrails.keep_last_ingest_key)",
+                            ingest_type,
+                            account_id
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut deletions = self.0.deletions.lock().unwrap();
+        if let Some(max) = self.0.max_deletions {
+            for (account_id, deleted) in &per_account {
+                let done = deletions.get(account_id).copied().unwrap_or(0);
+                if done + deleted.len() > max {
+                    anyhow::bail!(
+                        "Refusing to delete {} key(s) in account {}: \
+                         guardrails.max_deletions_per_account allows {} per run and {} were \
+                         deleted already",
+                        deleted.len(),
+                        account_id,
+                        max,
+                        done
+                    );
+                }
+            }
+        }
+        for (account_id, deleted) in per_account {
+            *deletions.entry(account_id).or_insert(0) += deleted.len();
+        }
+        Ok(())
+    }
+
+    /// Lock every account a delete touches, in a fixed order so two deletes cannot deadlock.
+    async fn lock_deletions(&self, keys: &[Target]) -> Vec<OwnedMutexGuard<()>> {
+        if !self.0.keep_last_ingest_key {
+            return Vec::new();
+        }
+        let mut accounts: Vec<i64> = keys
+            .iter()
+            .filter(|key| key.deleted && key.ingest_type.is_some())
+            .map(|key| key.account_id)
+            .collect();
+        accounts.sort_unstable();
+        accounts.dedup();
+        let mut guards = Vec::new();
+        for account_id in accounts {
+            let lock = self
+                .0
+                .deleting
+                .lock()
+                .unwrap()
+                .entry(account_id)
+                .or_default()
+                .clone();
+            guards.push(lock.lock_owned().await);
+        }
+        guards
+    }
+
+    fn semaphore(&self, account_id: i64, permits: usize) -> Arc<Semaphore> {
+        let mut in_flight = self.0.in_flight.lock().unwrap();
+        in_flight
+            .entry(account_id)
+            .or_insert_with(|| Arc::new(Semaphore::new(permits)))
+            .clone()
+    }
+}
+
+impl Middleware for Limits {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            if !request.is_mutation() {
+                return next.run(request).await;
+            }
+            let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+            let variables = &body["variables"];
+            let keys = self.targets(&request, next, variables).await?;
+            let locks = self.lock_deletions(&keys).await;
+            self.check_deletions(&request, next, &keys).await?;
+
+            let mut permits = Vec::new();
+            if let Some(max) = self.0.max_concurrent {
+                let mut accounts = targets::accounts(variables);
+                accounts.extend(keys.iter().map(|key| key.account_id));
+                // In a fixed order, so two mutations of the same accounts cannot deadlock.
+                accounts.sort_unstable();
+                accounts.dedup();
+                for account_id in accounts {
+                    permits.push(self.semaphore(account_id, max).acquire_owned().await?);
+                }
+            }
+            let response = next.run(request).await;
+            drop(permits);
+            drop(locks);
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::HttpResponse;
+    use crate::{inventory, NewRelicClient};
+
+    /// Account 1 has license keys L1 and L2 and browser key B1.
+    fn client(guardrails: &Guardrails) -> NewRelicClient {
+        NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(Limits::new(guardrails).unwrap().unwrap())
+            .transport(|request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+                let key = |id: &str| {
+                    let ingest_type = if id.starts_with('B') {
+                        "BROWSER"
+                    } else {
+                        "LICENSE"
+                    };
+                    serde_json::json!({ "id": id, "type": "INGEST", "ingestType": ingest_type,
+                        "accountId": 1, "createdAt": 1, "name": null, "notes": null })
+                };
+                let query = body["query"].as_str().unwrap_or_default();
+                let data = if query.contains("keySearch") {
+                    serde_json::json!({ "actor": { "apiAccess": { "keySearch": {
+                        "keys": [key("L1"), key("L2"), key("B1")], "nextCursor": null
+                    }}}})
+                } else if query.trim_start().starts_with("mutation") {
+                    let deleted: Vec<serde_json::Value> = body["variables"]["keys"]["ingestKeyIds"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(|id| serde_json::json!({ "id": id }))
+                        .collect();
+                    serde_json::json!({ "apiAccessDeleteKeys": {
+                        "deletedKeys": deleted, "errors": []
+                    }})
+                } else {
+                    let id = body["variables"]["id"].as_str().unwrap_or_default();
+                    serde_json::json!({ "actor": { "apiAccess": { "key": key(id) }}})
+                };
+                Ok(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: serde_json::to_vec(&serde_json::json!({ "data": data }))?,
+                })
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_max_deletions_per_account() {
+        let client = client(&Guardrails {
+            max_deletions_per_account: Some(2),
+            ..Guardrails::default()
+        });
+        assert!(inventory::delete_keys(&client, &ids(&["L1"]), &[])
+            .await
+            .is_ok());
+        let error = inventory::delete_keys(&client, &ids(&["L2", "B1"]), &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("1 were deleted already"));
+        assert!(inventory::delete_keys(&client, &ids(&["L2"]), &[])
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_keep_last_ingest_key() {
+        let client = client(&Guardrails {
+            keep_last_ingest_key: true,
+            ..Guardrails::default()
+        });
+        let error = inventory::delete_keys(&client, &ids(&["B1"]), &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("last BROWSER key of account 1"));
+        assert!(inventory::delete_keys(&client, &ids(&["L1", "L2"]), &[])
+            .await
+            .is_err());
+        assert!(inventory::delete_keys(&client, &ids(&["L2"]), &[])
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_failed_lookup_refuses_the_delete() {
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(
+                Limits::new(&Guardrails {
+                    keep_last_ingest_key: true,
+                    ..Guardrails::default()
+                })
+                .unwrap()
+                .unwrap(),
+            )
+            .transport(|request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                assert!(!request.is_mutation(), "the delete was sent");
+                Ok(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: br#"{"data": null, "errors": [{"message": "Service unavailable"}]}"#
+                        .to_vec(),
+                })
+            })
+            .build()
+            .unwrap();
+        let error = inventory::delete_keys(&client, &ids(&["L1"]), &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("Lookup failed: Service unavailable"));
+    }
+}
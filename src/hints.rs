@@ -0,0 +1,171 @@
+//! Actionable suggestions printed under an error: how to fix common NerdGraph failures, and
+//! "did you mean" for misspelled values. Misspelled flags and subcommands are already caught by
+//! clap, which suggests the closest match itself.
+
+const API_KEYS_DOCS: &str = "https://docs.newrelic.com/docs/apis/intro-apis/new-relic-api-keys/";
+const NERDGRAPH_KEYS_DOCS: &str =
+    "https://docs.newrelic.com/docs/apis/nerdgraph/examples/use-nerdgraph-manage-license-keys-user-keys/";
+
+/// NerdGraph enums the CLI sends, their values and the flag that sets them.
+const ENUMS: [(&str, &[&str], &str); 2] = [
+    ("ApiAccessKeyType", &["INGEST", "USER"], "--key-type"),
+    (
+        "ApiAccessIngestKeyType",
+        &["BROWSER", "LICENSE"],
+        "ingest_type",
+    ),
+];
+
+/// Edit distance between `a` and `b`, ignoring case.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `value`, if it is close enough to be a plausible typo.
+pub fn did_you_mean<'a>(value: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let threshold = value.chars().count().div_ceil(3);
+    candidates
+        .iter()
+        .map(|candidate| (levenshtein(value, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// A suggestion for `error`, or `None` when there is nothing useful to add.
+pub fn hint(error: &anyhow::Error) -> Option<String> {
+    let message = format!("{:#}", error);
+    let lower = message.to_lowercase();
+
+    if let Some(hint) = misspelled_value(&message) {
+        return Some(hint);
+    }
+    // The failure of `rotate --verify` is about the new key, not the one the CLI signs in with.
+    if lower.contains("the replacement") && lower.contains("does not work") {
+        return Some(
+            "New Relic did not accept the new key yet; the old key was kept, so the rotation can \
+             be retried later"
+                .to_string(),
+        );
+    }
+    for (enum_type, values, flag) in ENUMS {
+        if message.contains(enum_type) {
+            let found = quoted(&message, '"');
+            let suggestion = found
+                .and_then(|found| did_you_mean(found, values))
+                .map(|value| format!("did you mean {}? ", value))
+                .unwrap_or_default();
+            return Some(format!(
+                "{}{} accepts {}; see {}",
+                suggestion,
+                flag,
+                values.join(" or "),
+                NERDGRAPH_KEYS_DOCS
+            ));
+        }
+    }
+    if lower.contains("invalid api key") || lower.contains("http 401") {
+        return Some(format!(
+            "the API key was rejected: check it with `auth verify`, and use a User key (NRAK-...) \
+             rather than a license or browser key; see {}",
+            API_KEYS_DOCS
+        ));
+    }
+    if lower.contains("not authorized")
+        || lower.contains("access denied")
+        || lower.contains("forbidden")
+        || lower.contains("http 403")
+    {
+        return Some(format!(
+            "the key's user lacks permission for this: `whoami` shows its roles; managing other \
+             users' keys needs an admin role; see {}",
+            API_KEYS_DOCS
+        ));
+    }
+    if lower.contains("account")
+        && (lower.contains("not found")
+            || lower.contains("does not exist")
+            || lower.contains("invalid account"))
+    {
+        return Some(
+            "`auth verify` lists the accounts this key can access; pass one with --account-id or \
+             set account_id in the profile"
+                .to_string(),
+        );
+    }
+    // Requests to other services, such as webhooks, name them in their message.
+    if (lower.contains("error sending request") || lower.contains("error trying to connect"))
+        && !lower.contains("webhook")
+    {
+        return Some(
+            "NerdGraph could not be reached: `doctor` checks proxies, DNS and TLS, and \
+             `--offline` answers read commands from the cache"
+                .to_string(),
+        );
+    }
+    None
+}
+
+/// "Unsupported output format 'tabel' (expected json, table or csv)" style messages.
+fn misspelled_value(message: &str) -> Option<String> {
+    let value = quoted(message, '\'')?;
+    let (_, expected) = message.split_once("(expected ")?;
+    let expected = expected.split(')').next()?;
+    let candidates: Vec<&str> = expected
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|word| !word.is_empty() && *word != "or" && *word != "e.g.")
+        .collect();
+    did_you_mean(value, &candidates).map(|candidate| format!("did you mean '{}'?", candidate))
+}
+
+fn quoted(message: &str, quote: char) -> Option<&str> {
+    let start = message.find(quote)? + 1;
+    let end = start + message[start..].find(quote)?;
+    Some(&message[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("table", "table"), 0);
+        assert_eq!(levenshtein("tabel", "table"), 2);
+        assert_eq!(levenshtein("INGEST", "ingst"), 1);
+        assert_eq!(levenshtein("", "csv"), 3);
+    }
+
+    #[test]
+    fn test_hints() {
+        let hint = |message: &str| hint(&anyhow::anyhow!("{}", message));
+        assert_eq!(
+            hint("Unsupported output format 'tabel' (expected json, table, csv or yaml)"),
+            Some("did you mean 'table'?".to_string())
+        );
+        assert!(hint(
+            "GraphQL errors: Expected type 'ApiAccessKeyType!', found \"INGST\"; enum value not valid"
+        )
+        .unwrap()
+        .starts_with("did you mean INGEST? --key-type accepts INGEST or USER"));
+        assert!(hint("GraphQL errors: Invalid API key")
+            .unwrap()
+            .contains("auth verify"));
+        assert_eq!(
+            hint("Unsupported output format 'xml' (expected json or csv)"),
+            None
+        );
+    }
+}
@@ -0,0 +1,670 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use minisign::{PublicKey, SecretKey, SecretKeyBox, SignatureBox};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::warnings::{self, Code};
+
+const REDACTED: &str = "<redacted>";
+
+/// Options whose values are secrets, like keys, tokens and webhook URLs with a token in the
+/// path.
+const SECRET_OPTIONS: [&str; 5] = [
+    "--api-key",
+    "--token",
+    "--ship-header",
+    "--anomaly-webhook",
+    "--webhook",
+];
+
+/// The [`SECRET_OPTIONS`] that can also be supplied via the environment, so a replay may drop
+/// them.
+const ENVIRONMENT_OPTIONS: [&str; 2] = ["--api-key", "--token"];
+
+/// The short form of `--api-key`, which subcommands use for `--account-id`, so it is only a
+/// secret before the subcommand.
+const SECRET_SHORT: &str = "-a";
+
+/// `prev_hash` of the first chained entry.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    timestamp: DateTime<Utc>,
+    args: Vec<String>,
+    /// The subcommand path, e.g. `scheduler run`; absent on entries written before it was
+    /// recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// [`Entry::link`] of the previous entry; absent on entries written before the log was
+    /// chained
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prev_hash: Option<String>,
+    /// SHA-256 over `prev_hash` and the fields above
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    /// Minisign signature of `hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+impl Entry {
+    fn compute_hash(&self) -> anyhow::Result<String> {
+        let (timestamp, args, error, prev_hash) =
+            (&self.timestamp, &self.args, &self.error, &self.prev_hash);
+        // Entries without a command keep the hash they were written with.
+        let body = match &self.command {
+            Some(command) => {
+                serde_json::to_string(&(timestamp, args, command, self.success, error, prev_hash))?
+            }
+            None => serde_json::to_string(&(timestamp, args, self.success, error, prev_hash))?,
+        };
+        Ok(sha256_hex(&body))
+    }
+
+    /// What the next entry's `prev_hash` commits to: the hash, together with the signature of a
+    /// signed entry, so that removing a signature breaks the chain like any other edit.
+    fn link(&self) -> Option<String> {
+        let hash = self.hash.as_ref()?;
+        Some(match &self.signature {
+            Some(signature) => sha256_hex(&format!("{}\n{}", hash, signature)),
+            None => hash.clone(),
+        })
+    }
+}
+
+fn sha256_hex(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Load an unencrypted minisign secret key (`minisign -G -W`) for signing history entries.
+pub fn load_signing_key(path: &Path) -> anyhow::Result<SecretKey> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Unable to read signing key {}: {}", path.display(), e))?;
+    SecretKeyBox::from_string(&contents)?
+        .into_unencrypted_secret_key()
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Unable to use signing key {} (it must be unencrypted): {}",
+                path.display(),
+                e
+            )
+        })
+}
+
+/// Append an invocation and its outcome to the history file, with secrets redacted. Each entry
+/// carries the hash and signature of the one before it, so edits or deletions break the chain.
+/// `command` is the subcommand path clap parsed, and `global_values` are the global options that
+/// take a value, so their values are not mistaken for the subcommand.
+pub fn record(
+    path: &Path,
+    args: &[OsString],
+    command: &str,
+    global_values: &[String],
+    result: &anyhow::Result<()>,
+    signing_key: Option<&SecretKey>,
+) -> anyhow::Result<()> {
+    let prev_hash = load(path)?
+        .last()
+        .and_then(Entry::link)
+        .unwrap_or_else(|| GENESIS_HASH.to_string());
+    let mut entry = Entry {
+        timestamp: Utc::now(),
+        args: redact(args, global_values),
+        command: Some(command.to_string()),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        prev_hash: Some(prev_hash),
+        hash: None,
+        signature: None,
+    };
+    let hash = entry.compute_hash()?;
+    if let Some(key) = signing_key {
+        let signature = minisign::sign(None, key, Cursor::new(hash.as_bytes()), None, None)?;
+        entry.signature = Some(signature.into_string());
+    }
+    entry.hash = Some(hash);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // Also for logs created before they were private.
+    config::restrict_to_owner(path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    Ok(())
+}
+
+/// Outcome of checking the history chain.
+#[derive(Debug, PartialEq)]
+pub struct Verification {
+    pub legacy: usize,
+    pub chained: usize,
+    pub signed: usize,
+}
+
+fn check_chain(entries: &[Entry], public_key: Option<&PublicKey>) -> anyhow::Result<Verification> {
+    let mut result = Verification {
+        legacy: 0,
+        chained: 0,
+        signed: 0,
+    };
+    let mut expected_prev: Option<String> = None;
+    for (index, entry) in entries.iter().enumerate() {
+        let number = index + 1;
+        let (Some(prev_hash), Some(hash)) = (&entry.prev_hash, &entry.hash) else {
+            if expected_prev.is_some() {
+                return Err(anyhow::anyhow!(
+                    "Entry {} is missing its hash: the log was edited after entry {}",
+                    number,
+                    index
+                ));
+            }
+            result.legacy += 1;
+            continue;
+        };
+
+        let expected = expected_prev.as_deref().unwrap_or(GENESIS_HASH);
+        if prev_hash != expected {
+            return Err(anyhow::anyhow!(
+                "Entry {} does not follow entry {}: entries were removed, reordered or edited",
+                number,
+                index
+            ));
+        }
+        if &entry.compute_hash()? != hash {
+            return Err(anyhow::anyhow!(
+                "Entry {} was modified after it was written",
+                number
+            ));
+        }
+
+        match (&entry.signature, public_key) {
+            (Some(signature), Some(public_key)) => {
+                let signature = SignatureBox::from_string(signature)?;
+                minisign::verify(
+                    public_key,
+                    &signature,
+                    Cursor::new(hash.as_bytes()),
+                    true,
+                    false,
+                    false,
+                )
+                .map_err(|e| anyhow::anyhow!("Entry {} has an invalid signature: {}", number, e))?;
+                result.signed += 1;
+            }
+            (None, Some(_)) if result.signed > 0 => {
+                return Err(anyhow::anyhow!(
+                    "Entry {} is unsigned although earlier entries are signed",
+                    number
+                ));
+            }
+            _ => {}
+        }
+        result.chained += 1;
+        expected_prev = entry.link();
+    }
+    Ok(result)
+}
+
+/// Check the hash chain and, with a public key, the signatures of the history file.
+pub fn verify(path: &Path, public_key: Option<&Path>) -> anyhow::Result<()> {
+    let public_key = public_key
+        .map(|p| {
+            PublicKey::from_file(p)
+                .map_err(|e| anyhow::anyhow!("Unable to read public key {}: {}", p.display(), e))
+        })
+        .transpose()?;
+    let entries = load(path)?;
+    let result = check_chain(&entries, public_key.as_ref())?;
+
+    println!(
+        "Audit log {}: {} chained entries verified",
+        path.display(),
+        result.chained
+    );
+    if public_key.is_some() {
+        println!("  {} entries carry a valid signature", result.signed);
+    }
+    if result.legacy > 0 {
+        println!(
+            "  {} older entries predate hash chaining and cannot be verified",
+            result.legacy
+        );
+    }
+    Ok(())
+}
+
+pub fn list(path: &Path, limit: usize) -> anyhow::Result<()> {
+    let entries = load(path)?;
+    let skip = entries.len().saturating_sub(limit);
+    for (index, entry) in entries.iter().enumerate().skip(skip) {
+        let status = if entry.success { "ok" } else { "failed" };
+        println!(
+            "{:>4}  {}  {:<6}  {}",
+            index + 1,
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            status,
+            shell_words::join(&entry.args)
+        );
+        if let Some(error) = &entry.error {
+            println!("      error: {}", error);
+        }
+    }
+    Ok(())
+}
+
+/// Re-run entry `number` (1-based) by invoking this binary again with the recorded arguments.
+pub fn rerun(path: &Path, number: usize) -> anyhow::Result<()> {
+    let entries = load(path)?;
+    let entry = number
+        .checked_sub(1)
+        .and_then(|index| entries.get(index))
+        .ok_or_else(|| anyhow::anyhow!("No history entry {}", number))?;
+    let args = replayable_args(&entry.args)?;
+
+    eprintln!("Re-running: {}", shell_words::join(&args));
+    let status = std::process::Command::new(std::env::current_exe()?)
+        .args(&args)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "Re-run of entry {} failed ({})",
+            number,
+            status
+        ));
+    }
+    Ok(())
+}
+
+/// Number of recorded invocations per (subcommand path, success).
+pub fn operation_counts(path: &Path) -> anyhow::Result<BTreeMap<(String, bool), u64>> {
+    let mut counts = BTreeMap::new();
+    for entry in load(path)? {
+        let command = match entry.command {
+            Some(command) => command,
+            None => command_name(&entry.args).unwrap_or("unknown").to_string(),
+        };
+        *counts.entry((command, entry.success)).or_default() += 1;
+    }
+    Ok(counts)
+}
+
+/// The subcommand of an invocation recorded without its command path, skipping the global
+/// options that took a value back then.
+fn command_name(args: &[String]) -> Option<&str> {
+    const VALUE_OPTIONS: [&str; 8] = [
+        "-a",
+        "--api-key",
+        "-e",
+        "--endpoint",
+        "-f",
+        "--format",
+        "-p",
+        "--profile",
+    ];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if VALUE_OPTIONS.contains(&arg.as_str()) {
+            iter.next();
+        } else if !arg.starts_with('-') {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+fn load(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+fn redact(args: &[OsString], global_values: &[String]) -> Vec<String> {
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut hide_next = false;
+    let mut value_next = false;
+    let mut before_command = true;
+    for arg in args {
+        let arg = arg.to_string_lossy();
+        let inline = arg
+            .split_once('=')
+            .filter(|(option, _)| SECRET_OPTIONS.contains(option));
+        let secret_short = before_command && arg.starts_with(SECRET_SHORT);
+        if hide_next {
+            redacted.push(REDACTED.to_string());
+            hide_next = false;
+        } else if value_next {
+            redacted.push(arg.to_string());
+            value_next = false;
+        } else if SECRET_OPTIONS.contains(&arg.as_ref()) || (secret_short && arg == SECRET_SHORT) {
+            redacted.push(arg.to_string());
+            hide_next = true;
+        } else if let Some((option, _)) = inline {
+            redacted.push(format!("{}={}", option, REDACTED));
+        } else if secret_short {
+            // `-aSECRET` or `-a=SECRET`
+            redacted.push(format!("{}={}", SECRET_SHORT, REDACTED));
+        } else if before_command && global_values.contains(&arg.to_string()) {
+            redacted.push(arg.to_string());
+            value_next = true;
+        } else if looks_like_secret(&arg) {
+            warnings::warn(
+                Code::Redacted,
+                "an argument that looks like a key secret was not recorded in the history; \
+                 pass secrets via the environment or a profile instead",
+            );
+            redacted.push(REDACTED.to_string());
+        } else {
+            before_command &= arg.starts_with('-');
+            redacted.push(arg.to_string());
+        }
+    }
+    redacted
+}
+
+/// New Relic key formats: NRAK- (user), NRII- (insert), NRJS- (browser), ...NRAL (license).
+pub fn looks_like_secret(arg: &str) -> bool {
+    ["NRAK-", "NRII-", "NRJS-", "NRIQ-"]
+        .iter()
+        .any(|prefix| arg.contains(prefix))
+        || (arg.len() >= 40 && arg.ends_with("NRAL"))
+}
+
+/// Recorded arguments minus redacted secret options (their values then come from the
+/// environment or profile); any other redacted value cannot be replayed.
+fn replayable_args(args: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut replay = Vec::with_capacity(args.len());
+    let mut iter = args.iter().peekable();
+    let secret_options = || ENVIRONMENT_OPTIONS.iter().chain([&SECRET_SHORT]);
+    while let Some(arg) = iter.next() {
+        if secret_options().any(|option| arg == option)
+            && iter.peek().is_some_and(|next| *next == REDACTED)
+        {
+            iter.next();
+        } else if secret_options().any(|option| arg == &format!("{}={}", option, REDACTED)) {
+            continue;
+        } else if arg.contains(REDACTED) {
+            return Err(anyhow::anyhow!(
+                "This entry contains a redacted secret and cannot be replayed; run it manually"
+            ));
+        } else {
+            replay.push(arg.clone());
+        }
+    }
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_redact_secrets() {
+        let redacted = redact(
+            &os_args(&[
+                "--api-key",
+                "NRAK-ABC",
+                "create",
+                "--name",
+                "NRII-LEAKED",
+                "--account-id",
+                "123",
+            ]),
+            &[],
+        );
+        assert_eq!(
+            redacted,
+            vec![
+                "--api-key",
+                "<redacted>",
+                "create",
+                "--name",
+                "<redacted>",
+                "--account-id",
+                "123"
+            ]
+        );
+        assert_eq!(
+            redact(&os_args(&["--api-key=NRAK-ABC"]), &[]),
+            vec!["--api-key=<redacted>"]
+        );
+        assert_eq!(
+            redact(
+                &os_args(&["serve", "--token", "hunter2hunter2hunter2"]),
+                &[]
+            ),
+            vec!["serve", "--token", "<redacted>"]
+        );
+        assert_eq!(
+            redact(
+                &os_args(&["audit-events", "--ship-header", "Authorization: Bearer abc"]),
+                &[]
+            ),
+            vec!["audit-events", "--ship-header", "<redacted>"]
+        );
+        assert_eq!(
+            redact(
+                &os_args(&[
+                    "daemon",
+                    "--anomaly-webhook",
+                    "https://hooks.slack.com/services/T000/B000/XXXXXXXXXXXXXXXX",
+                ]),
+                &[]
+            ),
+            vec!["daemon", "--anomaly-webhook", "<redacted>"]
+        );
+        assert_eq!(
+            redact(
+                &os_args(&[
+                    "daemon",
+                    "--anomaly-webhook=https://hooks.slack.com/services/T/B/X"
+                ]),
+                &[]
+            ),
+            vec!["daemon", "--anomaly-webhook=<redacted>"]
+        );
+        assert_eq!(
+            redact(
+                &os_args(&[
+                    "decommission",
+                    "env",
+                    "staging",
+                    "--webhook",
+                    "https://hooks.slack.com/services/T000/B000/XXXXXXXXXXXXXXXX",
+                ]),
+                &[]
+            ),
+            vec!["decommission", "env", "staging", "--webhook", "<redacted>"]
+        );
+
+        // `-a` is the API key before the subcommand and the account ID after it.
+        let globals = ["-p".to_string(), "--profile".to_string()];
+        assert_eq!(
+            redact(
+                &os_args(&["-p", "prod", "-a", "secret", "query", "-a", "1"]),
+                &globals
+            ),
+            vec!["-p", "prod", "-a", "<redacted>", "query", "-a", "1"]
+        );
+        assert_eq!(
+            redact(&os_args(&["-asecret", "whoami"]), &globals),
+            vec!["-a=<redacted>", "whoami"]
+        );
+    }
+
+    #[test]
+    fn test_replayable_args() {
+        let args: Vec<String> = ["--api-key", REDACTED, "-a", REDACTED, "whoami", "-a", "1"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(replayable_args(&args).unwrap(), vec!["whoami", "-a", "1"]);
+
+        let args: Vec<String> = ["create", "--name", REDACTED]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(replayable_args(&args).is_err());
+
+        // A webhook cannot come from the environment, so it is not silently dropped.
+        let args: Vec<String> = ["daemon", "--anomaly-webhook", REDACTED]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(replayable_args(&args).is_err());
+    }
+
+    #[test]
+    fn test_record_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+
+        record(&path, &os_args(&["whoami"]), "whoami", &[], &Ok(()), None).unwrap();
+        record(
+            &path,
+            &os_args(&["delete", "--key-id", "x"]),
+            "delete",
+            &[],
+            &Err(anyhow::anyhow!("boom")),
+            None,
+        )
+        .unwrap();
+
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(entries[0].success);
+        assert_eq!(entries[1].error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_command_name_skips_global_options() {
+        let args: Vec<String> = ["-v", "--profile", "prod", "cleanup", "stale"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(command_name(&args), Some("cleanup"));
+        assert_eq!(command_name(&["--help".to_string()]), None);
+    }
+
+    #[test]
+    fn test_operation_counts_use_the_recorded_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        record(
+            &path,
+            &os_args(&["--deadline", "5m", "scheduler", "run"]),
+            "scheduler run",
+            &["--deadline".to_string()],
+            &Ok(()),
+            None,
+        )
+        .unwrap();
+        // An entry from before the command was recorded.
+        let legacy =
+            r#"{"timestamp":"2024-01-01T00:00:00Z","args":["-p","prod","whoami"],"success":false}"#;
+        let mut contents = fs::read_to_string(&path).unwrap();
+        contents.insert_str(0, &format!("{}\n", legacy));
+        fs::write(&path, contents).unwrap();
+
+        let counts = operation_counts(&path).unwrap();
+        assert_eq!(counts.get(&("scheduler run".to_string(), true)), Some(&1));
+        assert_eq!(counts.get(&("whoami".to_string(), false)), Some(&1));
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        for command in ["whoami", "query", "doctor"] {
+            record(&path, &os_args(&[command]), command, &[], &Ok(()), None).unwrap();
+        }
+        assert_eq!(check_chain(&load(&path).unwrap(), None).unwrap().chained, 3);
+
+        let mut entries = load(&path).unwrap();
+        entries[1].args = vec!["delete".to_string()];
+        assert!(check_chain(&entries, None).is_err());
+
+        let mut entries = load(&path).unwrap();
+        entries.remove(1);
+        assert!(check_chain(&entries, None).is_err());
+    }
+
+    #[test]
+    fn test_signed_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let keys = minisign::KeyPair::generate_unencrypted_keypair().unwrap();
+
+        record(
+            &path,
+            &os_args(&["whoami"]),
+            "whoami",
+            &[],
+            &Ok(()),
+            Some(&keys.sk),
+        )
+        .unwrap();
+        record(
+            &path,
+            &os_args(&["query"]),
+            "query",
+            &[],
+            &Ok(()),
+            Some(&keys.sk),
+        )
+        .unwrap();
+        let result = check_chain(&load(&path).unwrap(), Some(&keys.pk)).unwrap();
+        assert_eq!(result.signed, 2);
+
+        let other = minisign::KeyPair::generate_unencrypted_keypair().unwrap();
+        assert!(check_chain(&load(&path).unwrap(), Some(&other.pk)).is_err());
+
+        // Stripping signatures breaks the chain, with or without the public key.
+        let mut entries = load(&path).unwrap();
+        entries[0].signature = None;
+        assert!(check_chain(&entries, None).is_err());
+        for entry in &mut entries {
+            entry.signature = None;
+        }
+        assert!(check_chain(&entries, Some(&keys.pk)).is_err());
+
+        record(&path, &os_args(&["doctor"]), "doctor", &[], &Ok(()), None).unwrap();
+        assert!(check_chain(&load(&path).unwrap(), Some(&keys.pk)).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! `hooks install`: a git pre-commit hook that runs `scan --staged` and blocks commits that
+//! contain live key material.
+
+use std::path::{Path, PathBuf};
+
+/// Identifies hooks this command wrote, so it never overwrites or removes anyone else's.
+const MARKER: &str = "# Installed by newrelic-apikeys-cli hooks install";
+
+/// `.git/hooks/pre-commit` of the repository at `repo`, honouring `core.hooksPath` and
+/// worktrees.
+fn hook_path(repo: &Path) -> anyhow::Result<PathBuf> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["rev-parse", "--git-path", "hooks/pre-commit"])
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run git: {}", e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} is not a git repository: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    Ok(if path.is_absolute() {
+        path
+    } else {
+        repo.join(path)
+    })
+}
+
+/// The hook script: run `program scan --staged` with the extra `args`.
+fn script(program: &str, args: &[String]) -> String {
+    let mut command = vec![
+        shell_words::quote(program).into_owned(),
+        "scan".to_string(),
+        "--staged".to_string(),
+    ];
+    command.extend(args.iter().map(|arg| shell_words::quote(arg).into_owned()));
+    format!(
+        "#!/bin/sh\n{}: blocks commits that contain live New\n\
+         # Relic keys. Skip it once with `git commit --no-verify`.\n\
+         exec {}\n",
+        MARKER,
+        command.join(" ")
+    )
+}
+
+/// Write the pre-commit hook of `repo`. Profile and allowlist options in `args` are passed on to
+/// `scan`. An existing hook is only replaced with `force`.
+pub fn install(repo: &Path, args: &[String], force: bool) -> anyhow::Result<()> {
+    let path = hook_path(repo)?;
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        if !existing.contains(MARKER) && !force {
+            anyhow::bail!(
+                "{} already exists; pass --force to replace it",
+                path.display()
+            );
+        }
+    }
+    let program = std::env::current_exe()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, script(&program.to_string_lossy(), args))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    println!("Installed {}", path.display());
+    Ok(())
+}
+
+/// Remove the pre-commit hook of `repo` if this command installed it.
+pub fn uninstall(repo: &Path) -> anyhow::Result<()> {
+    let path = hook_path(repo)?;
+    match std::fs::read_to_string(&path) {
+        Ok(existing) if existing.contains(MARKER) => {
+            std::fs::remove_file(&path)?;
+            println!("Removed {}", path.display());
+        }
+        Ok(_) => anyhow::bail!(
+            "{} was not installed by newrelic-apikeys-cli; leaving it alone",
+            path.display()
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("No pre-commit hook installed");
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_quotes_arguments() {
+        let script = script(
+            "/opt/nr cli/newrelic-apikeys-cli",
+            &[
+                "--allow".to_string(),
+                "tests/fixtures/**".to_string(),
+                "--profile".to_string(),
+                "prod".to_string(),
+            ],
+        );
+        assert!(script.starts_with("#!/bin/sh\n# Installed by newrelic-apikeys-cli"));
+        assert!(script.ends_with(
+            "exec '/opt/nr cli/newrelic-apikeys-cli' scan --staged --allow 'tests/fixtures/**' \
+             --profile prod\n"
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+//! Who a key belongs to: the user, organization and accounts behind it.
+
+use serde::Deserialize;
+
+use crate::NewRelicClient;
+
+#[derive(Deserialize)]
+pub struct Identity {
+    pub actor: IdentityActor,
+}
+
+#[derive(Deserialize)]
+pub struct IdentityActor {
+    pub user: Option<IdentityUser>,
+    pub organization: Option<IdentityOrganization>,
+    #[serde(default)]
+    pub accounts: Vec<IdentityAccount>,
+}
+
+#[derive(Deserialize)]
+pub struct IdentityUser {
+    pub id: i64,
+    pub email: String,
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IdentityOrganization {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IdentityAccount {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Guess the kind of New Relic key from its well-known prefix.
+pub fn key_type_from_prefix(api_key: &str) -> &'static str {
+    if api_key.starts_with("NRAK-") {
+        "USER"
+    } else if api_key.starts_with("NRII-") {
+        "INGEST (insert)"
+    } else if api_key.starts_with("NRJS-") {
+        "INGEST (browser)"
+    } else if api_key.ends_with("NRAL") {
+        "INGEST (license)"
+    } else {
+        "unknown"
+    }
+}
+
+pub(crate) const IDENTITY_QUERY: &str = r#"
+    query {
+        actor {
+            user {
+                id
+                email
+                name
+            }
+            organization {
+                id
+                name
+            }
+            accounts {
+                id
+                name
+            }
+        }
+    }"#;
+
+pub async fn fetch_identity(client: &NewRelicClient) -> anyhow::Result<Identity> {
+    let result = client
+        .execute_query(IDENTITY_QUERY, None)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "API key verification failed (key type: {}): {}",
+                key_type_from_prefix(client.api_key()),
+                e
+            )
+        })?;
+    client.check_schema(
+        "actor",
+        &result,
+        &[
+            "actor.user.id",
+            "actor.user.email",
+            "actor.organization.id",
+            "actor.accounts[].id",
+            "actor.accounts[].name",
+        ],
+    )?;
+    Ok(serde_json::from_value(result)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_type_from_prefix() {
+        assert_eq!(key_type_from_prefix("NRAK-ABCDEF"), "USER");
+        assert_eq!(key_type_from_prefix("NRII-ABCDEF"), "INGEST (insert)");
+        assert_eq!(key_type_from_prefix("NRJS-ABCDEF"), "INGEST (browser)");
+        assert_eq!(
+            key_type_from_prefix("0123456789abcdefNRAL"),
+            "INGEST (license)"
+        );
+        assert_eq!(key_type_from_prefix("something-else"), "unknown");
+    }
+
+    #[test]
+    fn test_identity_deserialization() {
+        let data = serde_json::json!({
+            "actor": {
+                "user": {"id": 42, "email": "jane@example.com", "name": "Jane"},
+                "organization": {"id": "org-1", "name": "Example Org"},
+                "accounts": [{"id": 123456, "name": "Production"}]
+            }
+        });
+
+        let identity: Identity = serde_json::from_value(data).unwrap();
+        assert_eq!(identity.actor.user.unwrap().email, "jane@example.com");
+        assert_eq!(identity.actor.organization.unwrap().id, "org-1");
+        assert_eq!(identity.actor.accounts.len(), 1);
+        assert_eq!(identity.actor.accounts[0].id, 123456);
+    }
+}
@@ -0,0 +1,235 @@
+use std::io::{self, BufRead, Write};
+
+use crate::config::{ConfigFile, Region};
+use crate::paths::Paths;
+use crate::{credentials, fetch_identity, IdentityAccount, NewRelicClient, SecretString};
+
+/// Line-based prompts over any reader/writer, so the wizard can be driven from tests.
+struct Prompter<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    fn ask(&mut self, question: &str, default: Option<&str>) -> io::Result<String> {
+        loop {
+            match default {
+                Some(default) if !default.is_empty() => {
+                    write!(self.output, "{} [{}]: ", question, default)?
+                }
+                _ => write!(self.output, "{}: ", question)?,
+            }
+            self.output.flush()?;
+
+            let mut line = String::new();
+            let read = self.input.read_line(&mut line)?;
+            let answer = line.trim();
+            if !answer.is_empty() {
+                return Ok(answer.to_string());
+            }
+            if let Some(default) = default {
+                return Ok(default.to_string());
+            }
+            if read == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "input closed before setup finished",
+                ));
+            }
+        }
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> io::Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            let answer = self.ask(&format!("{} ({})", question, hint), Some(""))?;
+            match answer.to_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.output, "Please answer y or n")?,
+            }
+        }
+    }
+
+    fn region(&mut self) -> io::Result<Region> {
+        loop {
+            match self
+                .ask("Region (us/eu)", Some("us"))?
+                .to_lowercase()
+                .as_str()
+            {
+                "us" => return Ok(Region::Us),
+                "eu" => return Ok(Region::Eu),
+                other => writeln!(self.output, "Unknown region '{}'", other)?,
+            }
+        }
+    }
+
+    fn account(&mut self, accounts: &[IdentityAccount]) -> io::Result<Option<i64>> {
+        match accounts {
+            [] => Ok(None),
+            [only] => Ok(Some(only.id)),
+            _ => {
+                for (index, account) in accounts.iter().enumerate() {
+                    writeln!(
+                        self.output,
+                        "  {}) {} - {}",
+                        index + 1,
+                        account.id,
+                        account.name
+                    )?;
+                }
+                loop {
+                    let answer = self.ask("Default account", Some("1"))?;
+                    match answer.parse::<usize>() {
+                        Ok(n) if (1..=accounts.len()).contains(&n) => {
+                            return Ok(Some(accounts[n - 1].id))
+                        }
+                        _ => writeln!(
+                            self.output,
+                            "Enter a number between 1 and {}",
+                            accounts.len()
+                        )?,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Interactive first-run setup: pick a region, validate and store the API key, discover
+/// accounts and write a starter profile.
+pub async fn run(paths: &Paths) -> anyhow::Result<()> {
+    let stdin = io::stdin();
+    let mut prompter = Prompter {
+        input: stdin.lock(),
+        output: io::stdout(),
+    };
+    let config_path = paths.config_file();
+    let mut file = ConfigFile::load(&config_path)?;
+
+    println!("Setting up newrelic-apikeys-cli. Press Enter to accept [defaults].");
+    let profile = prompter.ask("Profile name", Some("default"))?;
+    let profile_key = format!("profiles.{}", profile);
+    if file.get(&profile_key).is_some() {
+        if !prompter.confirm(
+            &format!("Profile '{}' exists. Overwrite it?", profile),
+            false,
+        )? {
+            return Err(anyhow::anyhow!("Setup cancelled"));
+        }
+        file.unset(&profile_key)?;
+    }
+
+    let region = prompter.region()?;
+    let entered = SecretString::from(rpassword::prompt_password("User API key (NRAK-...): ")?);
+    let api_key = SecretString::from(entered.expose().trim());
+
+    println!("Validating the API key...");
+    let client = NewRelicClient::builder()
+        .api_key(api_key.clone())
+        .region(region)
+        .build()?;
+    let identity = fetch_identity(&client).await?;
+    if let Some(user) = &identity.actor.user {
+        println!("Authenticated as {}", user.email);
+    }
+    println!(
+        "Found {} accessible account(s)",
+        identity.actor.accounts.len()
+    );
+    let account_id = prompter.account(&identity.actor.accounts)?;
+
+    match credentials::store_api_key(&profile, api_key.expose()) {
+        Ok(()) => println!("Stored the API key in the system keyring"),
+        Err(e) => {
+            println!("Unable to use the system keyring: {}", e);
+            if prompter.confirm(
+                "Store the key in config.toml instead (readable only by your user)?",
+                false,
+            )? {
+                file.set(&format!("{}.api_key", profile_key), api_key.expose())?;
+            } else {
+                println!(
+                    "Provide the key with --api-key or NEW_RELIC_API_KEY when running commands"
+                );
+            }
+        }
+    }
+
+    let region_name = match region {
+        Region::Us => "us",
+        Region::Eu => "eu",
+    };
+    file.set(&format!("{}.region", profile_key), region_name)?;
+    if let Some(account_id) = account_id {
+        file.set(
+            &format!("{}.account_id", profile_key),
+            &account_id.to_string(),
+        )?;
+    }
+    if file.get("default_profile").is_none() {
+        file.set("default_profile", &profile)?;
+    }
+    file.save(&config_path)?;
+
+    println!("Profile '{}' written to {}", profile, config_path.display());
+    println!(
+        "Try it out with: newrelic-apikeys-cli --profile {} whoami",
+        profile
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompter(input: &str) -> Prompter<&[u8], Vec<u8>> {
+        Prompter {
+            input: input.as_bytes(),
+            output: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_ask_uses_default_on_empty_answer() {
+        let mut p = prompter("\nprod\n");
+        assert_eq!(p.ask("Profile name", Some("default")).unwrap(), "default");
+        assert_eq!(p.ask("Profile name", Some("default")).unwrap(), "prod");
+    }
+
+    #[test]
+    fn test_ask_without_default_fails_on_eof() {
+        let mut p = prompter("");
+        assert!(p.ask("Profile name", None).is_err());
+    }
+
+    #[test]
+    fn test_region_reprompts_on_unknown_value() {
+        let mut p = prompter("mars\neu\n");
+        assert_eq!(p.region().unwrap(), Region::Eu);
+        assert!(String::from_utf8(p.output)
+            .unwrap()
+            .contains("Unknown region 'mars'"));
+    }
+
+    #[test]
+    fn test_account_selection() {
+        let accounts = vec![
+            IdentityAccount {
+                id: 1,
+                name: "Production".to_string(),
+            },
+            IdentityAccount {
+                id: 2,
+                name: "Staging".to_string(),
+            },
+        ];
+
+        assert_eq!(prompter("").account(&accounts[..1]).unwrap(), Some(1));
+        assert_eq!(prompter("5\n2\n").account(&accounts).unwrap(), Some(2));
+        assert_eq!(prompter("").account(&[]).unwrap(), None);
+    }
+}
@@ -0,0 +1,651 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{NewRelicClient, SecretString, Variables};
+
+/// An API key as returned by `keySearch`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: String,
+    pub name: Option<String>,
+    pub notes: Option<String>,
+    #[serde(rename = "type")]
+    pub key_type: Option<String>,
+    /// The key secret; only returned for keys the caller is allowed to see
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<SecretString>,
+    /// Creation time in epoch seconds
+    pub created_at: Option<i64>,
+    pub account_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingest_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i64>,
+}
+
+impl ApiKey {
+    /// The key secret, when NerdGraph returned it.
+    pub fn secret(&self) -> Option<&str> {
+        self.key.as_ref().map(SecretString::expose)
+    }
+
+    pub fn created(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.created_at?, 0)
+    }
+
+    pub fn age_days(&self, now: DateTime<Utc>) -> Option<i64> {
+        Some((now - self.created()?).num_days())
+    }
+
+    pub fn is_user_key(&self) -> bool {
+        self.key_type.as_deref() == Some("USER")
+    }
+}
+
+#[cfg(test)]
+impl ApiKey {
+    /// A key for tests: `fields` over an ingest key `K1` in account 1 without name or notes,
+    /// created at the epoch.
+    pub(crate) fn fixture(fields: serde_json::Value) -> ApiKey {
+        let mut key = serde_json::json!({
+            "id": "K1", "name": null, "notes": null, "type": "INGEST",
+            "createdAt": 0, "accountId": 1
+        });
+        if let (Some(key), serde_json::Value::Object(fields)) = (key.as_object_mut(), fields) {
+            key.extend(fields);
+        }
+        serde_json::from_value(key).unwrap()
+    }
+}
+
+/// Fields selected for every key, including the type-specific ones.
+const KEY_FIELDS: &str = "id
+                        name
+                        notes
+                        type
+                        key
+                        createdAt
+                        ... on ApiAccessIngestKey {
+                            accountId
+                            ingestType
+                        }
+                        ... on ApiAccessUserKey {
+                            accountId
+                            userId
+                        }";
+
+/// Fields of every key that [`ApiKey`] depends on; the secret is not returned for every key.
+const KEY_PATHS: [&str; 6] = ["id", "name", "notes", "type", "createdAt", "accountId"];
+
+/// `KEY_PATHS` under `prefix`, for [`NewRelicClient::check_schema`].
+fn key_paths(prefix: &str) -> Vec<String> {
+    KEY_PATHS
+        .iter()
+        .map(|field| format!("{}.{}", prefix, field))
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchPage {
+    keys: Vec<ApiKey>,
+    next_cursor: Option<String>,
+}
+
+pub(crate) fn search_query() -> String {
+    r#"
+    query($query: ApiAccessKeySearchQuery!, $cursor: String) {
+        actor {
+            apiAccess {
+                keySearch(query: $query, cursor: $cursor) {
+                    keys {
+                        {key_fields}
+                    }
+                    nextCursor
+                }
+            }
+        }
+    }"#
+    .replace("{key_fields}", KEY_FIELDS)
+}
+
+/// Fetch every key of the given types in the given accounts, following pagination cursors.
+///
+/// Cursors can shift when keys are created or deleted between pages, so a key may come back
+/// twice; the result holds each key once, ordered by account, type and ID.
+pub async fn fetch(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    key_types: &[&str],
+) -> anyhow::Result<Vec<ApiKey>> {
+    let query = search_query();
+    let mut paths = key_paths("actor.apiAccess.keySearch.keys[]");
+    paths.push("actor.apiAccess.keySearch.nextCursor".to_string());
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+    let mut keys = Vec::new();
+    let mut cursor: Option<String> = None;
+    let mut seen_cursors = HashSet::new();
+    loop {
+        let variables = search_variables(account_ids, key_types, cursor.as_deref());
+        let result = client.execute_query(&query, Some(variables)).await?;
+        client.check_schema("keySearch", &result, &paths)?;
+        let page: SearchPage =
+            serde_json::from_value(result["actor"]["apiAccess"]["keySearch"].clone())?;
+        keys.extend(page.keys);
+        match page.next_cursor {
+            Some(next) if !next.is_empty() && seen_cursors.insert(next.clone()) => {
+                cursor = Some(next)
+            }
+            _ => break,
+        }
+    }
+    Ok(settle(keys))
+}
+
+/// The variables of [`search_query`]. Key types are enum values, so they are sent upper-case.
+pub(crate) fn search_variables(
+    account_ids: &[i64],
+    key_types: &[&str],
+    cursor: Option<&str>,
+) -> Variables {
+    let key_types: Vec<String> = key_types.iter().map(|t| t.to_uppercase()).collect();
+    let variables = Variables::new().value(
+        "query",
+        serde_json::json!({
+            "types": key_types,
+            "scope": {"accountIds": account_ids},
+        }),
+    );
+    match cursor {
+        Some(cursor) => variables.string("cursor", cursor),
+        None => variables,
+    }
+}
+
+/// `keys` in a deterministic order with duplicates removed; a later page's copy of a key wins,
+/// being the more recent one.
+fn settle(keys: Vec<ApiKey>) -> Vec<ApiKey> {
+    let mut unique: HashMap<(Option<String>, String), ApiKey> = HashMap::new();
+    for key in keys {
+        unique.insert((key.key_type.clone(), key.id.clone()), key);
+    }
+    let mut keys: Vec<ApiKey> = unique.into_values().collect();
+    keys.sort_by(|a, b| {
+        (a.account_id, &a.key_type, &a.id).cmp(&(b.account_id, &b.key_type, &b.id))
+    });
+    keys
+}
+
+pub(crate) fn get_query() -> String {
+    r#"
+    query($id: ID!, $keyType: ApiAccessKeyType!) {
+        actor {
+            apiAccess {
+                key(id: $id, keyType: $keyType) {
+                    {key_fields}
+                }
+            }
+        }
+    }"#
+    .replace("{key_fields}", KEY_FIELDS)
+}
+
+/// The variables of [`get_query`].
+pub(crate) fn get_variables(key_id: &str, key_type: &str) -> Variables {
+    Variables::new()
+        .string("id", key_id)
+        .enum_value("keyType", key_type)
+}
+
+/// Look up a single key by ID and type (`INGEST` or `USER`).
+pub async fn get(client: &NewRelicClient, key_id: &str, key_type: &str) -> anyhow::Result<ApiKey> {
+    let query = get_query();
+    let result = client
+        .execute_query(&query, Some(get_variables(key_id, key_type)))
+        .await?;
+    let paths = key_paths("actor.apiAccess.key");
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    client.check_schema("key", &result, &paths)?;
+    let key = &result["actor"]["apiAccess"]["key"];
+    if key.is_null() {
+        return Err(anyhow::anyhow!("{} key {} not found", key_type, key_id));
+    }
+    Ok(serde_json::from_value(key.clone())?)
+}
+
+/// Everything needed to create a key.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewKey {
+    #[serde(rename = "type")]
+    pub key_type: String,
+    pub account_id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// `LICENSE` or `BROWSER`, for ingest keys (default: `LICENSE`)
+    #[serde(default)]
+    pub ingest_type: Option<String>,
+    /// Owner of a user key
+    #[serde(default)]
+    pub user_id: Option<i64>,
+}
+
+impl NewKey {
+    /// The same kind of key in the same account, for replacing `key`.
+    pub fn replacing(key: &ApiKey) -> anyhow::Result<Self> {
+        Ok(Self {
+            key_type: key.key_type.clone().unwrap_or_else(|| "INGEST".to_string()),
+            account_id: key
+                .account_id
+                .ok_or_else(|| anyhow::anyhow!("Key {} has no account", key.id))?,
+            name: key.name.clone().unwrap_or_default(),
+            notes: key.notes.clone(),
+            ingest_type: key.ingest_type.clone(),
+            user_id: key.user_id,
+        })
+    }
+
+    /// The `keys` variable of [`create_query`].
+    pub(crate) fn input(&self) -> anyhow::Result<serde_json::Value> {
+        match self.key_type.to_uppercase().as_str() {
+            "INGEST" => Ok(serde_json::json!({
+                "ingest": [{
+                    "accountId": self.account_id,
+                    "ingestType": self
+                        .ingest_type
+                        .as_deref()
+                        .unwrap_or("LICENSE")
+                        .to_uppercase(),
+                    "name": self.name,
+                    "notes": self.notes,
+                }]
+            })),
+            "USER" => {
+                let user_id = self
+                    .user_id
+                    .ok_or_else(|| anyhow::anyhow!("Creating a USER key requires a user ID"))?;
+                Ok(serde_json::json!({
+                    "user": [{
+                        "accountId": self.account_id,
+                        "userId": user_id,
+                        "name": self.name,
+                        "notes": self.notes,
+                    }]
+                }))
+            }
+            other => Err(anyhow::anyhow!(
+                "Unsupported key type '{}' (expected INGEST or USER)",
+                other
+            )),
+        }
+    }
+}
+
+pub(crate) fn create_query() -> String {
+    r#"
+    mutation($keys: ApiAccessCreateInput!) {
+        apiAccessCreateKeys(keys: $keys) {
+            createdKeys {
+                {key_fields}
+            }
+            errors {
+                message
+            }
+        }
+    }"#
+    .replace("{key_fields}", KEY_FIELDS)
+}
+
+/// Create one key and return it, including its secret.
+pub async fn create(client: &NewRelicClient, spec: &NewKey) -> anyhow::Result<ApiKey> {
+    let query = create_query();
+
+    let variables = Variables::new().value("keys", spec.input()?);
+    let result = client.execute_query(&query, Some(variables)).await?;
+    let response = &result["apiAccessCreateKeys"];
+    match response["createdKeys"]
+        .as_array()
+        .and_then(|keys| keys.first())
+    {
+        Some(key) => Ok(serde_json::from_value(key.clone())?),
+        None => Err(anyhow::anyhow!(
+            "Unable to create key '{}': {}",
+            spec.name,
+            key_errors(response)
+        )),
+    }
+}
+
+/// The per-key `errors` of a create or update response.
+fn key_errors(response: &serde_json::Value) -> String {
+    let errors: Vec<&str> = response["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|e| e["message"].as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    if errors.is_empty() {
+        "no key returned".to_string()
+    } else {
+        errors.join(", ")
+    }
+}
+
+pub(crate) fn update_query() -> String {
+    r#"
+    mutation($keys: ApiAccessUpdateInput!) {
+        apiAccessUpdateKeys(keys: $keys) {
+            updatedKeys {
+                {key_fields}
+            }
+            errors {
+                message
+            }
+        }
+    }"#
+    .replace("{key_fields}", KEY_FIELDS)
+}
+
+/// The variables of [`update_query`].
+pub(crate) fn update_variables(
+    key_id: &str,
+    key_type: &str,
+    name: Option<&str>,
+    notes: Option<&str>,
+) -> anyhow::Result<Variables> {
+    update_keys_variables(&[KeyChange {
+        key_id: key_id.to_string(),
+        key_type: key_type.to_string(),
+        name: name.map(str::to_string),
+        notes: notes.map(str::to_string),
+    }])
+}
+
+/// A new name and/or notes for one key; `None` leaves that field as it is.
+#[derive(Clone, Debug, Serialize)]
+pub struct KeyChange {
+    pub key_id: String,
+    pub key_type: String,
+    pub name: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// The variables of [`update_query`] for several keys, ingest and user keys in their own lists.
+pub(crate) fn update_keys_variables(changes: &[KeyChange]) -> anyhow::Result<Variables> {
+    let (mut ingest, mut user) = (Vec::new(), Vec::new());
+    for change in changes {
+        let mut key = serde_json::json!({ "keyId": change.key_id });
+        if let Some(name) = &change.name {
+            key["name"] = serde_json::json!(name);
+        }
+        if let Some(notes) = &change.notes {
+            key["notes"] = serde_json::json!(notes);
+        }
+        match change.key_type.to_uppercase().as_str() {
+            "INGEST" => ingest.push(key),
+            "USER" => user.push(key),
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported key type '{}' (expected INGEST or USER)",
+                    other
+                ))
+            }
+        }
+    }
+    let mut input = serde_json::Map::new();
+    if !ingest.is_empty() {
+        input.insert("ingest".to_string(), ingest.into());
+    }
+    if !user.is_empty() {
+        input.insert("user".to_string(), user.into());
+    }
+    Ok(Variables::new().value("keys", input.into()))
+}
+
+/// Change the name and/or notes of a key; `None` leaves that field as it is.
+pub async fn update(
+    client: &NewRelicClient,
+    key_id: &str,
+    key_type: &str,
+    name: Option<&str>,
+    notes: Option<&str>,
+) -> anyhow::Result<ApiKey> {
+    let variables = update_variables(key_id, key_type, name, notes)?;
+    let result = client
+        .execute_query(&update_query(), Some(variables))
+        .await?;
+    parse_update(key_id, &result)
+}
+
+/// The updated key in the `data` of [`update_query`], or its errors.
+pub(crate) fn parse_update(key_id: &str, result: &serde_json::Value) -> anyhow::Result<ApiKey> {
+    let response = &result["apiAccessUpdateKeys"];
+    match response["updatedKeys"]
+        .as_array()
+        .and_then(|keys| keys.first())
+    {
+        Some(key) => Ok(serde_json::from_value(key.clone())?),
+        None => Err(anyhow::anyhow!(
+            "Unable to update key {}: {}",
+            key_id,
+            key_errors(response)
+        )),
+    }
+}
+
+/// Result of a batch update: the keys as they are now, and per-key errors.
+#[derive(Serialize)]
+pub struct UpdateOutcome {
+    pub updated: Vec<ApiKey>,
+    pub errors: Vec<String>,
+}
+
+/// Change the names and/or notes of several keys in one mutation.
+pub async fn update_keys(
+    client: &NewRelicClient,
+    changes: &[KeyChange],
+) -> anyhow::Result<UpdateOutcome> {
+    let variables = update_keys_variables(changes)?;
+    let result = client
+        .execute_query(&update_query(), Some(variables))
+        .await?;
+    let response = &result["apiAccessUpdateKeys"];
+    let updated = match response["updatedKeys"].as_array() {
+        Some(keys) => keys
+            .iter()
+            .map(|key| serde_json::from_value(key.clone()))
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    let errors = response["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|e| e["message"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(UpdateOutcome { updated, errors })
+}
+
+pub(crate) const DELETE_QUERY: &str = r#"
+    mutation($keys: ApiAccessDeleteInput!) {
+        apiAccessDeleteKeys(keys: $keys) {
+            deletedKeys {
+                id
+            }
+            errors {
+                message
+            }
+        }
+    }"#;
+
+/// Result of a batch delete: New Relic reports deleted IDs and per-key errors separately.
+#[derive(Serialize)]
+pub struct DeleteOutcome {
+    pub deleted: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// The variables of [`DELETE_QUERY`].
+pub(crate) fn delete_variables(ingest_key_ids: &[String], user_key_ids: &[String]) -> Variables {
+    Variables::new().value(
+        "keys",
+        serde_json::json!({
+            "ingestKeyIds": ingest_key_ids,
+            "userKeyIds": user_key_ids,
+        }),
+    )
+}
+
+/// Delete ingest and user keys in one mutation.
+pub async fn delete_keys(
+    client: &NewRelicClient,
+    ingest_key_ids: &[String],
+    user_key_ids: &[String],
+) -> anyhow::Result<DeleteOutcome> {
+    let variables = delete_variables(ingest_key_ids, user_key_ids);
+    let result = client.execute_query(DELETE_QUERY, Some(variables)).await?;
+    Ok(parse_delete_outcome(&result["apiAccessDeleteKeys"]))
+}
+
+pub(crate) fn parse_delete_outcome(response: &serde_json::Value) -> DeleteOutcome {
+    let strings = |field: &str, key: &str| -> Vec<String> {
+        response[field]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item[key].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    DeleteOutcome {
+        deleted: strings("deletedKeys", "id"),
+        errors: strings("errors", "message"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_page_deserialization() {
+        let page: SearchPage = serde_json::from_value(serde_json::json!({
+            "keys": [
+                {
+                    "id": "ingest-1",
+                    "name": "Prod license",
+                    "notes": null,
+                    "type": "INGEST",
+                    "key": "abcNRAL",
+                    "createdAt": 1700000000,
+                    "accountId": 123,
+                    "ingestType": "LICENSE"
+                },
+                {
+                    "id": "user-1",
+                    "name": "Automation",
+                    "notes": "owned by platform",
+                    "type": "USER",
+                    "createdAt": 1600000000,
+                    "accountId": 123,
+                    "userId": 42
+                }
+            ],
+            "nextCursor": "abc"
+        }))
+        .unwrap();
+
+        assert_eq!(page.keys.len(), 2);
+        assert_eq!(page.keys[0].ingest_type.as_deref(), Some("LICENSE"));
+        assert!(page.keys[1].is_user_key());
+        assert_eq!(page.keys[1].user_id, Some(42));
+        assert_eq!(page.next_cursor.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_settle_orders_and_deduplicates() {
+        let key = |id: &str, key_type: &str, account_id: i64, name: &str| -> ApiKey {
+            ApiKey::fixture(serde_json::json!({
+                "id": id, "name": name, "type": key_type,
+                "createdAt": 1700000000, "accountId": account_id
+            }))
+        };
+        let keys = settle(vec![
+            key("b", "INGEST", 2, "b"),
+            key("a", "USER", 1, "a"),
+            key("c", "INGEST", 1, "old"),
+            key("a", "INGEST", 1, "a"),
+            key("c", "INGEST", 1, "renamed"),
+        ]);
+        let order: Vec<(&str, &str)> = keys
+            .iter()
+            .map(|k| (k.id.as_str(), k.key_type.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("a", "INGEST"),
+                ("c", "INGEST"),
+                ("a", "USER"),
+                ("b", "INGEST")
+            ]
+        );
+        assert_eq!(keys[1].name.as_deref(), Some("renamed"));
+    }
+
+    #[test]
+    fn test_new_key_input() {
+        let spec = NewKey {
+            key_type: "INGEST".to_string(),
+            account_id: 1,
+            name: "ingest".to_string(),
+            notes: None,
+            ingest_type: None,
+            user_id: None,
+        };
+        assert_eq!(spec.input().unwrap()["ingest"][0]["ingestType"], "LICENSE");
+
+        let user = NewKey {
+            key_type: "USER".to_string(),
+            ..spec
+        };
+        assert!(user.input().is_err());
+        let user = NewKey {
+            user_id: Some(7),
+            ..user
+        };
+        assert_eq!(user.input().unwrap()["user"][0]["userId"], 7);
+    }
+
+    #[test]
+    fn test_parse_delete_outcome() {
+        let outcome = parse_delete_outcome(&serde_json::json!({
+            "deletedKeys": [{"id": "a"}, {"id": "b"}],
+            "errors": [{"message": "Key c not found"}]
+        }));
+        assert_eq!(outcome.deleted, vec!["a", "b"]);
+        assert_eq!(outcome.errors, vec!["Key c not found"]);
+    }
+
+    #[test]
+    fn test_age_days() {
+        let key = ApiKey::fixture(serde_json::json!({
+            "id": "k", "type": "USER",
+            "createdAt": 1700000000, "accountId": 1
+        }));
+        let now = DateTime::from_timestamp(1700000000 + 10 * 86400, 0).unwrap();
+        assert_eq!(key.age_days(now), Some(10));
+    }
+}
@@ -0,0 +1,184 @@
+//! Client and types for managing New Relic API keys through NerdGraph.
+//!
+//! The `newrelic-apikeys-cli` binary is built on top of this library. Embedders that only
+//! need the HTTP client and key types can drop the CLI and its dependencies:
+//!
+//! ```toml
+//! newrelic_apikeys_cli = { version = "0.0.1", default-features = false, features = ["rustls"] }
+//! ```
+//!
+//! ```no_run
+//! # #[cfg(feature = "reqwest")]
+//! # async fn example() -> anyhow::Result<()> {
+//! use std::time::Duration;
+//! use newrelic_apikeys_cli::{inventory, NewRelicClient, Region};
+//!
+//! let client = NewRelicClient::builder()
+//!     .api_key("NRAK-...")
+//!     .region(Region::Eu)
+//!     .timeout(Duration::from_secs(30))
+//!     .build()?;
+//! for key in inventory::fetch(&client, &[123456], &["USER", "INGEST"]).await? {
+//!     println!("{} {}", key.id, key.name.unwrap_or_default());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod audit_events;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod client;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod identity;
+pub mod inventory;
+pub mod middleware;
+pub mod nrql;
+pub mod output;
+#[cfg(feature = "python")]
+mod python;
+pub mod rate_limit;
+pub mod rotation;
+pub mod schema;
+mod secret;
+pub mod transport;
+pub mod usage;
+mod variables;
+
+#[cfg(feature = "blocking")]
+pub use blocking::NewRelicClientBlocking;
+pub use client::{
+    GraphQLError, GraphQLErrors, Location, NewRelicClient, NewRelicClientBuilder, Region,
+    RequestError, RetryPolicy, DEFAULT_ENDPOINT, DEFAULT_USER_AGENT, REQUEST_ID_HEADER,
+};
+pub use identity::{
+    fetch_identity, key_type_from_prefix, Identity, IdentityAccount, IdentityActor,
+    IdentityOrganization, IdentityUser,
+};
+pub use secret::SecretString;
+pub use variables::Variables;
+
+#[cfg(feature = "cli")]
+mod alias;
+#[cfg(feature = "cli")]
+mod anomaly;
+#[cfg(feature = "cli")]
+mod audit;
+#[cfg(feature = "cli")]
+mod bench;
+#[cfg(feature = "cli")]
+mod blueprint;
+#[cfg(feature = "cli")]
+mod cache;
+#[cfg(feature = "cli")]
+mod cancel;
+#[cfg(feature = "cli")]
+mod ci;
+#[cfg(feature = "cli")]
+mod cleanup;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+mod cloning;
+#[cfg(feature = "cli")]
+mod commands;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "cli")]
+mod consumption;
+#[cfg(feature = "cli")]
+mod context;
+#[cfg(feature = "cli")]
+mod contract;
+#[cfg(feature = "cli")]
+mod credentials;
+#[cfg(feature = "cli")]
+mod crypt;
+#[cfg(feature = "cli")]
+mod daemon;
+#[cfg(feature = "cli")]
+mod doctor;
+#[cfg(feature = "cli")]
+mod drift;
+#[cfg(feature = "cli")]
+mod environment;
+#[cfg(feature = "cli")]
+mod export;
+#[cfg(feature = "cli")]
+mod expression;
+#[cfg(feature = "cli")]
+mod filter;
+#[cfg(feature = "cli")]
+mod fingerprint;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzz;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "cli")]
+mod guardrails;
+#[cfg(feature = "cli")]
+mod hints;
+#[cfg(feature = "cli")]
+mod history;
+#[cfg(feature = "cli")]
+mod hooks;
+#[cfg(feature = "cli")]
+mod init;
+#[cfg(feature = "cli")]
+mod list;
+#[cfg(feature = "cli")]
+mod mcp;
+#[cfg(feature = "cli")]
+mod metrics;
+#[cfg(feature = "cli")]
+mod onboard;
+#[cfg(feature = "cli")]
+mod output_file;
+#[cfg(feature = "package")]
+mod package;
+#[cfg(feature = "cli")]
+mod pager;
+#[cfg(feature = "cli")]
+mod paths;
+#[cfg(feature = "cli")]
+mod prompt;
+#[cfg(feature = "cli")]
+mod protect;
+#[cfg(feature = "cli")]
+mod report;
+#[cfg(feature = "cli")]
+mod scan;
+#[cfg(feature = "cli")]
+mod scheduler;
+#[cfg(feature = "cli")]
+mod selector;
+#[cfg(feature = "cli")]
+mod serve;
+#[cfg(feature = "cli")]
+mod service_account;
+#[cfg(feature = "cli")]
+mod session;
+#[cfg(feature = "cli")]
+mod siem;
+#[cfg(feature = "cli")]
+mod sink;
+#[cfg(feature = "cli")]
+mod snapshot;
+#[cfg(feature = "cli")]
+mod sweep;
+#[cfg(feature = "cli")]
+mod targets;
+#[cfg(feature = "cli")]
+mod tfstate;
+#[cfg(feature = "cli")]
+mod time;
+#[cfg(feature = "cli")]
+mod transfer;
+#[cfg(feature = "cli")]
+mod warnings;
+#[cfg(feature = "cli")]
+mod webhook;
+#[cfg(feature = "cli")]
+mod window;
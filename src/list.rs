@@ -0,0 +1,629 @@
+use chrono::{DateTime, Utc};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use crate::config::Config;
+use crate::filter::KeyFilter;
+use crate::output::{self, Format, Renderer};
+use crate::warnings::{self, Code};
+use crate::{credentials, fingerprint, inventory, time, NewRelicClient, Region, SecretString};
+
+/// One endpoint to list keys from.
+pub struct Target {
+    /// Set when listing across profiles, which adds a profile column
+    pub profile: Option<String>,
+    pub region: String,
+    pub client: NewRelicClient,
+    pub account_ids: Vec<i64>,
+}
+
+/// A listed key; deliberately without the key secret.
+#[derive(Serialize)]
+struct Row {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    region: String,
+    id: String,
+    name: Option<String>,
+    key_type: Option<String>,
+    account_id: Option<i64>,
+    created_at: Option<DateTime<Utc>>,
+    notes: Option<String>,
+    /// Only for keys whose secret NerdGraph returns
+    fingerprint: Option<String>,
+}
+
+impl Row {
+    fn new(profile: Option<String>, region: String, key: inventory::ApiKey) -> Self {
+        Row {
+            profile,
+            region,
+            created_at: key.created(),
+            fingerprint: key.secret().map(fingerprint::fingerprint),
+            id: key.id,
+            name: key.name,
+            key_type: key.key_type,
+            account_id: key.account_id,
+            notes: key.notes,
+        }
+    }
+}
+
+/// `us`, `eu`, or the endpoint's host for anything else.
+pub fn region_label(endpoint: &str) -> String {
+    for region in [Region::Us, Region::Eu] {
+        if endpoint == region.endpoint() {
+            return format!("{:?}", region).to_lowercase();
+        }
+    }
+    endpoint
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .unwrap_or(endpoint)
+        .to_string()
+}
+
+/// Every endpoint of the named profiles, or of every profile when `names` is empty: the
+/// profile's own endpoint when it has an account, plus each entry of its `endpoints`. `build`
+/// turns an API key and endpoint into a client.
+pub fn profile_targets(
+    config: &Config,
+    names: &[String],
+    build: impl Fn(SecretString, String) -> anyhow::Result<NewRelicClient>,
+) -> anyhow::Result<Vec<Target>> {
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !config.profiles.contains_key(*name))
+    {
+        anyhow::bail!("Profile '{}' is not defined", unknown);
+    }
+    let mut targets = Vec::new();
+    for (name, profile) in &config.profiles {
+        if !names.is_empty() && !names.contains(name) {
+            continue;
+        }
+        config.check_resolved(name)?;
+        let api_key = match &profile.api_key {
+            Some(api_key) => Some(api_key.clone()),
+            None => credentials::load_api_key(name).unwrap_or(None),
+        };
+        let mut add = |endpoint: String, api_key: Option<SecretString>, account_ids: Vec<i64>| {
+            let api_key = api_key.ok_or_else(|| {
+                anyhow::anyhow!("Profile '{}' has no API key for {}", name, endpoint)
+            })?;
+            targets.push(Target {
+                profile: Some(name.clone()),
+                region: region_label(&endpoint),
+                client: build(api_key, endpoint)?,
+                account_ids,
+            });
+            anyhow::Ok(())
+        };
+        if let Some(account_id) = profile.account_id {
+            add(
+                config.endpoint(Some(profile)),
+                api_key.clone(),
+                vec![account_id],
+            )?;
+        }
+        for endpoint in &profile.endpoints {
+            if endpoint.account_ids.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "An endpoint of profile '{}' has no account_ids",
+                    name
+                ));
+            }
+            add(
+                endpoint.url(),
+                endpoint.api_key.clone().or_else(|| api_key.clone()),
+                endpoint.account_ids.clone(),
+            )?;
+        }
+    }
+    if targets.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No profile has an account_id or endpoints to list"
+        ));
+    }
+    Ok(targets)
+}
+
+/// List keys from every target concurrently and render them merged.
+pub async fn run(
+    targets: Vec<Target>,
+    key_types: &[&str],
+    format: Format,
+    view: &View,
+) -> anyhow::Result<String> {
+    let mut rows = collect(targets, key_types, &view.filter).await?;
+    view.filter(&mut rows);
+    view.sort(&mut rows);
+    render(&rows, format, view)
+}
+
+/// Render the `limit` keys whose name or ID best matches `pattern`, best first unless `view`
+/// sorts them, using skim-style fuzzy matching (the pattern's characters in order, not
+/// necessarily adjacent).
+pub async fn find(
+    targets: Vec<Target>,
+    key_types: &[&str],
+    pattern: &str,
+    limit: usize,
+    format: Format,
+    view: &View,
+) -> anyhow::Result<String> {
+    let mut rows = collect(targets, key_types, &view.filter).await?;
+    view.filter(&mut rows);
+    let mut matches = best_matches(rows, pattern, limit);
+    if matches.is_empty() {
+        eprintln!("No keys match '{}'", pattern);
+    }
+    view.sort(&mut matches);
+    render(&matches, format, view)
+}
+
+/// Render `keys` as `run` would, without fetching them; for the fuzz targets.
+#[cfg(feature = "fuzzing")]
+pub(crate) fn render_keys(
+    keys: Vec<inventory::ApiKey>,
+    format: Format,
+    view: &View,
+) -> anyhow::Result<String> {
+    let mut rows: Vec<Row> = keys
+        .into_iter()
+        .map(|key| Row::new(None, "us".to_string(), key))
+        .collect();
+    view.filter(&mut rows);
+    view.sort(&mut rows);
+    render(&rows, format, view)
+}
+
+fn best_matches(rows: Vec<Row>, pattern: &str, limit: usize) -> Vec<Row> {
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut scored: Vec<(i64, Row)> = rows
+        .into_iter()
+        .filter_map(|row| {
+            let name = row.name.as_deref().unwrap_or_default();
+            let score = matcher
+                .fuzzy_match(name, pattern)
+                .max(matcher.fuzzy_match(&row.id, pattern))?;
+            Some((score, row))
+        })
+        .collect();
+    // Stable, so equal scores keep the listing order.
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().take(limit).map(|(_, row)| row).collect()
+}
+
+/// Fetch from every target concurrently. A failing target is reported on stderr without
+/// hiding the others' keys; only if all fail is it an error.
+async fn collect(
+    targets: Vec<Target>,
+    key_types: &[&str],
+    filter: &KeyFilter,
+) -> anyhow::Result<Vec<Row>> {
+    let count = targets.len();
+    let key_types: Vec<String> = key_types.iter().map(|t| t.to_string()).collect();
+    let mut tasks = JoinSet::new();
+    for target in targets {
+        let key_types = key_types.clone();
+        tasks.spawn(async move {
+            let key_types: Vec<&str> = key_types.iter().map(String::as_str).collect();
+            let keys = inventory::fetch(&target.client, &target.account_ids, &key_types).await;
+            (target, keys)
+        });
+    }
+
+    let mut rows = Vec::new();
+    let mut failures = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (target, keys) = joined?;
+        let keys = match keys {
+            Ok(keys) => keys,
+            Err(e) => {
+                failures.push((target, e));
+                continue;
+            }
+        };
+        rows.extend(
+            keys.into_iter()
+                .filter(|key| filter.matches(key))
+                .map(|key| Row::new(target.profile.clone(), target.region.clone(), key)),
+        );
+    }
+    if count == 1 {
+        if let Some((_, e)) = failures.pop() {
+            return Err(e);
+        }
+    }
+    for (target, e) in &failures {
+        let source = match &target.profile {
+            Some(profile) => format!("profile '{}' ({})", profile, target.region),
+            None => target.region.clone(),
+        };
+        warnings::warn(
+            Code::PartialResults,
+            format!("unable to list keys from {}: {}", source, e),
+        );
+    }
+    if failures.len() == count {
+        return Err(anyhow::anyhow!("Unable to list keys from any endpoint"));
+    }
+    rows.sort_by(|a, b| {
+        (&a.profile, &a.region, a.account_id, &a.id).cmp(&(
+            &b.profile,
+            &b.region,
+            b.account_id,
+            &b.id,
+        ))
+    });
+    Ok(rows)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    Created,
+    Name,
+    Type,
+}
+
+impl SortKey {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "created" => Ok(SortKey::Created),
+            "name" => Ok(SortKey::Name),
+            "type" => Ok(SortKey::Type),
+            other => Err(anyhow::anyhow!(
+                "Unsupported sort key '{}' (expected created, name or type)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Column {
+    Profile,
+    Region,
+    Account,
+    Type,
+    Id,
+    Name,
+    Created,
+    Notes,
+    Fingerprint,
+}
+
+impl Column {
+    const NAMES: [(&'static str, Column); 9] = [
+        ("profile", Column::Profile),
+        ("region", Column::Region),
+        ("account", Column::Account),
+        ("type", Column::Type),
+        ("id", Column::Id),
+        ("name", Column::Name),
+        ("created", Column::Created),
+        ("notes", Column::Notes),
+        ("fingerprint", Column::Fingerprint),
+    ];
+
+    /// A comma-separated list such as `id,name,notes`.
+    pub fn parse_list(value: &str) -> anyhow::Result<Vec<Self>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Self::NAMES
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|(_, column)| *column)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Unknown column '{}' (expected {})",
+                            name,
+                            Self::NAMES.map(|(n, _)| n).join(", ")
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    fn name(self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(_, column)| *column == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    /// `now` renders creation times as relative ages ("3 months ago"), for the table format.
+    fn cell(self, row: &Row, now: Option<DateTime<Utc>>) -> String {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        match self {
+            Column::Profile => text(&row.profile),
+            Column::Region => row.region.clone(),
+            Column::Account => row.account_id.map(|id| id.to_string()).unwrap_or_default(),
+            Column::Type => text(&row.key_type),
+            Column::Id => row.id.clone(),
+            Column::Name => text(&row.name),
+            Column::Created => match (row.created_at, now) {
+                (Some(created), Some(now)) => time::ago(created, now),
+                (Some(created), None) => created.format("%Y-%m-%d").to_string(),
+                (None, _) => String::new(),
+            },
+            Column::Notes => text(&row.notes),
+            Column::Fingerprint => text(&row.fingerprint),
+        }
+    }
+
+    fn value(self, row: &Row) -> serde_json::Value {
+        match self {
+            Column::Profile => serde_json::json!(row.profile),
+            Column::Region => serde_json::json!(row.region),
+            Column::Account => serde_json::json!(row.account_id),
+            Column::Type => serde_json::json!(row.key_type),
+            Column::Id => serde_json::json!(row.id),
+            Column::Name => serde_json::json!(row.name),
+            Column::Created => serde_json::json!(row.created_at),
+            Column::Notes => serde_json::json!(row.notes),
+            Column::Fingerprint => serde_json::json!(row.fingerprint),
+        }
+    }
+}
+
+/// How to order and which columns to show; the default is the merged listing order and every
+/// column.
+#[derive(Default)]
+pub struct View {
+    pub sort: Option<SortKey>,
+    pub reverse: bool,
+    pub columns: Option<Vec<Column>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub created_after: Option<DateTime<Utc>>,
+    /// `--selector`; applied to the keys before they become rows
+    pub filter: KeyFilter,
+}
+
+impl View {
+    /// Keys without a creation time never match a date filter.
+    fn filter(&self, rows: &mut Vec<Row>) {
+        if self.created_before.is_none() && self.created_after.is_none() {
+            return;
+        }
+        rows.retain(|row| {
+            row.created_at.is_some_and(|created| {
+                self.created_before.is_none_or(|before| created < before)
+                    && self.created_after.is_none_or(|after| created >= after)
+            })
+        });
+    }
+
+    /// Stable, so keys that compare equal keep the deterministic listing order however the
+    /// pages arrived; `reverse` flips the sort key, not the tie order.
+    fn sort(&self, rows: &mut [Row]) {
+        let Some(key) = self.sort else {
+            if self.reverse {
+                rows.reverse();
+            }
+            return;
+        };
+        rows.sort_by(|a, b| {
+            let ordering = match key {
+                SortKey::Created => a.created_at.cmp(&b.created_at),
+                SortKey::Name => {
+                    let name = |row: &Row| row.name.as_deref().unwrap_or_default().to_lowercase();
+                    name(a).cmp(&name(b))
+                }
+                SortKey::Type => a.key_type.cmp(&b.key_type),
+            };
+            if self.reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+fn render(rows: &[Row], format: Format, view: &View) -> anyhow::Result<String> {
+    Listing { rows, view }.render(&format)
+}
+
+/// Rows with the columns of a [`View`].
+struct Listing<'a> {
+    rows: &'a [Row],
+    view: &'a View,
+}
+
+impl Listing<'_> {
+    /// `--columns`, or every column, with PROFILE only when the rows span profiles.
+    fn columns(&self) -> Vec<Column> {
+        match &self.view.columns {
+            Some(columns) => columns.clone(),
+            None => {
+                let with_profile = self.rows.iter().any(|row| row.profile.is_some());
+                Column::NAMES
+                    .iter()
+                    .map(|(_, column)| *column)
+                    .filter(|column| with_profile || *column != Column::Profile)
+                    .collect()
+            }
+        }
+    }
+
+    fn cells(&self, now: Option<DateTime<Utc>>) -> Vec<Vec<String>> {
+        let columns = self.columns();
+        self.rows
+            .iter()
+            .map(|row| columns.iter().map(|column| column.cell(row, now)).collect())
+            .collect()
+    }
+}
+
+impl Renderer for Listing<'_> {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(match &self.view.columns {
+            None => serde_json::to_value(self.rows)?,
+            Some(columns) => self
+                .rows
+                .iter()
+                .map(|row| {
+                    columns
+                        .iter()
+                        .map(|column| (column.name().to_string(), column.value(row)))
+                        .collect::<serde_json::Map<_, _>>()
+                })
+                .collect(),
+        })
+    }
+
+    fn headers(&self) -> Vec<String> {
+        self.columns()
+            .iter()
+            .map(|column| column.name().to_uppercase())
+            .collect()
+    }
+
+    /// Ages relative to now, e.g. "3 days ago".
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.cells(Some(Utc::now()))
+    }
+
+    /// Dates instead of ages, which would be stale by the time the file is read.
+    fn render_csv(&self) -> String {
+        let headers = self.headers();
+        let headers: Vec<&str> = headers.iter().map(String::as_str).collect();
+        output::csv(&headers, &self.cells(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_label() {
+        assert_eq!(region_label(crate::DEFAULT_ENDPOINT), "us");
+        assert_eq!(region_label("https://api.eu.newrelic.com/graphql"), "eu");
+        assert_eq!(
+            region_label("https://nerdgraph.example.com/graphql"),
+            "nerdgraph.example.com"
+        );
+    }
+
+    fn row(id: &str, name: &str) -> Row {
+        Row {
+            profile: None,
+            region: "us".to_string(),
+            id: id.to_string(),
+            name: Some(name.to_string()),
+            key_type: Some("INGEST".to_string()),
+            account_id: Some(1),
+            created_at: None,
+            notes: None,
+            fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn test_best_matches() {
+        let rows = vec![
+            row("a1", "payments-prod-license"),
+            row("b2", "checkout staging"),
+            row("c3", "Payments Staging Browser"),
+        ];
+        let ids = |rows: Vec<Row>| rows.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(best_matches(rows, "pay stg", 10)), ["c3"]);
+
+        let rows = vec![row("a1", "payments-prod"), row("b2", "pay")];
+        assert_eq!(ids(best_matches(rows, "pay", 1)).len(), 1);
+        assert!(best_matches(vec![row("a1", "checkout")], "zzz", 10).is_empty());
+    }
+
+    #[test]
+    fn test_view_sorts_stably_and_selects_columns() {
+        let mut rows = vec![row("a1", "beta"), row("b2", "Alpha"), row("c3", "beta")];
+        let view = View {
+            sort: Some(SortKey::Name),
+            reverse: true,
+            columns: Some(Column::parse_list("id, name").unwrap()),
+            ..View::default()
+        };
+        view.sort(&mut rows);
+        assert_eq!(
+            rows.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            ["a1", "c3", "b2"]
+        );
+        assert_eq!(
+            render(&rows[..1], Format::Csv, &view).unwrap(),
+            "ID,NAME\na1,beta\n"
+        );
+        assert!(Column::parse_list("id,secret").is_err());
+        assert!(SortKey::parse("age").is_err());
+    }
+
+    #[test]
+    fn test_view_filters_by_creation_time() {
+        let created = |id: &str, timestamp: Option<i64>| Row {
+            created_at: timestamp.and_then(|t| DateTime::from_timestamp(t, 0)),
+            ..row(id, id)
+        };
+        let mut rows = vec![
+            created("old", Some(1_000)),
+            created("new", Some(3_000)),
+            created("unknown", None),
+        ];
+        let view = View {
+            created_after: DateTime::from_timestamp(2_000, 0),
+            ..View::default()
+        };
+        view.filter(&mut rows);
+        assert_eq!(
+            rows.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            ["new"]
+        );
+    }
+
+    #[test]
+    fn test_profile_targets_include_extra_endpoints() {
+        let config = Config::parse_in(
+            r#"
+            [profiles.org]
+            api_key = "NRAK-US"
+            account_id = 1
+
+            [[profiles.org.endpoints]]
+            region = "eu"
+            api_key = "NRAK-EU"
+            account_ids = [2, 3]
+
+            [profiles.other]
+            api_key = "NRAK-OTHER"
+            account_id = 4
+            "#,
+            std::path::Path::new("."),
+        )
+        .unwrap();
+        let build = |api_key, endpoint| {
+            NewRelicClient::builder()
+                .api_key(api_key)
+                .endpoint(endpoint)
+                .build()
+        };
+        let targets = profile_targets(&config, &["org".to_string()], build).unwrap();
+        let summary: Vec<(&str, &str, &[i64])> = targets
+            .iter()
+            .map(|t| (t.region.as_str(), t.client.api_key(), &t.account_ids[..]))
+            .collect();
+        assert_eq!(
+            summary,
+            [("us", "NRAK-US", &[1][..]), ("eu", "NRAK-EU", &[2, 3][..])]
+        );
+        assert_eq!(profile_targets(&config, &[], build).unwrap().len(), 3);
+        assert!(profile_targets(&config, &["missing".to_string()], build).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::inventory::{self, NewKey};
+use crate::{history, rotation, usage, NewRelicClient};
+
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const PARSE_ERROR: i64 = -32700;
+
+/// Model Context Protocol server exposing key inventory as tools over stdio (JSON-RPC 2.0,
+/// one message per line).
+pub struct Server {
+    client: NewRelicClient,
+    default_account_ids: Vec<i64>,
+    allow_mutations: bool,
+}
+
+fn schema(properties: Value, required: &[&str]) -> Value {
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn key_type_property() -> Value {
+    json!({ "type": "string", "enum": ["INGEST", "USER"] })
+}
+
+fn tools(allow_mutations: bool) -> Vec<Value> {
+    let mut tools = vec![
+        json!({
+            "name": "list_keys",
+            "description": "List New Relic API keys (without secrets) in one or more accounts.",
+            "inputSchema": schema(json!({
+                "account_ids": { "type": "array", "items": { "type": "integer" } },
+                "type": key_type_property(),
+            }), &[]),
+        }),
+        json!({
+            "name": "get_key",
+            "description": "Show one API key (without its secret).",
+            "inputSchema": schema(json!({
+                "id": { "type": "string" },
+                "type": key_type_property(),
+            }), &["id", "type"]),
+        }),
+        json!({
+            "name": "key_usage",
+            "description": "When a key last changed and last appeared in an ingest error. New Relic does not meter use per key, so this is not when the key was last used.",
+            "inputSchema": schema(json!({
+                "id": { "type": "string" },
+                "account_id": { "type": "integer" },
+                "since_days": { "type": "integer", "default": 30 },
+            }), &["id", "account_id"]),
+        }),
+    ];
+    if allow_mutations {
+        tools.extend([
+            json!({
+                "name": "create_key",
+                "description": "Create an API key. The secret is not returned.",
+                "inputSchema": schema(json!({
+                    "type": key_type_property(),
+                    "account_id": { "type": "integer" },
+                    "name": { "type": "string" },
+                    "notes": { "type": "string" },
+                    "ingest_type": { "type": "string", "enum": ["LICENSE", "BROWSER"] },
+                    "user_id": { "type": "integer" },
+                }), &["type", "account_id", "name"]),
+            }),
+            json!({
+                "name": "rotate_key",
+                "description": "Replace a key with a new one and delete the old key unless keep_old is set. The new secret is not returned.",
+                "inputSchema": schema(json!({
+                    "id": { "type": "string" },
+                    "type": key_type_property(),
+                    "keep_old": { "type": "boolean", "default": false },
+                }), &["id", "type"]),
+            }),
+            json!({
+                "name": "delete_key",
+                "description": "Delete an API key.",
+                "inputSchema": schema(json!({
+                    "id": { "type": "string" },
+                    "type": key_type_property(),
+                }), &["id", "type"]),
+            }),
+        ]);
+    }
+    tools
+}
+
+/// Strip key secrets and anything that looks like one before it reaches the assistant.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("key");
+            map.values_mut().for_each(redact);
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::String(s) if history::looks_like_secret(s) => *s = "<redacted>".to_string(),
+        _ => {}
+    }
+}
+
+fn string_arg(args: &Value, name: &str) -> Result<String, String> {
+    args[name]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("Missing string argument '{}'", name))
+}
+
+fn key_type_arg(args: &Value) -> Result<String, String> {
+    match string_arg(args, "type")?.to_uppercase().as_str() {
+        t @ ("INGEST" | "USER") => Ok(t.to_string()),
+        other => Err(format!("Unsupported key type '{}'", other)),
+    }
+}
+
+fn success(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn failure(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+impl Server {
+    pub fn new(
+        client: NewRelicClient,
+        default_account_ids: Vec<i64>,
+        allow_mutations: bool,
+    ) -> Self {
+        Self {
+            client,
+            default_account_ids,
+            allow_mutations,
+        }
+    }
+
+    async fn call_tool(&self, name: &str, args: &Value) -> anyhow::Result<Value> {
+        let arg_error = |e: String| anyhow::anyhow!(e);
+        let mutation = matches!(name, "create_key" | "rotate_key" | "delete_key");
+        if mutation && !self.allow_mutations {
+            return Err(anyhow::anyhow!(
+                "Tool '{}' is disabled; start the server with --allow-mutations",
+                name
+            ));
+        }
+
+        let result = match name {
+            "list_keys" => {
+                let account_ids: Vec<i64> = match args["account_ids"].as_array() {
+                    Some(ids) => ids.iter().filter_map(Value::as_i64).collect(),
+                    None => self.default_account_ids.clone(),
+                };
+                if account_ids.is_empty() {
+                    return Err(anyhow::anyhow!("account_ids is required"));
+                }
+                let key_type = args.get("type").map(|_| key_type_arg(args)).transpose();
+                let types = match key_type.map_err(arg_error)? {
+                    Some(t) => vec![t],
+                    None => vec!["INGEST".to_string(), "USER".to_string()],
+                };
+                let types: Vec<&str> = types.iter().map(String::as_str).collect();
+                serde_json::to_value(inventory::fetch(&self.client, &account_ids, &types).await?)?
+            }
+            "get_key" => {
+                let id = string_arg(args, "id").map_err(arg_error)?;
+                let key_type = key_type_arg(args).map_err(arg_error)?;
+                serde_json::to_value(inventory::get(&self.client, &id, &key_type).await?)?
+            }
+            "key_usage" => {
+                let id = string_arg(args, "id").map_err(arg_error)?;
+                let account_id = args["account_id"]
+                    .as_i64()
+                    .ok_or_else(|| anyhow::anyhow!("Missing integer argument 'account_id'"))?;
+                let since_days = args["since_days"].as_u64().unwrap_or(30) as u32;
+                let signals = usage::signals(&self.client, account_id, &id, since_days).await?;
+                json!({
+                    "last_activity": usage::last_activity(&signals),
+                    "signals": signals.iter().map(|s| json!({
+                        "label": s.label,
+                        "source": s.source,
+                        "description": s.description,
+                        "count": s.count,
+                        "last_seen": s.last_seen,
+                    })).collect::<Vec<_>>(),
+                })
+            }
+            "create_key" => {
+                let spec = NewKey {
+                    key_type: key_type_arg(args).map_err(arg_error)?,
+                    account_id: args["account_id"]
+                        .as_i64()
+                        .ok_or_else(|| anyhow::anyhow!("Missing integer argument 'account_id'"))?,
+                    name: string_arg(args, "name").map_err(arg_error)?,
+                    notes: args["notes"].as_str().map(str::to_string),
+                    ingest_type: args["ingest_type"].as_str().map(str::to_string),
+                    user_id: args["user_id"].as_i64(),
+                };
+                serde_json::to_value(inventory::create(&self.client, &spec).await?)?
+            }
+            "rotate_key" => {
+                let id = string_arg(args, "id").map_err(arg_error)?;
+                let key_type = key_type_arg(args).map_err(arg_error)?;
+                let keep_old = args["keep_old"].as_bool().unwrap_or(false);
+                serde_json::to_value(
+                    rotation::rotate(&self.client, &id, &key_type, keep_old).await?,
+                )?
+            }
+            "delete_key" => {
+                let ids = vec![string_arg(args, "id").map_err(arg_error)?];
+                let outcome = match key_type_arg(args).map_err(arg_error)?.as_str() {
+                    "USER" => inventory::delete_keys(&self.client, &[], &ids).await?,
+                    _ => inventory::delete_keys(&self.client, &ids, &[]).await?,
+                };
+                json!({ "deleted": outcome.deleted, "errors": outcome.errors })
+            }
+            other => return Err(anyhow::anyhow!("Unknown tool '{}'", other)),
+        };
+        Ok(result)
+    }
+
+    /// Handle one JSON-RPC message; notifications get no response.
+    pub async fn handle(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        let response = match method {
+            "initialize" => success(
+                &id,
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": {
+                        "name": "newrelic-apikeys-cli",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            ),
+            "ping" => success(&id, json!({})),
+            "tools/list" => success(&id, json!({ "tools": tools(self.allow_mutations) })),
+            "tools/call" => {
+                let Some(name) = params["name"].as_str() else {
+                    return Some(failure(&id, INVALID_PARAMS, "Missing tool name"));
+                };
+                let args = params.get("arguments").cloned().unwrap_or(json!({}));
+                let (mut content, is_error) = match self.call_tool(name, &args).await {
+                    Ok(result) => (result, false),
+                    Err(e) => (json!({ "error": e.to_string() }), true),
+                };
+                redact(&mut content);
+                success(
+                    &id,
+                    json!({
+                        "content": [{
+                            "type": "text",
+                            "text": serde_json::to_string_pretty(&content).unwrap_or_default(),
+                        }],
+                        "isError": is_error,
+                    }),
+                )
+            }
+            other => failure(
+                &id,
+                METHOD_NOT_FOUND,
+                &format!("Method '{}' not found", other),
+            ),
+        };
+        Some(response)
+    }
+
+    /// Serve requests from stdin until it is closed; diagnostics go to stderr.
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        eprintln!("newrelic-apikeys-cli MCP server ready on stdio");
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(&message).await,
+                Err(e) => Some(failure(&Value::Null, PARSE_ERROR, &e.to_string())),
+            };
+            if let Some(response) = response {
+                stdout
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await?;
+                stdout.flush().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(allow_mutations: bool) -> Server {
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .endpoint("http://127.0.0.1:1")
+            .build()
+            .unwrap();
+        Server::new(client, vec![], allow_mutations)
+    }
+
+    fn tool_names(response: &Value) -> Vec<&str> {
+        response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_mutations_are_gated() {
+        let list = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"});
+        let read_only = server(false).handle(&list).await.unwrap();
+        assert!(!tool_names(&read_only).contains(&"delete_key"));
+        let mutable = server(true).handle(&list).await.unwrap();
+        assert!(tool_names(&mutable).contains(&"delete_key"));
+
+        let call = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call",
+            "params": {"name": "delete_key", "arguments": {"id": "x", "type": "INGEST"}}});
+        let response = server(false).handle(&call).await.unwrap();
+        assert_eq!(response["result"]["isError"], true);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_messages() {
+        let init = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        let response = server(false).handle(&init).await.unwrap();
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        let note = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(server(false).handle(&note).await.is_none());
+
+        let unknown = json!({"jsonrpc": "2.0", "id": 3, "method": "resources/list"});
+        let response = server(false).handle(&unknown).await.unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_redact_removes_secrets() {
+        let mut value = json!({
+            "new_key": {"id": "a", "key": "NRAK-SECRET"},
+            "notes": ["copied from NRII-LEAKED", "fine"],
+        });
+        redact(&mut value);
+        assert!(value["new_key"].get("key").is_none());
+        assert_eq!(value["notes"][0], "<redacted>");
+        assert_eq!(value["notes"][1], "fine");
+    }
+}
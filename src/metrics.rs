@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+
+use crate::inventory::ApiKey;
+
+/// Key gauges from the most recent inventory collection.
+#[derive(Default)]
+pub struct Snapshot {
+    /// Keys per (account, type)
+    keys: BTreeMap<(i64, String), u64>,
+    /// Keys older than `policies.max_key_age_days` per (account, type)
+    over_policy: BTreeMap<(i64, String), u64>,
+    /// Creation time of the newest key per account; every rotation creates a new key
+    last_rotation: BTreeMap<i64, i64>,
+    collected_at: Option<DateTime<Utc>>,
+    collections: u64,
+    collection_errors: u64,
+}
+
+impl Snapshot {
+    pub fn update(&mut self, keys: &[ApiKey], now: DateTime<Utc>, max_key_age_days: Option<u32>) {
+        self.keys.clear();
+        self.over_policy.clear();
+        self.last_rotation.clear();
+        for key in keys {
+            let account = key.account_id.unwrap_or_default();
+            let labels = (account, key.key_type.clone().unwrap_or_default());
+            *self.keys.entry(labels.clone()).or_default() += 1;
+            if let (Some(age), Some(max)) = (key.age_days(now), max_key_age_days) {
+                if age > i64::from(max) {
+                    *self.over_policy.entry(labels).or_default() += 1;
+                }
+            }
+            if let Some(created_at) = key.created_at {
+                let latest = self.last_rotation.entry(account).or_default();
+                *latest = (*latest).max(created_at);
+            }
+        }
+        self.collected_at = Some(now);
+        self.collections += 1;
+    }
+
+    pub fn record_error(&mut self) {
+        self.collection_errors += 1;
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Prometheus text exposition of the snapshot plus CLI operation counts from the history log.
+pub fn render(snapshot: &Snapshot, operations: &BTreeMap<(String, bool), u64>) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "newrelic_apikeys_keys_total",
+        "gauge",
+        "API keys by account and type.",
+    );
+    for ((account, key_type), count) in &snapshot.keys {
+        let _ = writeln!(
+            out,
+            "newrelic_apikeys_keys_total{{account_id=\"{}\",type=\"{}\"}} {}",
+            account,
+            label(key_type),
+            count
+        );
+    }
+
+    header(
+        &mut out,
+        "newrelic_apikeys_keys_older_than_policy",
+        "gauge",
+        "API keys older than policies.max_key_age_days.",
+    );
+    for labels @ (account, key_type) in snapshot.keys.keys() {
+        let _ = writeln!(
+            out,
+            "newrelic_apikeys_keys_older_than_policy{{account_id=\"{}\",type=\"{}\"}} {}",
+            account,
+            label(key_type),
+            snapshot.over_policy.get(labels).copied().unwrap_or(0)
+        );
+    }
+
+    header(
+        &mut out,
+        "newrelic_apikeys_last_rotation_timestamp_seconds",
+        "gauge",
+        "Creation time of the newest key in the account.",
+    );
+    for (account, created_at) in &snapshot.last_rotation {
+        let _ = writeln!(
+            out,
+            "newrelic_apikeys_last_rotation_timestamp_seconds{{account_id=\"{}\"}} {}",
+            account, created_at
+        );
+    }
+
+    header(
+        &mut out,
+        "newrelic_apikeys_last_collection_timestamp_seconds",
+        "gauge",
+        "When the key inventory was last collected successfully.",
+    );
+    if let Some(collected_at) = snapshot.collected_at {
+        let _ = writeln!(
+            out,
+            "newrelic_apikeys_last_collection_timestamp_seconds {}",
+            collected_at.timestamp()
+        );
+    }
+
+    header(
+        &mut out,
+        "newrelic_apikeys_collections_total",
+        "counter",
+        "Inventory collections by result.",
+    );
+    let _ = writeln!(
+        out,
+        "newrelic_apikeys_collections_total{{result=\"success\"}} {}",
+        snapshot.collections
+    );
+    let _ = writeln!(
+        out,
+        "newrelic_apikeys_collections_total{{result=\"failure\"}} {}",
+        snapshot.collection_errors
+    );
+
+    header(
+        &mut out,
+        "newrelic_apikeys_cli_operations_total",
+        "counter",
+        "CLI invocations recorded in the history log by command and result.",
+    );
+    for ((command, success), count) in operations {
+        let _ = writeln!(
+            out,
+            "newrelic_apikeys_cli_operations_total{{command=\"{}\",result=\"{}\"}} {}",
+            label(command),
+            if *success { "success" } else { "failure" },
+            count
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_snapshot() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let keys = [
+            ApiKey::fixture(serde_json::json!({"id": "a", "createdAt": 1_600_000_000})),
+            ApiKey::fixture(serde_json::json!({"id": "b", "createdAt": 1_699_000_000})),
+            ApiKey::fixture(
+                serde_json::json!({"id": "c", "type": "USER", "createdAt": 1_699_500_000, "accountId": 2}),
+            ),
+        ];
+        let mut snapshot = Snapshot::default();
+        snapshot.update(&keys, now, Some(90));
+        snapshot.record_error();
+
+        let mut operations = BTreeMap::new();
+        operations.insert(("query".to_string(), true), 4);
+
+        let text = render(&snapshot, &operations);
+        assert!(text.contains("newrelic_apikeys_keys_total{account_id=\"1\",type=\"INGEST\"} 2\n"));
+        assert!(text.contains(
+            "newrelic_apikeys_keys_older_than_policy{account_id=\"1\",type=\"INGEST\"} 1\n"
+        ));
+        assert!(text.contains(
+            "newrelic_apikeys_keys_older_than_policy{account_id=\"2\",type=\"USER\"} 0\n"
+        ));
+        assert!(text.contains(
+            "newrelic_apikeys_last_rotation_timestamp_seconds{account_id=\"1\"} 1699000000\n"
+        ));
+        assert!(text.contains("newrelic_apikeys_collections_total{result=\"failure\"} 1\n"));
+        assert!(text.contains(
+            "newrelic_apikeys_cli_operations_total{command=\"query\",result=\"success\"} 4\n"
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+//! Request/response interceptors around the [`Transport`].
+//!
+//! A [`Middleware`] sees every HTTP attempt and decides whether, and how, to pass it on via
+//! [`Next`]: logging and metrics wrap the call, auth injection edits headers first, and a cache
+//! can answer without calling `next` at all. The client's own retry, logging and statistics are
+//! middleware too.
+//!
+//! Layers registered with [`NewRelicClientBuilder::middleware`](crate::NewRelicClientBuilder::middleware)
+//! run outermost, in registration order, so they see one call per query even when it is
+//! retried underneath.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::rate_limit::RateLimit;
+use crate::transport::{
+    HttpRequest, HttpResponse, MaybeSync, SleepFuture, Transport, TransportFuture,
+};
+use crate::{RetryPolicy, REQUEST_ID_HEADER};
+
+/// HTTP statuses that mean the request was not processed and may be sent again.
+const RETRY_STATUSES: [u16; 4] = [429, 502, 503, 504];
+/// The subset that is safe to retry for mutations, which must never run twice.
+const MUTATION_RETRY_STATUSES: [u16; 2] = [429, 503];
+
+pub trait Middleware: MaybeSync {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a>;
+}
+
+/// The rest of the chain: the remaining middleware, then the transport.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    transport: &'a dyn Transport,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(middleware: &'a [Arc<dyn Middleware>], transport: &'a dyn Transport) -> Self {
+        Self {
+            middleware,
+            transport,
+        }
+    }
+
+    pub fn run(self, request: HttpRequest) -> TransportFuture<'a> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(
+                request,
+                Next {
+                    middleware: rest,
+                    transport: self.transport,
+                },
+            ),
+            None => self.transport.send(request),
+        }
+    }
+
+    /// Wait using the transport's timer, e.g. between retries.
+    pub fn sleep(self, duration: Duration) -> SleepFuture<'a> {
+        self.transport.sleep(duration)
+    }
+}
+
+/// Retries connection errors and 429/502/503/504 for queries; mutations only on 429 and 503,
+/// where NerdGraph did not process them. A `Retry-After` from the server replaces the backoff.
+pub struct Retry(pub RetryPolicy);
+
+impl Middleware for Retry {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let mutation = request.is_mutation();
+            let mut attempt = 0;
+            loop {
+                let result = next.run(request.clone()).await;
+                let retryable = match &result {
+                    Err(_) => !mutation,
+                    Ok(response) if mutation => MUTATION_RETRY_STATUSES.contains(&response.status),
+                    Ok(response) => RETRY_STATUSES.contains(&response.status),
+                };
+                if !retryable || attempt >= self.0.max_retries {
+                    return result;
+                }
+                let retry_after = result
+                    .as_ref()
+                    .ok()
+                    .and_then(|response| RateLimit::from_response(response).retry_after);
+                let wait = match retry_after {
+                    Some(wait) if wait > self.0.max_retry_after => return result,
+                    Some(wait) => wait,
+                    None => self.0.backoff(attempt),
+                };
+                next.sleep(wait).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+/// Logs each attempt's request ID and outcome to stderr.
+pub struct Logging;
+
+impl Middleware for Logging {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let request_id = request.header(REQUEST_ID_HEADER).unwrap_or("-").to_string();
+            let result = next.run(request).await;
+            match &result {
+                Ok(response) => {
+                    eprintln!("NerdGraph request {}: HTTP {}", request_id, response.status)
+                }
+                Err(e) => eprintln!("NerdGraph request {}: {}", request_id, e),
+            }
+            result
+        })
+    }
+}
+
+/// Counts the requests passing through it and how many failed; clones share the counters.
+#[derive(Clone, Default)]
+pub struct Stats(Arc<Counters>);
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Stats {
+    pub fn requests(&self) -> u64 {
+        self.0.requests.load(Ordering::Relaxed)
+    }
+
+    /// Requests that failed to connect or returned a non-2xx status.
+    pub fn failures(&self) -> u64 {
+        self.0.failures.load(Ordering::Relaxed)
+    }
+}
+
+impl Middleware for Stats {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let result = next.run(request).await;
+            self.0.requests.fetch_add(1, Ordering::Relaxed);
+            let failed = match &result {
+                Ok(HttpResponse { status, .. }) => !(200..300).contains(status),
+                Err(_) => true,
+            };
+            if failed {
+                self.0.failures.fetch_add(1, Ordering::Relaxed);
+            }
+            result
+        })
+    }
+}
+
+/// Refuses every mutation before it is sent, so a client can be handed to code that must only
+/// read keys.
+pub struct ReadOnly;
+
+impl Middleware for ReadOnly {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        if request.is_mutation() {
+            return Box::pin(async {
+                Err(anyhow::anyhow!(
+                    "Changes to keys are not possible in read-only mode"
+                ))
+            });
+        }
+        next.run(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NewRelicClient;
+
+    /// Adds a header, like an auth-injecting middleware would.
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn handle<'a>(&'a self, mut request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+            request
+                .headers
+                .push(("X-Tag".to_string(), self.0.to_string()));
+            next.run(request)
+        }
+    }
+
+    /// Answers every request itself, like a cache hit.
+    struct Canned;
+
+    impl Middleware for Canned {
+        fn handle<'a>(&'a self, _request: HttpRequest, _next: Next<'a>) -> TransportFuture<'a> {
+            Box::pin(async {
+                Ok(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: br#"{"data":{"cached":true}}"#.to_vec(),
+                })
+            })
+        }
+    }
+
+    fn echo_tags(request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let tags: Vec<&str> = request
+            .headers
+            .iter()
+            .filter(|(name, _)| name == "X-Tag")
+            .map(|(_, value)| value.as_str())
+            .collect();
+        Ok(HttpResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: serde_json::to_vec(&serde_json::json!({ "data": { "tags": tags } }))?,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_middleware_runs_in_registration_order() {
+        let stats = Stats::default();
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(Tag("first"))
+            .middleware(Tag("second"))
+            .middleware(stats.clone())
+            .transport(echo_tags)
+            .build()
+            .unwrap();
+
+        let data = client
+            .execute_query("{ actor { user { id } } }", None)
+            .await
+            .unwrap();
+        assert_eq!(data["tags"], serde_json::json!(["first", "second"]));
+        assert_eq!(stats.requests(), 1);
+        assert_eq!(stats.failures(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_can_short_circuit() {
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(Canned)
+            .transport(|_request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                panic!("the transport should not be called")
+            })
+            .build()
+            .unwrap();
+
+        let data = client
+            .execute_query("{ actor { user { id } } }", None)
+            .await
+            .unwrap();
+        assert_eq!(data["cached"], true);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_refuses_mutations() {
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(ReadOnly)
+            .transport(echo_tags)
+            .build()
+            .unwrap();
+
+        assert!(client
+            .execute_query("{ actor { user { id } } }", None)
+            .await
+            .is_ok());
+        let err = client
+            .execute_query(
+                "mutation { apiAccessDeleteKeys(keys: {}) { errors { message } } }",
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+
+use crate::{NewRelicClient, Variables};
+
+pub(crate) const QUERY: &str = r#"
+    query($accountId: Int!, $nrql: Nrql!) {
+        actor {
+            account(id: $accountId) {
+                nrql(query: $nrql) {
+                    results
+                }
+            }
+        }
+    }"#;
+
+/// Run an NRQL query against one account and return its result rows.
+pub async fn query(
+    client: &NewRelicClient,
+    account_id: i64,
+    nrql: &str,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let variables = Variables::new()
+        .int("accountId", account_id)
+        .string("nrql", nrql);
+    let result = client.execute_query(QUERY, Some(variables)).await?;
+    Ok(result["actor"]["account"]["nrql"]["results"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Quote a value as an NRQL string literal.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Convert an NRQL timestamp (epoch milliseconds) into a UTC time.
+pub fn timestamp(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(value.as_f64()? as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_escapes_literals() {
+        assert_eq!(quote("abc"), "'abc'");
+        assert_eq!(quote("it's"), "'it\\'s'");
+        assert_eq!(quote("a\\b"), "'a\\\\b'");
+    }
+
+    #[test]
+    fn test_timestamp_from_epoch_millis() {
+        let ts = timestamp(&serde_json::json!(1700000000000_i64)).unwrap();
+        assert_eq!(ts.to_rfc3339(), "2023-11-14T22:13:20+00:00");
+        assert!(timestamp(&serde_json::Value::Null).is_none());
+    }
+}
@@ -0,0 +1,259 @@
+//! `onboard customer`: the standard keys of a managed customer's account, created in one
+//! operation. Every key carries the labels `customer:<name>` and `owner:<owner>` in its notes,
+//! so `--selector customer=<name>` finds them again; the secrets go to the secret sink and a
+//! JSON report records what was done.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::{self, KeyTemplate, Onboarding};
+use crate::environment::ReportEntry;
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::prompt::Confirmation;
+use crate::sink::Secrets;
+use crate::NewRelicClient;
+
+/// The notes label of the keys of `customer`.
+pub fn label(customer: &str) -> String {
+    format!("customer:{}", customer)
+}
+
+/// Customer names and owners become labels, so they must be single words.
+pub fn validate_label_value(kind: &str, value: &str) -> anyhow::Result<()> {
+    let valid = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        anyhow::bail!(
+            "Invalid {} '{}': use letters, digits, '-', '_' and '.'",
+            kind,
+            value
+        );
+    }
+    Ok(())
+}
+
+fn default_templates() -> Vec<KeyTemplate> {
+    ["LICENSE", "BROWSER"]
+        .into_iter()
+        .map(|ingest_type| KeyTemplate {
+            ingest_type: ingest_type.to_string(),
+            name: "{customer} {ingest_type} ({account_id})".to_string(),
+            notes: None,
+        })
+        .collect()
+}
+
+fn render(template: &str, customer: &str, account_id: i64, ingest_type: &str) -> String {
+    template
+        .replace("{customer}", customer)
+        .replace("{account_id}", &account_id.to_string())
+        .replace("{ingest_type}", &ingest_type.to_lowercase())
+}
+
+/// The keys `customer` still lacks in `account_id`, and those it already has: a key counts as
+/// created when it carries the customer's label and has the template's name.
+pub fn plan<'k>(
+    customer: &str,
+    owner: &str,
+    onboarding: &Onboarding,
+    account_id: i64,
+    existing: &'k [ApiKey],
+) -> anyhow::Result<(Vec<NewKey>, Vec<&'k ApiKey>)> {
+    let templates = if onboarding.keys.is_empty() {
+        default_templates()
+    } else {
+        onboarding.keys.clone()
+    };
+    let labels = format!("{} owner:{}", label(customer), owner);
+    let mut planned = Vec::new();
+    let mut present = Vec::new();
+    for template in &templates {
+        let ingest_type = template.ingest_type.to_uppercase();
+        if !matches!(ingest_type.as_str(), "LICENSE" | "BROWSER") {
+            anyhow::bail!(
+                "Unsupported ingest type '{}' in [onboarding] (expected LICENSE or BROWSER)",
+                template.ingest_type
+            );
+        }
+        let name = render(&template.name, customer, account_id, &ingest_type);
+        let found = existing.iter().find(|key| {
+            key.account_id == Some(account_id)
+                && key.name.as_deref() == Some(name.as_str())
+                && key.notes.as_deref().is_some_and(|notes| {
+                    notes.split_whitespace().any(|word| word == label(customer))
+                })
+        });
+        if let Some(key) = found {
+            present.push(key);
+            continue;
+        }
+        let notes = match &template.notes {
+            Some(notes) => format!(
+                "{} {}",
+                render(notes, customer, account_id, &ingest_type),
+                labels
+            ),
+            None => labels.clone(),
+        };
+        planned.push(NewKey {
+            key_type: "INGEST".to_string(),
+            account_id,
+            name,
+            notes: Some(notes),
+            ingest_type: Some(ingest_type),
+            user_id: None,
+        });
+    }
+    Ok((planned, present))
+}
+
+/// Options for [`customer`].
+pub struct Options<'a> {
+    pub dry_run: bool,
+    pub confirmation: Confirmation,
+    pub report_path: &'a Path,
+    /// The sink the secrets went to, for the report
+    pub secret_sink: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    customer: &'a str,
+    owner: &'a str,
+    account_id: i64,
+    generated_at: DateTime<Utc>,
+    created: Vec<ReportEntry>,
+    /// Keys of the standard set the account already had
+    existing: Vec<ReportEntry>,
+    secret_sink: Option<&'a str>,
+    errors: Vec<String>,
+}
+
+/// Create the standard keys `customer` lacks in `account_id`, hand their secrets to `secrets`
+/// and write the onboarding report. Keys created before a failure are still published and
+/// reported, so that no secret is lost.
+pub async fn customer(
+    client: &NewRelicClient,
+    customer: &str,
+    owner: &str,
+    onboarding: &Onboarding,
+    account_id: i64,
+    options: Options<'_>,
+    secrets: &Secrets,
+) -> anyhow::Result<()> {
+    let existing = inventory::fetch(client, &[account_id], &["INGEST"]).await?;
+    let (planned, present) = plan(customer, owner, onboarding, account_id, &existing)?;
+    eprintln!(
+        "{} key(s) to create for customer {} in account {} ({} already there):",
+        planned.len(),
+        customer,
+        account_id,
+        present.len()
+    );
+    for key in &planned {
+        eprintln!(
+            "  {} {}",
+            key.ingest_type.as_deref().unwrap_or_default(),
+            key.name
+        );
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+    if !planned.is_empty() {
+        secrets.check()?;
+        if !options
+            .confirmation
+            .confirm(&format!("Create {} key(s)?", planned.len()))?
+        {
+            println!("Nothing created");
+            return Ok(());
+        }
+    }
+
+    let mut created = Vec::new();
+    let mut failure = None;
+    for spec in &planned {
+        match inventory::create(client, spec).await {
+            Ok(key) => created.push(key),
+            Err(e) => {
+                failure = Some(e);
+                break;
+            }
+        }
+    }
+    let created: Vec<&ApiKey> = created.iter().collect();
+    if !created.is_empty() {
+        secrets
+            .publish(&format!("Onboarded {}", customer), &created)
+            .await?;
+        secrets.print(&created, &created)?;
+    }
+
+    let report = Report {
+        customer,
+        owner,
+        account_id,
+        generated_at: Utc::now(),
+        created: created.iter().map(|key| ReportEntry::from(*key)).collect(),
+        existing: present.into_iter().map(ReportEntry::from).collect(),
+        secret_sink: options.secret_sink,
+        errors: failure.iter().map(|e| format!("{:#}", e)).collect(),
+    };
+    config::write_private(options.report_path, &serde_json::to_string_pretty(&report)?)?;
+    eprintln!("Report written to {}", options.report_path.display());
+
+    match failure {
+        Some(e) => Err(e.context(format!(
+            "Onboarding {} stopped after {} of {} key(s)",
+            customer,
+            created.len(),
+            planned.len()
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_labels_keys_and_skips_existing_ones() {
+        let existing = vec![ApiKey::fixture(serde_json::json!({
+            "id": "K1", "name": "acme license (7)", "notes": "customer:acme owner:msp",
+            "type": "INGEST", "createdAt": 1, "accountId": 7, "ingestType": "LICENSE"
+        }))];
+        let (planned, present) = plan("acme", "noc", &Onboarding::default(), 7, &existing).unwrap();
+        assert_eq!(present.len(), 1);
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].name, "acme browser (7)");
+        assert_eq!(planned[0].notes.as_deref(), Some("customer:acme owner:noc"));
+
+        let onboarding = Onboarding {
+            keys: vec![KeyTemplate {
+                ingest_type: "license".to_string(),
+                name: "{customer}-{ingest_type}".to_string(),
+                notes: Some("managed for {customer}".to_string()),
+            }],
+            ..Onboarding::default()
+        };
+        let (planned, _) = plan("globex", "noc", &onboarding, 7, &[]).unwrap();
+        assert_eq!(planned[0].name, "globex-license");
+        assert_eq!(
+            planned[0].notes.as_deref(),
+            Some("managed for globex customer:globex owner:noc")
+        );
+    }
+
+    #[test]
+    fn test_validate_label_value() {
+        assert!(validate_label_value("customer", "acme-corp_1.eu").is_ok());
+        assert!(validate_label_value("customer", "acme corp").is_err());
+        assert!(validate_label_value("owner", "").is_err());
+    }
+}
@@ -0,0 +1,479 @@
+use std::fmt::Write;
+#[cfg(feature = "templates")]
+use std::sync::Arc;
+
+/// The formats `Format::parse` accepts, for error messages.
+#[cfg(all(feature = "yaml", feature = "templates"))]
+pub const EXPECTED: &str = "json, ndjson, psobject, table, csv, yaml or template";
+#[cfg(all(feature = "yaml", not(feature = "templates")))]
+pub const EXPECTED: &str = "json, ndjson, psobject, table, csv or yaml";
+#[cfg(all(not(feature = "yaml"), feature = "templates"))]
+pub const EXPECTED: &str = "json, ndjson, psobject, table, csv or template";
+#[cfg(all(not(feature = "yaml"), not(feature = "templates")))]
+pub const EXPECTED: &str = "json, ndjson, psobject, table or csv";
+
+/// Output formats accepted by `--format` / the `format` config setting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    Json,
+    /// One JSON record per line, see [`ndjson`]
+    Ndjson,
+    /// JSON for PowerShell's `ConvertFrom-Json`, see [`psobject`]
+    PsObject,
+    Table,
+    Csv,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "templates")]
+    Template(Template),
+}
+
+/// A Handlebars template rendered once per record, e.g. `{{id}} {{name}}`. Values are
+/// inserted as they are, without HTML escaping.
+#[cfg(feature = "templates")]
+#[derive(Clone, Debug)]
+pub struct Template {
+    source: Arc<str>,
+    registry: Arc<handlebars::Handlebars<'static>>,
+}
+
+#[cfg(feature = "templates")]
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+#[cfg(feature = "templates")]
+impl Template {
+    pub fn new(source: &str) -> anyhow::Result<Self> {
+        let mut registry = handlebars::Handlebars::new();
+        registry.register_escape_fn(handlebars::no_escape);
+        registry
+            .register_template_string("record", source)
+            .map_err(|e| anyhow::anyhow!("Invalid template: {}", e))?;
+        Ok(Self {
+            source: source.into(),
+            registry: Arc::new(registry),
+        })
+    }
+
+    /// Each record rendered on its own line, or block for templates that end in a newline.
+    pub fn render<T: serde::Serialize>(&self, records: &[T]) -> anyhow::Result<String> {
+        let mut out = String::new();
+        for record in records {
+            let rendered = self
+                .registry
+                .render("record", record)
+                .map_err(|e| anyhow::anyhow!("Unable to render template: {}", e))?;
+            out.push_str(&rendered);
+            if !rendered.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Format {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "ndjson" | "jsonl" => Ok(Format::Ndjson),
+            "psobject" => Ok(Format::PsObject),
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Ok(Format::Yaml),
+            #[cfg(not(feature = "yaml"))]
+            "yaml" | "yml" => Err(anyhow::anyhow!(
+                "YAML output requires building with the `yaml` feature"
+            )),
+            #[cfg(feature = "templates")]
+            "template" => Err(anyhow::anyhow!(
+                "--format template needs a template: pass --template"
+            )),
+            #[cfg(not(feature = "templates"))]
+            "template" => Err(anyhow::anyhow!(
+                "Template output requires building with the `templates` feature"
+            )),
+            other => Err(anyhow::anyhow!(
+                "Unsupported output format '{}' (expected {})",
+                other,
+                EXPECTED
+            )),
+        }
+    }
+}
+
+/// `value` with the keys of every object, nested ones included, in alphabetical order. The
+/// machine formats render through this, so that field order never depends on how a value was
+/// built: a struct's declaration order, a `HashMap`'s iteration order, or whether some
+/// dependency turned on serde_json's `preserve_order`.
+fn canonical<T: serde::Serialize + ?Sized>(value: &T) -> anyhow::Result<serde_json::Value> {
+    let mut value = serde_json::to_value(value)?;
+    value.sort_all_objects();
+    Ok(value)
+}
+
+/// Pretty-printed JSON, keys in alphabetical order.
+pub fn json<T: serde::Serialize + ?Sized>(value: &T) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(&canonical(value)?)?)
+}
+
+/// Serialize a value as a YAML document, keys in alphabetical order.
+#[cfg(feature = "yaml")]
+pub fn yaml<T: serde::Serialize + ?Sized>(value: &T) -> anyhow::Result<String> {
+    Ok(serde_yaml::to_string(&canonical(value)?)?)
+}
+
+/// JSON that `ConvertFrom-Json` reads the same way in Windows PowerShell 5.1 and PowerShell 7:
+/// always an array, on a single line, and ASCII only. Windows PowerShell decodes the output of
+/// native commands with the console code page, which garbles UTF-8 names; `\uXXXX` escapes
+/// survive any code page. Keys are in alphabetical order, as in [`json`].
+pub fn psobject<T: serde::Serialize + ?Sized>(value: &T) -> anyhow::Result<String> {
+    let value = match canonical(value)? {
+        array @ serde_json::Value::Array(_) => array,
+        other => serde_json::Value::Array(vec![other]),
+    };
+    let mut out = String::new();
+    // Outside strings JSON is ASCII already, so every other character is inside one.
+    for c in serde_json::to_string(&value)?.chars() {
+        if c.is_ascii() {
+            out.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                let _ = write!(out, "\\u{:04x}", unit);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Newline-delimited JSON: each element of an array on its own line, or a single value on one
+/// line, keys in alphabetical order. Streams into `jq -c`, log shippers and `while read` loops.
+pub fn ndjson<T: serde::Serialize + ?Sized>(value: &T) -> anyhow::Result<String> {
+    let records = match canonical(value)? {
+        serde_json::Value::Array(records) => records,
+        other => vec![other],
+    };
+    let mut out = String::new();
+    for record in records {
+        out.push_str(&serde_json::to_string(&record)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// A command's result, rendered in every [`Format`]. Implementors describe the table once,
+/// with [`headers`](Renderer::headers) and [`rows`](Renderer::rows), and what the machine
+/// formats serialize with [`data`](Renderer::data); a new format is one more method here
+/// rather than an arm in every command.
+pub trait Renderer {
+    /// What JSON, NDJSON, psobject, YAML and templates render: usually the array of records.
+    fn data(&self) -> anyhow::Result<serde_json::Value>;
+
+    /// Table and CSV column names.
+    fn headers(&self) -> Vec<String>;
+
+    /// Table and CSV cells, one `Vec` per row in [`headers`](Renderer::headers) order.
+    fn rows(&self) -> Vec<Vec<String>>;
+
+    /// The sentence a table shows instead of its headers when there are no rows, e.g.
+    /// "No protected keys".
+    fn empty_message(&self) -> Option<String> {
+        None
+    }
+
+    fn render_table(&self) -> String {
+        let rows = self.rows();
+        match self.empty_message() {
+            Some(message) if rows.is_empty() => message + "\n",
+            _ => table(
+                &self
+                    .headers()
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>(),
+                &rows,
+            ),
+        }
+    }
+
+    fn render_csv(&self) -> String {
+        csv(
+            &self
+                .headers()
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            &self.rows(),
+        )
+    }
+
+    fn render_json(&self) -> anyhow::Result<String> {
+        Ok(json(&self.data()?)? + "\n")
+    }
+
+    fn render_ndjson(&self) -> anyhow::Result<String> {
+        ndjson(&self.data()?)
+    }
+
+    fn render_psobject(&self) -> anyhow::Result<String> {
+        Ok(psobject(&self.data()?)? + "\n")
+    }
+
+    #[cfg(feature = "yaml")]
+    fn render_yaml(&self) -> anyhow::Result<String> {
+        yaml(&self.data()?)
+    }
+
+    #[cfg(feature = "templates")]
+    fn render_template(&self, template: &Template) -> anyhow::Result<String> {
+        match self.data()? {
+            serde_json::Value::Array(records) => template.render(&records),
+            record => template.render(&[record]),
+        }
+    }
+
+    /// The result in `format`, ending in a newline unless it is empty.
+    fn render(&self, format: &Format) -> anyhow::Result<String> {
+        match format {
+            Format::Json => self.render_json(),
+            Format::Ndjson => self.render_ndjson(),
+            Format::PsObject => self.render_psobject(),
+            Format::Table => Ok(self.render_table()),
+            Format::Csv => Ok(self.render_csv()),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => self.render_yaml(),
+            #[cfg(feature = "templates")]
+            Format::Template(template) => self.render_template(template),
+        }
+    }
+}
+
+/// Control characters in `cell` replaced, so that a key name or note cannot send escape
+/// sequences to the terminal or break the table into extra lines.
+fn printable(cell: &str) -> std::borrow::Cow<'_, str> {
+    if cell.chars().any(char::is_control) {
+        cell.chars()
+            .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+            .collect::<String>()
+            .into()
+    } else {
+        cell.into()
+    }
+}
+
+/// Left-aligned columns padded to the widest cell, with a header row.
+pub fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let rows: Vec<Vec<std::borrow::Cow<str>>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| printable(cell)).collect())
+        .collect();
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    let header: Vec<std::borrow::Cow<str>> = headers.iter().map(|h| (*h).into()).collect();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        let _ = writeln!(out, "{}", line.join("  ").trim_end());
+    }
+    out
+}
+
+/// RFC 4180 CSV: fields containing commas, quotes or newlines are quoted.
+pub fn csv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let escape = |field: &str| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}",
+        headers
+            .iter()
+            .map(|h| escape(h))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    for row in rows {
+        let _ = writeln!(
+            out,
+            "{}",
+            row.iter().map(|f| escape(f)).collect::<Vec<_>>().join(",")
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_pads_columns() {
+        let rows = vec![vec!["a".to_string(), "long value".to_string()]];
+        assert_eq!(
+            table(&["NAME", "VALUE"], &rows),
+            "NAME  VALUE\na     long value\n"
+        );
+    }
+
+    #[test]
+    fn test_table_replaces_control_characters() {
+        let rows = vec![vec!["\u{1b}[31mred\nkey".to_string()]];
+        assert_eq!(
+            table(&["NAME"], &rows),
+            "NAME\n\u{fffd}[31mred\u{fffd}key\n"
+        );
+    }
+
+    #[test]
+    fn test_psobject_is_an_ascii_array() {
+        let json = psobject(&serde_json::json!({"name": "caf\u{e9} \u{1f511}"})).unwrap();
+        assert_eq!(json, r#"[{"name":"caf\u00e9 \ud83d\udd11"}]"#);
+        assert_eq!(psobject(&Vec::<i32>::new()).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_machine_formats_sort_keys() {
+        #[derive(serde::Serialize)]
+        struct Record {
+            name: &'static str,
+            id: &'static str,
+            labels: std::collections::HashMap<&'static str, i32>,
+        }
+        let record = Record {
+            name: "ci",
+            id: "K1",
+            labels: [("team", 1), ("env", 2), ("owner", 3)].into(),
+        };
+        assert_eq!(
+            json(&record).unwrap(),
+            "{\n  \"id\": \"K1\",\n  \"labels\": {\n    \"env\": 2,\n    \"owner\": 3,\n    \
+             \"team\": 1\n  },\n  \"name\": \"ci\"\n}"
+        );
+        assert_eq!(
+            psobject(&record).unwrap(),
+            r#"[{"id":"K1","labels":{"env":2,"owner":3,"team":1},"name":"ci"}]"#
+        );
+        #[cfg(feature = "yaml")]
+        assert_eq!(
+            yaml(&record).unwrap(),
+            "id: K1\nlabels:\n  env: 2\n  owner: 3\n  team: 1\nname: ci\n"
+        );
+    }
+
+    #[test]
+    fn test_ndjson_is_one_record_per_line() {
+        let records = serde_json::json!([{"name": "a", "id": 1}, {"id": 2}]);
+        assert_eq!(
+            ndjson(&records).unwrap(),
+            "{\"id\":1,\"name\":\"a\"}\n{\"id\":2}\n"
+        );
+        assert_eq!(
+            ndjson(&serde_json::json!({"id": 3})).unwrap(),
+            "{\"id\":3}\n"
+        );
+        assert_eq!(ndjson(&Vec::<i32>::new()).unwrap(), "");
+    }
+
+    struct Entries(Vec<(&'static str, i32)>);
+
+    impl Renderer for Entries {
+        fn data(&self) -> anyhow::Result<serde_json::Value> {
+            Ok(self
+                .0
+                .iter()
+                .map(|(name, count)| serde_json::json!({"name": name, "count": count}))
+                .collect())
+        }
+
+        fn headers(&self) -> Vec<String> {
+            vec!["NAME".to_string(), "COUNT".to_string()]
+        }
+
+        fn rows(&self) -> Vec<Vec<String>> {
+            self.0
+                .iter()
+                .map(|(name, count)| vec![name.to_string(), count.to_string()])
+                .collect()
+        }
+
+        fn empty_message(&self) -> Option<String> {
+            Some("No entries".to_string())
+        }
+    }
+
+    #[test]
+    fn test_renderer_formats() {
+        let entries = Entries(vec![("a", 1), ("b", 22)]);
+        assert_eq!(
+            entries.render(&Format::Table).unwrap(),
+            "NAME  COUNT\na     1\nb     22\n"
+        );
+        assert_eq!(
+            entries.render(&Format::Csv).unwrap(),
+            "NAME,COUNT\na,1\nb,22\n"
+        );
+        assert_eq!(
+            entries.render(&Format::Ndjson).unwrap(),
+            "{\"count\":1,\"name\":\"a\"}\n{\"count\":22,\"name\":\"b\"}\n"
+        );
+        assert!(entries.render(&Format::Json).unwrap().ends_with("]\n"));
+        assert_eq!(
+            Entries(vec![]).render(&Format::Table).unwrap(),
+            "No entries\n"
+        );
+        assert_eq!(
+            Entries(vec![]).render(&Format::Csv).unwrap(),
+            "NAME,COUNT\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_quotes_special_fields() {
+        let rows = vec![vec!["plain".to_string(), "a, \"b\"".to_string()]];
+        assert_eq!(csv(&["x", "y"], &rows), "x,y\nplain,\"a, \"\"b\"\"\"\n");
+    }
+
+    #[cfg(feature = "templates")]
+    #[test]
+    fn test_template_renders_each_record() {
+        let template = Template::new("{{id}} {{name}}<{{missing}}>").unwrap();
+        let records = [
+            serde_json::json!({"id": "a", "name": "ci & cd"}),
+            serde_json::json!({"id": "b", "name": null}),
+        ];
+        assert_eq!(template.render(&records).unwrap(), "a ci & cd<>\nb <>\n");
+
+        let block = Template::new("- {{id}}\n").unwrap();
+        assert_eq!(block.render(&records).unwrap(), "- a\n- b\n");
+        assert!(Template::new("{{#each}}").is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(Format::parse("JSON").unwrap(), Format::Json);
+        assert_eq!(Format::parse("psobject").unwrap(), Format::PsObject);
+        assert_eq!(Format::parse("jsonl").unwrap(), Format::Ndjson);
+        assert!(Format::parse("xml").is_err());
+        #[cfg(feature = "yaml")]
+        assert_eq!(Format::parse("yml").unwrap(), Format::Yaml);
+    }
+}
@@ -0,0 +1,202 @@
+//! `--output-file`: everything a command prints to stdout goes to a file instead, readable only by
+//! the current user. The output is collected in a temporary file next to the target and renamed
+//! over it once the command finishes, so the secrets in it are never readable by anyone else,
+//! unlike with shell redirection. A command that fails before printing anything leaves no file
+//! behind; one that fails after printing, such as a rotation whose old key could not be deleted,
+//! keeps what it printed, since that may be the only copy of a new secret.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+pub struct OutputFile {
+    path: PathBuf,
+    temp: PathBuf,
+    file: File,
+    stdout: Option<Stdout>,
+}
+
+impl OutputFile {
+    /// Start sending stdout to `path`, which must not exist unless `force` is given.
+    pub fn create(path: &Path, force: bool) -> anyhow::Result<Self> {
+        if path.is_dir() {
+            anyhow::bail!("{} is a directory", path.display());
+        }
+        if path.exists() && !force {
+            anyhow::bail!(
+                "{} already exists; pass --force to overwrite it",
+                path.display()
+            );
+        }
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} is not a file name", path.display()))?;
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir)?;
+        let temp = dir.join(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        let file = config::create_private(&temp)
+            .map_err(|e| anyhow::anyhow!("Could not create {}: {}", temp.display(), e))?;
+        let stdout = match Stdout::redirect(&file) {
+            Ok(stdout) => stdout,
+            Err(e) => {
+                let _ = fs::remove_file(&temp);
+                return Err(e);
+            }
+        };
+        Ok(OutputFile {
+            path: path.to_path_buf(),
+            temp,
+            file,
+            stdout: Some(stdout),
+        })
+    }
+
+    /// Restore stdout and move the output into place.
+    pub fn commit(mut self) -> anyhow::Result<()> {
+        self.restore()?;
+        self.file.sync_all()?;
+        fs::rename(&self.temp, &self.path).map_err(|e| {
+            anyhow::anyhow!(
+                "Could not move the output to {}: {}",
+                self.path.display(),
+                e
+            )
+        })?;
+        eprintln!("Wrote {}", self.path.display());
+        Ok(())
+    }
+
+    /// Commit the output if the command succeeded or printed anything, and return the
+    /// command's result, which takes precedence over a failure to commit.
+    pub fn finish(mut self, result: anyhow::Result<()>) -> anyhow::Result<()> {
+        if result.is_err() && !self.has_output() {
+            return result;
+        }
+        match (result, self.commit()) {
+            (Err(e), Err(committed)) => {
+                eprintln!("{}", committed);
+                Err(e)
+            }
+            (result, committed) => result.and(committed),
+        }
+    }
+
+    fn has_output(&mut self) -> bool {
+        let _ = io::stdout().flush();
+        self.file
+            .metadata()
+            .is_ok_and(|metadata| metadata.len() > 0)
+    }
+
+    fn restore(&mut self) -> anyhow::Result<()> {
+        match self.stdout.take() {
+            Some(stdout) => stdout.restore(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for OutputFile {
+    /// Without a [`commit`](Self::commit), as when the command failed without printing, the
+    /// output is discarded.
+    fn drop(&mut self) {
+        if self.stdout.is_some() {
+            let _ = self.restore();
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// The original stdout while it points at the output file.
+#[cfg(unix)]
+struct Stdout(std::os::fd::RawFd);
+
+#[cfg(unix)]
+impl Stdout {
+    fn redirect(file: &File) -> anyhow::Result<Self> {
+        use std::os::fd::AsRawFd;
+        io::stdout().flush()?;
+        // SAFETY: plain descriptor calls; the saved descriptor is owned by the returned value.
+        unsafe {
+            let saved = libc::dup(libc::STDOUT_FILENO);
+            if saved < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            if libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+                let e = io::Error::last_os_error();
+                libc::close(saved);
+                return Err(e.into());
+            }
+            Ok(Stdout(saved))
+        }
+    }
+
+    fn restore(self) -> anyhow::Result<()> {
+        io::stdout().flush()?;
+        // SAFETY: `self.0` is the descriptor saved by `redirect` and is closed exactly once.
+        unsafe {
+            let result = libc::dup2(self.0, libc::STDOUT_FILENO);
+            libc::close(self.0);
+            if result < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rust looks up the standard output handle on every write, so swapping it is enough.
+#[cfg(windows)]
+struct Stdout(windows_sys::Win32::Foundation::HANDLE);
+
+#[cfg(windows)]
+impl Stdout {
+    fn redirect(file: &File) -> anyhow::Result<Self> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::System::Console::{GetStdHandle, SetStdHandle, STD_OUTPUT_HANDLE};
+        io::stdout().flush()?;
+        // SAFETY: the file outlives the redirection, which `OutputFile` undoes before closing it.
+        unsafe {
+            let saved = GetStdHandle(STD_OUTPUT_HANDLE);
+            if SetStdHandle(STD_OUTPUT_HANDLE, file.as_raw_handle() as _) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(Stdout(saved))
+        }
+    }
+
+    fn restore(self) -> anyhow::Result<()> {
+        use windows_sys::Win32::System::Console::{SetStdHandle, STD_OUTPUT_HANDLE};
+        io::stdout().flush()?;
+        // SAFETY: puts back the handle that was current before `redirect`.
+        if unsafe { SetStdHandle(STD_OUTPUT_HANDLE, self.0) } == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refuses_to_overwrite_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        fs::write(&path, "old").unwrap();
+        let error = OutputFile::create(&path, false).err().unwrap();
+        assert!(error.to_string().contains("pass --force"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(OutputFile::create(dir.path(), true).is_err());
+    }
+}
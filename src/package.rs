@@ -0,0 +1,346 @@
+//! `package metadata`: everything a release needs besides the binary, generated from the CLI
+//! definition itself so that it never falls behind:
+//!
+//! ```text
+//! dist/
+//!   completions/   bash, zsh, fish, PowerShell and Elvish completions
+//!   man/           a man page for the command and each subcommand
+//!   homebrew/newrelic-apikeys-cli.rb
+//!   scoop/newrelic-apikeys-cli.json
+//!   SHA256SUMS
+//! ```
+//!
+//! Completions and man pages are always written; they go into the release archives and the
+//! deb and rpm packages (see `[package.metadata.deb]` in Cargo.toml). The Homebrew formula and
+//! the Scoop manifest point at the archives passed with `--archive`, so they are written once
+//! those exist.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap_complete::Shell;
+use sha2::{Digest, Sha256};
+
+/// The name users type; the archives hold the Cargo binary, which is installed under this name.
+pub const COMMAND: &str = "newrelic-apikeys-cli";
+const BINARY: &str = "newrelic_apikeys_cli";
+const HOMEPAGE: &str = "https://github.com/harrykimpel/newrelic_apikeys_cli";
+pub const DEFAULT_URL: &str =
+    "https://github.com/harrykimpel/newrelic_apikeys_cli/releases/download/v{version}/{archive}";
+
+/// A release archive for one target triple.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archive {
+    pub target: String,
+    pub path: PathBuf,
+}
+
+impl Archive {
+    /// `TARGET=PATH`, e.g. `x86_64-apple-darwin=newrelic-apikeys-cli-0.1.0-x86_64-apple-darwin.tar.gz`.
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.split_once('=') {
+            Some((target, path)) if !target.is_empty() && !path.is_empty() => Ok(Archive {
+                target: target.to_string(),
+                path: PathBuf::from(path),
+            }),
+            _ => anyhow::bail!("Invalid archive '{}' (expected TARGET=PATH)", value),
+        }
+    }
+}
+
+/// An archive as the package managers see it.
+#[derive(Debug, Clone, PartialEq)]
+struct Download {
+    target: String,
+    url: String,
+    sha256: String,
+}
+
+/// Options for [`metadata`].
+pub struct Options<'a> {
+    pub out_dir: &'a Path,
+    pub version: &'a str,
+    /// Download URL of an archive, with `{version}` and `{archive}` (its file name) filled in
+    pub url: &'a str,
+    pub archives: &'a [Archive],
+}
+
+/// Write the dist layout for `command` to `options.out_dir`.
+pub fn metadata(command: clap::Command, options: Options<'_>) -> anyhow::Result<()> {
+    let completions = options.out_dir.join("completions");
+    fs::create_dir_all(&completions)?;
+    for shell in [
+        Shell::Bash,
+        Shell::Zsh,
+        Shell::Fish,
+        Shell::PowerShell,
+        Shell::Elvish,
+    ] {
+        clap_complete::generate_to(shell, &mut command.clone(), COMMAND, &completions)?;
+    }
+    eprintln!("Wrote completions to {}", completions.display());
+
+    let man = options.out_dir.join("man");
+    fs::create_dir_all(&man)?;
+    clap_mangen::generate_to(without_help_subcommands(command.clone()), &man)?;
+    eprintln!("Wrote man pages to {}", man.display());
+
+    if options.archives.is_empty() {
+        eprintln!("No --archive given; skipping the Homebrew formula and Scoop manifest");
+        return Ok(());
+    }
+    let mut downloads = Vec::new();
+    for archive in options.archives {
+        let bytes = fs::read(&archive.path)
+            .map_err(|e| anyhow::anyhow!("Unable to read {}: {}", archive.path.display(), e))?;
+        let name = archive
+            .path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} is not a file name", archive.path.display()))?
+            .to_string_lossy();
+        downloads.push(Download {
+            target: archive.target.clone(),
+            url: options
+                .url
+                .replace("{version}", options.version)
+                .replace("{archive}", &name),
+            sha256: Sha256::digest(&bytes)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        });
+    }
+
+    let sums: String = downloads
+        .iter()
+        .map(|download| {
+            let name = download.url.rsplit('/').next().unwrap_or_default();
+            format!("{}  {}\n", download.sha256, name)
+        })
+        .collect();
+    fs::write(options.out_dir.join("SHA256SUMS"), sums)?;
+
+    match homebrew_formula(options.version, &downloads) {
+        Some(formula) => write(
+            &options.out_dir.join("homebrew"),
+            &format!("{}.rb", COMMAND),
+            &formula,
+        )?,
+        None => eprintln!("No macOS or Linux archive given; skipping the Homebrew formula"),
+    }
+    match scoop_manifest(options.version, &downloads)? {
+        Some(manifest) => write(
+            &options.out_dir.join("scoop"),
+            &format!("{}.json", COMMAND),
+            &manifest,
+        )?,
+        None => eprintln!("No Windows archive given; skipping the Scoop manifest"),
+    }
+    Ok(())
+}
+
+/// Every command with subcommands gets a `help` subcommand, which needs no man page of its own.
+fn without_help_subcommands(command: clap::Command) -> clap::Command {
+    command
+        .disable_help_subcommand(true)
+        .mut_subcommands(without_help_subcommands)
+}
+
+fn write(dir: &Path, name: &str, contents: &str) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(name);
+    fs::write(&path, contents)?;
+    eprintln!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// The Homebrew OS and CPU blocks a target triple belongs in.
+fn homebrew_platform(target: &str) -> Option<(&'static str, &'static str)> {
+    let os = if target.contains("-apple-darwin") {
+        "on_macos"
+    } else if target.contains("-linux-") {
+        "on_linux"
+    } else {
+        return None;
+    };
+    let cpu = match target.split('-').next() {
+        Some("aarch64") => "on_arm",
+        Some("x86_64") => "on_intel",
+        _ => return None,
+    };
+    Some((os, cpu))
+}
+
+fn homebrew_formula(version: &str, downloads: &[Download]) -> Option<String> {
+    let mut blocks = String::new();
+    for os in ["on_macos", "on_linux"] {
+        let mut cpus = String::new();
+        for cpu in ["on_arm", "on_intel"] {
+            let download = downloads
+                .iter()
+                .find(|d| homebrew_platform(&d.target) == Some((os, cpu)));
+            if let Some(download) = download {
+                let _ = write!(
+                    cpus,
+                    "    {} do\n      url \"{}\"\n      sha256 \"{}\"\n    end\n",
+                    cpu, download.url, download.sha256
+                );
+            }
+        }
+        if !cpus.is_empty() {
+            let _ = write!(blocks, "  {} do\n{}  end\n\n", os, cpus);
+        }
+    }
+    if blocks.is_empty() {
+        return None;
+    }
+    Some(format!(
+        r##"class NewrelicApikeysCli < Formula
+  desc "{desc}"
+  homepage "{homepage}"
+  version "{version}"
+  license "MIT"
+
+{blocks}  def install
+    bin.install "{binary}" => "{command}"
+    bash_completion.install "completions/{command}.bash" => "{command}"
+    zsh_completion.install "completions/_{command}"
+    fish_completion.install "completions/{command}.fish"
+    man1.install Dir["man/*.1"]
+  end
+
+  test do
+    assert_match version.to_s, shell_output("#{{bin}}/{command} --version")
+  end
+end
+"##,
+        desc = env!("CARGO_PKG_DESCRIPTION"),
+        homepage = HOMEPAGE,
+        version = version,
+        blocks = blocks,
+        binary = BINARY,
+        command = COMMAND,
+    ))
+}
+
+fn scoop_manifest(version: &str, downloads: &[Download]) -> anyhow::Result<Option<String>> {
+    let mut architecture = serde_json::Map::new();
+    for download in downloads.iter().filter(|d| d.target.contains("-windows-")) {
+        let arch = match download.target.split('-').next() {
+            Some("x86_64") => "64bit",
+            Some("aarch64") => "arm64",
+            _ => continue,
+        };
+        architecture.insert(
+            arch.to_string(),
+            serde_json::json!({ "url": download.url, "hash": download.sha256 }),
+        );
+    }
+    if architecture.is_empty() {
+        return Ok(None);
+    }
+    let manifest = serde_json::json!({
+        "version": version,
+        "description": env!("CARGO_PKG_DESCRIPTION"),
+        "homepage": HOMEPAGE,
+        "license": "MIT",
+        "architecture": architecture,
+        "bin": [[format!("{}.exe", BINARY), COMMAND]],
+        "checkver": "github",
+    });
+    Ok(Some(serde_json::to_string_pretty(&manifest)? + "\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn download(target: &str) -> Download {
+        Download {
+            target: target.to_string(),
+            url: format!("https://example.com/{}.tar.gz", target),
+            sha256: "ab".repeat(32),
+        }
+    }
+
+    #[test]
+    fn test_homebrew_formula_covers_given_platforms() {
+        let formula = homebrew_formula(
+            "1.2.3",
+            &[
+                download("aarch64-apple-darwin"),
+                download("x86_64-unknown-linux-gnu"),
+                download("x86_64-pc-windows-msvc"),
+            ],
+        )
+        .unwrap();
+        assert!(formula.contains(
+            "  on_macos do\n    on_arm do\n      url \"https://example.com/aarch64-apple-darwin.tar.gz\""
+        ));
+        assert!(formula.contains("  on_linux do\n    on_intel do\n"));
+        assert!(!formula.contains("windows"));
+        assert!(
+            formula.contains("bin.install \"newrelic_apikeys_cli\" => \"newrelic-apikeys-cli\"")
+        );
+        assert!(homebrew_formula("1.2.3", &[download("x86_64-pc-windows-msvc")]).is_none());
+    }
+
+    #[test]
+    fn test_scoop_manifest_lists_windows_archives() {
+        let manifest = scoop_manifest("1.2.3", &[download("x86_64-pc-windows-msvc")])
+            .unwrap()
+            .unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["version"], "1.2.3");
+        assert_eq!(
+            manifest["architecture"]["64bit"]["hash"],
+            serde_json::json!("ab".repeat(32))
+        );
+        assert_eq!(
+            manifest["bin"],
+            serde_json::json!([["newrelic_apikeys_cli.exe", "newrelic-apikeys-cli"]])
+        );
+        assert!(scoop_manifest("1.2.3", &[download("aarch64-apple-darwin")])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_metadata_writes_completions_and_man_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = clap::Command::new(COMMAND)
+            .version("1.2.3")
+            .subcommand(clap::Command::new("list").about("List keys"));
+        metadata(
+            command,
+            Options {
+                out_dir: dir.path(),
+                version: "1.2.3",
+                url: DEFAULT_URL,
+                archives: &[],
+            },
+        )
+        .unwrap();
+        assert!(dir
+            .path()
+            .join("completions/_newrelic-apikeys-cli")
+            .exists());
+        assert!(dir
+            .path()
+            .join("completions/newrelic-apikeys-cli.bash")
+            .exists());
+        assert!(dir.path().join("man/newrelic-apikeys-cli.1").exists());
+        assert!(dir.path().join("man/newrelic-apikeys-cli-list.1").exists());
+        assert!(!dir.path().join("man/newrelic-apikeys-cli-help.1").exists());
+        assert!(!dir.path().join("homebrew").exists());
+    }
+
+    #[test]
+    fn test_parse_archive() {
+        let archive = Archive::parse("x86_64-apple-darwin=dist/a.tar.gz").unwrap();
+        assert_eq!(archive.target, "x86_64-apple-darwin");
+        assert_eq!(archive.path, PathBuf::from("dist/a.tar.gz"));
+        assert!(Archive::parse("dist/a.tar.gz").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use terminal_size::{terminal_size, Height};
+
+/// Used when `$PAGER` is unset: quit if the text fits after all, keep colors, and leave the
+/// text on screen afterwards, as git does.
+#[cfg(not(windows))]
+const DEFAULT_PAGER: &str = "less -FRX";
+#[cfg(windows)]
+const DEFAULT_PAGER: &str = "more";
+
+/// Print `text` to stdout, through `$PAGER` when `enabled`, stdout is a terminal and the text
+/// is taller than it. If the pager cannot be started the text is printed directly.
+pub fn print(text: &str, enabled: bool) -> anyhow::Result<()> {
+    if enabled && io::stdout().is_terminal() && exceeds_terminal(text) {
+        let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+        // An empty PAGER disables paging, like git.
+        if !pager.trim().is_empty() && page(&pager, text).is_ok() {
+            return Ok(());
+        }
+    }
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(text.as_bytes()) {
+        // Whoever reads our output stopped early (`| head`); that is not an error.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn exceeds_terminal(text: &str) -> bool {
+    match terminal_size() {
+        Some((_, Height(rows))) => text.lines().count() >= usize::from(rows),
+        None => false,
+    }
+}
+
+fn page(pager: &str, text: &str) -> anyhow::Result<()> {
+    let words = shell_words::split(pager)?;
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("PAGER is empty"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user may quit the pager before reading everything.
+        match stdin.write_all(text.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
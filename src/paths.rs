@@ -0,0 +1,188 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use directories::{BaseDirs, ProjectDirs};
+
+const APPLICATION: &str = "newrelic-apikeys-cli";
+const CONFIG_FILE: &str = "config.toml";
+
+/// Platform-specific locations for everything the CLI keeps on disk.
+pub struct Paths {
+    pub config_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub data_dir: PathBuf,
+}
+
+impl Paths {
+    pub fn discover() -> anyhow::Result<Self> {
+        let dirs = ProjectDirs::from("", "", APPLICATION)
+            .ok_or_else(|| anyhow::anyhow!("Unable to determine the home directory"))?;
+        Ok(Self {
+            config_dir: dirs.config_dir().to_path_buf(),
+            cache_dir: dirs.cache_dir().to_path_buf(),
+            data_dir: dirs.data_dir().to_path_buf(),
+        })
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join(CONFIG_FILE)
+    }
+
+    pub fn history_file(&self) -> PathBuf {
+        self.data_dir.join("history.jsonl")
+    }
+
+    pub fn reports_dir(&self) -> PathBuf {
+        self.data_dir.join("reports")
+    }
+
+    /// NerdGraph responses kept for `--offline`.
+    pub fn responses_dir(&self) -> PathBuf {
+        self.cache_dir.join("responses")
+    }
+
+    /// The cached session token of a profile with `[session]` sign-in.
+    pub fn session_file(&self, profile: &str) -> PathBuf {
+        self.cache_dir
+            .join("sessions")
+            .join(format!("{}.json", profile))
+    }
+
+    /// Deletions scheduled by `rotate --delete-after`, run by `scheduler run`.
+    pub fn schedule_file(&self) -> PathBuf {
+        self.data_dir.join("schedule.json")
+    }
+
+    /// Keys protected with `protect add`.
+    pub fn protected_file(&self) -> PathBuf {
+        self.data_dir.join("protected.json")
+    }
+
+    /// Progress saved by a cancelled bulk operation, e.g. `cleanup-stale`.
+    pub fn resume_file(&self, operation: &str) -> PathBuf {
+        self.data_dir
+            .join("resume")
+            .join(format!("{}.json", operation))
+    }
+
+    /// Move files from the pre-platform-directories location (`~/.newrelic-apikeys-cli`) into
+    /// the platform directories, returning the legacy directory if anything was migrated.
+    pub fn migrate_legacy(&self) -> io::Result<Option<PathBuf>> {
+        let Some(base) = BaseDirs::new() else {
+            return Ok(None);
+        };
+        let legacy_dir = base.home_dir().join(format!(".{}", APPLICATION));
+        if self.migrate_from(&legacy_dir)? {
+            Ok(Some(legacy_dir))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn migrate_from(&self, legacy_dir: &Path) -> io::Result<bool> {
+        if !legacy_dir.is_dir() {
+            return Ok(false);
+        }
+
+        let mut migrated = false;
+        for entry in fs::read_dir(legacy_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let target_dir = if name == CONFIG_FILE {
+                &self.config_dir
+            } else {
+                &self.data_dir
+            };
+            let target = target_dir.join(&name);
+            if target.exists() {
+                continue;
+            }
+            fs::create_dir_all(target_dir)?;
+            move_path(&entry.path(), &target)?;
+            migrated = true;
+        }
+
+        if fs::read_dir(legacy_dir)?.next().is_none() {
+            fs::remove_dir(legacy_dir)?;
+        }
+        Ok(migrated)
+    }
+
+    pub fn print(&self) {
+        println!("Config file: {}", self.config_file().display());
+        println!("Config dir:  {}", self.config_dir.display());
+        println!("Cache dir:   {}", self.cache_dir.display());
+        println!("Data dir:    {}", self.data_dir.display());
+    }
+}
+
+/// Rename `from` to `to`, falling back to copy-and-delete across filesystems.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(from)
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths_in(root: &Path) -> Paths {
+        Paths {
+            config_dir: root.join("config"),
+            cache_dir: root.join("cache"),
+            data_dir: root.join("data"),
+        }
+    }
+
+    #[test]
+    fn test_migrate_from_legacy_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = root.path().join("legacy");
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("config.toml"), "format = \"json\"\n").unwrap();
+        fs::write(legacy.join("history.jsonl"), "").unwrap();
+
+        let paths = paths_in(root.path());
+        assert!(paths.migrate_from(&legacy).unwrap());
+
+        assert!(paths.config_file().exists());
+        assert!(paths.data_dir.join("history.jsonl").exists());
+        assert!(!legacy.exists());
+    }
+
+    #[test]
+    fn test_migrate_keeps_existing_files() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = root.path().join("legacy");
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("config.toml"), "old").unwrap();
+
+        let paths = paths_in(root.path());
+        fs::create_dir_all(&paths.config_dir).unwrap();
+        fs::write(paths.config_file(), "new").unwrap();
+
+        assert!(!paths.migrate_from(&legacy).unwrap());
+        assert_eq!(fs::read_to_string(paths.config_file()).unwrap(), "new");
+        assert!(legacy.join("config.toml").exists());
+    }
+
+    #[test]
+    fn test_migrate_without_legacy_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = paths_in(root.path());
+        assert!(!paths.migrate_from(&root.path().join("missing")).unwrap());
+    }
+}
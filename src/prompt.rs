@@ -0,0 +1,37 @@
+use std::io::{self, BufRead, Write};
+
+/// Ask a yes/no question on the terminal; anything but "y"/"yes" counts as no. The question
+/// goes to stderr so that it is seen even when stdout is redirected.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    eprint!("{} [y/N]: ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Whether a command asks before a destructive step: `--yes` assumes the answer.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Confirmation {
+    #[default]
+    Prompt,
+    Assume,
+}
+
+impl Confirmation {
+    pub fn assume(yes: bool) -> Self {
+        if yes {
+            Confirmation::Assume
+        } else {
+            Confirmation::Prompt
+        }
+    }
+
+    /// `true` without asking when the answer is assumed, otherwise see [`confirm`].
+    pub fn confirm(self, question: &str) -> io::Result<bool> {
+        match self {
+            Confirmation::Assume => Ok(true),
+            Confirmation::Prompt => confirm(question),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Protected keys: IDs or names listed in `protected_keys` in the config or added with
+//! `protect add`, which keeps them in a local file. Deleting or rotating a protected key fails
+//! unless the CLI runs with `--allow-protected`, and so does deleting or rotating a key that cannot
+//! be looked up, since it may be protected by name.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Config};
+use crate::middleware::{Middleware, Next};
+use crate::output::{Format, Renderer};
+use crate::transport::{HttpRequest, TransportFuture};
+use crate::{inventory, targets, NewRelicClient};
+
+#[derive(Default, Serialize, Deserialize)]
+struct Store {
+    keys: Vec<String>,
+}
+
+/// The keys protected with `protect add`.
+pub fn load(path: &Path) -> anyhow::Result<Vec<String>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow::anyhow!("Unable to read {}: {}", path.display(), e)),
+    };
+    let store: Store = serde_json::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("Invalid protected keys {}: {}", path.display(), e))?;
+    Ok(store.keys)
+}
+
+fn save(path: &Path, keys: Vec<String>) -> anyhow::Result<()> {
+    let store = Store { keys };
+    config::write_private(path, &(serde_json::to_string_pretty(&store)? + "\n"))
+}
+
+/// Protect keys by ID or name, returning those that were not protected yet.
+pub fn add(path: &Path, keys: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut stored = load(path)?;
+    let added: Vec<String> = keys
+        .iter()
+        .filter(|key| !stored.contains(key))
+        .cloned()
+        .collect();
+    stored.extend(added.iter().cloned());
+    save(path, stored)?;
+    Ok(added)
+}
+
+/// Withdraw the protection added for `key`, returning whether there was one.
+pub fn remove(path: &Path, key: &str) -> anyhow::Result<bool> {
+    let mut stored = load(path)?;
+    let before = stored.len();
+    stored.retain(|stored| stored != key);
+    if stored.len() == before {
+        return Ok(false);
+    }
+    save(path, stored)?;
+    Ok(true)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    /// Key ID or name
+    pub key: String,
+    /// `config` for `protected_keys`, `local` for `protect add`
+    pub source: &'static str,
+}
+
+/// Every protected key, from the config first.
+pub fn entries(config: &Config, path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let configured = config
+        .protected_keys
+        .iter()
+        .map(|key| (key.clone(), "config"));
+    let local = load(path)?.into_iter().map(|key| (key, "local"));
+    Ok(configured
+        .chain(local)
+        .map(|(key, source)| Entry { key, source })
+        .collect())
+}
+
+const HEADERS: [&str; 2] = ["KEY", "SOURCE"];
+
+impl Renderer for [Entry] {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|e| vec![e.key.clone(), e.source.to_string()])
+            .collect()
+    }
+
+    fn empty_message(&self) -> Option<String> {
+        Some("No protected keys".to_string())
+    }
+}
+
+pub fn print(entries: &[Entry], format: Format) -> anyhow::Result<()> {
+    print!("{}", entries.render(&format)?);
+    Ok(())
+}
+
+/// Refuses deletes of protected keys; [`Protection::check_rotation`] refuses rotating them.
+#[derive(Clone)]
+pub struct Protection(Arc<Vec<String>>);
+
+impl Protection {
+    /// The protection of the config and the local file, or `None` when no key is protected.
+    pub fn new(config: &Config, path: &Path) -> anyhow::Result<Option<Self>> {
+        let keys: Vec<String> = entries(config, path)?.into_iter().map(|e| e.key).collect();
+        Ok((!keys.is_empty()).then(|| Protection(Arc::new(keys))))
+    }
+
+    fn check(&self, id: &str, name: Option<&str>) -> anyhow::Result<()> {
+        let protected = self
+            .0
+            .iter()
+            .any(|key| key == id || Some(key.as_str()) == name);
+        if !protected {
+            return Ok(());
+        }
+        let key = match name {
+            Some(name) => format!("{} ({})", id, name),
+            None => id.to_string(),
+        };
+        anyhow::bail!(
+            "Key {} is protected; pass --allow-protected to delete or rotate it",
+            key
+        )
+    }
+
+    /// Why a key whose name could not be learned is refused: it may be protected by name.
+    fn unknown(id: &str, error: impl std::fmt::Display) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Could not look up key {} to check it against the protected keys: {}; \
+             pass --allow-protected to go ahead anyway",
+            id,
+            error
+        )
+    }
+
+    /// Refuse to rotate protected keys, before any replacement is created.
+    pub async fn check_rotation(
+        &self,
+        client: &NewRelicClient,
+        key_ids: &[String],
+        key_type: &str,
+    ) -> anyhow::Result<()> {
+        for key_id in key_ids {
+            self.check(key_id, None)?;
+            let key = inventory::get(client, key_id, key_type)
+                .await
+                .map_err(|e| Self::unknown(key_id, format!("{:#}", e)))?;
+            self.check(key_id, key.name.as_deref())?;
+        }
+        Ok(())
+    }
+}
+
+impl Middleware for Protection {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            if request.is_mutation() {
+                let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+                for (key_id, key_type) in targets::deleted_key_ids(&body["variables"]) {
+                    self.check(&key_id, None)?;
+                    let key = targets::lookup(&request, next, &key_id, key_type)
+                        .await
+                        .map_err(|e| Self::unknown(&key_id, format!("{:#}", e)))?;
+                    if key.is_null() {
+                        return Err(Self::unknown(&key_id, "it was not found"));
+                    }
+                    self.check(&key_id, key["name"].as_str())?;
+                }
+            }
+            next.run(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::HttpResponse;
+
+    #[test]
+    fn test_add_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("protected.json");
+        let added = add(&path, &["a".to_string(), "b".to_string()]).unwrap();
+        assert_eq!(added.len(), 2);
+        assert!(add(&path, &["a".to_string()]).unwrap().is_empty());
+        assert!(remove(&path, "a").unwrap());
+        assert!(!remove(&path, "a").unwrap());
+
+        let config = Config {
+            protected_keys: vec!["prod-license".to_string()],
+            ..Config::default()
+        };
+        let sources: Vec<(String, &str)> = entries(&config, &path)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.key, e.source))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                ("prod-license".to_string(), "config"),
+                ("b".to_string(), "local")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refuses_deleting_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            protected_keys: vec!["prod-license".to_string()],
+            ..Config::default()
+        };
+        let protection = Protection::new(&config, &dir.path().join("protected.json"))
+            .unwrap()
+            .unwrap();
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(protection)
+            .transport(|request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+                let id = body["variables"]["id"].as_str().unwrap_or_default();
+                let key = match id {
+                    "K1" => serde_json::json!({ "id": id, "name": "prod-license", "accountId": 1 }),
+                    "K2" => serde_json::json!({ "id": id, "name": "other", "accountId": 1 }),
+                    _ => serde_json::Value::Null,
+                };
+                let data = serde_json::json!({
+                    "actor": { "apiAccess": { "key": key }},
+                    "apiAccessDeleteKeys": { "deletedKeys": [], "errors": [] },
+                });
+                Ok(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: serde_json::to_vec(&serde_json::json!({ "data": data }))?,
+                })
+            })
+            .build()
+            .unwrap();
+
+        let error = inventory::delete_keys(&client, &["K1".to_string()], &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("Key K1 (prod-license) is protected"));
+        assert!(inventory::delete_keys(&client, &["K2".to_string()], &[])
+            .await
+            .is_ok());
+        let error = inventory::delete_keys(&client, &["K3".to_string()], &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("Could not look up key K3"));
+    }
+}
@@ -0,0 +1,136 @@
+//! The `newrelic_apikeys` Python module, built with maturin (see `pyproject.toml`).
+//!
+//! Keys cross the boundary as plain dicts with the same fields as the JSON output, so scripts
+//! that used to parse the CLI's stdout keep working on the same shapes.
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::inventory::NewKey;
+use crate::{NewRelicClient, NewRelicClientBlocking};
+
+fn runtime_error(error: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", error))
+}
+
+/// Round-trip through the `json` module, which maps values exactly as `json.loads` would.
+fn to_python<'py, T: serde::Serialize>(py: Python<'py>, value: &T) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value).map_err(|e| runtime_error(e.into()))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+fn from_python<T: serde::de::DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json: String = value
+        .py()
+        .import("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&json).map_err(|e| runtime_error(e.into()))
+}
+
+/// NerdGraph API key client. Calls block and release the GIL while waiting on the network.
+#[pyclass(name = "Client", module = "newrelic_apikeys")]
+struct PyClient(NewRelicClientBlocking);
+
+#[pymethods]
+impl PyClient {
+    #[new]
+    #[pyo3(signature = (api_key, endpoint = None))]
+    fn new(api_key: String, endpoint: Option<String>) -> PyResult<Self> {
+        let mut builder = NewRelicClient::builder().api_key(api_key);
+        if let Some(endpoint) = endpoint {
+            builder = builder.endpoint(endpoint);
+        }
+        builder
+            .build()
+            .and_then(NewRelicClientBlocking::from_async)
+            .map(PyClient)
+            .map_err(runtime_error)
+    }
+
+    /// Every key of the given types (default: USER and INGEST) in the given accounts.
+    #[pyo3(signature = (account_ids, key_types = None))]
+    fn list_keys<'py>(
+        &self,
+        py: Python<'py>,
+        account_ids: Vec<i64>,
+        key_types: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let key_types = key_types.unwrap_or_else(|| vec!["USER".into(), "INGEST".into()]);
+        let keys = py
+            .detach(|| {
+                let key_types: Vec<&str> = key_types.iter().map(String::as_str).collect();
+                self.0.list_keys(&account_ids, &key_types)
+            })
+            .map_err(runtime_error)?;
+        to_python(py, &keys)
+    }
+
+    fn get_key<'py>(
+        &self,
+        py: Python<'py>,
+        key_id: String,
+        key_type: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let key = py
+            .detach(|| self.0.get_key(&key_id, &key_type))
+            .map_err(runtime_error)?;
+        to_python(py, &key)
+    }
+
+    /// Create a key from a dict with `type`, `account_id`, `name` and optionally `notes`,
+    /// `ingest_type` and `user_id`. The result includes the secret.
+    fn create_key<'py>(
+        &self,
+        py: Python<'py>,
+        spec: &Bound<'py, PyDict>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let spec: NewKey = from_python(spec.as_any())?;
+        let key = py
+            .detach(|| self.0.create_key(&spec))
+            .map_err(runtime_error)?;
+        to_python(py, &key)
+    }
+
+    /// Delete one key; raises if NerdGraph reports an error for it.
+    fn delete_key(&self, py: Python<'_>, key_id: String, key_type: String) -> PyResult<()> {
+        let ids = vec![key_id];
+        let outcome = py
+            .detach(|| match key_type.to_uppercase().as_str() {
+                "INGEST" => self.0.delete_keys(&ids, &[]),
+                "USER" => self.0.delete_keys(&[], &ids),
+                other => Err(anyhow::anyhow!(
+                    "Unsupported key type '{}' (expected INGEST or USER)",
+                    other
+                )),
+            })
+            .map_err(runtime_error)?;
+        match outcome.errors.first() {
+            Some(error) => Err(PyRuntimeError::new_err(error.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Replace a key and delete the old one unless `keep_old` is set.
+    #[pyo3(signature = (key_id, key_type, keep_old = false))]
+    fn rotate_key<'py>(
+        &self,
+        py: Python<'py>,
+        key_id: String,
+        key_type: String,
+        keep_old: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let rotation = py
+            .detach(|| self.0.rotate_key(&key_id, &key_type, keep_old))
+            .map_err(runtime_error)?;
+        to_python(py, &rotation)
+    }
+}
+
+#[pymodule]
+fn newrelic_apikeys(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyClient>()?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! Rate-limit and quota details from throttled NerdGraph responses.
+//!
+//! A throttled request fails with a [`RateLimited`] error (inside the
+//! [`RequestError`](crate::RequestError)) that says when to try again and how many requests are
+//! left, taken from the `Retry-After` and `X-RateLimit-Remaining` headers where NerdGraph sends
+//! them. The built-in retry waits for `Retry-After` instead of its own backoff.
+
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::transport::HttpResponse;
+
+/// GraphQL `extensions.errorClass` values NerdGraph uses for throttling.
+const ERROR_CLASSES: [&str; 2] = ["TOO_MANY_REQUESTS", "QUOTA_EXCEEDED"];
+/// Message fragments of throttling errors that come without an error class.
+const MESSAGES: [&str; 3] = ["rate limit", "too many requests", "quota"];
+
+/// What the server said about its limits; either part may be missing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateLimit {
+    pub retry_after: Option<Duration>,
+    pub remaining: Option<u64>,
+}
+
+impl RateLimit {
+    pub fn from_response(response: &HttpResponse) -> Self {
+        Self::from_headers(response, Utc::now())
+    }
+
+    fn from_headers(response: &HttpResponse, now: DateTime<Utc>) -> Self {
+        let retry_after = response
+            .header("Retry-After")
+            .and_then(|value| parse_retry_after(value, now));
+        let remaining = ["X-RateLimit-Remaining", "RateLimit-Remaining"]
+            .iter()
+            .find_map(|name| response.header(name))
+            .and_then(|value| value.trim().parse().ok());
+        Self {
+            retry_after,
+            remaining,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.retry_after.is_none() && self.remaining.is_none()
+    }
+}
+
+impl fmt::Display for RateLimit {
+    /// `retry after 30s, 0 requests remaining`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(retry_after) = self.retry_after {
+            parts.push(format!("retry after {}s", retry_after.as_secs().max(1)));
+        }
+        if let Some(remaining) = self.remaining {
+            parts.push(format!(
+                "{} request{} remaining",
+                remaining,
+                if remaining == 1 { "" } else { "s" }
+            ));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// A request NerdGraph refused because of rate limiting or an exhausted quota.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub message: String,
+    pub limit: RateLimit,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.limit.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} ({})", self.message, self.limit)
+        }
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Whether a GraphQL error is about throttling rather than the request itself.
+pub(crate) fn is_rate_limit_error(message: &str, extensions: Option<&serde_json::Value>) -> bool {
+    let class = extensions
+        .and_then(|extensions| extensions["errorClass"].as_str())
+        .unwrap_or_default();
+    let message = message.to_lowercase();
+    ERROR_CLASSES.contains(&class) || MESSAGES.iter().any(|m| message.contains(m))
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &[(&str, &str)]) -> HttpResponse {
+        HttpResponse {
+            status: 429,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_from_headers() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:27:30Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let limit = RateLimit::from_headers(
+            &response(&[("retry-after", "30"), ("X-RateLimit-Remaining", "0")]),
+            now,
+        );
+        assert_eq!(limit.retry_after, Some(Duration::from_secs(30)));
+        assert_eq!(limit.to_string(), "retry after 30s, 0 requests remaining");
+
+        let limit = RateLimit::from_headers(
+            &response(&[("Retry-After", "Wed, 21 Oct 2015 07:28:00 GMT")]),
+            now,
+        );
+        assert_eq!(limit.retry_after, Some(Duration::from_secs(30)));
+        assert!(RateLimit::from_headers(&response(&[]), now).is_empty());
+    }
+
+    #[test]
+    fn test_is_rate_limit_error() {
+        let class = serde_json::json!({ "errorClass": "TOO_MANY_REQUESTS" });
+        assert!(is_rate_limit_error("Slow down", Some(&class)));
+        assert!(is_rate_limit_error("Account quota exceeded", None));
+        assert!(!is_rate_limit_error("Invalid API key", None));
+    }
+}
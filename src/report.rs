@@ -0,0 +1,663 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::inventory::{self, ApiKey};
+use crate::output::{self, Format, Renderer};
+use crate::{config, pager, NewRelicClient};
+
+/// Age buckets for the histogram, as (label, upper bound in days).
+const AGE_BUCKETS: [(&str, i64); 5] = [
+    ("< 30 days", 30),
+    ("30-90 days", 90),
+    ("90-180 days", 180),
+    ("180-365 days", 365),
+    ("> 1 year", i64::MAX),
+];
+
+pub enum ReportFormat {
+    Html,
+    Output(Format),
+}
+
+impl ReportFormat {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        if value.eq_ignore_ascii_case("html") {
+            return Ok(ReportFormat::Html);
+        }
+        Format::parse(value).map(ReportFormat::Output).map_err(|_| {
+            anyhow::anyhow!(
+                "Unsupported report format '{}' (expected html, {})",
+                value,
+                output::EXPECTED
+            )
+        })
+    }
+}
+
+/// One inventory row; deliberately without the key secret.
+#[derive(Serialize)]
+struct Row {
+    id: String,
+    name: Option<String>,
+    key_type: Option<String>,
+    account_id: Option<i64>,
+    created_at: Option<DateTime<Utc>>,
+    age_days: Option<i64>,
+    over_policy: bool,
+    notes: Option<String>,
+}
+
+#[derive(Default, Serialize)]
+struct AccountSummary {
+    total: usize,
+    ingest: usize,
+    user: usize,
+    over_policy: usize,
+    oldest_days: Option<i64>,
+}
+
+struct Inventory {
+    generated_at: DateTime<Utc>,
+    max_key_age_days: Option<u32>,
+    rows: Vec<Row>,
+}
+
+impl Inventory {
+    fn new(keys: Vec<ApiKey>, now: DateTime<Utc>, max_key_age_days: Option<u32>) -> Self {
+        let mut rows: Vec<Row> = keys
+            .into_iter()
+            .map(|key| {
+                let age_days = key.age_days(now);
+                Row {
+                    over_policy: matches!(
+                        (age_days, max_key_age_days),
+                        (Some(age), Some(max)) if age > i64::from(max)
+                    ),
+                    id: key.id,
+                    name: key.name,
+                    key_type: key.key_type,
+                    account_id: key.account_id,
+                    created_at: key.created_at.and_then(|s| DateTime::from_timestamp(s, 0)),
+                    age_days,
+                    notes: key.notes,
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| (a.account_id, &a.id).cmp(&(b.account_id, &b.id)));
+        Self {
+            generated_at: now,
+            max_key_age_days,
+            rows,
+        }
+    }
+
+    fn accounts(&self) -> BTreeMap<Option<i64>, AccountSummary> {
+        let mut accounts: BTreeMap<Option<i64>, AccountSummary> = BTreeMap::new();
+        for row in &self.rows {
+            let summary = accounts.entry(row.account_id).or_default();
+            summary.total += 1;
+            match row.key_type.as_deref() {
+                Some("USER") => summary.user += 1,
+                Some("INGEST") => summary.ingest += 1,
+                _ => {}
+            }
+            if row.over_policy {
+                summary.over_policy += 1;
+            }
+            summary.oldest_days = summary.oldest_days.max(row.age_days);
+        }
+        accounts
+    }
+
+    /// Key counts per age bucket, followed by the count of keys with an unknown age.
+    fn histogram(&self) -> (Vec<(&'static str, usize)>, usize) {
+        let mut counts: Vec<(&str, usize)> = AGE_BUCKETS.iter().map(|(l, _)| (*l, 0)).collect();
+        let mut unknown = 0;
+        for row in &self.rows {
+            match row.age_days {
+                Some(age) => {
+                    let index = AGE_BUCKETS
+                        .iter()
+                        .position(|(_, bound)| age < *bound)
+                        .unwrap_or(AGE_BUCKETS.len() - 1);
+                    counts[index].1 += 1;
+                }
+                None => unknown += 1,
+            }
+        }
+        (counts, unknown)
+    }
+
+    fn table_rows(&self) -> Vec<Vec<String>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                vec![
+                    row.account_id.map(|id| id.to_string()).unwrap_or_default(),
+                    row.key_type.clone().unwrap_or_default(),
+                    row.id.clone(),
+                    row.name.clone().unwrap_or_default(),
+                    row.created_at
+                        .map(|c| c.format("%Y-%m-%d").to_string())
+                        .unwrap_or_default(),
+                    row.age_days.map(|a| a.to_string()).unwrap_or_default(),
+                    row.notes.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+}
+
+const HEADERS: [&str; 7] = [
+    "ACCOUNT",
+    "TYPE",
+    "ID",
+    "NAME",
+    "CREATED",
+    "AGE (DAYS)",
+    "NOTES",
+];
+
+/// Escape text for HTML and XML.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#1d252c}\
+table{border-collapse:collapse;margin-bottom:2rem}\
+th,td{border:1px solid #d5d7d9;padding:.3rem .6rem;text-align:left}\
+th{background:#f3f4f4}th.sortable{cursor:pointer}th.sortable:after{content:' \\2195';color:#8e9494}\
+tr.over td{background:#fdf1f1}.bar{background:#1ce783;height:1rem;display:inline-block}\
+.muted{color:#8e9494}";
+
+/// Click a header to sort by that column; numbers compare numerically, everything else as text
+/// (ISO dates therefore sort chronologically).
+const SCRIPT: &str = "document.querySelectorAll('th.sortable').forEach(function(th){\
+th.addEventListener('click',function(){\
+var table=th.closest('table'),body=table.tBodies[0],index=th.cellIndex,\
+asc=th.dataset.order!=='asc';th.dataset.order=asc?'asc':'desc';\
+var rows=Array.from(body.rows);rows.sort(function(a,b){\
+var x=a.cells[index].textContent,y=b.cells[index].textContent,\
+n=parseFloat(x)-parseFloat(y);\
+var c=isNaN(n)?x.localeCompare(y):n;return asc?c:-c;});\
+rows.forEach(function(r){body.appendChild(r);});});});";
+
+fn render_html(inventory: &Inventory) -> String {
+    let mut html = String::new();
+    let generated = inventory.generated_at.format("%Y-%m-%d %H:%M UTC");
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>API key inventory - {generated}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>API key inventory</h1>\n<p class=\"muted\">Generated {generated} by newrelic-apikeys-cli {}. \
+         {} key(s).",
+        env!("CARGO_PKG_VERSION"),
+        inventory.rows.len()
+    );
+    if let Some(max) = inventory.max_key_age_days {
+        let _ = write!(
+            html,
+            " Keys older than the {} day policy are highlighted.",
+            max
+        );
+    }
+    html.push_str("</p>\n");
+
+    html.push_str(
+        "<h2>Accounts</h2>\n<table>\n<thead><tr><th class=\"sortable\">Account</th>\
+         <th class=\"sortable\">Keys</th><th class=\"sortable\">Ingest</th>\
+         <th class=\"sortable\">User</th><th class=\"sortable\">Over policy</th>\
+         <th class=\"sortable\">Oldest (days)</th></tr></thead>\n<tbody>\n",
+    );
+    for (account, summary) in inventory.accounts() {
+        let account = account.map(|id| id.to_string()).unwrap_or_default();
+        let oldest = summary
+            .oldest_days
+            .map(|d| d.to_string())
+            .unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&account),
+            summary.total,
+            summary.ingest,
+            summary.user,
+            summary.over_policy,
+            oldest
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    let (buckets, unknown) = inventory.histogram();
+    let largest = buckets.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+    html.push_str("<h2>Key age</h2>\n<table>\n<tbody>\n");
+    for (label, count) in &buckets {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td><span class=\"bar\" style=\"width:{}px\"></span></td></tr>",
+            label,
+            count,
+            count * 300 / largest
+        );
+    }
+    if unknown > 0 {
+        let _ = writeln!(
+            html,
+            "<tr><td>unknown</td><td>{}</td><td></td></tr>",
+            unknown
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    html.push_str("<h2>Keys</h2>\n<table>\n<thead><tr>");
+    for header in [
+        "Account",
+        "Type",
+        "ID",
+        "Name",
+        "Created",
+        "Age (days)",
+        "Notes",
+    ] {
+        let _ = write!(html, "<th class=\"sortable\">{}</th>", header);
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for (row, cells) in inventory.rows.iter().zip(inventory.table_rows()) {
+        let class = if row.over_policy {
+            " class=\"over\""
+        } else {
+            ""
+        };
+        let _ = write!(html, "<tr{}>", class);
+        for cell in &cells {
+            let _ = write!(html, "<td>{}</td>", escape(cell));
+        }
+        html.push_str("</tr>\n");
+    }
+    let _ = write!(
+        html,
+        "</tbody>\n</table>\n<script>{SCRIPT}</script>\n</body>\n</html>\n"
+    );
+    html
+}
+
+impl Renderer for Inventory {
+    /// The machine-readable report: per-account summaries plus every key row.
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        let accounts: BTreeMap<String, AccountSummary> = self
+            .accounts()
+            .into_iter()
+            .map(|(account, summary)| {
+                (
+                    account.map(|id| id.to_string()).unwrap_or_default(),
+                    summary,
+                )
+            })
+            .collect();
+        Ok(serde_json::json!({
+            "generated_at": self.generated_at,
+            "max_key_age_days": self.max_key_age_days,
+            "accounts": accounts,
+            "keys": self.rows,
+        }))
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.table_rows()
+    }
+
+    /// One record per key rather than the summary.
+    #[cfg(feature = "templates")]
+    fn render_template(&self, template: &output::Template) -> anyhow::Result<String> {
+        template.render(&self.rows)
+    }
+}
+
+/// Aggregate statistics over the keys of several accounts, for management rather than review.
+struct Summary {
+    generated_at: DateTime<Utc>,
+    keys: usize,
+    by_type: BTreeMap<String, usize>,
+    age: Vec<(&'static str, usize)>,
+    unknown_age: usize,
+    /// Requested accounts in which no key was found
+    empty_accounts: Vec<i64>,
+    max_keys_per_account: Option<usize>,
+    /// Accounts with more than `max_keys_per_account` keys, and their key counts
+    over_threshold: Vec<(i64, usize)>,
+}
+
+impl Summary {
+    fn new(
+        inventory: &Inventory,
+        account_ids: &[i64],
+        max_keys_per_account: Option<usize>,
+    ) -> Self {
+        let accounts = inventory.accounts();
+        let mut by_type = BTreeMap::new();
+        for row in &inventory.rows {
+            let key_type = row
+                .key_type
+                .clone()
+                .unwrap_or_else(|| "UNKNOWN".to_string());
+            *by_type.entry(key_type).or_insert(0) += 1;
+        }
+        let (age, unknown_age) = inventory.histogram();
+        let empty_accounts = account_ids
+            .iter()
+            .filter(|id| !accounts.contains_key(&Some(**id)))
+            .copied()
+            .collect();
+        let over_threshold = match max_keys_per_account {
+            Some(max) => accounts
+                .iter()
+                .filter_map(|(account, summary)| Some(((*account)?, summary.total)))
+                .filter(|(_, total)| *total > max)
+                .collect(),
+            None => Vec::new(),
+        };
+        Self {
+            generated_at: inventory.generated_at,
+            keys: inventory.rows.len(),
+            by_type,
+            age,
+            unknown_age,
+            empty_accounts,
+            max_keys_per_account,
+            over_threshold,
+        }
+    }
+}
+
+fn join<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Renderer for Summary {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        let mut age: Vec<serde_json::Value> = self
+            .age
+            .iter()
+            .map(|(bucket, keys)| serde_json::json!({"bucket": bucket, "keys": keys}))
+            .collect();
+        if self.unknown_age > 0 {
+            age.push(serde_json::json!({"bucket": "unknown", "keys": self.unknown_age}));
+        }
+        let over_threshold: BTreeMap<String, usize> = self
+            .over_threshold
+            .iter()
+            .map(|(account, total)| (account.to_string(), *total))
+            .collect();
+        Ok(serde_json::json!({
+            "generated_at": self.generated_at,
+            "keys": self.keys,
+            "by_type": self.by_type,
+            "age": age,
+            "empty_accounts": self.empty_accounts,
+            "max_keys_per_account": self.max_keys_per_account,
+            "over_threshold": over_threshold,
+        }))
+    }
+
+    fn headers(&self) -> Vec<String> {
+        vec!["STATISTIC".to_string(), "VALUE".to_string()]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows = vec![vec!["keys".to_string(), self.keys.to_string()]];
+        for (key_type, count) in &self.by_type {
+            rows.push(vec![format!("type {}", key_type), count.to_string()]);
+        }
+        for (label, count) in &self.age {
+            rows.push(vec![format!("age {}", label), count.to_string()]);
+        }
+        if self.unknown_age > 0 {
+            rows.push(vec![
+                "age unknown".to_string(),
+                self.unknown_age.to_string(),
+            ]);
+        }
+        rows.push(vec![
+            "accounts without keys".to_string(),
+            join(&self.empty_accounts),
+        ]);
+        if let Some(max) = self.max_keys_per_account {
+            rows.push(vec![
+                format!("accounts over {} keys", max),
+                join(
+                    self.over_threshold
+                        .iter()
+                        .map(|(account, total)| format!("{} ({})", account, total)),
+                ),
+            ]);
+        }
+        rows
+    }
+}
+
+fn render_summary_html(summary: &Summary) -> String {
+    let mut html = String::new();
+    let generated = summary.generated_at.format("%Y-%m-%d %H:%M UTC");
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>API key summary - {generated}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>API key summary</h1>\n<p class=\"muted\">Generated {generated} by newrelic-apikeys-cli {}. \
+         {} key(s).</p>\n",
+        env!("CARGO_PKG_VERSION"),
+        summary.keys
+    );
+
+    html.push_str("<h2>Keys by type</h2>\n<table>\n<tbody>\n");
+    for (key_type, count) in &summary.by_type {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape(key_type),
+            count
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    let largest = summary
+        .age
+        .iter()
+        .map(|(_, n)| *n)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    html.push_str("<h2>Key age</h2>\n<table>\n<tbody>\n");
+    for (label, count) in &summary.age {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td><span class=\"bar\" style=\"width:{}px\"></span></td></tr>",
+            label,
+            count,
+            count * 300 / largest
+        );
+    }
+    if summary.unknown_age > 0 {
+        let _ = writeln!(
+            html,
+            "<tr><td>unknown</td><td>{}</td><td></td></tr>",
+            summary.unknown_age
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    html.push_str("<h2>Accounts</h2>\n<table>\n<tbody>\n");
+    let _ = writeln!(
+        html,
+        "<tr><td>Without keys</td><td>{}</td></tr>",
+        escape(&join(&summary.empty_accounts))
+    );
+    if let Some(max) = summary.max_keys_per_account {
+        let _ = writeln!(
+            html,
+            "<tr class=\"{}\"><td>Over {} keys</td><td>{}</td></tr>",
+            if summary.over_threshold.is_empty() {
+                ""
+            } else {
+                "over"
+            },
+            max,
+            escape(&join(
+                summary
+                    .over_threshold
+                    .iter()
+                    .map(|(account, total)| format!("{} ({})", account, total))
+            ))
+        );
+    }
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Print `rendered`, or write it to `output_path` and say so with `what`.
+fn emit(rendered: &str, output_path: Option<&Path>, what: &str, pager: bool) -> anyhow::Result<()> {
+    match output_path {
+        Some(path) => {
+            config::write_private(path, rendered)?;
+            println!("{} written to {}", what, path.display());
+        }
+        None => pager::print(rendered, pager)?,
+    }
+    Ok(())
+}
+
+/// Inventory of every key in the given accounts, for access reviews.
+pub async fn inventory(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    max_key_age_days: Option<u32>,
+    format: ReportFormat,
+    output_path: Option<&Path>,
+    pager: bool,
+) -> anyhow::Result<()> {
+    let keys = inventory::fetch(client, account_ids, &["INGEST", "USER"]).await?;
+    let inventory = Inventory::new(keys, Utc::now(), max_key_age_days);
+
+    let rendered = match format {
+        ReportFormat::Html => render_html(&inventory),
+        ReportFormat::Output(format) => inventory.render(&format)?,
+    };
+    let what = format!("Inventory of {} key(s)", inventory.rows.len());
+    emit(&rendered, output_path, &what, pager)
+}
+
+/// Counts by type and age, and the accounts without keys or with more than
+/// `max_keys_per_account`, over the given accounts.
+pub async fn summary(
+    client: &NewRelicClient,
+    account_ids: &[i64],
+    max_keys_per_account: Option<usize>,
+    format: ReportFormat,
+    output_path: Option<&Path>,
+    pager: bool,
+) -> anyhow::Result<()> {
+    let keys = inventory::fetch(client, account_ids, &["INGEST", "USER"]).await?;
+    let inventory = Inventory::new(keys, Utc::now(), None);
+    let summary = Summary::new(&inventory, account_ids, max_keys_per_account);
+
+    let rendered = match format {
+        ReportFormat::Html => render_summary_html(&summary),
+        ReportFormat::Output(format) => summary.render(&format)?,
+    };
+    let what = format!("Summary of {} key(s)", summary.keys);
+    emit(&rendered, output_path, &what, pager)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, key_type: &str, account_id: i64, age_days: Option<i64>) -> ApiKey {
+        let now = 1_700_000_000;
+        ApiKey::fixture(serde_json::json!({
+            "id": id,
+            "name": "<script>alert(1)</script>",
+            "type": key_type,
+            "createdAt": age_days.map(|d| now - d * 86_400),
+            "accountId": account_id
+        }))
+    }
+
+    fn sample() -> Inventory {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        Inventory::new(
+            vec![
+                key("a", "INGEST", 1, Some(10)),
+                key("b", "USER", 1, Some(200)),
+                key("c", "INGEST", 2, Some(400)),
+                key("d", "INGEST", 2, None),
+            ],
+            now,
+            Some(90),
+        )
+    }
+
+    #[test]
+    fn test_summaries_and_histogram() {
+        let inventory = sample();
+        let accounts = inventory.accounts();
+        let first = &accounts[&Some(1)];
+        assert_eq!((first.total, first.ingest, first.user), (2, 1, 1));
+        assert_eq!(first.over_policy, 1);
+        assert_eq!(accounts[&Some(2)].oldest_days, Some(400));
+
+        let (buckets, unknown) = inventory.histogram();
+        let counts: Vec<usize> = buckets.iter().map(|(_, n)| *n).collect();
+        assert_eq!(counts, vec![1, 0, 0, 1, 1]);
+        assert_eq!(unknown, 1);
+    }
+
+    #[test]
+    fn test_summary_counts_types_and_flags_accounts() {
+        let summary = Summary::new(&sample(), &[1, 2, 3], Some(1));
+        assert_eq!(summary.keys, 4);
+        assert_eq!(summary.by_type["INGEST"], 3);
+        assert_eq!(summary.by_type["USER"], 1);
+        assert_eq!(summary.empty_accounts, vec![3]);
+        assert_eq!(summary.over_threshold, vec![(1, 2), (2, 2)]);
+        assert_eq!(summary.unknown_age, 1);
+
+        let data = summary.data().unwrap();
+        assert_eq!(
+            data["age"][5],
+            serde_json::json!({"bucket": "unknown", "keys": 1})
+        );
+        assert_eq!(data["over_threshold"]["2"], 2);
+        let rows = summary.rows();
+        assert_eq!(rows[0], vec!["keys", "4"]);
+        assert_eq!(
+            rows.last().unwrap(),
+            &vec!["accounts over 1 keys", "1 (2), 2 (2)"]
+        );
+        assert!(render_summary_html(&summary).contains("<tr class=\"over\">"));
+    }
+
+    #[test]
+    fn test_html_escapes_key_fields() {
+        let html = render_html(&sample());
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(!html.contains("<script>alert(1)"));
+        assert!(html.contains("<tr class=\"over\">"));
+    }
+}
@@ -0,0 +1,631 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::NewRelicClient;
+
+/// How often a replacement is tried before `verify` gives up, since new keys take a moment to
+/// be accepted everywhere.
+const VERIFY_ATTEMPTS: u32 = 5;
+const VERIFY_DELAY: Duration = Duration::from_secs(2);
+
+/// What a user key replacement runs to show that it works.
+pub(crate) const VERIFY_QUERY: &str = "{ actor { user { id } } }";
+
+/// Event type of the canary event a license key replacement sends.
+pub const CANARY_EVENT: &str = "NrApiKeyRotationCanary";
+
+#[derive(Serialize)]
+pub struct Rotation {
+    pub old_key_id: String,
+    /// The replacement, including its secret
+    pub new_key: ApiKey,
+    /// Whether the replacement was seen working before the old key was deleted
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub verified: bool,
+    pub old_key_deleted: bool,
+    /// Why the old key is still active although its deletion was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete_error: Option<String>,
+}
+
+/// Replace a key with a new one of the same type, account, name and notes, then delete the
+/// old key unless `keep_old` is set. A failed delete is reported rather than returned as an
+/// error, so the new secret is never lost.
+pub async fn rotate(
+    client: &NewRelicClient,
+    key_id: &str,
+    key_type: &str,
+    keep_old: bool,
+) -> anyhow::Result<Rotation> {
+    rotate_verified(
+        client,
+        key_id,
+        key_type,
+        keep_old,
+        false,
+        async |_: &[&ApiKey]| anyhow::Ok(()),
+    )
+    .await
+}
+
+/// Like [`rotate`], and with `verify` the replacement must pass [`verify`] before the old key
+/// is deleted. `store` gets the replacement before that, e.g. to put its secret in a sink, so
+/// no key is retired while its successor's secret exists only in memory. A replacement that
+/// fails either step is deleted again and the old key stays untouched.
+pub async fn rotate_verified(
+    client: &NewRelicClient,
+    key_id: &str,
+    key_type: &str,
+    keep_old: bool,
+    verify: bool,
+    store: impl AsyncFn(&[&ApiKey]) -> anyhow::Result<()>,
+) -> anyhow::Result<Rotation> {
+    let (old_key, new_key) = replace(client, key_id, key_type, verify).await?;
+    if let Err(e) = store(&[&new_key]).await {
+        return Err(discard(client, &new_key, unstored(&new_key, e)).await);
+    }
+
+    let mut rotation = Rotation {
+        old_key_id: old_key.id.clone(),
+        new_key,
+        verified: verify,
+        old_key_deleted: false,
+        delete_error: None,
+    };
+    if keep_old {
+        return Ok(rotation);
+    }
+
+    match retire(client, &old_key).await {
+        Ok(()) => rotation.old_key_deleted = true,
+        Err(error) => rotation.delete_error = Some(error),
+    }
+    Ok(rotation)
+}
+
+/// Look up a key and create its replacement, verified with `verify`.
+async fn replace(
+    client: &NewRelicClient,
+    key_id: &str,
+    key_type: &str,
+    verify: bool,
+) -> anyhow::Result<(ApiKey, ApiKey)> {
+    let old_key = inventory::get(client, key_id, key_type).await?;
+    if verify {
+        verifiable(&old_key)?;
+    }
+    let new_key = inventory::create(client, &NewKey::replacing(&old_key)?).await?;
+    if verify {
+        if let Err(e) = self::verify(client, &new_key).await {
+            return Err(discard(client, &new_key, e).await);
+        }
+    }
+    Ok((old_key, new_key))
+}
+
+/// The error of a `store` step that failed for `new_key`.
+pub(crate) fn unstored(new_key: &ApiKey, error: anyhow::Error) -> anyhow::Error {
+    error.context(format!(
+        "Could not store the secret of {}; fix the secret sink and try again",
+        new_key.id
+    ))
+}
+
+/// Delete a key that has been replaced, with why it is still there if the delete failed.
+pub(crate) async fn retire(client: &NewRelicClient, old_key: &ApiKey) -> Result<(), String> {
+    let old = [old_key.id.clone()];
+    let (ingest, user) = if old_key.is_user_key() {
+        (&[][..], &old[..])
+    } else {
+        (&old[..], &[][..])
+    };
+    match inventory::delete_keys(client, ingest, user).await {
+        Ok(outcome) if outcome.deleted.contains(&old_key.id) => Ok(()),
+        Ok(outcome) if outcome.errors.is_empty() => Err("not reported as deleted".to_string()),
+        Ok(outcome) => Err(outcome.errors.join(", ")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Fail for keys whose replacement [`verify`] cannot check.
+pub fn verifiable(key: &ApiKey) -> anyhow::Result<()> {
+    if !key.is_user_key() && key.ingest_type.as_deref() == Some("BROWSER") {
+        anyhow::bail!(
+            "--verify cannot check browser key {}: browser keys only work with a browser \
+             application's agent",
+            key.id
+        );
+    }
+    Ok(())
+}
+
+/// Use a new key once: a user key runs a trivial NerdGraph query, a license key sends a
+/// [`CANARY_EVENT`] to the Event API of its account.
+pub async fn verify(client: &NewRelicClient, key: &ApiKey) -> anyhow::Result<()> {
+    let secret = key
+        .secret()
+        .ok_or_else(|| anyhow::anyhow!("NerdGraph did not return the secret of {}", key.id))?;
+    let mut last_error = None;
+    for attempt in 0..VERIFY_ATTEMPTS {
+        if attempt > 0 {
+            client.sleep(VERIFY_DELAY).await;
+        }
+        let result = if key.is_user_key() {
+            verify_user_key(client, secret).await
+        } else {
+            verify_license_key(client, key, secret).await
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| anyhow::anyhow!("not tried"))
+        .context(format!("The replacement {} does not work", key.id)))
+}
+
+async fn verify_user_key(client: &NewRelicClient, secret: &str) -> anyhow::Result<()> {
+    let data = client
+        .with_api_key(secret)
+        .execute_query(VERIFY_QUERY, None)
+        .await?;
+    if data["actor"]["user"]["id"].is_null() {
+        anyhow::bail!("NerdGraph did not return the key's user");
+    }
+    Ok(())
+}
+
+async fn verify_license_key(
+    client: &NewRelicClient,
+    key: &ApiKey,
+    secret: &str,
+) -> anyhow::Result<()> {
+    let account_id = key
+        .account_id
+        .ok_or_else(|| anyhow::anyhow!("NerdGraph did not return the account of {}", key.id))?;
+    let event = serde_json::json!([{
+        "eventType": CANARY_EVENT,
+        "keyId": key.id,
+    }]);
+    let response = client
+        .post(
+            &events_url(client.endpoint(), account_id),
+            vec![("Api-Key".to_string(), secret.to_string())],
+            &event,
+        )
+        .await?;
+    if !(200..300).contains(&response.status) {
+        anyhow::bail!(
+            "the Event API answered HTTP {} {}",
+            response.status,
+            String::from_utf8_lossy(&response.body).trim()
+        );
+    }
+    Ok(())
+}
+
+/// The Event API of the data center the NerdGraph `endpoint` belongs to.
+fn events_url(endpoint: &str, account_id: i64) -> String {
+    let host = if endpoint.contains(".eu.") {
+        "insights-collector.eu01.nr-data.net"
+    } else if endpoint.contains("gov-api.") {
+        "gov-insights-collector.newrelic.com"
+    } else {
+        "insights-collector.newrelic.com"
+    };
+    format!("https://{}/v1/accounts/{}/events", host, account_id)
+}
+
+/// Delete a replacement that failed verification or could not be stored, returning `error`
+/// with the outcome.
+pub(crate) async fn discard(
+    client: &NewRelicClient,
+    new_key: &ApiKey,
+    error: anyhow::Error,
+) -> anyhow::Error {
+    let id = [new_key.id.clone()];
+    let (ingest, user) = if new_key.is_user_key() {
+        (&[][..], &id[..])
+    } else {
+        (&id[..], &[][..])
+    };
+    match inventory::delete_keys(client, ingest, user).await {
+        Ok(outcome) if outcome.deleted.contains(&new_key.id) => {
+            error.context("Deleted the replacement again; the old key is still active")
+        }
+        _ => error.context(format!(
+            "Could not delete the replacement {}; the old key is still active",
+            new_key.id
+        )),
+    }
+}
+
+#[derive(Serialize)]
+pub struct FailedRotation {
+    pub key_id: String,
+    pub error: String,
+}
+
+/// Outcome of rotating several keys; every requested key ends up in exactly one list.
+#[derive(Serialize, Default)]
+pub struct BulkRotation {
+    pub rotated: Vec<Rotation>,
+    pub failed: Vec<FailedRotation>,
+    /// Keys not attempted because the run was stopped
+    pub pending: Vec<String>,
+    /// Replacements deleted again by an atomic rollback or because `store` failed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rolled_back: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rollback_errors: Vec<String>,
+}
+
+impl BulkRotation {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} rotated, {} failed, {} pending",
+            self.rotated.len(),
+            self.failed.len(),
+            self.pending.len()
+        );
+        if !self.rolled_back.is_empty() || !self.rollback_errors.is_empty() {
+            summary.push_str(&format!(
+                "; rolled back {} replacement(s)",
+                self.rolled_back.len()
+            ));
+        }
+        summary
+    }
+
+    /// Whether the run stopped early or had failures.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.pending.is_empty()
+    }
+}
+
+/// How [`rotate_all`] rotates.
+#[derive(Clone, Copy, Default)]
+pub struct BulkOptions {
+    /// Keep the old keys active instead of deleting them
+    pub keep_old: bool,
+    /// Roll every replacement back if one fails or the run is stopped
+    pub atomic: bool,
+    /// Use each replacement once with [`verify`] before any old key is deleted
+    pub verify: bool,
+}
+
+/// Rotate several keys of one type, checking `stop` before each key.
+///
+/// Every replacement is created (and verified with `verify`) and handed to `store` before any
+/// old key is deleted. Without `atomic`, a key whose replacement fails is reported and the
+/// others are rotated. With `atomic`, a failure or stop deletes the replacements again so the
+/// old keys stay the only ones in use. If `store` fails, the replacements are deleted again and
+/// no old key is touched.
+pub async fn rotate_all(
+    client: &NewRelicClient,
+    key_ids: &[String],
+    key_type: &str,
+    options: BulkOptions,
+    stop: impl Fn() -> bool,
+    store: impl AsyncFn(&[&ApiKey]) -> anyhow::Result<()>,
+) -> BulkRotation {
+    let BulkOptions {
+        keep_old,
+        atomic,
+        verify,
+    } = options;
+    let mut bulk = BulkRotation::default();
+    let mut created = Vec::new();
+    let mut remaining = key_ids.iter();
+    for key_id in remaining.by_ref() {
+        if stop() {
+            bulk.pending.push(key_id.clone());
+            break;
+        }
+        match replace(client, key_id, key_type, verify).await {
+            Ok(pair) => created.push(pair),
+            Err(e) => {
+                bulk.failed.push(FailedRotation {
+                    key_id: key_id.clone(),
+                    error: e.to_string(),
+                });
+                if atomic {
+                    break;
+                }
+            }
+        }
+    }
+    bulk.pending.extend(remaining.cloned());
+
+    if atomic && !bulk.is_complete() {
+        roll_back(client, &mut bulk, &created).await;
+        // Nothing was rotated; the old keys that were already handled are pending again.
+        let handled = created.into_iter().map(|(old_key, _)| old_key.id);
+        bulk.pending.splice(0..0, handled);
+        return bulk;
+    }
+
+    let new_keys: Vec<&ApiKey> = created.iter().map(|(_, new_key)| new_key).collect();
+    if let Err(e) = store(&new_keys).await {
+        roll_back(client, &mut bulk, &created).await;
+        for (old_key, new_key) in created {
+            bulk.failed.push(FailedRotation {
+                key_id: old_key.id,
+                error: format!(
+                    "Could not store the secret of {}: {:#}; the old key is still active",
+                    new_key.id, e
+                ),
+            });
+        }
+        return bulk;
+    }
+
+    let (old_user, old_ingest): (Vec<&ApiKey>, Vec<&ApiKey>) = created
+        .iter()
+        .map(|(old_key, _)| old_key)
+        .partition(|k| k.is_user_key());
+    let outcome = if keep_old || created.is_empty() {
+        None
+    } else {
+        Some(inventory::delete_keys(client, &ids(&old_ingest), &ids(&old_user)).await)
+    };
+    for (old_key, new_key) in created {
+        let mut rotation = Rotation {
+            old_key_id: old_key.id.clone(),
+            new_key,
+            verified: verify,
+            old_key_deleted: false,
+            delete_error: None,
+        };
+        match &outcome {
+            None => {}
+            Some(Ok(outcome)) if outcome.deleted.contains(&old_key.id) => {
+                rotation.old_key_deleted = true
+            }
+            Some(Ok(outcome)) => {
+                rotation.delete_error = Some(if outcome.errors.is_empty() {
+                    "not reported as deleted".to_string()
+                } else {
+                    outcome.errors.join(", ")
+                })
+            }
+            Some(Err(e)) => rotation.delete_error = Some(e.to_string()),
+        }
+        bulk.rotated.push(rotation);
+    }
+    bulk
+}
+
+/// Delete the replacements in `created` again, recording the outcome in `bulk`.
+async fn roll_back(client: &NewRelicClient, bulk: &mut BulkRotation, created: &[(ApiKey, ApiKey)]) {
+    if created.is_empty() {
+        return;
+    }
+    let (user, ingest): (Vec<&ApiKey>, Vec<&ApiKey>) = created
+        .iter()
+        .map(|(_, new_key)| new_key)
+        .partition(|k| k.is_user_key());
+    match inventory::delete_keys(client, &ids(&ingest), &ids(&user)).await {
+        Ok(outcome) => {
+            bulk.rolled_back = outcome.deleted;
+            bulk.rollback_errors = outcome.errors;
+        }
+        Err(e) => bulk.rollback_errors.push(e.to_string()),
+    }
+}
+
+fn ids(keys: &[&ApiKey]) -> Vec<String> {
+    keys.iter().map(|k| k.id.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{HttpRequest, HttpResponse};
+
+    /// Answers key lookups and creates; fails creating the replacement for `bad`.
+    fn nerdgraph(request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+        let query = body["query"].as_str().unwrap_or_default();
+        let data = if query.contains("apiAccessCreateKeys") {
+            let name = body["variables"]["keys"]["ingest"][0]["name"].as_str();
+            if name == Some("bad") {
+                serde_json::json!({ "apiAccessCreateKeys": {
+                    "createdKeys": [], "errors": [{ "message": "quota exceeded" }]
+                }})
+            } else {
+                serde_json::json!({ "apiAccessCreateKeys": { "createdKeys": [{
+                    "id": format!("new-{}", name.unwrap_or_default()), "name": name,
+                    "type": "INGEST", "accountId": 1
+                }], "errors": [] }})
+            }
+        } else if query.contains("apiAccessDeleteKeys") {
+            let ids = &body["variables"]["keys"]["ingestKeyIds"];
+            let deleted: Vec<_> = ids
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|id| serde_json::json!({ "id": id }))
+                .collect();
+            serde_json::json!({ "apiAccessDeleteKeys": { "deletedKeys": deleted, "errors": [] }})
+        } else {
+            let id = body["variables"]["id"].as_str().unwrap_or_default();
+            serde_json::json!({ "actor": { "apiAccess": { "key": {
+                "id": id, "name": id, "type": "INGEST", "accountId": 1
+            }}}})
+        };
+        Ok(HttpResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: serde_json::to_vec(&serde_json::json!({ "data": data }))?,
+        })
+    }
+
+    fn client() -> NewRelicClient {
+        NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .transport(nerdgraph)
+            .build()
+            .unwrap()
+    }
+
+    fn key_ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_atomic_failure_rolls_back_replacements() {
+        let options = BulkOptions {
+            atomic: true,
+            ..BulkOptions::default()
+        };
+        let bulk = rotate_all(
+            &client(),
+            &key_ids(&["a", "bad", "c"]),
+            "INGEST",
+            options,
+            || false,
+            async |_: &[&ApiKey]| Ok(()),
+        )
+        .await;
+        assert!(bulk.rotated.is_empty());
+        assert_eq!(bulk.failed[0].key_id, "bad");
+        assert_eq!(bulk.pending, ["a", "c"]);
+        assert_eq!(bulk.rolled_back, ["new-a"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_verification_keeps_old_key() {
+        let deleted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = deleted.clone();
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .transport(move |request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                if request.url.ends_with("/v1/accounts/1/events") {
+                    assert_eq!(request.header("Api-Key"), Some("NRAL-NEW"));
+                    return Ok(HttpResponse {
+                        status: 403,
+                        headers: Vec::new(),
+                        body: b"{\"error\": \"invalid key\"}".to_vec(),
+                    });
+                }
+                let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+                let query = body["query"].as_str().unwrap_or_default();
+                let data = if query.contains("apiAccessCreateKeys") {
+                    serde_json::json!({ "apiAccessCreateKeys": { "createdKeys": [{
+                        "id": "new-a", "name": "a", "type": "INGEST", "ingestType": "LICENSE",
+                        "key": "NRAL-NEW", "accountId": 1
+                    }], "errors": [] }})
+                } else if query.contains("apiAccessDeleteKeys") {
+                    let ids = body["variables"]["keys"]["ingestKeyIds"].clone();
+                    seen.lock().unwrap().push(ids.clone());
+                    let deleted: Vec<_> = ids
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(|id| serde_json::json!({ "id": id }))
+                        .collect();
+                    serde_json::json!({ "apiAccessDeleteKeys": { "deletedKeys": deleted, "errors": [] }})
+                } else {
+                    serde_json::json!({ "actor": { "apiAccess": { "key": {
+                        "id": "a", "name": "a", "type": "INGEST", "ingestType": "LICENSE",
+                        "accountId": 1
+                    }}}})
+                };
+                Ok(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: serde_json::to_vec(&serde_json::json!({ "data": data }))?,
+                })
+            })
+            .build()
+            .unwrap();
+
+        let error = rotate_verified(
+            &client,
+            "a",
+            "INGEST",
+            false,
+            true,
+            async |_: &[&ApiKey]| Ok(()),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(format!("{:#}", error).contains("HTTP 403"));
+        // Only the replacement was deleted.
+        assert_eq!(*deleted.lock().unwrap(), vec![serde_json::json!(["new-a"])]);
+    }
+
+    #[test]
+    fn test_events_url_follows_region() {
+        assert_eq!(
+            events_url("https://api.eu.newrelic.com/graphql", 7),
+            "https://insights-collector.eu01.nr-data.net/v1/accounts/7/events"
+        );
+        assert_eq!(
+            events_url(crate::client::DEFAULT_ENDPOINT, 7),
+            "https://insights-collector.newrelic.com/v1/accounts/7/events"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_leaves_remaining_keys_pending() {
+        let calls = std::cell::Cell::new(0);
+        let bulk = rotate_all(
+            &client(),
+            &key_ids(&["a", "b", "c"]),
+            "INGEST",
+            BulkOptions::default(),
+            || {
+                calls.set(calls.get() + 1);
+                calls.get() > 1
+            },
+            async |_: &[&ApiKey]| Ok(()),
+        )
+        .await;
+        assert_eq!(bulk.rotated.len(), 1);
+        assert!(bulk.rotated[0].old_key_deleted);
+        assert_eq!(bulk.pending, ["b", "c"]);
+        assert_eq!(bulk.summary(), "1 rotated, 0 failed, 2 pending");
+    }
+
+    #[tokio::test]
+    async fn test_failed_store_keeps_the_old_keys() {
+        let error = rotate_verified(
+            &client(),
+            "a",
+            "INGEST",
+            false,
+            false,
+            async |_: &[&ApiKey]| anyhow::bail!("Vault is sealed"),
+        )
+        .await
+        .err()
+        .unwrap();
+        let message = format!("{:#}", error);
+        assert!(message.contains("Could not store the secret of new-a"));
+        assert!(message.contains("Deleted the replacement again"));
+
+        let stored = std::cell::Cell::new(0);
+        let bulk = rotate_all(
+            &client(),
+            &key_ids(&["a", "b"]),
+            "INGEST",
+            BulkOptions::default(),
+            || false,
+            async |keys: &[&ApiKey]| {
+                stored.set(keys.len());
+                anyhow::bail!("Vault is sealed")
+            },
+        )
+        .await;
+        assert_eq!(stored.get(), 2);
+        assert!(bulk.rotated.is_empty());
+        assert_eq!(bulk.failed.len(), 2);
+        assert_eq!(bulk.rolled_back, ["new-a", "new-b"]);
+    }
+}
@@ -0,0 +1,447 @@
+//! `scan`: find New Relic key secrets hardcoded in files and tell which of them are live keys.
+//! `hooks install` runs it on the staged files before every commit.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::inventory::{self, ApiKey};
+use crate::output::{Format, Renderer};
+use crate::prompt::Confirmation;
+use crate::sink::Secrets;
+use crate::{key_type_from_prefix, rotation, NewRelicClient};
+
+/// Directories that hold build output or third-party code rather than the project's sources.
+const SKIPPED_DIRS: [&str; 6] = [".git", "target", "node_modules", "vendor", ".venv", "dist"];
+
+/// Files larger than this are not scanned.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Key prefixes followed by a run of key characters, and the shortest run that counts.
+const PREFIXES: [(&str, usize); 3] = [("NRAK-", 27), ("NRII-", 32), ("NRJS-", 19)];
+
+/// A license key is 36 hexadecimal characters followed by `NRAL`.
+const LICENSE_SUFFIX: &str = "NRAL";
+const LICENSE_HEX_LEN: usize = 36;
+
+fn is_key_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'-'
+}
+
+/// A key-shaped string in a file.
+#[derive(Debug, PartialEq)]
+pub struct Match {
+    pub path: PathBuf,
+    /// 1-based line and column
+    pub line: usize,
+    pub column: usize,
+    pub secret: String,
+}
+
+/// Every key-shaped string in `text`.
+pub fn scan_text(path: &Path, text: &str) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let bytes = line.as_bytes();
+        let boundary = |at: usize| at == 0 || !is_key_char(bytes[at - 1]);
+        let mut found = |start: usize, end: usize| {
+            matches.push(Match {
+                path: path.to_path_buf(),
+                line: index + 1,
+                column: line[..start].chars().count() + 1,
+                secret: line[start..end].to_string(),
+            })
+        };
+
+        for (prefix, min_len) in PREFIXES {
+            for (start, _) in line.match_indices(prefix) {
+                let body = start + prefix.len();
+                let end = body
+                    + bytes[body..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_alphanumeric())
+                        .count();
+                if boundary(start) && end - body >= min_len {
+                    found(start, end);
+                }
+            }
+        }
+        for (suffix, _) in line.match_indices(LICENSE_SUFFIX) {
+            let end = suffix + LICENSE_SUFFIX.len();
+            let Some(start) = suffix.checked_sub(LICENSE_HEX_LEN) else {
+                continue;
+            };
+            let hex = bytes[start..suffix].iter().all(|c| c.is_ascii_hexdigit());
+            let ends = end == bytes.len() || !is_key_char(bytes[end]);
+            if hex && ends && boundary(start) {
+                found(start, end);
+            }
+        }
+    }
+    matches.sort_by_key(|m| (m.line, m.column));
+    matches
+}
+
+/// Every key-shaped string in the files under `root`, skipping build directories, large files
+/// and binary files.
+pub fn scan_dir(root: &Path) -> anyhow::Result<Vec<Match>> {
+    let mut matches = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let metadata = std::fs::symlink_metadata(&path)
+            .map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+        if metadata.is_dir() {
+            let skipped = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| SKIPPED_DIRS.contains(&name));
+            if skipped && path != root {
+                continue;
+            }
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(&path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<_, _>>()?;
+            // Reverse order so popping visits the entries alphabetically.
+            entries.sort_by(|a, b| b.cmp(a));
+            pending.extend(entries);
+        } else if metadata.is_file() && metadata.len() <= MAX_FILE_SIZE {
+            let bytes = std::fs::read(&path)?;
+            if bytes.contains(&0) {
+                continue;
+            }
+            matches.extend(scan_text(&path, &String::from_utf8_lossy(&bytes)));
+        }
+    }
+    Ok(matches)
+}
+
+/// Every key-shaped string in the files staged in the git repository at `repo`, as they will
+/// be committed. Paths are relative to the repository root.
+pub fn scan_staged(repo: &Path) -> anyhow::Result<Vec<Match>> {
+    let git = |args: &[&str]| -> anyhow::Result<Vec<u8>> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(args)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Could not run git: {}", e))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.stdout)
+    };
+    let names = git(&[
+        "diff",
+        "--cached",
+        "--name-only",
+        "-z",
+        "--diff-filter=ACMR",
+    ])?;
+    let mut matches = Vec::new();
+    for name in names.split(|c| *c == 0).filter(|name| !name.is_empty()) {
+        let name = String::from_utf8_lossy(name);
+        let bytes = git(&["show", &format!(":{}", name)])?;
+        if bytes.len() as u64 > MAX_FILE_SIZE || bytes.contains(&0) {
+            continue;
+        }
+        matches.extend(scan_text(
+            Path::new(name.as_ref()),
+            &String::from_utf8_lossy(&bytes),
+        ));
+    }
+    Ok(matches)
+}
+
+/// Whether `path` matches `pattern`, where `*` and `?` match within one path component and `**`
+/// matches any number of components.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[u8], path: &[u8]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            [b'*', b'*', b'/', rest @ ..] => {
+                matches(rest, path)
+                    || path
+                        .iter()
+                        .enumerate()
+                        .any(|(i, c)| *c == b'/' && matches(rest, &path[i + 1..]))
+            }
+            [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+            [b'*', rest @ ..] => (0..=path.len())
+                .take_while(|i| *i == 0 || path[i - 1] != b'/')
+                .any(|i| matches(rest, &path[i..])),
+            [b'?', rest @ ..] => !path.is_empty() && path[0] != b'/' && matches(rest, &path[1..]),
+            [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+        }
+    }
+    let path = path.replace('\\', "/");
+    matches(pattern.as_bytes(), path.as_bytes())
+}
+
+/// The start and end of a secret, enough to recognize it but useless to an attacker.
+pub fn redact(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..7].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+/// A hardcoded secret and the live key it belongs to, if any.
+#[derive(Serialize)]
+pub struct Leak {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub kind: &'static str,
+    /// The secret, redacted
+    pub secret: String,
+    /// `live` when the secret belongs to a key in the scanned accounts, `allowed` for a live key
+    /// on the allowlist, `unknown` otherwise
+    pub status: &'static str,
+    pub key_id: Option<String>,
+    pub key_type: Option<String>,
+    pub key_name: Option<String>,
+    pub account_id: Option<i64>,
+}
+
+fn leaks(matches: Vec<Match>, live: &[ApiKey], allow_key_ids: &[String]) -> Vec<Leak> {
+    let by_secret: HashMap<&str, &ApiKey> = live
+        .iter()
+        .filter_map(|key| Some((key.secret()?, key)))
+        .collect();
+    matches
+        .into_iter()
+        .map(|m| {
+            let key = by_secret.get(m.secret.as_str());
+            Leak {
+                kind: key_type_from_prefix(&m.secret),
+                secret: redact(&m.secret),
+                status: match key {
+                    Some(key) if allow_key_ids.contains(&key.id) => "allowed",
+                    Some(_) => "live",
+                    None => "unknown",
+                },
+                key_id: key.map(|k| k.id.clone()),
+                key_type: key.and_then(|k| k.key_type.clone()),
+                key_name: key.and_then(|k| k.name.clone()),
+                account_id: key.and_then(|k| k.account_id),
+                path: m.path,
+                line: m.line,
+                column: m.column,
+            }
+        })
+        .collect()
+}
+
+const HEADERS: [&str; 6] = ["LOCATION", "KIND", "SECRET", "STATUS", "KEY ID", "NAME"];
+
+/// The keys a scan of `root` found.
+struct Leaks<'a> {
+    root: &'a Path,
+    leaks: &'a [Leak],
+}
+
+impl Renderer for Leaks<'_> {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self.leaks)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.leaks
+            .iter()
+            .map(|leak| {
+                vec![
+                    format!("{}:{}:{}", leak.path.display(), leak.line, leak.column),
+                    leak.kind.to_string(),
+                    leak.secret.clone(),
+                    leak.status.to_string(),
+                    leak.key_id.clone().unwrap_or_default(),
+                    leak.key_name.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+
+    fn empty_message(&self) -> Option<String> {
+        Some(format!(
+            "No New Relic keys found under {}",
+            self.root.display()
+        ))
+    }
+}
+
+pub struct ScanOptions {
+    /// Scan the files staged for the next commit instead of the working tree
+    pub staged: bool,
+    /// Path globs and key IDs whose findings are accepted
+    pub allow_paths: Vec<String>,
+    pub allow_key_ids: Vec<String>,
+    pub rotate: bool,
+    pub keep_old: bool,
+    pub confirmation: Confirmation,
+}
+
+/// Scan `root`, match the secrets against the keys of the accounts `connect` returns and print
+/// the results. Fails when a live key is hardcoded, after rotating the affected keys if asked
+/// to. `connect` is only called when something key-shaped was found, so a clean scan works
+/// without credentials.
+pub async fn run<'a>(
+    connect: impl FnOnce() -> anyhow::Result<(&'a NewRelicClient, Vec<i64>)>,
+    root: &Path,
+    format: Format,
+    secrets: &Secrets,
+    options: ScanOptions,
+) -> anyhow::Result<()> {
+    let mut matches = if options.staged {
+        scan_staged(root)?
+    } else {
+        scan_dir(root)?
+    };
+    matches.retain(|m| {
+        let relative = m.path.strip_prefix(root).unwrap_or(&m.path);
+        !options
+            .allow_paths
+            .iter()
+            .any(|pattern| glob_match(pattern, &relative.to_string_lossy()))
+    });
+    let client = if matches.is_empty() {
+        None
+    } else {
+        Some(connect()?)
+    };
+    let live = match &client {
+        Some((client, account_ids)) => {
+            inventory::fetch(client, account_ids, &["INGEST", "USER"]).await?
+        }
+        None => Vec::new(),
+    };
+    let leaks = leaks(matches, &live, &options.allow_key_ids);
+
+    let rendered = Leaks {
+        root,
+        leaks: &leaks,
+    }
+    .render(&format)?;
+    print!("{}", rendered);
+
+    let mut seen = HashSet::new();
+    let compromised: Vec<&Leak> = leaks
+        .iter()
+        .filter(|leak| leak.status == "live")
+        .filter(|leak| seen.insert(leak.key_id.clone()))
+        .collect();
+    let Some((client, _)) = client.filter(|_| !compromised.is_empty()) else {
+        return Ok(());
+    };
+
+    if options.rotate {
+        let question = format!(
+            "Rotate {} hardcoded key(s){}?",
+            compromised.len(),
+            if options.keep_old {
+                ""
+            } else {
+                " and delete the old ones"
+            }
+        );
+        secrets.check()?;
+        if options.confirmation.confirm(&question)? {
+            for leak in &compromised {
+                let (Some(id), Some(key_type)) = (&leak.key_id, &leak.key_type) else {
+                    continue;
+                };
+                let rotation = rotation::rotate_verified(
+                    client,
+                    id,
+                    key_type,
+                    options.keep_old,
+                    false,
+                    async |keys: &[&ApiKey]| secrets.publish("Rotated API key", keys).await,
+                )
+                .await?;
+                secrets.print(&rotation, &[&rotation.new_key])?;
+            }
+        }
+    }
+    anyhow::bail!(
+        "{} live key(s) hardcoded under {}",
+        compromised.len(),
+        root.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER_KEY: &str = "NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0";
+    const LICENSE_KEY: &str = "0123456789abcdef0123456789abcdef0123NRAL";
+
+    #[test]
+    fn test_scan_text_finds_key_shapes() {
+        let text = format!(
+            "api_key = \"{}\"\n\
+             license_key: {}  # prod\n\
+             not a key: XNRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0 NRAK-SHORT {}NRAL\n\
+             NRJS-0123456789abcdef012",
+            USER_KEY,
+            LICENSE_KEY,
+            "g".repeat(36)
+        );
+        let matches = scan_text(Path::new("app.toml"), &text);
+        let found: Vec<(usize, usize, &str)> = matches
+            .iter()
+            .map(|m| (m.line, m.column, m.secret.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, 12, USER_KEY),
+                (2, 14, LICENSE_KEY),
+                (4, 1, "NRJS-0123456789abcdef012"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leaks_match_live_keys_without_exposing_secrets() {
+        let live = ApiKey::fixture(serde_json::json!({
+            "id": "K1", "name": "prod", "type": "INGEST",
+            "key": LICENSE_KEY, "createdAt": 1, "accountId": 1
+        }));
+        let matches = scan_text(
+            Path::new("a.env"),
+            &format!("{}\n{}", LICENSE_KEY, USER_KEY),
+        );
+        let leaks = leaks(matches, &[live], &[]);
+        assert_eq!(leaks[0].status, "live");
+        assert_eq!(leaks[0].key_id.as_deref(), Some("K1"));
+        assert_eq!(leaks[0].kind, "INGEST (license)");
+        assert_eq!(leaks[0].secret, "0123456...NRAL");
+        assert_eq!(leaks[1].status, "unknown");
+        assert_eq!(leaks[1].kind, "USER");
+        assert_eq!(redact("NRAK-SHORT"), "**********");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("tests/fixtures/**", "tests/fixtures/a/b.env"));
+        assert!(glob_match("**/*.md", "README.md"));
+        assert!(glob_match("**/*.md", "docs/setup/keys.md"));
+        assert!(glob_match("docs/*.md", "docs/keys.md"));
+        assert!(!glob_match("docs/*.md", "docs/setup/keys.md"));
+        assert!(glob_match("config/?.toml", "config\\a.toml"));
+        assert!(!glob_match("*.env", "app/.env"));
+    }
+}
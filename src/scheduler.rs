@@ -0,0 +1,296 @@
+//! Delayed deletions: `rotate --delete-after 7d` keeps the old key and queues its deletion in a
+//! local file, `scheduler run` (from cron or a systemd timer) deletes the keys that are due, and
+//! `scheduler list` / `scheduler cancel` show and withdraw what is queued. Every change to the
+//! queue is made under a lock on a `.lock` file next to it, so a run cannot undo a deletion
+//! queued or cancelled while it was deleting keys.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::output::{Format, Renderer};
+use crate::{config, inventory, NewRelicClient};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deletion {
+    pub key_id: String,
+    pub key_type: String,
+    pub account_id: Option<i64>,
+    pub name: Option<String>,
+    /// The key that replaces this one
+    pub replaced_by: Option<String>,
+    pub scheduled_at: DateTime<Utc>,
+    pub delete_after: DateTime<Utc>,
+    /// Why the last attempt failed; the deletion is tried again on the next run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Queue {
+    deletions: Vec<Deletion>,
+}
+
+/// The queued deletions, soonest first.
+pub fn load(path: &Path) -> anyhow::Result<Vec<Deletion>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow::anyhow!("Unable to read {}: {}", path.display(), e)),
+    };
+    let mut queue: Queue = serde_json::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("Invalid schedule {}: {}", path.display(), e))?;
+    queue.deletions.sort_by_key(|d| d.delete_after);
+    Ok(queue.deletions)
+}
+
+fn save(path: &Path, deletions: Vec<Deletion>) -> anyhow::Result<()> {
+    let queue = Queue { deletions };
+    config::write_private(path, &(serde_json::to_string_pretty(&queue)? + "\n"))
+}
+
+/// Change the queue at `path` while holding its lock, which other processes wait for.
+fn update<T>(path: &Path, change: impl FnOnce(&mut Vec<Deletion>) -> T) -> anyhow::Result<T> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lock = File::create(&lock_path)
+        .map_err(|e| anyhow::anyhow!("Unable to create {}: {}", lock_path.display(), e))?;
+    lock.lock()
+        .map_err(|e| anyhow::anyhow!("Unable to lock {}: {}", lock_path.display(), e))?;
+    let mut deletions = load(path)?;
+    let result = change(&mut deletions);
+    save(path, deletions)?;
+    Ok(result)
+}
+
+/// Queue a deletion, replacing one already queued for the same key.
+pub fn schedule(path: &Path, deletion: Deletion) -> anyhow::Result<()> {
+    update(path, |deletions| {
+        deletions.retain(|d| d.key_id != deletion.key_id);
+        deletions.push(deletion);
+    })
+}
+
+/// Withdraw the deletion of `key_id`, returning it if one was queued.
+pub fn cancel(path: &Path, key_id: &str) -> anyhow::Result<Option<Deletion>> {
+    update(path, |deletions| {
+        let index = deletions.iter().position(|d| d.key_id == key_id)?;
+        Some(deletions.remove(index))
+    })
+}
+
+/// What one `scheduler run` did.
+#[derive(Debug, Default, Serialize)]
+pub struct Run {
+    pub deleted: Vec<String>,
+    pub failed: Vec<Deletion>,
+    /// Deletions that are not due yet
+    pub waiting: usize,
+}
+
+/// Delete every key whose time has come. Failed deletions stay queued for the next run, with
+/// the reason in `last_error`.
+pub async fn run(
+    client: &NewRelicClient,
+    path: &Path,
+    now: DateTime<Utc>,
+    dry_run: bool,
+) -> anyhow::Result<Run> {
+    let (due, waiting): (Vec<Deletion>, Vec<Deletion>) =
+        load(path)?.into_iter().partition(|d| d.delete_after <= now);
+    let mut run = Run {
+        waiting: waiting.len(),
+        ..Run::default()
+    };
+    if dry_run || due.is_empty() {
+        run.deleted = due.into_iter().map(|d| d.key_id).collect();
+        return Ok(run);
+    }
+
+    let ids = |user: bool| -> Vec<String> {
+        due.iter()
+            .filter(|d| (d.key_type == "USER") == user)
+            .map(|d| d.key_id.clone())
+            .collect()
+    };
+    let (deleted, error) = match inventory::delete_keys(client, &ids(false), &ids(true)).await {
+        Ok(outcome) if outcome.errors.is_empty() => {
+            (outcome.deleted, "not reported as deleted".to_string())
+        }
+        Ok(outcome) => (outcome.deleted, outcome.errors.join(", ")),
+        Err(e) => (Vec::new(), format!("{:#}", e)),
+    };
+    for mut deletion in due {
+        if deleted.contains(&deletion.key_id) {
+            run.deleted.push(deletion.key_id);
+        } else {
+            deletion.last_error = Some(error.clone());
+            run.failed.push(deletion);
+        }
+    }
+
+    // Merged into the queue as it is now: deletions may have been queued, rescheduled or
+    // cancelled since it was read.
+    update(path, |deletions| {
+        deletions.retain(|d| !run.deleted.contains(&d.key_id));
+        for deletion in deletions.iter_mut() {
+            let failed = run.failed.iter().find(|failed| {
+                failed.key_id == deletion.key_id && failed.delete_after == deletion.delete_after
+            });
+            if let Some(failed) = failed {
+                deletion.last_error = failed.last_error.clone();
+            }
+        }
+    })?;
+    Ok(run)
+}
+
+const HEADERS: [&str; 6] = [
+    "KEY ID",
+    "TYPE",
+    "ACCOUNT",
+    "NAME",
+    "DELETE AFTER",
+    "LAST ERROR",
+];
+
+impl Renderer for [Deletion] {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|d| {
+                vec![
+                    d.key_id.clone(),
+                    d.key_type.clone(),
+                    d.account_id.map(|id| id.to_string()).unwrap_or_default(),
+                    d.name.clone().unwrap_or_default(),
+                    d.delete_after.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    d.last_error.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+
+    fn empty_message(&self) -> Option<String> {
+        Some("No deletions scheduled".to_string())
+    }
+}
+
+pub fn print(deletions: &[Deletion], format: Format) -> anyhow::Result<()> {
+    print!("{}", deletions.render(&format)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{HttpRequest, HttpResponse};
+
+    fn deletion(key_id: &str, delete_after: DateTime<Utc>) -> Deletion {
+        Deletion {
+            key_id: key_id.to_string(),
+            key_type: "INGEST".to_string(),
+            account_id: Some(1),
+            name: None,
+            replaced_by: None,
+            scheduled_at: delete_after,
+            delete_after,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn test_schedule_and_cancel() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedule.json");
+        let now = Utc::now();
+        schedule(&path, deletion("b", now + chrono::Duration::days(2))).unwrap();
+        schedule(&path, deletion("a", now + chrono::Duration::days(1))).unwrap();
+        schedule(&path, deletion("b", now + chrono::Duration::days(3))).unwrap();
+        let queued: Vec<String> = load(&path).unwrap().into_iter().map(|d| d.key_id).collect();
+        assert_eq!(queued, ["a", "b"]);
+
+        assert!(cancel(&path, "a").unwrap().is_some());
+        assert!(cancel(&path, "a").unwrap().is_none());
+        assert_eq!(load(&path).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_deletes_due_keys_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedule.json");
+        let now = Utc::now();
+        schedule(&path, deletion("due", now - chrono::Duration::hours(1))).unwrap();
+        schedule(&path, deletion("later", now + chrono::Duration::days(1))).unwrap();
+        let queued_meanwhile = path.clone();
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .transport(
+                move |request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                    // A rotation queues another deletion while the run is deleting.
+                    schedule(
+                        &queued_meanwhile,
+                        deletion("meanwhile", now + chrono::Duration::days(2)),
+                    )?;
+                    let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+                    assert_eq!(
+                        body["variables"]["keys"]["ingestKeyIds"],
+                        serde_json::json!(["due"])
+                    );
+                    let data = serde_json::json!({ "apiAccessDeleteKeys": {
+                        "deletedKeys": [{ "id": "due" }], "errors": []
+                    }});
+                    Ok(HttpResponse {
+                        status: 200,
+                        headers: Vec::new(),
+                        body: serde_json::to_vec(&serde_json::json!({ "data": data }))?,
+                    })
+                },
+            )
+            .build()
+            .unwrap();
+
+        let run = run(&client, &path, now, false).await.unwrap();
+        assert_eq!(run.deleted, ["due"]);
+        assert_eq!(run.waiting, 1);
+        let queued: Vec<String> = load(&path).unwrap().into_iter().map(|d| d.key_id).collect();
+        assert_eq!(queued, ["later", "meanwhile"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_request_is_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedule.json");
+        let now = Utc::now();
+        schedule(&path, deletion("due", now - chrono::Duration::hours(1))).unwrap();
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .transport(|_: HttpRequest| -> anyhow::Result<HttpResponse> {
+                anyhow::bail!("connection refused")
+            })
+            .build()
+            .unwrap();
+
+        let run = run(&client, &path, now, false).await.unwrap();
+        assert_eq!(run.failed.len(), 1);
+        let queued = load(&path).unwrap();
+        assert!(queued[0]
+            .last_error
+            .as_deref()
+            .unwrap()
+            .contains("connection refused"));
+    }
+}
@@ -0,0 +1,140 @@
+//! Checks that NerdGraph responses still contain the fields the typed models read.
+//!
+//! The models make most fields optional, so a field NerdGraph stops returning would quietly
+//! deserialize to `None` and print as null. GraphQL always returns every selected field, null or
+//! not, which makes an absent one a reliable sign that the schema changed under the query.
+//! By default the client reports drift and carries on; with
+//! [`NewRelicClientBuilder::strict_schema`](crate::NewRelicClientBuilder::strict_schema) it is an
+//! error.
+
+use std::fmt;
+
+use crate::transport::MaybeSync;
+
+/// Fields missing from a response.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaDrift {
+    /// The response the fields are missing from, e.g. `keySearch`
+    pub context: String,
+    pub missing: Vec<String>,
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "NerdGraph {} response is missing {}; the schema may have changed",
+            self.context,
+            self.missing.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for SchemaDrift {}
+
+/// Receives the drift a non-strict client tolerated, e.g. to log it.
+pub trait DriftHandler: Fn(&SchemaDrift) + MaybeSync {}
+impl<F: Fn(&SchemaDrift) + MaybeSync> DriftHandler for F {}
+
+/// The `paths` absent from `value`. Paths are dotted field names; `keys[].id` checks `id` in
+/// every element of the `keys` list. Null values count as present and are not descended into.
+pub fn missing_fields(value: &serde_json::Value, paths: &[&str]) -> Vec<String> {
+    paths
+        .iter()
+        .filter(|path| {
+            let segments: Vec<&str> = path.split('.').collect();
+            !present(value, &segments)
+        })
+        .map(|path| path.to_string())
+        .collect()
+}
+
+fn present(value: &serde_json::Value, segments: &[&str]) -> bool {
+    let Some((segment, rest)) = segments.split_first() else {
+        return true;
+    };
+    if value.is_null() {
+        return true;
+    }
+    let (name, each) = match segment.strip_suffix("[]") {
+        Some(name) => (name, true),
+        None => (*segment, false),
+    };
+    let Some(field) = value.get(name) else {
+        return false;
+    };
+    match field.as_array() {
+        Some(items) if each => items.iter().all(|item| present(item, rest)),
+        _ => present(field, rest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields() {
+        let value = serde_json::json!({
+            "keys": [
+                {"id": "a", "name": null, "createdAt": 1},
+                {"id": "b", "name": "ci"}
+            ],
+            "nextCursor": null
+        });
+        assert_eq!(
+            missing_fields(
+                &value,
+                &[
+                    "keys[].id",
+                    "keys[].name",
+                    "keys[].createdAt",
+                    "nextCursor",
+                    "total"
+                ]
+            ),
+            vec!["keys[].createdAt", "total"]
+        );
+        assert!(missing_fields(&serde_json::json!({"keys": []}), &["keys[].id"]).is_empty());
+        assert!(missing_fields(&serde_json::json!({"key": null}), &["key.id"]).is_empty());
+    }
+
+    #[test]
+    fn test_check_schema_reports_or_fails() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::transport::{HttpRequest, HttpResponse};
+        use crate::NewRelicClient;
+
+        let no_network = |_request: HttpRequest| -> anyhow::Result<HttpResponse> {
+            unreachable!("check_schema does not send requests")
+        };
+        let value = serde_json::json!({"key": {"id": "a"}});
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let lenient = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .transport(no_network)
+            .on_schema_drift(move |drift: &SchemaDrift| sink.lock().unwrap().push(drift.clone()))
+            .build()
+            .unwrap();
+        lenient
+            .check_schema("key", &value, &["key.id", "key.name"])
+            .unwrap();
+        assert_eq!(reported.lock().unwrap()[0].missing, vec!["key.name"]);
+
+        let strict = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .transport(no_network)
+            .strict_schema(true)
+            .build()
+            .unwrap();
+        let error = strict
+            .check_schema("key", &value, &["key.name"])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "NerdGraph key response is missing key.name; the schema may have changed"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! [`SecretString`], the type key secrets and API keys are held in.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// A key secret or API key. Its memory is zeroed when it is dropped, and `Debug` prints
+/// `[REDACTED]`, so secrets do not end up in logs through `{:?}` or in freed memory. There is no
+/// `Display`; reading the value takes an explicit [`SecretString::expose`].
+///
+/// It serializes as the plain string, since printing a newly created key is the point of
+/// `create` and `rotate`; types that must never carry the secret leave it out instead.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted_and_serde_is_transparent() {
+        let secret = SecretString::from("NRAK-SECRET");
+        assert_eq!(format!("{:?}", secret), "[REDACTED]");
+        assert_eq!(format!("{:?}", Some(&secret)), "Some([REDACTED])");
+        assert_eq!(secret.expose(), "NRAK-SECRET");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"NRAK-SECRET\"");
+        let parsed: SecretString = serde_json::from_str("\"NRII-X\"").unwrap();
+        assert_eq!(parsed.expose(), "NRII-X");
+    }
+}
@@ -0,0 +1,184 @@
+//! Label selectors for `--selector`, in the Kubernetes syntax. Keys carry labels as `name:value`
+//! words in their notes, like the `env:<name>` label of [`environment`](crate::environment);
+//! `env=staging,team in (payments,web),!legacy` selects the keys labeled `env:staging` and
+//! `team:payments` or `team:web` that have no `legacy` label.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    NotExists(String),
+}
+
+/// Every requirement must hold for a key to match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector(Vec<Requirement>);
+
+/// The `name:value` labels in a key's notes.
+pub fn labels(notes: Option<&str>) -> BTreeMap<&str, &str> {
+    notes
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|word| word.split_once(':'))
+        .filter(|(name, value)| is_name(name) && !value.is_empty() && !value.starts_with('/'))
+        .collect()
+}
+
+fn is_name(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
+/// Split at the commas that are not inside a `(...)` value set.
+fn split_requirements(text: &str) -> anyhow::Result<Vec<&str>> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => anyhow::bail!("unmatched ')'"),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        anyhow::bail!("unmatched '('");
+    }
+    parts.push(&text[start..]);
+    Ok(parts)
+}
+
+fn parse_set(text: &str) -> anyhow::Result<Vec<String>> {
+    let inner = text
+        .trim()
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or_else(|| anyhow::anyhow!("expected a set of values like (a,b) after in/notin"))?;
+    let values: Vec<String> = inner.split(',').map(|v| v.trim().to_string()).collect();
+    if values.iter().any(|v| !is_name(v)) {
+        anyhow::bail!("invalid value in ({})", inner);
+    }
+    Ok(values)
+}
+
+fn parse_requirement(text: &str) -> anyhow::Result<Requirement> {
+    let text = text.trim();
+    let name = |name: &str| -> anyhow::Result<String> {
+        let name = name.trim();
+        if !is_name(name) {
+            anyhow::bail!("invalid label name '{}'", name);
+        }
+        Ok(name.to_string())
+    };
+    let value =
+        |value: &str| name(value).map_err(|_| anyhow::anyhow!("invalid value '{}'", value.trim()));
+    for (operator, negated) in [(" notin ", true), (" in ", false)] {
+        if let Some((label, set)) = text.split_once(operator) {
+            let (label, set) = (name(label)?, parse_set(set)?);
+            return Ok(if negated {
+                Requirement::NotIn(label, set)
+            } else {
+                Requirement::In(label, set)
+            });
+        }
+    }
+    if let Some((label, v)) = text.split_once("!=") {
+        return Ok(Requirement::NotEquals(name(label)?, value(v)?));
+    }
+    if let Some((label, v)) = text.split_once("==").or_else(|| text.split_once('=')) {
+        return Ok(Requirement::Equals(name(label)?, value(v)?));
+    }
+    match text.strip_prefix('!') {
+        Some(label) => Ok(Requirement::NotExists(name(label)?)),
+        None => Ok(Requirement::Exists(name(text)?)),
+    }
+}
+
+impl Selector {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let invalid = |e: anyhow::Error| anyhow::anyhow!("Invalid selector '{}': {}", text, e);
+        let requirements = split_requirements(text)
+            .map_err(invalid)?
+            .into_iter()
+            .filter(|part| !part.trim().is_empty())
+            .map(parse_requirement)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(invalid)?;
+        if requirements.is_empty() {
+            return Err(invalid(anyhow::anyhow!("no requirements")));
+        }
+        Ok(Selector(requirements))
+    }
+
+    /// Whether a key with these notes matches.
+    pub fn matches(&self, notes: Option<&str>) -> bool {
+        let labels = labels(notes);
+        self.0.iter().all(|requirement| match requirement {
+            Requirement::Equals(name, value) => labels.get(name.as_str()) == Some(&value.as_str()),
+            // As in Kubernetes, keys without the label match != and notin.
+            Requirement::NotEquals(name, value) => {
+                labels.get(name.as_str()) != Some(&value.as_str())
+            }
+            Requirement::In(name, values) => labels
+                .get(name.as_str())
+                .is_some_and(|label| values.iter().any(|v| v == label)),
+            Requirement::NotIn(name, values) => labels
+                .get(name.as_str())
+                .is_none_or(|label| !values.iter().any(|v| v == label)),
+            Requirement::Exists(name) => labels.contains_key(name.as_str()),
+            Requirement::NotExists(name) => !labels.contains_key(name.as_str()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        let labels = labels(Some("owner: platform env:prod team:payments see https://x"));
+        assert_eq!(
+            labels.into_iter().collect::<Vec<_>>(),
+            [("env", "prod"), ("team", "payments")]
+        );
+    }
+
+    #[test]
+    fn test_selector_matches() {
+        let notes = Some("env:staging team:payments");
+        let matches = |selector: &str| Selector::parse(selector).unwrap().matches(notes);
+        assert!(matches("env=staging,team=payments"));
+        assert!(matches("env==staging"));
+        assert!(matches("team in (payments, web),env notin (prod)"));
+        assert!(matches("env,!legacy"));
+        assert!(matches("tier!=gold"));
+        assert!(!matches("env!=staging"));
+        assert!(!matches("team in (web)"));
+        assert!(!matches("legacy"));
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        for selector in [
+            "",
+            "env in staging",
+            "env in (a",
+            "=staging",
+            "env=sta ging",
+        ] {
+            assert!(Selector::parse(selector).is_err(), "{}", selector);
+        }
+    }
+}
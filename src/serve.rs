@@ -0,0 +1,239 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::{rotation, NewRelicClient};
+
+struct AppState {
+    client: NewRelicClient,
+    token: String,
+    default_account_ids: Vec<i64>,
+}
+
+/// JSON error body with the status it is returned with.
+#[derive(Debug)]
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, message.into())
+    }
+}
+
+/// Anything failing after validation is a NerdGraph error.
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError(StatusCode::BAD_GATEWAY, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Byte-wise comparison that does not stop at the first mismatch.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), state.token.as_bytes()));
+    if !authorized {
+        return Err(ApiError(
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid bearer token".to_string(),
+        ));
+    }
+    Ok(next.run(request).await)
+}
+
+fn key_type(value: Option<&str>) -> Result<String, ApiError> {
+    match value.map(str::to_uppercase).as_deref() {
+        Some(t @ ("INGEST" | "USER")) => Ok(t.to_string()),
+        Some(other) => Err(ApiError::bad_request(format!(
+            "Unsupported key type '{}' (expected INGEST or USER)",
+            other
+        ))),
+        None => Err(ApiError::bad_request("Missing key type")),
+    }
+}
+
+/// Listings never return key secrets; only create and rotate do.
+fn without_secret(mut key: ApiKey) -> ApiKey {
+    key.key = None;
+    key
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    /// Comma-separated account IDs
+    account_ids: Option<String>,
+    #[serde(rename = "type")]
+    key_type: Option<String>,
+}
+
+async fn list_keys(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    let account_ids = match params.account_ids.as_deref() {
+        Some(ids) => ids
+            .split(',')
+            .map(|id| id.trim().parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ApiError::bad_request("account_ids must be comma-separated integers"))?,
+        None => state.default_account_ids.clone(),
+    };
+    if account_ids.is_empty() {
+        return Err(ApiError::bad_request("Missing account_ids"));
+    }
+    let types = match params.key_type.as_deref() {
+        Some(t) => vec![key_type(Some(t))?],
+        None => vec!["INGEST".to_string(), "USER".to_string()],
+    };
+    let types: Vec<&str> = types.iter().map(String::as_str).collect();
+
+    let keys = inventory::fetch(&state.client, &account_ids, &types).await?;
+    Ok(Json(keys.into_iter().map(without_secret).collect()))
+}
+
+#[derive(Deserialize)]
+struct TypeParams {
+    #[serde(rename = "type")]
+    key_type: Option<String>,
+}
+
+async fn get_key(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TypeParams>,
+) -> Result<Json<ApiKey>, ApiError> {
+    let key_type = key_type(params.key_type.as_deref())?;
+    let key = inventory::get(&state.client, &id, &key_type).await?;
+    Ok(Json(without_secret(key)))
+}
+
+async fn create_key(
+    State(state): State<Arc<AppState>>,
+    Json(spec): Json<NewKey>,
+) -> Result<(StatusCode, Json<ApiKey>), ApiError> {
+    key_type(Some(&spec.key_type))?;
+    if spec.name.trim().is_empty() {
+        return Err(ApiError::bad_request("Missing name"));
+    }
+    let key = inventory::create(&state.client, &spec).await?;
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+#[derive(Deserialize)]
+struct RotateBody {
+    #[serde(rename = "type")]
+    key_type: Option<String>,
+    #[serde(default)]
+    keep_old: bool,
+}
+
+async fn rotate_key(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<RotateBody>,
+) -> Result<Json<rotation::Rotation>, ApiError> {
+    let key_type = key_type(body.key_type.as_deref())?;
+    let rotation = rotation::rotate(&state.client, &id, &key_type, body.keep_old).await?;
+    Ok(Json(rotation))
+}
+
+async fn delete_key(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TypeParams>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let ids = vec![id];
+    let outcome = match key_type(params.key_type.as_deref())?.as_str() {
+        "USER" => inventory::delete_keys(&state.client, &[], &ids).await?,
+        _ => inventory::delete_keys(&state.client, &ids, &[]).await?,
+    };
+    if outcome.deleted.is_empty() {
+        return Err(ApiError(StatusCode::BAD_GATEWAY, outcome.errors.join(", ")));
+    }
+    Ok(Json(serde_json::json!({ "deleted": outcome.deleted })))
+}
+
+fn router(state: Arc<AppState>) -> Router {
+    let api = Router::new()
+        .route("/v1/keys", get(list_keys).post(create_key))
+        .route("/v1/keys/{id}", get(get_key).delete(delete_key))
+        .route("/v1/keys/{id}/rotate", post(rotate_key))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate));
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .merge(api)
+        .with_state(state)
+}
+
+/// Serve list/get/create/rotate/delete as a small JSON API authenticated with a bearer token.
+pub async fn run(
+    client: NewRelicClient,
+    token: String,
+    default_account_ids: Vec<i64>,
+    listen: SocketAddr,
+) -> anyhow::Result<()> {
+    if token.len() < 16 {
+        return Err(anyhow::anyhow!(
+            "The serve token must be at least 16 characters long"
+        ));
+    }
+    let state = Arc::new(AppState {
+        client,
+        token,
+        default_account_ids,
+    });
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .map_err(|e| anyhow::anyhow!("Unable to listen on {}: {}", listen, e))?;
+    println!("Serving the key API on http://{}", listener.local_addr()?);
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret-token", b"secret-token"));
+        assert!(!constant_time_eq(b"secret-token", b"secret-tokem"));
+        assert!(!constant_time_eq(b"secret", b"secret-token"));
+    }
+
+    #[test]
+    fn test_key_type_validation() {
+        assert_eq!(key_type(Some("ingest")).unwrap(), "INGEST");
+        assert!(key_type(Some("browser")).is_err());
+        assert!(key_type(None).is_err());
+    }
+}
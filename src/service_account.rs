@@ -0,0 +1,508 @@
+//! `create --service-account`: the user that owns a machine credential, created in an
+//! authentication domain through NerdGraph user management (or reused when it exists), so the
+//! user and its USER key come from one command. `--group` and `--grant-role` then put the user
+//! in a group and grant that group a role on the key's account through authorization
+//! management, which is what makes the key able to do anything.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{NewRelicClient, Variables};
+
+pub(crate) const DOMAINS_QUERY: &str = r#"
+    query($email: String!) {
+        actor {
+            organization {
+                userManagement {
+                    authenticationDomains {
+                        authenticationDomains {
+                            id
+                            name
+                            provisioningType
+                            users(filter: {email: {eq: $email}}) {
+                                users {
+                                    id
+                                    name
+                                    email
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }"#;
+
+pub(crate) const CREATE_USER_QUERY: &str = r#"
+    mutation($options: UserManagementCreateUser!) {
+        userManagementCreateUser(createUserOptions: $options) {
+            createdUser {
+                id
+                name
+                email
+                authenticationDomainId
+            }
+        }
+    }"#;
+
+pub(crate) const GROUPS_QUERY: &str = r#"
+    query($domainId: [ID!], $cursor: String) {
+        actor {
+            organization {
+                userManagement {
+                    authenticationDomains(id: $domainId) {
+                        authenticationDomains {
+                            groups(cursor: $cursor) {
+                                groups {
+                                    id
+                                    displayName
+                                }
+                                nextCursor
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }"#;
+
+pub(crate) const CREATE_GROUP_QUERY: &str = r#"
+    mutation($options: UserManagementCreateGroup!) {
+        userManagementCreateGroup(createGroupOptions: $options) {
+            group {
+                id
+                displayName
+            }
+        }
+    }"#;
+
+pub(crate) const ADD_TO_GROUP_QUERY: &str = r#"
+    mutation($options: UserManagementUsersGroupsInput!) {
+        userManagementAddUsersToGroups(addUsersToGroupsOptions: $options) {
+            groups {
+                id
+            }
+        }
+    }"#;
+
+pub(crate) const ROLES_QUERY: &str = r#"
+    query($cursor: String) {
+        actor {
+            organization {
+                authorizationManagement {
+                    roles(cursor: $cursor) {
+                        roles {
+                            id
+                            name
+                            scope
+                        }
+                        nextCursor
+                    }
+                }
+            }
+        }
+    }"#;
+
+pub(crate) const GRANT_QUERY: &str = r#"
+    mutation($options: AuthorizationManagementGrantAccess!) {
+        authorizationManagementGrantAccess(grantAccessOptions: $options) {
+            roles {
+                id
+                roleId
+                accountId
+                displayName
+            }
+        }
+    }"#;
+
+/// User tiers a service account may be created with.
+pub const TIERS: [&str; 3] = ["basic", "core", "full"];
+
+#[derive(Deserialize)]
+struct Domain {
+    id: String,
+    name: String,
+    #[serde(rename = "provisioningType")]
+    provisioning_type: Option<String>,
+    users: Option<Users>,
+}
+
+#[derive(Deserialize)]
+struct Users {
+    users: Vec<User>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+    name: String,
+    email: String,
+}
+
+/// The user a service account key belongs to.
+#[derive(Debug, Serialize)]
+pub struct ServiceAccount {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub authentication_domain_id: String,
+    /// Whether this run created the user, as opposed to finding it
+    pub created: bool,
+}
+
+/// What `create --service-account` asks for.
+pub struct Request<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    /// Required when the organization has several authentication domains
+    pub authentication_domain: Option<&'a str>,
+    /// One of [`TIERS`]
+    pub tier: &'a str,
+}
+
+/// `template` with `{name}` replaced by `name` in lowercase, with whitespace turned into dashes,
+/// e.g. `newrelic+{name}@example.com` for the `[service_accounts] email` setting.
+pub fn email_for(template: &str, name: &str) -> String {
+    let slug = name
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    template.replace("{name}", &slug)
+}
+
+fn tier_name(tier: &str) -> anyhow::Result<String> {
+    if !TIERS.contains(&tier.to_lowercase().as_str()) {
+        anyhow::bail!(
+            "Unsupported user tier '{}' (expected basic, core or full)",
+            tier
+        );
+    }
+    Ok(format!("{}_USER_TIER", tier.to_uppercase()))
+}
+
+/// The domain to provision in: the one asked for, the one already holding the user, or the
+/// only one there is.
+fn pick_domain<'d>(domains: &'d [Domain], wanted: Option<&str>) -> anyhow::Result<&'d Domain> {
+    if let Some(wanted) = wanted {
+        return domains
+            .iter()
+            .find(|domain| domain.id == wanted || domain.name.eq_ignore_ascii_case(wanted))
+            .ok_or_else(|| anyhow::anyhow!("No authentication domain '{}'", wanted));
+    }
+    let holding: Vec<&Domain> = domains
+        .iter()
+        .filter(|domain| domain.users.as_ref().is_some_and(|u| !u.users.is_empty()))
+        .collect();
+    match (domains, holding.as_slice()) {
+        (_, [domain]) => Ok(domain),
+        ([domain], _) => Ok(domain),
+        ([], _) => anyhow::bail!("The organization has no authentication domains"),
+        _ => anyhow::bail!(
+            "The organization has several authentication domains; pass \
+             --authentication-domain with one of: {}",
+            domains
+                .iter()
+                .map(|domain| format!("{} ({})", domain.id, domain.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn parse_domains(data: &serde_json::Value) -> anyhow::Result<Vec<Domain>> {
+    let domains = &data["actor"]["organization"]["userManagement"]["authenticationDomains"]
+        ["authenticationDomains"];
+    if domains.is_null() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_value(domains.clone())
+        .map_err(|e| anyhow::anyhow!("Unexpected authentication domains: {}", e))
+}
+
+fn user_id(id: &str) -> anyhow::Result<i64> {
+    id.parse()
+        .map_err(|_| anyhow::anyhow!("NerdGraph returned a non-numeric user ID '{}'", id))
+}
+
+/// Find the service account user by email, or create it.
+pub async fn provision(
+    client: &NewRelicClient,
+    request: &Request<'_>,
+) -> anyhow::Result<ServiceAccount> {
+    let tier = tier_name(request.tier)?;
+    let variables = Variables::new().string("email", request.email);
+    let data = client.execute_query(DOMAINS_QUERY, Some(variables)).await?;
+    let domains = parse_domains(&data)?;
+    let domain = pick_domain(&domains, request.authentication_domain)?;
+
+    let existing = domain
+        .users
+        .as_ref()
+        .and_then(|users| users.users.iter().find(|u| u.email == request.email));
+    if let Some(user) = existing {
+        return Ok(ServiceAccount {
+            id: user_id(&user.id)?,
+            name: user.name.clone(),
+            email: user.email.clone(),
+            authentication_domain_id: domain.id.clone(),
+            created: false,
+        });
+    }
+    if domain.provisioning_type.as_deref() == Some("SCIM") {
+        anyhow::bail!(
+            "Users of authentication domain {} ({}) are provisioned by SCIM; create {} in the \
+             identity provider, then pass its --user-id",
+            domain.id,
+            domain.name,
+            request.email
+        );
+    }
+
+    let variables = Variables::new().value(
+        "options",
+        serde_json::json!({
+            "authenticationDomainId": domain.id,
+            "email": request.email,
+            "name": request.name,
+            "userType": tier,
+        }),
+    );
+    let data = client
+        .execute_query(CREATE_USER_QUERY, Some(variables))
+        .await?;
+    let user = &data["userManagementCreateUser"]["createdUser"];
+    let id = user["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Unable to create service account {}", request.email))?;
+    Ok(ServiceAccount {
+        id: user_id(id)?,
+        name: request.name.to_string(),
+        email: request.email.to_string(),
+        authentication_domain_id: domain.id.clone(),
+        created: true,
+    })
+}
+
+#[derive(Deserialize)]
+struct Group {
+    id: String,
+    #[serde(rename = "displayName")]
+    display_name: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Role {
+    pub id: String,
+    pub name: String,
+    scope: Option<String>,
+}
+
+/// The group and role a service account was given.
+#[derive(Debug, Serialize)]
+pub struct Grant {
+    pub group_id: String,
+    pub group: String,
+    /// Whether this run created the group
+    pub created_group: bool,
+    pub role_id: Option<String>,
+    pub role: Option<String>,
+    pub account_id: i64,
+}
+
+/// The role named `wanted`, by ID or case-insensitive name, among those that apply to accounts.
+fn pick_role<'r>(roles: &'r [Role], wanted: &str) -> anyhow::Result<&'r Role> {
+    roles
+        .iter()
+        .filter(|role| role.scope.as_deref().is_none_or(|scope| scope == "ACCOUNT"))
+        .find(|role| role.id == wanted || role.name.eq_ignore_ascii_case(wanted))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No account role '{}'; the organization has: {}",
+                wanted,
+                roles
+                    .iter()
+                    .map(|role| role.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// Every role of the organization, following `nextCursor` across pages.
+async fn fetch_roles(client: &NewRelicClient) -> anyhow::Result<Vec<Role>> {
+    let mut roles = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut variables = Variables::new();
+        if let Some(cursor) = &cursor {
+            variables = variables.string("cursor", cursor);
+        }
+        let data = client.execute_query(ROLES_QUERY, Some(variables)).await?;
+        let page = &data["actor"]["organization"]["authorizationManagement"]["roles"];
+        if !page["roles"].is_null() {
+            let more: Vec<Role> = serde_json::from_value(page["roles"].clone())
+                .map_err(|e| anyhow::anyhow!("Unexpected roles: {}", e))?;
+            roles.extend(more);
+        }
+        match page["nextCursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => return Ok(roles),
+        }
+    }
+}
+
+/// Look up an account role by ID or name, before anything is created for it.
+pub async fn find_role(client: &NewRelicClient, wanted: &str) -> anyhow::Result<Role> {
+    let roles = fetch_roles(client).await?;
+    pick_role(&roles, wanted).cloned()
+}
+
+/// Every group of authentication domain `domain_id`, following `nextCursor` across pages.
+async fn fetch_groups(client: &NewRelicClient, domain_id: &str) -> anyhow::Result<Vec<Group>> {
+    let mut groups = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut variables = Variables::new().list("domainId", [domain_id]);
+        if let Some(cursor) = &cursor {
+            variables = variables.string("cursor", cursor);
+        }
+        let data = client.execute_query(GROUPS_QUERY, Some(variables)).await?;
+        let page = &data["actor"]["organization"]["userManagement"]["authenticationDomains"]
+            ["authenticationDomains"][0]["groups"];
+        if !page["groups"].is_null() {
+            let more: Vec<Group> = serde_json::from_value(page["groups"].clone())
+                .map_err(|e| anyhow::anyhow!("Unexpected groups: {}", e))?;
+            groups.extend(more);
+        }
+        match page["nextCursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => return Ok(groups),
+        }
+    }
+}
+
+/// Put `owner` in `group` of its authentication domain, creating the group if needed, and grant
+/// the group `role` on `account_id`.
+pub async fn grant(
+    client: &NewRelicClient,
+    owner: &ServiceAccount,
+    group: &str,
+    role: Option<&Role>,
+    account_id: i64,
+) -> anyhow::Result<Grant> {
+    let groups = fetch_groups(client, &owner.authentication_domain_id).await?;
+    let existing = groups
+        .into_iter()
+        .find(|g| g.id == group || g.display_name.eq_ignore_ascii_case(group));
+    let created_group = existing.is_none();
+    let group = match existing {
+        Some(group) => group,
+        None => {
+            let variables = Variables::new().value(
+                "options",
+                serde_json::json!({
+                    "authenticationDomainId": owner.authentication_domain_id,
+                    "displayName": group,
+                }),
+            );
+            let data = client
+                .execute_query(CREATE_GROUP_QUERY, Some(variables))
+                .await?;
+            serde_json::from_value(data["userManagementCreateGroup"]["group"].clone())
+                .map_err(|_| anyhow::anyhow!("Unable to create group '{}'", group))?
+        }
+    };
+
+    let variables = Variables::new().value(
+        "options",
+        serde_json::json!({
+            "groupIds": [group.id],
+            "userIds": [owner.id.to_string()],
+        }),
+    );
+    client
+        .execute_query(ADD_TO_GROUP_QUERY, Some(variables))
+        .await?;
+
+    let mut granted = Grant {
+        group_id: group.id.clone(),
+        group: group.display_name.clone(),
+        created_group,
+        role_id: None,
+        role: None,
+        account_id,
+    };
+    if let Some(role) = role {
+        let variables = Variables::new().value(
+            "options",
+            serde_json::json!({
+                "groupId": group.id,
+                "accountAccessGrants": [{"accountId": account_id, "roleId": role.id}],
+            }),
+        );
+        client.execute_query(GRANT_QUERY, Some(variables)).await?;
+        granted.role_id = Some(role.id.clone());
+        granted.role = Some(role.name.clone());
+    }
+    Ok(granted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domains(value: serde_json::Value) -> Vec<Domain> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_email_and_tier() {
+        assert_eq!(
+            email_for("newrelic+{name}@example.com", "CI Deploy"),
+            "newrelic+ci-deploy@example.com"
+        );
+        assert_eq!(tier_name("Basic").unwrap(), "BASIC_USER_TIER");
+        assert!(tier_name("admin").is_err());
+    }
+
+    #[test]
+    fn test_pick_domain() {
+        let one = domains(serde_json::json!([
+            {"id": "d-1", "name": "Default", "provisioningType": "MANUAL", "users": {"users": []}}
+        ]));
+        assert_eq!(pick_domain(&one, None).unwrap().id, "d-1");
+        assert!(pick_domain(&one, Some("d-9")).is_err());
+
+        let two = domains(serde_json::json!([
+            {"id": "d-1", "name": "Default", "users": {"users": []}},
+            {"id": "d-2", "name": "Machines", "users": {"users": [
+                {"id": "1001", "name": "ci", "email": "ci@example.com"}
+            ]}}
+        ]));
+        assert_eq!(pick_domain(&two, None).unwrap().id, "d-2");
+        assert_eq!(pick_domain(&two, Some("default")).unwrap().id, "d-1");
+
+        let empty = domains(serde_json::json!([
+            {"id": "d-1", "name": "Default", "users": {"users": []}},
+            {"id": "d-2", "name": "Machines", "users": {"users": []}}
+        ]));
+        let error = pick_domain(&empty, None).err().unwrap().to_string();
+        assert!(error.contains("d-1 (Default), d-2 (Machines)"));
+    }
+
+    #[test]
+    fn test_pick_role() {
+        let roles: Vec<Role> = serde_json::from_value(serde_json::json!([
+            {"id": "1", "name": "All Product Admin", "scope": "ACCOUNT"},
+            {"id": "2", "name": "Read Only", "scope": "ACCOUNT"},
+            {"id": "3", "name": "Organization Manager", "scope": "ORGANIZATION"}
+        ]))
+        .unwrap();
+        assert_eq!(pick_role(&roles, "read only").unwrap().id, "2");
+        assert_eq!(pick_role(&roles, "1").unwrap().name, "All Product Admin");
+        let error = pick_role(&roles, "Organization Manager").err().unwrap();
+        assert!(error
+            .to_string()
+            .contains("the organization has: All Product Admin"));
+    }
+}
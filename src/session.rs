@@ -0,0 +1,182 @@
+//! Session tokens for organizations that sign in through OAuth or SAML instead of issuing
+//! static user keys. A profile's `[profiles.<name>.session]` names a command that prints a token
+//! or an OAuth endpoint that refreshes one; before each run the CLI reuses the cached token or
+//! gets a new one once it is about to expire.
+
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Session};
+use crate::SecretString;
+
+/// Lifetime assumed for tokens that do not state one.
+const DEFAULT_TTL_SECONDS: u64 = 300;
+
+/// Tokens this close to expiring are replaced, so that none expires in the middle of a run.
+const EXPIRY_MARGIN_SECONDS: i64 = 60;
+
+/// What `token_command` prints as JSON and what the token endpoint returns.
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: SecretString,
+    expires_in: Option<u64>,
+    refresh_token: Option<SecretString>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cached {
+    token: SecretString,
+    expires_at: DateTime<Utc>,
+    /// The latest refresh token, which endpoints that rotate them invalidate on every use
+    refresh_token: Option<SecretString>,
+}
+
+/// A valid session token for `session`, cached in `cache_file` until shortly before it expires.
+pub async fn token(session: &Session, cache_file: &Path) -> anyhow::Result<SecretString> {
+    let cached = std::fs::read_to_string(cache_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Cached>(&contents).ok());
+    let now = Utc::now();
+    if let Some(cached) = &cached {
+        if cached.expires_at - now > Duration::seconds(EXPIRY_MARGIN_SECONDS) {
+            return Ok(cached.token.clone());
+        }
+    }
+
+    let (response, used_refresh_token) = match (&session.token_command, &session.refresh_url) {
+        (Some(command), None) => (run_command(command)?, None),
+        (None, Some(url)) => {
+            let refresh_token = cached
+                .and_then(|cached| cached.refresh_token)
+                .or_else(|| session.refresh_token.clone())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("The session needs a refresh_token to use refresh_url")
+                })?;
+            let response = refresh(url, session.client_id.as_deref(), &refresh_token).await?;
+            (response, Some(refresh_token))
+        }
+        _ => anyhow::bail!("A session needs exactly one of token_command or refresh_url"),
+    };
+    let ttl = response
+        .expires_in
+        .or(session.ttl_seconds)
+        .unwrap_or(DEFAULT_TTL_SECONDS);
+    let cached = Cached {
+        token: response.access_token,
+        expires_at: now + Duration::seconds(i64::from(u32::try_from(ttl).unwrap_or(u32::MAX))),
+        refresh_token: response.refresh_token.or(used_refresh_token),
+    };
+    config::write_private(cache_file, &serde_json::to_string(&cached)?)?;
+    Ok(cached.token)
+}
+
+/// Run `token_command`, which prints the token itself or a JSON token response.
+fn run_command(command: &str) -> anyhow::Result<TokenResponse> {
+    let words = shell_words::split(command)?;
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("token_command is empty"))?;
+    let output = std::process::Command::new(program)
+        .args(args)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run token_command '{}': {}", program, e))?;
+    if !output.status.success() {
+        anyhow::bail!("token_command '{}' failed with {}", program, output.status);
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    parse_token(stdout.trim())
+}
+
+fn parse_token(text: &str) -> anyhow::Result<TokenResponse> {
+    if text.starts_with('{') {
+        return serde_json::from_str(text)
+            .map_err(|e| anyhow::anyhow!("token_command printed invalid JSON: {}", e));
+    }
+    if text.is_empty() {
+        anyhow::bail!("token_command printed no token");
+    }
+    Ok(TokenResponse {
+        access_token: SecretString::from(text),
+        expires_in: None,
+        refresh_token: None,
+    })
+}
+
+async fn refresh(
+    url: &str,
+    client_id: Option<&str>,
+    refresh_token: &SecretString,
+) -> anyhow::Result<TokenResponse> {
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.expose()),
+    ];
+    if let Some(client_id) = client_id {
+        form.push(("client_id", client_id));
+    }
+    let response = reqwest::Client::new().post(url).form(&form).send().await?;
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "The token endpoint refused the refresh: HTTP {} {}",
+            response.status(),
+            response.text().await.unwrap_or_default().trim()
+        );
+    }
+    Ok(response.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token() {
+        let plain = parse_token("eyJ.plain").unwrap();
+        assert_eq!(plain.access_token.expose(), "eyJ.plain");
+        assert!(plain.expires_in.is_none());
+
+        let json = parse_token(r#"{"access_token": "eyJ.json", "expires_in": 3600}"#).unwrap();
+        assert_eq!(json.access_token.expose(), "eyJ.json");
+        assert_eq!(json.expires_in, Some(3600));
+        assert!(parse_token("").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_token_is_cached_until_it_expires() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_file = dir.path().join("sessions").join("sso.json");
+        let session = Session {
+            token_command: Some(r#"echo '{"access_token": "first", "expires_in": 3600}'"#.into()),
+            ..Session::default()
+        };
+        assert_eq!(
+            token(&session, &cache_file).await.unwrap().expose(),
+            "first"
+        );
+
+        let renewed = Session {
+            token_command: Some("echo second".into()),
+            ..Session::default()
+        };
+        assert_eq!(
+            token(&renewed, &cache_file).await.unwrap().expose(),
+            "first"
+        );
+
+        // Expiring within the margin: fetched again.
+        let cached = Cached {
+            token: "first".into(),
+            expires_at: Utc::now() + Duration::seconds(10),
+            refresh_token: None,
+        };
+        std::fs::write(&cache_file, serde_json::to_string(&cached).unwrap()).unwrap();
+        assert_eq!(
+            token(&renewed, &cache_file).await.unwrap().expose(),
+            "second"
+        );
+    }
+}
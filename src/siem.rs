@@ -0,0 +1,311 @@
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::audit_events::AuditEvent;
+
+const VENDOR: &str = "New Relic";
+const PRODUCT: &str = "newrelic-apikeys-cli";
+const DEFAULT_SYSLOG_PORT: u16 = 514;
+/// Facility 10 (security/authorization), severity 6 (informational).
+const SYSLOG_PRIORITY: u8 = 10 * 8 + 6;
+
+/// SIEM interchange formats for audit events.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Cef,
+    Ocsf,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "cef" => Ok(ExportFormat::Cef),
+            "ocsf" => Ok(ExportFormat::Ocsf),
+            other => Err(anyhow::anyhow!(
+                "Unsupported export format '{}' (expected cef or ocsf)",
+                other
+            )),
+        }
+    }
+
+    pub fn render(self, event: &AuditEvent) -> String {
+        match self {
+            ExportFormat::Cef => cef(event),
+            ExportFormat::Ocsf => ocsf(event).to_string(),
+        }
+    }
+}
+
+/// Where exported events are shipped.
+#[derive(Debug, PartialEq)]
+enum Target {
+    Udp(String),
+    Tcp(String),
+    Http(String),
+}
+
+/// `syslog://host[:port]` and `udp://` send RFC 5424 datagrams, `syslog+tcp://` and `tcp://`
+/// use octet-counted framing (RFC 6587), `http(s)://` POSTs the batch.
+fn parse_target(url: &str) -> anyhow::Result<Target> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return Ok(Target::Http(url.to_string()));
+    }
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| anyhow::anyhow!("Invalid SIEM target '{}': missing scheme", url))?;
+    let address = rest.trim_end_matches('/');
+    if address.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Invalid SIEM target '{}': missing host",
+            url
+        ));
+    }
+    let address = if address
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+    {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_SYSLOG_PORT)
+    };
+    match scheme {
+        "syslog" | "udp" => Ok(Target::Udp(address)),
+        "syslog+tcp" | "tcp" => Ok(Target::Tcp(address)),
+        other => Err(anyhow::anyhow!(
+            "Unsupported SIEM target scheme '{}' (expected syslog, syslog+tcp, udp, tcp, http or https)",
+            other
+        )),
+    }
+}
+
+fn cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn cef_extension(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+/// ArcSight Common Event Format: `CEF:0|vendor|product|version|signature|name|severity|ext`.
+pub fn cef(event: &AuditEvent) -> String {
+    let action = event.action.as_deref().unwrap_or("unknown");
+    let name = event.description.as_deref().unwrap_or(action);
+
+    let mut extension = Vec::new();
+    if let Some(timestamp) = event.timestamp {
+        extension.push(format!("rt={}", timestamp.timestamp_millis()));
+    }
+    let fields = [
+        ("suser", event.actor.as_deref()),
+        ("cs1Label", Some("accountId")),
+        ("cs1", Some(event.account_id.to_string().as_str())),
+        ("act", event.action.as_deref()),
+        ("duid", event.target_id.as_deref()),
+        ("cs2Label", event.target_type.as_ref().map(|_| "targetType")),
+        ("cs2", event.target_type.as_deref()),
+        ("msg", event.description.as_deref()),
+    ]
+    .map(|(key, value)| value.map(|v| format!("{}={}", key, cef_extension(v))));
+    extension.extend(fields.into_iter().flatten());
+
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|3|{}",
+        cef_header(VENDOR),
+        cef_header(PRODUCT),
+        env!("CARGO_PKG_VERSION"),
+        cef_header(action),
+        cef_header(name),
+        extension.join(" ")
+    )
+}
+
+/// OCSF API Activity (class 6003), with the activity derived from the audit action.
+pub fn ocsf(event: &AuditEvent) -> serde_json::Value {
+    let action = event.action.as_deref().unwrap_or_default().to_lowercase();
+    let (activity_id, activity_name) = if action.contains("create") {
+        (1, "Create")
+    } else if action.contains("delete") {
+        (4, "Delete")
+    } else if action.contains("update") || action.contains("rotate") {
+        (3, "Update")
+    } else {
+        (99, "Other")
+    };
+    let actor = event.actor.as_deref();
+    let (email, uid) = match actor {
+        Some(actor) if actor.contains('@') => (Some(actor), None),
+        other => (None, other),
+    };
+
+    serde_json::json!({
+        "class_uid": 6003,
+        "class_name": "API Activity",
+        "category_uid": 6,
+        "category_name": "Application Activity",
+        "activity_id": activity_id,
+        "activity_name": activity_name,
+        "type_uid": 6003 * 100 + activity_id,
+        "severity_id": 1,
+        "severity": "Informational",
+        "time": event.timestamp.map(|t| t.timestamp_millis()),
+        "message": event.description,
+        "actor": {
+            "user": {
+                "email_addr": email,
+                "uid": uid,
+                "type": event.actor_type,
+            }
+        },
+        "api": {
+            "operation": event.action,
+            "service": { "name": "NerdGraph" },
+        },
+        "resources": [{
+            "uid": event.target_id,
+            "type": event.target_type,
+        }],
+        "cloud": {
+            "provider": VENDOR,
+            "account": { "uid": event.account_id.to_string() },
+        },
+        "metadata": {
+            "version": "1.1.0",
+            "product": {
+                "name": PRODUCT,
+                "vendor_name": VENDOR,
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        },
+    })
+}
+
+fn syslog_message(event: &AuditEvent, body: &str) -> String {
+    let hostname = std::env::var("HOSTNAME").unwrap_or_else(|_| "-".to_string());
+    let timestamp = event
+        .timestamp
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_else(|| "-".to_string());
+    format!(
+        "<{}>1 {} {} {} - - - {}",
+        SYSLOG_PRIORITY, timestamp, hostname, PRODUCT, body
+    )
+}
+
+/// Send every event to the SIEM target, returning how many were shipped.
+pub async fn ship(
+    events: &[AuditEvent],
+    format: ExportFormat,
+    url: &str,
+    headers: &[String],
+) -> anyhow::Result<usize> {
+    let target = parse_target(url)?;
+    match target {
+        Target::Udp(address) => {
+            let socket = UdpSocket::bind("0.0.0.0:0").await?;
+            socket.connect(&address).await?;
+            for event in events {
+                let message = syslog_message(event, &format.render(event));
+                socket.send(message.as_bytes()).await?;
+            }
+        }
+        Target::Tcp(address) => {
+            let mut stream = TcpStream::connect(&address).await?;
+            for event in events {
+                let message = syslog_message(event, &format.render(event));
+                stream
+                    .write_all(format!("{} {}", message.len(), message).as_bytes())
+                    .await?;
+            }
+            stream.flush().await?;
+        }
+        Target::Http(url) => {
+            let (content_type, body) = match format {
+                ExportFormat::Ocsf => (
+                    "application/json",
+                    serde_json::Value::Array(events.iter().map(ocsf).collect()).to_string(),
+                ),
+                ExportFormat::Cef => (
+                    "text/plain",
+                    events.iter().map(|e| cef(e) + "\n").collect::<String>(),
+                ),
+            };
+            let mut request = reqwest::Client::new()
+                .post(&url)
+                .header("Content-Type", content_type)
+                .body(body);
+            for header in headers {
+                let (name, value) = header.split_once(':').ok_or_else(|| {
+                    anyhow::anyhow!("Invalid header '{}': expected 'Name: value'", header)
+                })?;
+                request = request.header(name.trim(), value.trim());
+            }
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!(
+                    "SIEM endpoint returned {}: {}",
+                    response.status(),
+                    response.text().await.unwrap_or_default()
+                ));
+            }
+        }
+    }
+    Ok(events.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> AuditEvent {
+        AuditEvent {
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000, 0),
+            account_id: 123,
+            actor: Some("jane@example.com".to_string()),
+            actor_type: Some("user".to_string()),
+            action: Some("api_access.delete_key".to_string()),
+            target_id: Some("ABC".to_string()),
+            target_type: Some("api_key".to_string()),
+            description: Some("Deleted key a=b|c".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_cef_escapes_header_and_extension() {
+        let line = cef(&event());
+        assert!(line.starts_with("CEF:0|New Relic|newrelic-apikeys-cli|"));
+        assert!(line.contains("|api_access.delete_key|Deleted key a=b\\|c|3|"));
+        assert!(line.contains("rt=1700000000000 suser=jane@example.com"));
+        assert!(line.ends_with("msg=Deleted key a\\=b|c"));
+    }
+
+    #[test]
+    fn test_ocsf_maps_activity() {
+        let record = ocsf(&event());
+        assert_eq!(record["activity_id"], 4);
+        assert_eq!(record["type_uid"], 600304);
+        assert_eq!(record["actor"]["user"]["email_addr"], "jane@example.com");
+        assert_eq!(record["resources"][0]["uid"], "ABC");
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            parse_target("syslog://siem.local").unwrap(),
+            Target::Udp("siem.local:514".to_string())
+        );
+        assert_eq!(
+            parse_target("syslog+tcp://siem.local:6514").unwrap(),
+            Target::Tcp("siem.local:6514".to_string())
+        );
+        assert!(matches!(
+            parse_target("https://siem.local/ingest").unwrap(),
+            Target::Http(_)
+        ));
+        assert!(parse_target("ftp://siem.local").is_err());
+        assert!(parse_target("siem.local").is_err());
+    }
+}
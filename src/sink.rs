@@ -0,0 +1,268 @@
+//! Where the secrets of created and rotated keys go. By default they are printed with the
+//! command's JSON output; `--secret-sink` stores them in the keyring, a private file or Vault
+//! instead, and stdout gets `[masked]` in their place.
+//!
+//! `strict_secrets = true` in the config, or `NEW_RELIC_STRICT_SECRETS=1`, forbids secrets on
+//! stdout altogether: commands that would print one refuse to start without a sink, and read
+//! commands mask what NerdGraph returns.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::ci::{self, Ci};
+use crate::inventory::ApiKey;
+use crate::{config, credentials, scan};
+
+/// Turns on strict mode like `strict_secrets` in the config.
+pub const STRICT_ENV: &str = "NEW_RELIC_STRICT_SECRETS";
+
+pub enum Sink {
+    /// The platform keyring, one entry per key named `key/<id>`
+    Keyring,
+    /// A JSON file of the keys, readable only by the current user
+    File(PathBuf),
+    /// A Vault KV version 2 secret at `<mount>/<path>`, or `<mount>/<path>/<id>` per key when
+    /// there are several
+    Vault { mount: String, path: String },
+}
+
+impl Sink {
+    /// `keyring`, `file:<path>` or `vault:<mount>/<path>`.
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        let (kind, target) = value.split_once(':').unwrap_or((value, ""));
+        match (kind.to_lowercase().as_str(), target) {
+            ("keyring", "") => Ok(Sink::Keyring),
+            ("file", path) if !path.is_empty() => Ok(Sink::File(PathBuf::from(path))),
+            ("vault", target) => match target.trim_matches('/').split_once('/') {
+                Some((mount, path)) if !path.is_empty() => Ok(Sink::Vault {
+                    mount: mount.to_string(),
+                    path: path.to_string(),
+                }),
+                _ => anyhow::bail!(
+                    "--secret-sink vault needs a mount and a path, e.g. vault:secret/newrelic/ci"
+                ),
+            },
+            _ => anyhow::bail!(
+                "Unsupported secret sink '{}' (expected keyring, file:<path> or vault:<mount>/<path>)",
+                value
+            ),
+        }
+    }
+
+    async fn store(&self, keys: &[&ApiKey]) -> anyhow::Result<()> {
+        let keys: Vec<&ApiKey> = keys
+            .iter()
+            .copied()
+            .filter(|key| key.key.is_some())
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Sink::Keyring => {
+                for key in &keys {
+                    let entry = format!("key/{}", key.id);
+                    credentials::store_secret(&entry, key.secret().unwrap_or_default())?;
+                    eprintln!(
+                        "Stored the secret of {} in the keyring as {}",
+                        key.id, entry
+                    );
+                }
+            }
+            Sink::File(path) => {
+                config::write_private(path, &(serde_json::to_string_pretty(&keys)? + "\n"))?;
+                eprintln!("Wrote {} key secret(s) to {}", keys.len(), path.display());
+            }
+            Sink::Vault { mount, path } => {
+                for key in &keys {
+                    let path = match keys.len() {
+                        1 => path.clone(),
+                        _ => format!("{}/{}", path, key.id),
+                    };
+                    vault_write(mount, &path, key).await?;
+                    eprintln!(
+                        "Stored the secret of {} in Vault at {}/{}",
+                        key.id, mount, path
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `VAULT_ADDR` and `VAULT_TOKEN`, falling back to the token `vault login` saves.
+fn vault_env() -> anyhow::Result<(String, String)> {
+    let addr = std::env::var("VAULT_ADDR")
+        .map_err(|_| anyhow::anyhow!("--secret-sink vault needs $VAULT_ADDR"))?;
+    let token = match std::env::var("VAULT_TOKEN") {
+        Ok(token) => token,
+        Err(_) => directories::BaseDirs::new()
+            .and_then(|dirs| std::fs::read_to_string(dirs.home_dir().join(".vault-token")).ok())
+            .map(|token| token.trim().to_string())
+            .ok_or_else(|| {
+                anyhow::anyhow!("--secret-sink vault needs $VAULT_TOKEN or a `vault login` token")
+            })?,
+    };
+    Ok((addr.trim_end_matches('/').to_string(), token))
+}
+
+async fn vault_write(mount: &str, path: &str, key: &ApiKey) -> anyhow::Result<()> {
+    let (addr, token) = vault_env()?;
+    let body = serde_json::json!({
+        "data": {
+            "key": key.secret(),
+            "id": key.id,
+            "type": key.key_type,
+            "account_id": key.account_id,
+            "name": key.name,
+        }
+    });
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/{}/data/{}", addr, mount, path))
+        .header("X-Vault-Token", token)
+        .json(&body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "Vault refused to store the secret of {} at {}/{}: HTTP {} {}",
+            key.id,
+            mount,
+            path,
+            response.status(),
+            response.text().await.unwrap_or_default().trim()
+        );
+    }
+    Ok(())
+}
+
+/// How a command hands out the secrets it produces: the CI adapter, the sink and strict mode.
+#[derive(Default)]
+pub struct Secrets {
+    pub ci: Option<Ci>,
+    pub sink: Option<Sink>,
+    pub strict: bool,
+}
+
+impl Secrets {
+    /// Whether `strict_secrets` applies, from the config or [`STRICT_ENV`].
+    pub fn strict_from(config: bool) -> bool {
+        config
+            || std::env::var(STRICT_ENV).is_ok_and(|value| {
+                matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on")
+            })
+    }
+
+    /// Fail before anything is created when strict mode is on and new secrets would reach
+    /// stdout. GitLab and Jenkins runs count as a sink, since their adapters write the secrets
+    /// to a private file and mask them on stdout.
+    pub fn check(&self) -> anyhow::Result<()> {
+        let file_ci = matches!(self.ci, Some(Ci::GitLab { .. } | Ci::Jenkins { .. }));
+        if self.strict && self.sink.is_none() && !file_ci {
+            anyhow::bail!(
+                "strict_secrets is on and this command produces key secrets: pass --secret-sink \
+                 keyring, file:<path> or vault:<mount>/<path>, or use --ci gitlab|jenkins"
+            );
+        }
+        Ok(())
+    }
+
+    /// Print `value` as JSON. The secrets of `keys` are masked when they go to a sink or strict
+    /// mode is on, and otherwise left to the CI adapter.
+    pub fn print<T: Serialize>(&self, value: &T, keys: &[&ApiKey]) -> anyhow::Result<()> {
+        match &self.ci {
+            Some(ci) if self.sink.is_none() && !self.strict => ci.print(value, keys),
+            _ => {
+                let json = serde_json::to_string_pretty(value)?;
+                if self.sink.is_some() || self.strict {
+                    println!("{}", ci::mask(&json, keys)?);
+                } else {
+                    println!("{}", json);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Store `keys` in the sink and hand them to the CI system.
+    pub async fn publish(&self, heading: &str, keys: &[&ApiKey]) -> anyhow::Result<()> {
+        if let Some(sink) = &self.sink {
+            sink.store(keys).await?;
+        }
+        if let Some(ci) = &self.ci {
+            ci.publish(heading, keys)?;
+        }
+        Ok(())
+    }
+
+    /// `text` with anything key-shaped replaced by [`ci::MASKED`] in strict mode, for output
+    /// such as raw GraphQL responses whose secrets are not known up front.
+    pub fn redact(&self, text: &str) -> String {
+        if !self.strict {
+            return text.to_string();
+        }
+        let mut redacted = text.to_string();
+        for m in scan::scan_text(std::path::Path::new(""), text) {
+            redacted = redacted.replace(&m.secret, ci::MASKED);
+        }
+        redacted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn key() -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": "K1", "name": "ci", "type": "USER",
+            "key": "NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0", "createdAt": 1, "accountId": 123
+        }))
+    }
+
+    #[test]
+    fn test_parse_sink() {
+        assert!(matches!(Sink::parse("keyring").unwrap(), Sink::Keyring));
+        assert!(
+            matches!(Sink::parse("file:keys.json").unwrap(), Sink::File(p) if p == Path::new("keys.json"))
+        );
+        assert!(matches!(
+            Sink::parse("vault:secret/newrelic/ci").unwrap(),
+            Sink::Vault { mount, path } if mount == "secret" && path == "newrelic/ci"
+        ));
+        assert!(Sink::parse("vault:secret").is_err());
+        assert!(Sink::parse("file:").is_err());
+        assert!(Sink::parse("s3:bucket").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_requires_a_sink() {
+        let strict = Secrets {
+            strict: true,
+            ..Secrets::default()
+        };
+        assert!(strict.check().is_err());
+        assert!(Secrets::default().check().is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        let with_file = Secrets {
+            sink: Some(Sink::File(path.clone())),
+            strict: true,
+            ..Secrets::default()
+        };
+        assert!(with_file.check().is_ok());
+        let key = key();
+        with_file.publish("Created API key", &[&key]).await.unwrap();
+        let stored = std::fs::read_to_string(&path).unwrap();
+        assert!(stored.contains("NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0"));
+
+        let text = format!("{{\"key\": \"{}\"}}", key.secret().unwrap());
+        assert_eq!(strict.redact(&text), "{\"key\": \"[masked]\"}");
+        assert_eq!(Secrets::default().redact(&text), text);
+    }
+}
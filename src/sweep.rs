@@ -0,0 +1,354 @@
+//! `sweep departed-users`: revoke the user keys of people who left, as an offboarding step.
+//! Departed users come from a list (a CSV export from HR or the identity provider, or
+//! `--user-id`) and, with `--not-in-organization`, from NerdGraph user management: a key whose
+//! owner is no longer a user of any authentication domain belongs to someone who was removed.
+//!
+//! A user list that NerdGraph returned only in part would make everyone look departed, so the
+//! organization's users must include the caller, and keys picked only from that list need a
+//! confirmation `--yes` does not give once they are more than [`MAX_UNLISTED_PERCENT`] of the
+//! user keys, unless the departed users are also listed with `--user-ids-file`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::inventory::{self, ApiKey};
+use crate::{NewRelicClient, Variables};
+
+pub(crate) const USERS_QUERY: &str = r#"
+    query($domainCursor: String, $domainId: [ID!], $cursor: String) {
+        actor {
+            organization {
+                userManagement {
+                    authenticationDomains(cursor: $domainCursor, id: $domainId) {
+                        authenticationDomains {
+                            id
+                            users(cursor: $cursor) {
+                                users {
+                                    id
+                                }
+                                nextCursor
+                            }
+                        }
+                        nextCursor
+                    }
+                }
+            }
+        }
+    }"#;
+
+/// The user the API key belongs to.
+pub(crate) const CALLER_QUERY: &str = "{ actor { user { id } } }";
+
+/// The share of the user keys, in percent, that keys picked only because their owners are
+/// missing from the organization may make up before a sweep needs an explicit confirmation.
+pub const MAX_UNLISTED_PERCENT: usize = 20;
+
+const LISTED: &str = "listed as departed";
+const NOT_IN_ORGANIZATION: &str = "no longer in the organization";
+
+/// User IDs in the first column of `contents`, one per line. A header line, blank lines and
+/// lines starting with `#` are skipped.
+fn parse_user_ids(contents: &str) -> anyhow::Result<BTreeSet<i64>> {
+    let mut ids = BTreeSet::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let field = line.split(',').next().unwrap_or_default().trim();
+        let field = field.trim_matches('"').trim();
+        match field.parse() {
+            Ok(id) => {
+                ids.insert(id);
+            }
+            Err(_) if index == 0 => continue,
+            Err(_) => anyhow::bail!("Line {}: '{}' is not a user ID", index + 1, field),
+        }
+    }
+    Ok(ids)
+}
+
+/// The user IDs listed in the CSV file at `path`.
+pub fn read_user_ids(path: &Path) -> anyhow::Result<BTreeSet<i64>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    parse_user_ids(&contents).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+}
+
+fn user_ids(users: &serde_json::Value) -> anyhow::Result<Vec<i64>> {
+    users["users"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|user| {
+            let id = user["id"].as_str().unwrap_or_default();
+            id.parse()
+                .map_err(|_| anyhow::anyhow!("NerdGraph returned a non-numeric user ID '{}'", id))
+        })
+        .collect()
+}
+
+fn authentication_domains(data: &serde_json::Value) -> &serde_json::Value {
+    &data["actor"]["organization"]["userManagement"]["authenticationDomains"]
+}
+
+/// Every user of every authentication domain of the organization, checked to include the
+/// caller.
+pub async fn organization_user_ids(client: &NewRelicClient) -> anyhow::Result<BTreeSet<i64>> {
+    let users = fetch_organization_user_ids(client).await?;
+    let caller = client.execute_query(CALLER_QUERY, None).await?;
+    check_complete(&users, caller["actor"]["user"]["id"].as_i64())?;
+    Ok(users)
+}
+
+/// Fail unless `users` can be the whole organization: not empty, and including `caller`.
+fn check_complete(users: &BTreeSet<i64>, caller: Option<i64>) -> anyhow::Result<()> {
+    if users.is_empty() {
+        anyhow::bail!(
+            "NerdGraph returned no users for the organization; refusing to treat every key \
+             owner as departed"
+        );
+    }
+    match caller {
+        Some(caller) if users.contains(&caller) => Ok(()),
+        Some(caller) => anyhow::bail!(
+            "The organization's users returned by NerdGraph do not include you (user {}), so \
+             the list may be incomplete; nothing was swept",
+            caller
+        ),
+        None => anyhow::bail!(
+            "Cannot tell whether the organization's user list is complete: the API key does \
+             not belong to a user"
+        ),
+    }
+}
+
+async fn fetch_organization_user_ids(client: &NewRelicClient) -> anyhow::Result<BTreeSet<i64>> {
+    let mut ids = BTreeSet::new();
+    let mut more = Vec::new();
+    let mut domain_cursor: Option<String> = None;
+    loop {
+        let mut variables = Variables::new();
+        if let Some(cursor) = &domain_cursor {
+            variables = variables.string("domainCursor", cursor);
+        }
+        let data = client.execute_query(USERS_QUERY, Some(variables)).await?;
+        let page = authentication_domains(&data);
+        for domain in page["authenticationDomains"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            ids.extend(user_ids(&domain["users"])?);
+            if let (Some(id), Some(cursor)) = (
+                domain["id"].as_str(),
+                domain["users"]["nextCursor"].as_str(),
+            ) {
+                more.push((id.to_string(), cursor.to_string()));
+            }
+        }
+        match page["nextCursor"].as_str() {
+            Some(cursor) => domain_cursor = Some(cursor.to_string()),
+            None => break,
+        }
+    }
+    for (domain_id, cursor) in more {
+        let mut cursor = Some(cursor);
+        while let Some(current) = cursor {
+            let variables = Variables::new()
+                .list("domainId", [domain_id.as_str()])
+                .string("cursor", current);
+            let data = client.execute_query(USERS_QUERY, Some(variables)).await?;
+            let domain = &authentication_domains(&data)["authenticationDomains"][0];
+            ids.extend(user_ids(&domain["users"])?);
+            cursor = domain["users"]["nextCursor"].as_str().map(str::to_string);
+        }
+    }
+    Ok(ids)
+}
+
+/// Who counts as departed.
+pub struct Departed {
+    /// Users listed as departed
+    pub listed: BTreeSet<i64>,
+    /// The organization's current users, when users outside it count as departed
+    pub organization: Option<BTreeSet<i64>>,
+}
+
+impl Departed {
+    /// Why `user_id` counts as departed, or `None` if it does not.
+    fn reason(&self, user_id: i64) -> Option<&'static str> {
+        if self.listed.contains(&user_id) {
+            Some(LISTED)
+        } else if self
+            .organization
+            .as_ref()
+            .is_some_and(|users| !users.contains(&user_id))
+        {
+            Some(NOT_IN_ORGANIZATION)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SweptKey {
+    pub id: String,
+    pub name: Option<String>,
+    pub account_id: Option<i64>,
+    pub user_id: i64,
+    pub reason: String,
+    pub deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a sweep found and revoked, written to the reports directory.
+#[derive(Serialize)]
+pub struct Report {
+    pub generated_at: DateTime<Utc>,
+    pub departed_users: Vec<i64>,
+    pub keys: Vec<SweptKey>,
+    /// User keys the selection was made from
+    #[serde(skip)]
+    pub user_keys: usize,
+}
+
+impl Report {
+    /// Whether the keys picked only because their owners are missing from the organization
+    /// are more than [`MAX_UNLISTED_PERCENT`] of the user keys.
+    pub fn mostly_unlisted(&self) -> bool {
+        let unlisted = self
+            .keys
+            .iter()
+            .filter(|key| key.reason == NOT_IN_ORGANIZATION)
+            .count();
+        unlisted * 100 > self.user_keys * MAX_UNLISTED_PERCENT
+    }
+
+    pub fn deleted(&self) -> usize {
+        self.keys.iter().filter(|key| key.deleted).count()
+    }
+}
+
+/// The user keys among `keys` that belong to departed users.
+pub fn select(keys: &[ApiKey], departed: &Departed) -> Report {
+    let user_keys: Vec<&ApiKey> = keys.iter().filter(|key| key.is_user_key()).collect();
+    let keys: Vec<SweptKey> = user_keys
+        .iter()
+        .filter_map(|key| {
+            let user_id = key.user_id?;
+            let reason = departed.reason(user_id)?;
+            Some(SweptKey {
+                id: key.id.clone(),
+                name: key.name.clone(),
+                account_id: key.account_id,
+                user_id,
+                reason: reason.to_string(),
+                deleted: false,
+                error: None,
+            })
+        })
+        .collect();
+    let departed_users: BTreeSet<i64> = keys.iter().map(|key| key.user_id).collect();
+    Report {
+        generated_at: Utc::now(),
+        departed_users: departed_users.into_iter().collect(),
+        keys,
+        user_keys: user_keys.len(),
+    }
+}
+
+/// Delete the selected keys in one mutation and record in `report` which were deleted.
+pub async fn revoke(client: &NewRelicClient, report: &mut Report) -> anyhow::Result<()> {
+    let ids: Vec<String> = report.keys.iter().map(|key| key.id.clone()).collect();
+    let outcome = inventory::delete_keys(client, &[], &ids).await?;
+    for key in &mut report.keys {
+        key.deleted = outcome.deleted.contains(&key.id);
+        if !key.deleted {
+            key.error = Some(if outcome.errors.is_empty() {
+                "not reported as deleted".to_string()
+            } else {
+                outcome.errors.join(", ")
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, key_type: &str, user_id: i64) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "name": format!("{} key", id), "type": key_type, "accountId": 1, "userId": user_id
+        }))
+    }
+
+    #[test]
+    fn test_parse_user_ids_reads_the_first_column() {
+        let csv =
+            "user_id,email\n# contractors\n1001,jane@example.com\n\n\"1002\",joe@example.com\n";
+        assert_eq!(
+            parse_user_ids(csv).unwrap().into_iter().collect::<Vec<_>>(),
+            [1001, 1002]
+        );
+        assert_eq!(parse_user_ids("1003").unwrap().len(), 1);
+        let error = parse_user_ids("1001\njane@example.com\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 2: 'jane@example.com' is not a user ID"
+        );
+    }
+
+    #[test]
+    fn test_select_keeps_user_keys_of_departed_users() {
+        let keys = [
+            key("U1", "USER", 5),
+            key("U2", "USER", 6),
+            key("U3", "USER", 7),
+            key("I1", "INGEST", 5),
+        ];
+        let departed = Departed {
+            listed: BTreeSet::from([5]),
+            organization: Some(BTreeSet::from([5, 6])),
+        };
+        let report = select(&keys, &departed);
+        let selected: Vec<(&str, &str)> = report
+            .keys
+            .iter()
+            .map(|key| (key.id.as_str(), key.reason.as_str()))
+            .collect();
+        assert_eq!(
+            selected,
+            [
+                ("U1", "listed as departed"),
+                ("U3", "no longer in the organization")
+            ]
+        );
+        assert_eq!(report.departed_users, [5, 7]);
+        // U3 is one of three user keys.
+        assert!(report.mostly_unlisted());
+
+        let listed_only = Departed {
+            listed: BTreeSet::from([6]),
+            organization: None,
+        };
+        assert_eq!(select(&keys, &listed_only).keys.len(), 1);
+    }
+
+    #[test]
+    fn test_organization_must_include_the_caller() {
+        let users = BTreeSet::from([5, 6]);
+        assert!(check_complete(&users, Some(5)).is_ok());
+        assert!(check_complete(&users, Some(7)).is_err());
+        assert!(check_complete(&users, None).is_err());
+        assert!(check_complete(&BTreeSet::new(), Some(5)).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! What a mutation request touches, for middleware that vets mutations before they are sent:
+//! the accounts named in its variables and the keys it names by ID. Lookups go down the rest of
+//! the middleware chain with the credentials of the request being vetted. A lookup that fails
+//! is an error, so middleware that cannot vet a mutation refuses it rather than letting it through.
+
+use crate::inventory;
+use crate::middleware::Next;
+use crate::transport::HttpRequest;
+
+/// Every `accountId` in the variables of a mutation.
+pub(crate) fn accounts(variables: &serde_json::Value) -> Vec<i64> {
+    let mut accounts = Vec::new();
+    collect_accounts(variables, &mut accounts);
+    accounts
+}
+
+fn collect_accounts(value: &serde_json::Value, accounts: &mut Vec<i64>) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (name, value) in object {
+                match (name.as_str(), value.as_i64()) {
+                    ("accountId", Some(id)) => accounts.push(id),
+                    _ => collect_accounts(value, accounts),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_accounts(item, accounts);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The keys a delete or update names by ID, with their types.
+pub(crate) fn key_ids(variables: &serde_json::Value) -> Vec<(String, &'static str)> {
+    let mut ids = deleted_key_ids(variables);
+    let keys = &variables["keys"];
+    for (field, key_type) in [("ingest", "INGEST"), ("user", "USER")] {
+        for key in keys[field].as_array().into_iter().flatten() {
+            ids.extend(key["keyId"].as_str().map(|id| (id.to_string(), key_type)));
+        }
+    }
+    ids
+}
+
+/// The keys a delete names, with their types.
+pub(crate) fn deleted_key_ids(variables: &serde_json::Value) -> Vec<(String, &'static str)> {
+    let keys = &variables["keys"];
+    let mut ids = Vec::new();
+    for (field, key_type) in [("ingestKeyIds", "INGEST"), ("userKeyIds", "USER")] {
+        for id in keys[field].as_array().into_iter().flatten() {
+            ids.extend(id.as_str().map(|id| (id.to_string(), key_type)));
+        }
+    }
+    ids
+}
+
+/// Send a query down the rest of the chain and return its `data`. Fails on a non-2xx status, a
+/// body that is not JSON, or GraphQL `errors`.
+async fn query(
+    request: &HttpRequest,
+    next: Next<'_>,
+    query: String,
+    variables: serde_json::Value,
+) -> anyhow::Result<serde_json::Value> {
+    let body = serde_json::json!({ "query": query, "variables": variables });
+    let lookup = HttpRequest {
+        url: request.url.clone(),
+        headers: request.headers.clone(),
+        body: serde_json::to_vec(&body)?,
+    };
+    let response = next.run(lookup).await?;
+    if !(200..300).contains(&response.status) {
+        anyhow::bail!("Lookup returned HTTP {}", response.status);
+    }
+    let mut body: serde_json::Value = serde_json::from_slice(&response.body)
+        .map_err(|e| anyhow::anyhow!("Invalid lookup response: {}", e))?;
+    let messages: Vec<&str> = body["errors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|error| error["message"].as_str().unwrap_or("unknown error"))
+        .collect();
+    if !messages.is_empty() {
+        anyhow::bail!("Lookup failed: {}", messages.join(", "));
+    }
+    Ok(body["data"].take())
+}
+
+/// A key as NerdGraph returns it, or null when it does not exist.
+pub(crate) async fn lookup(
+    request: &HttpRequest,
+    next: Next<'_>,
+    key_id: &str,
+    key_type: &str,
+) -> anyhow::Result<serde_json::Value> {
+    let variables = serde_json::json!({ "id": key_id, "keyType": key_type });
+    let mut data = query(request, next, inventory::get_query(), variables).await?;
+    Ok(data["actor"]["apiAccess"]["key"].take())
+}
+
+/// Every key of `key_type` in an account, following pagination cursors.
+pub(crate) async fn search(
+    request: &HttpRequest,
+    next: Next<'_>,
+    account_id: i64,
+    key_type: &str,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let mut keys = Vec::new();
+    let mut cursor = serde_json::Value::Null;
+    loop {
+        let variables = serde_json::json!({
+            "query": { "types": [key_type], "scope": { "accountIds": [account_id] } },
+            "cursor": cursor,
+        });
+        let mut data = query(request, next, inventory::search_query(), variables).await?;
+        let page = &mut data["actor"]["apiAccess"]["keySearch"];
+        if let serde_json::Value::Array(found) = page["keys"].take() {
+            keys.extend(found);
+        }
+        match page["nextCursor"].take() {
+            serde_json::Value::String(next) if !next.is_empty() => {
+                cursor = serde_json::Value::String(next)
+            }
+            _ => return Ok(keys),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mutation_targets() {
+        let variables = serde_json::json!({
+            "keys": {
+                "ingest": [{ "accountId": 1, "name": "a" }, { "keyId": "K1", "name": "b" }],
+                "userKeyIds": ["U1"],
+            }
+        });
+        assert_eq!(accounts(&variables), [1]);
+        assert_eq!(
+            key_ids(&variables),
+            [("U1".to_string(), "USER"), ("K1".to_string(), "INGEST")]
+        );
+        assert_eq!(deleted_key_ids(&variables).len(), 1);
+    }
+}
@@ -0,0 +1,350 @@
+//! Compares the `newrelic_api_access_key` resources in a Terraform or OpenTofu state file with the
+//! keys NerdGraph has, to find keys deleted or edited out-of-band and keys nobody manages.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::{self, ApiKey};
+use crate::output::{Format, Renderer};
+use crate::NewRelicClient;
+
+const RESOURCE_TYPE: &str = "newrelic_api_access_key";
+
+#[derive(Deserialize)]
+struct State {
+    version: u32,
+    #[serde(default)]
+    resources: Vec<Resource>,
+}
+
+#[derive(Deserialize)]
+struct Resource {
+    #[serde(default)]
+    module: Option<String>,
+    mode: String,
+    #[serde(rename = "type")]
+    resource_type: String,
+    name: String,
+    #[serde(default)]
+    instances: Vec<Instance>,
+}
+
+#[derive(Deserialize)]
+struct Instance {
+    #[serde(default)]
+    index_key: Option<serde_json::Value>,
+    attributes: Attributes,
+}
+
+#[derive(Deserialize)]
+struct Attributes {
+    id: String,
+    account_id: Option<i64>,
+    key_type: Option<String>,
+    name: Option<String>,
+    notes: Option<String>,
+}
+
+/// A key as Terraform last recorded it.
+#[derive(Debug, PartialEq)]
+struct ManagedKey {
+    address: String,
+    id: String,
+    account_id: Option<i64>,
+    key_type: Option<String>,
+    name: Option<String>,
+    notes: Option<String>,
+}
+
+fn managed_keys(state: &str) -> anyhow::Result<Vec<ManagedKey>> {
+    let state: State = serde_json::from_str(state)
+        .map_err(|e| anyhow::anyhow!("Not a Terraform state file: {}", e))?;
+    if state.version != 4 {
+        anyhow::bail!(
+            "Unsupported Terraform state version {} (expected 4)",
+            state.version
+        );
+    }
+    let mut keys = Vec::new();
+    for resource in state.resources {
+        if resource.mode != "managed" || resource.resource_type != RESOURCE_TYPE {
+            continue;
+        }
+        for instance in resource.instances {
+            let mut address = format!("{}.{}", RESOURCE_TYPE, resource.name);
+            if let Some(module) = &resource.module {
+                address = format!("{}.{}", module, address);
+            }
+            match &instance.index_key {
+                Some(serde_json::Value::String(key)) => address += &format!("[\"{}\"]", key),
+                Some(index) => address += &format!("[{}]", index),
+                None => {}
+            }
+            let attributes = instance.attributes;
+            // Imported resources may keep the `<id>:<type>` import ID.
+            let id = match attributes.id.split_once(':') {
+                Some((id, _)) => id.to_string(),
+                None => attributes.id,
+            };
+            keys.push(ManagedKey {
+                address,
+                id,
+                account_id: attributes.account_id,
+                key_type: attributes.key_type,
+                name: attributes.name,
+                notes: attributes.notes,
+            });
+        }
+    }
+    Ok(keys)
+}
+
+/// One difference between the state and NerdGraph.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Drift {
+    /// `deleted` (in the state, gone from NerdGraph), `changed` or `unmanaged` (not in the state)
+    pub status: &'static str,
+    pub address: Option<String>,
+    pub id: String,
+    pub key_type: Option<String>,
+    pub account_id: Option<i64>,
+    pub name: Option<String>,
+    pub detail: String,
+}
+
+fn compare(managed: &[ManagedKey], live: &[ApiKey]) -> Vec<Drift> {
+    let live_by_id: HashMap<&str, &ApiKey> = live.iter().map(|k| (k.id.as_str(), k)).collect();
+    let mut drift = Vec::new();
+    for key in managed {
+        let Some(live) = live_by_id.get(key.id.as_str()) else {
+            drift.push(Drift {
+                status: "deleted",
+                address: Some(key.address.clone()),
+                id: key.id.clone(),
+                key_type: key.key_type.clone(),
+                account_id: key.account_id,
+                name: key.name.clone(),
+                detail: "in the state but not in NerdGraph".to_string(),
+            });
+            continue;
+        };
+        let mut changes = Vec::new();
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        for (field, recorded, actual) in [
+            ("name", &key.name, &live.name),
+            ("notes", &key.notes, &live.notes),
+        ] {
+            if text(recorded) != text(actual) {
+                changes.push(format!(
+                    "{} '{}' -> '{}'",
+                    field,
+                    text(recorded),
+                    text(actual)
+                ));
+            }
+        }
+        if !changes.is_empty() {
+            drift.push(Drift {
+                status: "changed",
+                address: Some(key.address.clone()),
+                id: key.id.clone(),
+                key_type: live.key_type.clone(),
+                account_id: live.account_id,
+                name: live.name.clone(),
+                detail: changes.join(", "),
+            });
+        }
+    }
+
+    let managed_ids: BTreeSet<&str> = managed.iter().map(|k| k.id.as_str()).collect();
+    for key in live {
+        if !managed_ids.contains(key.id.as_str()) {
+            drift.push(Drift {
+                status: "unmanaged",
+                address: None,
+                id: key.id.clone(),
+                key_type: key.key_type.clone(),
+                account_id: key.account_id,
+                name: key.name.clone(),
+                detail: "in NerdGraph but not in the state".to_string(),
+            });
+        }
+    }
+    drift
+}
+
+const HEADERS: [&str; 7] = [
+    "STATUS", "ADDRESS", "ID", "TYPE", "ACCOUNT", "NAME", "DETAIL",
+];
+
+/// The drift between `managed` keys and NerdGraph.
+struct DriftReport<'a> {
+    managed: usize,
+    drift: &'a [Drift],
+}
+
+impl Renderer for DriftReport<'_> {
+    fn data(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::to_value(self.drift)?)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        HEADERS.map(String::from).to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.drift
+            .iter()
+            .map(|d| {
+                vec![
+                    d.status.to_string(),
+                    d.address.clone().unwrap_or_default(),
+                    d.id.clone(),
+                    d.key_type.clone().unwrap_or_default(),
+                    d.account_id.map(|id| id.to_string()).unwrap_or_default(),
+                    d.name.clone().unwrap_or_default(),
+                    d.detail.clone(),
+                ]
+            })
+            .collect()
+    }
+
+    fn empty_message(&self) -> Option<String> {
+        Some(format!(
+            "No drift: {} managed key(s) match NerdGraph and every key is managed",
+            self.managed
+        ))
+    }
+}
+
+/// Compare `state_file` with the keys in `account_ids`, or in the accounts the state refers to
+/// when none are given. Fails when anything drifted.
+pub async fn check(
+    client: &NewRelicClient,
+    state_file: &Path,
+    account_ids: Option<Vec<i64>>,
+    format: Format,
+) -> anyhow::Result<()> {
+    let state = std::fs::read_to_string(state_file)
+        .map_err(|e| anyhow::anyhow!("Could not read {}: {}", state_file.display(), e))?;
+    let mut managed = managed_keys(&state)?;
+    let account_ids = match account_ids {
+        Some(account_ids) => {
+            managed.retain(|k| k.account_id.is_none_or(|id| account_ids.contains(&id)));
+            account_ids
+        }
+        None => {
+            let accounts: BTreeSet<i64> = managed.iter().filter_map(|k| k.account_id).collect();
+            if accounts.is_empty() {
+                anyhow::bail!(
+                    "{} has no {} resources with an account_id; pass --account-id",
+                    state_file.display(),
+                    RESOURCE_TYPE
+                );
+            }
+            accounts.into_iter().collect()
+        }
+    };
+
+    let live = inventory::fetch(client, &account_ids, &["INGEST", "USER"]).await?;
+    let drift = compare(&managed, &live);
+
+    let rendered = DriftReport {
+        managed: managed.len(),
+        drift: &drift,
+    }
+    .render(&format)?;
+    print!("{}", rendered);
+
+    if !drift.is_empty() {
+        anyhow::bail!(
+            "{} key(s) drifted from {}",
+            drift.len(),
+            state_file.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: &str = r#"{
+        "version": 4,
+        "resources": [
+            {
+                "mode": "managed", "type": "newrelic_api_access_key", "name": "ci",
+                "instances": [{"attributes": {
+                    "id": "K1", "account_id": 1, "key_type": "INGEST", "name": "ci", "notes": ""
+                }}]
+            },
+            {
+                "module": "module.team", "mode": "managed", "type": "newrelic_api_access_key",
+                "name": "user", "instances": [
+                    {"index_key": "jane", "attributes": {
+                        "id": "K2:USER", "account_id": 1, "key_type": "USER", "name": "jane"
+                    }},
+                    {"index_key": 0, "attributes": {
+                        "id": "K3", "account_id": 1, "key_type": "USER", "name": "gone"
+                    }}
+                ]
+            },
+            {
+                "mode": "data", "type": "newrelic_api_access_key", "name": "ignored",
+                "instances": [{"attributes": {"id": "K9"}}]
+            }
+        ]
+    }"#;
+
+    fn live(id: &str, name: &str, notes: Option<&str>) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": id, "name": name, "notes": notes, "type": "USER",
+            "createdAt": 1, "accountId": 1
+        }))
+    }
+
+    #[test]
+    fn test_managed_keys_addresses() {
+        let keys = managed_keys(STATE).unwrap();
+        let addresses: Vec<(&str, &str)> = keys
+            .iter()
+            .map(|k| (k.address.as_str(), k.id.as_str()))
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                ("newrelic_api_access_key.ci", "K1"),
+                ("module.team.newrelic_api_access_key.user[\"jane\"]", "K2"),
+                ("module.team.newrelic_api_access_key.user[0]", "K3"),
+            ]
+        );
+        assert!(managed_keys(r#"{"version": 3, "modules": []}"#).is_err());
+        assert!(managed_keys("[]").is_err());
+    }
+
+    #[test]
+    fn test_compare_reports_deleted_changed_and_unmanaged() {
+        let managed = managed_keys(STATE).unwrap();
+        let live = vec![
+            live("K1", "ci", None),
+            live("K2", "jane (rotated)", None),
+            live("K4", "hand-made", None),
+        ];
+        let drift = compare(&managed, &live);
+        let summary: Vec<(&str, &str, &str)> = drift
+            .iter()
+            .map(|d| (d.status, d.id.as_str(), d.detail.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("changed", "K2", "name 'jane' -> 'jane (rotated)'"),
+                ("deleted", "K3", "in the state but not in NerdGraph"),
+                ("unmanaged", "K4", "in NerdGraph but not in the state"),
+            ]
+        );
+    }
+}
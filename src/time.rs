@@ -0,0 +1,104 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// "just now", "5 minutes ago", "3 months ago", "2 years ago".
+pub fn ago(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now - then;
+    let days = elapsed.num_days();
+    let (amount, unit) = if days >= 365 {
+        (days / 365, "year")
+    } else if days >= 30 {
+        (days / 30, "month")
+    } else if days > 0 {
+        (days, "day")
+    } else if elapsed.num_hours() > 0 {
+        (elapsed.num_hours(), "hour")
+    } else if elapsed.num_minutes() > 0 {
+        (elapsed.num_minutes(), "minute")
+    } else {
+        return "just now".to_string();
+    };
+    format!(
+        "{} {}{} ago",
+        amount,
+        unit,
+        if amount == 1 { "" } else { "s" }
+    )
+}
+
+/// A point in time given as RFC 3339 (`2024-05-01T12:00:00Z`), a date (`2024-05-01`, midnight
+/// UTC) or an age relative to `now` (`12h`, `30d`, `2w`, `6mo`, `1y`).
+pub fn parse_time(value: &str, now: DateTime<Utc>) -> anyhow::Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    let age = parse_age(value).map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid time '{}' (expected RFC 3339, YYYY-MM-DD or an age like 30d, 6mo or 1y)",
+            value
+        )
+    })?;
+    Ok(now - age)
+}
+
+/// A duration such as `12h`, `7d`, `2w`, `6mo` (30 days each) or `1y` (365 days).
+pub fn parse_age(value: &str) -> anyhow::Result<Duration> {
+    let value = value.trim();
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid age '{}' (expected a number with h, d, w, mo or y, e.g. 7d)",
+            value
+        )
+    };
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    match unit {
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        "mo" => Ok(Duration::days(amount * 30)),
+        "y" => Ok(Duration::days(amount * 365)),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ago() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(ago(now, now), "just now");
+        assert_eq!(ago(now - Duration::minutes(1), now), "1 minute ago");
+        assert_eq!(ago(now - Duration::hours(3), now), "3 hours ago");
+        assert_eq!(ago(now - Duration::days(20), now), "20 days ago");
+        assert_eq!(ago(now - Duration::days(95), now), "3 months ago");
+        assert_eq!(ago(now - Duration::days(800), now), "2 years ago");
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(
+            parse_time("2024-05-01T12:00:00+02:00", now).unwrap(),
+            DateTime::from_timestamp(1_714_557_600, 0).unwrap()
+        );
+        assert_eq!(
+            parse_time("2024-05-01", now).unwrap(),
+            DateTime::from_timestamp(1_714_521_600, 0).unwrap()
+        );
+        assert_eq!(parse_time("30d", now).unwrap(), now - Duration::days(30));
+        assert_eq!(parse_time("6mo", now).unwrap(), now - Duration::days(180));
+        assert!(parse_time("30", now).is_err());
+        assert!(parse_time("soon", now).is_err());
+        assert_eq!(parse_age("7d").unwrap(), Duration::days(7));
+        assert!(parse_age("7").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! `transfer`: hand a user key to another user, e.g. when its owner leaves. NerdGraph cannot
+//! change the owner of a user key, so the key is recreated under the new user with the same
+//! account and name, its notes carry the new `owner:` label, and the old key is deleted.
+
+use serde::Serialize;
+
+use crate::inventory::{self, ApiKey, NewKey};
+use crate::{rotation, NewRelicClient};
+
+/// `notes` with each `owner:` label replaced by `owner:<owner>`, or the label appended if there
+/// is none. Everything else, including line breaks and spacing, is kept as it is.
+pub fn with_owner(notes: Option<&str>, owner: &str) -> String {
+    let notes = notes.unwrap_or_default();
+    let label = format!("owner:{}", owner);
+    let mut result = String::with_capacity(notes.len() + label.len() + 1);
+    let mut replaced = false;
+    let mut rest = notes;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        let end = rest[start..]
+            .find(char::is_whitespace)
+            .map_or(rest.len(), |len| start + len);
+        result.push_str(&rest[..start]);
+        let word = &rest[start..end];
+        if word.starts_with("owner:") {
+            result.push_str(&label);
+            replaced = true;
+        } else {
+            result.push_str(word);
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    if !replaced {
+        if !result.is_empty() && !result.ends_with(char::is_whitespace) {
+            result.push(' ');
+        }
+        result.push_str(&label);
+    }
+    result
+}
+
+/// The key that replaces `key` under `to_user`, labeled with `owner` (default: the user ID).
+pub fn plan(key: &ApiKey, to_user: i64, owner: Option<&str>) -> anyhow::Result<NewKey> {
+    if !key.is_user_key() {
+        anyhow::bail!(
+            "Key {} is not a USER key; only user keys belong to a user",
+            key.id
+        );
+    }
+    if key.user_id == Some(to_user) {
+        anyhow::bail!("Key {} already belongs to user {}", key.id, to_user);
+    }
+    let mut spec = NewKey::replacing(key)?;
+    spec.user_id = Some(to_user);
+    let owner = owner
+        .map(str::to_string)
+        .unwrap_or_else(|| to_user.to_string());
+    spec.notes = Some(with_owner(key.notes.as_deref(), &owner));
+    Ok(spec)
+}
+
+#[derive(Serialize)]
+pub struct Transfer {
+    pub old_key_id: String,
+    pub from_user_id: Option<i64>,
+    /// The replacement, including its secret
+    pub new_key: ApiKey,
+    pub old_key_deleted: bool,
+    /// Why the old key is still active although its deletion was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete_error: Option<String>,
+}
+
+/// Create `spec` in place of `old_key`, hand the new key to `store`, then delete the old key
+/// unless `keep_old` is set. If `store` fails, the new key is deleted again and the old one is
+/// kept. A failed delete is reported rather than returned as an error, so the new secret is
+/// never lost.
+pub async fn transfer(
+    client: &NewRelicClient,
+    old_key: &ApiKey,
+    spec: &NewKey,
+    keep_old: bool,
+    store: impl AsyncFn(&[&ApiKey]) -> anyhow::Result<()>,
+) -> anyhow::Result<Transfer> {
+    let new_key = inventory::create(client, spec).await?;
+    if let Err(e) = store(&[&new_key]).await {
+        return Err(rotation::discard(client, &new_key, rotation::unstored(&new_key, e)).await);
+    }
+    let mut transfer = Transfer {
+        old_key_id: old_key.id.clone(),
+        from_user_id: old_key.user_id,
+        new_key,
+        old_key_deleted: false,
+        delete_error: None,
+    };
+    if !keep_old {
+        match rotation::retire(client, old_key).await {
+            Ok(()) => transfer.old_key_deleted = true,
+            Err(error) => transfer.delete_error = Some(error),
+        }
+    }
+    Ok(transfer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_type: &str) -> ApiKey {
+        ApiKey::fixture(serde_json::json!({
+            "id": "K1", "name": "deploy", "notes": "team:web owner:jane ci", "type": key_type, "accountId": 1, "userId": 5
+        }))
+    }
+
+    #[test]
+    fn test_plan_moves_the_key_to_the_new_owner() {
+        let spec = plan(&key("USER"), 9, Some("joe")).unwrap();
+        assert_eq!(spec.user_id, Some(9));
+        assert_eq!(spec.account_id, 1);
+        assert_eq!(spec.name, "deploy");
+        assert_eq!(spec.notes.as_deref(), Some("team:web owner:joe ci"));
+
+        assert_eq!(
+            plan(&key("USER"), 9, None).unwrap().notes.as_deref(),
+            Some("team:web owner:9 ci")
+        );
+        assert!(plan(&key("USER"), 5, None).is_err());
+        assert!(plan(&key("INGEST"), 9, None).is_err());
+        assert_eq!(with_owner(None, "joe"), "owner:joe");
+    }
+
+    #[test]
+    fn test_with_owner_keeps_the_rest_of_the_notes() {
+        assert_eq!(
+            with_owner(Some("Deploys web.\n  team:web   owner:jane\n"), "joe"),
+            "Deploys web.\n  team:web   owner:joe\n"
+        );
+        assert_eq!(
+            with_owner(Some("Deploys web.\n\nteam:web"), "joe"),
+            "Deploys web.\n\nteam:web owner:joe"
+        );
+        assert_eq!(with_owner(Some("ci\n"), "joe"), "ci\nowner:joe");
+    }
+}
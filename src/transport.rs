@@ -0,0 +1,163 @@
+//! The HTTP layer under [`NewRelicClient`](crate::NewRelicClient).
+//!
+//! NerdGraph only ever needs a JSON `POST`, so a transport is a single method. The default is
+//! `ReqwestTransport` (the `reqwest` feature), which also runs on `wasm32-unknown-unknown` via
+//! `fetch`; hyper, ureq or WASI HTTP backends implement [`Transport`] themselves, and tests can
+//! pass a closure that answers requests directly.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// A `POST` to the NerdGraph endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the GraphQL document is a mutation, which must not be sent twice.
+    pub fn is_mutation(&self) -> bool {
+        serde_json::from_slice::<serde_json::Value>(&self.body)
+            .ok()
+            .and_then(|body| {
+                body["query"]
+                    .as_str()
+                    .map(|q| q.trim_start().starts_with("mutation"))
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = anyhow::Result<HttpResponse>> + Send + 'a>>;
+/// Browser and worker runtimes are single-threaded and their `fetch` futures are not `Send`.
+#[cfg(target_arch = "wasm32")]
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<HttpResponse>> + 'a>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub type SleepFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+#[cfg(target_arch = "wasm32")]
+pub type SleepFuture<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+/// `Send + Sync` everywhere except wasm32, where nothing crosses threads.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSync: Send + Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync + ?Sized> MaybeSync for T {}
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Sends a request and returns the response, whatever its status.
+pub trait Transport: MaybeSync {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_>;
+
+    /// Wait between retries. The default returns immediately, so transports that own a timer
+    /// (like `ReqwestTransport`, on Tokio or the JS event loop) should override it.
+    fn sleep(&self, duration: Duration) -> SleepFuture<'_> {
+        let _ = duration;
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// Closures are transports, which keeps test doubles to a line or two.
+impl<F> Transport for F
+where
+    F: Fn(HttpRequest) -> anyhow::Result<HttpResponse> + MaybeSync,
+{
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        let response = self(request);
+        Box::pin(async move { response })
+    }
+}
+
+/// The default transport, backed by `reqwest`.
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    /// Use a preconfigured `reqwest` client (proxies, timeouts, custom roots).
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Transport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let mut builder = self.client.post(&request.url).body(request.body);
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+            let response = builder.send().await?;
+            let status = response.status().as_u16();
+            let headers = response
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect();
+            let body = response.bytes().await?.to_vec();
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn sleep(&self, duration: Duration) -> SleepFuture<'_> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    /// Tokio's timer needs its runtime, so browsers and workers wait on `setTimeout` instead.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    fn sleep(&self, duration: Duration) -> SleepFuture<'_> {
+        let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+        let timeout = js_sys::Promise::new(&mut |resolve, _reject| {
+            let global = js_sys::global();
+            let set_timeout = js_sys::Reflect::get(&global, &"setTimeout".into())
+                .map(js_sys::Function::from)
+                .expect("setTimeout is available in browsers and workers");
+            let _ = set_timeout.call2(&global, &resolve, &millis.into());
+        });
+        Box::pin(async move {
+            let _ = wasm_bindgen_futures::JsFuture::from(timeout).await;
+        })
+    }
+}
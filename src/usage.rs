@@ -0,0 +1,159 @@
+//! `usage`: when a key last changed and when it last showed up in an ingest error. New Relic
+//! does not meter use per key, so neither is a measure of use: a key that ingests or queries
+//! every minute can go months without either, and no activity never shows a key is unused.
+
+use chrono::{DateTime, Utc};
+
+use crate::{nrql, NewRelicClient};
+
+/// NRQL event types that record something happening to a key: the label of what they record,
+/// the event type, the condition matching the key and what the events are.
+const SOURCES: [(&str, &str, &str, &str); 2] = [
+    (
+        "last changed",
+        "NrAuditEvent",
+        "targetId = {id}",
+        "changes made to the key",
+    ),
+    (
+        "last ingest error",
+        "NrIntegrationError",
+        "message LIKE {id_pattern}",
+        "ingest errors naming the key",
+    ),
+];
+
+pub struct UsageSignal {
+    /// What the signal's time is, e.g. `last changed`
+    pub label: &'static str,
+    pub source: &'static str,
+    pub description: &'static str,
+    pub count: u64,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+fn signal_query(event_type: &str, condition: &str, key_id: &str, since_days: u32) -> String {
+    let condition = condition
+        .replace("{id_pattern}", &nrql::quote(&format!("%{}%", key_id)))
+        .replace("{id}", &nrql::quote(key_id));
+    format!(
+        "SELECT count(*), latest(timestamp) FROM {} WHERE {} SINCE {} days ago",
+        event_type, condition, since_days
+    )
+}
+
+fn parse_signal(
+    (label, source, description): (&'static str, &'static str, &'static str),
+    rows: &[serde_json::Value],
+) -> UsageSignal {
+    let row = rows.first();
+    let count = row
+        .and_then(|r| r.get("count"))
+        .and_then(|c| c.as_u64())
+        .unwrap_or(0);
+    let last_seen = row
+        .and_then(|r| r.get("latest.timestamp"))
+        .and_then(nrql::timestamp)
+        .filter(|_| count > 0);
+    UsageSignal {
+        label,
+        source,
+        description,
+        count,
+        last_seen,
+    }
+}
+
+/// Collect every signal for a key over the last `since_days` days.
+pub async fn signals(
+    client: &NewRelicClient,
+    account_id: i64,
+    key_id: &str,
+    since_days: u32,
+) -> anyhow::Result<Vec<UsageSignal>> {
+    let mut signals = Vec::with_capacity(SOURCES.len());
+    for (label, event_type, condition, description) in SOURCES {
+        let nrql = signal_query(event_type, condition, key_id, since_days);
+        let rows = nrql::query(client, account_id, &nrql).await?;
+        signals.push(parse_signal((label, event_type, description), &rows));
+    }
+    Ok(signals)
+}
+
+/// When the key last changed or appeared in an ingest error, whichever is later. This is not
+/// when it was last used.
+pub fn last_activity(signals: &[UsageSignal]) -> Option<DateTime<Utc>> {
+    signals.iter().filter_map(|s| s.last_seen).max()
+}
+
+pub async fn report(
+    client: &NewRelicClient,
+    account_id: i64,
+    key_id: &str,
+    since_days: u32,
+) -> anyhow::Result<()> {
+    let signals = signals(client, account_id, key_id, since_days).await?;
+
+    println!(
+        "Activity of key {} in account {} (last {} days):",
+        key_id, account_id, since_days
+    );
+    for signal in &signals {
+        match signal.last_seen {
+            Some(last_seen) => println!(
+                "  {:<18} {} ({} event(s) in {}: {})",
+                signal.label,
+                last_seen.format("%Y-%m-%d %H:%M:%S UTC"),
+                signal.count,
+                signal.source,
+                signal.description
+            ),
+            None => println!(
+                "  {:<18} none ({}: {})",
+                signal.label, signal.source, signal.description
+            ),
+        }
+    }
+    if last_activity(&signals).is_none() {
+        println!(
+            "No changes or ingest errors in the last {} days.",
+            since_days
+        );
+    }
+    println!(
+        "New Relic does not meter use per key, so this is not when the key was last used; check \
+         the systems that might hold it before deleting."
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_query() {
+        assert_eq!(
+            signal_query("NrAuditEvent", "targetId = {id}", "ABC", 30),
+            "SELECT count(*), latest(timestamp) FROM NrAuditEvent WHERE targetId = 'ABC' SINCE 30 days ago"
+        );
+        assert_eq!(
+            signal_query("NrIntegrationError", "message LIKE {id_pattern}", "ABC", 7),
+            "SELECT count(*), latest(timestamp) FROM NrIntegrationError WHERE message LIKE '%ABC%' SINCE 7 days ago"
+        );
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        let rows = vec![serde_json::json!({"count": 3, "latest.timestamp": 1700000000000_i64})];
+        let signal = parse_signal(("last changed", "NrAuditEvent", "changes"), &rows);
+        assert_eq!(signal.count, 3);
+        assert!(signal.last_seen.is_some());
+
+        let rows = vec![serde_json::json!({"count": 0, "latest.timestamp": null})];
+        let signal = parse_signal(("last changed", "NrAuditEvent", "changes"), &rows);
+        assert_eq!(signal.count, 0);
+        assert!(signal.last_seen.is_none());
+        assert!(last_activity(&[signal]).is_none());
+    }
+}
@@ -0,0 +1,110 @@
+//! GraphQL variables for [`NewRelicClient::execute_query`](crate::NewRelicClient::execute_query).
+//!
+//! ```
+//! use newrelic_apikeys_cli::Variables;
+//!
+//! let variables = Variables::new()
+//!     .string("id", "NRAK-ID")
+//!     .enum_value("keyType", "user")
+//!     .list("accountIds", [1, 2]);
+//! assert_eq!(
+//!     serde_json::to_string(&variables).unwrap(),
+//!     r#"{"accountIds":[1,2],"id":"NRAK-ID","keyType":"USER"}"#
+//! );
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+/// Named variable values, serialized in name order so that the same request always has the
+/// same body.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Variables(BTreeMap<String, serde_json::Value>);
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An `Int`.
+    pub fn int(self, name: &str, value: i64) -> Self {
+        self.value(name, serde_json::Value::from(value))
+    }
+
+    /// A `String` or `ID`.
+    pub fn string(self, name: &str, value: impl Into<String>) -> Self {
+        self.value(name, serde_json::Value::String(value.into()))
+    }
+
+    /// An enum value, which GraphQL spells in upper case: `user` becomes `USER`.
+    pub fn enum_value(self, name: &str, value: &str) -> Self {
+        self.string(name, value.to_uppercase())
+    }
+
+    /// A list, such as `[Int!]` of account IDs or `[ID!]` of group IDs.
+    pub fn list<T: Into<serde_json::Value>>(
+        self,
+        name: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.value(
+            name,
+            serde_json::Value::Array(values.into_iter().map(Into::into).collect()),
+        )
+    }
+
+    /// Any other value, usually an input object built with `serde_json::json!`.
+    pub fn value(mut self, name: &str, value: serde_json::Value) -> Self {
+        self.0.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&serde_json::Value> {
+        self.0.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Names and values, in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &serde_json::Value)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value))
+    }
+}
+
+impl FromIterator<(String, serde_json::Value)> for Variables {
+    fn from_iter<I: IntoIterator<Item = (String, serde_json::Value)>>(iter: I) -> Self {
+        Variables(iter.into_iter().collect())
+    }
+}
+
+impl From<HashMap<String, serde_json::Value>> for Variables {
+    fn from(variables: HashMap<String, serde_json::Value>) -> Self {
+        variables.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variables_serialize_in_name_order() {
+        let variables = Variables::new()
+            .string("nrql", "SELECT 1")
+            .int("accountId", 7)
+            .value("keys", serde_json::json!({"ingestKeyIds": []}));
+        assert_eq!(
+            serde_json::to_string(&variables).unwrap(),
+            r#"{"accountId":7,"keys":{"ingestKeyIds":[]},"nrql":"SELECT 1"}"#
+        );
+        assert_eq!(variables.get("accountId"), Some(&serde_json::json!(7)));
+        assert_eq!(
+            variables.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["accountId", "keys", "nrql"]
+        );
+    }
+}
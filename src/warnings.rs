@@ -0,0 +1,90 @@
+//! Notices about a command's results, kept on stderr so piped stdout stays clean.
+//!
+//! Each warning is one line with a stable code, `warning[partial-results]: ...`, or with
+//! `--warnings json` a JSON object per line for tools that collect them.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Code {
+    /// Some endpoints, accounts or pages could not be read; the results are incomplete
+    PartialResults,
+    /// The results come from a cache rather than NerdGraph
+    StaleData,
+    /// A secret was removed before it was stored
+    Redacted,
+    /// The config file or platform directories could not be used as configured
+    Config,
+    /// The local command history could not be read, written or signed
+    History,
+    /// A NerdGraph response lacked fields the CLI reads; see `--strict`
+    SchemaDrift,
+    /// Key secrets were written or printed without encryption
+    PlaintextSecrets,
+}
+
+impl Code {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::PartialResults => "partial-results",
+            Code::StaleData => "stale-data",
+            Code::Redacted => "redacted",
+            Code::Config => "config",
+            Code::History => "history",
+            Code::SchemaDrift => "schema-drift",
+            Code::PlaintextSecrets => "plaintext-secrets",
+        }
+    }
+}
+
+/// `text` (the default) or `json`.
+pub fn set_format(value: &str) -> anyhow::Result<()> {
+    match value.to_lowercase().as_str() {
+        "text" => JSON.store(false, Ordering::Relaxed),
+        "json" => JSON.store(true, Ordering::Relaxed),
+        other => {
+            return Err(anyhow::anyhow!(
+                "Unsupported warning format '{}' (expected text or json)",
+                other
+            ))
+        }
+    }
+    Ok(())
+}
+
+pub fn warn(code: Code, message: impl Display) {
+    eprintln!(
+        "{}",
+        line(code, &message.to_string(), JSON.load(Ordering::Relaxed))
+    );
+}
+
+fn line(code: Code, message: &str, json: bool) -> String {
+    if json {
+        serde_json::json!({ "level": "warning", "code": code.as_str(), "message": message })
+            .to_string()
+    } else {
+        format!("warning[{}]: {}", code.as_str(), message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        assert_eq!(
+            line(Code::PartialResults, "profile 'eu' failed", false),
+            "warning[partial-results]: profile 'eu' failed"
+        );
+        assert_eq!(
+            line(Code::StaleData, "cached", true),
+            r#"{"code":"stale-data","level":"warning","message":"cached"}"#
+        );
+        assert!(set_format("xml").is_err());
+    }
+}
@@ -0,0 +1,25 @@
+//! JSON notifications for webhooks. Every payload carries a `text` summary next to its details,
+//! which is what chat webhooks such as Slack's and Teams' display.
+
+use serde::Serialize;
+
+/// POST `details` with `text` added as the summary.
+pub async fn notify<T: Serialize>(url: &str, text: &str, details: &T) -> anyhow::Result<()> {
+    let mut body = serde_json::to_value(details)?;
+    match body.as_object_mut() {
+        Some(object) => {
+            object.insert("text".to_string(), serde_json::json!(text));
+        }
+        None => body = serde_json::json!({"text": text, "details": body}),
+    }
+    let response = reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Could not notify the webhook {}: {}", url, e))?;
+    if !response.status().is_success() {
+        anyhow::bail!("The webhook {} answered HTTP {}", url, response.status());
+    }
+    Ok(())
+}
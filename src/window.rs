@@ -0,0 +1,384 @@
+//! Maintenance windows: `[[maintenance_windows.<group>]]` in the config says when keys in the
+//! accounts of an account group may change. Outside every window of a group, [`Guard`] refuses
+//! mutations that touch one of its accounts, unless the CLI runs with `--override-window`.
+//!
+//! Deletes and updates only name key IDs, so the guard looks those keys up first; see
+//! [`targets`]. While a window is closed, a key whose account cannot be learned is refused.
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+
+use crate::config::{Config, MaintenanceWindow};
+use crate::middleware::{Middleware, Next};
+use crate::transport::{HttpRequest, TransportFuture};
+use crate::{targets, time};
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// How far ahead the next opening of a closed window is looked for.
+const LOOKAHEAD: Duration = Duration::days(366);
+
+/// A five-field cron expression: minute, hour, day of month, month and day of week.
+#[derive(Debug, Clone)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// The day of month and day of week fields were `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            anyhow::bail!(
+                "Invalid cron expression '{}' (expected 5 fields: minute hour day month weekday)",
+                expression
+            );
+        };
+        let field = |text: &str, min: u32, max: u32, names: &[&str]| {
+            parse_field(text, min, max, names)
+                .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", expression, e))
+        };
+        let mut weekdays = field(weekday, 0, 7, &WEEKDAYS)?;
+        // 7 is Sunday as well.
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Cron {
+            minutes: field(minute, 0, 59, &[])?,
+            hours: field(hour, 0, 23, &[])?,
+            days: field(day, 1, 31, &[])?,
+            months: field(month, 1, 12, &MONTHS)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = bit(self.days, time.day());
+        let weekday = bit(self.weekdays, time.weekday().num_days_from_sunday());
+        // As in cron, a day matches either restricted field when both are given.
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        };
+        bit(self.minutes, time.minute())
+            && bit(self.hours, time.hour())
+            && bit(self.months, time.month())
+            && day_matches
+    }
+}
+
+/// `*`, `5`, `1-5`, `*/15`, `10-40/10`, `MON-FRI` or a comma-separated list of those, as a bit
+/// mask of the values it covers.
+fn parse_field(text: &str, min: u32, max: u32, names: &[&str]) -> anyhow::Result<u64> {
+    let value = |text: &str| -> anyhow::Result<u32> {
+        let upper = text.to_uppercase();
+        if let Some(index) = names.iter().position(|name| *name == upper) {
+            return Ok(index as u32 + min);
+        }
+        let number: u32 = text
+            .parse()
+            .map_err(|_| anyhow::anyhow!("'{}' is not a number", text))?;
+        if !(min..=max).contains(&number) {
+            anyhow::bail!("{} is outside {}-{}", number, min, max);
+        }
+        Ok(number)
+    };
+    let mut mask = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| anyhow::anyhow!("invalid step '{}'", step))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let single = value(range)?;
+                    (single, single)
+                }
+            },
+        };
+        if start > end {
+            anyhow::bail!("range {} is backwards", range);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+#[derive(Debug, Clone)]
+pub struct Window {
+    schedule: String,
+    cron: Cron,
+    duration: Duration,
+}
+
+impl Window {
+    pub fn parse(window: &MaintenanceWindow) -> anyhow::Result<Self> {
+        let duration = time::parse_age(&window.duration)?;
+        if duration < Duration::minutes(1) {
+            anyhow::bail!("A maintenance window must last at least a minute");
+        }
+        Ok(Window {
+            schedule: window.schedule.clone(),
+            cron: Cron::parse(&window.schedule)?,
+            duration,
+        })
+    }
+
+    /// Whether the window opened less than `duration` before `now`.
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let now = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+        (0..self.duration.num_minutes()).any(|m| self.cron.matches(now - Duration::minutes(m)))
+    }
+
+    /// When the window opens next after `now`, if within a year.
+    pub fn next_opening(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = now.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        (0..LOOKAHEAD.num_minutes())
+            .map(|m| start + Duration::minutes(m))
+            .find(|time| self.cron.matches(*time))
+    }
+}
+
+#[derive(Clone)]
+struct Group {
+    name: String,
+    accounts: Vec<i64>,
+    windows: Vec<Window>,
+}
+
+impl Group {
+    fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.windows.iter().any(|window| window.is_open(now))
+    }
+
+    fn refusal(&self, account_id: i64, now: DateTime<Utc>) -> anyhow::Error {
+        let schedules: Vec<String> = self
+            .windows
+            .iter()
+            .map(|w| {
+                format!(
+                    "'{}' for {}h",
+                    w.schedule,
+                    w.duration.num_minutes() as f64 / 60.0
+                )
+            })
+            .collect();
+        let next = self
+            .windows
+            .iter()
+            .filter_map(|w| w.next_opening(now))
+            .min()
+            .map(|next| format!("; it opens next at {}", next.format("%Y-%m-%d %H:%M UTC")))
+            .unwrap_or_default();
+        anyhow::anyhow!(
+            "Account {} is in account group '{}', whose maintenance window ({}) is closed{}. \
+             Pass --override-window to change its keys anyway",
+            account_id,
+            self.name,
+            schedules.join(", "),
+            next
+        )
+    }
+}
+
+/// Refuses mutations of accounts whose maintenance windows are all closed.
+#[derive(Clone)]
+pub struct Guard {
+    groups: Vec<Group>,
+}
+
+impl Guard {
+    /// The guard for the config's windows, or `None` when it has none.
+    pub fn new(config: &Config) -> anyhow::Result<Option<Self>> {
+        let mut groups = Vec::new();
+        for (name, windows) in &config.maintenance_windows {
+            let accounts = config.account_groups.get(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "maintenance_windows.{} has no account group of that name in account_groups",
+                    name
+                )
+            })?;
+            let windows = windows
+                .iter()
+                .map(Window::parse)
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(|e| anyhow::anyhow!("maintenance_windows.{}: {}", name, e))?;
+            groups.push(Group {
+                name: name.clone(),
+                accounts: accounts.clone(),
+                windows,
+            });
+        }
+        Ok((!groups.is_empty()).then_some(Guard { groups }))
+    }
+
+    async fn check(
+        &self,
+        request: &HttpRequest,
+        next: Next<'_>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let closed: Vec<&Group> = self.groups.iter().filter(|g| !g.is_open(now)).collect();
+        if closed.is_empty() {
+            return Ok(());
+        }
+        let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+        let variables = &body["variables"];
+        let mut accounts = targets::accounts(variables);
+        for (key_id, key_type) in targets::key_ids(variables) {
+            // The key may be in a closed account, so one whose account is unknown is refused.
+            let unknown = |reason: String| {
+                anyhow::anyhow!(
+                    "Could not find the account of key {} while a maintenance window is closed: \
+                     {}. Pass --override-window to change it anyway",
+                    key_id,
+                    reason
+                )
+            };
+            let key = targets::lookup(request, next, &key_id, key_type)
+                .await
+                .map_err(|e| unknown(format!("{:#}", e)))?;
+            let account_id = key["accountId"]
+                .as_i64()
+                .ok_or_else(|| unknown("the key was not found".to_string()))?;
+            accounts.push(account_id);
+        }
+        for account_id in accounts {
+            if let Some(group) = closed.iter().find(|g| g.accounts.contains(&account_id)) {
+                return Err(group.refusal(account_id, now));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Middleware for Guard {
+    fn handle<'a>(&'a self, request: HttpRequest, next: Next<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            if request.is_mutation() {
+                self.check(&request, next, Utc::now()).await?;
+            }
+            next.run(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::HttpResponse;
+    use crate::{inventory, NewRelicClient};
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_cron_matches() {
+        let cron = Cron::parse("*/15 2-4 * * SAT,SUN").unwrap();
+        // 2024-06-01 is a Saturday.
+        assert!(cron.matches(at("2024-06-01T02:30:00Z")));
+        assert!(!cron.matches(at("2024-06-01T02:31:00Z")));
+        assert!(!cron.matches(at("2024-06-03T02:30:00Z")));
+        let first = Cron::parse("0 0 1 JAN *").unwrap();
+        assert!(first.matches(at("2024-01-01T00:00:00Z")));
+        assert!(!first.matches(at("2024-02-01T00:00:00Z")));
+        assert!(Cron::parse("0 2 * *").is_err());
+        assert!(Cron::parse("61 * * * *").is_err());
+        assert!(Cron::parse("0 5-2 * * *").is_err());
+    }
+
+    #[test]
+    fn test_window_open_and_next_opening() {
+        let window = Window::parse(&MaintenanceWindow {
+            schedule: "0 2 * * SAT".to_string(),
+            duration: "4h".to_string(),
+        })
+        .unwrap();
+        assert!(window.is_open(at("2024-06-01T02:00:00Z")));
+        assert!(window.is_open(at("2024-06-01T05:59:30Z")));
+        assert!(!window.is_open(at("2024-06-01T06:00:00Z")));
+        assert_eq!(
+            window.next_opening(at("2024-06-03T12:00:00Z")),
+            Some(at("2024-06-08T02:00:00Z"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_guard_refuses_closed_accounts() {
+        let mut config = Config::default();
+        config.account_groups.insert("prod".into(), vec![1]);
+        config.maintenance_windows.insert(
+            "prod".into(),
+            vec![MaintenanceWindow {
+                // February 30th never comes.
+                schedule: "0 0 30 FEB *".to_string(),
+                duration: "1h".to_string(),
+            }],
+        );
+        let client = NewRelicClient::builder()
+            .api_key("NRAK-TEST")
+            .middleware(Guard::new(&config).unwrap().unwrap())
+            .transport(|request: HttpRequest| -> anyhow::Result<HttpResponse> {
+                let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+                let id = body["variables"]["id"].as_str().unwrap_or_default();
+                let key = match id {
+                    "prod-key" => serde_json::json!({ "id": id, "accountId": 1 }),
+                    "dev-key" => serde_json::json!({ "id": id, "accountId": 2 }),
+                    _ => serde_json::Value::Null,
+                };
+                let data = serde_json::json!({ "actor": { "apiAccess": { "key": key }},
+                    "apiAccessDeleteKeys": { "deletedKeys": [], "errors": [] }});
+                Ok(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: serde_json::to_vec(&serde_json::json!({ "data": data }))?,
+                })
+            })
+            .build()
+            .unwrap();
+
+        let error = inventory::delete_keys(&client, &["prod-key".to_string()], &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("account group 'prod'"));
+        assert!(
+            inventory::delete_keys(&client, &["dev-key".to_string()], &[])
+                .await
+                .is_ok()
+        );
+        let error = inventory::delete_keys(&client, &["gone-key".to_string()], &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("Could not find the account of key gone-key"));
+    }
+}
@@ -0,0 +1,1880 @@
+//! The `newrelic-apikeys-cli` binary against a NerdGraph stand-in: the documents and variables
+//! each subcommand sends, and how it handles successes, GraphQL errors, rate limits and
+//! malformed responses.
+
+mod common;
+
+use serde_json::{json, Value};
+use wiremock::matchers::{body_string_contains, method};
+use wiremock::{Mock, ResponseTemplate};
+
+use common::{graphql_error, key, key_search, nrql, stderr, stdout, NerdGraph};
+
+#[tokio::test]
+async fn test_list_follows_cursors() {
+    let nerdgraph = NerdGraph::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("keySearch"))
+        .and(body_string_contains("\"cursor\":\"page-2\""))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"data": {"actor": {
+                "apiAccess": {"keySearch": {"keys": [key("U1", "USER")], "nextCursor": null}}
+            }}})),
+        )
+        .with_priority(1)
+        .mount(&nerdgraph.server)
+        .await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            json!({"actor": {"apiAccess": {"keySearch": {
+                "keys": [key("I1", "INGEST")],
+                "nextCursor": "page-2",
+            }}}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "--format",
+            "json",
+            "list",
+            "--account-id",
+            "1",
+            "--account-id",
+            "2",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let listed: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let ids: Vec<&str> = listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| key["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["I1", "U1"]);
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0]["variables"],
+        json!({"query": {"scope": {"accountIds": [1, 2]}, "types": ["INGEST", "USER"]}})
+    );
+    assert_eq!(requests[1]["variables"]["cursor"], "page-2");
+}
+
+#[tokio::test]
+async fn test_create_sends_typed_inputs() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I1", "INGEST")], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "create",
+            "--account-id",
+            "1",
+            "--key-type",
+            "INGEST",
+            "--name",
+            "ci",
+            "--notes",
+            "team:web",
+            "--ingest-type",
+            "BROWSER",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("NRAK-SECRET-I1"));
+
+    let output = nerdgraph
+        .run(&[
+            "create",
+            "--account-id",
+            "1",
+            "--key-type",
+            "USER",
+            "--name",
+            "deploy",
+            "--user-id",
+            "5",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert!(requests[0]["query"]
+        .as_str()
+        .unwrap()
+        .contains("mutation($keys: ApiAccessCreateInput!)"));
+    assert_eq!(
+        requests[0]["variables"],
+        json!({"keys": {"ingest": [{
+            "accountId": 1, "ingestType": "BROWSER", "name": "ci", "notes": "team:web"
+        }]}})
+    );
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"user": [{"accountId": 1, "name": "deploy", "notes": null, "userId": 5}]}})
+    );
+}
+
+#[tokio::test]
+async fn test_create_reports_key_errors() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {
+                "createdKeys": [],
+                "errors": [{"message": "Account 1 is not accessible"}],
+            }}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "create",
+            "--account-id",
+            "1",
+            "--key-type",
+            "INGEST",
+            "--name",
+            "ci",
+        ])
+        .await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Account 1 is not accessible"));
+}
+
+#[tokio::test]
+async fn test_create_service_account_finds_groups_and_roles_past_the_first_page() {
+    let nerdgraph = NerdGraph::start().await;
+    let domains = |users: Value| {
+        json!({"actor": {"organization": {"userManagement": {"authenticationDomains": {
+            "authenticationDomains": [{
+                "id": "d-1", "name": "Default", "provisioningType": "MANUAL", "users": users
+            }]
+        }}}}})
+    };
+    let groups = |groups: Value, next: Value| {
+        json!({"actor": {"organization": {"userManagement": {"authenticationDomains": {
+            "authenticationDomains": [{"groups": {"groups": groups, "nextCursor": next}}]
+        }}}}})
+    };
+    let roles = |roles: Value, next: Value| {
+        json!({"actor": {"organization": {"authorizationManagement": {
+            "roles": {"roles": roles, "nextCursor": next}
+        }}}})
+    };
+    let second_page = |operation: &str, cursor: &str, data: Value| {
+        Mock::given(method("POST"))
+            .and(body_string_contains(operation))
+            .and(body_string_contains(format!("\"cursor\":\"{}\"", cursor)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": data })))
+            .with_priority(1)
+    };
+    second_page(
+        "groups(cursor",
+        "groups-2",
+        groups(json!([{"id": "g-2", "displayName": "CI"}]), Value::Null),
+    )
+    .mount(&nerdgraph.server)
+    .await;
+    nerdgraph
+        .answer(
+            "groups(cursor",
+            groups(
+                json!([{"id": "g-1", "displayName": "Admins"}]),
+                json!("groups-2"),
+            ),
+        )
+        .await;
+    second_page(
+        "roles(cursor",
+        "roles-2",
+        roles(
+            json!([{"id": "2", "name": "Read Only", "scope": "ACCOUNT"}]),
+            Value::Null,
+        ),
+    )
+    .mount(&nerdgraph.server)
+    .await;
+    nerdgraph
+        .answer(
+            "roles(cursor",
+            roles(
+                json!([{"id": "1", "name": "All Product Admin", "scope": "ACCOUNT"}]),
+                json!("roles-2"),
+            ),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "users(filter",
+            domains(json!({"users": [{"id": "1001", "name": "ci", "email": "ci@example.com"}]})),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "userManagementAddUsersToGroups",
+            json!({"userManagementAddUsersToGroups": {"groups": [{"id": "g-2"}]}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "authorizationManagementGrantAccess",
+            json!({"authorizationManagementGrantAccess": {"roles": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("U1", "USER")], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "create",
+            "--account-id",
+            "1",
+            "--key-type",
+            "USER",
+            "--name",
+            "deploy",
+            "--service-account",
+            "ci",
+            "--service-account-email",
+            "ci@example.com",
+            "--group",
+            "ci",
+            "--grant-role",
+            "read only",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    let sent = |operation: &str| {
+        requests
+            .iter()
+            .filter(|request| request["query"].as_str().unwrap().contains(operation))
+            .collect::<Vec<_>>()
+    };
+    assert!(sent("userManagementCreateGroup").is_empty());
+    assert_eq!(sent("groups(cursor")[1]["variables"]["cursor"], "groups-2");
+    assert_eq!(sent("roles(cursor")[1]["variables"]["cursor"], "roles-2");
+    assert_eq!(
+        sent("userManagementAddUsersToGroups")[0]["variables"],
+        json!({"options": {"groupIds": ["g-2"], "userIds": ["1001"]}})
+    );
+    assert_eq!(
+        sent("authorizationManagementGrantAccess")[0]["variables"]["options"],
+        json!({"groupId": "g-2", "accountAccessGrants": [{"accountId": 1, "roleId": "2"}]})
+    );
+}
+
+#[tokio::test]
+async fn test_update_and_delete_send_key_ids() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "apiAccessUpdateKeys",
+            json!({"apiAccessUpdateKeys": {"updatedKeys": [key("U1", "USER")], "errors": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {"deletedKeys": [{"id": "I1"}], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "update",
+            "--key-id",
+            "U1",
+            "--key-type",
+            "USER",
+            "--notes",
+            "team:web",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = nerdgraph.run(&["delete", "--key-id", "I1", "--yes"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(
+        requests[0]["variables"],
+        json!({"keys": {"user": [{"keyId": "U1", "notes": "team:web"}]}})
+    );
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"ingestKeyIds": ["I1"], "userKeyIds": []}})
+    );
+}
+
+#[tokio::test]
+async fn test_update_by_selector_sends_one_mutation() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            json!({"actor": {"apiAccess": {"keySearch": {
+                "keys": [key("U1", "USER"), key("U2", "USER")],
+                "nextCursor": null
+            }}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessUpdateKeys",
+            json!({"apiAccessUpdateKeys": {
+                "updatedKeys": [key("U1", "USER"), key("U2", "USER")],
+                "errors": []
+            }}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "update",
+            "--key-type",
+            "USER",
+            "--account-id",
+            "1",
+            "--selector",
+            "team=payments",
+            "--set-notes",
+            "owned by platform team",
+            "--rename-template",
+            "legacy-{name}",
+            "--yes",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("name: U2 key -> legacy-U2 key"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"user": [
+            {"keyId": "U1", "name": "legacy-U1 key", "notes": "owned by platform team"},
+            {"keyId": "U2", "name": "legacy-U2 key", "notes": "owned by platform team"},
+        ]}})
+    );
+}
+
+#[tokio::test]
+async fn test_clone_creates_the_key_in_the_target_account() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "key(id: $id",
+            json!({"actor": {"apiAccess": {"key": key("I1", "INGEST")}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I2", "INGEST")], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["clone", "--key-id", "I1", "--to-account", "456"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"ingest": [{
+            "accountId": 456,
+            "ingestType": "LICENSE",
+            "name": "I1 key",
+            "notes": "team:payments",
+        }]}})
+    );
+}
+
+#[tokio::test]
+async fn test_rotate_gets_creates_then_deletes() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "key(id: $id",
+            json!({"actor": {"apiAccess": {"key": key("I1", "INGEST")}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I2", "INGEST")], "errors": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {"deletedKeys": [{"id": "I1"}], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph.run(&["rotate", "--key-id", "I1", "--yes"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("NRAK-SECRET-I2"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 3);
+    assert_eq!(
+        requests[0]["variables"],
+        json!({"id": "I1", "keyType": "INGEST"})
+    );
+    assert_eq!(
+        requests[1]["variables"]["keys"]["ingest"][0]["name"],
+        "I1 key"
+    );
+    assert_eq!(
+        requests[2]["variables"],
+        json!({"keys": {"ingestKeyIds": ["I1"], "userKeyIds": []}})
+    );
+}
+
+#[tokio::test]
+async fn test_transfer_recreates_the_key_for_the_new_owner() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "key(id: $id",
+            json!({"actor": {"apiAccess": {"key": key("U1", "USER")}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("U2", "USER")], "errors": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {"deletedKeys": [{"id": "U1"}], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["transfer", "--key-id", "U1", "--to-user", "9", "--yes"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("NRAK-SECRET-U2"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 3);
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"user": [{
+            "accountId": 1,
+            "name": "U1 key",
+            "notes": "team:payments owner:9",
+            "userId": 9,
+        }]}})
+    );
+    assert_eq!(
+        requests[2]["variables"],
+        json!({"keys": {"ingestKeyIds": [], "userKeyIds": ["U1"]}})
+    );
+}
+
+/// Account 1 has user keys U1 of user 5 (the caller), U2 of user 9 and U3 of user 12; the
+/// organization has users 5 and 12.
+async fn departed_users_nerdgraph() -> NerdGraph {
+    let nerdgraph = NerdGraph::start().await;
+    let mut contractor = key("U2", "USER");
+    contractor["userId"] = json!(9);
+    let mut intern = key("U3", "USER");
+    intern["userId"] = json!(12);
+    nerdgraph
+        .answer(
+            "keySearch",
+            json!({"actor": {"apiAccess": {"keySearch": {
+                "keys": [key("U1", "USER"), contractor, intern],
+                "nextCursor": null
+            }}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "userManagement",
+            json!({"actor": {"organization": {"userManagement": {"authenticationDomains": {
+                "authenticationDomains": [{"id": "d1", "users": {
+                    "users": [{"id": "5"}, {"id": "12"}], "nextCursor": null
+                }}],
+                "nextCursor": null
+            }}}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "{ actor { user { id } } }",
+            json!({"actor": {"user": {"id": 5}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {
+                "deletedKeys": [{"id": "U2"}, {"id": "U3"}],
+                "errors": []
+            }}),
+        )
+        .await;
+    nerdgraph
+}
+
+#[tokio::test]
+async fn test_sweep_deletes_keys_of_departed_users() {
+    let nerdgraph = departed_users_nerdgraph().await;
+    std::fs::write(
+        nerdgraph.home().join("users.csv"),
+        "user_id,email\n12,intern@example.com\n",
+    )
+    .unwrap();
+
+    let output = nerdgraph
+        .run(&[
+            "sweep",
+            "departed-users",
+            "--account-id",
+            "1",
+            "--user-ids-file",
+            "users.csv",
+            "--not-in-organization",
+            "--report",
+            "sweep.json",
+            "--yes",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Deleted 2 of 2 key(s)"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 4);
+    assert_eq!(
+        requests[3]["variables"],
+        json!({"keys": {"ingestKeyIds": [], "userKeyIds": ["U2", "U3"]}})
+    );
+    let report: Value = serde_json::from_str(
+        &std::fs::read_to_string(nerdgraph.home().join("sweep.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(report["departed_users"], json!([9, 12]));
+    assert_eq!(report["keys"][1]["reason"], "listed as departed");
+}
+
+#[tokio::test]
+async fn test_sweep_refuses_yes_when_most_owners_are_only_missing_from_the_organization() {
+    let nerdgraph = departed_users_nerdgraph().await;
+    // U2 alone is a third of the user keys.
+    let output = nerdgraph
+        .run(&[
+            "sweep",
+            "departed-users",
+            "--account-id",
+            "1",
+            "--not-in-organization",
+            "--yes",
+        ])
+        .await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Refusing to sweep this many keys with --yes"));
+    assert_eq!(nerdgraph.requests().await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_output_file_is_kept_when_rotate_cannot_delete_the_old_key() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "key(id: $id",
+            json!({"actor": {"apiAccess": {"key": key("I1", "INGEST")}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I2", "INGEST")], "errors": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {
+                "deletedKeys": [],
+                "errors": [{"message": "Key I1 could not be deleted"}]
+            }}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "--output-file",
+            "keys.json",
+            "rotate",
+            "--key-id",
+            "I1",
+            "--yes",
+        ])
+        .await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("could not delete I1"));
+    let written = std::fs::read_to_string(nerdgraph.home().join("keys.json")).unwrap();
+    assert!(written.contains("NRAK-SECRET-I2"));
+
+    let nothing_printed = NerdGraph::start().await;
+    let output = nothing_printed
+        .run(&[
+            "--output-file",
+            "none.json",
+            "rotate",
+            "--key-id",
+            "I9",
+            "--yes",
+        ])
+        .await;
+    assert!(!output.status.success());
+    assert!(!nothing_printed.home().join("none.json").exists());
+}
+
+#[tokio::test]
+async fn test_rotate_keeps_the_old_key_when_the_sink_fails() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "key(id: $id",
+            json!({"actor": {"apiAccess": {"key": key("I1", "INGEST")}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I2", "INGEST")], "errors": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {"deletedKeys": [{"id": "I2"}], "errors": []}}),
+        )
+        .await;
+    // A file where the sink expects a directory makes every write fail.
+    std::fs::write(nerdgraph.home().join("secrets"), "").unwrap();
+
+    let output = nerdgraph
+        .run(&[
+            "--secret-sink",
+            "file:secrets/keys.json",
+            "rotate",
+            "--key-id",
+            "I1",
+            "--yes",
+        ])
+        .await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Could not store the secret of I2"));
+    assert!(stderr(&output).contains("the old key is still active"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 3);
+    assert_eq!(
+        requests[2]["variables"],
+        json!({"keys": {"ingestKeyIds": ["I2"], "userKeyIds": []}})
+    );
+}
+
+#[tokio::test]
+async fn test_query_requires_id_and_type() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "ApiAccessKeyType!",
+            json!({"actor": {"apiAccess": {"key": key("U1", "USER")}}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["query", "--key-id", "U1", "--key-type", "USER"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("U1 key"));
+
+    let output = nerdgraph.run(&["query", "--key-id", "U1"]).await;
+    assert!(!output.status.success());
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0]["variables"],
+        json!({"id": "U1", "keyType": "USER"})
+    );
+}
+
+#[tokio::test]
+async fn test_graphql_passes_variables_through() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer("accounts", json!({"actor": {"accounts": [{"id": 1}]}}))
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "graphql",
+            "query($id: Int!) { actor { accounts { id } } }",
+            "--variables",
+            r#"{"id": 1}"#,
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let data: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(data, json!({"actor": {"accounts": [{"id": 1}]}}));
+    assert_eq!(nerdgraph.requests().await[0]["variables"], json!({"id": 1}));
+}
+
+#[tokio::test]
+async fn test_whoami_verifies_the_key() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "organization",
+            json!({"actor": {
+                "user": {"id": 5, "email": "jane@example.com", "name": "Jane"},
+                "organization": {"id": "org-1", "name": "Example"},
+                "accounts": [{"id": 1, "name": "Production"}],
+            }}),
+        )
+        .await;
+
+    let output = nerdgraph.run(&["whoami"]).await;
+    assert!(
+        stdout(&output).contains("jane@example.com"),
+        "{}",
+        stderr(&output)
+    );
+    // Nothing answers the key search, so the key cannot even read keys.
+    assert!(stdout(&output).contains("API key access: no read access for account 1"));
+    assert_eq!(nerdgraph.requests().await[0]["variables"], Value::Null);
+
+    nerdgraph
+        .answer(
+            "keySearch",
+            json!({"actor": {"apiAccess": {"keySearch": {"count": 2}}}}),
+        )
+        .await;
+    let output = nerdgraph.run(&["whoami"]).await;
+    assert!(
+        stdout(&output).contains("API key access: read access (checked against account 1;"),
+        "{}",
+        stdout(&output)
+    );
+    assert!(!stdout(&output).contains("allowed"));
+}
+
+#[tokio::test]
+async fn test_rename_sends_the_new_names() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            key_search(vec![key("U1", "USER"), key("U2", "USER")]),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessUpdateKeys",
+            json!({"apiAccessUpdateKeys": {
+                "updatedKeys": [key("U1", "USER"), key("U2", "USER")],
+                "errors": []
+            }}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "rename",
+            "--match",
+            "^U(\\d) key$",
+            "--replace",
+            "svc-$1",
+            "--key-type",
+            "USER",
+            "--account-id",
+            "1",
+            "--dry-run",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(nerdgraph.requests().await.len(), 1);
+
+    let output = nerdgraph
+        .run(&[
+            "rename",
+            "--match",
+            "^U(\\d) key$",
+            "--replace",
+            "svc-$1",
+            "--key-type",
+            "USER",
+            "--account-id",
+            "1",
+            "--yes",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 3);
+    assert_eq!(
+        requests[2]["variables"],
+        json!({"keys": {"user": [
+            {"keyId": "U1", "name": "svc-1"},
+            {"keyId": "U2", "name": "svc-2"},
+        ]}})
+    );
+}
+
+#[tokio::test]
+async fn test_find_ranks_the_closest_names_first() {
+    let nerdgraph = NerdGraph::start().await;
+    let mut license = key("I1", "INGEST");
+    license["name"] = json!("payments-prod-license");
+    let mut browser = key("I2", "INGEST");
+    browser["name"] = json!("web-staging-browser");
+    nerdgraph
+        .answer("keySearch", key_search(vec![browser, license]))
+        .await;
+
+    let output = nerdgraph
+        .run(&["--format", "json", "find", "payprd", "--account-id", "1"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let found: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(found[0]["id"], "I1");
+    assert_eq!(found.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_usage_queries_audit_events_and_ingest_errors() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "FROM NrAuditEvent",
+            nrql(json!([{"count": 2, "latest.timestamp": 1_700_000_000_000i64}])),
+        )
+        .await;
+    nerdgraph
+        .answer("FROM NrIntegrationError", nrql(json!([{"count": 0}])))
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "usage",
+            "--key-id",
+            "I1",
+            "--account-id",
+            "1",
+            "--since-days",
+            "7",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("2023-11-14 22:13:20 UTC (2 event(s) in NrAuditEvent"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0]["variables"],
+        json!({
+            "accountId": 1,
+            "nrql": "SELECT count(*), latest(timestamp) FROM NrAuditEvent \
+                     WHERE targetId = 'I1' SINCE 7 days ago",
+        })
+    );
+    assert_eq!(
+        requests[1]["variables"]["nrql"],
+        "SELECT count(*), latest(timestamp) FROM NrIntegrationError \
+         WHERE message LIKE '%I1%' SINCE 7 days ago"
+    );
+}
+
+#[tokio::test]
+async fn test_audit_events_query_every_account() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "FROM NrAuditEvent",
+            nrql(json!([{
+                "timestamp": 1_700_000_000_000i64,
+                "actorEmail": "jane@example.com",
+                "actionIdentifier": "api_access.create_key",
+                "targetId": "I1",
+                "targetType": "key",
+                "description": "created key",
+            }])),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "--format",
+            "json",
+            "audit-events",
+            "--since",
+            "2d",
+            "--actor",
+            "jane@example.com",
+            "--account-id",
+            "1",
+            "--account-id",
+            "2",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let events: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(events.as_array().unwrap().len(), 2);
+    assert_eq!(events[0]["actor"], "jane@example.com");
+    assert_eq!(events[1]["account_id"], 2);
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    let nrql = requests[0]["variables"]["nrql"].as_str().unwrap();
+    assert!(nrql.contains("actorEmail = 'jane@example.com'"), "{}", nrql);
+    assert!(nrql.ends_with("SINCE 2 days ago LIMIT MAX"), "{}", nrql);
+    assert_eq!(requests[1]["variables"]["accountId"], 2);
+}
+
+#[tokio::test]
+async fn test_audit_reports_findings() {
+    let nerdgraph = NerdGraph::start().await;
+    let mut old = key("I1", "INGEST");
+    old["createdAt"] = json!(1_500_000_000);
+    let mut unlabeled = key("U1", "USER");
+    unlabeled["createdAt"] = json!(chrono::Utc::now().timestamp());
+    unlabeled["notes"] = Value::Null;
+    nerdgraph
+        .answer("keySearch", key_search(vec![old, unlabeled]))
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "--format",
+            "json",
+            "audit",
+            "--account-id",
+            "1",
+            "--report",
+            "audit.xml",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let findings: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let findings: Vec<(&str, &str)> = findings
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["rule"].as_str().unwrap(), f["key_id"].as_str().unwrap()))
+        .collect();
+    assert_eq!(findings, [("key-age", "I1"), ("require-notes", "U1")]);
+    let report = std::fs::read_to_string(nerdgraph.home().join("audit.xml")).unwrap();
+    assert!(report.contains("<testsuite"), "{}", report);
+}
+
+#[tokio::test]
+async fn test_policy_check_fails_on_violations() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .write_config("[policies]\nrequire_notes = true\n")
+        .await;
+    let mut unlabeled = key("U1", "USER");
+    unlabeled["notes"] = Value::Null;
+    nerdgraph
+        .answer(
+            "keySearch",
+            key_search(vec![key("I1", "INGEST"), unlabeled]),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["policy", "check", "--account-id", "1"])
+        .await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("1 policy violation(s) in 2 key(s)"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn test_report_inventory_and_summary_fetch_both_key_types() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            key_search(vec![key("I1", "INGEST"), key("U1", "USER")]),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "report",
+            "inventory",
+            "--account-id",
+            "1",
+            "--format",
+            "html",
+            "--output",
+            "inventory.html",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let html = std::fs::read_to_string(nerdgraph.home().join("inventory.html")).unwrap();
+    assert!(html.contains("I1") && html.contains("U1"));
+    assert!(!html.contains("NRAK-SECRET"));
+
+    let output = nerdgraph
+        .run(&[
+            "report",
+            "summary",
+            "--account-id",
+            "1",
+            "--account-id",
+            "2",
+            "--format",
+            "json",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let summary: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(summary["keys"], 2);
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(
+        requests[1]["variables"]["query"],
+        json!({"scope": {"accountIds": [1, 2]}, "types": ["INGEST", "USER"]})
+    );
+}
+
+#[tokio::test]
+async fn test_report_usage_by_key_splits_account_ingest() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            key_search(vec![key("I1", "INGEST"), key("I2", "INGEST")]),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "FROM NrConsumption",
+            nrql(json!([{"usageMetric": "MetricsBytes", "sum.GigabytesIngested": 3.0}])),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "--format",
+            "json",
+            "report",
+            "usage-by-key",
+            "--account-id",
+            "1",
+            "--since",
+            "7d",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let usage: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(usage.as_array().unwrap().len(), 2);
+    assert_eq!(usage[0]["gigabytes"], 1.5);
+    assert_eq!(usage[0]["attribution"], "even share of 2 keys");
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(
+        requests[0]["variables"]["query"]["types"],
+        json!(["INGEST"])
+    );
+    let nrql = requests[1]["variables"]["nrql"].as_str().unwrap();
+    assert!(nrql.contains("consumingAccountId = 1"), "{}", nrql);
+    assert!(nrql.contains("SINCE 7 days ago"), "{}", nrql);
+}
+
+#[tokio::test]
+async fn test_cleanup_stale_deletes_only_unused_keys() {
+    let nerdgraph = NerdGraph::start().await;
+    let mut license = key("I1", "INGEST");
+    license["createdAt"] = json!(1_500_000_000);
+    let mut user = key("U1", "USER");
+    user["createdAt"] = json!(1_500_000_000);
+    nerdgraph
+        .answer("keySearch", key_search(vec![license, user]))
+        .await;
+    nerdgraph
+        .answer("SELECT count(*)", nrql(json!([{"count": 0}])))
+        .await;
+    nerdgraph
+        .answer("FROM NrConsumption", nrql(json!([])))
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {"deletedKeys": [{"id": "I1"}], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "cleanup",
+            "stale",
+            "--account-id",
+            "1",
+            "--delete",
+            "--yes",
+            "--report",
+            "cleanup.json",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Deleted 1 of 1 key(s)"));
+
+    let requests = nerdgraph.requests().await;
+    // The key search, two usage signals per key, the account's ingest and the deletion.
+    assert_eq!(requests.len(), 7);
+    assert_eq!(
+        requests[6]["variables"],
+        json!({"keys": {"ingestKeyIds": ["I1"], "userKeyIds": []}})
+    );
+    let report: Value = serde_json::from_str(
+        &std::fs::read_to_string(nerdgraph.home().join("cleanup.json")).unwrap(),
+    )
+    .unwrap();
+    assert!(report.to_string().contains("\"deleted\":true"));
+}
+
+#[tokio::test]
+async fn test_cleanup_stale_declined_still_writes_the_report_and_discards_progress() {
+    let nerdgraph = NerdGraph::start().await;
+    let mut license = key("I1", "INGEST");
+    license["createdAt"] = json!(1_500_000_000);
+    nerdgraph
+        .answer("keySearch", key_search(vec![license]))
+        .await;
+    nerdgraph
+        .answer("FROM NrConsumption", nrql(json!([])))
+        .await;
+    // Left behind by a cancelled run that had already found I1 stale.
+    let progress = nerdgraph
+        .path("Data dir")
+        .await
+        .join("resume")
+        .join("cleanup-stale.json");
+    std::fs::create_dir_all(progress.parent().unwrap()).unwrap();
+    std::fs::write(
+        &progress,
+        json!({
+            "started_at": "2026-01-01T00:00:00Z", "days": 90, "account_ids": [1],
+            "checked": ["I1"], "stale": ["I1"]
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    // No --yes, and the prompt reads an empty stdin.
+    let output = nerdgraph
+        .run(&[
+            "cleanup",
+            "stale",
+            "--account-id",
+            "1",
+            "--resume",
+            "--delete",
+            "--report",
+            "cleanup.json",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Nothing deleted"));
+    assert!(stdout(&output).contains("Report written to cleanup.json"));
+
+    assert!(!nerdgraph
+        .requests()
+        .await
+        .iter()
+        .any(|request| request["query"]
+            .as_str()
+            .unwrap()
+            .contains("apiAccessDeleteKeys")));
+    assert!(!progress.exists());
+    let report: Value = serde_json::from_str(
+        &std::fs::read_to_string(nerdgraph.home().join("cleanup.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(report["entries"][0]["id"], "I1");
+    assert_eq!(report["entries"][0]["deleted"], false);
+}
+
+#[tokio::test]
+async fn test_provision_creates_the_missing_environment_keys() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .write_config(
+            r#"
+[environments.staging]
+account_ids = [1]
+keys = [
+  { ingest_type = "LICENSE", name = "{env}-{account_id}-license", notes = "owner:platform" },
+  { ingest_type = "BROWSER", name = "{env}-{account_id}-browser" },
+]
+"#,
+        )
+        .await;
+    let mut existing = key("I1", "INGEST");
+    existing["name"] = json!("staging-1-license");
+    existing["notes"] = json!("owner:platform env:staging");
+    nerdgraph
+        .answer("keySearch", key_search(vec![existing]))
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I2", "INGEST")], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["provision", "env", "staging", "--yes"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("NRAK-SECRET-I2"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0]["variables"]["query"]["types"],
+        json!(["INGEST"])
+    );
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"ingest": [{
+            "accountId": 1,
+            "ingestType": "BROWSER",
+            "name": "staging-1-browser",
+            "notes": "env:staging",
+        }]}})
+    );
+}
+
+#[tokio::test]
+async fn test_decommission_deletes_labeled_keys_and_lists_again() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .write_config("[environments.staging]\naccount_ids = [1]\n")
+        .await;
+    let mut labeled = key("I1", "INGEST");
+    labeled["notes"] = json!("env:staging");
+    Mock::given(method("POST"))
+        .and(body_string_contains("keySearch"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": key_search(vec![labeled, key("U1", "USER")])
+        })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&nerdgraph.server)
+        .await;
+    nerdgraph
+        .answer("keySearch", key_search(vec![key("U1", "USER")]))
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {"deletedKeys": [{"id": "I1"}], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "decommission",
+            "env",
+            "staging",
+            "--yes",
+            "--report",
+            "staging.json",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Deleted 1 of 1 key(s)"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 3);
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"ingestKeyIds": ["I1"], "userKeyIds": []}})
+    );
+    let report: Value = serde_json::from_str(
+        &std::fs::read_to_string(nerdgraph.home().join("staging.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(report["deleted"][0]["id"], "I1");
+    assert_eq!(report["remaining"], json!([]));
+}
+
+#[tokio::test]
+async fn test_onboard_labels_the_customer_keys() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .write_config(
+            r#"
+[onboarding]
+owner = "noc"
+
+[[onboarding.keys]]
+ingest_type = "LICENSE"
+name = "{customer} license"
+"#,
+        )
+        .await;
+    nerdgraph.answer("keySearch", key_search(vec![])).await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I1", "INGEST")], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "onboard",
+            "customer",
+            "7",
+            "--customer",
+            "acme",
+            "--yes",
+            "--report",
+            "acme.json",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0]["variables"]["query"]["scope"],
+        json!({"accountIds": [7]})
+    );
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"ingest": [{
+            "accountId": 7,
+            "ingestType": "LICENSE",
+            "name": "acme license",
+            "notes": "customer:acme owner:noc",
+        }]}})
+    );
+    assert!(nerdgraph.home().join("acme.json").exists());
+}
+
+#[tokio::test]
+async fn test_bootstrap_creates_missing_keys_and_relabels_existing_ones() {
+    let nerdgraph = NerdGraph::start().await;
+    std::fs::write(
+        nerdgraph.home().join("blueprint.json"),
+        r#"{
+            "name": "standard",
+            "labels": {"team": "platform"},
+            "keys": [
+                {"name": "{account_id} license", "ingest_type": "LICENSE"},
+                {"name": "{account_id} browser", "ingest_type": "BROWSER"}
+            ]
+        }"#,
+    )
+    .unwrap();
+    let mut license = key("I1", "INGEST");
+    license["name"] = json!("1 license");
+    license["notes"] = json!("blueprint:standard");
+    nerdgraph
+        .answer("keySearch", key_search(vec![license]))
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I2", "INGEST")], "errors": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessUpdateKeys",
+            json!({"apiAccessUpdateKeys": {"updatedKeys": [key("I1", "INGEST")], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "bootstrap",
+            "--blueprint",
+            "blueprint.json",
+            "--account-id",
+            "1",
+            "--yes",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 3);
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"ingest": [{
+            "accountId": 1,
+            "ingestType": "BROWSER",
+            "name": "1 browser",
+            "notes": "blueprint:standard team:platform",
+        }]}})
+    );
+    assert_eq!(
+        requests[2]["variables"],
+        json!({"keys": {"ingest": [
+            {"keyId": "I1", "notes": "blueprint:standard team:platform"}
+        ]}})
+    );
+}
+
+#[tokio::test]
+async fn test_export_writes_terraform_without_secrets() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            key_search(vec![key("U1", "USER"), key("I1", "INGEST")]),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["export", "--format", "terraform", "--account-id", "1"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let terraform = stdout(&output);
+    assert!(terraform.contains("resource \"newrelic_api_access_key\""));
+    assert!(terraform.contains("terraform import newrelic_api_access_key."));
+    assert!(terraform.contains("'I1:INGEST'"), "{}", terraform);
+    assert!(!terraform.contains("NRAK-SECRET"));
+    assert!(
+        terraform.find("'I1:INGEST'").unwrap() < terraform.find("'U1:USER'").unwrap(),
+        "{}",
+        terraform
+    );
+}
+
+#[tokio::test]
+async fn test_import_recreates_only_deleted_keys() {
+    let nerdgraph = NerdGraph::start().await;
+    std::fs::write(
+        nerdgraph.home().join("keys.json"),
+        json!([key("I1", "INGEST"), key("U1", "USER")]).to_string(),
+    )
+    .unwrap();
+    nerdgraph
+        .answer("keySearch", key_search(vec![key("I1", "INGEST")]))
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("U2", "USER")], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph.run(&["import", "keys.json", "--yes"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[1]["variables"],
+        json!({"keys": {"user": [{
+            "accountId": 1,
+            "name": "U1 key",
+            "notes": "team:payments",
+            "userId": 5,
+        }]}})
+    );
+}
+
+#[tokio::test]
+async fn test_scheduler_deletes_due_keys_queued_by_rotate() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "key(id: $id",
+            json!({"actor": {"apiAccess": {"key": key("I1", "INGEST")}}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessCreateKeys",
+            json!({"apiAccessCreateKeys": {"createdKeys": [key("I2", "INGEST")], "errors": []}}),
+        )
+        .await;
+    nerdgraph
+        .answer(
+            "apiAccessDeleteKeys",
+            json!({"apiAccessDeleteKeys": {"deletedKeys": [{"id": "I1"}], "errors": []}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["rotate", "--key-id", "I1", "--delete-after", "7d", "--yes"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(nerdgraph.requests().await.len(), 2);
+
+    let output = nerdgraph
+        .run(&["--format", "json", "scheduler", "list"])
+        .await;
+    let queued: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(queued[0]["key_id"], "I1");
+    assert_eq!(queued[0]["replaced_by"], "I2");
+
+    let output = nerdgraph.run(&["scheduler", "run"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("0 deleted, 0 failed, 1 not due yet"));
+
+    // Make the deletion due.
+    let schedule = nerdgraph.path("Data dir").await.join("schedule.json");
+    let mut queue: Value =
+        serde_json::from_str(&std::fs::read_to_string(&schedule).unwrap()).unwrap();
+    queue["deletions"][0]["delete_after"] = json!("2020-01-01T00:00:00Z");
+    std::fs::write(&schedule, queue.to_string()).unwrap();
+
+    let output = nerdgraph.run(&["scheduler", "run"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Deleted I1"));
+
+    let requests = nerdgraph.requests().await;
+    assert_eq!(requests.len(), 3);
+    assert_eq!(
+        requests[2]["variables"],
+        json!({"keys": {"ingestKeyIds": ["I1"], "userKeyIds": []}})
+    );
+    let output = nerdgraph.run(&["scheduler", "cancel", "I1"]).await;
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn test_check_terraform_state_reports_drift() {
+    let nerdgraph = NerdGraph::start().await;
+    std::fs::write(
+        nerdgraph.home().join("terraform.tfstate"),
+        json!({
+            "version": 4,
+            "resources": [{
+                "mode": "managed", "type": "newrelic_api_access_key", "name": "ci",
+                "instances": [
+                    {"index_key": 0, "attributes": {
+                        "id": "I1", "account_id": 1, "key_type": "INGEST", "name": "I1 key",
+                        "notes": "team:payments"
+                    }},
+                    {"index_key": 1, "attributes": {
+                        "id": "I9", "account_id": 1, "key_type": "INGEST", "name": "gone"
+                    }}
+                ]
+            }]
+        })
+        .to_string(),
+    )
+    .unwrap();
+    nerdgraph
+        .answer(
+            "keySearch",
+            key_search(vec![key("I1", "INGEST"), key("U1", "USER")]),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "--format",
+            "json",
+            "check",
+            "terraform-state",
+            "--state-file",
+            "terraform.tfstate",
+        ])
+        .await;
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let drift: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let statuses: Vec<(&str, &str)> = drift
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| (d["status"].as_str().unwrap(), d["id"].as_str().unwrap()))
+        .collect();
+    assert_eq!(statuses, [("deleted", "I9"), ("unmanaged", "U1")]);
+    assert_eq!(
+        nerdgraph.requests().await[0]["variables"]["query"]["scope"],
+        json!({"accountIds": [1]})
+    );
+}
+
+#[tokio::test]
+async fn test_drift_check_reports_missing_and_undeclared_keys() {
+    let nerdgraph = NerdGraph::start().await;
+    std::fs::write(
+        nerdgraph.home().join("keys.yaml"),
+        json!({"keys": [
+            {"name": "I1 key", "type": "INGEST", "account_id": 1, "notes": "team:payments"},
+            {"name": "deploy bot", "type": "USER", "account_id": 1},
+        ]})
+        .to_string(),
+    )
+    .unwrap();
+    nerdgraph
+        .answer(
+            "keySearch",
+            key_search(vec![key("I1", "INGEST"), key("U1", "USER")]),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "drift",
+            "check",
+            "--manifest",
+            "keys.yaml",
+            "--format",
+            "json",
+        ])
+        .await;
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let drift = stdout(&output);
+    assert!(drift.contains("\"missing\""), "{}", drift);
+    assert!(drift.contains("deploy bot"), "{}", drift);
+    assert!(drift.contains("\"undeclared\""), "{}", drift);
+    assert!(drift.contains("U1"), "{}", drift);
+    assert!(!drift.contains("I1"), "{}", drift);
+}
+
+#[tokio::test]
+async fn test_snapshot_diff_shows_changes_since_save() {
+    let nerdgraph = NerdGraph::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("keySearch"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": key_search(vec![key("I1", "INGEST"), key("U1", "USER")])
+        })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&nerdgraph.server)
+        .await;
+    let mut renamed = key("I1", "INGEST");
+    renamed["name"] = json!("payments license");
+    nerdgraph
+        .answer("keySearch", key_search(vec![renamed, key("U2", "USER")]))
+        .await;
+
+    let output = nerdgraph
+        .run(&[
+            "snapshot",
+            "save",
+            "--account-id",
+            "1",
+            "--store",
+            "snapshots",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = nerdgraph
+        .run(&[
+            "--format",
+            "json",
+            "snapshot",
+            "diff",
+            "--account-id",
+            "1",
+            "--store",
+            "snapshots",
+        ])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let diff = stdout(&output);
+    assert!(diff.contains("U2") && diff.contains("U1"), "{}", diff);
+    assert!(diff.contains("payments license"), "{}", diff);
+}
+
+#[tokio::test]
+async fn test_protected_keys_are_not_deleted() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "key(id: $id",
+            json!({"actor": {"apiAccess": {"key": key("I1", "INGEST")}}}),
+        )
+        .await;
+
+    let output = nerdgraph.run(&["protect", "add", "I1 key"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = nerdgraph.run(&["delete", "--key-id", "I1", "--yes"]).await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("protected"), "{}", stderr(&output));
+
+    let requests = nerdgraph.requests().await;
+    assert!(requests.iter().all(|request| !request["query"]
+        .as_str()
+        .unwrap()
+        .contains("apiAccessDeleteKeys")));
+}
+
+#[tokio::test]
+async fn test_auth_verify_prints_the_identity() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "organization",
+            json!({"actor": {
+                "user": {"id": 5, "email": "jane@example.com", "name": "Jane"},
+                "organization": {"id": "org-1", "name": "Example"},
+                "accounts": [{"id": 1, "name": "Production"}],
+            }}),
+        )
+        .await;
+
+    let output = nerdgraph.run(&["auth", "verify"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("jane@example.com"));
+    assert!(stdout(&output).contains("Production"));
+}
+
+#[tokio::test]
+async fn test_scan_reports_live_keys_without_their_secrets() {
+    let nerdgraph = NerdGraph::start().await;
+    let secret = "NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0";
+    let mut live = key("U1", "USER");
+    live["key"] = json!(secret);
+    nerdgraph.answer("keySearch", key_search(vec![live])).await;
+    std::fs::create_dir(nerdgraph.home().join("repo")).unwrap();
+    std::fs::write(
+        nerdgraph.home().join("repo").join("app.env"),
+        format!("NEW_RELIC_API_KEY={}\n", secret),
+    )
+    .unwrap();
+
+    let output = nerdgraph
+        .run(&["--format", "json", "scan", "repo", "--account-id", "1"])
+        .await;
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert!(stderr(&output).contains("1 live key(s) hardcoded under repo"));
+    assert!(!stdout(&output).contains(secret));
+    let leaks: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(leaks[0]["status"], "live");
+    assert_eq!(leaks[0]["key_id"], "U1");
+    assert_eq!(leaks[0]["path"], "repo/app.env");
+}
+
+#[tokio::test]
+async fn test_history_records_commands_in_a_verifiable_log() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph.answer("keySearch", key_search(vec![])).await;
+
+    let output = nerdgraph.run(&["list", "--account-id", "1"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = nerdgraph.run(&["history", "list"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("list --account-id 1"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = nerdgraph.run(&["audit-log", "verify"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn test_graphql_errors_fail_the_command() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .respond("keySearch", graphql_error("Access denied to account 1"))
+        .await;
+
+    let output = nerdgraph.run(&["list", "--account-id", "1"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Access denied to account 1"));
+}
+
+#[tokio::test]
+async fn test_rate_limited_requests_are_retried() {
+    let nerdgraph = NerdGraph::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&nerdgraph.server)
+        .await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            json!({"actor": {"apiAccess": {"keySearch": {"keys": [], "nextCursor": null}}}}),
+        )
+        .await;
+
+    let output = nerdgraph
+        .run(&["--format", "json", "list", "--account-id", "1"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(nerdgraph.requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn test_persistent_rate_limits_are_reported() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .respond(
+            "keySearch",
+            ResponseTemplate::new(429).insert_header("Retry-After", "3600"),
+        )
+        .await;
+
+    let output = nerdgraph.run(&["list", "--account-id", "1"]).await;
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("rate limit"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_responses_are_reported() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .respond(
+            "keySearch",
+            ResponseTemplate::new(200).set_body_string("{\"data\": {"),
+        )
+        .await;
+
+    let output = nerdgraph.run(&["list", "--account-id", "1"]).await;
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Invalid NerdGraph response"),
+        "{}",
+        stderr(&output)
+    );
+}
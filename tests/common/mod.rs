@@ -0,0 +1,142 @@
+//! A NerdGraph stand-in for running the `newrelic-apikeys-cli` binary in integration tests.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub struct NerdGraph {
+    pub server: MockServer,
+    home: TempDir,
+}
+
+impl NerdGraph {
+    /// Every request not answered by [`NerdGraph::answer`] fails with a GraphQL error.
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(graphql_error("Unexpected request"))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+        NerdGraph {
+            server,
+            home: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    /// Answer documents containing `operation` with `data`.
+    pub async fn answer(&self, operation: &str, data: Value) {
+        self.respond(
+            operation,
+            ResponseTemplate::new(200).set_body_json(json!({ "data": data })),
+        )
+        .await;
+    }
+
+    pub async fn respond(&self, operation: &str, response: ResponseTemplate) {
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_string_contains(operation))
+            .respond_with(response)
+            .with_priority(5)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// The home and working directory of [`NerdGraph::run`].
+    pub fn home(&self) -> &Path {
+        self.home.path()
+    }
+
+    /// Run the CLI with a fresh home and working directory, so no config, cache or history
+    /// leaks in.
+    pub async fn run(&self, args: &[&str]) -> Output {
+        tokio::process::Command::new(env!("CARGO_BIN_EXE_newrelic_apikeys_cli"))
+            .env_clear()
+            .env("HOME", self.home.path())
+            .env("APPDATA", self.home.path())
+            .env("LOCALAPPDATA", self.home.path())
+            .current_dir(self.home.path())
+            .args(["--api-key", "NRAK-TEST", "--endpoint"])
+            .arg(format!("{}/graphql", self.server.uri()))
+            .args(args)
+            .output()
+            .await
+            .unwrap()
+    }
+
+    /// A location printed by `config path` for [`NerdGraph::run`], e.g. `Data dir`.
+    pub async fn path(&self, label: &str) -> PathBuf {
+        let output = self.run(&["config", "path"]).await;
+        stdout(&output)
+            .lines()
+            .find_map(|line| line.strip_prefix(label)?.strip_prefix(':'))
+            .map(|path| PathBuf::from(path.trim()))
+            .unwrap()
+    }
+
+    /// Write the config file of [`NerdGraph::run`].
+    pub async fn write_config(&self, contents: &str) {
+        let path = self.path("Config file").await;
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// The bodies of the requests received so far.
+    pub async fn requests(&self) -> Vec<Value> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| serde_json::from_slice(&request.body).unwrap())
+            .collect()
+    }
+}
+
+pub fn graphql_error(message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "data": null,
+        "errors": [{ "message": message }],
+    }))
+}
+
+pub fn key(id: &str, key_type: &str) -> Value {
+    let mut key = json!({
+        "id": id,
+        "name": format!("{} key", id),
+        "notes": "team:payments",
+        "type": key_type,
+        "key": format!("NRAK-SECRET-{}", id),
+        "createdAt": 1_700_000_000,
+        "accountId": 1,
+    });
+    match key_type {
+        "INGEST" => key["ingestType"] = json!("LICENSE"),
+        _ => key["userId"] = json!(5),
+    }
+    key
+}
+
+/// `keySearch` data with `keys` on a single page.
+pub fn key_search(keys: Vec<Value>) -> Value {
+    json!({"actor": {"apiAccess": {"keySearch": {"keys": keys, "nextCursor": null}}}})
+}
+
+/// NRQL query data with `results`.
+pub fn nrql(results: Value) -> Value {
+    json!({"actor": {"account": {"nrql": {"results": results}}}})
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
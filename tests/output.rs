@@ -0,0 +1,414 @@
+//! Golden files for the output of every command with `--format`, in each format scripts parse.
+//! A changed snapshot is a changed output format: review it with `cargo insta review` and
+//! accept it only if the change is deliberate.
+
+mod common;
+
+use serde_json::{json, Value};
+use wiremock::matchers::{body_string_contains, method};
+use wiremock::{Mock, ResponseTemplate};
+
+use common::{key_search, nrql, stderr, stdout, NerdGraph};
+
+const FORMATS: [&str; 5] = ["table", "csv", "json", "ndjson", "yaml"];
+
+/// Seconds since the epoch `days` ago, so that ages and "... ago" columns are the same on every
+/// run; only the dates need masking.
+fn days_ago(days: i64) -> i64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap();
+    now.as_secs() as i64 - days * 86_400
+}
+
+/// Keys covering both key types, missing fields, and names that need quoting in CSV or YAML.
+fn keys() -> Value {
+    json!([
+        {
+            "id": "INGEST-1", "name": "Production license", "notes": "team:payments",
+            "type": "INGEST", "key": "NRII-0123456789abcdef0123456789abcdef",
+            "createdAt": days_ago(500), "accountId": 1, "ingestType": "LICENSE",
+        },
+        {
+            "id": "INGEST-2", "name": "Browser, \"checkout\"", "notes": null,
+            "type": "INGEST", "key": null, "createdAt": days_ago(100),
+            "accountId": 1, "ingestType": "BROWSER",
+        },
+        {
+            "id": "USER-1", "name": "Déploiement: CI", "notes": "owner:jane rotated",
+            "type": "USER", "key": "NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0",
+            "createdAt": days_ago(3), "accountId": 2, "userId": 5,
+        },
+        {
+            "id": "USER-2", "name": null, "notes": "",
+            "type": "USER", "key": null, "createdAt": null,
+            "accountId": 2, "userId": 6,
+        },
+    ])
+}
+
+async fn nerdgraph() -> NerdGraph {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "keySearch",
+            json!({"actor": {"apiAccess": {"keySearch": {"keys": keys(), "nextCursor": null}}}}),
+        )
+        .await;
+    nerdgraph
+}
+
+/// Snapshot `args` in every format, with dates and the mock's address masked.
+async fn assert_formats(name: &str, nerdgraph: &NerdGraph, args: &[&str]) {
+    assert_formats_with(name, nerdgraph, args, 0, &[]).await;
+}
+
+/// Like [`assert_formats`], for a command that exits with `code` and output that also needs
+/// `filters` masked.
+async fn assert_formats_with(
+    name: &str,
+    nerdgraph: &NerdGraph,
+    args: &[&str],
+    code: i32,
+    filters: &[(&str, &str)],
+) {
+    for format in FORMATS {
+        let mut full = vec!["--format", format];
+        full.extend_from_slice(args);
+        let output = nerdgraph.run(&full).await;
+        assert_eq!(output.status.code(), Some(code), "{}", stderr(&output));
+        let mut filters = filters.to_vec();
+        filters.extend([
+            (
+                r"\d{4}-\d{2}-\d{2}(T\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:\d{2}))?",
+                "[date]",
+            ),
+            (r"127\.0\.0\.1:\d+", "[nerdgraph]"),
+        ]);
+        insta::with_settings!({
+            snapshot_suffix => format,
+            filters => filters,
+        }, {
+            insta::assert_snapshot!(name, stdout(&output));
+        });
+    }
+}
+
+#[tokio::test]
+async fn test_list_output() {
+    let nerdgraph = nerdgraph().await;
+    assert_formats(
+        "list",
+        &nerdgraph,
+        &["list", "--account-id", "1", "--account-id", "2"],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_find_output() {
+    let nerdgraph = nerdgraph().await;
+    assert_formats(
+        "find",
+        &nerdgraph,
+        &["find", "--account-id", "1", "--account-id", "2", "prod"],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_audit_output() {
+    let nerdgraph = nerdgraph().await;
+    assert_formats(
+        "audit",
+        &nerdgraph,
+        &["audit", "--account-id", "1", "--account-id", "2"],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_report_inventory_output() {
+    let nerdgraph = nerdgraph().await;
+    assert_formats(
+        "report_inventory",
+        &nerdgraph,
+        &[
+            "report",
+            "inventory",
+            "--account-id",
+            "1",
+            "--account-id",
+            "2",
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_report_summary_output() {
+    let nerdgraph = nerdgraph().await;
+    assert_formats(
+        "report_summary",
+        &nerdgraph,
+        &[
+            "report",
+            "summary",
+            "--account-id",
+            "1",
+            "--account-id",
+            "2",
+            "--account-id",
+            "3",
+            "--max-keys-per-account",
+            "1",
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_fingerprint_output() {
+    let nerdgraph = NerdGraph::start().await;
+    std::fs::write(
+        nerdgraph.home().join("settings.env"),
+        "NEW_RELIC_LICENSE_KEY=0123456789abcdef0123456789abcdef0123NRAL\n\
+         NEW_RELIC_API_KEY=NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0\n",
+    )
+    .unwrap();
+    assert_formats("fingerprint", &nerdgraph, &["fingerprint", "settings.env"]).await;
+}
+
+#[tokio::test]
+async fn test_audit_events_output() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .answer(
+            "FROM NrAuditEvent",
+            nrql(json!([
+                {
+                    "timestamp": 1_700_000_000_000i64, "actorEmail": "jane@example.com",
+                    "actorType": "user", "actionIdentifier": "api_access.create_key",
+                    "targetId": "INGEST-1", "targetType": "key",
+                    "description": "Created key \"Production license\", for payments",
+                },
+                {
+                    "timestamp": 1_700_003_600_000i64, "actorEmail": null, "actorId": 5,
+                    "actorType": "api_key", "actionIdentifier": "api_access.delete_key",
+                    "targetId": "USER-1", "targetType": "key", "description": null,
+                },
+            ])),
+        )
+        .await;
+    assert_formats(
+        "audit_events",
+        &nerdgraph,
+        &["audit-events", "--account-id", "1"],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_report_usage_by_key_output() {
+    let nerdgraph = NerdGraph::start().await;
+    let ingest: Vec<Value> = keys()
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|key| key["type"] == "INGEST")
+        .cloned()
+        .collect();
+    nerdgraph.answer("keySearch", key_search(ingest)).await;
+    nerdgraph
+        .answer(
+            "FROM NrConsumption",
+            nrql(json!([
+                {"usageMetric": "MetricsBytes", "sum.GigabytesIngested": 12.5},
+                {"usageMetric": "BrowserEventsBytes", "sum.GigabytesIngested": 0.25},
+            ])),
+        )
+        .await;
+    assert_formats(
+        "report_usage_by_key",
+        &nerdgraph,
+        &[
+            "report",
+            "usage-by-key",
+            "--account-id",
+            "1",
+            "--account-id",
+            "2",
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_check_terraform_state_output() {
+    let nerdgraph = nerdgraph().await;
+    std::fs::write(
+        nerdgraph.home().join("terraform.tfstate"),
+        json!({
+            "version": 4,
+            "resources": [{
+                "mode": "managed", "type": "newrelic_api_access_key", "name": "keys",
+                "instances": [
+                    {"index_key": "license", "attributes": {
+                        "id": "INGEST-1", "account_id": 1, "key_type": "INGEST",
+                        "name": "Production license", "notes": "team:payments"
+                    }},
+                    {"index_key": "browser", "attributes": {
+                        "id": "INGEST-2", "account_id": 1, "key_type": "INGEST",
+                        "name": "Browser", "notes": ""
+                    }},
+                    {"index_key": "gone", "attributes": {
+                        "id": "INGEST-9", "account_id": 1, "key_type": "INGEST",
+                        "name": "Retired, \"old\" license"
+                    }}
+                ]
+            }]
+        })
+        .to_string(),
+    )
+    .unwrap();
+    assert_formats_with(
+        "check_terraform_state",
+        &nerdgraph,
+        &[
+            "check",
+            "terraform-state",
+            "--state-file",
+            "terraform.tfstate",
+            "--account-id",
+            "1",
+        ],
+        1,
+        &[],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_diff_output() {
+    let nerdgraph = NerdGraph::start().await;
+    let mut saved = keys();
+    saved[0]["name"] = json!("Old license");
+    saved.as_array_mut().unwrap().remove(3);
+    saved.as_array_mut().unwrap().push(json!({
+        "id": "USER-9", "name": "Retired bot", "notes": null, "type": "USER",
+        "createdAt": days_ago(900), "accountId": 2, "userId": 9,
+    }));
+    Mock::given(method("POST"))
+        .and(body_string_contains("keySearch"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": key_search(saved.as_array().unwrap().clone())
+        })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&nerdgraph.server)
+        .await;
+    nerdgraph
+        .answer("keySearch", key_search(keys().as_array().unwrap().clone()))
+        .await;
+    let accounts = ["--account-id", "1", "--account-id", "2"];
+
+    let mut save = vec!["snapshot", "save", "--store", "snapshots"];
+    save.extend(accounts);
+    let output = nerdgraph.run(&save).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let mut diff = vec!["snapshot", "diff", "--store", "snapshots"];
+    diff.extend(accounts);
+    assert_formats("snapshot_diff", &nerdgraph, &diff).await;
+}
+
+#[tokio::test]
+async fn test_scan_output() {
+    let nerdgraph = nerdgraph().await;
+    std::fs::create_dir(nerdgraph.home().join("repo")).unwrap();
+    std::fs::write(
+        nerdgraph.home().join("repo").join("settings.env"),
+        "NEW_RELIC_LICENSE_KEY=NRII-0123456789abcdef0123456789abcdef\n\
+         NEW_RELIC_API_KEY=NRAK-ABCDEFGHIJKLMNOPQRSTUVWXYZ0\n\
+         OLD_API_KEY=NRAK-ZYXWVUTSRQPONMLKJIHGFEDCBA9\n",
+    )
+    .unwrap();
+    assert_formats_with(
+        "scan",
+        &nerdgraph,
+        &["scan", "repo", "--account-id", "1", "--account-id", "2"],
+        1,
+        &[],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_protect_list_output() {
+    let nerdgraph = NerdGraph::start().await;
+    nerdgraph
+        .write_config("protected_keys = [\"Production license\"]\n")
+        .await;
+    let output = nerdgraph
+        .run(&["protect", "add", "USER-1", "Browser, \"checkout\""])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_formats("protect_list", &nerdgraph, &["protect", "list"]).await;
+}
+
+#[tokio::test]
+async fn test_scheduler_list_output() {
+    let nerdgraph = NerdGraph::start().await;
+    let schedule = nerdgraph.path("Data dir").await.join("schedule.json");
+    std::fs::create_dir_all(schedule.parent().unwrap()).unwrap();
+    std::fs::write(
+        &schedule,
+        json!({"deletions": [
+            {
+                "key_id": "INGEST-1", "key_type": "INGEST", "account_id": 1,
+                "name": "Production license", "replaced_by": "INGEST-3",
+                "scheduled_at": "2030-01-01T00:00:00Z", "delete_after": "2030-01-08T00:00:00Z",
+            },
+            {
+                "key_id": "USER-1", "key_type": "USER", "account_id": 2,
+                "name": "Déploiement: CI", "replaced_by": null,
+                "scheduled_at": "2030-01-01T00:00:00Z", "delete_after": "2030-01-02T12:30:00Z",
+                "last_error": "Key USER-1 not found, \"retrying\"",
+            },
+        ]})
+        .to_string(),
+    )
+    .unwrap();
+    assert_formats("scheduler_list", &nerdgraph, &["scheduler", "list"]).await;
+}
+
+#[tokio::test]
+async fn test_bench_output() {
+    let nerdgraph = NerdGraph::start().await;
+    // Timings differ on every run; the pipelines and request counts must not.
+    assert_formats_with(
+        "bench",
+        &nerdgraph,
+        &[
+            "bench",
+            "--keys",
+            "20",
+            "--page-size",
+            "10",
+            "--concurrency",
+            "2",
+            "--batch-size",
+            "5",
+            "--latency-ms",
+            "0",
+        ],
+        0,
+        &[
+            (r#"("?elapsed_ms"?: ?)[\d.e+-]+"#, "$1[ms]"),
+            (r#"("?keys_per_second"?: ?)[\d.e+-]+"#, "$1[rate]"),
+            (r"(?m),\d+,[\d.e+-]+$", ",[ms],[rate]"),
+            (r"(?m) \d+ +[\d.e+-]+$", " [ms]  [rate]"),
+        ],
+    )
+    .await;
+}